@@ -0,0 +1,89 @@
+//! Measures the heap memory used by many tiny archetypes.
+
+use bevy_ptr::OwningPtr;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use worlds_ecs::archetype::ArchetypeInfo;
+use worlds_ecs::prelude::*;
+
+/// Counts the bytes that are currently allocated.
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+macro_rules! markers {
+    ($($name:ident),*) => {
+        $(
+            #[derive(Component, PartialEq)]
+            struct $name(usize);
+        )*
+
+        fn register_markers(world: &mut World) -> Vec<ComponentId> {
+            vec![$(world.register_comparable_component::<$name>().unwrap()),*]
+        }
+    };
+}
+
+markers!(M0, M1, M2, M3, M4, M5, M6, M7, M8, M9);
+
+/// A bundle of markers, that are all stored as a `usize`.
+struct Markers<'a>(&'a [ComponentId], usize);
+
+impl Bundle for Markers<'_> {
+    fn raw_components_scope(
+        self,
+        _comp_factory: &ComponentFactory,
+        f: &mut impl FnMut(ComponentId, OwningPtr<'_>),
+    ) {
+        for comp_id in self.0 {
+            OwningPtr::make(self.1, |ptr| f(*comp_id, ptr));
+        }
+    }
+}
+
+fn main() {
+    let archetypes = 1000;
+    let mut world = World::default();
+    let markers = register_markers(&mut world);
+
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let mut entities = 0;
+    for archetype in 1..=archetypes {
+        let components: Vec<ComponentId> = markers
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| archetype & (1 << bit) != 0)
+            .map(|(_, comp_id)| *comp_id)
+            .collect();
+        let arch_info = ArchetypeInfo::from_component_ids(components.clone());
+        let amount = 1 + archetype % 2;
+        // SAFETY: Every bundle stores exactly the components of the archetype, which are distinct.
+        unsafe {
+            world.spawn_batch_with_info(&arch_info, (0..amount).map(|i| Markers(&components, i)))
+        }
+        .unwrap();
+        entities += amount;
+    }
+    let used = ALLOCATED.load(Ordering::Relaxed) - before;
+
+    println!("|  Tiny archetypes memory  |");
+    println!("\t archetypes \t: {archetypes}");
+    println!("\t entities \t: {entities}");
+    println!("\t heap bytes \t: {used}");
+    println!("\t per archetype \t: {}", used / archetypes);
+}
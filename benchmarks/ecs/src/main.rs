@@ -6,7 +6,7 @@ use bevy_ecs_13::prelude as bevy13;
 use worlds_ecs::entity::EntityId;
 use worlds_ecs::prelude::*;
 
-#[derive(Component, bevy13::Component)]
+#[derive(Component, bevy13::Component, Clone, Copy)]
 struct A(usize);
 
 #[derive(Component, bevy13::Component)]
@@ -39,6 +39,22 @@ fn main() {
 
     compare_spawning_entities(&mut bevy_world, &mut bevy1_world, &mut world, 200_000);
     compare_querying(&mut bevy_world, &mut bevy1_world, &mut world);
+    compare_component_iteration(&mut world);
+    compare_batch_storing(200_000);
+    compare_reuse_policies(100_000, 100);
+    compare_bulk_despawning(500_000);
+    compare_mirroring(200_000);
+    compare_region_queries(100_000, 1_000);
+    compare_presence_checks(200_000, 10);
+    compare_bulk_writes(1_000_000);
+    compare_query_setup(1_000);
+    compare_reordering(500_000, 10);
+    compare_empty_queries(10_000);
+    compare_ordered_iteration(100_000, 10);
+    compare_mixed_spawning(100_000);
+    compare_boxed_components(2_000);
+    compare_value_filters(1_000_000, 10);
+    compare_spawn_batch(200_000);
 }
 
 fn compare_spawning_entities(
@@ -175,6 +191,752 @@ fn compare_querying(
     }
 }
 
+fn compare_component_iteration(world: &mut World) {
+    println!(" \n ");
+    // Component Iteration Bench 1
+    compare_worlds_code_blocks! {
+        "query::<&A>" {
+            world.query::<&A>().for_each(|_| {});
+        },
+        "iter_component::<A>" {
+            world.iter_component::<A>().for_each(|_| {});
+        },
+        "Component iteration bench 1"
+    }
+
+    // Component Iteration Bench 2
+    compare_worlds_code_blocks! {
+        "query::<&mut B>" {
+            world.query::<&mut B>().for_each(|b| b.0 += 1);
+        },
+        "iter_component_mut::<B>" {
+            world.iter_component_mut::<B>().for_each(|(_, b)| b.0 += 1);
+        },
+        "Component iteration bench 2"
+    }
+}
+
+fn compare_batch_storing(amount_to_store: usize) {
+    use worlds_ecs::world::storage::arch_storage::ArchStorage;
+
+    println!(" \n ");
+    let mut comp_factory = ComponentFactory::default();
+    comp_factory.register_component::<A>();
+    comp_factory.register_component::<B>();
+    comp_factory.register_component::<C>();
+    comp_factory.register_component::<D>();
+    comp_factory.register_component::<E>();
+    comp_factory.register_component::<F>();
+    comp_factory.register_component::<G>();
+    comp_factory.register_component::<H>();
+    let mut storage_a = ArchStorage::new::<(A, B, C, D, E, F, G, H)>(&comp_factory).unwrap();
+    let mut storage_b = ArchStorage::new::<(A, B, C, D, E, F, G, H)>(&comp_factory).unwrap();
+
+    // Batch Storing Bench 1
+    compare_worlds_code_blocks! {
+        "reserve + store_bundle_unchecked" {
+            storage_a.reserve(amount_to_store);
+            (0..amount_to_store).for_each(|i| unsafe {
+                storage_a.store_bundle_unchecked(
+                    &comp_factory,
+                    (A(i), B(i), C(i), D(i), E(i), F(i), G(i), H(i)),
+                );
+            });
+        },
+        "store_bundles_unchecked" {
+            unsafe {
+                storage_b.store_bundles_unchecked(
+                    &comp_factory,
+                    (0..amount_to_store).map(|i| (A(i), B(i), C(i), D(i), E(i), F(i), G(i), H(i))),
+                );
+            }
+        },
+        "Batch storing bench 1"
+    }
+}
+
+fn compare_reuse_policies(amount_of_entities: usize, frames: usize) {
+    use worlds_ecs::entity::ReusePolicy;
+
+    /// Simulate `frames` frames, in each of which half of the entities are despawned and replaced,
+    /// and entities are accessed at random.
+    fn churn(world: &mut World, amount_of_entities: usize, frames: usize) {
+        let mut entities: Vec<EntityId> = (0..amount_of_entities)
+            .map(|i| world.spawn((A(i), B(i))))
+            .collect();
+        let mut seed: usize = 0x2545F491;
+        let mut next_random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        for _ in 0..frames {
+            for _ in 0..amount_of_entities / 2 {
+                let i = next_random() % entities.len();
+                world.despawn(entities.swap_remove(i));
+            }
+            for i in 0..amount_of_entities / 2 {
+                entities.push(world.spawn((A(i), B(i))));
+            }
+            for _ in 0..amount_of_entities {
+                let entity = entities[next_random() % entities.len()];
+                std::hint::black_box(world.get_component::<A>(entity));
+            }
+        }
+    }
+
+    println!(" \n ");
+    let mut fifo_world = World::default();
+    fifo_world.set_entity_reuse_policy(ReusePolicy::Fifo);
+    let mut lifo_world = World::default();
+    lifo_world.set_entity_reuse_policy(ReusePolicy::Lifo);
+
+    // Entity Churn Bench 1
+    compare_worlds_code_blocks! {
+        "ReusePolicy::Fifo" {
+            churn(&mut fifo_world, amount_of_entities, frames);
+        },
+        "ReusePolicy::Lifo" {
+            churn(&mut lifo_world, amount_of_entities, frames);
+        },
+        "Entity churn bench 1"
+    }
+}
+
+fn compare_bulk_despawning(amount_to_despawn: usize) {
+    println!(" \n ");
+    let mut loop_world = World::default();
+    let mut bulk_world = World::default();
+    for i in 0..amount_to_despawn {
+        loop_world.spawn((A(i), B(i)));
+        bulk_world.spawn((A(i), B(i)));
+    }
+
+    // Bulk Despawn Bench 1
+    compare_worlds_code_blocks! {
+        "despawn loop" {
+            let entities: Vec<EntityId> = loop_world.query_filtered::<EntityId, Has<A>>().collect();
+            entities.into_iter().for_each(|entity| loop_world.despawn(entity));
+        },
+        "despawn_matching" {
+            bulk_world.despawn_matching::<Has<A>>();
+        },
+        "Bulk despawn bench 1"
+    }
+}
+
+fn compare_mirroring(amount_to_mirror: usize) {
+    use worlds_ecs::interop::{mirror_from_bevy, InteropRegistry};
+
+    println!(" \n ");
+    let mut bevy_world = bevy13::World::default();
+    for i in 0..amount_to_mirror {
+        match i % 3 {
+            0 => bevy_world.spawn((A(i), B(i))),
+            1 => bevy_world.spawn((A(i), B(i), C(i))),
+            _ => bevy_world.spawn(C(i)),
+        };
+    }
+    let mut registry = InteropRegistry::new();
+    registry
+        .register::<A, A>(|a| A(a.0))
+        .register::<B, B>(|b| B(b.0))
+        .register::<C, C>(|c| C(c.0));
+    let mut spawned_world = World::default();
+    let mut mirrored_world = World::default();
+
+    // Mirror Bench 1
+    compare_worlds_code_blocks! {
+        "spawn" {
+            for i in 0..amount_to_mirror {
+                match i % 3 {
+                    0 => spawned_world.spawn((A(i), B(i))),
+                    1 => spawned_world.spawn((A(i), B(i), C(i))),
+                    _ => spawned_world.spawn(C(i)),
+                };
+            }
+        },
+        "mirror_from_bevy" {
+            mirror_from_bevy(&bevy_world, &registry, &mut mirrored_world);
+        },
+        "Mirror bench 1"
+    }
+}
+
+fn compare_region_queries(amount_of_entities: usize, amount_of_queries: usize) {
+    #[derive(Component, Clone, PartialEq)]
+    struct Position([f32; 3]);
+
+    impl SpatialPosition for Position {
+        fn position(&self) -> [f32; 3] {
+            self.0
+        }
+    }
+
+    println!(" \n ");
+    let mut world = World::default();
+    world.attach_spatial_index::<Position>(Box::new(UniformGrid::new(10.0)));
+    // Spread the entities over a 1000x1000 square.
+    for i in 0..amount_of_entities {
+        let (x, y) = ((i * 7919) % 1000, (i * 104_729) % 1000);
+        world.spawn((Position([x as f32, y as f32, 0.0]), A(i)));
+    }
+    world.sync_spatial_indexes();
+    let regions: Vec<Aabb> = (0..amount_of_queries)
+        .map(|i| {
+            let (x, y) = ((i * 31) % 990, (i * 17) % 990);
+            Aabb::new(
+                [x as f32, y as f32, -1.0],
+                [x as f32 + 10.0, y as f32 + 10.0, 1.0],
+            )
+        })
+        .collect();
+    let (mut brute_force_sum, mut region_sum) = (0, 0);
+
+    // Region Query Bench 1
+    compare_worlds_code_blocks! {
+        "brute force" {
+            for aabb in &regions {
+                brute_force_sum += world
+                    .query::<(&Position, &A)>()
+                    .filter(|(position, _)| aabb.contains(position.0))
+                    .map(|(_, a)| a.0)
+                    .sum::<usize>();
+            }
+        },
+        "query_region" {
+            for aabb in &regions {
+                region_sum += world
+                    .query_region::<&A, Position>(*aabb)
+                    .map(|a| a.0)
+                    .sum::<usize>();
+            }
+        },
+        "Region query bench 1"
+    }
+    assert_eq!(brute_force_sum, region_sum);
+
+    // Move 1% of the entities, and sync the index.
+    compare_worlds_code_blocks! {
+        "move" {
+            world
+                .query::<(&mut Position, &A)>()
+                .filter(|(_, a)| a.0 % 100 == 0)
+                .for_each(|(position, _)| position.0[0] += 15.0);
+        },
+        "sync_spatial_indexes" {
+            world.sync_spatial_indexes();
+        },
+        "Spatial sync bench 1"
+    }
+}
+
+fn compare_presence_checks(amount_of_entities: usize, rounds: usize) {
+    println!(" \n ");
+    let mut world = World::default();
+    let entities: Vec<EntityId> = (0..amount_of_entities)
+        .map(|i| match i % 3 {
+            0 => world.spawn((A(i), B(i))),
+            1 => world.spawn((A(i), C(i), D(i))),
+            _ => world.spawn(E(i)),
+        })
+        .collect();
+    let (mut storage_hits, mut meta_hits) = (0, 0);
+
+    // Presence Check Bench 1 (5 checks per entity per round)
+    compare_worlds_code_blocks! {
+        "storage lookup" {
+            for _ in 0..rounds {
+                for entity in &entities {
+                    storage_hits += world.get_component::<A>(*entity).is_some() as usize
+                        + world.get_component::<B>(*entity).is_some() as usize
+                        + world.get_component::<C>(*entity).is_some() as usize
+                        + world.get_component::<D>(*entity).is_some() as usize
+                        + world.get_component::<E>(*entity).is_some() as usize;
+                }
+            }
+        },
+        "contains_component" {
+            for _ in 0..rounds {
+                for entity in &entities {
+                    meta_hits += world.contains_component::<A>(*entity) as usize
+                        + world.contains_component::<B>(*entity) as usize
+                        + world.contains_component::<C>(*entity) as usize
+                        + world.contains_component::<D>(*entity) as usize
+                        + world.contains_component::<E>(*entity) as usize;
+                }
+            }
+        },
+        "Presence check bench 1"
+    }
+    assert_eq!(storage_hits, meta_hits);
+}
+
+fn compare_bulk_writes(amount_of_entities: usize) {
+    println!(" \n ");
+    let mut world = World::default();
+    for i in 0..amount_of_entities {
+        match i % 4 {
+            0 => world.spawn((A(i), B(i))),
+            1 => world.spawn((A(i), C(i), D(i))),
+            2 => world.spawn((A(i), E(i))),
+            _ => world.spawn(A(i)),
+        };
+    }
+
+    // Bulk Write Bench 1
+    compare_worlds_code_blocks! {
+        "query" {
+            for a in world.query::<&mut A>() {
+                a.0 = 0;
+            }
+        },
+        "write_component_to_all" {
+            world.write_component_to_all(A(0));
+        },
+        "Bulk write bench 1"
+    }
+
+    // Bulk Write Bench 2
+    compare_worlds_code_blocks! {
+        "query" {
+            for a in world.query::<&mut A>() {
+                a.0 += 1;
+            }
+        },
+        "update_component_all" {
+            world.update_component_all::<A>(|a| a.0 += 1);
+        },
+        "Bulk write bench 2"
+    }
+    assert!(world.query::<&A>().all(|a| a.0 == 2));
+}
+
+fn compare_query_setup(invocations: usize) {
+    println!(" \n ");
+    let mut world = World::default();
+    // Many storages, few matches: the setup of each query dominates.
+    for i in 0..64 {
+        match i % 4 {
+            0 => world.spawn((A(i), B(i), C(i), D(i))),
+            1 => world.spawn((A(i), B(i), C(i), D(i), E(i))),
+            2 => world.spawn((A(i), B(i), F(i))),
+            _ => world.spawn((E(i), G(i), H(i))),
+        };
+    }
+    let key = world.precompute_query_key::<(&mut A, &B, &C, &D)>();
+    let (mut resolved_sum, mut precomputed_sum) = (0, 0);
+
+    // Query Setup Bench 1
+    compare_worlds_code_blocks! {
+        "resolved" {
+            for _ in 0..invocations {
+                for (a, b, c, d) in world.query::<(&mut A, &B, &C, &D)>() {
+                    resolved_sum += a.0 + b.0 + c.0 + d.0;
+                }
+            }
+        },
+        "precomputed" {
+            for _ in 0..invocations {
+                for (a, b, c, d) in world.query_with_key::<(&mut A, &B, &C, &D)>(key) {
+                    precomputed_sum += a.0 + b.0 + c.0 + d.0;
+                }
+            }
+        },
+        "Query setup bench 1"
+    }
+    assert_eq!(resolved_sum, precomputed_sum);
+}
+
+fn compare_reordering(amount_of_entities: usize, rounds: usize) {
+    #[derive(Component, Clone, Copy)]
+    struct Position([u16; 2]);
+    #[derive(Component)]
+    struct Body([u64; 8]);
+
+    // Interleave the bits of the coordinates, so entities that are close to each other get close keys.
+    fn morton(position: &Position) -> u64 {
+        let spread = |v: u16| {
+            let mut v = v as u64;
+            v = (v | (v << 8)) & 0x00FF_00FF;
+            v = (v | (v << 4)) & 0x0F0F_0F0F;
+            v = (v | (v << 2)) & 0x3333_3333;
+            (v | (v << 1)) & 0x5555_5555
+        };
+        spread(position.0[0]) | (spread(position.0[1]) << 1)
+    }
+
+    println!(" \n ");
+    let spawn = |world: &mut World| -> Vec<EntityId> {
+        // Spread the entities over a 1024x1024 square, in a random order.
+        let position = |i: usize| {
+            let hash = (i as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            Position([(hash >> 32) as u16 % 1024, (hash >> 48) as u16 % 1024])
+        };
+        let entities: Vec<EntityId> = (0..amount_of_entities)
+            .map(|i| world.spawn((position(i), Body([i as u64; 8]))))
+            .collect();
+        // Churn, like a long-lived world.
+        for entity in entities.iter().step_by(4) {
+            world.despawn(*entity);
+        }
+        for i in amount_of_entities..amount_of_entities * 5 / 4 {
+            world.spawn((position(i), Body([i as u64; 8])));
+        }
+        world.query::<EntityId>().collect()
+    };
+    let mut scattered = World::default();
+    let mut reordered = World::default();
+    let entities = spawn(&mut scattered);
+    spawn(&mut reordered);
+    let keys: Vec<u64> = entities
+        .iter()
+        .map(|e| morton(scattered.get_component::<Position>(*e).unwrap()))
+        .collect();
+    let mut key_of = vec![0; entities.iter().map(|e| e.id() as usize + 1).max().unwrap()];
+    entities
+        .iter()
+        .zip(&keys)
+        .for_each(|(e, key)| key_of[e.id() as usize] = *key);
+    assert_eq!(
+        reordered.reorder_matching::<Position>(|e| key_of[e.id() as usize]),
+        Ok(1)
+    );
+    // A system that splats every entity into a density grid, like a broad phase or a flow field would.
+    let splat = |world: &mut World, grid: &mut [u64]| {
+        for (position, body) in world.query::<(&Position, &Body)>() {
+            grid[position.0[0] as usize * 1024 + position.0[1] as usize] += body.0[0];
+        }
+    };
+    let mut scattered_grid = vec![0u64; 1024 * 1024];
+    let mut reordered_grid = vec![0u64; 1024 * 1024];
+
+    // Locality Bench 1
+    compare_worlds_code_blocks! {
+        "scattered" {
+            for _ in 0..rounds {
+                splat(&mut scattered, &mut scattered_grid);
+            }
+        },
+        "reordered" {
+            for _ in 0..rounds {
+                splat(&mut reordered, &mut reordered_grid);
+            }
+        },
+        "Locality bench 1"
+    }
+    assert!(scattered_grid == reordered_grid);
+}
+
+fn compare_empty_queries(frames: usize) {
+    // Events that are registered, but rarely (here, never) spawned.
+    #[derive(Component, Clone)]
+    struct Event<const N: usize>(usize);
+
+    println!(" \n ");
+    let mut world = World::default();
+    for i in 0..64 {
+        match i % 4 {
+            0 => world.spawn((A(i), B(i), C(i), D(i))),
+            1 => world.spawn((A(i), B(i), C(i), D(i), E(i))),
+            2 => world.spawn((A(i), B(i), F(i))),
+            _ => world.spawn((E(i), G(i), H(i))),
+        };
+    }
+    macro_rules! events {
+        ($($n:literal)*) => {
+            $(world.register_cloneable_component::<Event<$n>>();)*
+        };
+    }
+    events!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19);
+    let mut states: Vec<QueryState<&Event<0>>> = (0..20).map(|_| QueryState::new(&world)).collect();
+    let (mut scanned, mut bailed, mut stateful) = (0, 0, 0);
+
+    // 20 empty queries per frame. `query_shared` visits the storages to find out that none of them match.
+    compare_worlds_code_blocks! {
+        "query_shared" {
+            for _ in 0..frames {
+                macro_rules! query_events {
+                    ($($n:literal)*) => {
+                        $(scanned += world.query_shared::<&Event<$n>>().count();)*
+                    };
+                }
+                query_events!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19);
+            }
+        },
+        "query" {
+            for _ in 0..frames {
+                macro_rules! query_events {
+                    ($($n:literal)*) => {
+                        $(bailed += world.query::<&Event<$n>>().count();)*
+                    };
+                }
+                query_events!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19);
+            }
+        },
+        "Empty query bench 1"
+    }
+
+    compare_worlds_code_blocks! {
+        "query" {
+            for _ in 0..frames {
+                macro_rules! query_events {
+                    ($($n:literal)*) => {
+                        $(bailed += world.query::<&Event<$n>>().count();)*
+                    };
+                }
+                query_events!(0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19);
+            }
+        },
+        "QueryState" {
+            for _ in 0..frames {
+                for state in &mut states {
+                    stateful += state.iter(&mut world).count();
+                }
+            }
+        },
+        "Empty query bench 2"
+    }
+    assert_eq!((scanned, bailed, stateful), (0, 0, 0));
+}
+
+fn compare_ordered_iteration(amount_of_entities: usize, frames: usize) {
+    println!(" \n ");
+    let mut world = World::default();
+    world.track_ordered::<A>();
+    // Spread the entities over a few archetypes, and churn them so the ids are out of row order.
+    let entities: Vec<EntityId> = (0..amount_of_entities)
+        .map(|i| match i % 3 {
+            0 => world.spawn(A(i)),
+            1 => world.spawn((A(i), B(i))),
+            _ => world.spawn((A(i), C(i))),
+        })
+        .collect();
+    for entity in entities.iter().step_by(4) {
+        world.despawn(*entity);
+    }
+    for i in 0..amount_of_entities / 4 {
+        world.spawn((A(i), D(i)));
+    }
+
+    // Ordered Iteration Bench 1
+    compare_worlds_code_blocks! {
+        "iter_component + sort per frame" {
+            for _ in 0..frames {
+                let mut sorted: Vec<(EntityId, &A)> = world.iter_component::<A>().collect();
+                sorted.sort_unstable_by_key(|(entity, _)| entity.to_bits());
+                sorted.iter().for_each(|_| {});
+            }
+        },
+        "iter_component_ordered" {
+            for _ in 0..frames {
+                world.iter_component_ordered::<A>().for_each(|_| {});
+            }
+        },
+        "Ordered iteration bench 1"
+    }
+}
+
+fn compare_mixed_spawning(amount_to_spawn: usize) {
+    worlds_ecs::mixed_bundles! {
+        enum LevelEntity {
+            Floor((A, B)),
+            Wall((A, B, C)),
+            Door((A, B, C, D)),
+            Torch((A, E)),
+            Chest((A, D, F)),
+            Enemy((A, B, G, H)),
+            Boss((A, B, C, D, E, F, G, H)),
+            Trigger(H),
+            Decal((A, F)),
+            Light((A, E, F)),
+            Spawner((A, G)),
+            Item((A, D)),
+        }
+    }
+
+    println!(" \n ");
+    // Most of a level is floors and walls, with a long tail of rarer archetypes.
+    let level = || {
+        let mut seed: usize = 0x2545F491;
+        (0..amount_to_spawn).map(move |i| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            match seed % 100 {
+                0..=39 => LevelEntity::Floor((A(i), B(i))),
+                40..=64 => LevelEntity::Wall((A(i), B(i), C(i))),
+                65..=69 => LevelEntity::Door((A(i), B(i), C(i), D(i))),
+                70..=74 => LevelEntity::Torch((A(i), E(i))),
+                75..=77 => LevelEntity::Chest((A(i), D(i), F(i))),
+                78..=84 => LevelEntity::Enemy((A(i), B(i), G(i), H(i))),
+                85 => LevelEntity::Boss((A(i), B(i), C(i), D(i), E(i), F(i), G(i), H(i))),
+                86..=88 => LevelEntity::Trigger(H(i)),
+                89..=93 => LevelEntity::Decal((A(i), F(i))),
+                94..=95 => LevelEntity::Light((A(i), E(i), F(i))),
+                96 => LevelEntity::Spawner((A(i), G(i))),
+                _ => LevelEntity::Item((A(i), D(i))),
+            }
+        })
+    };
+    let naive_level: Vec<LevelEntity> = level().collect();
+    let mixed_level: Vec<LevelEntity> = level().collect();
+    let mut naive_world = World::default();
+    let mut mixed_world = World::default();
+
+    // Mixed Spawning Bench 1
+    compare_worlds_code_blocks! {
+        "spawn per item" {
+            for item in naive_level {
+                match item {
+                    LevelEntity::Floor(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Wall(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Door(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Torch(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Chest(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Enemy(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Boss(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Trigger(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Decal(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Light(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Spawner(bundle) => naive_world.spawn(bundle),
+                    LevelEntity::Item(bundle) => naive_world.spawn(bundle),
+                };
+            }
+        },
+        "spawn_batch_mixed" {
+            mixed_world.spawn_batch_mixed(mixed_level);
+        },
+        "Mixed spawning bench 1"
+    }
+}
+
+fn compare_boxed_components(amount_of_entities: usize) {
+    const SIZE: usize = 64 * 1024;
+
+    #[derive(Component)]
+    struct InlineHeightmap(#[allow(dead_code)] [u8; SIZE]);
+
+    #[derive(Component)]
+    #[component(boxed)]
+    struct BoxedHeightmap(#[allow(dead_code)] [u8; SIZE]);
+
+    println!(" \n ");
+    let mut inline_world = World::default();
+    let mut boxed_world = World::default();
+    let mut inline_entities = Vec::with_capacity(amount_of_entities);
+    let mut boxed_entities = Vec::with_capacity(amount_of_entities);
+    for i in 0..amount_of_entities {
+        inline_entities.push(inline_world.spawn((A(i), InlineHeightmap([i as u8; SIZE]))));
+        boxed_entities.push(boxed_world.spawn((A(i), BoxedHeightmap([i as u8; SIZE]))));
+    }
+
+    // Boxed Components Bench 1
+    // Despawning from the front moves the last row of the storage into each hole.
+    compare_worlds_code_blocks! {
+        "inline 64KB component" {
+            inline_entities.iter().step_by(2).for_each(|entity| inline_world.despawn(*entity));
+        },
+        "boxed 64KB component" {
+            boxed_entities.iter().step_by(2).for_each(|entity| boxed_world.despawn(*entity));
+        },
+        "Boxed components bench 1"
+    }
+}
+
+fn compare_value_filters(amount_of_entities: usize, rounds: usize) {
+    println!(" \n ");
+    let mut world = World::default();
+    for i in 0..amount_of_entities {
+        world.spawn((A(i), B(i), C(i), D(i), E(i), F(i), G(i % 100)));
+    }
+    let (mut post_hoc, mut pushed_down) = (0, 0);
+
+    // Value Filters Bench 1
+    // 1% of the entities pass the predicate. Filtering in the loop body fetches all 6 components of every entity
+    // first, while `filter_component` only reads `G` before deciding.
+    compare_worlds_code_blocks! {
+        "post-hoc filter" {
+            for _ in 0..rounds {
+                for (g, a, b, c, d, e, f) in world.query::<(&G, &A, &B, &C, &D, &E, &F)>() {
+                    if g.0 == 0 {
+                        post_hoc += a.0 + b.0 + c.0 + d.0 + e.0 + f.0;
+                    }
+                }
+            }
+        },
+        "filter_component" {
+            for _ in 0..rounds {
+                for (a, b, c, d, e, f) in world
+                    .query::<(&A, &B, &C, &D, &E, &F)>()
+                    .filter_component(|g: &G| g.0 == 0)
+                {
+                    pushed_down += a.0 + b.0 + c.0 + d.0 + e.0 + f.0;
+                }
+            }
+        },
+        "Value filters bench 1"
+    }
+    assert_eq!(post_hoc, pushed_down);
+}
+
+fn compare_spawn_batch(amount_to_spawn: usize) {
+    println!(" \n ");
+    let bundle = |i: usize| (A(i), B(i), C(i), D(i), E(i), F(i), G(i), H(i));
+    let (mut world_a, mut world_b) = (World::default(), World::default());
+
+    // Spawn Batch Bench 1
+    // Into a new storage.
+    compare_worlds_code_blocks! {
+        "spawn in a loop" {
+            for i in 0..amount_to_spawn {
+                world_a.spawn(bundle(i));
+            }
+        },
+        "spawn_batch" {
+            world_b.spawn_batch((0..amount_to_spawn).map(bundle));
+        },
+        "Spawn batch bench 1"
+    }
+
+    // Spawn Batch Bench 2
+    // Into a storage that already has entities.
+    compare_worlds_code_blocks! {
+        "spawn in a loop" {
+            for i in 0..amount_to_spawn {
+                world_a.spawn(bundle(i));
+            }
+        },
+        "spawn_batch" {
+            world_b.spawn_batch((0..amount_to_spawn).map(bundle));
+        },
+        "Spawn batch bench 2"
+    }
+    assert_eq!(world_a.entities(), world_b.entities());
+}
+
+#[macro_export]
+macro_rules! compare_worlds_code_blocks {
+    ($label_a:literal $a:block, $label_b:literal $b:block, $msg:literal) => {
+
+        println!("|  {}  |", $msg);
+
+        let a_instant = std::time::Instant::now();
+        $a
+        let a_time = a_instant.elapsed();
+        println!("\t {} \t: {:?}", $label_a, a_time);
+
+        let b_instant = std::time::Instant::now();
+        $b
+        let b_time = b_instant.elapsed();
+        println!("\t {} \t: {:?}", $label_b, b_time);
+
+        println!("  RATIO: {} ({} / {})  ", b_time.as_secs_f64() / a_time.as_secs_f64(), $label_b, $label_a);
+        println!("  {}  ", "-".repeat($msg.len()));
+    };
+}
+
 #[macro_export]
 macro_rules! compare_code_blocks {
     ($bevy:block, $worlds:block, $msg:literal) => {
@@ -1,6 +1,13 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, parse_quote, DeriveInput};
+use syn::{
+    parse_macro_input, parse_quote, visit::Visit, Data, DeriveInput, Error, Field, LitStr,
+    ParenthesizedGenericArguments, Path, TypeBareFn, TypePath, TypePtr,
+};
+
+mod query_item;
+
+pub use query_item::derive_query_item;
 
 pub fn derive_component(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let mut ast = parse_macro_input!(input as DeriveInput);
@@ -10,15 +17,260 @@ pub fn derive_component(input: proc_macro::TokenStream) -> proc_macro::TokenStre
         .predicates
         .push(parse_quote! { Self: Send + Sync + 'static });
 
+    let attrs = match component_attrs(&ast) {
+        Ok(attrs) => attrs,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    if !attrs.no_send_sync_check {
+        if let Err(error) = check_send_sync(&ast) {
+            return error.to_compile_error().into();
+        }
+    }
+    let mut body = match attrs.storage {
+        Some(ComponentStorage::Boxed) => quote! { const BOXED: bool = true; },
+        Some(ComponentStorage::Shared) => quote! { const SHARED: bool = true; },
+        None => quote! {},
+    };
+
     let struct_name = &ast.ident;
     let (impl_generics, type_generics, where_clause) = &ast.generics.split_for_impl();
 
+    let storage_drop = attrs.storage_drop.as_ref().map(|drop_fn| {
+        body.extend(quote! {
+            fn data_info() -> DataInfo {
+                DataInfo::for_component::<Self>().with_storage_drop::<Self>()
+            }
+        });
+        quote! {
+            impl #impl_generics StorageDrop for #struct_name #type_generics #where_clause {
+                fn storage_drop(self) {
+                    #drop_fn(self)
+                }
+            }
+        }
+    });
+
+    let registration = auto_registration(&ast, &attrs);
+
     TokenStream::from(quote! {
         impl #impl_generics Data for #struct_name #type_generics #where_clause {}
-        impl #impl_generics Component for #struct_name #type_generics #where_clause {}
+        impl #impl_generics Component for #struct_name #type_generics #where_clause { #body }
+        #storage_drop
+        #registration
     })
 }
 
+/// The entry of the component in the registry of `worlds_ecs::auto_register`, with the `auto-register` feature.
+///
+/// Generic components aren't auto-registered, since they don't name a single type. Neither are the components of
+/// `worlds_ecs` itself (the ones of its unit tests), which can't name the crate by its path.
+fn auto_registration(ast: &DeriveInput, attrs: &ComponentAttrs) -> proc_macro2::TokenStream {
+    if !cfg!(feature = "auto-register")
+        || attrs.no_auto_register
+        || !ast.generics.params.is_empty()
+        || std::env::var("CARGO_CRATE_NAME").is_ok_and(|name| name == "worlds_ecs")
+    {
+        return quote! {};
+    }
+    let struct_name = &ast.ident;
+    let mut register = quote! { factory.register_component::<#struct_name>() };
+    if attrs.cloneable {
+        register = quote! { #register.and(factory.register_cloneable_component::<#struct_name>()) };
+    }
+    if attrs.default {
+        register =
+            quote! { #register.and(factory.register_component_with_default::<#struct_name>()) };
+    }
+    quote! {
+        const _: () = {
+            use ::worlds_ecs::{auto_register, component::{ComponentFactory, ComponentId}};
+
+            fn register(factory: &mut ComponentFactory) -> Option<ComponentId> {
+                #register
+            }
+
+            #[auto_register::linkme::distributed_slice(auto_register::AUTO_REGISTERED)]
+            #[linkme(crate = auto_register::linkme)]
+            static REGISTRATION: auto_register::AutoRegistration =
+                auto_register::AutoRegistration::new::<#struct_name>(::std::any::type_name::<#struct_name>, register);
+        };
+    }
+}
+
+/// The options of the `#[component(..)]` attribute of a component.
+#[derive(Default)]
+struct ComponentAttrs {
+    storage: Option<ComponentStorage>,
+    /// `#[component(no_auto_register)]`
+    no_auto_register: bool,
+    /// `#[component(cloneable)]`, which registers the component as cloneable when it's auto-registered.
+    cloneable: bool,
+    /// `#[component(default)]`, which registers the component with its default value when it's auto-registered.
+    default: bool,
+    /// `#[component(storage_drop = "path")]`, the function that the stored values are dropped with.
+    storage_drop: Option<Path>,
+    /// `#[component(no_send_sync_check)]`, which skips [`check_send_sync`], for fields whose types are named like
+    /// the standard types that aren't `Send` or `Sync`.
+    no_send_sync_check: bool,
+}
+
+/// How a component is stored, if it isn't stored inline.
+enum ComponentStorage {
+    /// `#[component(boxed)]`
+    Boxed,
+    /// `#[component(shared)]`
+    Shared,
+}
+
+/// The options of the `#[component(..)]` attributes of the component.
+fn component_attrs(ast: &DeriveInput) -> Result<ComponentAttrs, Error> {
+    let mut attrs = ComponentAttrs::default();
+    for attr in &ast.attrs {
+        if !attr.path().is_ident("component") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            let kind = if meta.path.is_ident("boxed") {
+                ComponentStorage::Boxed
+            } else if meta.path.is_ident("shared") {
+                ComponentStorage::Shared
+            } else if meta.path.is_ident("no_auto_register") {
+                attrs.no_auto_register = true;
+                return Ok(());
+            } else if meta.path.is_ident("cloneable") {
+                attrs.cloneable = true;
+                return Ok(());
+            } else if meta.path.is_ident("default") {
+                attrs.default = true;
+                return Ok(());
+            } else if meta.path.is_ident("storage_drop") {
+                attrs.storage_drop = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                return Ok(());
+            } else if meta.path.is_ident("no_send_sync_check") {
+                attrs.no_send_sync_check = true;
+                return Ok(());
+            } else {
+                return Err(meta.error(
+                    "unknown component attribute, expected `boxed`, `shared`, `no_auto_register`, `cloneable`, \
+                     `default`, `storage_drop` or `no_send_sync_check`",
+                ));
+            };
+            if attrs.storage.is_some() {
+                return Err(meta.error("a component can only be either `boxed` or `shared`"));
+            }
+            attrs.storage = Some(kind);
+            Ok(())
+        })?;
+    }
+    if let (Some(ComponentStorage::Shared), Some(path)) = (&attrs.storage, &attrs.storage_drop) {
+        return Err(Error::new_spanned(
+            path,
+            "a `shared` component can't have a `storage_drop`, its values are dropped when the last entity that \
+             shares them is despawned",
+        ));
+    }
+    Ok(attrs)
+}
+
+/// Fail with an error that names the field, if the type of a field of the component is (or contains) one of the
+/// standard types that aren't `Send` or `Sync`, instead of the error of the unsatisfied `Self: Send + Sync` bound.
+/// Types are recognized by their names, so other types that aren't `Send` or `Sync` are still caught by the bound.
+///
+/// The types that are only `!Sync` (the cells) are fine inside a `Mutex`, which is `Sync` as long as what it holds is
+/// `Send`, and nothing in the signature of a function pointer or a `Fn` trait matters, since those are always `Send`
+/// and `Sync`.
+fn check_send_sync(ast: &DeriveInput) -> Result<(), Error> {
+    let fields: Vec<&Field> = match &ast.data {
+        Data::Struct(data) => data.fields.iter().collect(),
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .flat_map(|variant| variant.fields.iter())
+            .collect(),
+        Data::Union(data) => data.fields.named.iter().collect(),
+    };
+    for (index, field) in fields.into_iter().enumerate() {
+        let mut finder = NotSendSync::default();
+        finder.visit_type(&field.ty);
+        let Some((span, name, instead)) = finder.found else {
+            continue;
+        };
+        let field_name = match &field.ident {
+            Some(ident) => ident.to_string(),
+            None => index.to_string(),
+        };
+        return Err(Error::new(
+            span,
+            format!(
+                "the field `{field_name}` of the component `{}` is a `{name}`, which isn't `Send` and `Sync`, but \
+                 components must be: use {instead} instead (or `#[component(no_send_sync_check)]` if this isn't \
+                 the standard `{name}`)",
+                ast.ident
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Finds the first standard type that isn't `Send` or `Sync` in a type: its span, its name, and what to use instead.
+#[derive(Default)]
+struct NotSendSync {
+    found: Option<(proc_macro2::Span, &'static str, &'static str)>,
+    /// Whether the visited type only has to be `Send`, because it's inside a `Mutex`.
+    only_send: bool,
+}
+
+impl<'ast> Visit<'ast> for NotSendSync {
+    fn visit_type_path(&mut self, path: &'ast TypePath) {
+        if self.found.is_some() {
+            return;
+        }
+        let Some(segment) = path.path.segments.last() else {
+            return syn::visit::visit_type_path(self, path);
+        };
+        let name = segment.ident.to_string();
+        let found = match name.as_str() {
+            "Rc" => Some(("Rc", "`Arc`")),
+            "RefCell" if !self.only_send => Some(("RefCell", "`Mutex` or `RwLock`")),
+            "UnsafeCell" if !self.only_send => {
+                Some(("UnsafeCell", "a type that implements `Sync`"))
+            }
+            // A `Cell` without arguments is more likely a cell of a grid.
+            "Cell" if !self.only_send && !segment.arguments.is_empty() => {
+                Some(("Cell", "an atomic or a `Mutex`"))
+            }
+            _ => None,
+        };
+        if let Some((name, instead)) = found {
+            self.found = Some((segment.ident.span(), name, instead));
+            return;
+        }
+        // A `Mutex` only needs what it holds to be `Send`, and an `Arc` needs it to be `Sync` again.
+        let only_send = match name.as_str() {
+            "Mutex" => true,
+            "Arc" => false,
+            _ => self.only_send,
+        };
+        let outer = std::mem::replace(&mut self.only_send, only_send);
+        syn::visit::visit_type_path(self, path);
+        self.only_send = outer;
+    }
+
+    fn visit_type_ptr(&mut self, ptr: &'ast TypePtr) {
+        if self.found.is_none() {
+            self.found = Some((
+                ptr.star_token.span,
+                "raw pointer",
+                "a wrapper that implements `Send` and `Sync`",
+            ));
+        }
+    }
+
+    fn visit_type_bare_fn(&mut self, _: &'ast TypeBareFn) {}
+
+    fn visit_parenthesized_generic_arguments(&mut self, _: &'ast ParenthesizedGenericArguments) {}
+}
+
 pub fn derive_tag(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let mut ast = parse_macro_input!(input as DeriveInput);
 
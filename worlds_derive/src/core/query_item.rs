@@ -0,0 +1,194 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields, GenericParam, Type,
+};
+
+/// The path of the items that the generated code uses.
+fn support() -> TokenStream {
+    quote! { ::worlds_ecs::query::query_data::__derive }
+}
+
+pub fn derive_query_item(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    match query_item(&ast) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn query_item(ast: &DeriveInput) -> Result<TokenStream, Error> {
+    let support = support();
+    let struct_name = &ast.ident;
+
+    let fields = match &ast.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => Some(&fields.named),
+            _ => None,
+        },
+        _ => None,
+    };
+    let Some(fields) = fields else {
+        return Err(Error::new(
+            struct_name.span(),
+            "`QueryItem` can only be derived for structs with named fields",
+        ));
+    };
+
+    let lifetime = match ast.generics.params.iter().collect::<Vec<_>>()[..] {
+        [GenericParam::Lifetime(param)] => &param.lifetime,
+        _ => {
+            let span = match ast.generics.params.is_empty() {
+                true => struct_name.span(),
+                false => ast.generics.span(),
+            };
+            return Err(Error::new(
+                span,
+                "a `QueryItem` struct must have exactly one lifetime parameter (the lifetime of the fetched \
+                 components), and no other generic parameters",
+            ));
+        }
+    };
+    if let Some(where_clause) = &ast.generics.where_clause {
+        return Err(Error::new(
+            where_clause.span(),
+            "a `QueryItem` struct can't have a where clause",
+        ));
+    }
+
+    let mut names = Vec::new();
+    let mut queries: Vec<Type> = Vec::new();
+    for field in fields {
+        names.push(field.ident.clone().unwrap());
+        queries.push(field_query(field)?);
+    }
+
+    // The checks are spanned to their fields, so a field whose type isn't a query is pointed at.
+    let checks = queries.iter().map(|query| {
+        quote_spanned! {query.span()=>
+            #support::assert_query::<#query>();
+        }
+    });
+    // Every field is fetched with the decisions after the ones of the fields before it.
+    let fetches = names.iter().zip(&queries).map(|(name, query)| {
+        quote_spanned! {query.span()=>
+            #name: {
+                let item = <#query as #support::ArchQuery>::fetch(arch_storage, index, comp_factory, ctx);
+                ctx = ctx.skip_decisions(<#query as #support::ArchQuery>::STORAGE_DECISIONS);
+                item
+            }
+        }
+    });
+
+    let shared_fetches = names.iter().zip(&queries).map(|(name, query)| {
+        quote_spanned! {query.span()=>
+            #name: {
+                let item = <#query as #support::ArchQuery>::fetch_shared(arch_storage, index, comp_factory, ctx);
+                ctx = ctx.skip_decisions(<#query as #support::ArchQuery>::STORAGE_DECISIONS);
+                item
+            }
+        }
+    });
+
+    Ok(quote! {
+        const _: () = {
+            #[allow(unused)]
+            fn check<#lifetime>() {
+                #(#checks)*
+            }
+
+            // SAFETY: Every field is fetched with its own query, and the keys, accesses and mutability of the
+            // queries of all the fields are merged.
+            unsafe impl<#lifetime> #support::ArchQuery for #struct_name<#lifetime> {
+                type Item<'__item> = #struct_name<'__item>;
+
+                const IS_MUTABLE: bool = false #(|| <#queries as #support::ArchQuery>::IS_MUTABLE)*;
+
+                const STORAGE_DECISIONS: u32 = 0 #(+ <#queries as #support::ArchQuery>::STORAGE_DECISIONS)*;
+
+                #[allow(unused_assignments, unused_mut)]
+                unsafe fn fetch<'__item>(
+                    arch_storage: *mut #support::ArchEntityStorage,
+                    index: #support::ArchStorageIndex,
+                    comp_factory: &'__item #support::ComponentFactory,
+                    mut ctx: #support::FetchContext<'_>,
+                ) -> Self::Item<'__item> {
+                    #struct_name {
+                        #(#fetches,)*
+                    }
+                }
+
+                #[allow(unused_assignments, unused_mut)]
+                unsafe fn fetch_shared<'__item>(
+                    arch_storage: &'__item #support::ArchEntityStorage,
+                    index: #support::ArchStorageIndex,
+                    comp_factory: &'__item #support::ComponentFactory,
+                    mut ctx: #support::FetchContext<'_>,
+                ) -> Self::Item<'__item> {
+                    #struct_name {
+                        #(#shared_fetches,)*
+                    }
+                }
+
+                fn decide_storage(
+                    arch_storage: &#support::ArchEntityStorage,
+                    comp_factory: &#support::ComponentFactory,
+                    decisions: &mut #support::StorageDecisions,
+                ) {
+                    #(<#queries as #support::ArchQuery>::decide_storage(arch_storage, comp_factory, decisions);)*
+                }
+
+                #[track_caller]
+                fn merge_prime_arch_key_with(
+                    pkey: &mut #support::PrimeArchKey,
+                    comp_factory: &#support::ComponentFactory,
+                ) {
+                    #(<#queries as #support::ArchQuery>::merge_prime_arch_key_with(pkey, comp_factory);)*
+                }
+
+                fn merge_required_presence_with(
+                    pkey: &mut #support::PrimeArchKey,
+                    comp_factory: &#support::ComponentFactory,
+                ) {
+                    #(<#queries as #support::ArchQuery>::merge_required_presence_with(pkey, comp_factory);)*
+                }
+
+                fn for_each_access(
+                    comp_factory: &#support::ComponentFactory,
+                    f: &mut dyn FnMut(#support::ComponentId, #support::AccessKind),
+                ) {
+                    #(<#queries as #support::ArchQuery>::for_each_access(comp_factory, f);)*
+                }
+
+                fn is_resolvable(comp_factory: &#support::ComponentFactory) -> bool {
+                    true #(&& <#queries as #support::ArchQuery>::is_resolvable(comp_factory))*
+                }
+            }
+
+            // SAFETY: The struct is only read-only if the query of every field is read-only.
+            unsafe impl<#lifetime> #support::ReadOnlyArchQuery for #struct_name<#lifetime>
+            where
+                #(#queries: #support::ReadOnlyField<#lifetime>,)*
+            {
+            }
+        };
+    })
+}
+
+/// The query of a field: its type, or the query of its `#[query(..)]` attribute.
+fn field_query(field: &syn::Field) -> Result<Type, Error> {
+    let mut query = None;
+    for attr in &field.attrs {
+        if !attr.path().is_ident("query") {
+            continue;
+        }
+        if query.is_some() {
+            return Err(Error::new(
+                attr.span(),
+                "a field can only have one `#[query(..)]` attribute",
+            ));
+        }
+        query = Some(attr.parse_args::<Type>()?);
+    }
+    Ok(query.unwrap_or_else(|| field.ty.clone()))
+}
@@ -2,7 +2,7 @@ use proc_macro::TokenStream;
 
 mod core;
 
-#[proc_macro_derive(Component)]
+#[proc_macro_derive(Component, attributes(component))]
 pub fn derive_component(input: TokenStream) -> proc_macro::TokenStream {
     core::derive_component(input)
 }
@@ -11,6 +11,11 @@ pub fn derive_component(input: TokenStream) -> proc_macro::TokenStream {
 pub fn derive_tag(input: TokenStream) -> proc_macro::TokenStream {
     core::derive_tag(input)
 }
+
+#[proc_macro_derive(QueryItem, attributes(query))]
+pub fn derive_query_item(input: TokenStream) -> proc_macro::TokenStream {
+    core::derive_query_item(input)
+}
 // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
 //                                      MACRO UTILS
 // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
@@ -1,6 +1,10 @@
+/// The key that identifies an archetype, and the set operations on keys.
+pub mod key;
+
 use crate::{
+    archetype::key::PrimeArchKey,
     component::{Component, ComponentFactory, ComponentId},
-    utils::prime_key::PrimeArchKey,
+    utils::panics,
 };
 use worlds_derive::all_tuples;
 
@@ -8,16 +12,75 @@ use worlds_derive::all_tuples;
 pub const MAX_COMPS_PER_ARCH: usize = 30;
 
 /// Information representing the information of a [`Archetype`] in the [`World`].
-#[derive(Default, Debug)]
+///
+/// The information is canonical: the components are sorted by their [`ComponentId`] and deduplicated, so archetypes
+/// made up of the same components have equal infos, however their tuples are ordered or nested. If there were
+/// duplicates, [`Self::check_for_duplicates`] returns `true`, and no storage can be created for the archetype.
+///
+/// With the `bundle-provenance` feature, the info also records which sub-bundle contributed each component (see
+/// `ArchetypeInfo::sources`). The sources aren't compared, so the info stays canonical.
+#[derive(Default, Debug, Clone)]
 pub struct ArchetypeInfo {
     component_ids: Vec<ComponentId>,
     prime_key: PrimeArchKey,
+    had_duplicates: bool,
+    /// The source of each component in [`Self::component_ids`], at the same index.
+    #[cfg(feature = "bundle-provenance")]
+    sources: Vec<&'static str>,
+}
+
+impl PartialEq for ArchetypeInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.component_ids == other.component_ids
+            && self.prime_key == other.prime_key
+            && self.had_duplicates == other.had_duplicates
+    }
 }
 
+impl Eq for ArchetypeInfo {}
+
 impl ArchetypeInfo {
-    fn merge_with(&mut self, other: ArchetypeInfo) {
-        self.component_ids.extend(other.component_ids);
-        self.prime_key.merge_with(other.prime_key);
+    /// Create the [`ArchetypeInfo`] of the archetype that is made up of the given components.
+    pub fn from_component_ids(component_ids: Vec<ComponentId>) -> ArchetypeInfo {
+        let mut arch_info = ArchetypeInfo::default();
+        component_ids
+            .into_iter()
+            .for_each(|comp_id| arch_info.insert(comp_id, UNKNOWN_SOURCE));
+        arch_info
+    }
+
+    /// The [`ArchetypeInfo`] of an archetype with the single component `C`.
+    fn single<C: Component>(comp_id: ComponentId) -> ArchetypeInfo {
+        ArchetypeInfo {
+            component_ids: vec![comp_id],
+            prime_key: comp_id.prime_key(),
+            had_duplicates: false,
+            #[cfg(feature = "bundle-provenance")]
+            sources: vec![std::any::type_name::<C>()],
+        }
+    }
+
+    #[cfg_attr(not(feature = "bundle-provenance"), allow(unused_variables))]
+    fn insert(&mut self, comp_id: ComponentId, source: &'static str) {
+        match self.component_ids.binary_search(&comp_id) {
+            Ok(_) => self.had_duplicates = true,
+            Err(index) => {
+                self.component_ids.insert(index, comp_id);
+                self.prime_key.merge_with(comp_id.prime_key());
+                #[cfg(feature = "bundle-provenance")]
+                self.sources.insert(index, source);
+            }
+        }
+    }
+
+    /// Merge the components of `other` into this info, crediting all of them to the sub-bundle `B` (with the
+    /// `bundle-provenance` feature).
+    fn merge_from<B>(&mut self, other: ArchetypeInfo) {
+        self.had_duplicates |= other.had_duplicates;
+        other
+            .component_ids
+            .into_iter()
+            .for_each(|comp_id| self.insert(comp_id, std::any::type_name::<B>()));
     }
 
     /// Get the unique [`PrimeArchKey`] of this [`Archetype`].
@@ -25,26 +88,29 @@ impl ArchetypeInfo {
         self.prime_key
     }
 
-    /// Get the [`Component`]s that make up this [`Archetype`].
+    /// Get the [`Component`]s that make up this [`Archetype`], sorted by their [`ComponentId`].
     pub fn component_ids(&self) -> &[ComponentId] {
         &self.component_ids
     }
 
-    /// Verify that there aren't duplicate components in this archetype
-    /// Return `true` if there are duplicate components in this [`Archetype`]. else `false`.
+    /// Return `true` if a component appeared more than once in the components that this info was made of (it
+    /// appears once in [`Self::component_ids`]). Else `false`.
     pub fn check_for_duplicates(&self) -> bool {
-        for comp_id in self.component_ids() {
-            if self
-                .prime_key()
-                .is_sub_archetype(comp_id.prime_key().squared())
-            {
-                return true;
-            }
-        }
-        false
+        self.had_duplicates
+    }
+
+    /// The type name of the sub-bundle that contributed each component of [`Self::component_ids`], in the same
+    /// order. The sub-bundles are the elements of the outermost tuple: in `((A, B), C)`, `A` and `B` come from
+    /// `(A, B)`. Infos that weren't made from a bundle type have the source `"<unknown>"`.
+    #[cfg(feature = "bundle-provenance")]
+    pub fn sources(&self) -> &[&'static str] {
+        &self.sources
     }
 }
 
+/// The source of the components of an [`ArchetypeInfo`] that wasn't made from a bundle type.
+const UNKNOWN_SOURCE: &str = "<unknown>";
+
 /// An archetype is a unique set of components.
 // TODO: Expand on documentation with examples and explanations.
 ///
@@ -61,29 +127,55 @@ pub unsafe trait Archetype: Sized {
     fn get_prime_key_or_register(comp_factory: &mut ComponentFactory) -> PrimeArchKey;
     /// Get the [`PrimeArchKey`] of this archetype for a matching [`World`] (whose component info is stored in [`ComponentFactory`])
     fn prime_key(comp_factory: &ComponentFactory) -> Option<PrimeArchKey>;
+
+    /// Merge the prime keys of the components of this archetype into `pkey` (see
+    /// [`PrimeArchKey::merge_component_key`]), registering them if they aren't registered. Returns `false` if one
+    /// of the components was already merged into `pkey`, so the archetype has duplicate components.
+    #[doc(hidden)]
+    fn merge_prime_key_or_register(
+        comp_factory: &mut ComponentFactory,
+        pkey: &mut PrimeArchKey,
+    ) -> bool {
+        let arch_info = Self::get_info_or_register(comp_factory);
+        arch_info
+            .component_ids()
+            .iter()
+            .fold(!arch_info.check_for_duplicates(), |unique, comp_id| {
+                pkey.merge_component_key(comp_id.prime_key()) && unique
+            })
+    }
+
+    /// Like [`Self::merge_prime_key_or_register`], but returns `None` if some of the components aren't registered.
+    #[doc(hidden)]
+    fn merge_prime_key(comp_factory: &ComponentFactory, pkey: &mut PrimeArchKey) -> Option<bool> {
+        let arch_info = Self::arch_info(comp_factory)?;
+        Some(
+            arch_info
+                .component_ids()
+                .iter()
+                .fold(!arch_info.check_for_duplicates(), |unique, comp_id| {
+                    pkey.merge_component_key(comp_id.prime_key()) && unique
+                }),
+        )
+    }
 }
 
 unsafe impl<C> Archetype for C
 where
     C: Component,
 {
+    #[track_caller]
     fn get_info_or_register(comp_factory: &mut ComponentFactory) -> ArchetypeInfo {
-        comp_factory
-            .register_component::<C>()
-            .map(|id| ArchetypeInfo {
-                component_ids: vec![id],
-                prime_key: id.prime_key(),
-            })
-            .expect("The maximum amount of registered components has been reached.")
+        let Some(id) = comp_factory.register_component::<C>() else {
+            panics::fail_component_limit::<C>();
+        };
+        ArchetypeInfo::single::<C>(id)
     }
 
     fn arch_info(comp_factory: &ComponentFactory) -> Option<ArchetypeInfo> {
         comp_factory
             .get_component_id::<C>()
-            .map(|id| ArchetypeInfo {
-                component_ids: vec![id],
-                prime_key: id.prime_key(),
-            })
+            .map(ArchetypeInfo::single::<C>)
     }
 
     fn prime_key(comp_factory: &ComponentFactory) -> Option<PrimeArchKey> {
@@ -92,11 +184,24 @@ where
             .map(|cid| cid.prime_key())
     }
 
+    #[track_caller]
     fn get_prime_key_or_register(comp_factory: &mut ComponentFactory) -> PrimeArchKey {
-        comp_factory
-            .register_component::<C>()
-            .map(|cid| cid.prime_key())
-            .expect("The maximum amout of registered components has been reached.")
+        let Some(cid) = comp_factory.register_component::<C>() else {
+            panics::fail_component_limit::<C>();
+        };
+        cid.prime_key()
+    }
+
+    #[track_caller]
+    fn merge_prime_key_or_register(
+        comp_factory: &mut ComponentFactory,
+        pkey: &mut PrimeArchKey,
+    ) -> bool {
+        pkey.merge_component_key(Self::get_prime_key_or_register(comp_factory))
+    }
+
+    fn merge_prime_key(comp_factory: &ComponentFactory, pkey: &mut PrimeArchKey) -> Option<bool> {
+        Some(pkey.merge_component_key(Self::prime_key(comp_factory)?))
     }
 }
 
@@ -106,27 +211,42 @@ macro_rules! impl_archetype {
         unsafe impl<$($name: Archetype),*> Archetype for ($($name,)*) {
             fn get_info_or_register(components: &mut ComponentFactory) -> ArchetypeInfo {
                 let mut arch_info = ArchetypeInfo::default();
-                $(arch_info.merge_with($name::get_info_or_register(components));)*
+                $(arch_info.merge_from::<$name>($name::get_info_or_register(components));)*
                 arch_info
             }
 
             fn arch_info(components: &ComponentFactory) -> Option<ArchetypeInfo> {
                 let mut arch_info = ArchetypeInfo::default();
-                $(arch_info.merge_with($name::arch_info(components)?);)*
+                $(arch_info.merge_from::<$name>($name::arch_info(components)?);)*
                 Some(arch_info)
             }
 
             fn prime_key(comp_factory: &ComponentFactory) -> Option<PrimeArchKey> {
                 let mut pkey = PrimeArchKey::IDENTITY;
-                $(pkey.merge_with($name::prime_key(comp_factory)?);)*
+                Self::merge_prime_key(comp_factory, &mut pkey)?;
                 Some(pkey)
             }
 
             fn get_prime_key_or_register(comp_factory: &mut ComponentFactory) -> PrimeArchKey {
                 let mut pkey = PrimeArchKey::IDENTITY;
-                $(pkey.merge_with($name::get_prime_key_or_register(comp_factory));)*
+                Self::merge_prime_key_or_register(comp_factory, &mut pkey);
                 pkey
             }
+
+            fn merge_prime_key_or_register(
+                comp_factory: &mut ComponentFactory,
+                pkey: &mut PrimeArchKey,
+            ) -> bool {
+                let mut unique = true;
+                $(unique &= $name::merge_prime_key_or_register(comp_factory, pkey);)*
+                unique
+            }
+
+            fn merge_prime_key(comp_factory: &ComponentFactory, pkey: &mut PrimeArchKey) -> Option<bool> {
+                let mut unique = true;
+                $(unique &= $name::merge_prime_key(comp_factory, pkey)?;)*
+                Some(unique)
+            }
         }
     };
 }
@@ -135,7 +255,7 @@ all_tuples!(impl_archetype, 0, 15, A);
 
 #[cfg(test)]
 mod tests {
-    use super::Archetype;
+    use super::{Archetype, ArchetypeInfo};
     use crate::prelude::*;
 
     #[derive(Component)]
@@ -144,6 +264,8 @@ mod tests {
     struct B;
     #[derive(Component)]
     struct C;
+    #[derive(Component)]
+    struct D;
 
     #[test]
     fn test_archetype_prime_keys() {
@@ -227,12 +349,121 @@ mod tests {
         assert_eq!(comps[2], ComponentId::new(2));
         assert!(!arch_info.check_for_duplicates());
 
+        // Duplicates are removed, and the prime of `C` isn't squared.
         let arch_info = <(A, B, C, C) as Archetype>::arch_info(&comp_factory).unwrap();
         let comps = arch_info.component_ids();
-        assert_eq!(comps[0], ComponentId::new(0));
-        assert_eq!(comps[1], ComponentId::new(1));
-        assert_eq!(comps[2], ComponentId::new(2));
-        assert_eq!(comps[3], ComponentId::new(2));
+        assert_eq!(
+            comps,
+            [
+                ComponentId::new(0),
+                ComponentId::new(1),
+                ComponentId::new(2)
+            ]
+        );
+        assert_eq!(arch_info.prime_key().as_u64(), 30);
         assert!(arch_info.check_for_duplicates());
+        assert_eq!(
+            <(A, B, C, C) as Archetype>::prime_key(&comp_factory),
+            <(A, B, C) as Archetype>::prime_key(&comp_factory)
+        );
+    }
+
+    #[test]
+    fn test_canonical_nested_archetypes() {
+        let mut comp_factory = ComponentFactory::default();
+        comp_factory.register_component::<A>();
+        comp_factory.register_component::<B>();
+        comp_factory.register_component::<C>();
+        comp_factory.register_component::<D>();
+
+        let flat = <(A, B, C, D) as Archetype>::arch_info(&comp_factory).unwrap();
+        let nested = [
+            <(D, C, B, A) as Archetype>::arch_info(&comp_factory).unwrap(),
+            <((A, B), (C, D)) as Archetype>::arch_info(&comp_factory).unwrap(),
+            <(D, ((C,), (B, (A,)))) as Archetype>::arch_info(&comp_factory).unwrap(),
+            <(((((B,),),), A), (), (D, C)) as Archetype>::arch_info(&comp_factory).unwrap(),
+        ];
+        for arch_info in &nested {
+            assert_eq!(arch_info, &flat);
+            assert!(!arch_info.check_for_duplicates());
+        }
+        assert_eq!(
+            <(D, ((C,), (B, (A,)))) as Archetype>::prime_key(&comp_factory),
+            Some(flat.prime_key())
+        );
+
+        // Components that are repeated across the nesting are merged once.
+        let repeated = [
+            <((A, B), (B, C), (C, D)) as Archetype>::arch_info(&comp_factory).unwrap(),
+            <(A, (B, (C, (D, (A, D))))) as Archetype>::arch_info(&comp_factory).unwrap(),
+        ];
+        for arch_info in &repeated {
+            assert_eq!(arch_info.component_ids(), flat.component_ids());
+            assert_eq!(arch_info.prime_key(), flat.prime_key());
+            assert!(arch_info.check_for_duplicates());
+        }
+        assert_eq!(
+            <((A, B), (B, C), (C, D)) as Archetype>::prime_key(&comp_factory),
+            Some(flat.prime_key())
+        );
+        assert_eq!(
+            ArchetypeInfo::from_component_ids(vec![
+                ComponentId::new(3),
+                ComponentId::new(1),
+                ComponentId::new(2),
+                ComponentId::new(0),
+            ]),
+            flat
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "worlds_ecs: store_archetype failed: a component appears more than once in the archetype"
+    )]
+    fn test_spawn_duplicate_components() {
+        let mut world = World::default();
+        world.spawn(A);
+        // The archetype is `(A, B)` once it's deduplicated, but the bundle has two `A`s to store.
+        world.spawn((A, (B, A)));
+    }
+
+    #[test]
+    fn test_storage_rejects_duplicates() {
+        use crate::world::storage::arch_storage::{ArchStorage, ArchStorageError};
+
+        let mut comp_factory = ComponentFactory::default();
+        comp_factory.register_component::<A>();
+        comp_factory.register_component::<B>();
+        assert_eq!(
+            ArchStorage::new::<(A, B, A)>(&comp_factory).err(),
+            Some(ArchStorageError::DuplicateComponents)
+        );
+        assert_eq!(
+            ArchStorage::new::<(A, C)>(&comp_factory).err(),
+            Some(ArchStorageError::UnregisteredComponents)
+        );
+        assert!(ArchStorage::new::<(B, A)>(&comp_factory).is_ok());
+    }
+
+    #[cfg(feature = "bundle-provenance")]
+    #[test]
+    fn test_sources_of_sub_bundles() {
+        use std::any::type_name;
+
+        let mut comp_factory = ComponentFactory::default();
+        let arch_info = <((A, B), C, (D,)) as Archetype>::get_info_or_register(&mut comp_factory);
+        // The components are sorted by id, which is their order of registration here.
+        assert_eq!(
+            arch_info.sources(),
+            [
+                type_name::<(A, B)>(),
+                type_name::<(A, B)>(),
+                type_name::<C>(),
+                type_name::<(D,)>()
+            ]
+        );
+        let arch_info = ArchetypeInfo::from_component_ids(arch_info.component_ids().to_vec());
+        assert_eq!(arch_info.sources(), ["<unknown>"; 4]);
     }
 }
@@ -0,0 +1,443 @@
+//! [`PrimeArchKey`], the key that identifies an archetype: the product of a distinct prime for every component
+//! in it. Every set of components has exactly one key, and the set operations on archetypes are arithmetic on
+//! their keys: a component is in an archetype if its prime divides the key, an archetype is a sub-archetype of
+//! another if its key divides the other's, and the components two archetypes share are the greatest common
+//! divisor of their keys.
+//!
+//! The empty archetype is [`PrimeArchKey::IDENTITY`] (`1`), which contains no component, is a sub-archetype of
+//! every archetype, and is disjoint from every archetype (including itself).
+
+use crate::{
+    component::{ComponentFactory, ComponentId},
+    impl_id_struct,
+};
+#[cfg(not(many_components))]
+use primitive_types::U256 as PrimeNum;
+#[cfg(many_components)]
+use primitive_types::U512 as PrimeNum;
+
+/// The key of an archetype, see the [module docs](self). Two archetypes have the same key if and only if they
+/// have the same components, regardless of their order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PrimeArchKey(PrimeNum);
+
+impl Default for PrimeArchKey {
+    /// The key of the empty archetype, [`PrimeArchKey::IDENTITY`].
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// The maximum amount of registered components, one for every prime in the table of primes.
+pub const MAX_COMPONENTS: usize = {
+    if cfg!(many_components) {
+        512
+    } else {
+        256
+    }
+};
+
+impl_id_struct!(PrimeArchKey, PrimeNum, pub(crate));
+
+impl PrimeArchKey {
+    const PRIME_TABLE: [usize; MAX_COMPONENTS] = PRIME_NUMBERS;
+    /// The key of the empty archetype. It contains no component, every key [`is_sub_archetype`](Self::is_sub_archetype)
+    /// of it, and it's disjoint from every key. Merging it into a key leaves the key as it is.
+    pub const IDENTITY: PrimeArchKey = PrimeArchKey(PrimeNum::one());
+    /// A key that no archetype is a super-archetype of, because its prime (the first one after the
+    /// table of primes) isn't assigned to any component. Used for queries that can never match.
+    /// It contains no component.
+    pub const NEVER_MATCHES: PrimeArchKey = PrimeArchKey(PrimeNum(NEVER_MATCHES_LIMBS));
+
+    /// The key of the archetype with the single component `comp_id`.
+    ///
+    /// # Panics
+    /// Panics if `comp_id` isn't smaller than [`MAX_COMPONENTS`].
+    #[inline(always)]
+    pub fn component_key(comp_id: ComponentId) -> Self {
+        Self(Self::PRIME_TABLE[comp_id.id()].into())
+    }
+
+    /// The key of the archetype with the components `comp_ids`, in any order. Components that appear more than
+    /// once are counted once. Returns `None` if the key doesn't fit in the key's integer, which can happen
+    /// with many components that were registered late (the primes of later components are larger).
+    ///
+    /// # Panics
+    /// Panics if one of the ids isn't smaller than [`MAX_COMPONENTS`].
+    pub fn from_component_ids(comp_ids: &[ComponentId]) -> Option<Self> {
+        let mut key = Self::IDENTITY;
+        for comp_id in comp_ids {
+            if !key.contains(*comp_id) {
+                key.0 = key.0.checked_mul(Self::component_key(*comp_id).0)?;
+            }
+        }
+        Some(key)
+    }
+
+    /// Returns `true` if the component `comp_id` is in the archetype of this key.
+    /// [`Self::IDENTITY`] and [`Self::NEVER_MATCHES`] contain no component.
+    #[inline]
+    pub fn contains(&self, comp_id: ComponentId) -> bool {
+        comp_id.id() < MAX_COMPONENTS && self.is_sub_archetype(Self::component_key(comp_id))
+    }
+
+    /// Return `true` if the other [`PrimeArchKey`] represents a sub-archetype of the archetype
+    /// this [`PrimeArchKey`] represents. else return `false`.
+    /// An archetype `A` is a sub-archetype of a different archetype `B` if and only if every component
+    /// in `A` is also in `B`. Every key is a sub-archetype of itself, and [`Self::IDENTITY`] is a sub-archetype
+    /// of every key.
+    #[inline]
+    pub fn is_sub_archetype(&self, other: PrimeArchKey) -> bool {
+        (self.0 % other.0).is_zero()
+    }
+
+    /// Return `true` if both this Key and the other key represent the same archetype. Which can
+    /// only be true if and only if the keys are equal. else return `false`.
+    #[inline]
+    pub fn is_exact_archetype(&self, other: PrimeArchKey) -> bool {
+        self.0 == other.0
+    }
+
+    /// Returns `true` if the archetypes of the two keys have no component in common. A key is disjoint from
+    /// [`Self::IDENTITY`], and [`Self::IDENTITY`] is disjoint from itself.
+    pub fn is_disjoint(&self, other: PrimeArchKey) -> bool {
+        self.intersection(other).is_exact_archetype(Self::IDENTITY)
+    }
+
+    /// The key of the archetype with the components that are in both archetypes ([`Self::IDENTITY`] if they
+    /// are disjoint).
+    pub fn intersection(&self, other: PrimeArchKey) -> PrimeArchKey {
+        let (mut a, mut b) = (self.0, other.0);
+        while !b.is_zero() {
+            (a, b) = (b, a % b);
+        }
+        PrimeArchKey(a)
+    }
+
+    /// Merge the other key into this one, so this key represents the archetype with the components of both.
+    /// Merging is commutative and associative, as long as the keys are disjoint: a component that is in both
+    /// keys ends up in the result twice, which no archetype's key is. Use [`Self::merge_component_key`] to
+    /// merge a component that might already be in the key.
+    ///
+    /// # Panics
+    /// Panics if the merged key doesn't fit in the key's integer.
+    pub fn merge_with(&mut self, other: PrimeArchKey) {
+        self.0 *= other.0
+    }
+
+    /// Merge the key of a single component into this key, unless it's already in it (so a prime is never squared).
+    /// Returns `true` if it was merged, and `false` if the component was already in the key.
+    pub fn merge_component_key(&mut self, comp_key: PrimeArchKey) -> bool {
+        if self.is_sub_archetype(comp_key) {
+            return false;
+        }
+        self.merge_with(comp_key);
+        true
+    }
+
+    /// The key of this archetype without the component `comp_id`: this key divided by the component's prime.
+    /// The key is returned as it is if the component isn't in it.
+    pub fn without_component(&self, comp_id: ComponentId) -> PrimeArchKey {
+        match self.contains(comp_id) {
+            true => PrimeArchKey(self.0 / Self::component_key(comp_id).0),
+            false => *self,
+        }
+    }
+
+    /// Iterate over the ids of the components in the archetype of this key, in ascending order. Only the
+    /// components that are registered in `components` are checked, so this is meant for diagnostics rather than
+    /// hot paths.
+    pub fn iter_component_ids<'a>(
+        &'a self,
+        components: &ComponentFactory,
+    ) -> impl Iterator<Item = ComponentId> + 'a {
+        (0..components.component_count())
+            .map(ComponentId::new)
+            .filter(|comp_id| self.contains(*comp_id))
+    }
+
+    /// Conversion to u64 with overflow checking
+    ///
+    /// # Panics
+    /// Panics if the number is larger than u64::max_value().
+    pub fn as_u64(&self) -> u64 {
+        self.0.as_u64()
+    }
+}
+
+#[cfg(not(many_components))]
+const NEVER_MATCHES_LIMBS: [u64; 4] = [1627, 0, 0, 0];
+
+#[cfg(many_components)]
+const NEVER_MATCHES_LIMBS: [u64; 8] = [3673, 0, 0, 0, 0, 0, 0, 0];
+
+#[cfg(not(many_components))]
+const PRIME_NUMBERS: [usize; MAX_COMPONENTS] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311, 313, 317, 331, 337, 347, 349, 353, 359, 367, 373, 379, 383, 389, 397, 401, 409, 419, 421,
+    431, 433, 439, 443, 449, 457, 461, 463, 467, 479, 487, 491, 499, 503, 509, 521, 523, 541, 547,
+    557, 563, 569, 571, 577, 587, 593, 599, 601, 607, 613, 617, 619, 631, 641, 643, 647, 653, 659,
+    661, 673, 677, 683, 691, 701, 709, 719, 727, 733, 739, 743, 751, 757, 761, 769, 773, 787, 797,
+    809, 811, 821, 823, 827, 829, 839, 853, 857, 859, 863, 877, 881, 883, 887, 907, 911, 919, 929,
+    937, 941, 947, 953, 967, 971, 977, 983, 991, 997, 1009, 1013, 1019, 1021, 1031, 1033, 1039,
+    1049, 1051, 1061, 1063, 1069, 1087, 1091, 1093, 1097, 1103, 1109, 1117, 1123, 1129, 1151, 1153,
+    1163, 1171, 1181, 1187, 1193, 1201, 1213, 1217, 1223, 1229, 1231, 1237, 1249, 1259, 1277, 1279,
+    1283, 1289, 1297, 1301, 1303, 1307, 1319, 1321, 1327, 1361, 1367, 1373, 1381, 1399, 1409, 1423,
+    1427, 1429, 1433, 1439, 1447, 1451, 1453, 1459, 1471, 1481, 1483, 1487, 1489, 1493, 1499, 1511,
+    1523, 1531, 1543, 1549, 1553, 1559, 1567, 1571, 1579, 1583, 1597, 1601, 1607, 1609, 1613, 1619,
+    1621,
+];
+
+#[cfg(many_components)]
+const PRIME_NUMBERS: [usize; MAX_COMPONENTS] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311, 313, 317, 331, 337, 347, 349, 353, 359, 367, 373, 379, 383, 389, 397, 401, 409, 419, 421,
+    431, 433, 439, 443, 449, 457, 461, 463, 467, 479, 487, 491, 499, 503, 509, 521, 523, 541, 547,
+    557, 563, 569, 571, 577, 587, 593, 599, 601, 607, 613, 617, 619, 631, 641, 643, 647, 653, 659,
+    661, 673, 677, 683, 691, 701, 709, 719, 727, 733, 739, 743, 751, 757, 761, 769, 773, 787, 797,
+    809, 811, 821, 823, 827, 829, 839, 853, 857, 859, 863, 877, 881, 883, 887, 907, 911, 919, 929,
+    937, 941, 947, 953, 967, 971, 977, 983, 991, 997, 1009, 1013, 1019, 1021, 1031, 1033, 1039,
+    1049, 1051, 1061, 1063, 1069, 1087, 1091, 1093, 1097, 1103, 1109, 1117, 1123, 1129, 1151, 1153,
+    1163, 1171, 1181, 1187, 1193, 1201, 1213, 1217, 1223, 1229, 1231, 1237, 1249, 1259, 1277, 1279,
+    1283, 1289, 1297, 1301, 1303, 1307, 1319, 1321, 1327, 1361, 1367, 1373, 1381, 1399, 1409, 1423,
+    1427, 1429, 1433, 1439, 1447, 1451, 1453, 1459, 1471, 1481, 1483, 1487, 1489, 1493, 1499, 1511,
+    1523, 1531, 1543, 1549, 1553, 1559, 1567, 1571, 1579, 1583, 1597, 1601, 1607, 1609, 1613, 1619,
+    1621, 1627, 1637, 1657, 1663, 1667, 1669, 1693, 1697, 1699, 1709, 1721, 1723, 1733, 1741, 1747,
+    1753, 1759, 1777, 1783, 1787, 1789, 1801, 1811, 1823, 1831, 1847, 1861, 1867, 1871, 1873, 1877,
+    1879, 1889, 1901, 1907, 1913, 1931, 1933, 1949, 1951, 1973, 1979, 1987, 1993, 1997, 1999, 2003,
+    2011, 2017, 2027, 2029, 2039, 2053, 2063, 2069, 2081, 2083, 2087, 2089, 2099, 2111, 2113, 2129,
+    2131, 2137, 2141, 2143, 2153, 2161, 2179, 2203, 2207, 2213, 2221, 2237, 2239, 2243, 2251, 2267,
+    2269, 2273, 2281, 2287, 2293, 2297, 2309, 2311, 2333, 2339, 2341, 2347, 2351, 2357, 2371, 2377,
+    2381, 2383, 2389, 2393, 2399, 2411, 2417, 2423, 2437, 2441, 2447, 2459, 2467, 2473, 2477, 2503,
+    2521, 2531, 2539, 2543, 2549, 2551, 2557, 2579, 2591, 2593, 2609, 2617, 2621, 2633, 2647, 2657,
+    2659, 2663, 2671, 2677, 2683, 2687, 2689, 2693, 2699, 2707, 2711, 2713, 2719, 2729, 2731, 2741,
+    2749, 2753, 2767, 2777, 2789, 2791, 2797, 2801, 2803, 2819, 2833, 2837, 2843, 2851, 2857, 2861,
+    2879, 2887, 2897, 2903, 2909, 2917, 2927, 2939, 2953, 2957, 2963, 2969, 2971, 2999, 3001, 3011,
+    3019, 3023, 3037, 3041, 3049, 3061, 3067, 3079, 3083, 3089, 3109, 3119, 3121, 3137, 3163, 3167,
+    3169, 3181, 3187, 3191, 3203, 3209, 3217, 3221, 3229, 3251, 3253, 3257, 3259, 3271, 3299, 3301,
+    3307, 3313, 3319, 3323, 3329, 3331, 3343, 3347, 3359, 3361, 3371, 3373, 3389, 3391, 3407, 3413,
+    3433, 3449, 3457, 3461, 3463, 3467, 3469, 3491, 3499, 3511, 3517, 3527, 3529, 3533, 3539, 3541,
+    3547, 3557, 3559, 3571, 3581, 3583, 3593, 3607, 3613, 3617, 3623, 3631, 3637, 3643, 3659,
+    3671.,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{PrimeArchKey, MAX_COMPONENTS, PRIME_NUMBERS};
+    use crate::{
+        archetype::{Archetype, MAX_COMPS_PER_ARCH},
+        component::ComponentId,
+        prelude::*,
+        utils::component_mask::ComponentMask,
+    };
+
+    fn key(ids: &[usize]) -> PrimeArchKey {
+        let ids: Vec<ComponentId> = ids.iter().map(|id| ComponentId::new(*id)).collect();
+        PrimeArchKey::from_component_ids(&ids).unwrap()
+    }
+
+    fn merged(mut a: PrimeArchKey, b: PrimeArchKey) -> PrimeArchKey {
+        a.merge_with(b);
+        a
+    }
+
+    // Small sets of component ids, including the first and last components.
+    const SETS: [&[usize]; 8] = [
+        &[],
+        &[0],
+        &[1, 2],
+        &[0, 3, 7],
+        &[MAX_COMPONENTS - 1],
+        &[2, MAX_COMPONENTS - 1],
+        &[64, 65, 128],
+        &[0, 1, 2, 3, 64, MAX_COMPONENTS - 2],
+    ];
+
+    #[test]
+    fn test_primes() {
+        // Distinct primes, so every set of components has a different key.
+        assert!(PRIME_NUMBERS.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(PRIME_NUMBERS[0], 2);
+        for prime in PRIME_NUMBERS.iter() {
+            assert!((2..*prime)
+                .take_while(|d| d * d <= *prime)
+                .all(|d| !prime.is_multiple_of(d)));
+        }
+        // The prime of `NEVER_MATCHES` is larger than all of them, so no component has it.
+        let next = PrimeArchKey::NEVER_MATCHES.as_u64() as usize;
+        assert!(next > PRIME_NUMBERS[MAX_COMPONENTS - 1]);
+        assert!((2..next)
+            .take_while(|d| d * d <= next)
+            .all(|d| !next.is_multiple_of(d)));
+        for id in 0..MAX_COMPONENTS {
+            assert!(!PrimeArchKey::NEVER_MATCHES.contains(ComponentId::new(id)));
+        }
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let last = ComponentId::new(MAX_COMPONENTS - 1);
+        assert_eq!(
+            PrimeArchKey::component_key(last).as_u64(),
+            PRIME_NUMBERS[MAX_COMPONENTS - 1] as u64
+        );
+        assert!(key(&[MAX_COMPONENTS - 1]).contains(last));
+        // Ids past the table aren't in any key.
+        assert!(!key(&[0, MAX_COMPONENTS - 1]).contains(ComponentId::new(MAX_COMPONENTS)));
+
+        // The primes of the last components are large, so only so many of them fit in a key.
+        let largest: Vec<usize> = (0..MAX_COMPONENTS).rev().collect();
+        let fit = (1..=largest.len())
+            .take_while(|n| {
+                let ids: Vec<_> = largest[..*n]
+                    .iter()
+                    .map(|id| ComponentId::new(*id))
+                    .collect();
+                PrimeArchKey::from_component_ids(&ids).is_some()
+            })
+            .count();
+        assert!((20..MAX_COMPONENTS).contains(&fit), "{fit}");
+        assert!(std::panic::catch_unwind(|| {
+            let mut k = key(&largest[..fit]);
+            k.merge_with(key(&largest[fit..fit + 1]));
+        })
+        .is_err());
+        // An archetype of the first components always fits.
+        let smallest: Vec<usize> = (0..MAX_COMPS_PER_ARCH).collect();
+        key(&smallest);
+    }
+
+    #[test]
+    fn test_from_component_ids() {
+        assert_eq!(key(&[]), PrimeArchKey::IDENTITY);
+        assert_eq!(key(&[]), PrimeArchKey::default());
+        assert_eq!(key(&[1, 2, 3]).as_u64(), 3 * 5 * 7);
+        assert_eq!(key(&[3, 1, 2]), key(&[1, 2, 3]));
+        // Duplicates are counted once.
+        assert_eq!(key(&[2, 1, 2, 2]), key(&[1, 2]));
+    }
+
+    #[test]
+    fn test_identity() {
+        let identity = PrimeArchKey::IDENTITY;
+        assert!(identity.is_disjoint(identity));
+        assert_eq!(identity.intersection(identity), identity);
+        assert!(identity.is_sub_archetype(identity));
+        for set in SETS {
+            let k = key(set);
+            assert!(k.is_sub_archetype(identity));
+            assert_eq!(identity.is_sub_archetype(k), set.is_empty());
+            assert!(k.is_disjoint(identity));
+            assert!(identity.is_disjoint(k));
+            assert_eq!(k.intersection(identity), identity);
+            assert_eq!(merged(k, identity), k);
+            assert_eq!(merged(identity, k), k);
+        }
+        for id in 0..MAX_COMPONENTS {
+            assert!(!identity.contains(ComponentId::new(id)));
+        }
+    }
+
+    #[test]
+    fn test_merge_laws() {
+        for a in SETS {
+            for b in SETS {
+                let (ka, kb) = (key(a), key(b));
+                if !ka.is_disjoint(kb) {
+                    continue;
+                }
+                assert_eq!(merged(ka, kb), merged(kb, ka));
+                let union: Vec<usize> = a.iter().chain(b.iter()).copied().collect();
+                assert_eq!(merged(ka, kb), key(&union));
+                for c in SETS {
+                    let kc = key(c);
+                    if !kc.is_disjoint(merged(ka, kb)) {
+                        continue;
+                    }
+                    assert_eq!(merged(merged(ka, kb), kc), merged(ka, merged(kb, kc)));
+                }
+            }
+        }
+        // A component that's already in the key isn't merged again.
+        let mut k = key(&[0, 3]);
+        assert!(!k.merge_component_key(key(&[3])));
+        assert_eq!(k, key(&[0, 3]));
+        assert!(k.merge_component_key(key(&[4])));
+        assert_eq!(k, key(&[0, 3, 4]));
+        // Removing a component undoes merging it.
+        assert_eq!(k.without_component(ComponentId::new(4)), key(&[0, 3]));
+        assert_eq!(k.without_component(ComponentId::new(5)), k);
+        assert_eq!(
+            key(&[MAX_COMPONENTS - 1]).without_component(ComponentId::new(MAX_COMPONENTS - 1)),
+            PrimeArchKey::IDENTITY
+        );
+    }
+
+    #[test]
+    fn test_set_operations_agree_with_masks() {
+        let mask = |set: &[usize]| {
+            ComponentMask::from_component_ids(set.iter().map(|id| ComponentId::new(*id)))
+        };
+        for a in SETS {
+            let (ka, ma) = (key(a), mask(a));
+            for id in 0..MAX_COMPONENTS {
+                let comp_id = ComponentId::new(id);
+                assert_eq!(ka.contains(comp_id), ma.contains(comp_id));
+            }
+            for b in SETS {
+                let (kb, mb) = (key(b), mask(b));
+                let ids = (0..MAX_COMPONENTS).map(ComponentId::new);
+                let both: Vec<ComponentId> = ids
+                    .clone()
+                    .filter(|id| ma.contains(*id) && mb.contains(*id))
+                    .collect();
+                assert_eq!(
+                    ka.intersection(kb),
+                    PrimeArchKey::from_component_ids(&both).unwrap()
+                );
+                assert_eq!(ka.is_disjoint(kb), both.is_empty());
+                let subset = ids.clone().all(|id| !mb.contains(id) || ma.contains(id));
+                assert_eq!(ka.is_sub_archetype(kb), subset);
+                assert_eq!(ka.is_exact_archetype(kb), ma == mb);
+            }
+        }
+    }
+
+    #[test]
+    fn test_iter_component_ids() {
+        #[derive(Component)]
+        struct A;
+        #[derive(Component)]
+        struct B;
+        #[derive(Component)]
+        struct C;
+
+        let mut world = World::default();
+        world.spawn((C, A));
+        world.spawn(B);
+        let components = &world.components;
+        let key = <(A, C)>::prime_key(components).unwrap();
+        let ids: Vec<ComponentId> = key.iter_component_ids(components).collect();
+        let (a, c) = (
+            components.get_component_id::<A>().unwrap(),
+            components.get_component_id::<C>().unwrap(),
+        );
+        assert_eq!(ids, [a.min(c), a.max(c)]);
+        assert_eq!(
+            PrimeArchKey::IDENTITY
+                .iter_component_ids(components)
+                .count(),
+            0
+        );
+        assert_eq!(
+            PrimeArchKey::NEVER_MATCHES
+                .iter_component_ids(components)
+                .count(),
+            0
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! The registry of the components that are registered automatically, with the `auto-register` feature.
+//!
+//! With the feature, `#[derive(Component)]` adds every component to [`AUTO_REGISTERED`] at link time, and every
+//! new [`ComponentFactory`] (so every new [`World`](crate::world::World)) registers all of them before anything
+//! else, sorted by their type names. The [`ComponentId`]s are then the same in every run, and in every process
+//! that links the same components, however the code is laid out, and whatever is spawned first.
+//!
+//! A component can opt out with `#[component(no_auto_register)]`. Generic components aren't auto-registered,
+//! since they don't name a single type. `#[component(cloneable)]` and `#[component(default)]` register the
+//! component as cloneable and with its default value (see [`ComponentFactory::register_cloneable_component`]
+//! and [`ComponentFactory::register_component_with_default`]). Scene components still have to be registered with
+//! their names (see [`World::register_scene_component`](crate::world::World::register_scene_component)).
+//!
+//! The components that are derived inside `worlds_ecs` itself (including its tests) aren't auto-registered, so the
+//! ids in its tests don't depend on the feature.
+//!
+//! ```no_run
+//! use worlds_ecs::prelude::*;
+//!
+//! #[derive(Component)]
+//! struct Health(u32);
+//!
+//! let mut world = World::default();
+//! assert!(world.components().get_component_id::<Health>().is_some());
+//! assert_eq!(world.query::<&Health>().count(), 0);
+//! ```
+
+use crate::component::{ComponentFactory, ComponentId};
+use std::any::TypeId;
+
+/// The version of `linkme` that the derived entries of [`AUTO_REGISTERED`] are built with.
+#[doc(hidden)]
+pub use linkme;
+
+/// Every component that is registered automatically, in the order they were linked in.
+#[linkme::distributed_slice]
+pub static AUTO_REGISTERED: [AutoRegistration];
+
+/// An entry of [`AUTO_REGISTERED`]: how to register a component, and the name it's sorted by.
+pub struct AutoRegistration {
+    type_name: fn() -> &'static str,
+    type_id: fn() -> TypeId,
+    register: fn(&mut ComponentFactory) -> Option<ComponentId>,
+}
+
+impl AutoRegistration {
+    /// The entry of the component `C`, which is registered with `register`.
+    #[doc(hidden)]
+    pub const fn new<C: 'static>(
+        type_name: fn() -> &'static str,
+        register: fn(&mut ComponentFactory) -> Option<ComponentId>,
+    ) -> Self {
+        AutoRegistration {
+            type_name,
+            type_id: TypeId::of::<C>,
+            register,
+        }
+    }
+
+    /// The name of the type of the component.
+    pub fn type_name(&self) -> &'static str {
+        (self.type_name)()
+    }
+}
+
+impl ComponentFactory {
+    /// Register every component of [`AUTO_REGISTERED`] that isn't registered yet, sorted by their type names, and
+    /// return how many were registered. A new factory does this when it's created.
+    ///
+    /// ```no_run
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// // A new factory already registered every component.
+    /// assert!(components.get_component_id::<Health>().is_some());
+    /// assert_eq!(components.register_auto_registered(), 0);
+    /// ```
+    pub fn register_auto_registered(&mut self) -> usize {
+        let mut registrations: Vec<&AutoRegistration> = AUTO_REGISTERED
+            .iter()
+            .filter(|registration| {
+                self.get_component_id_from_type_id((registration.type_id)())
+                    .is_none()
+            })
+            .collect();
+        registrations.sort_by_key(|registration| registration.type_name());
+        registrations.dedup_by_key(|registration| (registration.type_id)());
+        for registration in &registrations {
+            if (registration.register)(self).is_none() {
+                crate::utils::panics::fail_component(
+                    "register_auto_registered",
+                    "the maximum amount of components is registered",
+                    registration.type_name(),
+                );
+            }
+        }
+        registrations.len()
+    }
+}
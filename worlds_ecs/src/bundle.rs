@@ -1,10 +1,18 @@
 use bevy_ptr::OwningPtr;
 use worlds_derive::all_tuples;
 
-use crate::prelude::{Component, ComponentFactory, ComponentId};
+use crate::{
+    component::{make_component, take_component},
+    prelude::{Component, ComponentFactory, ComponentId},
+};
 
 /// A bundle of components.
 pub trait Bundle {
+    /// The amount of components in the bundle, counted from its type: `1` for a component, and the sum of the
+    /// counts of the elements for a tuple (so a component that appears twice is counted twice). Bundles whose
+    /// components are only known at runtime count `0`. See [`const_assert_bundle_size!`](crate::const_assert_bundle_size).
+    const COMPONENT_COUNT: usize = 0;
+
     /// This method calls `f` on all of the components in the bundle. This could, for example,
     ///  be used in conjunction with [`Vec::push`] to collect all the components into a [`Vec`].
     fn raw_components_scope(
@@ -15,12 +23,14 @@ pub trait Bundle {
 }
 
 impl<C: Component> Bundle for C {
+    const COMPONENT_COUNT: usize = 1;
+
     fn raw_components_scope(
         self,
         comp_factory: &ComponentFactory,
         f: &mut impl FnMut(ComponentId, OwningPtr<'_>),
     ) {
-        OwningPtr::make(self, |ptr| {
+        make_component(self, comp_factory, |ptr| {
             f(
                 comp_factory.get_component_id::<C>().unwrap(),
                 // SAFETY: We own self
@@ -33,6 +43,8 @@ impl<C: Component> Bundle for C {
 macro_rules! impl_bundle_for_tuple {
     ($($name:ident),*) => {
         impl<$($name: Bundle),*> Bundle for ($($name,)*) {
+            const COMPONENT_COUNT: usize = 0 $(+ $name::COMPONENT_COUNT)*;
+
             #[allow(non_snake_case, unused)]
             fn raw_components_scope(self, comp_factory: &ComponentFactory, f: &mut impl FnMut(ComponentId, OwningPtr<'_>)) {
                 let ($($name,)*) = self;
@@ -44,9 +56,160 @@ macro_rules! impl_bundle_for_tuple {
 
 all_tuples!(impl_bundle_for_tuple, 0, 12, B);
 
+/// A [`Bundle`] that can be built back from its components, the inverse of [`Bundle::raw_components_scope`]
+/// (see [`World::extract_bundles`](crate::world::World::extract_bundles)).
+pub trait FromComponents: Bundle + Sized {
+    /// Build the bundle out of its components. `take` is called with the [`ComponentId`] of each component of the
+    /// bundle, and a function that moves the component out of the [`OwningPtr`] it's called with. Returns `None` if
+    /// `take` didn't call it for one of the components (or if one of them isn't registered, or is shared, see
+    /// [`Component::SHARED`]).
+    ///
+    /// # Safety
+    /// `take` must call the function with a pointer to a stored component of the [`ComponentId`] it was given (boxed
+    /// if the component is boxed, see [`Component::BOXED`]), which is moved out.
+    unsafe fn from_raw_components(
+        comp_factory: &ComponentFactory,
+        take: &mut impl FnMut(ComponentId, &mut dyn FnMut(OwningPtr<'_>)),
+    ) -> Option<Self>;
+}
+
+impl<C: Component> FromComponents for C {
+    unsafe fn from_raw_components(
+        comp_factory: &ComponentFactory,
+        take: &mut impl FnMut(ComponentId, &mut dyn FnMut(OwningPtr<'_>)),
+    ) -> Option<Self> {
+        if C::SHARED {
+            return None;
+        }
+        let mut component = None;
+        take(comp_factory.get_component_id::<C>()?, &mut |raw_comp| {
+            // SAFETY: The pointer points to a stored `C`, which isn't shared.
+            component = Some(take_component::<C>(raw_comp))
+        });
+        component
+    }
+}
+
+macro_rules! impl_from_components_for_tuple {
+    ($($name:ident),*) => {
+        impl<$($name: FromComponents),*> FromComponents for ($($name,)*) {
+            #[allow(unused)]
+            unsafe fn from_raw_components(
+                comp_factory: &ComponentFactory,
+                take: &mut impl FnMut(ComponentId, &mut dyn FnMut(OwningPtr<'_>)),
+            ) -> Option<Self> {
+                Some(($($name::from_raw_components(comp_factory, take)?,)*))
+            }
+        }
+    };
+}
+
+all_tuples!(impl_from_components_for_tuple, 0, 12, B);
+
+/// Fail the compilation if a bundle type has more than `max` components (see [`Bundle::COMPONENT_COUNT`]), with
+/// the actual count in the error. The runtime limit is [`MAX_COMPS_PER_ARCH`](crate::archetype::MAX_COMPS_PER_ARCH)
+/// components per archetype, so a crate that adds components to a shared bundle can check that there's room left:
+///
+/// ```
+/// use worlds_ecs::{const_assert_bundle_size, prelude::*};
+///
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Mana(u32);
+/// #[derive(Component)]
+/// struct Speed(f32);
+///
+/// type PlayerBundle = ((Health, Mana), Speed);
+/// const_assert_bundle_size!(PlayerBundle, 3);
+/// ```
+///
+/// ```compile_fail
+/// # use worlds_ecs::{const_assert_bundle_size, prelude::*};
+/// # #[derive(Component)]
+/// # struct Health(u32);
+/// # #[derive(Component)]
+/// # struct Mana(u32);
+/// # #[derive(Component)]
+/// # struct Speed(f32);
+/// // error: the bundle `((Health, Mana), Speed)` has 3 components, more than the maximum of 2
+/// const_assert_bundle_size!(((Health, Mana), Speed), 2);
+/// ```
+#[macro_export]
+macro_rules! const_assert_bundle_size {
+    ($bundle:ty, $max:expr $(,)?) => {
+        const _: () = {
+            let count = <$bundle as $crate::bundle::Bundle>::COMPONENT_COUNT;
+            let max: usize = $max;
+            if count > max {
+                let message =
+                    $crate::bundle::BundleSizeMessage::new(stringify!($bundle), count, max);
+                panic!("{}", message.as_str());
+            }
+        };
+    };
+}
+
+/// The message of [`const_assert_bundle_size!`], written at compile time. Messages that don't fit are cut short.
+#[doc(hidden)]
+pub struct BundleSizeMessage {
+    bytes: [u8; 256],
+    len: usize,
+}
+
+impl BundleSizeMessage {
+    #[doc(hidden)]
+    pub const fn new(bundle: &str, count: usize, max: usize) -> BundleSizeMessage {
+        let mut message = BundleSizeMessage {
+            bytes: [0; 256],
+            len: 0,
+        };
+        message.push_str("the bundle `");
+        message.push_str(bundle);
+        message.push_str("` has ");
+        message.push_number(count);
+        message.push_str(" components, more than the maximum of ");
+        message.push_number(max);
+        message
+    }
+
+    const fn push_str(&mut self, s: &str) {
+        let bytes = s.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() && self.len < self.bytes.len() {
+            self.bytes[self.len] = bytes[i];
+            self.len += 1;
+            i += 1;
+        }
+    }
+
+    const fn push_number(&mut self, n: usize) {
+        let mut digits = 1;
+        while n / digits >= 10 {
+            digits *= 10;
+        }
+        while digits > 0 && self.len < self.bytes.len() {
+            self.bytes[self.len] = b'0' + (n / digits % 10) as u8;
+            self.len += 1;
+            digits /= 10;
+        }
+    }
+
+    /// The message, or a shorter one if it was cut in the middle of a character.
+    #[doc(hidden)]
+    pub const fn as_str(&self) -> &str {
+        match std::str::from_utf8(self.bytes.split_at(self.len).0) {
+            Ok(message) => message,
+            Err(_) => "the bundle has too many components",
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::prelude::*;
+    use super::BundleSizeMessage;
+    use crate::{prelude::*, test_utils::Fx};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
     use worlds_derive::Component;
 
     #[derive(Component)]
@@ -72,7 +235,7 @@ mod tests {
                 .unwrap()
         };
 
-        let mut storage = vec![blob_vec_a, blob_vec_b];
+        let mut storage = [blob_vec_a, blob_vec_b];
         <(A, B) as Bundle>::raw_components_scope(
             (A(33), B(-11, -99, [-456; 20])),
             &comp_factory,
@@ -114,4 +277,86 @@ mod tests {
             [-4; 20]
         );
     }
+
+    type Left = (
+        Fx<0>,
+        Fx<1>,
+        Fx<2>,
+        Fx<3>,
+        Fx<4>,
+        Fx<5>,
+        Fx<6>,
+        Fx<7>,
+        Fx<8>,
+        Fx<9>,
+    );
+    type Middle = (
+        Fx<10>,
+        Fx<11>,
+        Fx<12>,
+        Fx<13>,
+        Fx<14>,
+        Fx<15>,
+        Fx<16>,
+        Fx<17>,
+        Fx<18>,
+        Fx<19>,
+    );
+    type Right = (
+        Fx<20>,
+        Fx<21>,
+        Fx<22>,
+        Fx<23>,
+        Fx<24>,
+        Fx<25>,
+        Fx<26>,
+        Fx<27>,
+        Fx<28>,
+        Fx<29>,
+        Fx<30>,
+    );
+
+    const_assert_bundle_size!((A, (B, A)), 3);
+    const_assert_bundle_size!((Left, Middle, Right), 31);
+
+    #[test]
+    fn test_component_count() {
+        assert_eq!(<A as Bundle>::COMPONENT_COUNT, 1);
+        assert_eq!(<() as Bundle>::COMPONENT_COUNT, 0);
+        assert_eq!(<(A, B) as Bundle>::COMPONENT_COUNT, 2);
+        assert_eq!(
+            <((A, B), (Fx<0>, (Fx<1>, Fx<2>)), ()) as Bundle>::COMPONENT_COUNT,
+            5
+        );
+        assert_eq!(<(Left, Middle, Right) as Bundle>::COMPONENT_COUNT, 31);
+        assert_eq!(
+            BundleSizeMessage::new("(A, B)", 31, 30).as_str(),
+            "the bundle `(A, B)` has 31 components, more than the maximum of 30"
+        );
+    }
+
+    #[test]
+    fn test_too_many_components() {
+        let mut world = World::default();
+        let bundle = (Left::default(), Middle::default(), Right::default());
+        let error = catch_unwind(AssertUnwindSafe(|| world.spawn(bundle)))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert!(error.contains("the archetype has 31 components, more than the maximum of 30"));
+        #[cfg(feature = "bundle-provenance")]
+        {
+            // The components are grouped by the sub-bundle that contributed them.
+            let groups = error.split("components=").nth(1).unwrap();
+            assert_eq!(groups.matches("]; ").count(), 2);
+            let right = groups.split("; ").nth(2).unwrap();
+            assert!(right.starts_with(std::any::type_name::<Right>()));
+            assert_eq!(right.matches("Fx<").count(), 11 + 11);
+        }
+        assert_eq!(world.storages.arch_storages.storage_count(), 0);
+        let error = catch_unwind(AssertUnwindSafe(|| {
+            world.spawn_batch([(Left::default(), Middle::default(), Right::default())])
+        }));
+        assert!(error.is_err());
+    }
 }
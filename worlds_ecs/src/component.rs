@@ -1,16 +1,252 @@
-use crate::prelude::storage::blob_vec::BlobVec;
+use crate::prelude::storage::{
+    alloc::{AllocTracker, StorageAlloc, StorageAllocHandle},
+    blob_vec::BlobVec,
+};
 use crate::{
+    archetype::key::{PrimeArchKey, MAX_COMPONENTS},
     impl_id_struct,
     utils::{
-        prime_key::{PrimeArchKey, MAX_COMPONENTS},
+        panics,
+        paranoid::{self, Violation},
         TypeIdMap,
     },
-    world::data::{Data, DataInfo},
+    world::{
+        archive::Archivable,
+        data::{Data, DataInfo, StableComponentKey},
+        drop_order::DropOrder,
+        rules::ComponentRules,
+        shared::{SharedHandle, SharedTables},
+    },
 };
+use bevy_ptr::{OwningPtr, Ptr, PtrMut};
 use std::any::TypeId;
 
 /// The trait that represents a component.
-pub trait Component: Data {}
+///
+/// Components must be `Send` and `Sync`. When a field of a derived component is one of the standard types that
+/// aren't, like an `Rc`, the derive fails with an error that names the field:
+///
+/// ```compile_fail
+/// # use worlds_ecs::prelude::*;
+/// # use std::rc::Rc;
+/// // error: the field `vertices` of the component `Mesh` is a `Rc`, which isn't `Send` and `Sync`, but components
+/// // must be: use `Arc` instead
+/// #[derive(Component)]
+/// struct Mesh {
+///     vertices: Rc<[f32]>,
+/// }
+/// ```
+///
+/// The cells are fine inside a `Mutex`, and so is a raw pointer in the signature of a function pointer, since those
+/// are `Send` and `Sync`:
+///
+/// ```
+/// # use worlds_ecs::prelude::*;
+/// use std::cell::{Cell, RefCell};
+/// use std::sync::Mutex;
+///
+/// #[derive(Component)]
+/// struct Hits(Mutex<Cell<u32>>);
+///
+/// #[derive(Component)]
+/// struct Scratch(Mutex<RefCell<Vec<u32>>>);
+///
+/// #[derive(Component)]
+/// struct Callback(fn(*const u8) -> i32);
+///
+/// fn first_byte(bytes: *const u8) -> i32 {
+///     unsafe { *bytes as i32 }
+/// }
+///
+/// let mut world = World::default();
+/// let entity = world.spawn((
+///     Hits(Mutex::new(Cell::new(0))),
+///     Scratch(Mutex::new(RefCell::new(Vec::new()))),
+///     Callback(first_byte),
+/// ));
+/// world.get_component::<Hits>(entity).unwrap().0.lock().unwrap().set(3);
+/// world.get_component::<Scratch>(entity).unwrap().0.lock().unwrap().borrow_mut().push(3);
+/// assert_eq!(world.get_component::<Hits>(entity).unwrap().0.lock().unwrap().get(), 3);
+/// assert_eq!((world.get_component::<Callback>(entity).unwrap().0)([7].as_ptr()), 7);
+/// ```
+pub trait Component: Data {
+    /// Whether the component is stored behind a [`Box`], so its storage only holds a pointer to it. This is for large
+    /// components (see [`EcsWarning::LargeComponent`](crate::world::warnings::EcsWarning::LargeComponent)): removing
+    /// an entity from a storage (when it's despawned or archived) moves the last row into the hole, and sorting a
+    /// storage moves its rows around, which only moves the pointer of a boxed component, whatever its size. Reading
+    /// a boxed component goes through the pointer. Every API still hands out `&C` and `&mut C`, except the ones that
+    /// hand out slices of a component (like
+    /// [`World::iter_component_grouped`](crate::world::World::iter_component_grouped)), which panic, because a boxed
+    /// component isn't stored contiguously.
+    ///
+    /// Set it with `#[component(boxed)]`:
+    ///
+    /// ```
+    /// # use worlds_ecs::prelude::*;
+    /// #[derive(Component)]
+    /// #[component(boxed)]
+    /// struct Heightmap([f32; 16 * 1024]);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Heightmap([0.0; 16 * 1024]));
+    /// world.get_component_mut::<Heightmap>(entity).unwrap().0[7] = 1.0;
+    /// assert_eq!(world.get_component::<Heightmap>(entity).unwrap().0[7], 1.0);
+    /// ```
+    const BOXED: bool = false;
+
+    /// Whether the values of the component are shared by the entities that have equal values, so each distinct
+    /// value is stored once, and the storages of the component only hold pointers to them. This is for heavy data
+    /// that many entities have the same value of, like a stat block, or the description of a mesh. The values are
+    /// deduplicated once the component is registered with [`World::register_shared`](crate::world::World::register_shared),
+    /// and a value is dropped when the last entity that has it is despawned.
+    ///
+    /// The values of a shared component are immutable: `&mut C` can't be queried, and the APIs that hand out
+    /// `&mut C` (like [`World::get_component_mut`](crate::world::World::get_component_mut)) panic. The value of an
+    /// entity is replaced with [`World::replace_shared`](crate::world::World::replace_shared) instead. Like boxed
+    /// components (see [`Component::BOXED`]), the APIs that hand out slices of the component panic, and shared
+    /// components can't have default values or be archived.
+    ///
+    /// Set it with `#[component(shared)]`:
+    ///
+    /// ```
+    /// # use worlds_ecs::prelude::*;
+    /// #[derive(Component, PartialEq, Eq, Hash)]
+    /// #[component(shared)]
+    /// struct StatBlock([u32; 64]);
+    ///
+    /// let mut world = World::default();
+    /// world.register_shared::<StatBlock>();
+    /// let entities: Vec<_> = (0..100).map(|_| world.spawn(StatBlock([10; 64]))).collect();
+    /// assert_eq!(world.shared_values::<StatBlock>(), 1);
+    /// world.replace_shared(entities[0], StatBlock([20; 64]));
+    /// assert_eq!(world.get_component::<StatBlock>(entities[0]).unwrap().0[0], 20);
+    /// assert_eq!(world.shared_values::<StatBlock>(), 2);
+    /// ```
+    const SHARED: bool = false;
+
+    /// The [`DataInfo`] that the component is registered with. By default it's [`DataInfo::for_component`]; the
+    /// derive overrides it to drop the stored values with a custom function (see [`StorageDrop`]).
+    fn data_info() -> DataInfo
+    where
+        Self: Sized,
+    {
+        component_info::<Self>()
+    }
+}
+
+/// A custom drop of the stored values of a component, which is called whenever the world drops one of them (for
+/// example when the entity is despawned, or when the world is dropped), instead of dropping it in place. The value is
+/// handed over by value, so the function can recycle it, or release it to an external system.
+///
+/// Implement it with `#[component(storage_drop = "path::to::fn")]`, where the function takes the component by value:
+///
+/// ```
+/// # use worlds_ecs::prelude::*;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static RELEASED: AtomicUsize = AtomicUsize::new(0);
+///
+/// #[derive(Component)]
+/// #[component(storage_drop = "release_texture")]
+/// struct Texture(u32);
+///
+/// fn release_texture(texture: Texture) {
+///     RELEASED.fetch_add(texture.0 as usize, Ordering::Relaxed);
+/// }
+///
+/// let mut world = World::default();
+/// let entity = world.spawn(Texture(3));
+/// world.despawn(entity);
+/// assert_eq!(RELEASED.load(Ordering::Relaxed), 3);
+/// ```
+///
+/// Shared components (see [`Component::SHARED`]) can't have a custom drop, since their values are only dropped when
+/// the last entity that shares them is despawned.
+pub trait StorageDrop: Component + Sized {
+    /// Drop the value of the component.
+    fn storage_drop(self);
+}
+
+/// The [`DataInfo`] of the component `C`, which describes a `Box<C>` if `C` is boxed (see [`Component::BOXED`]), or a
+/// handle to a shared value if `C` is shared (see [`Component::SHARED`]).
+pub(crate) fn component_info<C: Component>() -> DataInfo {
+    match (C::SHARED, C::BOXED) {
+        (true, _) => DataInfo::shared_for::<C>(),
+        (false, true) => DataInfo::boxed_for::<C>(),
+        (false, false) => DataInfo::default_for::<C>(),
+    }
+}
+
+/// Call `f` with an [`OwningPtr`] to the value of a component, in the form it's stored in (boxed if `C` is boxed, see
+/// [`Component::BOXED`], or interned if `C` is shared, see [`Component::SHARED`]).
+#[inline]
+pub(crate) fn make_component<C: Component, R>(
+    value: C,
+    comp_factory: &ComponentFactory,
+    f: impl FnOnce(OwningPtr<'_>) -> R,
+) -> R {
+    match (C::SHARED, C::BOXED) {
+        (true, _) => OwningPtr::make(comp_factory.shared.intern(value), f),
+        (false, true) => OwningPtr::make(Box::new(value), f),
+        (false, false) => OwningPtr::make(value, f),
+    }
+}
+
+/// Dereference a pointer to a stored component, through its box if `C` is boxed (see [`Component::BOXED`]), or
+/// through its handle if `C` is shared (see [`Component::SHARED`]).
+///
+/// # Safety
+/// The pointer must point to a stored `C`.
+#[inline]
+pub(crate) unsafe fn deref_component<C: Component>(ptr: Ptr<'_>) -> &C {
+    match (C::SHARED, C::BOXED) {
+        (true, _) => ptr.deref::<SharedHandle<C>>().value(),
+        (false, true) => ptr.deref::<Box<C>>(),
+        (false, false) => ptr.deref::<C>(),
+    }
+}
+
+/// Move a stored component out of its pointer, and out of its box if `C` is boxed (see [`Component::BOXED`]).
+///
+/// # Safety
+/// The pointer must point to a stored `C`, and `C` can't be shared (see [`Component::SHARED`]).
+#[inline]
+pub(crate) unsafe fn take_component<C: Component>(ptr: OwningPtr<'_>) -> C {
+    match C::BOXED {
+        true => *ptr.read::<Box<C>>(),
+        false => ptr.read::<C>(),
+    }
+}
+
+/// Like [`deref_component`], mutably.
+///
+/// # Panics
+/// If `C` is shared (see [`Component::SHARED`]), because its values are immutable.
+///
+/// # Safety
+/// The pointer must point to a stored `C`.
+#[inline]
+#[track_caller]
+pub(crate) unsafe fn deref_component_mut<C: Component>(ptr: PtrMut<'_>) -> &mut C {
+    reject_shared_mut::<C>("mutable access");
+    match C::BOXED {
+        true => ptr.deref_mut::<Box<C>>(),
+        false => ptr.deref_mut::<C>(),
+    }
+}
+
+/// Panic if `C` is shared (see [`Component::SHARED`]), because its values can't be accessed mutably.
+#[inline]
+#[track_caller]
+pub(crate) fn reject_shared_mut<C: Component>(operation: &str) {
+    if C::SHARED {
+        panics::fail_component(
+            operation,
+            "shared components are immutable, replace their values with World::replace_shared",
+            std::any::type_name::<C>(),
+        )
+    }
+}
 
 /// A unique identifer for a [`Component`] in the [`World`](crate::world::World)
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -25,26 +261,334 @@ impl ComponentId {
 }
 
 /// A data structure to keep track of all the components in the world, and their information.
-// TODO: Better docs
-#[derive(Default)]
+///
+/// Each registered component gets a [`ComponentId`], which indexes its [`DataInfo`] (its layout, drop function, and
+/// the optional capabilities it was registered with, like being comparable or cloneable). The id of a component is
+/// only meaningful in the factory that registered it: two factories can give the same type different ids, so
+/// ids shouldn't be shared between worlds (see [`World::config_fingerprint`](crate::world::World::config_fingerprint)).
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Health(u32);
+///
+/// #[derive(Component, Clone)]
+/// struct Name(String);
+///
+/// let mut components = ComponentFactory::default();
+/// let health = components.register_component::<Health>().unwrap();
+/// let name = components.register_cloneable_component::<Name>().unwrap();
+/// assert_ne!(health, name);
+/// assert_eq!(components.get_component_id::<Health>(), Some(health));
+/// assert!(components.get_component_info_from_component_id(name).unwrap().clone_fn().is_some());
+/// ```
+#[derive(Clone)]
 pub struct ComponentFactory {
     /// Map the [`TypeId`] of each [`Component`] to its [`ComponentId`]
     type_map: TypeIdMap<ComponentId>,
     /// The [`DataInfo`] for each component, indexed by [`ComponentId`]
     components: Vec<DataInfo>,
+    /// Bumped every time the association between types and components changes.
+    registration_epoch: u64,
+    /// The allocator of the storages of the components. It can't be changed after the factory is created.
+    storage_alloc: StorageAllocHandle,
+    /// The rules that the components declare about each other, see [`ComponentRules`].
+    pub(crate) rules: ComponentRules,
+    /// The order that the components of an entity are dropped in, see [`World::drop_order`](crate::world::World::drop_order).
+    pub(crate) drop_order: DropOrder,
+    /// The interned values of the shared components, see [`Component::SHARED`].
+    pub(crate) shared: SharedTables,
+    /// The components that were registered through a shared reference, and weren't applied yet, see
+    /// [`Self::register_component_concurrent`].
+    #[cfg(feature = "concurrent-registration")]
+    pending: PendingRegistrations,
+}
+
+/// The queue of [`ComponentFactory::register_component_concurrent`]. The ids of the queued components follow the
+/// ids of the registered ones.
+#[cfg(feature = "concurrent-registration")]
+#[derive(Default)]
+struct PendingRegistrations(std::sync::Mutex<PendingLog>);
+
+#[cfg(feature = "concurrent-registration")]
+#[derive(Default, Clone)]
+struct PendingLog {
+    type_map: TypeIdMap<ComponentId>,
+    components: Vec<DataInfo>,
+}
+
+#[cfg(feature = "concurrent-registration")]
+impl Clone for PendingRegistrations {
+    fn clone(&self) -> Self {
+        PendingRegistrations(std::sync::Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
+impl Default for ComponentFactory {
+    /// An empty factory, or with the `auto-register` feature, one with every auto-registered component (see
+    /// `ComponentFactory::register_auto_registered`).
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut factory = ComponentFactory {
+            type_map: Default::default(),
+            components: Vec::new(),
+            registration_epoch: 0,
+            storage_alloc: Default::default(),
+            rules: Default::default(),
+            drop_order: Default::default(),
+            shared: Default::default(),
+            #[cfg(feature = "concurrent-registration")]
+            pending: Default::default(),
+        };
+        #[cfg(feature = "auto-register")]
+        factory.register_auto_registered();
+        factory
+    }
 }
 
 impl ComponentFactory {
+    /// Create a new [`ComponentFactory`] whose component storages allocate their memory with `alloc`
+    /// (instead of the global allocator).
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::storage::alloc::GlobalStorageAlloc;
+    ///
+    /// let components = ComponentFactory::with_allocator(Arc::new(GlobalStorageAlloc));
+    /// assert!(!components.storage_alloc().is_global());
+    /// ```
+    pub fn with_allocator(alloc: std::sync::Arc<dyn StorageAlloc>) -> Self {
+        ComponentFactory {
+            storage_alloc: StorageAllocHandle::new(alloc),
+            ..Default::default()
+        }
+    }
+
+    /// Record the allocations of the storages that are created from now on in `tracker` (see
+    /// [`World::track_allocations`](crate::world::World::track_allocations)).
+    pub(crate) fn track_allocations(&mut self, tracker: std::sync::Arc<AllocTracker>) {
+        self.storage_alloc.track(tracker);
+    }
+
+    /// The allocator of the storages of the components, see [`Self::with_allocator`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let components = ComponentFactory::default();
+    /// assert!(components.storage_alloc().is_global());
+    /// ```
+    pub fn storage_alloc(&self) -> &StorageAllocHandle {
+        &self.storage_alloc
+    }
+
     /// Register a new component from a generic type.
     /// If this component is already registered, this method will return
     /// the [`ComponentId`] of the previously registered component.
     /// If the component couldn't be registered for some reason, return `None`
     /// (the reason is most likely that the maximum amount of registered components has been reached.)
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component::<Health>().unwrap();
+    /// // Registering again returns the same id.
+    /// assert_eq!(components.register_component::<Health>(), Some(health));
+    /// assert_eq!(components.get_component_id::<Health>(), Some(health));
+    /// ```
     pub fn register_component<C: Component>(&mut self) -> Option<ComponentId> {
         // SAFETY: the `DataInfo` provided indeed matches the type.
-        unsafe {
-            self.register_component_from_data(TypeId::of::<C>(), DataInfo::deafult_for::<C>())
+        unsafe { self.register_component_from_data(TypeId::of::<C>(), C::data_info()) }
+    }
+
+    /// Register a new component that can be compared for equality (for example by [`WorldDiff`](crate::diff::WorldDiff)).
+    /// If this component is already registered, it becomes comparable, and this method will return
+    /// the [`ComponentId`] of the previously registered component.
+    /// If the component couldn't be registered for some reason, return `None`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, PartialEq)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component::<Health>().unwrap();
+    /// assert!(components.get_component_info::<Health>().unwrap().eq_fn().is_none());
+    /// assert_eq!(components.register_comparable_component::<Health>(), Some(health));
+    /// assert!(components.get_component_info::<Health>().unwrap().eq_fn().is_some());
+    /// ```
+    pub fn register_comparable_component<C: Component + PartialEq>(
+        &mut self,
+    ) -> Option<ComponentId> {
+        if let Some(comp_id) = self.get_component_id::<C>() {
+            self.components[comp_id.id()].set_comparable::<C>();
+            return Some(comp_id);
         }
+        let mut data_info = C::data_info();
+        data_info.set_comparable::<C>();
+        // SAFETY: the `DataInfo` provided indeed matches the type.
+        unsafe { self.register_component_from_data(TypeId::of::<C>(), data_info) }
+    }
+
+    /// Register a new component that can be hashed with its [`Hash`](std::hash::Hash) implementation (for example
+    /// by [`World::checksum`](crate::world::World::checksum)).
+    /// If this component is already registered, it becomes hashable, and this method will return
+    /// the [`ComponentId`] of the previously registered component.
+    /// If the component couldn't be registered for some reason, return `None`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Hash)]
+    /// struct Name(String);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_hashable_component::<Name>().unwrap();
+    /// assert!(components.get_component_info::<Name>().unwrap().hash_fn().is_some());
+    /// ```
+    pub fn register_hashable_component<C: Component + std::hash::Hash>(
+        &mut self,
+    ) -> Option<ComponentId> {
+        let comp_id = self.register_component::<C>()?;
+        self.components[comp_id.id()].set_hashable::<C>();
+        Some(comp_id)
+    }
+
+    /// Register a new component that can be cloned (for example by [`World::clone_cow`](crate::world::World::clone_cow)).
+    /// If this component is already registered, it becomes cloneable, and this method will return
+    /// the [`ComponentId`] of the previously registered component.
+    /// If the component couldn't be registered for some reason, return `None`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone)]
+    /// struct Name(String);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_cloneable_component::<Name>().unwrap();
+    /// assert!(components.get_component_info::<Name>().unwrap().clone_fn().is_some());
+    /// ```
+    pub fn register_cloneable_component<C: Component + Clone>(&mut self) -> Option<ComponentId> {
+        let comp_id = self.register_component::<C>()?;
+        self.components[comp_id.id()].set_cloneable::<C>();
+        Some(comp_id)
+    }
+
+    /// Register a new component that can be mutated through a shared reference, like a component with an atomic or
+    /// a `Mutex` (see [`World::clone_cow`](crate::world::World::clone_cow), which doesn't share its storages).
+    /// If this component is already registered, it becomes interior-mutable, and this method will return
+    /// the [`ComponentId`] of the previously registered component.
+    /// If the component couldn't be registered for some reason, return `None`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use std::sync::atomic::AtomicU32;
+    ///
+    /// #[derive(Component)]
+    /// struct Hits(AtomicU32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_interior_mutable_component::<Hits>().unwrap();
+    /// assert!(components.get_component_info::<Hits>().unwrap().is_interior_mutable());
+    /// ```
+    pub fn register_interior_mutable_component<C: Component>(&mut self) -> Option<ComponentId> {
+        let comp_id = self.register_component::<C>()?;
+        self.components[comp_id.id()].set_interior_mutable();
+        Some(comp_id)
+    }
+
+    /// Register a new component that has a default value (for example to be inserted automatically, see
+    /// [`World::require_component_with_default`](crate::world::World::require_component_with_default)).
+    /// If this component is already registered, it gets a default value, and this method will return
+    /// the [`ComponentId`] of the previously registered component.
+    /// If the component couldn't be registered for some reason, return `None`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Default)]
+    /// struct Velocity(f32, f32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component_with_default::<Velocity>().unwrap();
+    /// assert!(components.get_component_info::<Velocity>().unwrap().default_fn().is_some());
+    /// ```
+    pub fn register_component_with_default<C: Component + Default>(
+        &mut self,
+    ) -> Option<ComponentId> {
+        let comp_id = self.register_component::<C>()?;
+        self.components[comp_id.id()].set_default::<C>();
+        Some(comp_id)
+    }
+
+    /// Register a new component that can be archived with its [`Archivable`] implementation (see
+    /// [`World::archive`](crate::world::World::archive)).
+    /// If this component is already registered, it becomes archivable, and this method will return
+    /// the [`ComponentId`] of the previously registered component.
+    /// If the component couldn't be registered for some reason, return `None`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Name(String);
+    ///
+    /// impl Archivable for Name {
+    ///     fn archive(&self, bytes: &mut Vec<u8>) {
+    ///         bytes.extend_from_slice(self.0.as_bytes());
+    ///     }
+    ///
+    ///     fn restore(bytes: &[u8]) -> Option<Self> {
+    ///         String::from_utf8(bytes.to_vec()).ok().map(Name)
+    ///     }
+    /// }
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_archivable_component::<Name>().unwrap();
+    /// assert!(components.get_component_info::<Name>().unwrap().archive_fns().is_some());
+    /// ```
+    pub fn register_archivable_component<C: Component + Archivable>(
+        &mut self,
+    ) -> Option<ComponentId> {
+        let comp_id = self.register_component::<C>()?;
+        self.components[comp_id.id()].set_archivable::<C>();
+        Some(comp_id)
+    }
+
+    /// Register a new component that can be archived by copying its bytes, which is faster than archiving it with
+    /// an [`Archivable`] implementation. Otherwise like [`Self::register_archivable_component`]. Unless the component
+    /// is hashable already (see [`Self::register_hashable_component`]), it's hashed by its bytes too.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Position(i32, i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// // SAFETY: `Position` has no padding.
+    /// unsafe { components.register_pod_component::<Position>() }.unwrap();
+    /// let info = components.get_component_info::<Position>().unwrap();
+    /// assert!(info.is_pod());
+    /// // Pod components are hashed by their bytes.
+    /// assert!(info.hash_fn().is_some());
+    /// ```
+    ///
+    /// # Safety
+    /// Every byte of `C` must be initialized: it can't have padding bytes (like a `#[repr(C)]` struct of an `u8` and
+    /// an `u32`), or fields with padding bytes.
+    pub unsafe fn register_pod_component<C: Component + Copy>(&mut self) -> Option<ComponentId> {
+        let comp_id = self.register_component::<C>()?;
+        self.components[comp_id.id()].set_pod::<C>();
+        Some(comp_id)
     }
 
     /// Register a new component from raw data.
@@ -52,25 +596,64 @@ impl ComponentFactory {
     /// the [`ComponentId`] of the previously registered component.
     /// If the component couldn't be registered for some reason, return `None`
     /// (the reason is most likely that the maximum amount of registered components has been reached.)
+    /// Components that aren't described by a Rust type can be given a name with [`DataInfo::named`], which is
+    /// accepted here directly.
+    ///
+    /// ```
+    /// use std::any::TypeId;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// // SAFETY: The `DataInfo` is of `Health`.
+    /// let health = unsafe {
+    ///     components.register_component_from_data(TypeId::of::<Health>(), DataInfo::for_component::<Health>())
+    /// }
+    /// .unwrap();
+    /// assert_eq!(components.get_component_id::<Health>(), Some(health));
+    /// ```
     ///
     /// # Safety
     /// The caller must ensure that the [`DataInfo`] does indeed match the type that is represented by the [`TypeId`]
     pub unsafe fn register_component_from_data(
         &mut self,
         type_id: TypeId,
-        data_info: DataInfo,
+        data_info: impl Into<DataInfo>,
     ) -> Option<ComponentId> {
+        #[cfg(feature = "concurrent-registration")]
+        self.apply_pending_registrations();
         if self.is_type_registered(type_id) {
             return self.get_component_id_from_type_id(type_id);
         }
         (self.components.len() < MAX_COMPONENTS)
-            .then_some(self.register_component_from_data_unchecked(type_id, data_info))
+            .then(|| self.register_component_from_data_unchecked(type_id, data_info.into()))
     }
 
     /// Register a new component like [`Self::register_component_from_data`] without checking whether this
     /// component is already registered, and whether the [`maximum amount of components`](MAX_COMPONENTS) has been reached.
     /// This method is not unsafe, but using it without caution may result in difficult to find bugs and / or wasted memory.
     ///
+    /// ```
+    /// use std::any::TypeId;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// // SAFETY: The `DataInfo` is of `Health`.
+    /// let health = unsafe {
+    ///     components.register_component_from_data_unchecked(
+    ///         TypeId::of::<Health>(),
+    ///         DataInfo::for_component::<Health>(),
+    ///     )
+    /// };
+    /// assert_eq!(components.get_component_id::<Health>(), Some(health));
+    /// assert_eq!(components.component_count(), 1);
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that the [`DataInfo`] does indeed match the type that is represented by the [`TypeId`]
     pub unsafe fn register_component_from_data_unchecked(
@@ -78,31 +661,151 @@ impl ComponentFactory {
         type_id: TypeId,
         data_info: DataInfo,
     ) -> ComponentId {
+        #[cfg(feature = "concurrent-registration")]
+        self.apply_pending_registrations();
+        paranoid::check(
+            "ComponentFactory::register_component_from_data_unchecked",
+            || {
+                (self.components.len() < MAX_COMPONENTS)
+                    .then_some(())
+                    .ok_or(Violation::ComponentLimit)
+            },
+        );
         let comp_id = ComponentId::new(self.components.len());
         self.type_map.insert(type_id, comp_id);
         self.components.push(data_info);
+        self.registration_epoch += 1;
         comp_id
     }
 
     /// Register a new component like [`Self::register_component`] without checking whether this
     /// component is already registered, and whether the [`maximum amount of components`](MAX_COMPONENTS) has been reached.
     /// This method is not unsafe, but using it without caution may result in difficult to find bugs and / or wasted memory.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component_unchecked::<Health>();
+    /// assert_eq!(components.get_component_id::<Health>(), Some(health));
+    /// ```
     pub fn register_component_unchecked<C: Component>(&mut self) -> ComponentId {
         // SAFETY: the `DataInfo` provided indeed matches the type.
-        unsafe {
-            self.register_component_from_data_unchecked(
-                TypeId::of::<C>(),
-                DataInfo::deafult_for::<C>(),
-            )
+        unsafe { self.register_component_from_data_unchecked(TypeId::of::<C>(), C::data_info()) }
+    }
+
+    /// Register a component through a shared reference, for code that may run on several threads at once (like
+    /// plugins that are initialized on a thread pool). Registering the same type concurrently returns the same
+    /// [`ComponentId`] on every thread, and distinct types get distinct ids. Returns `None` if the maximum amount of
+    /// components has been reached.
+    ///
+    /// The id is reserved right away, but the component is only queued: lookups (like [`Self::get_component_id`])
+    /// don't see it until the queue is applied, by the next registration through `&mut self`, or by
+    /// [`Self::apply_pending_registrations`]. This keeps the lookups lock-free: the registered components only change
+    /// through `&mut self`, and only the queue is behind a lock.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let ids: Vec<ComponentId> = std::thread::scope(|scope| {
+    ///     let handles: Vec<_> = (0..4)
+    ///         .map(|_| scope.spawn(|| components.register_component_concurrent::<Health>().unwrap()))
+    ///         .collect();
+    ///     handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    /// });
+    /// assert!(ids.iter().all(|id| *id == ids[0]));
+    /// // The component is queued until the registrations are applied.
+    /// assert!(!components.is_registered::<Health>());
+    /// assert_eq!(components.apply_pending_registrations(), 1);
+    /// assert_eq!(components.get_component_id::<Health>(), Some(ids[0]));
+    /// ```
+    #[cfg(feature = "concurrent-registration")]
+    pub fn register_component_concurrent<C: Component>(&self) -> Option<ComponentId> {
+        let type_id = TypeId::of::<C>();
+        if let Some(comp_id) = self.get_component_id_from_type_id(type_id) {
+            return Some(comp_id);
+        }
+        let mut pending = self.pending.0.lock().unwrap();
+        if let Some(comp_id) = pending.type_map.get(&type_id) {
+            return Some(*comp_id);
+        }
+        let comp_id = ComponentId::new(self.components.len() + pending.components.len());
+        if comp_id.id() >= MAX_COMPONENTS {
+            return None;
+        }
+        pending.type_map.insert(type_id, comp_id);
+        pending.components.push(C::data_info());
+        Some(comp_id)
+    }
+
+    /// Register the components that were queued by [`Self::register_component_concurrent`], with the ids that were
+    /// reserved for them. Returns how many components were registered.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component_concurrent::<Health>().unwrap();
+    /// assert_eq!(components.get_component_id::<Health>(), None);
+    /// assert_eq!(components.apply_pending_registrations(), 1);
+    /// assert_eq!(components.get_component_id::<Health>(), Some(health));
+    /// assert_eq!(components.apply_pending_registrations(), 0);
+    /// ```
+    #[cfg(feature = "concurrent-registration")]
+    pub fn apply_pending_registrations(&mut self) -> usize {
+        let pending = std::mem::take(self.pending.0.get_mut().unwrap());
+        let applied = pending.components.len();
+        if applied > 0 {
+            self.type_map.extend(pending.type_map);
+            self.components.extend(pending.components);
+            self.registration_epoch += 1;
         }
+        applied
     }
 
     /// Get the [`DataInfo`] of a component
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// assert!(components.get_component_info::<Health>().is_none());
+    /// components.register_component::<Health>();
+    /// let info = components.get_component_info::<Health>().unwrap();
+    /// assert_eq!(info.name(), std::any::type_name::<Health>());
+    /// assert_eq!(info.layout(), std::alloc::Layout::new::<Health>());
+    /// ```
     pub fn get_component_info<C: Component>(&self) -> Option<&DataInfo> {
         self.get_component_info_from_type_id(TypeId::of::<C>())
     }
 
     /// Get the [`DataInfo`] of a component from its [`TypeId`]
+    ///
+    /// ```
+    /// use std::any::TypeId;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Health>();
+    /// let info = components.get_component_info_from_type_id(TypeId::of::<Health>()).unwrap();
+    /// assert_eq!(info.name(), std::any::type_name::<Health>());
+    /// ```
     pub fn get_component_info_from_type_id(&self, type_id: TypeId) -> Option<&DataInfo> {
         self.type_map.get(&type_id).map(|id| {
             self.get_component_info_from_component_id(*id)
@@ -111,40 +814,314 @@ impl ComponentFactory {
     }
 
     /// Get the [`DataInfo`] of a component from its [`ComponentId`]
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component::<Health>().unwrap();
+    /// let info = components.get_component_info_from_component_id(health).unwrap();
+    /// assert_eq!(info.name(), std::any::type_name::<Health>());
+    /// ```
     pub fn get_component_info_from_component_id(&self, comp_id: ComponentId) -> Option<&DataInfo> {
         self.components.get(comp_id.id())
     }
 
     /// Get the [`ComponentId`] of a component
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// assert_eq!(components.get_component_id::<Health>(), None);
+    /// let health = components.register_component::<Health>();
+    /// assert_eq!(components.get_component_id::<Health>(), health);
+    /// ```
     pub fn get_component_id<C: Component>(&self) -> Option<ComponentId> {
         self.get_component_id_from_type_id(TypeId::of::<C>())
     }
 
+    /// Get the [`ComponentId`] of a component from its name (its [`DataInfo::name`], the full path of its type).
+    /// This looks through every registered component, so it's meant for data-driven setup, not for hot paths.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component::<Health>();
+    /// let name = std::any::type_name::<Health>();
+    /// assert_eq!(components.get_component_id_by_name(name), health);
+    /// assert_eq!(components.get_component_id_by_name("Health"), None);
+    /// ```
+    pub fn get_component_id_by_name(&self, name: &str) -> Option<ComponentId> {
+        self.components
+            .iter()
+            .position(|data_info| data_info.name() == name)
+            .map(ComponentId::new)
+    }
+
     /// Get the [`ComponentId`] of a component from it's [`TypeId`]
+    ///
+    /// ```
+    /// use std::any::TypeId;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component::<Health>();
+    /// assert_eq!(components.get_component_id_from_type_id(TypeId::of::<Health>()), health);
+    /// assert_eq!(components.get_component_id_from_type_id(TypeId::of::<u32>()), None);
+    /// ```
     pub fn get_component_id_from_type_id(&self, type_id: TypeId) -> Option<ComponentId> {
         self.type_map.get(&type_id).copied()
     }
 
+    /// A counter that is bumped every time a component is registered, or a type is rebound to a component
+    /// (see [`Self::rebind_type`]). Anything that caches [`ComponentId`]s that were resolved from types
+    /// should resolve them again when this changes.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let epoch = components.registration_epoch();
+    /// components.register_component::<Health>();
+    /// assert!(components.registration_epoch() > epoch);
+    /// // Registering it again doesn't change anything.
+    /// let epoch = components.registration_epoch();
+    /// components.register_component::<Health>();
+    /// assert_eq!(components.registration_epoch(), epoch);
+    /// ```
+    pub fn registration_epoch(&self) -> u64 {
+        self.registration_epoch
+    }
+
+    /// Returns how many components are registered.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// #[derive(Component)]
+    /// struct Armor(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// assert_eq!(components.component_count(), 0);
+    /// components.register_component::<Health>();
+    /// components.register_component::<Armor>();
+    /// components.register_component::<Health>();
+    /// assert_eq!(components.component_count(), 2);
+    /// ```
+    pub fn component_count(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Iterate over the [`TypeId`] of every registered component, alongside its [`ComponentId`]
+    ///
+    /// ```
+    /// use std::any::TypeId;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component::<Health>().unwrap();
+    /// let registered: Vec<(TypeId, ComponentId)> = components.iter_type_ids().collect();
+    /// assert_eq!(registered, vec![(TypeId::of::<Health>(), health)]);
+    /// ```
+    pub fn iter_type_ids(&self) -> impl Iterator<Item = (TypeId, ComponentId)> + '_ {
+        self.type_map
+            .iter()
+            .map(|(type_id, comp_id)| (*type_id, *comp_id))
+    }
+
+    /// Get the [`StableComponentKey`] of a component, from its [`ComponentId`]
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component::<Health>().unwrap();
+    /// let key = components.stable_key(health).unwrap();
+    /// assert_eq!(key, &StableComponentKey::Name(std::any::type_name::<Health>().into()));
+    /// ```
+    pub fn stable_key(&self, comp_id: ComponentId) -> Option<&StableComponentKey> {
+        self.get_component_info_from_component_id(comp_id)
+            .map(DataInfo::stable_key)
+    }
+
+    /// Associate the component with the [`StableComponentKey`] `key` with a new [`TypeId`], and return its [`ComponentId`].
+    /// This is needed after the code that defines the component was reloaded (which changes its [`TypeId`]).
+    /// The previous [`TypeId`]s of the component are no longer associated with it.
+    /// Returns `None` if no component has this key.
+    ///
+    /// Note that the rest of the component's [`DataInfo`] (including its drop function) isn't changed, so the
+    /// code it points to must still be valid.
+    ///
+    /// ```
+    /// use std::any::TypeId;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// // The same component, after the code that defines it was reloaded.
+    /// #[derive(Component)]
+    /// struct ReloadedHealth(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component::<Health>().unwrap();
+    /// let key = components.stable_key(health).unwrap().clone();
+    /// assert_eq!(components.rebind_type(&key, TypeId::of::<ReloadedHealth>()), Some(health));
+    /// assert_eq!(components.get_component_id::<ReloadedHealth>(), Some(health));
+    /// assert_eq!(components.get_component_id::<Health>(), None);
+    /// ```
+    pub fn rebind_type(
+        &mut self,
+        key: &StableComponentKey,
+        new_type_id: TypeId,
+    ) -> Option<ComponentId> {
+        #[cfg(feature = "concurrent-registration")]
+        self.apply_pending_registrations();
+        let comp_id = ComponentId::new(
+            self.components
+                .iter()
+                .position(|data_info| data_info.stable_key() == key)?,
+        );
+        self.type_map.retain(|_, id| *id != comp_id);
+        self.type_map.insert(new_type_id, comp_id);
+        self.registration_epoch += 1;
+        Some(comp_id)
+    }
+
+    /// Rebuild the association between [`TypeId`]s and components. The `resolver` is called with the
+    /// [`DataInfo`] of each registered component, and returns its new [`TypeId`] (typically by matching
+    /// [`DataInfo::name`], which is stable across reloads of the same code).
+    /// Components for which the `resolver` returns `None` keep their current [`TypeId`].
+    /// See [`Self::rebind_type`].
+    ///
+    /// ```
+    /// use std::any::{type_name, TypeId};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// // The same component, after the code that defines it was reloaded.
+    /// #[derive(Component)]
+    /// struct ReloadedHealth(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component::<Health>().unwrap();
+    /// components.rebind_types(|info| {
+    ///     (info.name() == type_name::<Health>()).then(TypeId::of::<ReloadedHealth>)
+    /// });
+    /// assert_eq!(components.get_component_id::<ReloadedHealth>(), Some(health));
+    /// assert!(!components.is_registered::<Health>());
+    /// ```
+    pub fn rebind_types(&mut self, resolver: impl Fn(&DataInfo) -> Option<TypeId>) {
+        #[cfg(feature = "concurrent-registration")]
+        self.apply_pending_registrations();
+        let resolved: Vec<(ComponentId, TypeId)> = self
+            .components
+            .iter()
+            .enumerate()
+            .filter_map(|(id, data_info)| Some((ComponentId::new(id), resolver(data_info)?)))
+            .collect();
+        self.type_map
+            .retain(|_, comp_id| resolved.iter().all(|(id, _)| id != comp_id));
+        self.type_map.extend(
+            resolved
+                .into_iter()
+                .map(|(comp_id, type_id)| (type_id, comp_id)),
+        );
+        self.registration_epoch += 1;
+    }
+
     /// Returns `true` if the component is registered. `false` if not.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// assert!(!components.is_registered::<Health>());
+    /// components.register_component::<Health>();
+    /// assert!(components.is_registered::<Health>());
+    /// ```
     pub fn is_registered<C: Component>(&self) -> bool {
         self.type_map.contains_key(&TypeId::of::<C>())
     }
 
     /// Returns `true` if a component with this [`TypeId`] is registered. `false` if not.
+    ///
+    /// ```
+    /// use std::any::TypeId;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Health>();
+    /// assert!(components.is_type_registered(TypeId::of::<Health>()));
+    /// assert!(!components.is_type_registered(TypeId::of::<u32>()));
+    /// ```
     pub fn is_type_registered(&self, type_id: TypeId) -> bool {
         self.type_map.contains_key(&type_id)
     }
 
     /// Generate a type-erased data structure that can store values with the type of the component
     /// that's represented by the [`ComponentId`]
+    ///
+    /// ```
+    /// use bevy_ptr::OwningPtr;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let health = components.register_component::<Health>().unwrap();
+    /// // SAFETY: The `DataInfo` of `Health` was created from its type.
+    /// let mut storage = unsafe { components.new_component_storage(health) }.unwrap();
+    /// // SAFETY: The storage stores `Health`s.
+    /// OwningPtr::make(Health(10), |ptr| unsafe { storage.push(ptr) });
+    /// assert_eq!(storage.len(), 1);
+    /// assert_eq!(unsafe { storage.as_slice::<Health>() }[0].0, 10);
+    /// ```
+    ///
     /// # Safety
     ///
     /// The caller must ensure that the [`DataInfo`] that is stored for this component matces the actual
     /// memory layout of this component, and that `DataInfo::drop_fn()` is safe to call with an [`OwningPtr`]  to the component.
     pub unsafe fn new_component_storage(&self, comp_id: ComponentId) -> Option<BlobVec> {
-        Some(BlobVec::new_for_data(
+        Some(BlobVec::new_for_data_in(
             self.get_component_info_from_component_id(comp_id)?,
             1,
+            self.storage_alloc.clone(),
         ))
     }
 }
@@ -199,4 +1176,372 @@ mod tests {
             "worlds_ecs::component::tests::C"
         );
     }
+
+    #[test]
+    fn test_named_component() {
+        #[derive(Component)]
+        struct Health(#[allow(dead_code)] String);
+
+        let mut components = ComponentFactory::default();
+        let info = DataInfo::named("Health")
+            .layout(std::alloc::Layout::new::<Health>())
+            .drop(DataInfo::default_for::<Health>().drop_fn().unwrap());
+        // SAFETY: The layout and the drop function are of `Health`.
+        let id = unsafe { components.register_component_from_data(TypeId::of::<Health>(), info) }
+            .unwrap();
+        let info = components.get_component_info::<Health>().unwrap();
+        assert_eq!(info.name(), "Health");
+        assert_eq!(info.layout(), std::alloc::Layout::new::<Health>());
+        assert!(info.drop_fn().is_some());
+        assert_eq!(
+            components.get_component_id_from_type_id(TypeId::of::<Health>()),
+            Some(id)
+        );
+
+        // Unset parts of the builder default to zero-sized data that isn't dropped.
+        let info = DataInfo::named("Marker").build();
+        assert_eq!(info.name(), "Marker");
+        assert_eq!(info.layout(), std::alloc::Layout::new::<()>());
+        assert!(info.drop_fn().is_none());
+    }
+
+    #[cfg(feature = "concurrent-registration")]
+    #[derive(Component)]
+    struct Plugin<const N: usize>;
+
+    #[cfg(feature = "concurrent-registration")]
+    #[test]
+    fn test_concurrent_registration_queue() {
+        let mut components = ComponentFactory::default();
+        let a = components.register_component::<A>().unwrap();
+        assert_eq!(components.register_component_concurrent::<A>(), Some(a));
+        let b = components.register_component_concurrent::<B>().unwrap();
+        assert_eq!(components.register_component_concurrent::<B>(), Some(b));
+        // Queued, but not visible yet.
+        assert!(!components.is_registered::<B>());
+        let epoch = components.registration_epoch();
+        // Registering through `&mut self` applies the queue first, so the reserved id isn't taken.
+        let c = components.register_component::<C>().unwrap();
+        assert_eq!((a.id(), b.id(), c.id()), (0, 1, 2));
+        assert_eq!(components.get_component_id::<B>(), Some(b));
+        assert!(components.registration_epoch() > epoch);
+        assert_eq!(components.apply_pending_registrations(), 0);
+    }
+
+    #[cfg(feature = "concurrent-registration")]
+    #[test]
+    fn test_concurrent_registration_stress() {
+        const PLUGINS: usize = 16;
+        const THREADS: usize = 8;
+        macro_rules! plugins {
+            ($($n:literal),*) => {
+                [$(ComponentFactory::register_component_concurrent::<Plugin<$n>>),*]
+            };
+        }
+        let register: [fn(&ComponentFactory) -> Option<ComponentId>; PLUGINS] =
+            plugins!(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15);
+        for _ in 0..20 {
+            let mut components = ComponentFactory::default();
+            let a = components.register_component::<A>().unwrap();
+            let seen: Vec<Vec<(usize, ComponentId)>> = std::thread::scope(|scope| {
+                let threads: Vec<_> = (0..THREADS)
+                    .map(|thread| {
+                        let components = &components;
+                        scope.spawn(move || {
+                            // Each thread registers half of the components, overlapping with the other threads, and
+                            // in the opposite order of its neighbours.
+                            let mut plugins: Vec<usize> = (0..PLUGINS / 2)
+                                .map(|i| (thread * 2 + i) % PLUGINS)
+                                .collect();
+                            if thread % 2 == 1 {
+                                plugins.reverse();
+                            }
+                            plugins
+                                .into_iter()
+                                .map(|plugin| {
+                                    let comp_id = register[plugin](components).unwrap();
+                                    // Reads of the registered components are stable while registering.
+                                    assert_eq!(components.get_component_id::<A>(), Some(a));
+                                    (plugin, comp_id)
+                                })
+                                .collect()
+                        })
+                    })
+                    .collect();
+                threads
+                    .into_iter()
+                    .map(|thread| thread.join().unwrap())
+                    .collect()
+            });
+            let mut ids = [None; PLUGINS];
+            for (plugin, comp_id) in seen.into_iter().flatten() {
+                // Every thread got the same id for the same component.
+                assert_eq!(*ids[plugin].get_or_insert(comp_id), comp_id);
+            }
+            // No registration was lost, and no id was given twice.
+            let mut ids: Vec<usize> = ids.iter().map(|comp_id| comp_id.unwrap().id()).collect();
+            assert_eq!(components.apply_pending_registrations(), PLUGINS);
+            assert_eq!(
+                components.get_component_id::<Plugin<7>>(),
+                Some(ComponentId::new(ids[7]))
+            );
+            assert_eq!(
+                components
+                    .get_component_info_from_component_id(ComponentId::new(ids[3]))
+                    .unwrap()
+                    .name(),
+                std::any::type_name::<Plugin<3>>()
+            );
+            ids.sort();
+            assert_eq!(ids, (1..=PLUGINS).collect::<Vec<_>>());
+            assert_eq!(components.component_count(), PLUGINS + 1);
+        }
+    }
+
+    /// A large component that is stored behind a box.
+    #[derive(Component, Clone, PartialEq, Debug)]
+    #[component(boxed)]
+    struct Terrain {
+        id: u32,
+        cells: [u32; 2048],
+    }
+
+    impl Terrain {
+        fn new(id: u32) -> Self {
+            Terrain {
+                id,
+                cells: [id; 2048],
+            }
+        }
+    }
+
+    impl crate::world::archive::Archivable for Terrain {
+        fn archive(&self, bytes: &mut Vec<u8>) {
+            bytes.extend_from_slice(&self.id.to_le_bytes());
+        }
+
+        fn restore(bytes: &[u8]) -> Option<Self> {
+            Some(Terrain::new(u32::from_le_bytes(bytes.try_into().ok()?)))
+        }
+    }
+
+    #[derive(Component, Clone, Copy, PartialEq, Debug)]
+    struct Chunk(u32);
+
+    fn terrain_world() -> (crate::world::World, Vec<crate::entity::EntityId>) {
+        let mut world = crate::world::World::default();
+        let entities = (0..6)
+            .map(|i| match i % 2 {
+                0 => world.spawn((Terrain::new(i), Chunk(i))),
+                _ => world.spawn(Terrain::new(i)),
+            })
+            .collect();
+        (world, entities)
+    }
+
+    #[test]
+    fn test_boxed_component_access() {
+        let (mut world, entities) = terrain_world();
+        let info = world.components.get_component_info::<Terrain>().unwrap();
+        assert!(info.is_boxed());
+        assert_eq!(info.layout(), std::alloc::Layout::new::<Box<Terrain>>());
+
+        assert_eq!(
+            world.get_component::<Terrain>(entities[3]),
+            Some(&Terrain::new(3))
+        );
+        world
+            .get_component_mut::<Terrain>(entities[3])
+            .unwrap()
+            .cells[5] = 100;
+        world
+            .entity_mut(entities[1])
+            .unwrap()
+            .get_mut::<Terrain>()
+            .unwrap()
+            .id = 10;
+        for (terrain, chunk) in world.query::<(&mut Terrain, Option<&Chunk>)>() {
+            terrain.cells[0] = chunk.map_or(0, |chunk| chunk.0 + 1);
+        }
+        let mut read: Vec<(u32, u32, u32)> = world
+            .query_shared::<&Terrain>()
+            .map(|terrain| (terrain.id, terrain.cells[0], terrain.cells[5]))
+            .collect();
+        read.sort();
+        assert_eq!(
+            read,
+            [
+                (0, 1, 0),
+                (2, 3, 2),
+                (3, 0, 100),
+                (4, 5, 4),
+                (5, 0, 5),
+                (10, 0, 1)
+            ]
+        );
+
+        assert_eq!(
+            world.update_component_all::<Terrain>(|terrain| terrain.cells[1] = 7),
+            6
+        );
+        for (_, terrain) in world.iter_component_mut::<Terrain>() {
+            terrain.cells[2] = terrain.id;
+        }
+        assert!(world
+            .iter_component::<Terrain>()
+            .all(|(_, terrain)| terrain.cells[1] == 7 && terrain.cells[2] == terrain.id));
+
+        let handle = world.handle::<Terrain>(entities[4]).unwrap();
+        assert!(world.apply_handle(handle, |terrain| terrain.id = 40));
+        world
+            .patch(entities[2])
+            .unwrap()
+            .update::<Terrain>(|terrain| terrain.id = 20);
+        assert_eq!(
+            world.set_component_if_changed(entities[0], Terrain::new(0)),
+            Some(true)
+        );
+
+        // Despawning moves the boxes of the last rows, without touching the other components.
+        world.despawn(entities[0]);
+        world.despawn(entities[3]);
+        let mut ids: Vec<u32> = world
+            .iter_component::<Terrain>()
+            .map(|(_, terrain)| terrain.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, [5, 10, 20, 40]);
+        assert_eq!(
+            world.get_component::<Terrain>(entities[4]).unwrap().cells[0],
+            5
+        );
+        assert_eq!(world.write_component_to_all(Terrain::new(9)), 4);
+        assert!(world
+            .iter_component::<Terrain>()
+            .all(|(_, terrain)| *terrain == Terrain::new(9)));
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_boxed_component_registrations() {
+        use crate::diff::WorldDiff;
+
+        let (mut world, entities) = terrain_world();
+        world.register_cloneable_component::<Terrain>();
+        world.register_cloneable_component::<Chunk>();
+        world.register_comparable_component::<Terrain>();
+        world.register_comparable_component::<Chunk>();
+        world.register_archivable_component::<Terrain>();
+
+        // The boxes are cloned when the copy is mutated, so the original keeps its values.
+        let mut copy = world.clone_cow().unwrap();
+        assert!(WorldDiff::between(&world, &copy).is_empty());
+        copy.get_component_mut::<Terrain>(entities[1])
+            .unwrap()
+            .cells[9] = 99;
+        assert_eq!(
+            world.get_component::<Terrain>(entities[1]),
+            Some(&Terrain::new(1))
+        );
+        assert_eq!(WorldDiff::between(&world, &copy).len(), 1);
+
+        world.archive(entities[1]).unwrap();
+        assert_eq!(world.get_component::<Terrain>(entities[1]), None);
+        world.restore(entities[1]).unwrap();
+        assert_eq!(
+            world.get_component::<Terrain>(entities[1]),
+            Some(&Terrain::new(1))
+        );
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_boxed_components_are_dropped_once() {
+        use crate::prelude::*;
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        #[derive(Component)]
+        #[component(boxed)]
+        struct Counted(Arc<AtomicUsize>, #[allow(dead_code)] [u8; 8192]);
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut world = World::default();
+        let entities: Vec<EntityId> = (0..10)
+            .map(|_| world.spawn(Counted(drops.clone(), [0; 8192])))
+            .collect();
+        for entity in &entities[..4] {
+            world.despawn(*entity);
+        }
+        assert_eq!(drops.load(Ordering::Relaxed), 4);
+        drop(world);
+        assert_eq!(drops.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn test_storage_drop() {
+        use crate::prelude::*;
+        use std::sync::Mutex;
+
+        static RELEASED: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+        fn release<T: Into<u32>>(value: T) {
+            RELEASED.lock().unwrap().push(value.into());
+        }
+
+        #[derive(Component)]
+        #[component(storage_drop = "release")]
+        struct Gpu(u32);
+
+        #[derive(Component)]
+        #[component(boxed, storage_drop = "release")]
+        struct BigGpu(u32, #[allow(dead_code)] [u8; 8192]);
+
+        impl From<Gpu> for u32 {
+            fn from(value: Gpu) -> u32 {
+                value.0
+            }
+        }
+
+        impl From<BigGpu> for u32 {
+            fn from(value: BigGpu) -> u32 {
+                value.0 + 100
+            }
+        }
+
+        let mut world = World::default();
+        let a = world.spawn(Gpu(1));
+        let b = world.spawn((Gpu(2), BigGpu(2, [0; 8192])));
+        world.spawn(BigGpu(3, [0; 8192]));
+        assert!(world
+            .components
+            .get_component_info::<BigGpu>()
+            .unwrap()
+            .is_boxed());
+
+        world.despawn(a);
+        assert_eq!(*RELEASED.lock().unwrap(), [1]);
+        // A removed value is handed out, not dropped by the world.
+        assert_eq!(world.remove_component::<Gpu>(b).unwrap().0, 2);
+        assert_eq!(*RELEASED.lock().unwrap(), [1]);
+        drop(world);
+        let mut released = RELEASED.lock().unwrap().clone();
+        released.sort();
+        assert_eq!(released, [1, 102, 103]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the component is boxed, so its values aren't stored contiguously")]
+    fn test_boxed_components_have_no_slices() {
+        let (mut world, _) = terrain_world();
+        world.iter_component_grouped::<Terrain>().for_each(drop);
+    }
 }
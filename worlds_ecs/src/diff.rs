@@ -0,0 +1,332 @@
+use crate::{
+    archetype::key::PrimeArchKey,
+    entity::EntityId,
+    world::{
+        storage::{arch_storage::ArchStorageIndex, ArchEntityStorage},
+        World,
+    },
+};
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+/// A difference in a single entity that is alive in both of the compared [`World`]s.
+/// Components and tags are identified by their names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityChange {
+    /// The component is only on the entity in the second world.
+    ComponentAdded(&'static str),
+    /// The component is only on the entity in the first world.
+    ComponentRemoved(&'static str),
+    /// The component is on the entity in both worlds, with different values.
+    ComponentChanged(&'static str),
+    /// The tag is only on the entity in the second world.
+    TagAdded(&'static str),
+    /// The tag is only on the entity in the first world.
+    TagRemoved(&'static str),
+}
+
+/// The differences between two [`World`]s. Entities are matched by their [`EntityId`] (including the generation),
+/// and components are matched by their type, so the worlds may register their components in a different order.
+///
+/// Component values can only be compared if the component was registered as comparable
+/// (see [`ComponentFactory::register_comparable_component`](crate::prelude::ComponentFactory::register_comparable_component)).
+/// Other components are only checked for presence, and are listed in [`WorldDiff::incomparable`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WorldDiff {
+    only_in_a: Vec<EntityId>,
+    only_in_b: Vec<EntityId>,
+    changes: Vec<(EntityId, EntityChange)>,
+    incomparable: Vec<&'static str>,
+}
+
+/// Where an entity's components are stored in a [`World`].
+type EntityLocations<'w> = HashMap<EntityId, (&'w ArchEntityStorage, ArchStorageIndex)>;
+
+fn entity_locations(world: &World) -> EntityLocations<'_> {
+    world
+        .storages
+        .arch_storages
+        .iter_storages_with_matching_archetype(PrimeArchKey::IDENTITY)
+        .flat_map(|storage| {
+            storage
+                .entities()
+                .iter()
+                .enumerate()
+                .map(move |(index, entity)| (*entity, (storage, ArchStorageIndex(index))))
+        })
+        .collect()
+}
+
+fn sorted_entities(locations: &EntityLocations<'_>) -> Vec<EntityId> {
+    let mut entities: Vec<EntityId> = locations.keys().copied().collect();
+    entities.sort_by_key(|entity| (entity.id(), entity.generation()));
+    entities
+}
+
+fn tag_names_of(world: &World, entity: EntityId) -> HashSet<&'static str> {
+    let tagf = world.storages.tag_storage.tag_factory();
+    world
+        .tag_tracker_of(entity)
+        .tagged_ids()
+        .filter_map(|id| tagf.tag_name(id))
+        .collect()
+}
+
+impl WorldDiff {
+    /// Compute the differences between the [`World`]s `a` and `b`.
+    pub fn between(a: &World, b: &World) -> WorldDiff {
+        let mut diff = WorldDiff::default();
+        let locations_a = entity_locations(a);
+        let locations_b = entity_locations(b);
+        let type_ids_a: HashMap<_, _> = a
+            .components
+            .iter_type_ids()
+            .map(|(type_id, comp_id)| (comp_id, type_id))
+            .collect();
+        let type_ids_b: HashMap<_, _> = b
+            .components
+            .iter_type_ids()
+            .map(|(type_id, comp_id)| (comp_id, type_id))
+            .collect();
+        let mut incomparable = HashSet::new();
+
+        for entity in sorted_entities(&locations_a) {
+            let Some((storage_b, index_b)) = locations_b.get(&entity) else {
+                diff.only_in_a.push(entity);
+                continue;
+            };
+            let (storage_a, index_a) = locations_a[&entity];
+
+            let mut comps_a: Vec<(TypeId, _)> = storage_a
+                .component_ids()
+                .map(|comp_id| (type_ids_a[&comp_id], comp_id))
+                .collect();
+            comps_a.sort_by_key(|(_, comp_id)| *comp_id);
+            let comps_b: HashMap<TypeId, _> = storage_b
+                .component_ids()
+                .map(|comp_id| (type_ids_b[&comp_id], comp_id))
+                .collect();
+
+            for (type_id, comp_id_a) in &comps_a {
+                let info = a
+                    .components
+                    .get_component_info_from_component_id(*comp_id_a)
+                    .expect("Stored components are registered");
+                let Some(comp_id_b) = comps_b.get(type_id) else {
+                    diff.changes
+                        .push((entity, EntityChange::ComponentRemoved(info.name())));
+                    continue;
+                };
+                let Some(eq_fn) = info.eq_fn() else {
+                    incomparable.insert(info.name());
+                    continue;
+                };
+                // SAFETY: Both components have the same type, and that is the type of the `eq_fn`.
+                let equal = unsafe {
+                    eq_fn(
+                        storage_a.get_component_unchecked(index_a, *comp_id_a),
+                        storage_b.get_component_unchecked(*index_b, *comp_id_b),
+                    )
+                };
+                if !equal {
+                    diff.changes
+                        .push((entity, EntityChange::ComponentChanged(info.name())));
+                }
+            }
+
+            let type_ids_on_a: HashSet<TypeId> =
+                comps_a.iter().map(|(type_id, _)| *type_id).collect();
+            let mut added: Vec<_> = comps_b
+                .iter()
+                .filter(|(type_id, _)| !type_ids_on_a.contains(type_id))
+                .map(|(_, comp_id_b)| *comp_id_b)
+                .collect();
+            added.sort();
+            for comp_id_b in added {
+                let info = b
+                    .components
+                    .get_component_info_from_component_id(comp_id_b)
+                    .expect("Stored components are registered");
+                diff.changes
+                    .push((entity, EntityChange::ComponentAdded(info.name())));
+            }
+
+            let tags_a = tag_names_of(a, entity);
+            let tags_b = tag_names_of(b, entity);
+            let mut removed_tags: Vec<_> = tags_a.difference(&tags_b).copied().collect();
+            removed_tags.sort();
+            let mut added_tags: Vec<_> = tags_b.difference(&tags_a).copied().collect();
+            added_tags.sort();
+            diff.changes.extend(
+                removed_tags
+                    .into_iter()
+                    .map(|name| (entity, EntityChange::TagRemoved(name))),
+            );
+            diff.changes.extend(
+                added_tags
+                    .into_iter()
+                    .map(|name| (entity, EntityChange::TagAdded(name))),
+            );
+        }
+
+        diff.only_in_b = sorted_entities(&locations_b)
+            .into_iter()
+            .filter(|entity| !locations_a.contains_key(entity))
+            .collect();
+        diff.incomparable = incomparable.into_iter().collect();
+        diff.incomparable.sort();
+        diff
+    }
+
+    /// Entities that are only alive in the first world.
+    pub fn only_in_a(&self) -> &[EntityId] {
+        &self.only_in_a
+    }
+
+    /// Entities that are only alive in the second world.
+    pub fn only_in_b(&self) -> &[EntityId] {
+        &self.only_in_b
+    }
+
+    /// The changes of the entities that are alive in both worlds.
+    pub fn changes(&self) -> &[(EntityId, EntityChange)] {
+        &self.changes
+    }
+
+    /// The names of the components that were on entities in both worlds, but couldn't be compared.
+    pub fn incomparable(&self) -> &[&'static str] {
+        &self.incomparable
+    }
+
+    /// The amount of differences. Incomparable components aren't counted.
+    pub fn len(&self) -> usize {
+        self.only_in_a.len() + self.only_in_b.len() + self.changes.len()
+    }
+
+    /// Returns `true` if no differences were found. Incomparable components aren't counted.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for WorldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} difference(s) between the worlds", self.len())?;
+        for entity in &self.only_in_a {
+            writeln!(f, "  - {entity:?} is only in the first world")?;
+        }
+        for entity in &self.only_in_b {
+            writeln!(f, "  + {entity:?} is only in the second world")?;
+        }
+        for (entity, change) in &self.changes {
+            match change {
+                EntityChange::ComponentAdded(name) => {
+                    writeln!(f, "  ~ {entity:?}: component `{name}` was added")?
+                }
+                EntityChange::ComponentRemoved(name) => {
+                    writeln!(f, "  ~ {entity:?}: component `{name}` was removed")?
+                }
+                EntityChange::ComponentChanged(name) => {
+                    writeln!(f, "  ~ {entity:?}: component `{name}` changed")?
+                }
+                EntityChange::TagAdded(name) => {
+                    writeln!(f, "  ~ {entity:?}: tag `{name}` was added")?
+                }
+                EntityChange::TagRemoved(name) => {
+                    writeln!(f, "  ~ {entity:?}: tag `{name}` was removed")?
+                }
+            }
+        }
+        if !self.incomparable.is_empty() {
+            writeln!(
+                f,
+                "  (couldn't compare the values of: {})",
+                self.incomparable.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Assert that two [`World`]s are equal, as computed by [`WorldDiff::between`].
+/// On failure, the panic message contains the differences.
+#[macro_export]
+macro_rules! assert_worlds_eq {
+    ($a:expr, $b:expr $(,)?) => {{
+        let diff = $crate::diff::WorldDiff::between(&$a, &$b);
+        if !diff.is_empty() {
+            panic!("assertion `worlds are equal` failed\n{}", diff);
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EntityChange, WorldDiff};
+    use crate::prelude::*;
+
+    #[derive(Component, PartialEq)]
+    struct Health(u32);
+
+    #[derive(Component)]
+    struct Name(#[allow(dead_code)] &'static str);
+
+    #[derive(Tag)]
+    struct Poisoned;
+
+    fn world() -> World {
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Poisoned>();
+        let mut world = World::with_tags(tagf);
+        world.register_comparable_component::<Health>();
+        world
+    }
+
+    #[test]
+    fn test_world_diff() {
+        let mut a = world();
+        let mut b = world();
+        for world in [&mut a, &mut b] {
+            world.spawn((Health(10), Name("Knight")));
+            world.spawn((Health(5), Name("Archer")));
+            world.spawn(Health(1));
+        }
+        assert_worlds_eq!(a, b);
+        assert_eq!(WorldDiff::between(&a, &b).incomparable().len(), 1);
+
+        let entities: Vec<EntityId> = a.iter_entities().collect();
+        a.get_component_mut::<Health>(entities[0]).unwrap().0 = 9;
+        b.despawn(entities[1]);
+        unsafe { b.get_tag_tracker(entities[2]).unwrap().tag::<Poisoned>() };
+
+        let diff = WorldDiff::between(&a, &b);
+        assert_eq!(diff.len(), 3);
+        assert_eq!(diff.only_in_a(), &[entities[1]]);
+        assert!(diff.only_in_b().is_empty());
+        // The changes are sorted by entity id.
+        let mut changes = vec![
+            (
+                entities[0],
+                EntityChange::ComponentChanged(std::any::type_name::<Health>()),
+            ),
+            (
+                entities[2],
+                EntityChange::TagAdded(std::any::type_name::<Poisoned>()),
+            ),
+        ];
+        changes.sort_by_key(|(entity, _)| entity.id());
+        assert_eq!(diff.changes(), changes);
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds are equal")]
+    fn test_assert_worlds_eq() {
+        let mut a = world();
+        let b = world();
+        a.spawn(Health(1));
+        assert_worlds_eq!(a, b);
+    }
+}
@@ -1,15 +1,21 @@
-use crate::world::storage::{arch_storage::ArchStorageIndex, storages::ArchStorageId};
-use std::collections::VecDeque;
+use crate::{
+    utils::{
+        component_mask::ComponentMask,
+        paranoid::{self, Violation},
+    },
+    world::storage::{arch_storage::ArchStorageIndex, storages::ArchStorageId},
+};
+use std::collections::{HashMap, VecDeque};
 
 /// A unique identifer for an entity in the in the [`World`](crate::world::World)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct EntityId {
     id: u32,
     gen: u32,
 }
 
 impl EntityId {
-    fn new(id: u32) -> EntityId {
+    pub(crate) fn new(id: u32) -> EntityId {
         EntityId { id, gen: 0 }
     }
 
@@ -28,44 +34,171 @@ impl EntityId {
         self.gen = gen;
         self
     }
+
+    /// The [`EntityId`] packed into a `u64`: the generation in the high 32 bits, and the id in the low 32 bits.
+    /// This is the order of [`World::iter_component_ordered`](crate::world::World::iter_component_ordered).
+    pub fn to_bits(&self) -> u64 {
+        ((self.gen as u64) << 32) | self.id as u64
+    }
+
+    /// Unpack an [`EntityId`] that was packed with [`Self::to_bits`].
+    pub fn from_bits(bits: u64) -> EntityId {
+        EntityId {
+            id: bits as u32,
+            gen: (bits >> 32) as u32,
+        }
+    }
+}
+
+/// The order in which the [`EntityFactory`] reuses the ids of removed entities.
+///
+/// The default is [`ReusePolicy::Fifo`]: it keeps a removed id out of circulation for as long as possible,
+/// which makes it less likely that a stale [`EntityId`] meets a revived entity with a wrapped-around generation.
+/// Under churn (see the "Entity churn" benchmark), there was no measurable difference between the policies,
+/// so the safer policy is the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReusePolicy {
+    /// Reuse the id that was removed the longest time ago.
+    #[default]
+    Fifo,
+    /// Reuse the id that was removed most recently. Its slot is most likely to still be in the cache.
+    Lifo,
+}
+
+/// A bijection between the dense indexes of the entities in the [`EntityFactory`], and the ids of the
+/// [`EntityId`]s it hands out (see [`World::salt_entity_ids`](crate::world::World::salt_entity_ids)).
+#[derive(Clone, Copy)]
+struct IdSalt {
+    multiplier: u32,
+    /// The inverse of [`Self::multiplier`], modulo 2^32.
+    inverse: u32,
+    key: u32,
+}
+
+impl IdSalt {
+    fn new(seed: u64) -> IdSalt {
+        // Splitmix64, so similar seeds produce unrelated permutations.
+        let mut bits = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        bits = (bits ^ (bits >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        bits = (bits ^ (bits >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        bits ^= bits >> 31;
+        let multiplier = bits as u32 | 1;
+        // Newton's iteration doubles the correct low bits of the inverse (starting from 3) each time.
+        let mut inverse = multiplier;
+        for _ in 0..4 {
+            inverse = inverse.wrapping_mul(2u32.wrapping_sub(multiplier.wrapping_mul(inverse)));
+        }
+        IdSalt {
+            multiplier,
+            inverse,
+            key: (bits >> 32) as u32,
+        }
+    }
+
+    fn salt(self, index: u32) -> u32 {
+        index.wrapping_mul(self.multiplier).rotate_left(16) ^ self.key
+    }
+
+    fn unsalt(self, id: u32) -> u32 {
+        (id ^ self.key).rotate_right(16).wrapping_mul(self.inverse)
+    }
 }
 
 /// A data structure to keep track of all the entities in the world, and their information.
-// TODO: Better docs
-#[derive(Default)]
+///
+/// The information of the entities is stored densely, at their dense index, which is their [`EntityId::id`]
+/// unless the ids are salted.
+#[derive(Clone)]
 pub struct EntityFactory {
-    /// Indexed by an [`EntityId::id`], this list keeps track of the current generation of each entity.
+    /// Indexed by the dense index of an entity, this list keeps track of the current generation of each entity.
     generations: Vec<u32>,
     /// Queued [`EntityId`]s are ids of entities that have been removed. If the queue is non-empty, the next
     /// entity that this [`EntityFactory`] will produce with have the same id as the [`EntityId`] in the head of this
     /// queue, with a greater generation. If the queue is empty, this [`EntityFactory`] will allocate a new entity with
     /// a new unique [`EntityId`].
     queued_entitys: VecDeque<EntityId>,
-    /// Meta-data of entities. Indexed by [`EntityId::id`].
+    /// Meta-data of entities. Indexed by the dense index of an entity.
     entity_metas: Vec<EntityMeta>,
     /// Number of registered entities, also the length of [`Self::entity_metas`] & [`Self::generations`].
     entities: u32,
+    /// The order in which ids from [`Self::queued_entitys`] are reused.
+    reuse_policy: ReusePolicy,
+    /// How many entities were ever produced.
+    produced: u64,
+    /// How many entities were ever removed.
+    removed: u64,
+    /// The salt of the ids that are handed out, if they are salted.
+    salt: Option<IdSalt>,
+}
+
+impl Default for EntityFactory {
+    fn default() -> Self {
+        EntityFactory {
+            generations: Vec::new(),
+            queued_entitys: VecDeque::new(),
+            entity_metas: Vec::new(),
+            entities: 0,
+            reuse_policy: ReusePolicy::default(),
+            produced: 0,
+            removed: 0,
+            // `cargo test --features salted-ids` runs the whole test suite with salted ids.
+            salt: cfg!(all(test, feature = "salted-ids")).then(|| IdSalt::new(0x5A17)),
+        }
+    }
 }
 
 impl EntityFactory {
+    /// The index of an entity in the dense tables of the world (like the entity metas, or the tag trackers). It's
+    /// the [`EntityId::id`] of the entity, unless the ids are salted (see [`Self::salt_ids`]).
+    #[inline(always)]
+    pub(crate) fn dense_index(&self, entity: EntityId) -> usize {
+        match self.salt {
+            Some(salt) => salt.unsalt(entity.id) as usize,
+            None => entity.id as usize,
+        }
+    }
+
+    /// The [`EntityId`] (with generation 0) of the entity at a dense index.
+    fn entity_at(&self, index: usize) -> EntityId {
+        match self.salt {
+            Some(salt) => EntityId::new(salt.salt(index as u32)),
+            None => EntityId::new(index as u32),
+        }
+    }
+
+    /// Hand out the ids of the entities in a pseudo-random permutation (determined by the `seed`) of their dense
+    /// indexes. Returns `false` (and does nothing) if an entity was already produced.
+    #[cfg_attr(not(feature = "salted-ids"), allow(dead_code))]
+    pub(crate) fn salt_ids(&mut self, seed: u64) -> bool {
+        if !self.generations.is_empty() {
+            return false;
+        }
+        self.salt = Some(IdSalt::new(seed));
+        true
+    }
+
     /// Allocate a new entity, and return its [`EntityId`]. Note this is different from [`Self::new_entity`]
     /// because this will always *allocate* a new entity, whereas [`Self::new_entity`] could also pull from
     /// the depspawned entity queue. Panics if the maximum amount of entities has been reached (2^32).
     fn alloc_new_entity(&mut self, entity_meta: EntityMeta) -> EntityId {
+        let entity = self.entity_at(self.generations.len());
         self.generations.push(0);
         self.entity_metas.push(entity_meta);
-
-        EntityId::new(self.entities - 1)
+        entity
     }
 
     /// Produce a new entity, and return its [`EntityId`]. Note this is different from [`Self::alloc_new_entity`]
     /// & [`Self::new_entity`] because this will only use the [`EntityId`] of an entity that was removed.
     /// Panics if the maximum amount of entities has been reached (2^32).
     fn revive_removed_entity(&mut self, entity_meta: EntityMeta) -> Option<EntityId> {
-        let id = self.queued_entitys.pop_front()?;
-        let entity = id.with_generation(self.generations[id.id() as usize]);
-        self.set_entity_meta(entity_meta, entity);
-        Some(entity)
+        let id = match self.reuse_policy {
+            ReusePolicy::Fifo => self.queued_entitys.pop_front()?,
+            ReusePolicy::Lifo => self.queued_entitys.pop_back()?,
+        };
+        let index = self.dense_index(id);
+        // The meta is written directly: the entity isn't alive until it's written.
+        self.entity_metas[index] = entity_meta;
+        Some(id.with_generation(self.generations[index]))
     }
 
     /// Produce a new entity, and return its [`EntityId`]. Note this is different from [`Self::alloc_new_entity`]
@@ -73,13 +206,36 @@ impl EntityFactory {
     /// will always allocate a new entity. Panics if the maximum amount of entities has been reached (2^32).
     pub fn new_entity(&mut self, entity_meta: EntityMeta) -> EntityId {
         self.entities += 1;
-        self.revive_removed_entity(entity_meta)
-            .unwrap_or(self.alloc_new_entity(entity_meta))
+        self.produced += 1;
+        match self.revive_removed_entity(entity_meta) {
+            Some(entity) => entity,
+            None => self.alloc_new_entity(entity_meta),
+        }
+    }
+
+    /// The [`EntityId`] that the next call to [`Self::new_entity`] will return.
+    pub fn next_entity_id(&self) -> EntityId {
+        let queued = match self.reuse_policy {
+            ReusePolicy::Fifo => self.queued_entitys.front(),
+            ReusePolicy::Lifo => self.queued_entitys.back(),
+        };
+        match queued {
+            Some(id) => id.with_generation(self.generations[self.dense_index(*id)]),
+            None => self.entity_at(self.generations.len()),
+        }
     }
 
-    /// Verify the generation of this entity, meaning, verify that it hasn't been removed.
+    /// Verify the generation of this entity, meaning, verify that it hasn't been removed. Returns `false` for an id
+    /// that was never produced, and for the id of a removed entity with the generation that it will be revived with
+    /// (see [`Self::next_entity_id`]), until it's revived. Never panics, even for a hand-constructed id whose index
+    /// is out of range.
     pub fn verify_generation(&self, entity: EntityId) -> bool {
-        self.generations[entity.id() as usize] == entity.gen
+        let index = self.dense_index(entity);
+        self.generations.get(index) == Some(&entity.gen)
+            && self
+                .entity_metas
+                .get(index)
+                .is_some_and(|entity_meta| !entity_meta.is_dead())
     }
 
     /// remove an entity. This will increment the generation matching this entity's [`id`](EntityId::id).
@@ -89,38 +245,116 @@ impl EntityFactory {
             self.verify_generation(entity),
             "Can't remove removed entity"
         );
-        self.generations[entity.id() as usize] += 1;
+        let index = self.dense_index(entity);
+        // `verify_generation` passed, so `index` is in bounds of both `generations` and `entity_metas`.
+        self.generations[index] += 1;
+        self.entity_metas[index] = EntityMeta::DEAD;
         self.entities -= 1;
+        self.removed += 1;
         self.queued_entitys.push_back(entity)
     }
 
+    /// Remove many entities at once, like [`Self::remove_entity`]. Panic if any of the entities doesn't exist.
+    pub fn remove_many(&mut self, entities: &[EntityId]) {
+        for entity in entities {
+            assert!(
+                self.verify_generation(*entity),
+                "Can't remove removed entity"
+            );
+            let index = self.dense_index(*entity);
+            // `verify_generation` passed, so `index` is in bounds of both `generations` and `entity_metas`.
+            self.generations[index] += 1;
+            self.entity_metas[index] = EntityMeta::DEAD;
+        }
+        self.entities -= entities.len() as u32;
+        self.removed += entities.len() as u64;
+        self.queued_entitys.extend(entities);
+    }
+
     /// The the [`EntityMeta`] of an entity, with generation-verification.
     pub fn get_entity_meta(&self, entity: EntityId) -> Option<&EntityMeta> {
         self.verify_generation(entity)
-            .then(|| &self.entity_metas[entity.id() as usize])
+            .then(|| &self.entity_metas[self.dense_index(entity)])
     }
 
     /// Set the [`EntityMeta`] of an entity.
     pub fn set_entity_meta(&mut self, entity_meta: EntityMeta, entity: EntityId) {
-        self.entity_metas[entity.id() as usize] = entity_meta
+        self.check_alive("EntityFactory::set_entity_meta", entity);
+        let index = self.dense_index(entity);
+        self.entity_metas[index] = entity_meta
     }
 
     /// Set the [`ArchStorageIndex`] of an entity's [`EntityMeta`].
     pub fn set_entity_arch_storage_index(&mut self, index: ArchStorageIndex, entity: EntityId) {
-        self.entity_metas[entity.id() as usize].archetype_storage_index = index
+        self.check_alive("EntityFactory::set_entity_arch_storage_index", entity);
+        let dense_index = self.dense_index(entity);
+        self.entity_metas[dense_index].archetype_storage_index = index
+    }
+
+    /// Check that the entity is alive before its [`EntityMeta`] is overwritten, so a stale [`EntityId`] doesn't
+    /// move the entity that reused its id (see [`paranoid::check`]).
+    #[inline(always)]
+    #[track_caller]
+    fn check_alive(&self, method: &str, entity: EntityId) {
+        paranoid::check(method, || {
+            self.verify_generation(entity)
+                .then_some(())
+                .ok_or(Violation::StaleEntity(entity))
+        });
+    }
+
+    /// Set the [`ReusePolicy`] of this [`EntityFactory`].
+    pub fn set_reuse_policy(&mut self, reuse_policy: ReusePolicy) {
+        self.reuse_policy = reuse_policy;
+    }
+
+    /// Get the [`ReusePolicy`] of this [`EntityFactory`].
+    pub fn reuse_policy(&self) -> ReusePolicy {
+        self.reuse_policy
     }
 
     /// Returns how many entities are there in the world.
     pub fn entities(&self) -> u32 {
         self.entities
     }
+
+    /// Returns how many entities were ever produced by [`Self::new_entity`].
+    pub fn produced(&self) -> u64 {
+        self.produced
+    }
+
+    /// Returns how many entities were ever removed by [`Self::remove_entity`] (or [`Self::remove_many`]).
+    pub fn removed(&self) -> u64 {
+        self.removed
+    }
+
+    /// The alive entities whose [`EntityMeta`] points to the storage with the [`ArchStorageId`] `sid`, whether or
+    /// not the storage agrees (see [`World::check_and_quarantine`](crate::world::World::check_and_quarantine)).
+    pub(crate) fn alive_in_storage(&self, sid: ArchStorageId) -> Vec<EntityId> {
+        (self.entity_metas.iter().enumerate())
+            .filter(|(_, entity_meta)| entity_meta.archetype_storage_id == sid)
+            .map(|(index, _)| {
+                self.entity_at(index)
+                    .with_generation(self.generations[index])
+            })
+            .collect()
+    }
 }
 
 /// Meta-data of an entity.
+///
+/// Besides the location of the entity, the meta caches the components of the entity's storage (as a bit for each
+/// component), so checking whether the entity has a component
+/// (see [`World::contains_component`](crate::world::World::contains_component)) is a single bit test, without looking
+/// at its storage. This makes the meta 3 times larger (48 bytes instead of 16, with the default maximum amount of
+/// components), which only matters for worlds with many millions of entities.
 #[derive(Clone, Copy)]
 pub struct EntityMeta {
     pub(crate) archetype_storage_id: ArchStorageId,
     pub(crate) archetype_storage_index: ArchStorageIndex,
+    /// The components of the storage with [`Self::archetype_storage_id`]. It must be updated whenever the entity
+    /// moves to another storage.
+    pub(crate) component_mask: ComponentMask,
 }
 
 impl EntityMeta {
@@ -128,7 +362,117 @@ impl EntityMeta {
     pub(crate) const PLACEHOLDER: EntityMeta = EntityMeta {
         archetype_storage_id: ArchStorageId(usize::MAX),
         archetype_storage_index: ArchStorageIndex(usize::MAX),
+        component_mask: ComponentMask::EMPTY,
+    };
+
+    /// The meta of an entity that was archived (see [`World::archive`](crate::world::World::archive)): it isn't in
+    /// any storage, and has no components until it's restored.
+    pub(crate) const ARCHIVED: EntityMeta = EntityMeta {
+        archetype_storage_id: ArchStorageId(usize::MAX - 1),
+        archetype_storage_index: ArchStorageIndex(usize::MAX),
+        component_mask: ComponentMask::EMPTY,
+    };
+
+    /// The meta of a removed entity, until its id is reused. The generation of the id was already incremented, so
+    /// without it, the id with the next generation would pass for a live entity before it's produced.
+    pub(crate) const DEAD: EntityMeta = EntityMeta {
+        archetype_storage_id: ArchStorageId(usize::MAX - 2),
+        archetype_storage_index: ArchStorageIndex(usize::MAX),
+        component_mask: ComponentMask::EMPTY,
+    };
+
+    /// The meta of a tombstone (see [`World::despawn_retaining`](crate::world::World::despawn_retaining)): it isn't
+    /// in any storage of the world, its retained components are in the storages of the tombstones.
+    pub(crate) const TOMBSTONE: EntityMeta = EntityMeta {
+        archetype_storage_id: ArchStorageId(usize::MAX - 3),
+        archetype_storage_index: ArchStorageIndex(usize::MAX),
+        component_mask: ComponentMask::EMPTY,
     };
+
+    /// Returns `true` if this is the meta of an archived entity.
+    pub(crate) fn is_archived(&self) -> bool {
+        self.archetype_storage_id == Self::ARCHIVED.archetype_storage_id
+    }
+
+    /// Returns `true` if this is the meta of a removed entity.
+    pub(crate) fn is_dead(&self) -> bool {
+        self.archetype_storage_id == Self::DEAD.archetype_storage_id
+    }
+
+    /// Returns `true` if this is the meta of a tombstone.
+    pub(crate) fn is_tombstone(&self) -> bool {
+        self.archetype_storage_id == Self::TOMBSTONE.archetype_storage_id
+    }
+}
+
+/// A set of [`EntityId`]s, in the order they were inserted (until one is removed). Membership is by the full
+/// [`EntityId`] (including the generation): an entity that reuses the id of a removed member isn't a member, and
+/// inserting it replaces the stale member.
+#[derive(Default, Clone, Debug)]
+pub(crate) struct EntityIdSet {
+    members: Vec<EntityId>,
+    /// The index of each member in [`Self::members`], by its [`EntityId::id`].
+    indices: HashMap<u32, usize>,
+}
+
+impl EntityIdSet {
+    /// Insert an entity. Returns `false` if it's already a member.
+    pub(crate) fn insert(&mut self, entity: EntityId) -> bool {
+        match self.indices.get(&entity.id()) {
+            Some(&index) if self.members[index] == entity => false,
+            Some(&index) => {
+                self.members[index] = entity;
+                true
+            }
+            None => {
+                self.indices.insert(entity.id(), self.members.len());
+                self.members.push(entity);
+                true
+            }
+        }
+    }
+
+    /// Remove an entity. Returns `false` if it isn't a member.
+    pub(crate) fn remove(&mut self, entity: EntityId) -> bool {
+        if !self.contains(entity) {
+            return false;
+        }
+        let index = self.indices.remove(&entity.id()).unwrap();
+        self.members.swap_remove(index);
+        if let Some(moved) = self.members.get(index) {
+            self.indices.insert(moved.id(), index);
+        }
+        true
+    }
+
+    /// Returns `true` if the entity is a member.
+    #[inline]
+    pub(crate) fn contains(&self, entity: EntityId) -> bool {
+        self.indices
+            .get(&entity.id())
+            .is_some_and(|index| self.members[*index] == entity)
+    }
+
+    /// Keep only the members for which `f` returns `true`.
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(EntityId) -> bool) {
+        let before = self.members.len();
+        self.members.retain(|entity| f(*entity));
+        if self.members.len() != before {
+            self.indices = (self.members.iter().enumerate())
+                .map(|(index, entity)| (entity.id(), index))
+                .collect();
+        }
+    }
+
+    /// The amount of members.
+    pub(crate) fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Iterate over the members.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.members.iter().copied()
+    }
 }
 
 #[cfg(test)]
@@ -151,12 +495,12 @@ mod tests {
             assert!(entity_factory.get_entity_meta(*entity).is_some());
         }
 
-        for entity in entities.iter().filter(|id| id.id() % 2 == 0) {
+        for entity in entities.iter().step_by(2) {
             entity_factory.remove_entity(*entity);
         }
 
-        for entity in &entities {
-            assert!(entity_factory.verify_generation(*entity) || entity.id() % 2 == 0);
+        for (i, entity) in entities.iter().enumerate() {
+            assert!(entity_factory.verify_generation(*entity) || i % 2 == 0);
         }
 
         assert_eq!(entity_factory.entities(), 50);
@@ -167,4 +511,111 @@ mod tests {
 
         assert_eq!(entity_factory.entities(), 100);
     }
+
+    #[test]
+    fn test_removed_entities_are_not_alive() {
+        let mut entity_factory = EntityFactory::default();
+        let entity = entity_factory.new_entity(EntityMeta::PLACEHOLDER);
+        entity_factory.remove_entity(entity);
+        let next = entity_factory.next_entity_id();
+        assert_eq!(next, entity.with_generation(1));
+        assert!(!entity_factory.verify_generation(entity));
+        assert!(!entity_factory.verify_generation(next));
+        assert!(entity_factory.get_entity_meta(next).is_none());
+        assert!(!entity_factory.verify_generation(EntityId::new(1)));
+
+        assert_eq!(entity_factory.new_entity(EntityMeta::PLACEHOLDER), next);
+        assert!(entity_factory.verify_generation(next));
+        assert!(!entity_factory.verify_generation(entity));
+    }
+
+    #[test]
+    fn test_salted_ids() {
+        for seed in [0, 1, u64::MAX] {
+            let salt = IdSalt::new(seed);
+            for index in [0, 1, 2, 1000, u32::MAX - 1, u32::MAX] {
+                assert_eq!(salt.unsalt(salt.salt(index)), index);
+            }
+        }
+
+        let mut entity_factory = EntityFactory::default();
+        assert!(entity_factory.salt_ids(7));
+        let entities: Vec<EntityId> = (0..1000)
+            .map(|_| entity_factory.new_entity(EntityMeta::PLACEHOLDER))
+            .collect();
+        assert!(!entity_factory.salt_ids(8));
+        for (i, entity) in entities.iter().enumerate() {
+            assert_eq!(entity_factory.dense_index(*entity), i);
+        }
+        // Hardly any id is a valid index into an array of the entities.
+        assert!(entities.iter().filter(|entity| entity.id() < 1000).count() < 5);
+
+        entity_factory.remove_entity(entities[10]);
+        assert_eq!(entity_factory.next_entity_id().id(), entities[10].id());
+        let revived = entity_factory.new_entity(EntityMeta::PLACEHOLDER);
+        assert_eq!(revived, entities[10].with_generation(1));
+        assert_eq!(entity_factory.dense_index(revived), 10);
+        assert_eq!(
+            entity_factory.next_entity_id(),
+            entity_factory.new_entity(EntityMeta::PLACEHOLDER)
+        );
+    }
+
+    #[test]
+    fn test_reuse_policies() {
+        for reuse_policy in [ReusePolicy::Fifo, ReusePolicy::Lifo] {
+            let mut entity_factory = EntityFactory::default();
+            entity_factory.set_reuse_policy(reuse_policy);
+            let entities: Vec<EntityId> = (0..3)
+                .map(|_| entity_factory.new_entity(EntityMeta::PLACEHOLDER))
+                .collect();
+
+            entity_factory.remove_entity(entities[0]);
+            entity_factory.remove_entity(entities[1]);
+            let revived = entity_factory.new_entity(EntityMeta::PLACEHOLDER);
+            let expected = match reuse_policy {
+                ReusePolicy::Fifo => entities[0],
+                ReusePolicy::Lifo => entities[1],
+            };
+            assert_eq!(revived.id(), expected.id());
+            assert_eq!(revived.generation(), expected.generation() + 1);
+
+            // The stale id can't be used to access the revived entity.
+            assert!(!entity_factory.verify_generation(expected));
+            assert!(entity_factory.get_entity_meta(expected).is_none());
+            assert!(entity_factory.get_entity_meta(revived).is_some());
+        }
+    }
+
+    #[test]
+    fn test_entity_id_set() {
+        let mut set = EntityIdSet::default();
+        let entities: Vec<EntityId> = (0..6).map(EntityId::new).collect();
+        for entity in &entities {
+            assert!(set.insert(*entity));
+        }
+        assert!(!set.insert(entities[2]));
+        assert!(set.remove(entities[1]));
+        assert!(!set.remove(entities[1]));
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            [
+                entities[0],
+                entities[5],
+                entities[2],
+                entities[3],
+                entities[4]
+            ]
+        );
+        // A reused id is a different member, and replaces the stale one.
+        let reused = entities[3].with_generation(1);
+        assert!(!set.contains(reused));
+        assert!(!set.remove(reused));
+        assert!(set.insert(reused));
+        assert!(set.contains(reused) && !set.contains(entities[3]));
+        set.retain(|entity| entity.id() % 2 == 1);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [entities[5], reused]);
+        assert!(set.contains(reused) && set.remove(entities[5]) && set.contains(reused));
+        assert_eq!(set.len(), 1);
+    }
 }
@@ -0,0 +1,108 @@
+use crate::{
+    entity::EntityId,
+    world::{ffi_handle::FfiHandle, World},
+};
+
+/// What the functions of this module return instead of the bits of an entity (see [`EntityId::to_bits`]) when a
+/// handle doesn't resolve to one.
+pub const WORLDS_ENTITY_NONE: u64 = u64::MAX;
+
+/// Acquire the [`FfiHandle`] of an entity, given by its bits (see [`World::ffi_handles`]). Returns `0` (the null
+/// handle) if the entity isn't alive, or if no more handles can be acquired.
+///
+/// # Safety
+/// `world` must point to a live [`World`] that nothing else accesses during the call.
+#[no_mangle]
+pub unsafe extern "C" fn worlds_entity_acquire(world: *mut World, entity: u64) -> u32 {
+    (*world)
+        .ffi_handles()
+        .acquire(EntityId::from_bits(entity))
+        .unwrap_or(FfiHandle::NULL)
+        .to_raw()
+}
+
+/// Release a reference to a handle (see [`FfiHandleTable::release`](crate::world::ffi_handle::FfiHandleTable::release)).
+/// Returns `false` if the handle didn't resolve to an entity.
+///
+/// # Safety
+/// `world` must point to a live [`World`] that nothing else accesses during the call.
+#[no_mangle]
+pub unsafe extern "C" fn worlds_entity_release(world: *mut World, handle: u32) -> bool {
+    (*world).ffi_handles().release(FfiHandle::from_raw(handle))
+}
+
+/// The bits of the entity of a handle, or [`WORLDS_ENTITY_NONE`] if the handle was released, or its entity died.
+///
+/// # Safety
+/// `world` must point to a live [`World`] that isn't mutated during the call.
+#[no_mangle]
+pub unsafe extern "C" fn worlds_entity_resolve(world: *const World, handle: u32) -> u64 {
+    (*world)
+        .resolve_ffi_handle(FfiHandle::from_raw(handle))
+        .map_or(WORLDS_ENTITY_NONE, |entity| entity.to_bits())
+}
+
+/// Resolve `len` handles, like [`worlds_entity_resolve`], writing the bits of their entities to `out`. Returns how
+/// many of them resolved to an entity.
+///
+/// # Safety
+/// `world` must point to a live [`World`] that isn't mutated during the call, `handles` must point to `len`
+/// handles, and `out` to room for `len` entities (they may not overlap).
+#[no_mangle]
+pub unsafe extern "C" fn worlds_entity_resolve_many(
+    world: *const World,
+    handles: *const u32,
+    len: usize,
+    out: *mut u64,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    let handles = std::slice::from_raw_parts(handles, len);
+    let out = std::slice::from_raw_parts_mut(out, len);
+    let mut resolved = 0;
+    for (handle, out) in handles.iter().zip(out) {
+        *out = worlds_entity_resolve(world, *handle);
+        resolved += usize::from(*out != WORLDS_ENTITY_NONE);
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::Fx;
+
+    #[test]
+    fn test_c_functions() {
+        let mut world = World::default();
+        let entity = world.spawn(Fx::<0>(0));
+        let dead = world.spawn(Fx::<0>(0));
+        world.despawn(dead);
+        let world: *mut World = &mut world;
+        unsafe {
+            let handle = worlds_entity_acquire(world, entity.to_bits());
+            assert_ne!(handle, 0);
+            assert_eq!(worlds_entity_acquire(world, dead.to_bits()), 0);
+            assert_eq!(worlds_entity_resolve(world, handle), entity.to_bits());
+
+            let handles = [handle, 0, handle];
+            let mut out = [0; 3];
+            let resolved = worlds_entity_resolve_many(
+                world,
+                handles.as_ptr(),
+                handles.len(),
+                out.as_mut_ptr(),
+            );
+            assert_eq!(resolved, 2);
+            assert_eq!(
+                out,
+                [entity.to_bits(), WORLDS_ENTITY_NONE, entity.to_bits()]
+            );
+
+            assert!(worlds_entity_release(world, handle));
+            assert!(!worlds_entity_release(world, handle));
+            assert_eq!(worlds_entity_resolve(world, handle), WORLDS_ENTITY_NONE);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{
+    archetype::ArchetypeInfo,
+    component::make_component,
+    entity::EntityId,
+    prelude::{Bundle, Component, ComponentFactory, ComponentId},
+    world::World,
+};
+use bevy_ecs::{entity::Entity, world::EntityRef};
+use bevy_ptr::OwningPtr;
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+};
+
+/// Mirror a single bevy component of an entity, by converting it and passing the converted component to the callback.
+type MirrorFn =
+    Box<dyn Fn(EntityRef<'_>, &ComponentFactory, &mut dyn FnMut(OwningPtr<'_>)) + Send + Sync>;
+
+struct Mapping {
+    bevy_type: TypeId,
+    worlds_type: TypeId,
+    register: fn(&mut ComponentFactory) -> Option<ComponentId>,
+    mirror: MirrorFn,
+}
+
+/// Maps bevy components to worlds components, for [`mirror_from_bevy`].
+#[derive(Default)]
+pub struct InteropRegistry {
+    mappings: Vec<Mapping>,
+}
+
+impl InteropRegistry {
+    /// Create a new empty [`InteropRegistry`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mirror the bevy component `B` as the worlds component `W`, using `convert`.
+    /// Panics if `B` or `W` is already registered: each bevy component is mirrored as at most one worlds
+    /// component, and the other way around.
+    pub fn register<B, W>(&mut self, convert: impl Fn(&B) -> W + Send + Sync + 'static) -> &mut Self
+    where
+        B: bevy_ecs::component::Component,
+        W: Component,
+    {
+        assert!(
+            !self
+                .mappings
+                .iter()
+                .any(|mapping| mapping.bevy_type == TypeId::of::<B>()),
+            "The bevy component `{}` is already registered",
+            type_name::<B>()
+        );
+        assert!(
+            !self
+                .mappings
+                .iter()
+                .any(|mapping| mapping.worlds_type == TypeId::of::<W>()),
+            "The component `{}` is already registered",
+            type_name::<W>()
+        );
+        self.mappings.push(Mapping {
+            bevy_type: TypeId::of::<B>(),
+            worlds_type: TypeId::of::<W>(),
+            register: ComponentFactory::register_component::<W>,
+            mirror: Box::new(move |entity, comp_factory, f| {
+                let component = entity
+                    .get::<B>()
+                    .expect("Only entities with the component are mirrored");
+                make_component(convert(component), comp_factory, f)
+            }),
+        });
+        self
+    }
+
+    /// The amount of registered mappings.
+    pub fn len(&self) -> usize {
+        self.mappings.len()
+    }
+
+    /// Returns `true` if no mappings are registered.
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+}
+
+/// The result of [`mirror_from_bevy`].
+#[derive(Debug, Default)]
+pub struct MirrorReport {
+    entity_map: HashMap<Entity, EntityId>,
+    skipped: usize,
+}
+
+impl MirrorReport {
+    /// The [`EntityId`] that a bevy [`Entity`] was mirrored as, if it was mirrored.
+    pub fn get(&self, entity: Entity) -> Option<EntityId> {
+        self.entity_map.get(&entity).copied()
+    }
+
+    /// The [`EntityId`] that each mirrored bevy [`Entity`] was mirrored as.
+    pub fn entity_map(&self) -> &HashMap<Entity, EntityId> {
+        &self.entity_map
+    }
+
+    /// The amount of mirrored entities.
+    pub fn mirrored(&self) -> usize {
+        self.entity_map.len()
+    }
+
+    /// The amount of entities that weren't mirrored, because they don't have any registered component.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+}
+
+/// The mirrored components of a bevy entity.
+struct MirroredBundle<'a> {
+    entity: EntityRef<'a>,
+    mappings: &'a [(ComponentId, &'a Mapping)],
+}
+
+impl Bundle for MirroredBundle<'_> {
+    fn raw_components_scope(
+        self,
+        comp_factory: &ComponentFactory,
+        f: &mut impl FnMut(ComponentId, OwningPtr<'_>),
+    ) {
+        for (comp_id, mapping) in self.mappings {
+            (mapping.mirror)(self.entity, comp_factory, &mut |ptr| f(*comp_id, ptr));
+        }
+    }
+}
+
+/// Spawn an entity in `out` for every entity in `bevy_world` that has at least one of the components that are
+/// registered in the [`InteropRegistry`], with the converted components. The entities of each bevy archetype are
+/// spawned together as a batch. Components that aren't registered are ignored.
+pub fn mirror_from_bevy(
+    bevy_world: &bevy_ecs::world::World,
+    registry: &InteropRegistry,
+    out: &mut World,
+) -> MirrorReport {
+    let mut report = MirrorReport::default();
+    let mappings: Vec<(
+        ComponentId,
+        Option<bevy_ecs::component::ComponentId>,
+        &Mapping,
+    )> = registry
+        .mappings
+        .iter()
+        .map(|mapping| {
+            (
+                (mapping.register)(&mut out.components)
+                    .expect("The maximum amount of registered components has been reached."),
+                bevy_world.components().get_id(mapping.bevy_type),
+                mapping,
+            )
+        })
+        .collect();
+
+    for archetype in bevy_world.archetypes().iter() {
+        if archetype.is_empty() {
+            continue;
+        }
+        let present: Vec<(ComponentId, &Mapping)> = mappings
+            .iter()
+            .filter(|(_, bevy_id, _)| bevy_id.is_some_and(|id| archetype.contains(id)))
+            .map(|(comp_id, _, mapping)| (*comp_id, *mapping))
+            .collect();
+        if present.is_empty() {
+            report.skipped += archetype.len();
+            continue;
+        }
+        let arch_info = ArchetypeInfo::from_component_ids(
+            present.iter().map(|(comp_id, _)| *comp_id).collect(),
+        );
+        let bundles = archetype.entities().iter().map(|entity| MirroredBundle {
+            entity: bevy_world.entity(entity.id()),
+            mappings: &present,
+        });
+        // SAFETY: Every bundle stores exactly the components in `present`, which are distinct because each
+        // worlds component is registered at most once in the registry.
+        let entity_ids = unsafe { out.spawn_batch_with_info(&arch_info, bundles) }
+            .expect("The components were registered");
+        report.entity_map.extend(
+            archetype
+                .entities()
+                .iter()
+                .map(|entity| entity.id())
+                .zip(entity_ids),
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mirror_from_bevy, InteropRegistry};
+    use crate::prelude::*;
+
+    #[derive(bevy_ecs::component::Component)]
+    struct BevyPosition(f32, f32);
+
+    #[derive(bevy_ecs::component::Component)]
+    struct BevyName(String);
+
+    #[derive(bevy_ecs::component::Component)]
+    struct BevyIgnored;
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Position(i32, i32);
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Name(String);
+
+    fn registry() -> InteropRegistry {
+        let mut registry = InteropRegistry::new();
+        registry
+            .register::<BevyPosition, Position>(|pos| Position(pos.0 as i32, pos.1 as i32))
+            .register::<BevyName, Name>(|name| Name(name.0.to_uppercase()));
+        registry
+    }
+
+    #[test]
+    fn test_mirror_from_bevy() {
+        let mut bevy_world = bevy_ecs::world::World::default();
+        let positioned: Vec<_> = (0..10)
+            .map(|i| bevy_world.spawn(BevyPosition(i as f32, 1.5)).id())
+            .collect();
+        let named: Vec<_> = (0..5)
+            .map(|i| {
+                bevy_world
+                    .spawn((
+                        BevyPosition(0.0, i as f32),
+                        BevyName(format!("e{i}")),
+                        BevyIgnored,
+                    ))
+                    .id()
+            })
+            .collect();
+        bevy_world.spawn(BevyIgnored);
+        bevy_world.spawn_empty();
+
+        let mut world = World::default();
+        let report = mirror_from_bevy(&bevy_world, &registry(), &mut world);
+        assert_eq!(report.mirrored(), 15);
+        assert_eq!(report.skipped(), 2);
+        assert_eq!(world.iter_entities().count(), 15);
+        assert_eq!(world.component_count::<Position>(), 15);
+        assert_eq!(world.component_count::<Name>(), 5);
+
+        for (i, entity) in positioned.iter().enumerate() {
+            let entity = report.get(*entity).unwrap();
+            assert_eq!(
+                world.get_component::<Position>(entity),
+                Some(&Position(i as i32, 1))
+            );
+            assert!(world.get_component::<Name>(entity).is_none());
+        }
+        for (i, entity) in named.iter().enumerate() {
+            let entity = report.get(*entity).unwrap();
+            assert_eq!(
+                world.get_component::<Position>(entity),
+                Some(&Position(0, i as i32))
+            );
+            assert_eq!(
+                world.get_component::<Name>(entity),
+                Some(&Name(format!("E{i}")))
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "already registered")]
+    fn test_register_twice() {
+        let mut registry = registry();
+        registry.register::<BevyIgnored, Name>(|_| Name(String::new()));
+    }
+}
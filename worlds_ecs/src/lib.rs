@@ -1,21 +1,45 @@
 #![deny(missing_docs)]
-#![feature(get_mut_unchecked)]
 //! The ECS for the Worlds Engine.
 
+// The code generated by the derive macros refers to this crate as `::worlds_ecs`, also from inside it.
+extern crate self as worlds_ecs;
+
 /// Module responsible for anything to do archetypes.
 pub mod archetype;
+/// Module responsible for registering the components automatically.
+#[cfg(feature = "auto-register")]
+pub mod auto_register;
 /// Module responsible for anything to do with bundles.
 pub mod bundle;
 /// Module responsible for anything to do with components.
 pub mod component;
+/// Module responsible for computing the differences between worlds.
+pub mod diff;
 /// Module responsible for anything to do with entities.
 pub mod entity;
+/// Module responsible for the C functions that resolve the handles of entities, see [`world::ffi_handle`].
+#[cfg(feature = "ffi")]
+pub mod ffi;
+/// Module responsible for mirroring entities from a bevy world, see [`interop::mirror_from_bevy`].
+#[cfg(feature = "bevy-interop")]
+pub mod interop;
 /// Module responsible for anything to do with queries.
 pub mod query;
+/// Module responsible for populating the world from text files, see [`World::load_scene`](world::World::load_scene).
+#[cfg(feature = "scene")]
+pub mod scene;
+/// Module responsible for running systems in order, see [`schedule::Schedule`].
+pub mod schedule;
 /// Module responsible for anything to do with storage.
 pub mod storage;
 /// Module responsible for anything to do with tags.
 pub mod tag;
+/// Utilities for testing code that uses the ECS, enabled by the `test-utils` feature.
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+/// Module responsible for exporting the introspection of the world to JS, see [`wasm::world_stats_json`].
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
 /// Module responsible for anything to do with the world.
 pub mod world;
 
@@ -23,14 +47,60 @@ pub(crate) mod utils;
 
 /// The common and useful exports of this crate.
 pub mod prelude {
-    pub use super::bundle::Bundle;
+    pub use super::bundle::{Bundle, FromComponents};
     pub use super::component;
     pub use super::component::*;
     pub use super::entity::*;
     pub use super::query::*;
+    #[cfg(feature = "scene")]
+    pub use super::scene::{
+        MapEntities, MigrationError, OpaqueComponent, SceneError, SceneProblem, SceneRef,
+        SceneSpawned, UnknownComponentPolicy,
+    };
+    pub use super::schedule::{Schedule, ScheduleLabel, System, SystemAccess};
     pub use super::storage;
+    pub use super::storage::alloc::{AllocEvent, AllocReason};
     pub use super::tag::*;
+    pub use super::world::access::{Access, AccessKind, AccessReport, AccessWarning};
+    pub use super::world::annotations::Annotation;
+    pub use super::world::archetypes::{ArchetypeId, ArchetypeView};
+    pub use super::world::archive::{Archivable, ArchiveError, EntityState};
+    pub use super::world::async_queue::{WorldAsyncQueue, WorldFuture};
+    #[cfg(feature = "chaos")]
+    pub use super::world::chaos::ChaosConfig;
+    pub use super::world::checksum::{ChecksumConfig, ComponentChecksum, WorldChecksum};
+    pub use super::world::cow::CloneCowError;
     pub use super::world::data::*;
+    pub use super::world::drop_order::DropOrderError;
+    pub use super::world::entity_mut::EntityWorldMut;
+    pub use super::world::ffi_handle::{FfiHandle, FfiHandleError, FfiHandleTable};
+    pub use super::world::fingerprint::{ConfigFingerprint, ConfigMismatch};
+    pub use super::world::frame_query::FrameQueryResult;
+    pub use super::world::freeze::{FrozenColumns, UnfreezeError};
+    pub use super::world::group::GroupId;
+    pub use super::world::handle::ComponentHandle;
+    pub use super::world::history::HistoryRing;
+    #[cfg(feature = "diagnostics")]
+    pub use super::world::latency::{LatencyCategory, LatencyReport, LatencyStats};
+    pub use super::world::mixed::SpawnInto;
+    pub use super::world::partition::{PartitionError, QueryAccess, WorldPartition};
+    pub use super::world::patch::{EntityPatch, PatchResult};
+    pub use super::world::pin::{StoragePin, StoragePinned};
+    pub use super::world::precreate::{ArchetypeManifest, PrecreateError, StorageCreations};
+    pub use super::world::quarantine::{
+        Inconsistency, QuarantineMemory, QuarantineReport, QuarantinedStorage,
+    };
+    pub use super::world::quota::{QuotaScope, SpawnError};
+    pub use super::world::read_scope::{QueryChunk, WorldReadScope};
+    pub use super::world::reorder::ReorderError;
+    pub use super::world::rules::{ComponentRuleError, RuleViolation};
+    pub use super::world::scoped::{AccessDenied, ComponentWhitelist, ScopedWorld};
+    pub use super::world::spatial::{Aabb, SpatialIndex, SpatialPosition, UniformGrid};
+    pub use super::world::stats::{WorldStats, WorldStatsDelta};
+    pub use super::world::teardown::{TeardownProgress, WorldTeardown};
+    pub use super::world::two_pass::ScratchBuffer;
+    pub use super::world::userdata::{Userdata, UserdataKey};
+    pub use super::world::warnings::{EcsWarning, WarnLevel};
     pub use super::world::World;
-    pub use worlds_derive::{Component, Tag};
+    pub use worlds_derive::{Component, QueryItem, Tag};
 }
@@ -1,162 +1,509 @@
-use super::query_filter::{ArchFilter, FilterResult};
+use super::{
+    query_filter::{ArchFilter, StorageDecisions, StorageFilterResult},
+    query_iter::QueryIter,
+};
 use crate::{
+    archetype::key::PrimeArchKey,
+    component::{deref_component, deref_component_mut, reject_shared_mut},
     entity::EntityId,
-    prelude::{Component, ComponentFactory},
-    utils::prime_key::PrimeArchKey,
-    world::storage::{arch_storage::ArchStorageIndex, storages::ArchStorages, ArchEntityStorage},
+    prelude::{Component, ComponentFactory, ComponentId},
+    utils::panics,
+    world::{
+        access::AccessKind,
+        storage::{
+            arch_storage::ArchStorageIndex, storages::ArchStorages, tag_storage::EntityTags,
+            ArchEntityStorage,
+        },
+    },
 };
+use std::any::TypeId;
 use worlds_derive::all_tuples;
 
+/// # Safety
+/// The implementor must ensure that [`ArchQuery::fetch`] only accesses the components that are
+/// accounted for in [`ArchQuery::merge_prime_arch_key_with`] (or that are optional), and that
+/// [`ArchQuery::IS_MUTABLE`] is `true` if it accesses any of them mutably.
 pub unsafe trait ArchQuery {
     type Item<'a>;
+
+    /// Whether this is a [`Cached`](super::Cached) filter, whose results are cached by [`QueryState`](super::QueryState).
+    const IS_CACHED_FILTER: bool = false;
+
+    /// Whether [`ArchQuery::fetch`] accesses components mutably. If it does, a storage whose components are shared
+    /// (see [`World::clone_cow`](crate::world::World::clone_cow)) is unshared before anything is fetched from it.
+    const IS_MUTABLE: bool = false;
+
     #[inline]
     fn merge_prime_arch_key_with(_pkey: &mut PrimeArchKey, _comp_factory: &ComponentFactory) {}
+
+    /// Merge the components that must be present for an entity to match, but that aren't accessed (like the
+    /// components of [`Matches`](super::Matches)). This is called after [`ArchQuery::merge_prime_arch_key_with`], and
+    /// skips the components that were already merged, so presence never conflicts with an access.
+    #[inline]
+    fn merge_required_presence_with(_pkey: &mut PrimeArchKey, _comp_factory: &ComponentFactory) {}
+
+    /// Call `f` with every registered component that [`ArchQuery::fetch`] accesses, and how it accesses it. This is
+    /// only used to record accesses (see [`World::record_access`](crate::world::World::record_access)).
+    #[inline]
+    fn for_each_access(
+        _comp_factory: &ComponentFactory,
+        _f: &mut dyn FnMut(ComponentId, AccessKind),
+    ) {
+    }
+
+    /// Call `f` with the [`TypeId`] and the name of every component that [`ArchQuery::merge_prime_arch_key_with`]
+    /// merges as accessed, whether it's registered or not (see [`check_duplicate_accesses`]).
+    #[inline]
+    fn for_each_accessed_type(_f: &mut dyn FnMut(TypeId, &'static str)) {}
+
+    /// The [`PrimeArchKey`] that the storages this query matches are super-archetypes of.
+    ///
+    /// # Panics
+    /// If the query accesses a component more than once.
+    #[inline]
+    #[track_caller]
+    fn resolve_prime_arch_key(comp_factory: &ComponentFactory) -> PrimeArchKey {
+        let mut pkey = PrimeArchKey::IDENTITY;
+        Self::merge_prime_arch_key_with(&mut pkey, comp_factory);
+        Self::merge_required_presence_with(&mut pkey, comp_factory);
+        pkey
+    }
+
+    /// Fetch the item of the entity at `index`. The decisions of `ctx` are the ones that the query pushed for the
+    /// storage (see [`ArchQuery::decide_storage`]).
+    ///
     /// # Safety
     ///   1) The caller must ensure that the [`ArchStorageIndex`] is withing the bounds of the [`ArchStorage`]
-    /// (as specified in [`ArchStorage::get_component_unchecked`]).
+    ///      (as specified in [`ArchStorage::get_component_unchecked`]).
     ///   2) The caller must ensure that the raw pointer to [`ArchStorage`] is valid, and usable.
     unsafe fn fetch<'a>(
         arch_storage: *mut ArchEntityStorage,
         index: ArchStorageIndex,
         comp_factory: &'a ComponentFactory,
+        ctx: FetchContext<'_>,
     ) -> Self::Item<'a>;
 
+    /// Like [`ArchQuery::fetch`], through a shared reference to the storage, for a storage that was prepared for
+    /// writes that don't go through a mutable reference (see [`World::partition`](crate::world::World::partition)).
+    /// The default forwards to [`ArchQuery::fetch`], and panics if the query is mutable, so mutable queries must
+    /// override it.
+    ///
+    /// # Safety
+    ///   1) The same as [`ArchQuery::fetch`].
+    ///   2) If the query is mutable, the caller must ensure that the storage isn't shared (see
+    ///      [`World::clone_cow`](crate::world::World::clone_cow)), and that nothing else accesses the components that
+    ///      the query writes while the item is alive.
+    #[track_caller]
+    unsafe fn fetch_shared<'a>(
+        arch_storage: &'a ArchEntityStorage,
+        index: ArchStorageIndex,
+        comp_factory: &'a ComponentFactory,
+        ctx: FetchContext<'_>,
+    ) -> Self::Item<'a> {
+        if Self::IS_MUTABLE {
+            panics::fail(
+                "fetch_shared",
+                "the query is mutable, but doesn't implement ArchQuery::fetch_shared",
+                &[("query", &std::any::type_name::<Self>())],
+            )
+        }
+        // SAFETY: The query only reads, so nothing is mutated through the pointer.
+        Self::fetch(
+            arch_storage as *const ArchEntityStorage as *mut ArchEntityStorage,
+            index,
+            comp_factory,
+            ctx,
+        )
+    }
+
+    /// Returns `true` if every component that this query requires is registered, meaning the query can be
+    /// resolved (with [`ArchQuery::merge_prime_arch_key_with`]) and fetched. Optional components (like
+    /// `Option<&C>`) don't need to be registered: they are `None` if they aren't.
+    #[inline]
+    fn is_resolvable(_comp_factory: &ComponentFactory) -> bool {
+        true
+    }
+
+    /// When used as a filter, evaluate the filter for a whole storage at once. The default is
+    /// [`StorageFilterResult::PerEntity`], which is always correct: it means the filter is evaluated with
+    /// [`ArchQuery::fetch`] for each entity.
+    #[inline]
+    fn filter_storage(
+        _arch_storage: &ArchEntityStorage,
+        _comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        StorageFilterResult::PerEntity
+    }
+
+    /// Like [`ArchQuery::filter_storage`], but for filters that are combined with [`FilterResult::any`]
+    /// (like in [`Or`](super::Or)).
+    #[inline]
+    fn filter_storage_any(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        Self::filter_storage(arch_storage, comp_factory)
+    }
+
+    /// How many decisions [`ArchQuery::decide_storage`] pushes: one for each [`Not`](super::Not) and
+    /// [`Or`](super::Or) in the query.
+    const STORAGE_DECISIONS: u32 = 0;
+
+    /// Push what the query decides for a whole storage into `decisions`, once per storage, so
+    /// [`ArchQuery::fetch`] reads it from [`FetchContext::decisions`] instead of deciding it for each entity. A query
+    /// that pushes decisions must declare how many in [`ArchQuery::STORAGE_DECISIONS`], and a query that contains
+    /// other queries must push their decisions in order, and skip them in its [`FetchContext`] (see
+    /// [`FetchContext::skip_decisions`]) when it fetches them.
+    #[inline]
+    fn decide_storage(
+        _arch_storage: &ArchEntityStorage,
+        _comp_factory: &ComponentFactory,
+        _decisions: &mut StorageDecisions,
+    ) {
+    }
+
     /// # Safety
     ///  1) The caller must ensure that the raw pointer to [`ArchStorages`] is valid, and usable.
+    #[track_caller]
     unsafe fn iter_query_matches<'a>(
         arch_storages: *mut ArchStorages,
         comp_factory: &'a ComponentFactory,
-    ) -> impl Iterator<Item = Self::Item<'a>> + 'a {
-        let mut pkey = PrimeArchKey::IDENTITY;
-        Self::merge_prime_arch_key_with(&mut pkey, comp_factory);
-        (*arch_storages)
-            .iter_storages_with_matching_archetype_mut(pkey)
-            .map(|arch_storage| {
-                arch_storage
-                    .iter_indices()
-                    // SAFETY: The index must be in bounds because it came from the storage itself.
-                    .map(|index| unsafe { Self::fetch(arch_storage, index, comp_factory) })
-            })
-            .flatten()
+        tags: EntityTags<'a>,
+    ) -> QueryIter<'a, Self>
+    where
+        Self: Sized,
+    {
+        QueryIter::new(arch_storages, comp_factory, tags, false)
     }
 
     /// # Safety
     ///  1) The caller must ensure that the raw pointer to [`ArchStorages`] is valid, and usable.
+    #[track_caller]
     unsafe fn iter_filtered_query_matches<'a, F: ArchFilter>(
         arch_storages: *mut ArchStorages,
         comp_factory: &'a ComponentFactory,
-    ) -> impl Iterator<Item = Self::Item<'a>> + 'a {
-        let mut pkey = PrimeArchKey::IDENTITY;
-        Self::merge_prime_arch_key_with(&mut pkey, comp_factory);
-        (*arch_storages)
-            .iter_storages_with_matching_archetype_mut(pkey)
-            .map(|arch_storage| {
-                arch_storage
-                    .iter_indices()
-                    // SAFETY: The index must be in bounds because it came from the storage itself.
-                    .filter_map(|index| unsafe {
-                        F::filter(arch_storage, index, comp_factory)
-                            .collapse()
-                            .then_some(Self::fetch(arch_storage, index, comp_factory))
-                    })
-            })
-            .flatten()
+        tags: EntityTags<'a>,
+    ) -> QueryIter<'a, Self, F>
+    where
+        Self: Sized,
+    {
+        QueryIter::new(arch_storages, comp_factory, tags, true)
+    }
+}
+
+/// What [`ArchQuery::fetch`] gets besides the storage and the [`ComponentFactory`]: the tags of the entities (for
+/// filters like [`Tagged`](super::Tagged)), and what the query decided for the storage (see
+/// [`ArchQuery::decide_storage`]).
+#[derive(Clone, Copy)]
+pub struct FetchContext<'a> {
+    tags: EntityTags<'a>,
+    decisions: StorageDecisions,
+}
+
+impl<'a> FetchContext<'a> {
+    /// A context with the tags, and without any decision.
+    pub(crate) fn new(tags: EntityTags<'a>) -> Self {
+        FetchContext {
+            tags,
+            decisions: StorageDecisions::default(),
+        }
+    }
+
+    /// The same context, with the decisions of a storage.
+    #[inline]
+    pub(crate) fn with_decisions(self, decisions: StorageDecisions) -> Self {
+        FetchContext { decisions, ..self }
+    }
+
+    /// The same context, with the decisions of the query `Q` for the storage.
+    #[inline]
+    #[track_caller]
+    pub(crate) fn for_query<Q: ArchQuery>(
+        self,
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> Self {
+        self.with_decisions(StorageDecisions::of_query::<Q>(arch_storage, comp_factory))
+    }
+
+    /// The same context, with the decisions of the filter `F` for the storage.
+    #[inline]
+    #[track_caller]
+    pub(crate) fn for_filter<F: ArchFilter>(
+        self,
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> Self {
+        self.with_decisions(StorageDecisions::of_filter::<F>(arch_storage, comp_factory))
+    }
+
+    /// The tags of the entities.
+    #[inline]
+    pub fn tags(&self) -> EntityTags<'a> {
+        self.tags
+    }
+
+    /// What the query decided for the storage. The first decision is the query's own.
+    #[inline]
+    pub fn decisions(&self) -> StorageDecisions {
+        self.decisions
+    }
+
+    /// The same context, after the first `n` decisions. A query that fetches other queries passes each of them the
+    /// context after the decisions of the queries before it.
+    #[inline]
+    pub fn skip_decisions(self, n: u32) -> Self {
+        FetchContext {
+            decisions: self.decisions.skip(n),
+            ..self
+        }
+    }
+}
+
+/// An [`ArchQuery`] that only reads the components it fetches, so it can be fetched through a shared reference to
+/// the storages (see [`World::query_shared`](crate::world::World::query_shared)), even from several threads at once.
+///
+/// # Safety
+/// The implementor must ensure that [`ArchQuery::fetch`] never accesses anything mutably (and so that
+/// [`ArchQuery::IS_MUTABLE`] is `false`).
+pub unsafe trait ReadOnlyArchQuery: ArchQuery {}
+
+// SAFETY: `&C` only reads the component.
+unsafe impl<C: Component> ReadOnlyArchQuery for &C {}
+// SAFETY: `Option<&C>` only reads the component.
+unsafe impl<C: Component> ReadOnlyArchQuery for Option<&C> {}
+// SAFETY: `EntityId` only reads the id of the entity.
+unsafe impl ReadOnlyArchQuery for EntityId {}
+
+/// Merge the key of the accessed component `C` into `pkey`.
+///
+/// # Panics
+/// If `C` isn't registered, or `pkey` already has it (the query accesses it more than once).
+#[track_caller]
+fn merge_accessed_component<C: Component>(
+    pkey: &mut PrimeArchKey,
+    comp_factory: &ComponentFactory,
+) {
+    let Some(comp_id) = comp_factory.get_component_id::<C>() else {
+        panics::fail_component(
+            "query",
+            "the component isn't registered",
+            std::any::type_name::<C>(),
+        )
+    };
+    if pkey.is_sub_archetype(comp_id.prime_key()) {
+        fail_duplicate_access("query", std::any::type_name::<C>())
     }
+    pkey.merge_with(comp_id.prime_key());
+}
+
+/// Panic if the query `Q` accesses a component more than once. Unlike [`ArchQuery::resolve_prime_arch_key`], this
+/// compares the [`TypeId`]s of the components, so it also panics when they aren't registered (and the query would
+/// otherwise just be empty).
+#[track_caller]
+pub(crate) fn check_duplicate_accesses<Q: ArchQuery>(operation: &str) {
+    let mut duplicate = None;
+    Q::for_each_accessed_type(&mut |type_id, name| {
+        let mut count = 0;
+        Q::for_each_accessed_type(&mut |other, _| count += usize::from(other == type_id));
+        if count > 1 {
+            duplicate.get_or_insert(name);
+        }
+    });
+    if let Some(name) = duplicate {
+        fail_duplicate_access(operation, name)
+    }
+}
+
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn fail_duplicate_access(operation: &str, component: &'static str) -> ! {
+    panics::fail(
+        operation,
+        "the query accesses a component more than once",
+        &[
+            ("component", &component),
+            (
+                "help",
+                &"to write components based on the same components of other entities, gather them in one pass \
+                  and write them in another with World::query_two_pass",
+            ),
+        ],
+    )
 }
 
 unsafe impl<C: Component> ArchQuery for &C {
     type Item<'a> = &'a C;
 
+    fn is_resolvable(comp_factory: &ComponentFactory) -> bool {
+        comp_factory.is_registered::<C>()
+    }
+
+    fn for_each_access(
+        comp_factory: &ComponentFactory,
+        f: &mut dyn FnMut(ComponentId, AccessKind),
+    ) {
+        if let Some(comp_id) = comp_factory.get_component_id::<C>() {
+            f(comp_id, AccessKind::Read);
+        }
+    }
+
     unsafe fn fetch<'a>(
         arch_storage: *mut ArchEntityStorage,
         index: ArchStorageIndex,
         comp_factory: &'a ComponentFactory,
+        _ctx: FetchContext<'_>,
     ) -> Self::Item<'a> {
-        (*arch_storage)
-            .get_component_unchecked(
+        deref_component::<C>(
+            (*arch_storage).get_component_unchecked(
                 index,
                 comp_factory
                     .get_component_id::<C>()
                     .expect("Can't query unregistered component"),
-            )
-            .deref::<C>()
+            ),
+        )
     }
 
+    #[track_caller]
     fn merge_prime_arch_key_with(pkey: &mut PrimeArchKey, comp_factory: &ComponentFactory) {
-        pkey.merge_with_but_panic_if_already_merged(
-            comp_factory
-                .get_component_id::<C>()
-                .expect("Can't query unregistered component")
-                .prime_key(),
-            "Can't query duplicate components",
-        )
+        merge_accessed_component::<C>(pkey, comp_factory)
+    }
+
+    fn for_each_accessed_type(f: &mut dyn FnMut(TypeId, &'static str)) {
+        f(TypeId::of::<C>(), std::any::type_name::<C>());
     }
 }
 
 unsafe impl<C: Component> ArchQuery for &mut C {
     type Item<'a> = &'a mut C;
 
+    const IS_MUTABLE: bool = true;
+
+    fn is_resolvable(comp_factory: &ComponentFactory) -> bool {
+        comp_factory.is_registered::<C>()
+    }
+
+    fn for_each_access(
+        comp_factory: &ComponentFactory,
+        f: &mut dyn FnMut(ComponentId, AccessKind),
+    ) {
+        if let Some(comp_id) = comp_factory.get_component_id::<C>() {
+            f(comp_id, AccessKind::Write);
+        }
+    }
+
     unsafe fn fetch<'a>(
         arch_storage: *mut ArchEntityStorage,
         index: ArchStorageIndex,
         comp_factory: &'a ComponentFactory,
+        _ctx: FetchContext<'_>,
     ) -> Self::Item<'a> {
-        (*arch_storage)
-            .get_component_mut_unchecked(
+        deref_component_mut::<C>(
+            (*arch_storage).get_component_mut_unchecked(
                 index,
                 comp_factory
                     .get_component_id::<C>()
                     .expect("Can't query unregistered component"),
-            )
-            .deref_mut::<C>()
+            ),
+        )
     }
 
-    fn merge_prime_arch_key_with(pkey: &mut PrimeArchKey, comp_factory: &ComponentFactory) {
-        pkey.merge_with_but_panic_if_already_merged(
-            comp_factory
-                .get_component_id::<C>()
-                .expect("Can't query unregistered component")
-                .prime_key(),
-            "Can't query duplicate components",
+    unsafe fn fetch_shared<'a>(
+        arch_storage: &'a ArchEntityStorage,
+        index: ArchStorageIndex,
+        comp_factory: &'a ComponentFactory,
+        _ctx: FetchContext<'_>,
+    ) -> Self::Item<'a> {
+        deref_component_mut::<C>(
+            arch_storage
+                .get_component_unchecked(
+                    index,
+                    comp_factory
+                        .get_component_id::<C>()
+                        .expect("Can't query unregistered component"),
+                )
+                .assert_unique(),
         )
     }
+
+    #[track_caller]
+    fn merge_prime_arch_key_with(pkey: &mut PrimeArchKey, comp_factory: &ComponentFactory) {
+        reject_shared_mut::<C>("query");
+        merge_accessed_component::<C>(pkey, comp_factory)
+    }
+
+    fn for_each_accessed_type(f: &mut dyn FnMut(TypeId, &'static str)) {
+        f(TypeId::of::<C>(), std::any::type_name::<C>());
+    }
 }
 
 unsafe impl<C: Component> ArchQuery for Option<&mut C> {
     type Item<'a> = Option<&'a mut C>;
 
+    const IS_MUTABLE: bool = true;
+
+    fn for_each_access(
+        comp_factory: &ComponentFactory,
+        f: &mut dyn FnMut(ComponentId, AccessKind),
+    ) {
+        if let Some(comp_id) = comp_factory.get_component_id::<C>() {
+            f(comp_id, AccessKind::Write);
+        }
+    }
+
     unsafe fn fetch<'a>(
         arch_storage: *mut ArchEntityStorage,
         index: ArchStorageIndex,
         comp_factory: &'a ComponentFactory,
+        _ctx: FetchContext<'_>,
     ) -> Self::Item<'a> {
+        let comp_id = comp_factory.get_component_id::<C>()?;
         (*arch_storage)
-            .get_component_mut(
-                index,
-                comp_factory
-                    .get_component_id::<C>()
-                    .expect("Can't query unregistered component"),
-            )
-            .map(|c| c.deref_mut::<C>())
+            .get_component_mut(index, comp_id)
+            .map(|c| deref_component_mut::<C>(c))
+    }
+
+    unsafe fn fetch_shared<'a>(
+        arch_storage: &'a ArchEntityStorage,
+        index: ArchStorageIndex,
+        comp_factory: &'a ComponentFactory,
+        _ctx: FetchContext<'_>,
+    ) -> Self::Item<'a> {
+        let comp_id = comp_factory.get_component_id::<C>()?;
+        arch_storage
+            .get_component(index, comp_id)
+            .map(|c| deref_component_mut::<C>(c.assert_unique()))
+    }
+
+    #[track_caller]
+    fn merge_prime_arch_key_with(_pkey: &mut PrimeArchKey, _comp_factory: &ComponentFactory) {
+        reject_shared_mut::<C>("query");
     }
 }
 
 unsafe impl<C: Component> ArchQuery for Option<&C> {
     type Item<'a> = Option<&'a C>;
 
+    fn for_each_access(
+        comp_factory: &ComponentFactory,
+        f: &mut dyn FnMut(ComponentId, AccessKind),
+    ) {
+        if let Some(comp_id) = comp_factory.get_component_id::<C>() {
+            f(comp_id, AccessKind::Read);
+        }
+    }
+
     unsafe fn fetch<'a>(
         arch_storage: *mut ArchEntityStorage,
         index: ArchStorageIndex,
         comp_factory: &'a ComponentFactory,
+        _ctx: FetchContext<'_>,
     ) -> Self::Item<'a> {
+        let comp_id = comp_factory.get_component_id::<C>()?;
         (*arch_storage)
-            .get_component(
-                index,
-                comp_factory
-                    .get_component_id::<C>()
-                    .expect("Can't query unregistered component"),
-            )
-            .map(|c| c.deref::<C>())
+            .get_component(index, comp_id)
+            .map(|c| deref_component::<C>(c))
     }
 }
 
@@ -167,6 +514,7 @@ unsafe impl ArchQuery for EntityId {
         arch_storage: *mut ArchEntityStorage,
         index: ArchStorageIndex,
         _comp_factory: &'a ComponentFactory,
+        _ctx: FetchContext<'_>,
     ) -> Self::Item<'a> {
         (*arch_storage).get_entity_at_unchecked(index)
     }
@@ -184,19 +532,96 @@ macro_rules! impl_comp_query_for_tuple {
         unsafe impl<$($name: ArchQuery),*> ArchQuery for ($($name,)*) {
             type Item<'a> = ($($name::Item<'a>,)*);
 
+            const IS_MUTABLE: bool = false $(|| $name::IS_MUTABLE)*;
+
+            const STORAGE_DECISIONS: u32 = 0 $(+ $name::STORAGE_DECISIONS)*;
+
             unsafe fn fetch<'a>(
                 arch_storage: *mut ArchEntityStorage,
                 index: ArchStorageIndex,
                 comp_factory: &'a ComponentFactory,
+                ctx: FetchContext<'_>,
+            ) -> Self::Item<'a> {
+                let mut ctx = ctx;
+                #[allow(clippy::unused_unit)]
+                ($({
+                    let item = $name::fetch(arch_storage, index, comp_factory, ctx);
+                    ctx = ctx.skip_decisions($name::STORAGE_DECISIONS);
+                    item
+                },)*)
+            }
+
+            #[track_caller]
+            unsafe fn fetch_shared<'a>(
+                arch_storage: &'a ArchEntityStorage,
+                index: ArchStorageIndex,
+                comp_factory: &'a ComponentFactory,
+                ctx: FetchContext<'_>,
             ) -> Self::Item<'a> {
-                ($($name::fetch(arch_storage, index, comp_factory),)*)
+                let mut ctx = ctx;
+                #[allow(clippy::unused_unit)]
+                ($({
+                    let item = $name::fetch_shared(arch_storage, index, comp_factory, ctx);
+                    ctx = ctx.skip_decisions($name::STORAGE_DECISIONS);
+                    item
+                },)*)
+            }
+
+            fn decide_storage(
+                arch_storage: &ArchEntityStorage,
+                comp_factory: &ComponentFactory,
+                decisions: &mut StorageDecisions,
+            ) {
+                $($name::decide_storage(arch_storage, comp_factory, decisions);)*
             }
 
+            #[track_caller]
             fn merge_prime_arch_key_with(pkey: &mut PrimeArchKey, comp_factory: &ComponentFactory) {
                 $($name::merge_prime_arch_key_with(pkey, comp_factory);)*
             }
+
+            fn merge_required_presence_with(pkey: &mut PrimeArchKey, comp_factory: &ComponentFactory) {
+                $($name::merge_required_presence_with(pkey, comp_factory);)*
+            }
+
+            fn for_each_access(comp_factory: &ComponentFactory, f: &mut dyn FnMut(ComponentId, AccessKind)) {
+                $($name::for_each_access(comp_factory, f);)*
+            }
+
+            fn for_each_accessed_type(f: &mut dyn FnMut(TypeId, &'static str)) {
+                $($name::for_each_accessed_type(f);)*
+            }
+
+            fn is_resolvable(comp_factory: &ComponentFactory) -> bool {
+                true $(&& $name::is_resolvable(comp_factory))*
+            }
+
+            fn filter_storage(
+                arch_storage: &ArchEntityStorage,
+                comp_factory: &ComponentFactory,
+            ) -> StorageFilterResult {
+                StorageFilterResult::AllMatch
+                    $(.and($name::filter_storage(arch_storage, comp_factory)))*
+            }
+
+            fn filter_storage_any(
+                arch_storage: &ArchEntityStorage,
+                comp_factory: &ComponentFactory,
+            ) -> StorageFilterResult {
+                StorageFilterResult::NoneMatch
+                    $(.or($name::filter_storage_any(arch_storage, comp_factory)))*
+            }
         }
     };
 }
 
 all_tuples!(impl_comp_query_for_tuple, 0, 12, Q);
+
+macro_rules! impl_read_only_query_for_tuple {
+    ($($name:ident),*) => {
+        // SAFETY: Each of the queries in the tuple only reads.
+        unsafe impl<$($name: ReadOnlyArchQuery),*> ReadOnlyArchQuery for ($($name,)*) {}
+    };
+}
+
+all_tuples!(impl_read_only_query_for_tuple, 0, 12, Q);
@@ -0,0 +1,94 @@
+use super::{query_filter::ArchFilter, FetchContext, FilterResult, StorageFilterResult};
+use crate::{
+    archetype::key::PrimeArchKey,
+    prelude::ComponentFactory,
+    world::storage::{
+        storages::{ArchStorageId, ArchStorages},
+        tag_storage::EntityTags,
+    },
+};
+use std::collections::HashMap;
+
+/// The cached results of a filter for the rows of a single storage.
+struct CachedRows {
+    /// The generation of the storage when the filter was evaluated.
+    generation: u64,
+    /// A bit for each row of the storage, set if the row passes the filter.
+    rows: Vec<u64>,
+}
+
+/// The results of a [`Cached`](super::Cached) filter for each storage, owned by a [`QueryState`](super::QueryState).
+///
+/// Filters are types without any data, so the cache can't live in the filter itself. Instead, a [`QueryState`](super::QueryState)
+/// whose filter is [`Cached`](super::Cached) keeps a [`FilterCache`], and refreshes it before every iteration: the filter is
+/// evaluated again only for storages whose [generation](crate::world::storage::arch_storage::ArchStorage::generation)
+/// changed since it was last evaluated.
+#[derive(Default)]
+pub struct FilterCache {
+    entries: HashMap<ArchStorageId, CachedRows>,
+}
+
+impl FilterCache {
+    /// Forget all of the cached results.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut cache = FilterCache::default();
+    /// // A `QueryState` clears its cache whenever it resolves its query again.
+    /// cache.clear();
+    /// ```
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Evaluate `F` for the storages matched by `pkey` that changed since `F` was last evaluated for them.
+    ///
+    /// # Safety
+    /// The caller must ensure that `F` is the filter that the cache was used with, and that it's evaluated with the
+    /// same [`ComponentFactory`] (or that the cache was cleared since).
+    pub(crate) unsafe fn refresh<F: ArchFilter>(
+        &mut self,
+        arch_storages: &ArchStorages,
+        comp_factory: &ComponentFactory,
+        tags: EntityTags<'_>,
+        pkey: PrimeArchKey,
+    ) {
+        let mut start = ArchStorageId(0);
+        while let Some(sid) = arch_storages.next_storage_with_matching_archetype(start, pkey) {
+            start = ArchStorageId(sid.0 + 1);
+            let storage = arch_storages.get_storage(sid).unwrap_unchecked();
+            let entry = self.entries.entry(sid).or_insert(CachedRows {
+                generation: storage.generation().wrapping_sub(1),
+                rows: Vec::new(),
+            });
+            if entry.generation == storage.generation() {
+                continue;
+            }
+            entry.generation = storage.generation();
+            entry.rows.clear();
+            entry.rows.resize(storage.len().div_ceil(64), 0);
+            match F::filter_storage(storage, comp_factory) {
+                StorageFilterResult::NoneMatch => {}
+                StorageFilterResult::AllMatch => {
+                    storage
+                        .iter_indices()
+                        .for_each(|index| entry.rows[index.0 / 64] |= 1 << (index.0 % 64));
+                }
+                StorageFilterResult::PerEntity => {
+                    let ctx = FetchContext::new(tags).for_filter::<F>(storage, comp_factory);
+                    for index in storage.iter_indices() {
+                        if F::filter(storage, index, comp_factory, ctx).collapse() {
+                            entry.rows[index.0 / 64] |= 1 << (index.0 % 64);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The cached results of the storage, a bit for each row.
+    pub(crate) fn rows(&self, sid: ArchStorageId) -> Option<&[u64]> {
+        self.entries.get(&sid).map(|entry| entry.rows.as_slice())
+    }
+}
@@ -0,0 +1,269 @@
+use super::arch_query::{ArchQuery, FetchContext, ReadOnlyArchQuery};
+use crate::{
+    prelude::{ComponentFactory, ComponentId},
+    world::storage::{arch_storage::ArchStorageIndex, ArchEntityStorage},
+};
+
+/// A query item that yields the marker components (the zero-sized components) of each entity, as a [`MarkerSet`].
+/// It matches every entity, and doesn't access any component: the markers of a storage are found once, when it's
+/// created, and every entity of the storage shares them.
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Player;
+///
+/// let mut world = World::default();
+/// world.spawn((Health(100), Player));
+/// for markers in world.query_shared::<Markers>() {
+///     let names: Vec<&str> = markers.names(world.components()).collect();
+///     assert_eq!(names, [std::any::type_name::<Player>()]);
+/// }
+/// ```
+pub struct Markers;
+
+/// The marker components (the zero-sized components) of an entity. See [`Markers`] and
+/// [`World::marker_components_of`](crate::world::World::marker_components_of).
+#[derive(Debug, Clone, Copy)]
+pub struct MarkerSet<'a> {
+    markers: &'a [ComponentId],
+}
+
+impl<'a> MarkerSet<'a> {
+    /// Returns `true` if the component is one of the markers.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Player;
+    ///
+    /// let mut world = World::default();
+    /// let player = world.register_component::<Player>().unwrap();
+    /// let health = world.register_component::<Health>().unwrap();
+    /// world.spawn((Health(100), Player));
+    /// for markers in world.query_shared::<Markers>() {
+    ///     assert!(markers.contains(player));
+    ///     // Only zero-sized components are markers.
+    ///     assert!(!markers.contains(health));
+    /// }
+    /// ```
+    pub fn contains(&self, comp_id: ComponentId) -> bool {
+        self.markers.binary_search(&comp_id).is_ok()
+    }
+
+    /// Iterate over the [`ComponentId`]s of the markers, in ascending order.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Player;
+    /// #[derive(Component)]
+    /// struct Enemy;
+    ///
+    /// let mut world = World::default();
+    /// let player = world.register_component::<Player>().unwrap();
+    /// let enemy = world.register_component::<Enemy>().unwrap();
+    /// world.spawn((Enemy, Player));
+    /// for markers in world.query_shared::<Markers>() {
+    ///     assert_eq!(markers.iter().collect::<Vec<_>>(), [player, enemy]);
+    /// }
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = ComponentId> + 'a {
+        self.markers.iter().copied()
+    }
+
+    /// The amount of markers.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Player;
+    ///
+    /// let mut world = World::default();
+    /// world.spawn((Health(100), Player));
+    /// let lens: Vec<usize> = world.query_shared::<Markers>().map(|markers| markers.len()).collect();
+    /// assert_eq!(lens, [1]);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.markers.len()
+    }
+
+    /// Returns `true` if there are no markers.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(100));
+    /// assert!(world.query_shared::<Markers>().all(|markers| markers.is_empty()));
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+
+    /// Iterate over the names of the markers, in the order of [`Self::iter`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Player;
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Player);
+    /// for markers in world.query_shared::<Markers>() {
+    ///     let names: Vec<&str> = markers.names(world.components()).collect();
+    ///     assert_eq!(names, [std::any::type_name::<Player>()]);
+    /// }
+    /// ```
+    pub fn names<'f>(
+        &self,
+        comp_factory: &'f ComponentFactory,
+    ) -> impl Iterator<Item = &'f str> + 'a
+    where
+        'f: 'a,
+    {
+        self.iter().filter_map(|comp_id| {
+            comp_factory
+                .get_component_info_from_component_id(comp_id)
+                .map(|info| info.name())
+        })
+    }
+}
+
+// SAFETY: `Markers` doesn't access any component.
+unsafe impl ArchQuery for Markers {
+    type Item<'a> = MarkerSet<'a>;
+
+    unsafe fn fetch<'a>(
+        arch_storage: *mut ArchEntityStorage,
+        _index: ArchStorageIndex,
+        _comp_factory: &'a ComponentFactory,
+        _ctx: FetchContext<'_>,
+    ) -> Self::Item<'a> {
+        MarkerSet {
+            markers: (*arch_storage).markers(),
+        }
+    }
+}
+
+// SAFETY: `Markers` only reads the markers of the storage.
+unsafe impl ReadOnlyArchQuery for Markers {}
+
+#[cfg(test)]
+mod tests {
+    use super::Markers;
+    use crate::prelude::*;
+    use std::any::type_name;
+
+    #[derive(Component)]
+    struct Health(u32);
+    #[derive(Component)]
+    struct Player;
+    #[derive(Component)]
+    struct Enemy;
+    #[derive(Component)]
+    struct Frozen;
+
+    fn id<C: Component>(world: &World) -> ComponentId {
+        world.components.get_component_id::<C>().unwrap()
+    }
+
+    #[test]
+    fn test_markers_of_entities() {
+        let mut world = World::default();
+        let plain = world.spawn(Health(1));
+        let player = world.spawn((Player, Health(2)));
+        let frozen_enemy = world.spawn((Health(3), Frozen, Enemy));
+        let only_markers = world.spawn((Enemy, Player, Frozen));
+        let (player_id, enemy_id, frozen_id) = (
+            id::<Player>(&world),
+            id::<Enemy>(&world),
+            id::<Frozen>(&world),
+        );
+
+        let markers_of = |entity| world.marker_components_of(entity).collect::<Vec<_>>();
+        assert_eq!(markers_of(plain), []);
+        assert_eq!(markers_of(player), [player_id]);
+        let mut expected = vec![enemy_id, frozen_id];
+        expected.sort();
+        assert_eq!(markers_of(frozen_enemy), expected);
+        let mut expected = vec![player_id, enemy_id, frozen_id];
+        expected.sort();
+        assert_eq!(markers_of(only_markers), expected);
+        assert!(!markers_of(only_markers).contains(&id::<Health>(&world)));
+
+        world.despawn(player);
+        assert_eq!(world.marker_components_of(player).count(), 0);
+    }
+
+    #[test]
+    fn test_markers_query() {
+        let mut world = World::default();
+        world.spawn(Health(1));
+        world.spawn((Player, Health(2)));
+        world.spawn((Health(3), Frozen, Enemy));
+        world.spawn((Enemy, Player, Frozen));
+        let (player_id, enemy_id, health_id) = (
+            id::<Player>(&world),
+            id::<Enemy>(&world),
+            id::<Health>(&world),
+        );
+
+        let mut seen = Vec::new();
+        for (entity, markers) in world.query_shared::<(EntityId, Markers)>() {
+            assert_eq!(markers.len(), markers.iter().count());
+            assert_eq!(markers.is_empty(), markers.iter().next().is_none());
+            assert_eq!(
+                markers.iter().collect::<Vec<_>>(),
+                world.marker_components_of(entity).collect::<Vec<_>>()
+            );
+            assert!(!markers.contains(health_id));
+            assert_eq!(
+                markers.contains(player_id),
+                world.contains_component::<Player>(entity)
+            );
+            assert_eq!(
+                markers.contains(enemy_id),
+                world.contains_component::<Enemy>(entity)
+            );
+            let mut names: Vec<&str> = markers.names(world.components()).collect();
+            names.sort();
+            seen.push(names);
+        }
+        seen.sort();
+        let mut expected = vec![
+            vec![],
+            vec![type_name::<Player>()],
+            vec![type_name::<Enemy>(), type_name::<Frozen>()],
+            vec![
+                type_name::<Enemy>(),
+                type_name::<Frozen>(),
+                type_name::<Player>(),
+            ],
+        ];
+        expected.iter_mut().for_each(|names| names.sort());
+        expected.sort();
+        assert_eq!(seen, expected);
+
+        // Markers can be combined with filters and with mutable accesses.
+        for (health, markers) in world.query_filtered::<(&mut Health, Markers), Has<Enemy>>() {
+            assert!(markers.contains(enemy_id));
+            health.0 = 0;
+        }
+        assert_eq!(world.query::<&Health>().filter(|h| h.0 == 0).count(), 1);
+    }
+}
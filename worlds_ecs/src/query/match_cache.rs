@@ -0,0 +1,174 @@
+use crate::archetype::key::PrimeArchKey;
+use std::{borrow::Borrow, collections::HashMap};
+
+/// Whether any storage matched a query's [`PrimeArchKey`], as of the amount of storages that were checked.
+///
+/// Storages are never removed, and the key of a storage never changes, so only the storages that were created
+/// since the last check need to be checked, and a key that matched keeps matching. Asking a record whether a key
+/// that didn't match can match now is two integer comparisons, as long as no storage was created since.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct MatchRecord {
+    /// The amount of storages that were checked.
+    checked: usize,
+    /// Whether one of the checked storages matched.
+    matched: bool,
+}
+
+impl MatchRecord {
+    /// Returns `false` if none of the storages (with the keys `storage_keys`) match `pkey`, checking only the
+    /// storages that were created since the last call.
+    #[inline]
+    pub(crate) fn can_match(
+        &mut self,
+        storage_keys: &[impl Borrow<PrimeArchKey>],
+        pkey: PrimeArchKey,
+    ) -> bool {
+        if !self.matched && self.checked < storage_keys.len() {
+            self.matched = !pkey.is_exact_archetype(PrimeArchKey::NEVER_MATCHES)
+                && storage_keys[self.checked..]
+                    .iter()
+                    .any(|storage_key| storage_key.borrow().is_sub_archetype(pkey));
+            self.checked = storage_keys.len();
+        }
+        self.matched
+    }
+}
+
+/// The [`MatchRecord`]s of the keys that the storages were queried with, so a query that can't match anything
+/// returns an empty iterator right away, without visiting the storages. Queries that can match are unaffected.
+#[derive(Default)]
+pub(crate) struct MatchCache {
+    records: HashMap<PrimeArchKey, MatchRecord>,
+}
+
+impl MatchCache {
+    /// Returns `false` if none of the storages (with the keys `storage_keys`) match `pkey`
+    /// (see [`MatchRecord::can_match`]).
+    #[inline]
+    pub(crate) fn can_match(
+        &mut self,
+        storage_keys: &[impl Borrow<PrimeArchKey>],
+        pkey: PrimeArchKey,
+    ) -> bool {
+        if pkey.is_exact_archetype(PrimeArchKey::NEVER_MATCHES) {
+            return false;
+        }
+        self.records
+            .entry(pkey)
+            .or_default()
+            .can_match(storage_keys, pkey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MatchRecord;
+    use crate::prelude::*;
+
+    #[derive(Component)]
+    struct Position(f32);
+    #[derive(Component, Clone)]
+    struct Explosion(u32);
+    #[derive(Component)]
+    struct Debris;
+
+    #[test]
+    fn test_match_record() {
+        let mut world = World::default();
+        world.spawn(Position(0.0));
+        world.spawn(Explosion(0));
+        let keys = |world: &World| world.storages.arch_storages.storage_keys().to_vec();
+        let (position, explosion) = (
+            world.resolve_query_key::<&Position>().unwrap(),
+            world.resolve_query_key::<&Explosion>().unwrap(),
+        );
+        let mut both = position;
+        both.merge_with(explosion);
+
+        let mut record = MatchRecord::default();
+        assert!(!record.can_match(&keys(&world), both));
+        assert_eq!(record.checked, 2);
+        world.spawn(Position(1.0));
+        assert!(!record.can_match(&keys(&world), both));
+        assert_eq!(record.checked, 2);
+        world.spawn((Position(1.0), Explosion(1)));
+        assert!(record.can_match(&keys(&world), both));
+        assert_eq!(record.checked, 3);
+        // A key that matched keeps matching, without checking the new storages.
+        world.spawn((Position(1.0), Explosion(1), Debris));
+        assert!(record.can_match(&keys(&world), both));
+        assert_eq!(record.checked, 3);
+    }
+
+    #[test]
+    fn test_empty_query_unbails() {
+        let mut world = World::default();
+        world.register_cloneable_component::<Explosion>();
+        world.spawn(Position(0.0));
+        for _ in 0..3 {
+            assert_eq!(world.query::<&Explosion>().count(), 0);
+            assert_eq!(world.query::<&Explosion>().total_matched(), 0);
+            assert_eq!(world.query::<&Explosion>().size_hint(), (0, Some(0)));
+            assert_eq!(
+                world.query_filtered::<&Position, Has<Explosion>>().count(),
+                0
+            );
+        }
+        // The first frame a matching entity appears, it's yielded, including from a storage that only matches
+        // because it stores more components.
+        world.spawn((Position(1.0), Explosion(7), Debris));
+        assert_eq!(
+            world.query::<&Explosion>().map(|e| e.0).collect::<Vec<_>>(),
+            [7]
+        );
+        assert_eq!(
+            world
+                .query_filtered::<&Position, Has<Explosion>>()
+                .map(|p| p.0)
+                .collect::<Vec<_>>(),
+            [1.0]
+        );
+        world.spawn(Explosion(8));
+        assert_eq!(world.query::<&Explosion>().count(), 2);
+        // Despawning doesn't remove the storages, so the queries keep matching them.
+        world.despawn_matching::<Has<Explosion>>();
+        assert_eq!(world.query::<&Explosion>().count(), 0);
+        world.spawn(Explosion(9));
+        assert_eq!(world.query::<&Explosion>().count(), 1);
+    }
+
+    #[test]
+    fn test_empty_query_state_unbails() {
+        let mut world = World::default();
+        world.spawn(Position(0.0));
+        // `Explosion` isn't even registered, so the state can't match anything until it is.
+        let mut explosions = QueryState::<&Explosion>::new(&world);
+        let mut debris = QueryState::<&Position, Has<Debris>>::new_filtered(&world);
+        let key = world.precompute_query_key::<&Explosion>();
+        for _ in 0..3 {
+            assert_eq!(explosions.iter(&mut world).count(), 0);
+            assert_eq!(debris.iter(&mut world).count(), 0);
+            assert_eq!(world.query_with_key::<&Explosion>(key).count(), 0);
+        }
+
+        world.spawn((Explosion(1), Debris));
+        assert_eq!(
+            explosions.iter(&mut world).map(|e| e.0).collect::<Vec<_>>(),
+            [1]
+        );
+        assert_eq!(debris.iter(&mut world).count(), 0);
+        world.spawn((Position(2.0), Debris));
+        assert_eq!(
+            debris.iter(&mut world).map(|p| p.0).collect::<Vec<_>>(),
+            [2.0]
+        );
+
+        // A key that was precomputed once the components are registered bails until they are spawned.
+        let mut world = World::default();
+        world.register_cloneable_component::<Explosion>();
+        let key = world.precompute_query_key::<&Explosion>();
+        assert_eq!(world.query_with_key::<&Explosion>(key).count(), 0);
+        world.spawn(Explosion(3));
+        assert_eq!(world.query_with_key::<&Explosion>(key).count(), 1);
+    }
+}
@@ -1,15 +1,34 @@
 #![allow(missing_docs)] // TODO: Remove
 
 pub mod arch_query;
+pub mod filter_cache;
+pub mod markers;
+pub(crate) mod match_cache;
 pub mod query_data;
 pub mod query_filter;
+pub mod query_iter;
+pub mod query_key;
+#[cfg(feature = "diagnostics")]
+pub mod query_profile;
+pub mod query_state;
+pub mod query_view;
+pub mod value_filter;
 
 pub use arch_query::*;
+pub use filter_cache::*;
+pub use markers::*;
 pub use query_filter::*;
+pub use query_iter::*;
+pub use query_key::*;
+#[cfg(feature = "diagnostics")]
+pub use query_profile::*;
+pub use query_state::*;
+pub use query_view::*;
+pub use value_filter::*;
 
 #[cfg(test)]
 mod tests {
-    use crate::{entity::EntityId, prelude::*};
+    use crate::{entity::EntityId, prelude::*, test_utils::*};
 
     #[derive(Component)]
     struct A(usize);
@@ -20,6 +39,10 @@ mod tests {
     #[derive(Component)]
     struct C(usize);
 
+    /// Never spawned, so never registered.
+    #[derive(Component)]
+    struct Never;
+
     #[test]
     fn test_basic_component_queries_1() {
         let mut world = World::default();
@@ -230,9 +253,7 @@ mod tests {
             .query::<(&A, Has<B>)>()
             .for_each(|(A(a), has)| assert_eq!(*a < 4, !has));
 
-        world
-            .query_filtered::<&B, (Has<A>, Has<C>)>()
-            .for_each(|_| assert!(false));
+        assert_eq!(world.query_filtered::<&B, (Has<A>, Has<C>)>().count(), 0);
 
         assert_eq!(
             world.query_filtered::<&B, Or<(Has<A>, Has<C>)>>().count(),
@@ -258,6 +279,81 @@ mod tests {
         assert_eq!(world.query_filtered::<(), Has<(A, B)>>().count(), 3);
     }
 
+    #[test]
+    fn test_unregistered_components() {
+        let mut world = World::default();
+        world.spawn((A(1), B(String::from("Cart"))));
+        world.spawn(A(2));
+
+        assert_eq!(world.query::<&Never>().count(), 0);
+        assert_eq!(world.query::<(&A, &mut Never)>().count(), 0);
+        assert_eq!(world.query::<&Never>().total_matched(), 0);
+        assert_eq!(world.query_filtered::<&Never, Has<A>>().count(), 0);
+        assert_eq!(world.query_filtered::<&A, Has<Never>>().count(), 0);
+        assert_eq!(world.query_filtered::<&A, Not<Has<Never>>>().count(), 2);
+        assert_eq!(world.query_filtered::<&A, Matches<Never>>().count(), 0);
+
+        assert!(world
+            .query::<(&A, Option<&Never>)>()
+            .all(|(_, never)| never.is_none()));
+        assert!(world
+            .query::<(&A, Option<&mut Never>)>()
+            .all(|(_, never)| never.is_none()));
+        assert_eq!(world.query::<(&A, Option<&Never>)>().count(), 2);
+        assert_eq!(
+            world
+                .query::<(&A, Has<Never>)>()
+                .filter(|(_, has)| *has)
+                .count(),
+            0
+        );
+        assert_eq!(world.query::<Option<&Never>>().count(), 2);
+        // Nothing was registered by querying.
+        assert!(world.components.get_component_id::<Never>().is_none());
+    }
+
+    #[test]
+    fn test_with_and_without_skip_whole_storages() {
+        let mut world = World::default();
+        for i in 0..10 {
+            world.spawn((A(i), B(i.to_string())));
+            world.spawn(B(i.to_string()));
+            world.spawn((B(i.to_string()), C(i)));
+        }
+        for storage in world
+            .storages
+            .arch_storages
+            .iter_storages_with_matching_archetype(crate::archetype::key::PrimeArchKey::IDENTITY)
+        {
+            let has_a = storage.contains_archetype::<A>(&world.components);
+            let has_c = storage.contains_archetype::<C>(&world.components);
+            let decided = |has| match has {
+                true => StorageFilterResult::AllMatch,
+                false => StorageFilterResult::NoneMatch,
+            };
+            assert_eq!(
+                <With<A> as ArchFilter>::filter_storage(storage, &world.components),
+                decided(has_a)
+            );
+            assert_eq!(
+                <Without<A> as ArchFilter>::filter_storage(storage, &world.components),
+                decided(!has_a)
+            );
+            assert_eq!(
+                <Or<(With<A>, With<C>)> as ArchFilter>::filter_storage(storage, &world.components),
+                decided(has_a || has_c)
+            );
+        }
+        assert_eq!(world.query_filtered::<&B, With<A>>().count(), 10);
+        assert_eq!(world.query_filtered::<&mut B, Without<A>>().count(), 20);
+        assert_eq!(
+            world
+                .query_filtered::<&B, (Without<A>, Without<C>)>()
+                .count(),
+            10
+        );
+    }
+
     #[test]
     fn test_querying_entity_ids() {
         let mut world = World::default();
@@ -272,4 +368,595 @@ mod tests {
             .query_filtered::<EntityId, Has<(C, B)>>()
             .for_each(|eid| assert_eq!(eid, alice_id));
     }
+
+    #[test]
+    fn test_iter_with_entities() {
+        fn entities_of<Q: ArchQuery, F: ArchFilter>(
+            query: QueryIter<'_, Q, F>,
+        ) -> std::collections::HashSet<EntityId> {
+            set(query.iter_with_entities().map(|(eid, _)| eid))
+        }
+
+        let mut world = World::default();
+        let cart_id = world.spawn((A(1), B(String::from("Cart"))));
+        let alice_id = world.spawn((A(2), C(2)));
+        let james_id = world.spawn((A(3), B(String::from("James")), C(3)));
+        world.spawn(C(4));
+
+        assert_eq!(
+            entities_of(world.query::<&A>()),
+            set([cart_id, alice_id, james_id])
+        );
+        assert_eq!(
+            entities_of(world.query_filtered::<&A, Has<C>>()),
+            set([alice_id, james_id])
+        );
+        assert_eq!(
+            entities_of(world.query_filtered::<&B, Not<Has<C>>>()),
+            set([cart_id])
+        );
+
+        // Each entity is paired with its own component.
+        let expected = [(cart_id, 10), (alice_id, 20), (james_id, 30)];
+        for (eid, a) in world.query::<&mut A>().iter_with_entities() {
+            a.0 = expected.iter().find(|(id, _)| *id == eid).unwrap().1;
+        }
+        for (eid, a) in expected {
+            assert_eq!(world.get_component::<A>(eid).unwrap().0, a);
+        }
+    }
+
+    #[test]
+    fn test_enumerate_dense() {
+        let mut world = World::default();
+        let mut expected = Vec::new();
+        for i in 0..10 {
+            expected.push(world.spawn((A(i), B(i.to_string()))));
+            expected.push(world.spawn((A(i), C(i))));
+        }
+        world.spawn(B(String::from("Unmatched")));
+
+        let query = world.query::<(EntityId, &A)>();
+        assert_eq!(query.total_matched(), 20);
+        assert_eq!(query.size_hint(), (20, Some(20)));
+
+        let query = query.enumerate_dense();
+        let mut slots: Vec<Option<EntityId>> = vec![None; query.total()];
+        for (dense_index, (eid, _)) in query {
+            assert!(slots[dense_index].replace(eid).is_none());
+        }
+        let mut slots: Vec<EntityId> = slots.into_iter().map(Option::unwrap).collect();
+        slots.sort_by_key(|eid| eid.id());
+        expected.sort_by_key(|eid| eid.id());
+        assert_eq!(slots, expected);
+    }
+
+    #[test]
+    fn test_enumerate_dense_filtered() {
+        let mut world = World::default();
+        for i in 0..10 {
+            world.spawn((A(i), B(i.to_string())));
+            world.spawn((A(i), C(i)));
+        }
+
+        let query = world.query_filtered::<&A, Has<C>>();
+        // The filter is only evaluated per-entity, so the cheap total is an upper bound.
+        assert_eq!(query.total_matched(), 20);
+        assert_eq!(query.total_matched_exact(), 10);
+
+        let query = world.query_filtered::<&A, Has<C>>().enumerate_dense_exact();
+        let total = query.total();
+        assert_eq!(total, 10);
+        let indices: Vec<usize> = query.map(|(dense_index, _)| dense_index).collect();
+        assert_eq!(indices, (0..total).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_query_state_registration() {
+        let mut world = World::default();
+        world.spawn(A(0));
+
+        let mut state = QueryState::<(EntityId, &C)>::new(&world);
+        assert!(!state.is_resolved());
+        assert_eq!(state.iter(&mut world).count(), 0);
+
+        let c = world.spawn((A(1), C(10)));
+        assert_eq!(
+            state
+                .iter(&mut world)
+                .map(|(e, c)| (e, c.0))
+                .collect::<Vec<_>>(),
+            vec![(c, 10)]
+        );
+        assert!(state.is_resolved());
+
+        let mut filtered = QueryState::<&mut A, Has<B>>::new_filtered(&world);
+        assert_eq!(filtered.iter(&mut world).count(), 0);
+        world.spawn((A(2), B(String::from("B"))));
+        filtered.iter(&mut world).for_each(|a| a.0 += 10);
+        assert_eq!(world.query::<&A>().map(|a| a.0).sum::<usize>(), 13);
+    }
+
+    #[test]
+    fn test_query_state_rebind() {
+        use std::any::{type_name, TypeId};
+
+        struct StaleC;
+
+        let mut world = World::default();
+        world.spawn(C(1));
+        let mut state = QueryState::<&C>::new(&world);
+        assert_eq!(state.iter(&mut world).count(), 1);
+
+        let c_id = world.components.get_component_id::<C>().unwrap();
+        let key = world.components.stable_key(c_id).unwrap().clone();
+        world.components.rebind_type(&key, TypeId::of::<StaleC>());
+        assert!(state.iter(&mut world).next().is_none());
+        world.rebind_components(|info| (info.name() == type_name::<C>()).then(TypeId::of::<C>));
+        assert_eq!(state.iter(&mut world).count(), 1);
+    }
+
+    thread_local! {
+        static EVALUATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    /// A filter that counts its evaluations: passes entities with an even [`A`].
+    struct CountedEvenA;
+
+    unsafe impl ArchQuery for CountedEvenA {
+        type Item<'a> = bool;
+
+        unsafe fn fetch(
+            arch_storage: *mut crate::world::storage::ArchEntityStorage,
+            index: crate::world::storage::arch_storage::ArchStorageIndex,
+            comp_factory: &ComponentFactory,
+            _ctx: FetchContext<'_>,
+        ) -> bool {
+            EVALUATIONS.with(|evaluations| evaluations.set(evaluations.get() + 1));
+            comp_factory
+                .get_component_id::<A>()
+                .and_then(|comp_id| (*arch_storage).get_component(index, comp_id))
+                .is_some_and(|a| a.deref::<A>().0 % 2 == 0)
+        }
+    }
+
+    fn evaluations() -> usize {
+        EVALUATIONS.with(|evaluations| evaluations.replace(0))
+    }
+
+    #[test]
+    fn test_cached_filter_evaluations() {
+        let mut world = World::default();
+        let entities: Vec<EntityId> = (0..10).map(|i| world.spawn(A(i))).collect();
+        (0..5).for_each(|i| {
+            world.spawn((A(i), C(i)));
+        });
+        let mut state = QueryState::<EntityId, Cached<CountedEvenA>>::new_filtered(&world);
+        evaluations();
+
+        assert_eq!(state.iter(&mut world).count(), 8);
+        assert_eq!(evaluations(), 15);
+        assert_eq!(state.iter(&mut world).count(), 8);
+        assert_eq!(evaluations(), 0);
+
+        // Only the storage that was mutated is evaluated again.
+        world.get_component_mut::<A>(entities[1]).unwrap().0 = 100;
+        assert_eq!(state.iter(&mut world).count(), 9);
+        assert_eq!(evaluations(), 10);
+
+        world.despawn(entities[0]);
+        world.spawn((A(6), C(6)));
+        assert_eq!(state.iter(&mut world).count(), 9);
+        assert_eq!(evaluations(), 15);
+
+        // Reading doesn't change the storages.
+        assert_eq!(world.query::<&A>().count(), 15);
+        assert_eq!(state.iter(&mut world).count(), 9);
+        assert_eq!(evaluations(), 0);
+
+        // Without a state, the filter isn't cached.
+        assert_eq!(
+            world
+                .query_filtered::<EntityId, Cached<CountedEvenA>>()
+                .count(),
+            9
+        );
+        assert_eq!(evaluations(), 15);
+    }
+
+    #[test]
+    fn test_cached_filter_matches_uncached() {
+        let mut world = World::default();
+        let mut state = QueryState::<EntityId, Cached<CountedEvenA>>::new_filtered(&world);
+        let mut alive: Vec<EntityId> = vec![world.spawn(A(0))];
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as usize
+        };
+
+        // Entities are spawned first, and only despawned afterwards.
+        for step in 0..600 {
+            match random() % 4 {
+                0 if step < 300 => alive.push(world.spawn(A(random() % 10))),
+                1 if step < 300 => alive.push(world.spawn((A(random() % 10), C(0)))),
+                0 | 1 if !alive.is_empty() => {
+                    let entity = alive.swap_remove(random() % alive.len());
+                    world.despawn(entity);
+                }
+                2 if !alive.is_empty() => {
+                    let entity = alive[random() % alive.len()];
+                    world.get_component_mut::<A>(entity).unwrap().0 = random() % 10;
+                }
+                _ => world.query::<&mut A>().for_each(|a| a.0 += 1),
+            }
+            let expected: Vec<EntityId> =
+                world.query_filtered::<EntityId, CountedEvenA>().collect();
+            let cached: Vec<EntityId> = state.iter(&mut world).collect();
+            assert_eq!(cached, expected);
+        }
+    }
+
+    /// A filter that passes entities with an even [`Fx<0>`](crate::test_utils::Fx).
+    struct EvenFx;
+
+    unsafe impl ArchQuery for EvenFx {
+        type Item<'a> = bool;
+
+        unsafe fn fetch(
+            arch_storage: *mut crate::world::storage::ArchEntityStorage,
+            index: crate::world::storage::arch_storage::ArchStorageIndex,
+            comp_factory: &ComponentFactory,
+            _ctx: FetchContext<'_>,
+        ) -> bool {
+            comp_factory
+                .get_component_id::<Fx<0>>()
+                .and_then(|comp_id| (*arch_storage).get_component(index, comp_id))
+                .is_some_and(|fx| fx.deref::<Fx<0>>().0 % 2 == 0)
+        }
+    }
+
+    #[test]
+    fn test_cached_filter_under_churn() {
+        let mut world = World::default();
+        let mut state = QueryState::<EntityId, Cached<EvenFx>>::new_filtered(&world);
+        let script = ChurnScript::new(0x2545_f491_4f6c_dd1d)
+            .steps(2000)
+            .archetypes(&[&[0], &[0, 1], &[1], &[0, 2, 3]]);
+        let alive = script.run_with(&mut world, |world, _| {
+            let expected: Vec<EntityId> = world.query_filtered::<EntityId, EvenFx>().collect();
+            let cached: Vec<EntityId> = state.iter(world).collect();
+            assert_eq!(cached, expected);
+        });
+        assert_query_count::<EntityId>(&mut world, alive.len());
+    }
+
+    /// Spawns A-only, B-only and AB entities, and returns them in that order.
+    fn spawn_a_b_ab(world: &mut World) -> [Vec<EntityId>; 3] {
+        let a_only = vec![world.spawn(A(0)), world.spawn(A(1))];
+        let b_only = vec![world.spawn(B(String::from("b")))];
+        let ab = vec![
+            world.spawn((A(2), B(String::from("ab")))),
+            world.spawn((A(3), B(String::from("ab")))),
+        ];
+        [a_only, b_only, ab]
+    }
+
+    fn set(ids: impl IntoIterator<Item = EntityId>) -> std::collections::HashSet<EntityId> {
+        ids.into_iter().collect()
+    }
+
+    #[test]
+    fn test_has_doesnt_narrow_matches_does() {
+        let mut world = World::default();
+        let [a_only, b_only, ab] = spawn_a_b_ab(&mut world);
+
+        // `&A` narrows to the entities with both.
+        assert_eq!(
+            set(world.query::<(EntityId, &B, &A)>().map(|(id, _, _)| id)),
+            set(ab.clone())
+        );
+        // `Matches<A>` narrows the same way, without fetching.
+        assert_eq!(
+            set(world
+                .query::<(EntityId, &B, Matches<A>)>()
+                .map(|(id, _, ())| id)),
+            set(ab.clone())
+        );
+        // `Has<A>` yields every entity with `B`, with whether it has `A`.
+        let has: Vec<(EntityId, bool)> = world
+            .query::<(EntityId, &B, Has<A>)>()
+            .map(|(id, _, has)| (id, has))
+            .collect();
+        assert_eq!(
+            set(has.iter().map(|(id, _)| *id)),
+            set(b_only.iter().chain(&ab).copied())
+        );
+        assert!(has.iter().all(|(id, has)| *has == ab.contains(id)));
+
+        // A multi-component archetype narrows to its super-archetypes.
+        assert_eq!(
+            set(world
+                .query::<(EntityId, Matches<(A, B)>)>()
+                .map(|(id, ())| id)),
+            set(ab.clone())
+        );
+        assert_eq!(
+            set(world.query::<(EntityId, Matches<A>)>().map(|(id, ())| id)),
+            set(a_only.iter().chain(&ab).copied())
+        );
+    }
+
+    #[test]
+    fn test_matches_conflicts_with_nothing() {
+        let mut world = World::default();
+        let [_, _, ab] = spawn_a_b_ab(&mut world);
+
+        // Presence after and before an access of the same component.
+        for (a, ()) in world.query::<(&mut A, Matches<(A, B)>)>() {
+            a.0 += 10;
+        }
+        for ((), a) in world.query::<(Matches<A>, &A)>() {
+            assert_eq!(a.0 >= 10, a.0 >= 12);
+        }
+        assert_eq!(
+            world.query::<(Matches<A>, Matches<A>, &B)>().count(),
+            ab.len()
+        );
+        assert_eq!(
+            world.query::<(Matches<(A, B)>, (Matches<B>, &A))>().count(),
+            ab.len()
+        );
+        let mut state = QueryState::<(Matches<B>, &B)>::new(&world);
+        assert_eq!(state.iter(&mut world).count(), 3);
+        assert!(world.query_one::<(Matches<B>, &A)>(ab[0]).is_some());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "worlds_ecs: query failed: the query accesses a component more than once"
+    )]
+    fn test_duplicate_accesses_still_panic() {
+        let mut world = World::default();
+        spawn_a_b_ab(&mut world);
+        world.query::<(Matches<A>, &A, &A)>().count();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "worlds_ecs: query_filtered failed: the query accesses a component more than once"
+    )]
+    fn test_duplicate_accesses_of_unregistered_components_panic() {
+        #[derive(Component)]
+        struct Unregistered;
+
+        let mut world = World::default();
+        spawn_a_b_ab(&mut world);
+        // Without duplicates, a query of an unregistered component is just empty.
+        assert_eq!(world.query::<(&Unregistered, &A)>().count(), 0);
+        world
+            .query_filtered::<(&mut Unregistered, (EntityId, &Unregistered)), Has<A>>()
+            .count();
+    }
+
+    #[test]
+    fn test_matches_and_has_with_not_filters() {
+        let mut world = World::default();
+        let [a_only, b_only, ab] = spawn_a_b_ab(&mut world);
+        let even = |ids: &[EntityId], world: &mut World| -> Vec<EntityId> {
+            ids.iter()
+                .copied()
+                .filter(|id| world.get_component::<A>(*id).is_some_and(|a| a.0 % 2 == 0))
+                .collect()
+        };
+
+        for (query, expected) in [
+            (
+                world
+                    .query_filtered::<EntityId, Not<Has<A>>>()
+                    .collect::<Vec<_>>(),
+                b_only.clone(),
+            ),
+            (
+                world
+                    .query_filtered::<EntityId, Not<Matches<A>>>()
+                    .collect(),
+                b_only.clone(),
+            ),
+            (
+                world.query_filtered::<EntityId, Matches<A>>().collect(),
+                [&a_only[..], &ab].concat(),
+            ),
+            (
+                world
+                    .query_filtered::<(EntityId, Matches<A>), Not<Has<B>>>()
+                    .map(|(id, ())| id)
+                    .collect(),
+                a_only.clone(),
+            ),
+            (
+                world
+                    .query_filtered::<(EntityId, Has<B>), Not<Matches<B>>>()
+                    .map(|(id, _)| id)
+                    .collect(),
+                a_only.clone(),
+            ),
+            (
+                world
+                    .query_filtered::<(EntityId, Matches<A>), Not<Matches<A>>>()
+                    .map(|(id, ())| id)
+                    .collect(),
+                vec![],
+            ),
+        ] {
+            assert_eq!(set(query), set(expected));
+        }
+
+        // Combined with a filter that's evaluated per entity.
+        let expected = even(&a_only, &mut world);
+        assert_eq!(
+            set(world.query_filtered::<EntityId, (Not<Matches<B>>, CountedEvenA)>()),
+            set(expected)
+        );
+        let expected = [
+            even(&[&a_only[..], &ab].concat(), &mut world),
+            b_only.clone(),
+        ]
+        .concat();
+        assert_eq!(
+            set(world.query_filtered::<EntityId, Or<(Not<Matches<A>>, CountedEvenA)>>()),
+            set(expected)
+        );
+        let expected = [b_only.clone(), ab.clone()].concat();
+        assert_eq!(
+            set(world
+                .query_filtered::<EntityId, (Or<(Matches<B>, CountedEvenA)>, Not<CountedEvenA>)>()),
+            set(expected
+                .into_iter()
+                .filter(|id| !even(&[*id], &mut world).contains(id)))
+        );
+    }
+
+    thread_local! {
+        static STORAGE_EVALUATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    /// A filter that's decided for whole storages like [`Matches<A>`], and counts how many times it's decided.
+    struct CountedMatchesA;
+
+    unsafe impl ArchQuery for CountedMatchesA {
+        type Item<'a> = ();
+
+        unsafe fn fetch(
+            _arch_storage: *mut crate::world::storage::ArchEntityStorage,
+            _index: crate::world::storage::arch_storage::ArchStorageIndex,
+            _comp_factory: &ComponentFactory,
+            _ctx: FetchContext<'_>,
+        ) {
+        }
+
+        fn filter_storage(
+            arch_storage: &crate::world::storage::ArchEntityStorage,
+            comp_factory: &ComponentFactory,
+        ) -> StorageFilterResult {
+            STORAGE_EVALUATIONS.with(|evaluations| evaluations.set(evaluations.get() + 1));
+            <Matches<A> as ArchQuery>::filter_storage(arch_storage, comp_factory)
+        }
+    }
+
+    #[test]
+    fn test_not_and_or_are_decided_once_per_storage() {
+        let mut world = World::default();
+        for i in 0..50 {
+            world.spawn(A(i));
+            world.spawn((A(i), B(String::from("ab"))));
+            world.spawn(B(String::from("b")));
+        }
+        let storages = world.storages.arch_storages.storage_count();
+        let decisions = || STORAGE_EVALUATIONS.with(|evaluations| evaluations.replace(0));
+        decisions();
+
+        // Next to a filter that's evaluated per entity, so the storages aren't skipped as a whole.
+        let passed = world
+            .query_filtered::<EntityId, (Not<CountedMatchesA>, CountedEvenA)>()
+            .count();
+        assert_eq!(passed, 0);
+        assert!(decisions() <= 2 * storages);
+        let passed = world
+            .query_filtered::<EntityId, Or<(CountedMatchesA, CountedEvenA)>>()
+            .count();
+        assert_eq!(passed, 100);
+        assert!(decisions() <= 2 * storages);
+        // Data queries decide their `Not`s once per storage too.
+        let without_a = world
+            .query::<(&B, Not<CountedMatchesA>)>()
+            .filter(|(_, without_a)| *without_a)
+            .count();
+        assert_eq!(without_a, 50);
+        assert!(decisions() <= storages);
+        evaluations();
+    }
+
+    #[derive(Tag)]
+    struct Poisoned;
+    /// Never registered.
+    #[derive(Tag)]
+    struct Frozen;
+
+    #[test]
+    fn test_tag_filters() {
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Poisoned>();
+        let mut world = World::with_tags(tagf);
+        let [a_only, b_only, ab] = spawn_a_b_ab(&mut world);
+        let all = [&a_only[..], &b_only, &ab].concat();
+        let poisoned: Vec<EntityId> = all.iter().copied().step_by(3).collect();
+        for entity in &poisoned {
+            unsafe { world.get_tag_tracker(*entity).unwrap().tag::<Poisoned>() };
+        }
+        let untagged = |ids: &[EntityId]| -> Vec<EntityId> {
+            ids.iter()
+                .copied()
+                .filter(|id| !poisoned.contains(id))
+                .collect()
+        };
+
+        assert_eq!(
+            set(world.query_filtered::<EntityId, Tagged<Poisoned>>()),
+            set(poisoned.clone())
+        );
+        assert_eq!(
+            set(world.query_filtered::<EntityId, Untagged<Poisoned>>()),
+            set(untagged(&all))
+        );
+        assert_eq!(
+            set(world.query_filtered::<EntityId, Not<Tagged<Poisoned>>>()),
+            set(untagged(&all))
+        );
+        // Combined with the components of the query, and with other filters.
+        let expected: Vec<EntityId> = [&a_only[..], &ab]
+            .concat()
+            .into_iter()
+            .filter(|id| poisoned.contains(id))
+            .collect();
+        assert_eq!(
+            set(world
+                .query_filtered::<(EntityId, &A), Tagged<Poisoned>>()
+                .map(|(id, _)| id)),
+            set(expected)
+        );
+        assert_eq!(
+            set(world.query_filtered::<EntityId, (Untagged<Poisoned>, Without<A>)>()),
+            set(untagged(&b_only))
+        );
+        assert_eq!(
+            set(world.query_filtered::<EntityId, Or<(Tagged<Poisoned>, With<B>)>>()),
+            set([&poisoned[..], &b_only, &ab].concat())
+        );
+        // As data, it's a `bool`.
+        for (id, tagged) in world.query::<(EntityId, Tagged<Poisoned>)>() {
+            assert_eq!(tagged, poisoned.contains(&id));
+        }
+
+        // An unregistered tag is on no entity.
+        assert_eq!(
+            world.query_filtered::<EntityId, Tagged<Frozen>>().count(),
+            0
+        );
+        assert_eq!(
+            world.query_filtered::<EntityId, Untagged<Frozen>>().count(),
+            all.len()
+        );
+
+        // A despawned entity's slot is reused untagged.
+        world.despawn(poisoned[0]);
+        let revived = world.spawn(A(100));
+        assert_eq!(revived.id(), poisoned[0].id());
+        assert_eq!(
+            set(world.query_filtered::<EntityId, Tagged<Poisoned>>()),
+            set(poisoned[1..].to_vec())
+        );
+    }
 }
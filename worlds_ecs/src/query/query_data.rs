@@ -1 +1,245 @@
+//! Queries whose items are user structs, see [`QueryItem`](worlds_derive::QueryItem).
+//!
+//! `#[derive(QueryItem)]` implements [`ArchQuery`](super::ArchQuery) for a struct with named fields and exactly one
+//! lifetime parameter, so querying the struct yields the struct itself, with each field fetched by the query of its
+//! type. The struct is a [`ReadOnlyArchQuery`](super::ReadOnlyArchQuery) if the query of every field is.
+//!
+//! ```
+//! use worlds_ecs::prelude::*;
+//!
+//! #[derive(Component)]
+//! struct Transform(f32);
+//! #[derive(Component)]
+//! struct Velocity(f32);
+//! #[derive(Component)]
+//! struct Damping(f32);
+//! #[derive(Component)]
+//! struct Frozen;
+//!
+//! #[derive(QueryItem)]
+//! struct Movement<'a> {
+//!     transform: &'a mut Transform,
+//!     velocity: &'a Velocity,
+//!     damping: Option<&'a Damping>,
+//!     entity: EntityId,
+//!     // A field whose item isn't its query (like `bool` for `Has<Frozen>`) names the query in an attribute.
+//!     #[query(Has<Frozen>)]
+//!     frozen: bool,
+//! }
+//!
+//! let mut world = World::default();
+//! world.spawn((Transform(0.0), Velocity(2.0)));
+//! world.spawn((Transform(0.0), Velocity(2.0), Damping(0.5)));
+//! world.spawn((Transform(0.0), Velocity(2.0), Frozen));
+//! for movement in world.query::<Movement>() {
+//!     if !movement.frozen {
+//!         let damping = movement.damping.map_or(1.0, |damping| damping.0);
+//!         movement.transform.0 += movement.velocity.0 * damping;
+//!     }
+//! }
+//! let mut transforms: Vec<f32> = world.query::<&Transform>().map(|t| t.0).collect();
+//! transforms.sort_by(f32::total_cmp);
+//! assert_eq!(transforms, [0.0, 1.0, 2.0]);
+//! ```
+//!
+//! Every field must be a query:
+//!
+//! ```compile_fail
+//! use worlds_ecs::prelude::*;
+//!
+//! #[derive(Component)]
+//! struct Transform(f32);
+//!
+//! #[derive(QueryItem)]
+//! struct Named<'a> {
+//!     transform: &'a Transform,
+//!     name: String,
+//! }
+//! ```
+//!
+//! And the struct must have exactly one lifetime parameter:
+//!
+//! ```compile_fail
+//! use worlds_ecs::prelude::*;
+//!
+//! #[derive(Component)]
+//! struct Transform(f32);
+//!
+//! #[derive(QueryItem)]
+//! struct Pair<'a, 'b> {
+//!     first: &'a Transform,
+//!     second: Option<&'b Transform>,
+//! }
+//! ```
 
+/// The items that the code generated by `#[derive(QueryItem)]` refers to.
+#[doc(hidden)]
+pub mod __derive {
+    pub use crate::{
+        archetype::key::PrimeArchKey,
+        component::{ComponentFactory, ComponentId},
+        query::{ArchQuery, FetchContext, ReadOnlyArchQuery, StorageDecisions},
+        world::{
+            access::AccessKind,
+            storage::{arch_storage::ArchStorageIndex, ArchEntityStorage},
+        },
+    };
+
+    /// Fails to compile (pointing at the field) if the query of a field isn't an [`ArchQuery`].
+    pub fn assert_query<Q: ArchQuery>() {}
+
+    /// A [`ReadOnlyArchQuery`] in a struct with the lifetime `'w`. The bounds of the generated
+    /// [`ReadOnlyArchQuery`] impl go through this trait so they mention the lifetime of the struct, which makes them
+    /// hold or not (instead of failing to compile when a field is mutable).
+    pub trait ReadOnlyField<'w> {}
+
+    impl<Q: ReadOnlyArchQuery> ReadOnlyField<'_> for Q {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Transform(i32);
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Damping(i32);
+    #[derive(Component)]
+    struct Frozen;
+
+    #[derive(QueryItem)]
+    struct Movement<'a> {
+        transform: &'a Transform,
+        velocity: &'a mut Velocity,
+        damping: Option<&'a Damping>,
+        entity: EntityId,
+        #[query(Has<Frozen>)]
+        frozen: bool,
+    }
+
+    #[derive(QueryItem)]
+    struct ReadTransform<'w> {
+        transform: &'w Transform,
+        entity: EntityId,
+    }
+
+    #[derive(QueryItem)]
+    #[allow(dead_code)]
+    struct TwiceTransform<'a> {
+        transform: &'a Transform,
+        again: &'a mut Transform,
+    }
+
+    fn world() -> World {
+        let mut world = World::default();
+        for i in 0..12 {
+            match i % 4 {
+                0 => world.spawn((Transform(i), Velocity(1))),
+                1 => world.spawn((Transform(i), Velocity(2), Damping(i))),
+                2 => world.spawn((Transform(i), Velocity(3), Frozen)),
+                _ => world.spawn(Transform(i)),
+            };
+        }
+        world
+    }
+
+    #[test]
+    fn test_named_fields_match_the_tuple_query() {
+        let mut world = world();
+        let tuples: Vec<_> = world
+            .query::<(
+                &Transform,
+                &mut Velocity,
+                Option<&Damping>,
+                EntityId,
+                Has<Frozen>,
+            )>()
+            .map(|(transform, velocity, damping, entity, frozen)| {
+                (*transform, *velocity, damping.copied(), entity, frozen)
+            })
+            .collect();
+        let structs: Vec<_> = world
+            .query::<Movement>()
+            .map(|movement| {
+                (
+                    *movement.transform,
+                    *movement.velocity,
+                    movement.damping.copied(),
+                    movement.entity,
+                    movement.frozen,
+                )
+            })
+            .collect();
+        assert_eq!(structs.len(), 9);
+        assert_eq!(structs, tuples);
+
+        for movement in world.query::<Movement>() {
+            if !movement.frozen {
+                movement.velocity.0 += movement.damping.map_or(10, |damping| damping.0);
+            }
+        }
+        for (entity, velocity, damping, frozen) in
+            world.query::<(EntityId, &Velocity, Option<&Damping>, Has<Frozen>)>()
+        {
+            let before = tuples.iter().find(|tuple| tuple.3 == entity).unwrap().1;
+            let expected = match (frozen, damping) {
+                (true, _) => before.0,
+                (false, Some(damping)) => before.0 + damping.0,
+                (false, None) => before.0 + 10,
+            };
+            assert_eq!(velocity.0, expected);
+        }
+    }
+
+    #[test]
+    fn test_read_only_struct() {
+        let world = world();
+        let mut read: Vec<_> = world
+            .query_shared::<ReadTransform>()
+            .map(|item| (item.entity, item.transform.0))
+            .collect();
+        read.sort_by_key(|(_, transform)| *transform);
+        assert_eq!(read.len(), 12);
+        assert!(read.iter().all(
+            |(entity, transform)| world.get_component::<Transform>(*entity)
+                == Some(&Transform(*transform))
+        ));
+    }
+
+    #[test]
+    fn test_accesses_are_recorded_per_field() {
+        let mut world = world();
+        world.record_access(true);
+        world.query::<Movement>().for_each(drop);
+        let kinds = |world: &mut World| {
+            let mut kinds: Vec<(ComponentId, AccessKind)> = world
+                .access_report()
+                .accesses
+                .iter()
+                .map(|access| (access.component, access.kind))
+                .collect();
+            kinds.sort_by_key(|(component, _)| *component);
+            kinds
+        };
+        let from_struct = kinds(&mut world);
+        world
+            .query::<(
+                &Transform,
+                &mut Velocity,
+                Option<&Damping>,
+                EntityId,
+                Has<Frozen>,
+            )>()
+            .for_each(drop);
+        assert_eq!(from_struct, kinds(&mut world));
+    }
+
+    #[test]
+    #[should_panic(expected = "the query accesses a component more than once")]
+    fn test_conflicting_fields() {
+        let mut world = world();
+        world.query::<TwiceTransform>().for_each(drop);
+    }
+}
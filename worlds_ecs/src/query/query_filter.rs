@@ -1,8 +1,14 @@
-use super::arch_query::ArchQuery;
+use super::arch_query::{ArchQuery, FetchContext, ReadOnlyArchQuery};
 use crate::{
+    archetype::key::PrimeArchKey,
     archetype::Archetype,
-    prelude::ComponentFactory,
-    world::storage::{arch_storage::ArchStorageIndex, ArchEntityStorage},
+    prelude::{ComponentFactory, ComponentId},
+    tag::Tag,
+    utils::panics,
+    world::{
+        access::AccessKind,
+        storage::{arch_storage::ArchStorageIndex, ArchEntityStorage},
+    },
 };
 use std::marker::PhantomData;
 use worlds_derive::all_tuples;
@@ -11,25 +17,316 @@ pub struct Not<T>(PhantomData<T>);
 
 pub struct Or<T>(PhantomData<T>);
 
+/// Whether the entity has every component of the archetype `A`, as a `bool`.
+///
+/// `Has<A>` never narrows the entities that a query matches: in a data tuple, every entity that matches the rest of
+/// the tuple is yielded, with `true` or `false`. To only match the entities that have `A`, either fetch it (`&A`),
+/// or require it without fetching it with [`Matches<A>`]. As a filter, `Has<A>` does keep only the entities that
+/// have `A` (and `Not<Has<A>>` only the ones that don't).
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Armor(u32);
+///
+/// let mut world = World::default();
+/// world.spawn(Health(10));
+/// world.spawn((Health(20), Armor(5)));
+///
+/// // `Has` doesn't narrow: both entities are yielded.
+/// let mut has: Vec<_> = world.query::<(&Health, Has<Armor>)>().map(|(h, a)| (h.0, a)).collect();
+/// has.sort();
+/// assert_eq!(has, [(10, false), (20, true)]);
+/// // `&Armor` and `Matches<Armor>` do.
+/// assert_eq!(world.query::<(&Health, &Armor)>().count(), 1);
+/// assert_eq!(world.query::<(&Health, Matches<Armor>)>().count(), 1);
+/// // As a filter, `Has` narrows too.
+/// assert_eq!(world.query_filtered::<&Health, Has<Armor>>().count(), 1);
+/// ```
 pub struct Has<T>(PhantomData<T>);
 
+/// Requires every component of the archetype `A`, without fetching any of them (its item is `()`).
+///
+/// Unlike [`Has<A>`], which yields a `bool` for every entity, `Matches<A>` narrows the query like `&A` would, so it's
+/// the way to select the entities that have a component when its value isn't needed. Since nothing is accessed, it
+/// doesn't conflict with anything: `(&mut A, Matches<A>)` and `(Matches<A>, Matches<A>)` are valid queries.
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Position(i32);
+/// #[derive(Component)]
+/// struct Player;
+///
+/// let mut world = World::default();
+/// world.spawn(Position(0));
+/// world.spawn((Position(1), Player));
+///
+/// for (position, ()) in world.query::<(&mut Position, Matches<Player>)>() {
+///     position.0 += 10;
+/// }
+/// let mut positions: Vec<_> = world.query::<&Position>().map(|p| p.0).collect();
+/// positions.sort();
+/// assert_eq!(positions, [0, 11]);
+/// ```
+pub struct Matches<A>(PhantomData<A>);
+
+/// A filter that keeps the entities with every component of the archetype `A`. It's decided once per storage (see
+/// [`ArchFilter::filter_storage`]), so it never evaluates an entity on its own. An alias of [`Has<A>`].
+pub type With<A> = Has<A>;
+
+/// A filter that keeps the entities without some component of the archetype `A`. Like [`With<A>`], it's decided once
+/// per storage. An alias of `Not<Has<A>>`.
+pub type Without<A> = Not<Has<A>>;
+
+/// A filter that keeps the entities that have the [`Tag`] `T` (see [`World::get_tag_tracker`]). Tags aren't part of
+/// the archetype of an entity, so it's evaluated for each entity, in the tag trackers of the world. If `T` isn't
+/// registered, no entity has it. In a data tuple, it's a `bool`, like [`Has`].
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Tag)]
+/// struct Poisoned;
+///
+/// let mut tagf = TagFactory::default();
+/// tagf.register_tag::<Poisoned>();
+/// let mut world = World::with_tags(tagf);
+/// let poisoned = world.spawn(Health(10));
+/// world.spawn(Health(20));
+/// // SAFETY: No other tracker of the entity is accessed.
+/// unsafe { world.get_tag_tracker(poisoned).unwrap().tag::<Poisoned>() };
+///
+/// for health in world.query_filtered::<&mut Health, Tagged<Poisoned>>() {
+///     health.0 -= 1;
+/// }
+/// let mut healths: Vec<u32> = world.query::<&Health>().map(|health| health.0).collect();
+/// healths.sort();
+/// assert_eq!(healths, [9, 20]);
+/// assert_eq!(world.query_filtered::<&Health, Untagged<Poisoned>>().count(), 1);
+/// ```
+///
+/// [`World::get_tag_tracker`]: crate::world::World::get_tag_tracker
 pub struct Tagged<T>(PhantomData<T>);
 
+/// A filter that keeps the entities that don't have the [`Tag`] `T`, the opposite of [`Tagged<T>`]. If `T` isn't
+/// registered, every entity passes.
 pub struct Untagged<T>(PhantomData<T>);
 
+/// A filter whose results are cached per storage when it's used in a [`QueryState`](super::QueryState),
+/// see [`FilterCache`](super::FilterCache). Elsewhere it behaves exactly like `F`.
+pub struct Cached<F>(PhantomData<F>);
+
+/// # Safety
+/// The implementor must ensure that [`ArchFilter::filter`] doesn't mutate any of the storage's data.
 pub unsafe trait ArchFilter
 where
     Self: Sized,
 {
     /// # Safety
     ///   1) The caller must ensure that the [`ArchStorageIndex`] is withing the bounds of the [`ArchStorage`]
-    /// (as specified in [`ArchStorage::get_component_unchecked`]).
+    ///      (as specified in [`ArchStorage::get_component_unchecked`]).
     ///   2) The caller must ensure that the raw pointer to [`ArchStorage`] is valid, and usable.
-    unsafe fn filter<'a>(
+    unsafe fn filter(
         arch_storage: *const ArchEntityStorage,
         index: ArchStorageIndex,
-        comp_factory: &'a ComponentFactory,
+        comp_factory: &ComponentFactory,
+        ctx: FetchContext<'_>,
     ) -> impl FilterResult;
+
+    /// Evaluate the filter for a whole storage at once, if possible. See [`ArchQuery::filter_storage`].
+    fn filter_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult;
+
+    /// Push what the filter decides for the storage. See [`ArchQuery::decide_storage`].
+    fn decide_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+        decisions: &mut StorageDecisions,
+    );
+
+    /// How many decisions [`ArchFilter::decide_storage`] pushes. See [`ArchQuery::STORAGE_DECISIONS`].
+    const STORAGE_DECISIONS: u32;
+
+    /// Like [`ArchFilter::filter_storage`], but combined with [`FilterResult::any`]. See [`ArchQuery::filter_storage_any`].
+    fn filter_storage_any(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult;
+
+    /// Call `f` with every registered component that the filter reads. See [`ArchQuery::for_each_access`].
+    fn for_each_access(comp_factory: &ComponentFactory, f: &mut dyn FnMut(ComponentId, AccessKind));
+
+    /// Whether the results of this filter are cached when it's used in a [`QueryState`](super::QueryState).
+    /// See [`ArchQuery::IS_CACHED_FILTER`].
+    const IS_CACHED: bool;
+}
+
+/// The result of evaluating a filter for a whole storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageFilterResult {
+    /// Every entity in the storage passes the filter.
+    AllMatch,
+    /// No entity in the storage passes the filter.
+    NoneMatch,
+    /// The filter needs to be evaluated for each entity.
+    PerEntity,
+}
+
+impl StorageFilterResult {
+    /// Combine two results, where an entity passes if it passes both.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// assert_eq!(StorageFilterResult::AllMatch.and(StorageFilterResult::PerEntity), StorageFilterResult::PerEntity);
+    /// assert_eq!(StorageFilterResult::PerEntity.and(StorageFilterResult::NoneMatch), StorageFilterResult::NoneMatch);
+    /// ```
+    pub fn and(self, other: StorageFilterResult) -> StorageFilterResult {
+        match (self, other) {
+            (Self::NoneMatch, _) | (_, Self::NoneMatch) => Self::NoneMatch,
+            (Self::AllMatch, Self::AllMatch) => Self::AllMatch,
+            _ => Self::PerEntity,
+        }
+    }
+
+    /// Combine two results, where an entity passes if it passes either.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// assert_eq!(StorageFilterResult::AllMatch.or(StorageFilterResult::PerEntity), StorageFilterResult::AllMatch);
+    /// assert_eq!(StorageFilterResult::PerEntity.or(StorageFilterResult::NoneMatch), StorageFilterResult::PerEntity);
+    /// ```
+    pub fn or(self, other: StorageFilterResult) -> StorageFilterResult {
+        match (self, other) {
+            (Self::AllMatch, _) | (_, Self::AllMatch) => Self::AllMatch,
+            (Self::NoneMatch, Self::NoneMatch) => Self::NoneMatch,
+            _ => Self::PerEntity,
+        }
+    }
+
+    /// Negate the result, where an entity passes if it doesn't pass.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// assert_eq!(StorageFilterResult::AllMatch.negate(), StorageFilterResult::NoneMatch);
+    /// // Whether an entity passes still depends on the entity.
+    /// assert_eq!(StorageFilterResult::PerEntity.negate(), StorageFilterResult::PerEntity);
+    /// ```
+    pub fn negate(self) -> StorageFilterResult {
+        match self {
+            Self::AllMatch => Self::NoneMatch,
+            Self::NoneMatch => Self::AllMatch,
+            Self::PerEntity => Self::PerEntity,
+        }
+    }
+}
+
+/// What the [`Not`] and [`Or`] filters of a query decided for a storage, in the order they appear in the query. They
+/// are decided once per storage (see [`ArchQuery::decide_storage`]), so evaluating them for each entity only reads
+/// their decision, and only evaluates the filter they wrap if it's [`StorageFilterResult::PerEntity`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageDecisions {
+    /// Two bits per decision, the first decision in the lowest bits.
+    bits: u64,
+    len: u32,
+}
+
+impl StorageDecisions {
+    /// The most decisions a query can make, so the most [`Not`] and [`Or`] filters it can have.
+    pub const CAPACITY: u32 = u64::BITS / 2;
+
+    /// The decisions of the query `Q` for the storage.
+    #[track_caller]
+    pub(crate) fn of_query<Q: ArchQuery>(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> Self {
+        let mut decisions = Self::default();
+        if Q::STORAGE_DECISIONS > 0 {
+            Q::decide_storage(arch_storage, comp_factory, &mut decisions);
+        }
+        decisions
+    }
+
+    /// The decisions of the filter `F` for the storage.
+    #[track_caller]
+    pub(crate) fn of_filter<F: ArchFilter>(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> Self {
+        let mut decisions = Self::default();
+        if F::STORAGE_DECISIONS > 0 {
+            F::decide_storage(arch_storage, comp_factory, &mut decisions);
+        }
+        decisions
+    }
+
+    /// Add a decision after the others.
+    ///
+    /// # Panics
+    /// If there are already [`StorageDecisions::CAPACITY`] decisions.
+    #[track_caller]
+    pub fn push(&mut self, decision: StorageFilterResult) {
+        if self.len == Self::CAPACITY {
+            panics::fail(
+                "query",
+                "the query has too many Not and Or filters",
+                &[("max", &Self::CAPACITY)],
+            )
+        }
+        let bits: u64 = match decision {
+            StorageFilterResult::AllMatch => 0,
+            StorageFilterResult::NoneMatch => 1,
+            StorageFilterResult::PerEntity => 2,
+        };
+        self.bits |= bits << (2 * self.len);
+        self.len += 1;
+    }
+
+    /// The first decision, or [`StorageFilterResult::PerEntity`] if there is none.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut decisions = StorageDecisions::default();
+    /// decisions.push(StorageFilterResult::NoneMatch);
+    /// decisions.push(StorageFilterResult::AllMatch);
+    /// assert_eq!(decisions.first(), StorageFilterResult::NoneMatch);
+    /// assert_eq!(decisions.skip(1).first(), StorageFilterResult::AllMatch);
+    /// assert_eq!(decisions.skip(2).first(), StorageFilterResult::PerEntity);
+    /// ```
+    #[inline]
+    pub fn first(self) -> StorageFilterResult {
+        if self.len == 0 {
+            return StorageFilterResult::PerEntity;
+        }
+        match self.bits & 0b11 {
+            0 => StorageFilterResult::AllMatch,
+            1 => StorageFilterResult::NoneMatch,
+            _ => StorageFilterResult::PerEntity,
+        }
+    }
+
+    /// The decisions after the first `n`.
+    #[inline]
+    pub fn skip(self, n: u32) -> Self {
+        let n = n.min(self.len);
+        StorageDecisions {
+            bits: self.bits.checked_shr(2 * n).unwrap_or(0),
+            len: self.len - n,
+        }
+    }
 }
 
 #[doc(hidden)]
@@ -54,60 +351,327 @@ impl FilterResult for bool {
     }
 }
 
+// SAFETY: Filters only read, they are evaluated through a `*const ArchEntityStorage`.
+unsafe impl<Q: ArchFilter> ReadOnlyArchQuery for Not<Q> {}
+// SAFETY: See above.
+unsafe impl<Q: ArchFilter> ReadOnlyArchQuery for Or<Q> {}
+// SAFETY: See above.
+unsafe impl<F: ArchFilter> ReadOnlyArchQuery for Cached<F> {}
+// SAFETY: `Has` only reads the archetype of the storage.
+unsafe impl<A: Archetype> ReadOnlyArchQuery for Has<A> {}
+// SAFETY: `Matches` doesn't access anything.
+unsafe impl<A: Archetype> ReadOnlyArchQuery for Matches<A> {}
+// SAFETY: `Tagged` only reads the tags of the entities, which aren't components.
+unsafe impl<T: Tag> ReadOnlyArchQuery for Tagged<T> {}
+// SAFETY: See above.
+unsafe impl<T: Tag> ReadOnlyArchQuery for Untagged<T> {}
+
 unsafe impl<Q: ArchFilter> ArchQuery for Not<Q> {
     type Item<'a> = bool;
 
-    unsafe fn fetch<'a>(
+    fn for_each_access(
+        comp_factory: &ComponentFactory,
+        f: &mut dyn FnMut(ComponentId, AccessKind),
+    ) {
+        Q::for_each_access(comp_factory, f);
+    }
+
+    const STORAGE_DECISIONS: u32 = 1 + Q::STORAGE_DECISIONS;
+
+    unsafe fn fetch(
         arch_storage: *mut ArchEntityStorage,
         index: ArchStorageIndex,
-        comp_factory: &'a ComponentFactory,
+        comp_factory: &ComponentFactory,
+        ctx: FetchContext<'_>,
     ) -> bool {
-        !Q::filter(arch_storage, index, comp_factory).collapse()
+        // A filter that's decided for the whole storage (like `Matches`) may not be decided by its per-entity value,
+        // so the decision for the storage is used when there is one.
+        match ctx.decisions().first() {
+            StorageFilterResult::AllMatch => true,
+            StorageFilterResult::NoneMatch => false,
+            StorageFilterResult::PerEntity => {
+                !Q::filter(arch_storage, index, comp_factory, ctx.skip_decisions(1)).collapse()
+            }
+        }
+    }
+
+    fn filter_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        Q::filter_storage(arch_storage, comp_factory).negate()
+    }
+
+    fn decide_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+        decisions: &mut StorageDecisions,
+    ) {
+        decisions.push(<Self as ArchQuery>::filter_storage(
+            arch_storage,
+            comp_factory,
+        ));
+        Q::decide_storage(arch_storage, comp_factory, decisions);
     }
 }
 
 unsafe impl<Q: ArchFilter> ArchQuery for Or<Q> {
     type Item<'a> = bool;
 
-    unsafe fn fetch<'a>(
+    fn for_each_access(
+        comp_factory: &ComponentFactory,
+        f: &mut dyn FnMut(ComponentId, AccessKind),
+    ) {
+        Q::for_each_access(comp_factory, f);
+    }
+
+    const STORAGE_DECISIONS: u32 = 1 + Q::STORAGE_DECISIONS;
+
+    unsafe fn fetch(
+        arch_storage: *mut ArchEntityStorage,
+        index: ArchStorageIndex,
+        comp_factory: &ComponentFactory,
+        ctx: FetchContext<'_>,
+    ) -> bool {
+        // See `Not::fetch`.
+        match ctx.decisions().first() {
+            StorageFilterResult::AllMatch => true,
+            StorageFilterResult::NoneMatch => false,
+            StorageFilterResult::PerEntity => {
+                Q::filter(arch_storage, index, comp_factory, ctx.skip_decisions(1)).any()
+            }
+        }
+    }
+
+    fn filter_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        Q::filter_storage_any(arch_storage, comp_factory)
+    }
+
+    fn decide_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+        decisions: &mut StorageDecisions,
+    ) {
+        decisions.push(<Self as ArchQuery>::filter_storage(
+            arch_storage,
+            comp_factory,
+        ));
+        Q::decide_storage(arch_storage, comp_factory, decisions);
+    }
+}
+
+unsafe impl<F: ArchFilter> ArchQuery for Cached<F> {
+    type Item<'a> = bool;
+
+    const IS_CACHED_FILTER: bool = true;
+
+    fn for_each_access(
+        comp_factory: &ComponentFactory,
+        f: &mut dyn FnMut(ComponentId, AccessKind),
+    ) {
+        F::for_each_access(comp_factory, f);
+    }
+
+    const STORAGE_DECISIONS: u32 = F::STORAGE_DECISIONS;
+
+    unsafe fn fetch(
         arch_storage: *mut ArchEntityStorage,
         index: ArchStorageIndex,
-        comp_factory: &'a ComponentFactory,
+        comp_factory: &ComponentFactory,
+        ctx: FetchContext<'_>,
     ) -> bool {
-        Q::filter(arch_storage, index, comp_factory).any()
+        F::filter(arch_storage, index, comp_factory, ctx).collapse()
+    }
+
+    fn filter_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        F::filter_storage(arch_storage, comp_factory)
+    }
+
+    fn filter_storage_any(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        F::filter_storage_any(arch_storage, comp_factory)
+    }
+
+    fn decide_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+        decisions: &mut StorageDecisions,
+    ) {
+        F::decide_storage(arch_storage, comp_factory, decisions);
     }
 }
 
 unsafe impl<A: Archetype> ArchQuery for Has<A> {
     type Item<'a> = bool;
 
-    unsafe fn fetch<'a>(
+    unsafe fn fetch(
         arch_storage: *mut ArchEntityStorage,
         _index: ArchStorageIndex,
-        comp_factory: &'a ComponentFactory,
+        comp_factory: &ComponentFactory,
+        _ctx: FetchContext<'_>,
     ) -> bool {
         (*arch_storage).contains_archetype::<A>(comp_factory)
     }
 
+    fn filter_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        if arch_storage.contains_archetype::<A>(comp_factory) {
+            StorageFilterResult::AllMatch
+        } else {
+            StorageFilterResult::NoneMatch
+        }
+    }
+
     fn merge_prime_arch_key_with(
-        _pkey: &mut crate::utils::prime_key::PrimeArchKey,
+        _pkey: &mut crate::archetype::key::PrimeArchKey,
         _comp_factory: &ComponentFactory,
     ) {
         // No need, because this doesn't change the archetype.
     }
 }
 
+unsafe impl<A: Archetype> ArchQuery for Matches<A> {
+    type Item<'a> = ();
+
+    fn is_resolvable(comp_factory: &ComponentFactory) -> bool {
+        A::prime_key(comp_factory).is_some()
+    }
+
+    unsafe fn fetch(
+        _arch_storage: *mut ArchEntityStorage,
+        _index: ArchStorageIndex,
+        _comp_factory: &ComponentFactory,
+        _ctx: FetchContext<'_>,
+    ) {
+    }
+
+    fn filter_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        if arch_storage.contains_archetype::<A>(comp_factory) {
+            StorageFilterResult::AllMatch
+        } else {
+            StorageFilterResult::NoneMatch
+        }
+    }
+
+    fn merge_required_presence_with(pkey: &mut PrimeArchKey, comp_factory: &ComponentFactory) {
+        let arch_info = A::arch_info(comp_factory).expect("Can't query unregistered component");
+        for comp_id in arch_info.component_ids() {
+            let comp_key = comp_id.prime_key();
+            if !pkey.is_sub_archetype(comp_key) {
+                pkey.merge_with(comp_key);
+            }
+        }
+    }
+}
+
+/// Returns `true` if the entity at the index of the storage has the tag `T`.
+///
+/// # Safety
+/// The same as [`ArchQuery::fetch`], and no tracker of the entity may be mutated while it's checked.
+unsafe fn is_tagged<T: Tag>(
+    arch_storage: *const ArchEntityStorage,
+    index: ArchStorageIndex,
+    ctx: FetchContext<'_>,
+) -> bool {
+    let tags = ctx.tags();
+    let Some(tag_id) = tags.tag_factory().tag_id::<T>() else {
+        return false;
+    };
+    // The entities in the storages are alive, so the tracker at their dense index is their own.
+    tags.is_tagged_id((*arch_storage).get_entity_at_unchecked(index), tag_id)
+}
+
+// The tags aren't known when a storage is filtered, so `Tagged` and `Untagged` are always evaluated per entity.
+
+unsafe impl<T: Tag> ArchQuery for Tagged<T> {
+    type Item<'a> = bool;
+
+    unsafe fn fetch(
+        arch_storage: *mut ArchEntityStorage,
+        index: ArchStorageIndex,
+        _comp_factory: &ComponentFactory,
+        ctx: FetchContext<'_>,
+    ) -> bool {
+        is_tagged::<T>(arch_storage, index, ctx)
+    }
+}
+
+unsafe impl<T: Tag> ArchQuery for Untagged<T> {
+    type Item<'a> = bool;
+
+    unsafe fn fetch(
+        arch_storage: *mut ArchEntityStorage,
+        index: ArchStorageIndex,
+        _comp_factory: &ComponentFactory,
+        ctx: FetchContext<'_>,
+    ) -> bool {
+        !is_tagged::<T>(arch_storage, index, ctx)
+    }
+}
+
 unsafe impl<Q: ArchQuery> ArchFilter for Q
 where
     for<'a> Q::Item<'a>: FilterResult,
 {
-    unsafe fn filter<'a>(
+    unsafe fn filter(
         arch_storage: *const ArchEntityStorage,
         index: ArchStorageIndex,
-        comp_factory: &'a ComponentFactory,
+        comp_factory: &ComponentFactory,
+        ctx: FetchContext<'_>,
     ) -> impl FilterResult {
-        Q::fetch(arch_storage as *mut ArchEntityStorage, index, comp_factory)
+        Q::fetch(
+            arch_storage as *mut ArchEntityStorage,
+            index,
+            comp_factory,
+            ctx,
+        )
+    }
+
+    fn filter_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        Q::filter_storage(arch_storage, comp_factory)
+    }
+
+    fn filter_storage_any(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        Q::filter_storage_any(arch_storage, comp_factory)
     }
+
+    fn decide_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+        decisions: &mut StorageDecisions,
+    ) {
+        Q::decide_storage(arch_storage, comp_factory, decisions)
+    }
+
+    const STORAGE_DECISIONS: u32 = Q::STORAGE_DECISIONS;
+
+    fn for_each_access(
+        comp_factory: &ComponentFactory,
+        f: &mut dyn FnMut(ComponentId, AccessKind),
+    ) {
+        <Q as ArchQuery>::for_each_access(comp_factory, f)
+    }
+
+    const IS_CACHED: bool = Q::IS_CACHED_FILTER;
 }
 
 macro_rules! impl_filtering_value_for_tuple {
@@ -0,0 +1,835 @@
+use super::{
+    arch_query::{ArchQuery, FetchContext},
+    filter_cache::FilterCache,
+    query_filter::{ArchFilter, StorageFilterResult},
+    value_filter::{test_component, ComponentPredicate},
+    FilterResult,
+};
+use crate::{
+    archetype::key::PrimeArchKey,
+    component::Component,
+    entity::{EntityId, EntityIdSet},
+    prelude::ComponentFactory,
+    utils::panics,
+    world::group::{GroupId, Groups},
+    world::storage::{
+        arch_storage::ArchStorageIndex,
+        storages::{ArchStorageId, ArchStorages},
+        tag_storage::EntityTags,
+        ArchEntityStorage,
+    },
+};
+use std::{iter::Enumerate, marker::PhantomData, ptr};
+#[cfg(feature = "diagnostics")]
+use {super::query_profile::QueryProfile, std::time::Instant};
+
+/// The index of a query match in a dense, zero-based numbering of all the matches of a single
+/// query pass. See [`QueryIter::enumerate_dense`].
+pub type DenseIndex = usize;
+
+/// The iterator returned by [`World::query`](crate::prelude::World::query) and
+/// [`World::query_filtered`](crate::prelude::World::query_filtered).
+pub struct QueryIter<'w, Q: ArchQuery, F: ArchFilter = ()> {
+    arch_storages: *mut ArchStorages,
+    comp_factory: &'w ComponentFactory,
+    pkey: PrimeArchKey,
+    filtered: bool,
+    filter_cache: Option<&'w FilterCache>,
+    /// The groups of the world, so the matches can be limited to a group (see [`Self::in_group`]).
+    groups: Option<&'w Groups>,
+    group: Option<&'w EntityIdSet>,
+    next_storage: ArchStorageId,
+    /// The storages are visited from [`ArchStorages::query_start`] up to (but not including) this one.
+    end_storage: usize,
+    /// Where to stop after wrapping around to the first storage, if the storages weren't visited from the first one.
+    wrap_end: Option<usize>,
+    current_storage: *mut ArchEntityStorage,
+    current_rows: Option<&'w [u64]>,
+    /// Whether the filter was decided for the whole current storage, so it isn't evaluated per entity.
+    current_all_pass: bool,
+    /// The contexts that the query and the filter are fetched with, with their decisions for the current storage.
+    query_ctx: FetchContext<'w>,
+    filter_ctx: FetchContext<'w>,
+    current_index: usize,
+    current_len: usize,
+    _storages: PhantomData<&'w mut ArchStorages>,
+    _query: PhantomData<fn() -> (Q, F)>,
+}
+
+impl<'w, Q: ArchQuery, F: ArchFilter> QueryIter<'w, Q, F> {
+    /// # Safety
+    ///  1) The caller must ensure that the raw pointer to [`ArchStorages`] is valid, and usable, and that
+    ///     nothing else accesses the storages for `'w`.
+    ///  2) `filtered` must be false only if `F` doesn't filter out anything (like `()`).
+    #[track_caller]
+    pub(crate) unsafe fn new(
+        arch_storages: *mut ArchStorages,
+        comp_factory: &'w ComponentFactory,
+        tags: EntityTags<'w>,
+        filtered: bool,
+    ) -> Self {
+        let pkey = Q::resolve_prime_arch_key(comp_factory);
+        Self::matching(arch_storages, comp_factory, tags, pkey, filtered)
+    }
+
+    /// Like [`Self::with_key`], but if none of the storages match `pkey` (see [`ArchStorages::can_match`]), the
+    /// iterator is [empty](Self::empty), so it doesn't visit the storages at all.
+    /// # Safety
+    /// The same safety requirements as [`Self::with_key`].
+    #[inline]
+    pub(crate) unsafe fn matching(
+        arch_storages: *mut ArchStorages,
+        comp_factory: &'w ComponentFactory,
+        tags: EntityTags<'w>,
+        pkey: PrimeArchKey,
+        filtered: bool,
+    ) -> Self {
+        if (*arch_storages).can_match(pkey) {
+            Self::with_key(arch_storages, comp_factory, tags, pkey, filtered)
+        } else {
+            Self::empty(arch_storages, comp_factory, tags, filtered)
+        }
+    }
+
+    /// An iterator that doesn't yield anything, and returns `None` without visiting the storages.
+    /// # Safety
+    /// The same safety requirements as [`Self::new`].
+    pub(crate) unsafe fn empty(
+        arch_storages: *mut ArchStorages,
+        comp_factory: &'w ComponentFactory,
+        tags: EntityTags<'w>,
+        filtered: bool,
+    ) -> Self {
+        let mut iter = Self::with_key(
+            arch_storages,
+            comp_factory,
+            tags,
+            PrimeArchKey::NEVER_MATCHES,
+            filtered,
+        );
+        // Past the last storage, so finding the next storage fails on its first comparison.
+        iter.next_storage = ArchStorageId(usize::MAX);
+        iter.wrap_end = None;
+        iter
+    }
+
+    /// Like [`Self::new`], with an already merged [`PrimeArchKey`] for `Q`.
+    /// # Safety
+    ///  1) The same safety requirements as [`Self::new`].
+    ///  2) `pkey` must have been merged by `Q` with the same [`ComponentFactory`] (or be [`PrimeArchKey::NEVER_MATCHES`]).
+    pub(crate) unsafe fn with_key(
+        arch_storages: *mut ArchStorages,
+        comp_factory: &'w ComponentFactory,
+        tags: EntityTags<'w>,
+        pkey: PrimeArchKey,
+        filtered: bool,
+    ) -> Self {
+        let start = (*arch_storages).query_start();
+        QueryIter {
+            arch_storages,
+            comp_factory,
+            pkey,
+            filtered,
+            filter_cache: None,
+            groups: None,
+            group: None,
+            next_storage: start,
+            end_storage: usize::MAX,
+            wrap_end: (start.0 > 0).then_some(start.0),
+            current_storage: ptr::null_mut(),
+            current_rows: None,
+            current_all_pass: true,
+            query_ctx: FetchContext::new(tags),
+            filter_ctx: FetchContext::new(tags),
+            current_index: 0,
+            current_len: 0,
+            _storages: PhantomData,
+            _query: PhantomData,
+        }
+    }
+
+    /// Use the cached results of the filter instead of evaluating it.
+    /// # Safety
+    /// The cache must have been refreshed for `F`, with the same storages and [`ComponentFactory`], and nothing
+    /// may have changed in the storages since.
+    pub(crate) unsafe fn with_filter_cache(mut self, filter_cache: &'w FilterCache) -> Self {
+        self.filter_cache = Some(filter_cache);
+        self
+    }
+
+    /// Make the groups of the world available to [`Self::in_group`].
+    pub(crate) fn with_groups(mut self, groups: &'w Groups) -> Self {
+        self.groups = Some(groups);
+        self
+    }
+
+    /// Only yield the matches of entities that are members of the group (see
+    /// [`World::create_group`](crate::world::World::create_group)). Each match is looked up in the group, so this is
+    /// a filter that is evaluated per entity, like [`Has`](super::Has) on a component that only some entities of a
+    /// storage have.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let selected = world.create_group("selected");
+    /// let a = world.spawn(Health(1));
+    /// world.spawn(Health(2));
+    /// world.group_insert(selected, a);
+    /// let healths: Vec<u32> = world.query::<&Health>().in_group(selected).map(|h| h.0).collect();
+    /// assert_eq!(healths, [1]);
+    /// ```
+    ///
+    /// # Panics
+    /// If the group wasn't created in the world that is queried.
+    #[track_caller]
+    pub fn in_group(mut self, group: GroupId) -> Self {
+        let Some(groups) = self.groups else {
+            panics::fail(
+                "in_group",
+                "the query iterator can't see the groups of its world",
+                &[],
+            );
+        };
+        self.group = Some(groups.members(group));
+        self
+    }
+
+    /// The total amount of entities that are stored in the storages matched by this query, regardless of
+    /// how much of the iterator was consumed. This is cheap: it doesn't visit any entity.
+    ///
+    /// For unfiltered queries this is exactly the amount of items the full pass yields. For filtered queries (and
+    /// queries [in a group](Self::in_group)) this is only an upper bound, because the filter is evaluated per-entity
+    /// during iteration (see [`Self::total_matched_exact`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Player;
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(0));
+    /// world.spawn((Health(1), Player));
+    /// assert_eq!(world.query::<&Health>().total_matched(), 2);
+    /// // For filtered queries it's an upper bound: the filter isn't evaluated.
+    /// let players = world.query_filtered::<&Health, With<Player>>();
+    /// assert_eq!(players.total_matched(), 2);
+    /// assert_eq!(players.count(), 1);
+    /// ```
+    pub fn total_matched(&self) -> usize {
+        if self.pkey.is_exact_archetype(PrimeArchKey::NEVER_MATCHES) {
+            return 0;
+        }
+        // SAFETY: The storages are valid for 'w, and we only read the lengths.
+        unsafe {
+            (*self.arch_storages)
+                .iter_storages_with_matching_archetype(self.pkey)
+                .map(|storage| storage.len())
+                .sum()
+        }
+    }
+
+    /// The exact amount of items the full pass of this query yields. For filtered queries, this evaluates the
+    /// filter for every entity in the matched storages (without fetching the query data), so prefer
+    /// [`Self::total_matched`] when an upper bound is enough.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let a = world.spawn(Health(0));
+    /// world.spawn(Health(1));
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { world.get_tag_tracker(a).unwrap().tag::<Selected>() };
+    /// let query = world.query_filtered::<&Health, Tagged<Selected>>();
+    /// // Tags are evaluated per entity, so only the exact count is exact.
+    /// assert_eq!(query.total_matched(), 2);
+    /// assert_eq!(query.total_matched_exact(), 1);
+    /// ```
+    pub fn total_matched_exact(&self) -> usize {
+        if !self.filtered && self.group.is_none() {
+            return self.total_matched();
+        }
+        let in_group = |storage: &ArchEntityStorage, index: ArchStorageIndex| {
+            self.group
+                .is_none_or(|group| group.contains(storage.entities()[index.0]))
+        };
+        // SAFETY: The storages are valid for 'w, and filters only read from the storages.
+        unsafe {
+            (*self.arch_storages)
+                .iter_storages_with_matching_archetype(self.pkey)
+                .map(
+                    |storage| match F::filter_storage(storage, self.comp_factory) {
+                        StorageFilterResult::AllMatch if self.group.is_none() => storage.len(),
+                        StorageFilterResult::AllMatch => storage
+                            .iter_indices()
+                            .filter(|index| in_group(storage, *index))
+                            .count(),
+                        StorageFilterResult::NoneMatch => 0,
+                        StorageFilterResult::PerEntity => {
+                            let ctx = self.filter_ctx.for_filter::<F>(storage, self.comp_factory);
+                            storage
+                                .iter_indices()
+                                .filter(|index| {
+                                    in_group(storage, *index)
+                                        && F::filter(storage, *index, self.comp_factory, ctx)
+                                            .collapse()
+                                })
+                                .count()
+                        }
+                    },
+                )
+                .sum()
+        }
+    }
+
+    /// Enumerate the matches of this query with [`DenseIndex`]es: the items are numbered `0..n` in iteration order,
+    /// with no gaps, where `n` is at most [`DenseEnumerate::total`]. This makes it possible to size an external buffer
+    /// once, before consuming the query, and write each item to its own slot.
+    ///
+    /// [`DenseEnumerate::total`] is [`Self::total_matched`], so it's exact for unfiltered queries and an upper bound for
+    /// filtered queries. Use [`Self::enumerate_dense_exact`] if the exact count is needed for filtered queries.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    ///
+    /// let mut world = World::default();
+    /// for i in 0..3 {
+    ///     world.spawn(Position(i as f32));
+    /// }
+    /// let query = world.query::<&Position>().enumerate_dense();
+    /// // Size the buffer once, and write each item to its own slot.
+    /// let mut buffer = vec![0.0; query.total()];
+    /// for (index, position) in query {
+    ///     buffer[index] = position.0;
+    /// }
+    /// buffer.sort_by(f32::total_cmp);
+    /// assert_eq!(buffer, [0.0, 1.0, 2.0]);
+    /// ```
+    pub fn enumerate_dense(self) -> DenseEnumerate<Self> {
+        DenseEnumerate {
+            total: self.total_matched(),
+            inner: self.enumerate(),
+        }
+    }
+
+    /// Like [`Self::enumerate_dense`], but [`DenseEnumerate::total`] is always exact, at the cost of a pre-pass
+    /// that evaluates the filter (see [`Self::total_matched_exact`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Visible;
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Visible>();
+    /// let mut world = World::with_tags(tagf);
+    /// let visible = world.spawn(Position(1.0));
+    /// world.spawn(Position(2.0));
+    /// // SAFETY: `Visible` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { world.get_tag_tracker(visible).unwrap().tag::<Visible>() };
+    /// let query = world.query_filtered::<&Position, Tagged<Visible>>().enumerate_dense_exact();
+    /// assert_eq!(query.total(), 1);
+    /// let mut buffer = vec![0.0; query.total()];
+    /// for (index, position) in query {
+    ///     buffer[index] = position.0;
+    /// }
+    /// assert_eq!(buffer, [1.0]);
+    /// ```
+    pub fn enumerate_dense_exact(self) -> DenseEnumerate<Self> {
+        DenseEnumerate {
+            total: self.total_matched_exact(),
+            inner: self.enumerate(),
+        }
+    }
+
+    /// The amount of entities left in the storages that weren't visited yet (including the current one).
+    fn remaining_upper_bound(&self) -> usize {
+        let mut remaining = self.current_len - self.current_index;
+        // SAFETY: The storages are valid for 'w, and we only read the lengths.
+        let arch_storages = unsafe { &*self.arch_storages };
+        let mut visit = |mut sid: ArchStorageId, end: usize| {
+            while let Some(id) = arch_storages
+                .next_storage_with_matching_archetype(sid, self.pkey)
+                .filter(|id| id.0 < end)
+            {
+                remaining += arch_storages
+                    .get_storage(id)
+                    .map_or(0, |storage| storage.len());
+                sid = ArchStorageId(id.0 + 1);
+            }
+        };
+        visit(self.next_storage, self.end_storage);
+        if let Some(end) = self.wrap_end {
+            visit(ArchStorageId(0), end);
+        }
+        remaining
+    }
+
+    /// Yield the [`EntityId`] of each match together with its item, so code that is generic over `Q` doesn't need
+    /// `EntityId` in the query to recover the entity. Filters and groups apply as usual.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(3));
+    /// let matches: Vec<(EntityId, u32)> = world
+    ///     .query::<&Health>()
+    ///     .iter_with_entities()
+    ///     .map(|(entity, health)| (entity, health.0))
+    ///     .collect();
+    /// assert_eq!(matches, [(entity, 3)]);
+    /// ```
+    pub fn iter_with_entities(self) -> WithEntities<'w, Q, F> {
+        WithEntities { inner: self }
+    }
+
+    /// Only yield the matches whose component `C` passes `predicate` (see [`ComponentPredicate`]). The predicate is
+    /// tested before the rest of the query is fetched, so when it rejects most entities, their other components are
+    /// never touched. Entities without `C` don't pass. More predicates can be chained on the returned iterator.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, PartialEq)]
+    /// struct Team(u8);
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn((Team(1), Health(10)));
+    /// world.spawn((Team(2), Health(0)));
+    /// world.spawn((Team(2), Health(5)));
+    /// let alive_enemies = world
+    ///     .query::<&Health>()
+    ///     .filter_component(Equals(Team(2)))
+    ///     .filter_component(|health: &Health| health.0 > 0);
+    /// assert_eq!(alive_enemies.count(), 1);
+    /// ```
+    pub fn filter_component<C: Component>(
+        self,
+        predicate: impl ComponentPredicate<C> + 'w,
+    ) -> FilterComponent<'w, Q, F> {
+        FilterComponent {
+            inner: self,
+            tests: Vec::new(),
+        }
+        .filter_component(predicate)
+    }
+
+    /// Iterate as usual, while accumulating a timing breakdown of the iteration into `profile` (see
+    /// [`QueryProfile`]): how long matching the storages took, how long setting up each storage took, and how
+    /// long the rest of the iteration took. The measurements live in a separate iterator, so unprofiled queries
+    /// don't pay for them, but they read the clock twice per item, so the profiled iteration is slower than the
+    /// iteration it measures. Only with the `diagnostics` feature, which isn't supported on
+    /// `wasm32-unknown-unknown` (it has no clock).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(3));
+    /// let mut profile = QueryProfile::default();
+    /// for health in world.query::<&Health>().profiled(&mut profile) {
+    ///     assert_eq!(health.0, 3);
+    /// }
+    /// assert_eq!(profile.entities_yielded, 1);
+    /// println!("{profile}");
+    /// ```
+    #[cfg(feature = "diagnostics")]
+    pub fn profiled(self, profile: &mut QueryProfile) -> Profiled<'w, '_, Q, F> {
+        profile.passes += 1;
+        Profiled {
+            inner: self,
+            profile,
+        }
+    }
+
+    /// Advance to the next index that passes the filter (and the group), moving on to the next matching storage
+    /// once the current one is exhausted. The index is in the bounds of `current_storage`.
+    #[inline]
+    fn next_index(&mut self) -> Option<ArchStorageIndex> {
+        loop {
+            if let Some(index) = self.next_index_in_storage() {
+                return Some(index);
+            }
+            let sid = self.next_matching_storage()?;
+            // SAFETY: The storage matches the key of the query, and it's the next one to visit.
+            unsafe { self.enter_storage(sid) };
+        }
+    }
+
+    /// Advance to the next index of the current storage that passes the filter (and the group), or return `None`
+    /// once the current storage is exhausted.
+    #[inline]
+    fn next_index_in_storage(&mut self) -> Option<ArchStorageIndex> {
+        while self.current_index < self.current_len {
+            let index = ArchStorageIndex(self.current_index);
+            self.current_index += 1;
+            // SAFETY: The index is in bounds of the current storage, and the storage pointer is valid for 'w.
+            unsafe {
+                let passes = match self.current_rows {
+                    Some(rows) => rows[index.0 / 64] & (1 << (index.0 % 64)) != 0,
+                    None if self.current_all_pass => true,
+                    None => F::filter(
+                        self.current_storage,
+                        index,
+                        self.comp_factory,
+                        self.filter_ctx,
+                    )
+                    .collapse(),
+                };
+                let passes = passes
+                    && self.group.is_none_or(|group| {
+                        group.contains((*self.current_storage).entities()[index.0])
+                    });
+                if passes {
+                    return Some(index);
+                }
+            }
+        }
+        None
+    }
+
+    /// Find the next storage to visit that matches the key of the query, wrapping around to the storages before
+    /// the first visited one.
+    #[inline]
+    fn next_matching_storage(&mut self) -> Option<ArchStorageId> {
+        loop {
+            // SAFETY: The storages are valid for 'w.
+            let sid = unsafe { &*self.arch_storages }
+                .next_storage_with_matching_archetype(self.next_storage, self.pkey)
+                .filter(|sid| sid.0 < self.end_storage);
+            match sid {
+                Some(sid) => {
+                    self.next_storage = ArchStorageId(sid.0 + 1);
+                    return Some(sid);
+                }
+                None => {
+                    self.end_storage = self.wrap_end.take()?;
+                    self.next_storage = ArchStorageId(0);
+                }
+            }
+        }
+    }
+
+    /// Like [`Self::next_matching_storage`], comparing the storages one at a time, so the storages that were
+    /// considered are counted into `considered`.
+    #[cfg(feature = "diagnostics")]
+    fn next_matching_storage_counted(&mut self, considered: &mut usize) -> Option<ArchStorageId> {
+        // SAFETY: The storages are valid for 'w.
+        let arch_storages = unsafe { &*self.arch_storages };
+        loop {
+            let end = self.end_storage.min(arch_storages.storage_count());
+            while self.next_storage.0 < end {
+                let sid = self.next_storage;
+                self.next_storage = ArchStorageId(sid.0 + 1);
+                *considered += 1;
+                if (arch_storages.storage_key(sid))
+                    .is_some_and(|key| key.is_sub_archetype(self.pkey))
+                {
+                    return Some(sid);
+                }
+            }
+            self.end_storage = self.wrap_end.take()?;
+            self.next_storage = ArchStorageId(0);
+        }
+    }
+
+    /// Make the storage with the [`ArchStorageId`] `sid` the current storage, deciding the filter for the whole
+    /// storage if it can be.
+    /// # Safety
+    /// The storage must be one that [`Self::next_matching_storage`] returned, and it must not have been visited yet.
+    #[inline]
+    unsafe fn enter_storage(&mut self, sid: ArchStorageId) {
+        self.current_storage = (*self.arch_storages).get_storage_mut_unchecked(sid);
+        if Q::IS_MUTABLE {
+            // Nothing was fetched from this storage yet, so no reference into its components is alive.
+            (*self.current_storage).make_unique();
+        }
+        self.current_len = (*self.current_storage).len();
+        self.current_rows = self.filter_cache.and_then(|cache| cache.rows(sid));
+        self.current_all_pass = true;
+        if self.filtered && self.current_rows.is_none() {
+            match F::filter_storage(&*self.current_storage, self.comp_factory) {
+                StorageFilterResult::AllMatch => {}
+                StorageFilterResult::NoneMatch => self.current_len = 0,
+                StorageFilterResult::PerEntity => {
+                    self.current_all_pass = false;
+                    self.filter_ctx = self
+                        .filter_ctx
+                        .for_filter::<F>(&*self.current_storage, self.comp_factory);
+                }
+            }
+        }
+        self.query_ctx = self
+            .query_ctx
+            .for_query::<Q>(&*self.current_storage, self.comp_factory);
+        self.current_index = 0;
+    }
+}
+
+impl<'w, Q: ArchQuery, F: ArchFilter> Iterator for QueryIter<'w, Q, F> {
+    type Item = Q::Item<'w>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.next_index()?;
+        // SAFETY: The index is in bounds of the current storage, the storage pointer is valid for 'w, and every
+        // index is fetched at most once.
+        Some(unsafe {
+            Q::fetch(
+                self.current_storage,
+                index,
+                self.comp_factory,
+                self.query_ctx,
+            )
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let upper = self.remaining_upper_bound();
+        if self.filtered || self.group.is_some() {
+            (0, Some(upper))
+        } else {
+            (upper, Some(upper))
+        }
+    }
+}
+
+/// A test of the values of the components of an entity, see [`QueryIter::filter_component`].
+type ValueTest<'w> =
+    Box<dyn Fn(&ArchEntityStorage, ArchStorageIndex, &ComponentFactory) -> bool + 'w>;
+
+/// An iterator that yields the matches of a query whose components pass predicates.
+/// See [`QueryIter::filter_component`].
+pub struct FilterComponent<'w, Q: ArchQuery, F: ArchFilter = ()> {
+    inner: QueryIter<'w, Q, F>,
+    tests: Vec<ValueTest<'w>>,
+}
+
+impl<'w, Q: ArchQuery, F: ArchFilter> FilterComponent<'w, Q, F> {
+    /// Also require the component `C` to pass `predicate`, like [`QueryIter::filter_component`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Armor(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn((Health(10), Armor(0)));
+    /// world.spawn((Health(10), Armor(5)));
+    /// world.spawn((Health(0), Armor(5)));
+    /// let tanks = world
+    ///     .query::<(&Health, &Armor)>()
+    ///     .filter_component(|health: &Health| health.0 > 0)
+    ///     .filter_component(|armor: &Armor| armor.0 > 0);
+    /// assert_eq!(tanks.count(), 1);
+    /// ```
+    pub fn filter_component<C: Component>(
+        mut self,
+        predicate: impl ComponentPredicate<C> + 'w,
+    ) -> Self {
+        self.tests
+            .push(Box::new(move |storage, index, comp_factory| {
+                // SAFETY: The index came from the iterator, so it's in the bounds of the storage, and nothing of the
+                // entity was fetched yet.
+                unsafe { test_component::<C>(storage, index, comp_factory, &predicate) }
+            }));
+        self
+    }
+}
+
+impl<'w, Q: ArchQuery, F: ArchFilter> Iterator for FilterComponent<'w, Q, F> {
+    type Item = Q::Item<'w>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let inner = &mut self.inner;
+        loop {
+            let index = inner.next_index()?;
+            // SAFETY: The storage pointer is valid for 'w, and the index is in its bounds. The tests only read, and
+            // their references end before the item is fetched.
+            unsafe {
+                let storage = &*inner.current_storage;
+                if self
+                    .tests
+                    .iter()
+                    .all(|test| test(storage, index, inner.comp_factory))
+                {
+                    return Some(Q::fetch(
+                        inner.current_storage,
+                        index,
+                        inner.comp_factory,
+                        inner.query_ctx,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+/// An iterator that yields the matches of a query, together with their [`EntityId`]s.
+/// See [`QueryIter::iter_with_entities`].
+pub struct WithEntities<'w, Q: ArchQuery, F: ArchFilter = ()> {
+    inner: QueryIter<'w, Q, F>,
+}
+
+impl<'w, Q: ArchQuery, F: ArchFilter> Iterator for WithEntities<'w, Q, F> {
+    type Item = (EntityId, Q::Item<'w>);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let inner = &mut self.inner;
+        let index = inner.next_index()?;
+        // SAFETY: The index is in bounds of the current storage, the storage pointer is valid for 'w, and every
+        // index is fetched at most once.
+        unsafe {
+            let entity = (*inner.current_storage).get_entity_at_unchecked(index);
+            Some((
+                entity,
+                Q::fetch(
+                    inner.current_storage,
+                    index,
+                    inner.comp_factory,
+                    inner.query_ctx,
+                ),
+            ))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// An iterator that yields the matches of a query, while accumulating a timing breakdown of the iteration into a
+/// [`QueryProfile`]. See [`QueryIter::profiled`].
+#[cfg(feature = "diagnostics")]
+pub struct Profiled<'w, 'p, Q: ArchQuery, F: ArchFilter = ()> {
+    inner: QueryIter<'w, Q, F>,
+    profile: &'p mut QueryProfile,
+}
+
+#[cfg(feature = "diagnostics")]
+impl<'w, Q: ArchQuery, F: ArchFilter> Iterator for Profiled<'w, '_, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = Instant::now();
+        let (inner, profile) = (&mut self.inner, &mut *self.profile);
+        let item = loop {
+            let visited = inner.current_index;
+            if let Some(index) = inner.next_index_in_storage() {
+                profile.entities_rejected += inner.current_index - visited - 1;
+                profile.entities_yielded += 1;
+                // SAFETY: The index is in bounds of the current storage, the storage pointer is valid for 'w, and
+                // every index is fetched at most once.
+                break Some(unsafe {
+                    Q::fetch(
+                        inner.current_storage,
+                        index,
+                        inner.comp_factory,
+                        inner.query_ctx,
+                    )
+                });
+            }
+            profile.entities_rejected += inner.current_index - visited;
+            let matching = Instant::now();
+            let sid = inner.next_matching_storage_counted(&mut profile.storages_considered);
+            let setup = Instant::now();
+            profile.matching_time += setup - matching;
+            let Some(sid) = sid else {
+                break None;
+            };
+            // SAFETY: The storage matches the key of the query, and it's the next one to visit.
+            unsafe {
+                inner.enter_storage(sid);
+                // The entities of a storage that the filter rejected as a whole.
+                profile.entities_rejected += (*inner.current_storage).len() - inner.current_len;
+            }
+            profile.setup_time += setup.elapsed();
+            profile.storages_matched += 1;
+        };
+        profile.total_time += start.elapsed();
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// An iterator that yields the matches of a query, together with their [`DenseIndex`].
+/// See [`QueryIter::enumerate_dense`].
+pub struct DenseEnumerate<I> {
+    inner: Enumerate<I>,
+    total: usize,
+}
+
+impl<I> DenseEnumerate<I> {
+    /// The amount of [`DenseIndex`]es this iterator may yield: every yielded index is smaller than this.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(1));
+    /// world.spawn(Health(2));
+    /// let query = world.query::<&Health>().enumerate_dense();
+    /// assert_eq!(query.total(), 2);
+    /// assert!(query.map(|(index, _)| index).all(|index| index < 2));
+    /// ```
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+impl<I: Iterator> Iterator for DenseEnumerate<I> {
+    type Item = (DenseIndex, I::Item);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
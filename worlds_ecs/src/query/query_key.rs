@@ -0,0 +1,305 @@
+use super::{arch_query::ArchQuery, query_iter::QueryIter};
+use crate::{archetype::key::PrimeArchKey, world::World};
+use std::{any::TypeId, panic::Location};
+
+/// The resolved [`PrimeArchKey`] of a query, precomputed once with [`World::precompute_query_key`] and reused by
+/// [`World::query_with_key`] without resolving the query again.
+///
+/// Like a [`QueryState`](super::QueryState), but it's [`Copy`] and doesn't need to be borrowed mutably, so a set of
+/// keys for the hot queries (see [`query_keys!`](crate::query_keys)) can be initialized once at startup and handed
+/// around freely. A key is only valid for the query it was computed for, and until components are registered or
+/// rebound (see [`ComponentFactory::registration_epoch`](crate::prelude::ComponentFactory::registration_epoch)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryKey {
+    pkey: PrimeArchKey,
+    access: QueryAccess,
+    registration_epoch: u64,
+}
+
+/// What a [`QueryKey`] was computed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct QueryAccess {
+    query: TypeId,
+    is_mutable: bool,
+}
+
+impl QueryAccess {
+    fn of<Q: ArchQuery + 'static>() -> Self {
+        QueryAccess {
+            query: TypeId::of::<Q>(),
+            is_mutable: Q::IS_MUTABLE,
+        }
+    }
+}
+
+impl QueryKey {
+    /// Returns `true` if this key was computed for `Q`, and no components were registered or rebound in the
+    /// [`World`] since.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    /// #[derive(Component)]
+    /// struct Velocity(f32);
+    ///
+    /// let mut world = World::default();
+    /// world.register_component::<Position>();
+    /// let key = world.precompute_query_key::<&Position>();
+    /// assert!(key.is_valid_for::<&Position>(&world));
+    /// assert!(!key.is_valid_for::<&mut Position>(&world));
+    /// // Registering a component invalidates the key.
+    /// world.register_component::<Velocity>();
+    /// assert!(!key.is_valid_for::<&Position>(&world));
+    /// ```
+    pub fn is_valid_for<Q: ArchQuery + 'static>(&self, world: &World) -> bool {
+        self.access == QueryAccess::of::<Q>()
+            && self.registration_epoch == world.components.registration_epoch()
+    }
+
+    /// Returns `false` if the query can't match anything, because some of its components weren't registered
+    /// when the key was computed.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    ///
+    /// let mut world = World::default();
+    /// assert!(!world.precompute_query_key::<&Position>().is_resolved());
+    /// world.register_component::<Position>();
+    /// assert!(world.precompute_query_key::<&Position>().is_resolved());
+    /// ```
+    pub fn is_resolved(&self) -> bool {
+        !self.pkey.is_exact_archetype(PrimeArchKey::NEVER_MATCHES)
+    }
+}
+
+impl World {
+    /// Resolve the [`PrimeArchKey`] of the query `Q` once, so it can be queried with [`World::query_with_key`]
+    /// without resolving it again. If some of its components aren't registered, the key doesn't match anything.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    /// #[derive(Component)]
+    /// struct Velocity(f32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn((Position(0.0), Velocity(1.0)));
+    /// let key = world.precompute_query_key::<(&mut Position, &Velocity)>();
+    /// for _frame in 0..3 {
+    ///     for (position, velocity) in world.query_with_key::<(&mut Position, &Velocity)>(key) {
+    ///         position.0 += velocity.0;
+    ///     }
+    /// }
+    /// assert_eq!(world.query::<&Position>().next().unwrap().0, 3.0);
+    /// ```
+    pub fn precompute_query_key<Q: ArchQuery + 'static>(&self) -> QueryKey {
+        QueryKey {
+            pkey: self
+                .resolve_query_key::<Q>()
+                .unwrap_or(PrimeArchKey::NEVER_MATCHES),
+            access: QueryAccess::of::<Q>(),
+            registration_epoch: self.components.registration_epoch(),
+        }
+    }
+
+    /// Query the world for components, like [`World::query`], with a key that was precomputed for `Q` (see
+    /// [`World::precompute_query_key`]), skipping the resolution of the query.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(1));
+    /// let key = world.precompute_query_key::<&mut Health>();
+    /// for health in world.query_with_key::<&mut Health>(key) {
+    ///     health.0 += 1;
+    /// }
+    /// assert_eq!(world.query::<&Health>().next().unwrap().0, 2);
+    /// ```
+    ///
+    /// # Panics
+    /// In debug builds, if the key wasn't computed for `Q`, or if components were registered or rebound since it
+    /// was computed. In release builds, such a key is ignored and the query is resolved as usual.
+    #[track_caller]
+    pub fn query_with_key<Q: ArchQuery + 'static>(&mut self, key: QueryKey) -> QueryIter<'_, Q> {
+        self.access
+            .record_query::<Q>(&self.components, Location::caller());
+        debug_assert!(
+            key.access == QueryAccess::of::<Q>(),
+            "The QueryKey was computed for a different query"
+        );
+        debug_assert!(
+            key.registration_epoch == self.components.registration_epoch(),
+            "The QueryKey is stale: components were registered or rebound since it was computed"
+        );
+        if !key.is_valid_for::<Q>(self) {
+            return self.query::<Q>();
+        }
+        // SAFETY: The pointer to the storages came from a &mut, and the key was merged by `Q` with the world's
+        // current components, since none were registered or rebound since.
+        unsafe {
+            QueryIter::matching(
+                &mut self.storages.arch_storages,
+                &self.components,
+                self.storages.tag_storage.entity_tags(&self.entities),
+                key.pkey,
+                false,
+            )
+        }
+        .with_groups(&self.groups)
+    }
+}
+
+/// Declare a struct of [`QueryKey`]s for a list of queries, with a `new(&World)` constructor that precomputes all
+/// of them (see [`World::precompute_query_key`]).
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+/// use worlds_ecs::query_keys;
+///
+/// #[derive(Component)]
+/// struct Position(f32);
+/// #[derive(Component)]
+/// struct Velocity(f32);
+///
+/// query_keys! {
+///     struct HotQueries {
+///         movement: (&mut Position, &Velocity),
+///         positions: &Position,
+///     }
+/// }
+///
+/// let mut world = World::default();
+/// world.spawn((Position(0.0), Velocity(1.0)));
+/// world.spawn(Position(5.0));
+///
+/// let keys = HotQueries::new(&world);
+/// for (position, velocity) in world.query_with_key::<(&mut Position, &Velocity)>(keys.movement) {
+///     position.0 += velocity.0;
+/// }
+/// assert_eq!(world.query_with_key::<&Position>(keys.positions).count(), 2);
+/// ```
+#[macro_export]
+macro_rules! query_keys {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident: $query:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy)]
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $crate::query::QueryKey,)*
+        }
+
+        impl $name {
+            /// Precompute every key for the [`World`](`$crate::world::World`).
+            $vis fn new(world: &$crate::world::World) -> Self {
+                Self {
+                    $($field: world.precompute_query_key::<$query>(),)*
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[derive(Component, Debug, PartialEq)]
+    struct A(usize);
+    #[derive(Component)]
+    struct B(usize);
+    #[derive(Component)]
+    struct C;
+
+    query_keys! {
+        struct Keys {
+            a: &A,
+            ab: (&mut A, &B),
+            c: (EntityId, &C),
+        }
+    }
+
+    #[test]
+    fn test_query_with_key() {
+        let mut world = World::default();
+        for i in 0..10 {
+            world.spawn(A(i));
+            world.spawn((A(i), B(i)));
+        }
+        world.components.register_component::<C>();
+        let keys = Keys::new(&world);
+        assert!(keys.a.is_resolved() && keys.ab.is_resolved() && keys.c.is_resolved());
+        assert!(keys.ab.is_valid_for::<(&mut A, &B)>(&world));
+        assert!(!keys.ab.is_valid_for::<(&A, &B)>(&world));
+
+        for (a, b) in world.query_with_key::<(&mut A, &B)>(keys.ab) {
+            a.0 += b.0;
+        }
+        let expected: Vec<usize> = world.query::<&A>().map(|a| a.0).collect();
+        assert_eq!(expected.len(), 20);
+        assert!(world.query_with_key::<&A>(keys.a).map(|a| a.0).eq(expected));
+        assert_eq!(world.query_with_key::<(EntityId, &C)>(keys.c).count(), 0);
+        // New storages are found without a new key.
+        world.spawn(C);
+        assert_eq!(world.query_with_key::<(EntityId, &C)>(keys.c).count(), 1);
+    }
+
+    #[test]
+    fn test_unresolved_key_matches_nothing() {
+        let mut world = World::default();
+        let key = world.precompute_query_key::<&A>();
+        assert!(!key.is_resolved());
+        assert_eq!(world.query_with_key::<&A>(key).count(), 0);
+    }
+
+    #[test]
+    fn test_key_is_stale_after_registration() {
+        let mut world = World::default();
+        world.spawn(A(0));
+        let key = world.precompute_query_key::<&A>();
+        assert!(key.is_valid_for::<&A>(&world));
+        world.spawn(B(0));
+        assert!(!key.is_valid_for::<&A>(&world));
+        assert!(world
+            .precompute_query_key::<&A>()
+            .is_valid_for::<&A>(&world));
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "The QueryKey is stale"))]
+    fn test_stale_key_asserts() {
+        let mut world = World::default();
+        let key = world.precompute_query_key::<&A>();
+        world.spawn(A(0));
+        // Release builds resolve the query instead.
+        assert_eq!(world.query_with_key::<&A>(key).count(), 1);
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "The QueryKey was computed for a different query")
+    )]
+    fn test_mismatched_key_asserts() {
+        let mut world = World::default();
+        world.spawn((A(0), B(0)));
+        world.spawn(B(1));
+        let key = world.precompute_query_key::<&A>();
+        // Release builds resolve the query instead.
+        assert_eq!(world.query_with_key::<&B>(key).count(), 2);
+    }
+}
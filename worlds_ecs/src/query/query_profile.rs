@@ -0,0 +1,240 @@
+use std::{fmt, time::Duration};
+
+/// A timing breakdown of the iterations of a query, accumulated by [`QueryIter::profiled`](super::QueryIter::profiled).
+///
+/// The time of an iteration is split into matching the storages against the key of the query, setting up each
+/// matched storage (like deciding the filter for the whole storage), and everything else: finding the entities
+/// that pass the filter, and fetching their components ([`Self::iteration_time`]). Only the time spent inside the
+/// iterator is measured, not the body of the loop that consumes it.
+///
+/// Profiles of several iterations (like one per frame) can be accumulated with [`Self::merge`], or by profiling them
+/// all into the same profile.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct QueryProfile {
+    /// How many iterations were profiled.
+    pub passes: usize,
+    /// How many storages were matched against the key of the query.
+    pub storages_considered: usize,
+    /// How many storages matched the key of the query, and were visited.
+    pub storages_matched: usize,
+    /// How many entities the iterations yielded.
+    pub entities_yielded: usize,
+    /// How many entities of the matched storages were rejected by the filter of the query (or by its group, see
+    /// [`QueryIter::in_group`](super::QueryIter::in_group)).
+    pub entities_rejected: usize,
+    /// The time spent matching storages against the key of the query.
+    pub matching_time: Duration,
+    /// The time spent setting up the matched storages.
+    pub setup_time: Duration,
+    /// The total time spent in the iterators.
+    pub total_time: Duration,
+}
+
+impl QueryProfile {
+    /// The time spent finding the entities that pass the filter and fetching them: the total time, minus the time
+    /// spent matching and setting up storages.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let profile = QueryProfile {
+    ///     total_time: Duration::from_micros(10),
+    ///     matching_time: Duration::from_micros(2),
+    ///     setup_time: Duration::from_micros(3),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(profile.iteration_time(), Duration::from_micros(5));
+    /// ```
+    pub fn iteration_time(&self) -> Duration {
+        self.total_time
+            .saturating_sub(self.matching_time + self.setup_time)
+    }
+
+    /// Add the counters and the times of another profile to this one.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(1));
+    /// let mut total = QueryProfile::default();
+    /// for _frame in 0..2 {
+    ///     let mut frame = QueryProfile::default();
+    ///     world.query::<&Health>().profiled(&mut frame).for_each(drop);
+    ///     total.merge(&frame);
+    /// }
+    /// assert_eq!(total.passes, 2);
+    /// assert_eq!(total.entities_yielded, 2);
+    /// ```
+    pub fn merge(&mut self, other: &QueryProfile) {
+        self.passes += other.passes;
+        self.storages_considered += other.storages_considered;
+        self.storages_matched += other.storages_matched;
+        self.entities_yielded += other.entities_yielded;
+        self.entities_rejected += other.entities_rejected;
+        self.matching_time += other.matching_time;
+        self.setup_time += other.setup_time;
+        self.total_time += other.total_time;
+    }
+}
+
+impl fmt::Display for QueryProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let micros = |duration: Duration| duration.as_secs_f64() * 1e6;
+        let share = |duration: Duration| match self.total_time.is_zero() {
+            true => 0.0,
+            false => duration.as_secs_f64() / self.total_time.as_secs_f64() * 100.0,
+        };
+        writeln!(
+            f,
+            "{} passes: {} entities yielded, {} rejected, {} of {} storages matched",
+            self.passes,
+            self.entities_yielded,
+            self.entities_rejected,
+            self.storages_matched,
+            self.storages_considered
+        )?;
+        writeln!(f, "{:<12} {:>12} {:>8}", "stage", "time (µs)", "share")?;
+        for (stage, time) in [
+            ("matching", self.matching_time),
+            ("setup", self.setup_time),
+            ("iteration", self.iteration_time()),
+            ("total", self.total_time),
+        ] {
+            writeln!(
+                f,
+                "{stage:<12} {:>12.1} {:>7.1}%",
+                micros(time),
+                share(time)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QueryProfile;
+    use crate::prelude::*;
+    use std::time::{Duration, Instant};
+
+    #[derive(Component)]
+    struct A(usize);
+    #[derive(Component)]
+    struct B;
+    #[derive(Component)]
+    struct C;
+
+    /// 10 entities with `(A,)`, 20 with `(A, B)`, 30 with `(A, C)`, and 5 with `(C,)`.
+    fn world() -> World {
+        let mut world = World::default();
+        world.spawn_batch((0..10).map(A));
+        world.spawn_batch((0..20).map(|i| (A(i), B)));
+        world.spawn_batch((0..30).map(|i| (A(i), C)));
+        world.spawn_batch((0..5).map(|_| C));
+        world
+    }
+
+    #[test]
+    fn test_counters() {
+        let mut world = world();
+        let mut profile = QueryProfile::default();
+        assert_eq!(world.query::<&A>().profiled(&mut profile).count(), 60);
+        assert_eq!(profile.passes, 1);
+        assert_eq!(profile.storages_considered, 4);
+        assert_eq!(profile.storages_matched, 3);
+        assert_eq!(profile.entities_yielded, 60);
+        assert_eq!(profile.entities_rejected, 0);
+
+        // The filter rejects the storage of `(A, C)` as a whole.
+        let mut filtered = QueryProfile::default();
+        let yielded = world
+            .query_filtered::<&A, Without<C>>()
+            .profiled(&mut filtered)
+            .count();
+        assert_eq!(yielded, 30);
+        assert_eq!(filtered.storages_matched, 3);
+        assert_eq!(filtered.entities_yielded, 30);
+        assert_eq!(filtered.entities_rejected, 30);
+
+        // The filter is evaluated for every entity, and rejects half of them.
+        let mut filtered = QueryProfile::default();
+        let yielded = world
+            .query_filtered::<&A, ValueFilter<A, Even>>()
+            .profiled(&mut filtered)
+            .count();
+        assert_eq!(yielded, 30);
+        assert_eq!(filtered.entities_yielded, 30);
+        assert_eq!(filtered.entities_rejected, 30);
+
+        profile.merge(&filtered);
+        assert_eq!(profile.passes, 2);
+        assert_eq!(profile.storages_considered, 8);
+        assert_eq!(profile.entities_yielded, 90);
+        assert_eq!(profile.entities_rejected, 30);
+    }
+
+    #[derive(Default)]
+    struct Even;
+
+    impl ComponentPredicate<A> for Even {
+        fn test(&self, a: &A) -> bool {
+            a.0.is_multiple_of(2)
+        }
+    }
+
+    #[test]
+    fn test_queries_that_match_nothing() {
+        let mut world = world();
+        #[derive(Component)]
+        struct Unregistered;
+        let mut profile = QueryProfile::default();
+        assert_eq!(
+            world
+                .query::<&Unregistered>()
+                .profiled(&mut profile)
+                .count(),
+            0
+        );
+        assert_eq!(profile.storages_considered, 0);
+        assert_eq!(profile.storages_matched, 0);
+        assert_eq!(world.query::<(&B, &C)>().profiled(&mut profile).count(), 0);
+        assert_eq!(profile.passes, 2);
+        assert_eq!(profile.entities_yielded, 0);
+    }
+
+    #[test]
+    fn test_timings() {
+        let mut world = World::default();
+        for i in 0..100 {
+            world.spawn((A(i), B));
+            world.spawn((A(i), C));
+        }
+        let mut profile = QueryProfile::default();
+        let start = Instant::now();
+        let sum: usize = world
+            .query::<&A>()
+            .profiled(&mut profile)
+            .map(|a| a.0)
+            .sum();
+        let elapsed = start.elapsed();
+        assert_eq!(sum, 2 * (0..100).sum::<usize>());
+        assert!(profile.matching_time > Duration::ZERO);
+        assert!(profile.setup_time > Duration::ZERO);
+        assert!(profile.iteration_time() > Duration::ZERO);
+        // The stages add up to the total, which is measured inside the iteration.
+        assert_eq!(
+            profile.matching_time + profile.setup_time + profile.iteration_time(),
+            profile.total_time
+        );
+        assert!(profile.total_time <= elapsed);
+        let report = profile.to_string();
+        assert!(report
+            .starts_with("1 passes: 200 entities yielded, 0 rejected, 2 of 2 storages matched"));
+        assert!(report.contains("matching") && report.contains("iteration"));
+    }
+}
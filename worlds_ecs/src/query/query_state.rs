@@ -0,0 +1,232 @@
+use super::{
+    arch_query::ArchQuery, filter_cache::FilterCache, match_cache::MatchRecord,
+    query_filter::ArchFilter, query_iter::QueryIter,
+};
+use crate::{
+    archetype::key::PrimeArchKey,
+    prelude::ComponentFactory,
+    world::{warnings::EcsWarning, World},
+};
+use std::{marker::PhantomData, panic::Location};
+
+/// A query whose [`PrimeArchKey`] is resolved once, and reused for every iteration.
+///
+/// Unlike [`World::query`], creating a [`QueryState`] for components that aren't registered yet doesn't panic:
+/// the query doesn't match anything until they are registered. The state is revalidated (with a single integer
+/// comparison) every time it is iterated, and resolved again when components were registered or rebound since
+/// (see [`ComponentFactory::registration_epoch`]). New storages don't need revalidation, because the matching
+/// storages are found during iteration, but a state that isn't iterated while many storages are created emits an
+/// [`EcsWarning::StaleQueryState`] when it finally is.
+///
+/// If the filter is [`Cached`](super::Cached), the state also owns the [`FilterCache`] with its results.
+///
+/// While no storage matches the query (for example, because nothing with its components was spawned yet), iterating
+/// it returns an empty iterator right away: the state remembers that no storage matched, and only checks the storages
+/// that were created since.
+pub struct QueryState<Q: ArchQuery, F: ArchFilter = ()> {
+    pkey: PrimeArchKey,
+    registration_epoch: u64,
+    /// The amount of storages when the state was last iterated, see [`EcsWarning::StaleQueryState`].
+    storage_count: usize,
+    filtered: bool,
+    filter_cache: FilterCache,
+    match_record: MatchRecord,
+    _query: PhantomData<fn() -> (Q, F)>,
+}
+
+impl<Q: ArchQuery> QueryState<Q> {
+    /// Create a new [`QueryState`] for the [`World`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// // The component doesn't need to be registered yet.
+    /// let mut state = QueryState::<&Health>::new(&world);
+    /// assert!(!state.is_resolved());
+    /// world.spawn(Health(3));
+    /// assert_eq!(state.iter(&mut world).map(|health| health.0).sum::<u32>(), 3);
+    /// ```
+    pub fn new(world: &World) -> Self {
+        Self::resolved(world, false)
+    }
+}
+
+impl<Q: ArchQuery, F: ArchFilter> QueryState<Q, F> {
+    /// Create a new [`QueryState`] with a filter for the [`World`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Player;
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(1));
+    /// world.spawn((Health(2), Player));
+    /// let mut state = QueryState::<&Health, Without<Player>>::new_filtered(&world);
+    /// let healths: Vec<u32> = state.iter(&mut world).map(|health| health.0).collect();
+    /// assert_eq!(healths, [1]);
+    /// ```
+    pub fn new_filtered(world: &World) -> Self {
+        Self::resolved(world, true)
+    }
+
+    fn resolved(world: &World, filtered: bool) -> Self {
+        let mut state = QueryState {
+            pkey: PrimeArchKey::NEVER_MATCHES,
+            registration_epoch: world.components.registration_epoch(),
+            storage_count: world.storages.arch_storages.storage_count(),
+            filtered,
+            filter_cache: FilterCache::default(),
+            match_record: MatchRecord::default(),
+            _query: PhantomData,
+        };
+        state.resolve(&world.components);
+        state
+    }
+
+    fn resolve(&mut self, comp_factory: &ComponentFactory) {
+        self.registration_epoch = comp_factory.registration_epoch();
+        self.filter_cache.clear();
+        self.match_record = MatchRecord::default();
+        self.pkey = if Q::is_resolvable(comp_factory) {
+            Q::resolve_prime_arch_key(comp_factory)
+        } else {
+            PrimeArchKey::NEVER_MATCHES
+        };
+    }
+
+    /// Resolve the query again if components were registered or rebound since it was last resolved.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let mut state = QueryState::<&Health>::new(&world);
+    /// world.register_component::<Health>();
+    /// assert!(!state.is_resolved());
+    /// // `iter` revalidates the state too.
+    /// state.revalidate(&world);
+    /// assert!(state.is_resolved());
+    /// ```
+    #[inline]
+    pub fn revalidate(&mut self, world: &World) {
+        if self.registration_epoch != world.components.registration_epoch() {
+            self.resolve(&world.components);
+        }
+    }
+
+    /// Warn if many storages were created since the state was last iterated (see [`EcsWarning::StaleQueryState`]).
+    fn warn_if_stale(&mut self, world: &mut World) {
+        let storage_count = world.storages.arch_storages.storage_count();
+        let storages_created = storage_count.saturating_sub(self.storage_count);
+        self.storage_count = storage_count;
+        if storages_created > world.warnings.stale_query_state_threshold
+            && world.warnings.is_enabled()
+        {
+            world.warnings.emit(EcsWarning::StaleQueryState {
+                query: std::any::type_name::<Q>(),
+                storages_created,
+            });
+        }
+    }
+
+    /// Returns `false` if the query can't match anything, because some of its components weren't registered
+    /// (when it was last revalidated).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.register_component::<Health>();
+    /// let state = QueryState::<&Health>::new(&world);
+    /// assert!(state.is_resolved());
+    /// ```
+    pub fn is_resolved(&self) -> bool {
+        !self.pkey.is_exact_archetype(PrimeArchKey::NEVER_MATCHES)
+    }
+
+    /// Iterate over the matches of the query in the [`World`]. If the filter is [`Cached`](super::Cached),
+    /// it's evaluated again only for the storages that changed since the last iteration.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(1));
+    /// let mut state = QueryState::<&mut Health>::new(&world);
+    /// for _frame in 0..2 {
+    ///     for health in state.iter(&mut world) {
+    ///         health.0 += 1;
+    ///     }
+    /// }
+    /// assert_eq!(world.query::<&Health>().next().unwrap().0, 3);
+    /// ```
+    #[track_caller]
+    pub fn iter<'w>(&'w mut self, world: &'w mut World) -> QueryIter<'w, Q, F> {
+        world
+            .access
+            .record_filtered_query::<Q, F>(&world.components, Location::caller());
+        self.warn_if_stale(world);
+        self.revalidate(world);
+        if !world
+            .storages
+            .arch_storages
+            .can_match_record(&mut self.match_record, self.pkey)
+        {
+            // SAFETY: The pointer to the storages came from a &mut.
+            return unsafe {
+                QueryIter::empty(
+                    &mut world.storages.arch_storages,
+                    &world.components,
+                    world.storages.tag_storage.entity_tags(&world.entities),
+                    self.filtered,
+                )
+                .with_groups(&world.groups)
+            };
+        }
+        if F::IS_CACHED {
+            // SAFETY: The cache is only used with `F`, and it's cleared when the components are resolved again.
+            unsafe {
+                self.filter_cache.refresh::<F>(
+                    &world.storages.arch_storages,
+                    &world.components,
+                    world.storages.tag_storage.entity_tags(&world.entities),
+                    self.pkey,
+                )
+            };
+        }
+        // SAFETY: The pointer to the storages came from a &mut, and the key was merged by `Q` with the
+        // world's current components. The cache was just refreshed.
+        unsafe {
+            let iter = QueryIter::with_key(
+                &mut world.storages.arch_storages,
+                &world.components,
+                world.storages.tag_storage.entity_tags(&world.entities),
+                self.pkey,
+                self.filtered,
+            )
+            .with_groups(&world.groups);
+            if F::IS_CACHED {
+                iter.with_filter_cache(&self.filter_cache)
+            } else {
+                iter
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use super::{
+    arch_query::{ArchQuery, FetchContext, ReadOnlyArchQuery},
+    query_iter::QueryIter,
+};
+use crate::{
+    archetype::key::PrimeArchKey,
+    entity::EntityId,
+    world::{storage::ArchEntityStorage, World},
+};
+use std::{marker::PhantomData, panic::Location};
+
+/// A query of the [`World`] that can both be iterated, and looked up by entity (see [`World::query_view`]).
+///
+/// The [`PrimeArchKey`] of the query is resolved once, when the view is created. Looking up an entity checks the
+/// archetype of its storage against the key, so it costs a couple of lookups, and no iteration.
+pub struct Query<'w, Q: ArchQuery> {
+    world: &'w mut World,
+    /// The key of the query, or [`PrimeArchKey::NEVER_MATCHES`] if some of its components aren't registered.
+    pkey: PrimeArchKey,
+    _query: PhantomData<fn() -> Q>,
+}
+
+impl<'w, Q: ArchQuery> Query<'w, Q> {
+    /// Iterate over the matches of the query, like [`World::query`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(1));
+    /// world.spawn(Health(2));
+    /// let mut query = world.query_view::<&mut Health>();
+    /// for health in query.iter() {
+    ///     health.0 *= 10;
+    /// }
+    /// assert_eq!(query.iter().map(|health| health.0).sum::<u32>(), 30);
+    /// ```
+    pub fn iter(&mut self) -> QueryIter<'_, Q> {
+        let world = &mut *self.world;
+        // SAFETY: The pointer to the storages came from a &mut, and the key was merged by `Q` with the world's
+        // components (or never matches).
+        unsafe {
+            QueryIter::matching(
+                &mut world.storages.arch_storages,
+                &world.components,
+                world.storages.tag_storage.entity_tags(&world.entities),
+                self.pkey,
+                false,
+            )
+        }
+        .with_groups(&world.groups)
+    }
+
+    /// The item of the query for an entity, or `None` if the entity isn't alive (or is archived), or if its
+    /// archetype doesn't match the query.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Armor(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(1));
+    /// let other = world.spawn(Armor(1));
+    /// let mut query = world.query_view::<&mut Health>();
+    /// query.get_mut(entity).unwrap().0 = 5;
+    /// // The entity doesn't match the query.
+    /// assert!(query.get_mut(other).is_none());
+    /// assert_eq!(world.get_component::<Health>(entity).unwrap().0, 5);
+    /// ```
+    pub fn get_mut(&mut self, entity: EntityId) -> Option<Q::Item<'_>> {
+        let world = &mut *self.world;
+        let entity_meta = world.entities.get_entity_meta(entity)?;
+        let storage = world
+            .storages
+            .arch_storages
+            .get_storage_mut(entity_meta.archetype_storage_id)?;
+        if !storage.prime_key().is_sub_archetype(self.pkey) {
+            return None;
+        }
+        if Q::IS_MUTABLE {
+            // The item may write to the components, so they can't stay shared with a clone of the world.
+            storage.make_unique();
+        }
+        let ctx = FetchContext::new(world.storages.tag_storage.entity_tags(&world.entities))
+            .for_query::<Q>(storage, &world.components);
+        // SAFETY: The index of a live entity is in the bounds of its storage, and the storage is borrowed mutably
+        // for as long as the item lives.
+        Some(unsafe {
+            Q::fetch(
+                storage,
+                entity_meta.archetype_storage_index,
+                &world.components,
+                ctx,
+            )
+        })
+    }
+
+    /// Returns `true` if the entity is alive, and matches the query.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(1));
+    /// let empty = world.spawn(());
+    /// let query = world.query_view::<&Health>();
+    /// assert!(query.contains(entity));
+    /// assert!(!query.contains(empty));
+    /// ```
+    pub fn contains(&self, entity: EntityId) -> bool {
+        self.storage_of(entity).is_some()
+    }
+
+    fn storage_of(&self, entity: EntityId) -> Option<&ArchEntityStorage> {
+        let entity_meta = self.world.entities.get_entity_meta(entity)?;
+        let storage =
+            (self.world.storages.arch_storages).get_storage(entity_meta.archetype_storage_id)?;
+        storage
+            .prime_key()
+            .is_sub_archetype(self.pkey)
+            .then_some(storage)
+    }
+}
+
+impl<'w, Q: ReadOnlyArchQuery> Query<'w, Q> {
+    /// The item of the query for an entity, like [`Self::get_mut`], through a shared reference, so several items
+    /// can be held at once.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let a = world.spawn(Position(1));
+    /// let b = world.spawn(Position(4));
+    /// let query = world.query_view::<&Position>();
+    /// // Several items can be held at once.
+    /// let (pa, pb) = (query.get(a).unwrap(), query.get(b).unwrap());
+    /// assert_eq!(pb.0 - pa.0, 3);
+    /// ```
+    pub fn get(&self, entity: EntityId) -> Option<Q::Item<'_>> {
+        let storage = self.storage_of(entity)?;
+        let entity_meta = self.world.entities.get_entity_meta(entity)?;
+        let ctx = FetchContext::new(self.world.entity_tags())
+            .for_query::<Q>(storage, &self.world.components);
+        // SAFETY: The index of a live entity is in the bounds of its storage, and `Q` only reads.
+        Some(unsafe {
+            Q::fetch_shared(
+                storage,
+                entity_meta.archetype_storage_index,
+                &self.world.components,
+                ctx,
+            )
+        })
+    }
+}
+
+impl<'q, 'w, Q: ArchQuery> IntoIterator for &'q mut Query<'w, Q> {
+    type Item = Q::Item<'q>;
+    type IntoIter = QueryIter<'q, Q>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl World {
+    /// A [`Query`] of the world, which can both be iterated (like [`World::query`]) and looked up by entity
+    /// (with [`Query::get`] and [`Query::get_mut`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Target(EntityId);
+    ///
+    /// let mut world = World::default();
+    /// let enemy = world.spawn(Health(10));
+    /// let tower = world.spawn(Target(enemy));
+    /// let mut healths = world.query_view::<&mut Health>();
+    /// healths.get_mut(enemy).unwrap().0 -= 3;
+    /// assert!(healths.get_mut(tower).is_none());
+    /// assert_eq!(healths.iter().map(|health| health.0).sum::<u32>(), 7);
+    /// ```
+    ///
+    /// Like [`World::query`], the view doesn't match anything if some of the components aren't registered.
+    #[track_caller]
+    pub fn query_view<Q: ArchQuery>(&mut self) -> Query<'_, Q> {
+        self.access
+            .record_query::<Q>(&self.components, Location::caller());
+        let pkey = if Q::is_resolvable(&self.components) {
+            Q::resolve_prime_arch_key(&self.components)
+        } else {
+            self.warn_unregistered_query::<Q>();
+            PrimeArchKey::NEVER_MATCHES
+        };
+        Query {
+            world: self,
+            pkey,
+            _query: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, test_utils::*};
+
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Position(i32);
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Component)]
+    struct Frozen;
+
+    #[test]
+    fn test_get_matching_entities() {
+        let mut world = World::default();
+        let moving = world.spawn((Position(0), Velocity(2)));
+        let still = world.spawn(Position(5));
+        let frozen = world.spawn((Position(1), Velocity(3), Frozen));
+        let mut query = world.query_view::<(&mut Position, &Velocity)>();
+        let (position, velocity) = query.get_mut(moving).unwrap();
+        position.0 += velocity.0;
+        assert!(query.get_mut(still).is_none());
+        assert!(query.contains(frozen));
+        assert!(!query.contains(still));
+        assert_eq!(query.iter().count(), 2);
+        assert_entity_has(&world, moving, Position(2));
+
+        let query = world.query_view::<&Position>();
+        let (first, second) = (query.get(moving), query.get(still));
+        assert_eq!((first, second), (Some(&Position(2)), Some(&Position(5))));
+    }
+
+    #[test]
+    fn test_get_stale_and_unregistered() {
+        let mut world = World::default();
+        let entity = world.spawn(Position(0));
+        world.despawn(entity);
+        let reused = world.spawn(Position(1));
+        let query = world.query_view::<&Position>();
+        assert_eq!(query.get(entity), None);
+        assert_eq!(query.get(reused), Some(&Position(1)));
+
+        // The components of the query were never registered, so it doesn't match anything.
+        let mut query = world.query_view::<&Fx<0>>();
+        assert_eq!(query.get(reused), None);
+        assert_eq!(query.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_get_mut_unshares_clones() {
+        let mut world = World::default();
+        world.register_cloneable_component::<Position>();
+        let entity = world.spawn(Position(0));
+        let snapshot = world.clone_cow().unwrap();
+        for position in &mut world.query_view::<&mut Position>() {
+            position.0 = 1;
+        }
+        world
+            .query_view::<&mut Position>()
+            .get_mut(entity)
+            .unwrap()
+            .0 += 1;
+        assert_entity_has(&world, entity, Position(2));
+        assert_entity_has(&snapshot, entity, Position(0));
+    }
+}
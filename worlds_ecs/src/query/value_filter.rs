@@ -0,0 +1,289 @@
+use super::{
+    arch_query::{ArchQuery, FetchContext, ReadOnlyArchQuery},
+    query_filter::StorageFilterResult,
+};
+use crate::{
+    component::{deref_component, Component, ComponentFactory, ComponentId},
+    world::{
+        access::AccessKind,
+        storage::{arch_storage::ArchStorageIndex, ArchEntityStorage},
+    },
+};
+use std::{marker::PhantomData, ops::RangeBounds};
+
+/// A test of the value of a component `C`, to filter the matches of a query by it, before the rest of the query is
+/// fetched (see [`ValueFilter`] and [`QueryIter::filter_component`](super::QueryIter::filter_component)).
+///
+/// Closures that take `&C` and return a `bool` are predicates too.
+pub trait ComponentPredicate<C> {
+    /// Whether the entity with the component passes the filter.
+    fn test(&self, component: &C) -> bool;
+}
+
+impl<C, P: Fn(&C) -> bool> ComponentPredicate<C> for P {
+    #[inline]
+    fn test(&self, component: &C) -> bool {
+        self(component)
+    }
+}
+
+/// A predicate that passes the components that are equal to its value.
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component, PartialEq)]
+/// struct Team(u8);
+///
+/// let mut world = World::default();
+/// world.spawn(Team(1));
+/// world.spawn(Team(2));
+/// assert_eq!(world.query::<EntityId>().filter_component(Equals(Team(2))).count(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Equals<C>(pub C);
+
+impl<C: PartialEq> ComponentPredicate<C> for Equals<C> {
+    #[inline]
+    fn test(&self, component: &C) -> bool {
+        self.0 == *component
+    }
+}
+
+/// A predicate that passes the components in its range (any of the `std` ranges, like `a..b` or `a..=b`).
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component, PartialEq, PartialOrd)]
+/// struct Health(u32);
+///
+/// let mut world = World::default();
+/// for health in [0, 5, 50, 100] {
+///     world.spawn(Health(health));
+/// }
+/// let wounded = world.query::<&Health>().filter_component(Range(Health(1)..Health(60)));
+/// assert_eq!(wounded.count(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Range<R>(pub R);
+
+impl<C: PartialOrd, R: RangeBounds<C>> ComponentPredicate<C> for Range<R> {
+    #[inline]
+    fn test(&self, component: &C) -> bool {
+        self.0.contains(component)
+    }
+}
+
+/// A filter that keeps the entities whose component `C` passes the predicate `P`. Filters are types, so `P` is
+/// built with [`Default`] for every test, which is free for a unit struct. Predicates with values (like [`Equals`]
+/// and [`Range`]) are attached to the iterator instead, with
+/// [`QueryIter::filter_component`](super::QueryIter::filter_component).
+///
+/// Only `C` is read to evaluate the filter: the components of the query are fetched for the entities that pass it.
+/// Entities without `C` don't pass.
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Name(&'static str);
+///
+/// #[derive(Default)]
+/// struct Dead;
+///
+/// impl ComponentPredicate<Health> for Dead {
+///     fn test(&self, health: &Health) -> bool {
+///         health.0 == 0
+///     }
+/// }
+///
+/// let mut world = World::default();
+/// world.spawn((Health(0), Name("goblin")));
+/// world.spawn((Health(3), Name("troll")));
+/// let dead: Vec<&str> = world
+///     .query_filtered::<&Name, ValueFilter<Health, Dead>>()
+///     .map(|name| name.0)
+///     .collect();
+/// assert_eq!(dead, ["goblin"]);
+/// ```
+pub struct ValueFilter<C, P>(PhantomData<fn() -> (C, P)>);
+
+// SAFETY: The filter only reads `C`.
+unsafe impl<C: Component, P: ComponentPredicate<C> + Default> ArchQuery for ValueFilter<C, P> {
+    type Item<'a> = bool;
+
+    fn for_each_access(
+        comp_factory: &ComponentFactory,
+        f: &mut dyn FnMut(ComponentId, AccessKind),
+    ) {
+        if let Some(comp_id) = comp_factory.get_component_id::<C>() {
+            f(comp_id, AccessKind::Read);
+        }
+    }
+
+    unsafe fn fetch(
+        arch_storage: *mut ArchEntityStorage,
+        index: ArchStorageIndex,
+        comp_factory: &ComponentFactory,
+        _ctx: FetchContext<'_>,
+    ) -> bool {
+        test_component::<C>(&*arch_storage, index, comp_factory, &P::default())
+    }
+
+    fn filter_storage(
+        arch_storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> StorageFilterResult {
+        if arch_storage.contains_archetype::<C>(comp_factory) {
+            StorageFilterResult::PerEntity
+        } else {
+            StorageFilterResult::NoneMatch
+        }
+    }
+}
+
+// SAFETY: The filter only reads `C`.
+unsafe impl<C: Component, P: ComponentPredicate<C> + Default> ReadOnlyArchQuery
+    for ValueFilter<C, P>
+{
+}
+
+/// Test the component `C` of the entity at `index` with `predicate`. Entities without `C` don't pass.
+///
+/// # Safety
+/// The index must be in the bounds of the storage, and nothing may mutate `C` in the storage during the test.
+#[inline]
+pub(crate) unsafe fn test_component<C: Component>(
+    arch_storage: &ArchEntityStorage,
+    index: ArchStorageIndex,
+    comp_factory: &ComponentFactory,
+    predicate: &impl ComponentPredicate<C>,
+) -> bool {
+    comp_factory
+        .get_component_id::<C>()
+        .and_then(|comp_id| arch_storage.get_component(index, comp_id))
+        .is_some_and(|component| predicate.test(deref_component::<C>(component)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ComponentPredicate, Equals, Range, ValueFilter};
+    use crate::prelude::*;
+
+    #[derive(Component, Debug, Clone, Copy, PartialEq, PartialOrd)]
+    struct Health(u32);
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Team(u8);
+    #[derive(Component)]
+    struct Name(usize);
+
+    #[derive(Default)]
+    struct Dead;
+
+    impl ComponentPredicate<Health> for Dead {
+        fn test(&self, health: &Health) -> bool {
+            health.0 == 0
+        }
+    }
+
+    fn world() -> World {
+        let mut world = World::default();
+        for i in 0..100 {
+            match i % 3 {
+                0 => world.spawn((Health(i % 7), Team((i % 4) as u8), Name(i as usize))),
+                1 => world.spawn((Health(i % 5), Name(i as usize))),
+                _ => world.spawn((Team((i % 4) as u8), Name(i as usize))),
+            };
+        }
+        world
+    }
+
+    fn sorted(mut names: Vec<usize>) -> Vec<usize> {
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn test_value_filter_matches_post_hoc_filtering() {
+        let mut world = world();
+        let filtered = world
+            .query_filtered::<&Name, ValueFilter<Health, Dead>>()
+            .map(|name| name.0)
+            .collect();
+        let post_hoc = world
+            .query::<(&Name, &Health)>()
+            .filter(|(_, health)| health.0 == 0)
+            .map(|(name, _)| name.0)
+            .collect();
+        assert_eq!(sorted(filtered), sorted(post_hoc));
+
+        let not_dead = world
+            .query_filtered::<&Name, Not<ValueFilter<Health, Dead>>>()
+            .count();
+        let post_hoc = world
+            .query::<(&Name, Option<&Health>)>()
+            .filter(|(_, health)| health.is_none_or(|health| health.0 != 0))
+            .count();
+        assert_eq!(not_dead, post_hoc);
+    }
+
+    #[test]
+    fn test_filter_component_matches_post_hoc_filtering() {
+        let mut world = world();
+        let filtered = world
+            .query::<&Name>()
+            .filter_component(Equals(Team(2)))
+            .map(|name| name.0)
+            .collect();
+        let post_hoc = world
+            .query::<(&Name, &Team)>()
+            .filter(|(_, team)| team.0 == 2)
+            .map(|(name, _)| name.0)
+            .collect();
+        assert_eq!(sorted(filtered), sorted(post_hoc));
+
+        // Predicates can be chained, and combined with filters.
+        let filtered = world
+            .query_filtered::<&Name, Has<Team>>()
+            .filter_component(Range(Health(1)..=Health(3)))
+            .filter_component(|team: &Team| team.0 != 0)
+            .map(|name| name.0)
+            .collect();
+        let post_hoc = world
+            .query::<(&Name, &Health, &Team)>()
+            .filter(|(_, health, team)| (1..=3).contains(&health.0) && team.0 != 0)
+            .map(|(name, _, _)| name.0)
+            .collect();
+        assert_eq!(sorted(filtered), sorted(post_hoc));
+    }
+
+    #[test]
+    fn test_filter_component_before_mutable_fetch() {
+        let mut world = world();
+        for health in world
+            .query::<&mut Health>()
+            .filter_component(Equals(Health(0)))
+        {
+            health.0 = 10;
+        }
+        assert_eq!(
+            world
+                .query_filtered::<(), ValueFilter<Health, Dead>>()
+                .count(),
+            0
+        );
+        // An unregistered component never passes.
+        #[derive(Component, PartialEq)]
+        struct Unregistered;
+        assert_eq!(
+            world
+                .query::<&Name>()
+                .filter_component(Equals(Unregistered))
+                .count(),
+            0
+        );
+    }
+}
@@ -0,0 +1,1905 @@
+//! A scene is a list of entities, written as JSON:
+//!
+//! ```json
+//! [
+//!     { "id": 0, "components": { "Name": "root" } },
+//!     { "id": 1, "components": { "Name": "child", "Parent": 0 }, "tags": ["Selected"] }
+//! ]
+//! ```
+//!
+//! Every entity has a map from the names of its components to their values, and optionally a list of tags and a
+//! scene id. Components are named when they are registered with [`World::register_scene_component`]. An
+//! [`EntityId`] in a component is written as the scene id of the entity it refers to (a [`SceneRef`]), and is
+//! rewritten to the spawned entity when the scene is loaded, if the component was registered with
+//! [`World::register_scene_component_with_refs`].
+//!
+//! A component whose [`StableComponentKey`] is a schema hash is saved with it, under the entity's `"schemas"`, so an
+//! older save can be upgraded when it's loaded by the migrations that were registered with
+//! [`World::register_migration`]:
+//!
+//! ```json
+//! [{ "components": { "Health": 10 }, "schemas": { "Health": 1 } }]
+//! ```
+use crate::{
+    archetype::ArchetypeInfo,
+    bundle::Bundle,
+    component::{deref_component, deref_component_mut, make_component},
+    entity::EntityId,
+    prelude::{Component, ComponentFactory, ComponentId},
+    tag::TagFactory,
+    utils::panics,
+    world::{data::StableComponentKey, quota::SpawnError, World},
+};
+use bevy_ptr::{OwningPtr, Ptr, PtrMut};
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    ser, Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_json::Value;
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+};
+
+/// The generation of the placeholder [`EntityId`] of a [`SceneRef`].
+const SCENE_REF_GENERATION: u32 = u32::MAX;
+
+/// The scene id of an entity, that other entities of the same scene use to refer to it.
+///
+/// While a scene is loaded (or saved), an [`EntityId`] in a component is a placeholder of a [`SceneRef`] (see
+/// [`SceneRef::placeholder`]), until it's rewritten by the component's [`MapEntities`] implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SceneRef(pub u32);
+
+impl SceneRef {
+    /// The [`EntityId`] that stands for this scene id in a component, until it's rewritten.
+    pub fn placeholder(self) -> EntityId {
+        EntityId::new(self.0).with_generation(SCENE_REF_GENERATION)
+    }
+
+    /// The scene id that `entity` stands for, if it's a placeholder (see [`SceneRef::placeholder`]).
+    pub fn of_placeholder(entity: EntityId) -> Option<SceneRef> {
+        (entity.generation() == SCENE_REF_GENERATION).then_some(SceneRef(entity.id()))
+    }
+}
+
+/// A value that refers to entities, so the references can be rewritten when it's loaded from a scene (or saved to
+/// one). Register components that implement it with [`World::register_scene_component_with_refs`].
+pub trait MapEntities {
+    /// Replace every [`EntityId`] in `self` with the result of calling `f` with it.
+    fn map_entities(&mut self, f: &mut dyn FnMut(EntityId) -> EntityId);
+}
+
+impl MapEntities for EntityId {
+    fn map_entities(&mut self, f: &mut dyn FnMut(EntityId) -> EntityId) {
+        *self = f(*self);
+    }
+}
+
+impl<T: MapEntities> MapEntities for Option<T> {
+    fn map_entities(&mut self, f: &mut dyn FnMut(EntityId) -> EntityId) {
+        if let Some(value) = self {
+            value.map_entities(f);
+        }
+    }
+}
+
+impl<T: MapEntities> MapEntities for Vec<T> {
+    fn map_entities(&mut self, f: &mut dyn FnMut(EntityId) -> EntityId) {
+        self.iter_mut().for_each(|value| value.map_entities(f));
+    }
+}
+
+/// An [`EntityId`] is written as the scene id of the entity, so only placeholders (see [`SceneRef::placeholder`])
+/// can be serialized.
+impl Serialize for EntityId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match SceneRef::of_placeholder(*self) {
+            Some(scene_ref) => serializer.serialize_u32(scene_ref.0),
+            None => Err(ser::Error::custom(format!(
+                "{self:?} isn't a scene reference, is its component registered with references?"
+            ))),
+        }
+    }
+}
+
+/// An [`EntityId`] is read as the placeholder of a scene id (see [`SceneRef::placeholder`]).
+impl<'de> Deserialize<'de> for EntityId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(|id| SceneRef(id).placeholder())
+    }
+}
+
+/// Read a component from its JSON value.
+type DeserializeFn = fn(Value) -> Result<Box<dyn Any>, serde_json::Error>;
+/// Move a component that was read by a [`DeserializeFn`] into `f`.
+type StoreFn = fn(Box<dyn Any>, &ComponentFactory, &mut dyn FnMut(OwningPtr<'_>));
+/// Write a component as a JSON value, after rewriting its references with the function (if it has references).
+type SerializeFn =
+    unsafe fn(Ptr<'_>, &mut dyn FnMut(EntityId) -> EntityId) -> Result<Value, serde_json::Error>;
+/// Rewrite the references of a component, that was read by a [`DeserializeFn`] or is stored in the world.
+type MapRefsFn = (
+    fn(&mut dyn Any, &mut dyn FnMut(EntityId) -> EntityId),
+    unsafe fn(PtrMut<'_>, &mut dyn FnMut(EntityId) -> EntityId),
+);
+
+fn deserialize_component<C: Component + DeserializeOwned>(
+    value: Value,
+) -> Result<Box<dyn Any>, serde_json::Error> {
+    serde_json::from_value::<C>(value).map(|component| Box::new(component) as Box<dyn Any>)
+}
+
+fn store_component<C: Component>(
+    component: Box<dyn Any>,
+    comp_factory: &ComponentFactory,
+    f: &mut dyn FnMut(OwningPtr<'_>),
+) {
+    let component = *component
+        .downcast::<C>()
+        .expect("the component was read by its own deserializer");
+    make_component(component, comp_factory, f);
+}
+
+/// # Safety
+/// `ptr` must point to a `C`.
+unsafe fn serialize_component<C: Component + Serialize>(
+    ptr: Ptr<'_>,
+    _map: &mut dyn FnMut(EntityId) -> EntityId,
+) -> Result<Value, serde_json::Error> {
+    serde_json::to_value(deref_component::<C>(ptr))
+}
+
+/// # Safety
+/// `ptr` must point to a `C`.
+unsafe fn serialize_component_with_refs<C: Component + Serialize + MapEntities + Clone>(
+    ptr: Ptr<'_>,
+    map: &mut dyn FnMut(EntityId) -> EntityId,
+) -> Result<Value, serde_json::Error> {
+    let mut component = deref_component::<C>(ptr).clone();
+    component.map_entities(map);
+    serde_json::to_value(&component)
+}
+
+fn map_read_component<C: Component + MapEntities>(
+    component: &mut dyn Any,
+    map: &mut dyn FnMut(EntityId) -> EntityId,
+) {
+    component
+        .downcast_mut::<C>()
+        .expect("the component was read by its own deserializer")
+        .map_entities(map);
+}
+
+/// # Safety
+/// `ptr` must point to a `C`.
+unsafe fn map_stored_component<C: Component + MapEntities>(
+    ptr: PtrMut<'_>,
+    map: &mut dyn FnMut(EntityId) -> EntityId,
+) {
+    deref_component_mut::<C>(ptr).map_entities(map);
+}
+
+/// Upgrade the JSON value of a component from one schema to the next (see [`World::register_migration`]).
+pub type MigrateFn = fn(Value) -> Result<Value, MigrationError>;
+
+/// An upgrade of a component from one schema to the next.
+#[derive(Clone, Copy)]
+struct Migration {
+    to_schema: u64,
+    migrate: MigrateFn,
+}
+
+/// A component that can be loaded from scenes, and saved to them.
+#[derive(Clone)]
+struct SceneComponent {
+    name: &'static str,
+    comp_id: ComponentId,
+    deserialize: DeserializeFn,
+    store: StoreFn,
+    serialize: SerializeFn,
+    map_refs: Option<MapRefsFn>,
+}
+
+/// The components that can be loaded from scenes, by name (see [`World::register_scene_component`]).
+#[derive(Default, Clone)]
+pub(crate) struct SceneRegistry {
+    components: Vec<SceneComponent>,
+    by_name: HashMap<&'static str, usize>,
+    by_id: HashMap<ComponentId, usize>,
+    /// The migrations of each component, by the schema they upgrade from.
+    migrations: HashMap<(ComponentId, u64), Migration>,
+    /// The components of entities that were preserved by [`UnknownComponentPolicy::Preserve`].
+    opaque: HashMap<EntityId, Vec<OpaqueComponent>>,
+}
+
+impl SceneRegistry {
+    /// Register a component under its name, replacing its previous registration.
+    ///
+    /// # Panics
+    /// If another component is registered with the same name.
+    fn register(&mut self, component: SceneComponent) {
+        if let Some(&index) = self.by_name.get(component.name) {
+            assert!(
+                self.components[index].comp_id == component.comp_id,
+                "Can't register two scene components named `{}`",
+                component.name
+            );
+        }
+        match self.by_id.get(&component.comp_id) {
+            Some(&index) => {
+                self.by_name.remove(self.components[index].name);
+                self.by_name.insert(component.name, index);
+                self.components[index] = component;
+            }
+            None => {
+                self.by_name.insert(component.name, self.components.len());
+                self.by_id.insert(component.comp_id, self.components.len());
+                self.components.push(component);
+            }
+        }
+    }
+
+    /// The migrations that upgrade a value of the component from the schema `saved` to its current schema, in order.
+    fn migration_path(
+        &self,
+        comp_factory: &ComponentFactory,
+        comp_id: ComponentId,
+        saved: u64,
+    ) -> Result<Vec<MigrateFn>, MigrationError> {
+        let current = match comp_factory.stable_key(comp_id) {
+            Some(StableComponentKey::SchemaHash(schema)) => Some(*schema),
+            _ => None,
+        };
+        let mut schemas = vec![saved];
+        let mut path = Vec::new();
+        let mut schema = saved;
+        while Some(schema) != current {
+            let Some(migration) = self.migrations.get(&(comp_id, schema)) else {
+                return Err(MigrationError::MissingLink {
+                    saved,
+                    current,
+                    missing: schema,
+                });
+            };
+            schema = migration.to_schema;
+            schemas.push(schema);
+            if schemas[..schemas.len() - 1].contains(&schema) {
+                return Err(MigrationError::Cycle(schemas));
+            }
+            path.push(migration.migrate);
+        }
+        Ok(path)
+    }
+}
+
+/// Why the value of a component couldn't be upgraded to its current schema (see [`World::register_migration`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationError {
+    /// A migration couldn't upgrade the value.
+    Invalid(String),
+    /// No migration upgrades the value from a schema on the way to the current one.
+    MissingLink {
+        /// The schema that the value was saved with.
+        saved: u64,
+        /// The current schema of the component, or `None` if it isn't identified by a schema hash.
+        current: Option<u64>,
+        /// The schema that no migration upgrades from.
+        missing: u64,
+    },
+    /// The migrations from the saved schema lead back to a schema they already upgraded from.
+    Cycle(Vec<u64>),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Invalid(message) => write!(f, "{message}"),
+            MigrationError::MissingLink {
+                saved,
+                current: Some(current),
+                missing,
+            } => write!(
+                f,
+                "no migration from schema {missing:#x}, on the way from the saved schema {saved:#x} to the current schema {current:#x}"
+            ),
+            MigrationError::MissingLink {
+                saved,
+                current: None,
+                missing,
+            } => write!(
+                f,
+                "no migration from schema {missing:#x}, on the way from the saved schema {saved:#x} to the component, which has no schema hash"
+            ),
+            MigrationError::Cycle(schemas) => {
+                f.write_str("the migrations loop through the schemas ")?;
+                for (i, schema) in schemas.iter().enumerate() {
+                    match i {
+                        0 => write!(f, "{schema:#x}")?,
+                        _ => write!(f, " -> {schema:#x}")?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// What [`World::load_scene_with`] does with a component whose name isn't registered, for example because it was
+/// removed from the game since the scene was saved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownComponentPolicy {
+    /// Fail to load the scene with [`SceneProblem::UnknownComponent`].
+    #[default]
+    Reject,
+    /// Load the entity without the component, and report it in [`SceneSpawned::unknown_components`].
+    Drop,
+    /// Like [`UnknownComponentPolicy::Drop`], but keep the value in the world (see [`World::opaque_components`]), so
+    /// [`World::save_scene`] writes it back, and a version of the game that knows the component again can load it.
+    Preserve,
+}
+
+/// The value of a component whose name wasn't registered when its scene was loaded (see
+/// [`UnknownComponentPolicy::Preserve`]). Its references are scene ids of the scene it was loaded from, and aren't
+/// rewritten.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpaqueComponent {
+    /// The name the component was saved under.
+    pub name: String,
+    /// The schema the component was saved with, if it was saved with one.
+    pub schema: Option<u64>,
+    /// The saved value.
+    pub value: Value,
+}
+
+/// Why the text of a scene couldn't be read. The entities are counted by their position in the scene, from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SceneProblem {
+    /// The text isn't valid JSON, or doesn't have the shape of a scene.
+    Syntax(String),
+    /// An entity has a component with a name that isn't registered.
+    UnknownComponent {
+        /// The position of the entity in the scene.
+        entity: usize,
+        /// The name of the component.
+        name: String,
+    },
+    /// An entity has a tag that isn't registered, or whose name is ambiguous.
+    UnknownTag {
+        /// The position of the entity in the scene.
+        entity: usize,
+        /// The name of the tag.
+        name: String,
+    },
+    /// The value of a component couldn't be read.
+    InvalidComponent {
+        /// The position of the entity in the scene.
+        entity: usize,
+        /// The name of the component.
+        component: &'static str,
+        /// Why the value couldn't be read.
+        message: String,
+    },
+    /// The value of a component couldn't be upgraded from the schema it was saved with.
+    Migration {
+        /// The position of the entity in the scene.
+        entity: usize,
+        /// The name of the component.
+        component: &'static str,
+        /// Why it couldn't be upgraded.
+        error: MigrationError,
+    },
+    /// An entity has the same component twice.
+    DuplicateComponent {
+        /// The position of the entity in the scene.
+        entity: usize,
+        /// The name of the component.
+        component: &'static str,
+    },
+    /// Two entities have the same scene id.
+    DuplicateId {
+        /// The position of the second entity in the scene.
+        entity: usize,
+        /// The scene id.
+        id: SceneRef,
+    },
+}
+
+impl fmt::Display for SceneProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneProblem::Syntax(message) => write!(f, "{message}"),
+            SceneProblem::UnknownComponent { entity, name } => {
+                write!(
+                    f,
+                    "unknown component `{name}` at entities[{entity}].components"
+                )
+            }
+            SceneProblem::UnknownTag { entity, name } => {
+                write!(f, "unknown tag `{name}` at entities[{entity}].tags")
+            }
+            SceneProblem::InvalidComponent {
+                entity,
+                component,
+                message,
+            } => write!(
+                f,
+                "invalid value at entities[{entity}].components.{component}: {message}"
+            ),
+            SceneProblem::Migration {
+                entity,
+                component,
+                error,
+            } => write!(
+                f,
+                "can't migrate entities[{entity}].components.{component}: {error}"
+            ),
+            SceneProblem::DuplicateComponent { entity, component } => {
+                write!(
+                    f,
+                    "duplicate component at entities[{entity}].components.{component}"
+                )
+            }
+            SceneProblem::DuplicateId { entity, id } => {
+                write!(f, "duplicate scene id {} at entities[{entity}].id", id.0)
+            }
+        }
+    }
+}
+
+/// An error when loading a scene (see [`World::load_scene`]) or saving one (see [`World::save_scene`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SceneError {
+    /// The text of the scene couldn't be read.
+    Parse {
+        /// Why it couldn't be read.
+        problem: SceneProblem,
+        /// The line of the text where the problem was found, from 1.
+        line: usize,
+        /// The column of the line where the problem was found, from 1.
+        column: usize,
+    },
+    /// A component refers to a scene id that no entity of the scene has.
+    UnresolvedRef {
+        /// The position of the entity in the scene.
+        entity: usize,
+        /// The name of the component.
+        component: &'static str,
+        /// The scene id.
+        target: SceneRef,
+    },
+    /// The entity can't be saved, because it was despawned (or archived).
+    NotAlive(EntityId),
+    /// The entity has a component that wasn't registered as a scene component (see
+    /// [`World::register_scene_component`]).
+    NotSaveable {
+        /// The entity.
+        entity: EntityId,
+        /// The name of the component.
+        component: &'static str,
+    },
+    /// A component refers to an entity that isn't saved with it.
+    UnsavedRef {
+        /// The entity.
+        entity: EntityId,
+        /// The name of the component.
+        component: &'static str,
+        /// The entity that isn't saved.
+        target: EntityId,
+    },
+    /// Spawning the scene would exceed a quota (see [`World::set_entity_limit`]). Nothing is spawned.
+    QuotaExceeded {
+        /// The position in the scene of the first entity that doesn't fit in the quota.
+        entity: usize,
+        /// The quota that would be exceeded. Always a [`SpawnError::QuotaExceeded`].
+        error: SpawnError,
+    },
+    /// A component couldn't be serialized.
+    Serialize {
+        /// The entity.
+        entity: EntityId,
+        /// The name of the component.
+        component: &'static str,
+        /// Why it couldn't be serialized.
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Parse {
+                problem,
+                line,
+                column,
+            } => write!(f, "{problem} (line {line}, column {column})"),
+            SceneError::UnresolvedRef {
+                entity,
+                component,
+                target,
+            } => write!(
+                f,
+                "entities[{entity}].components.{component} refers to the scene id {}, which no entity has",
+                target.0
+            ),
+            SceneError::NotAlive(entity) => write!(f, "{entity:?} can't be saved, it isn't alive"),
+            SceneError::NotSaveable { entity, component } => write!(
+                f,
+                "{entity:?} can't be saved, `{component}` isn't registered as a scene component"
+            ),
+            SceneError::UnsavedRef {
+                entity,
+                component,
+                target,
+            } => write!(
+                f,
+                "`{component}` of {entity:?} refers to {target:?}, which isn't saved with it"
+            ),
+            SceneError::QuotaExceeded { entity, error } => {
+                write!(f, "entities[{entity}] can't be spawned: {error}")
+            }
+            SceneError::Serialize {
+                entity,
+                component,
+                message,
+            } => write!(f, "`{component}` of {entity:?} can't be serialized: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// The entities that were spawned by [`World::load_scene`].
+#[derive(Debug, Clone, Default)]
+pub struct SceneSpawned {
+    entities: Vec<EntityId>,
+    ids: HashMap<SceneRef, EntityId>,
+    migrations: HashMap<ComponentId, usize>,
+    unknown: Vec<(EntityId, String)>,
+}
+
+impl SceneSpawned {
+    /// The spawned entities, in the order of the scene.
+    pub fn entities(&self) -> &[EntityId] {
+        &self.entities
+    }
+
+    /// The entity that was spawned for a scene id.
+    pub fn get(&self, id: SceneRef) -> Option<EntityId> {
+        self.ids.get(&id).copied()
+    }
+
+    /// The entities that were spawned for each scene id.
+    pub fn ids(&self) -> &HashMap<SceneRef, EntityId> {
+        &self.ids
+    }
+
+    /// How many values of each component were upgraded from an older schema (see [`World::register_migration`]).
+    pub fn migrations(&self) -> &HashMap<ComponentId, usize> {
+        &self.migrations
+    }
+
+    /// The components whose names weren't registered, and the entities that had them (see
+    /// [`UnknownComponentPolicy`]).
+    pub fn unknown_components(&self) -> &[(EntityId, String)] {
+        &self.unknown
+    }
+}
+
+/// The name of a tag without its module path, if it isn't generic.
+fn short_name(name: &'static str) -> &'static str {
+    match name.contains('<') {
+        true => name,
+        false => name.rsplit("::").next().unwrap_or(name),
+    }
+}
+
+/// The id of the tag with this name: its full name, or its name without its module path if no other tag has it.
+fn tag_id_from_scene_name(tagf: &TagFactory, name: &str) -> Option<u32> {
+    tagf.tag_id_from_name(name).or_else(|| {
+        let mut matches = (0..)
+            .zip(tagf.tag_names())
+            .filter(|(_, tag_name)| short_name(tag_name) == name);
+        let (id, _) = matches.next()?;
+        matches.next().is_none().then_some(id)
+    })
+}
+
+/// The name a tag is saved with: its name without its module path if no other tag has it.
+fn scene_name_of_tag(tagf: &TagFactory, id: u32) -> &'static str {
+    let name = tagf.tag_names()[id as usize];
+    match tag_id_from_scene_name(tagf, short_name(name)) {
+        Some(short_id) if short_id == id => short_name(name),
+        _ => name,
+    }
+}
+
+/// An entity that was read from a scene, before it's spawned.
+#[derive(Default)]
+struct ParsedEntity {
+    id: Option<SceneRef>,
+    /// The index of each component in the registry, and its value.
+    components: Vec<(usize, Box<dyn Any>)>,
+    tags: Vec<u32>,
+    /// The components that were upgraded from an older schema.
+    migrated: Vec<ComponentId>,
+    /// The components whose names aren't registered.
+    unknown: Vec<OpaqueComponent>,
+}
+
+/// Reads the text of a scene, remembering what went wrong so it can be reported with the position of the error.
+struct SceneParser<'a> {
+    registry: &'a SceneRegistry,
+    comp_factory: &'a ComponentFactory,
+    tagf: &'a TagFactory,
+    unknown: UnknownComponentPolicy,
+    ids: RefCell<HashMap<SceneRef, usize>>,
+    problem: Cell<Option<SceneProblem>>,
+}
+
+impl SceneParser<'_> {
+    fn fail<E: de::Error>(&self, problem: SceneProblem) -> E {
+        let error = E::custom(&problem);
+        self.problem.set(Some(problem));
+        error
+    }
+
+    fn parse(self, src: &str) -> Result<(Vec<ParsedEntity>, HashMap<SceneRef, usize>), SceneError> {
+        let mut deserializer = serde_json::Deserializer::from_str(src);
+        let entities = (&self)
+            .deserialize(&mut deserializer)
+            .and_then(|entities| deserializer.end().map(|_| entities))
+            .map_err(|error| {
+                let (line, column) = (error.line(), error.column());
+                let problem = self.problem.take().unwrap_or_else(|| {
+                    let message = error.to_string();
+                    let position = format!(" at line {line} column {column}");
+                    SceneProblem::Syntax(
+                        message
+                            .strip_suffix(&position)
+                            .unwrap_or(&message)
+                            .to_string(),
+                    )
+                });
+                SceneError::Parse {
+                    problem,
+                    line,
+                    column,
+                }
+            })?;
+        Ok((entities, self.ids.into_inner()))
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for &SceneParser<'_> {
+    type Value = Vec<ParsedEntity>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for &SceneParser<'_> {
+    type Value = Vec<ParsedEntity>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of entities")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut entities = Vec::new();
+        while let Some(entity) = seq.next_element_seed(EntitySeed {
+            parser: self,
+            entity: entities.len(),
+        })? {
+            entities.push(entity);
+        }
+        Ok(entities)
+    }
+}
+
+/// Reads one entity of a scene.
+#[derive(Clone, Copy)]
+struct EntitySeed<'p, 'a> {
+    parser: &'p SceneParser<'a>,
+    entity: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for EntitySeed<'_, '_> {
+    type Value = ParsedEntity;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for EntitySeed<'_, '_> {
+    type Value = ParsedEntity;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an entity")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        const FIELDS: &[&str] = &["id", "components", "tags", "schemas"];
+        let mut parsed = ParsedEntity::default();
+        let mut components = Vec::new();
+        let mut schemas: HashMap<String, u64> = HashMap::new();
+        let mut seen = [false; 4];
+        while let Some(key) = map.next_key::<String>()? {
+            let Some(field) = FIELDS.iter().position(|field| *field == key) else {
+                return Err(de::Error::unknown_field(&key, FIELDS));
+            };
+            if std::mem::replace(&mut seen[field], true) {
+                return Err(de::Error::duplicate_field(FIELDS[field]));
+            }
+            match field {
+                0 => {
+                    let id = SceneRef(map.next_value()?);
+                    if let Some(_first) = self.parser.ids.borrow_mut().insert(id, self.entity) {
+                        return Err(self.parser.fail(SceneProblem::DuplicateId {
+                            entity: self.entity,
+                            id,
+                        }));
+                    }
+                    parsed.id = Some(id);
+                }
+                1 => (components, parsed.unknown) = map.next_value_seed(ComponentsSeed(self))?,
+                2 => parsed.tags = map.next_value_seed(TagsSeed(self))?,
+                _ => schemas = map.next_value()?,
+            }
+        }
+
+        // The values are read once the schemas they were saved with are known.
+        let EntitySeed { parser, entity } = self;
+        for (index, mut value) in components {
+            let registered = &parser.registry.components[index];
+            let component = registered.name;
+            if let Some(&saved) = schemas.get(component) {
+                let (hops, migrated) = parser
+                    .registry
+                    .migration_path(parser.comp_factory, registered.comp_id, saved)
+                    .and_then(|path| {
+                        path.iter()
+                            .try_fold(value, |value, migrate| migrate(value))
+                            .map(|value| (path.len(), value))
+                    })
+                    .map_err(|error| {
+                        parser.fail(SceneProblem::Migration {
+                            entity,
+                            component,
+                            error,
+                        })
+                    })?;
+                if hops > 0 {
+                    parsed.migrated.push(registered.comp_id);
+                }
+                value = migrated;
+            }
+            let value = (registered.deserialize)(value).map_err(|error| {
+                parser.fail(SceneProblem::InvalidComponent {
+                    entity,
+                    component,
+                    message: error.to_string(),
+                })
+            })?;
+            parsed.components.push((index, value));
+        }
+        for opaque in &mut parsed.unknown {
+            opaque.schema = schemas.get(&opaque.name).copied();
+        }
+        Ok(parsed)
+    }
+}
+
+/// Reads the components of an entity: the index of each registered component and its value, and the components whose
+/// names aren't registered (unless they are rejected).
+struct ComponentsSeed<'p, 'a>(EntitySeed<'p, 'a>);
+
+impl<'de> DeserializeSeed<'de> for ComponentsSeed<'_, '_> {
+    type Value = (Vec<(usize, Value)>, Vec<OpaqueComponent>);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ComponentsSeed<'_, '_> {
+    type Value = (Vec<(usize, Value)>, Vec<OpaqueComponent>);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map from component names to their values")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let EntitySeed { parser, entity } = self.0;
+        let mut components: Vec<(usize, Value)> = Vec::new();
+        let mut unknown = Vec::new();
+        while let Some(name) = map.next_key::<String>()? {
+            let Some(&index) = parser.registry.by_name.get(name.as_str()) else {
+                if parser.unknown == UnknownComponentPolicy::Reject {
+                    return Err(parser.fail(SceneProblem::UnknownComponent { entity, name }));
+                }
+                unknown.push(OpaqueComponent {
+                    name,
+                    schema: None,
+                    value: map.next_value()?,
+                });
+                continue;
+            };
+            let component = parser.registry.components[index].name;
+            if components.iter().any(|(other, _)| *other == index) {
+                return Err(parser.fail(SceneProblem::DuplicateComponent { entity, component }));
+            }
+            components.push((index, map.next_value()?));
+        }
+        Ok((components, unknown))
+    }
+}
+
+/// Reads the tags of an entity.
+struct TagsSeed<'p, 'a>(EntitySeed<'p, 'a>);
+
+impl<'de> DeserializeSeed<'de> for TagsSeed<'_, '_> {
+    type Value = Vec<u32>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TagsSeed<'_, '_> {
+    type Value = Vec<u32>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of tag names")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let EntitySeed { parser, entity } = self.0;
+        let mut tags = Vec::new();
+        while let Some(name) = seq.next_element::<String>()? {
+            match tag_id_from_scene_name(parser.tagf, &name) {
+                Some(id) => tags.push(id),
+                None => return Err(parser.fail(SceneProblem::UnknownTag { entity, name })),
+            }
+        }
+        Ok(tags)
+    }
+}
+
+/// The components of an entity that was read from a scene.
+struct SceneBundle(Vec<(ComponentId, StoreFn, Box<dyn Any>)>);
+
+impl Bundle for SceneBundle {
+    fn raw_components_scope(
+        self,
+        comp_factory: &ComponentFactory,
+        f: &mut impl FnMut(ComponentId, OwningPtr<'_>),
+    ) {
+        for (comp_id, store, component) in self.0 {
+            store(component, comp_factory, &mut |ptr| f(comp_id, ptr));
+        }
+    }
+}
+
+impl World {
+    /// Register a [`Component`] that can be loaded from scenes and saved to them under `name` (see
+    /// [`World::load_scene`]). Registering it again replaces its name.
+    /// If the component couldn't be registered, return `None`.
+    ///
+    /// A component that refers to entities must be registered with
+    /// [`World::register_scene_component_with_refs`] instead.
+    ///
+    /// ```
+    /// use serde::{Deserialize, Serialize};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Serialize, Deserialize)]
+    /// struct Name(String);
+    ///
+    /// let mut world = World::default();
+    /// world.register_scene_component::<Name>("Name").unwrap();
+    /// let spawned = world.load_scene(r#"[{ "components": { "Name": "root" } }]"#).unwrap();
+    /// assert_eq!(world.get_component::<Name>(spawned.entities()[0]).unwrap().0, "root");
+    /// ```
+    ///
+    /// # Panics
+    /// If another component is registered under the same name.
+    pub fn register_scene_component<C: Component + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &'static str,
+    ) -> Option<ComponentId> {
+        let comp_id = self.components.register_component::<C>()?;
+        self.scenes.register(SceneComponent {
+            name,
+            comp_id,
+            deserialize: deserialize_component::<C>,
+            store: store_component::<C>,
+            serialize: serialize_component::<C>,
+            map_refs: None,
+        });
+        Some(comp_id)
+    }
+
+    /// Register a [`Component`] that refers to entities, so it can be loaded from scenes and saved to them under
+    /// `name`. Its references are rewritten with its [`MapEntities`] implementation: from scene ids to the spawned
+    /// entities when it's loaded, and back when it's saved. Otherwise like [`World::register_scene_component`].
+    ///
+    /// ```
+    /// use serde::{Deserialize, Serialize};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Serialize, Deserialize, Clone)]
+    /// struct Parent(EntityId);
+    ///
+    /// impl MapEntities for Parent {
+    ///     fn map_entities(&mut self, f: &mut dyn FnMut(EntityId) -> EntityId) {
+    ///         self.0.map_entities(f);
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.register_scene_component_with_refs::<Parent>("Parent").unwrap();
+    /// let scene = r#"[{ "id": 0, "components": {} }, { "components": { "Parent": 0 } }]"#;
+    /// let spawned = world.load_scene(scene).unwrap();
+    /// let (root, child) = (spawned.entities()[0], spawned.entities()[1]);
+    /// assert_eq!(world.get_component::<Parent>(child).unwrap().0, root);
+    /// ```
+    ///
+    /// # Panics
+    /// If another component is registered under the same name.
+    pub fn register_scene_component_with_refs<
+        C: Component + Serialize + DeserializeOwned + MapEntities + Clone,
+    >(
+        &mut self,
+        name: &'static str,
+    ) -> Option<ComponentId> {
+        let comp_id = self.components.register_component::<C>()?;
+        self.scenes.register(SceneComponent {
+            name,
+            comp_id,
+            deserialize: deserialize_component::<C>,
+            store: store_component::<C>,
+            serialize: serialize_component_with_refs::<C>,
+            map_refs: Some((map_read_component::<C>, map_stored_component::<C>)),
+        });
+        Some(comp_id)
+    }
+
+    /// Register a migration that upgrades the saved values of the scene component `to_component` from the schema
+    /// `from_schema` to `to_schema` (see [`StableComponentKey::SchemaHash`]). When a scene is loaded, a value that
+    /// was saved with an older schema goes through the migrations from its schema to the current schema of the
+    /// component, one hop at a time, and is then read as usual. Registering a migration from the same schema again
+    /// replaces it.
+    ///
+    /// ```
+    /// use serde::{Deserialize, Serialize};
+    /// use serde_json::{json, Value};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// /// Version 2 of `Health`, which was saved as a bare number in version 1.
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Health {
+    ///     current: u32,
+    ///     max: u32,
+    /// }
+    /// impl Data for Health {}
+    /// impl Component for Health {
+    ///     fn data_info() -> DataInfo {
+    ///         DataInfo::for_component::<Self>().with_stable_key(StableComponentKey::SchemaHash(2))
+    ///     }
+    /// }
+    ///
+    /// fn health_v1_to_v2(value: Value) -> Result<Value, MigrationError> {
+    ///     Ok(json!({ "current": value, "max": value }))
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// let health = world.register_scene_component::<Health>("Health").unwrap();
+    /// world.register_migration(1, 2, health, health_v1_to_v2);
+    /// let old_save = r#"[{ "components": { "Health": 10 }, "schemas": { "Health": 1 } }]"#;
+    /// let spawned = world.load_scene(old_save).unwrap();
+    /// assert_eq!(spawned.migrations()[&health], 1);
+    /// assert_eq!(world.get_component::<Health>(spawned.entities()[0]).unwrap().max, 10);
+    /// ```
+    ///
+    /// # Panics
+    /// If the migration doesn't change the schema.
+    #[track_caller]
+    pub fn register_migration(
+        &mut self,
+        from_schema: u64,
+        to_schema: u64,
+        to_component: ComponentId,
+        migrate: MigrateFn,
+    ) {
+        if from_schema == to_schema {
+            panics::fail(
+                "register migration",
+                "a migration must change the schema",
+                &[("schema", &format_args!("{from_schema:#x}"))],
+            );
+        }
+        self.scenes.migrations.insert(
+            (to_component, from_schema),
+            Migration { to_schema, migrate },
+        );
+    }
+
+    /// The components of the entity whose names weren't registered when it was loaded from a scene, and were
+    /// preserved (see [`UnknownComponentPolicy::Preserve`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let scene = r#"[{ "components": { "Removed": 7 } }]"#;
+    /// let spawned = world.load_scene_with(scene, UnknownComponentPolicy::Preserve).unwrap();
+    /// let opaque = world.opaque_components(spawned.entities()[0]);
+    /// assert_eq!(opaque[0].name, "Removed");
+    /// assert_eq!(opaque[0].value, 7);
+    /// ```
+    pub fn opaque_components(&self, entity: EntityId) -> &[OpaqueComponent] {
+        self.scenes.opaque.get(&entity).map_or(&[], Vec::as_slice)
+    }
+
+    /// Take the preserved components of the entity (see [`World::opaque_components`]), so they are no longer saved
+    /// with it.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let scene = r#"[{ "components": { "Removed": 7 } }]"#;
+    /// let entity = world.load_scene_with(scene, UnknownComponentPolicy::Preserve).unwrap().entities()[0];
+    /// assert_eq!(world.take_opaque_components(entity).len(), 1);
+    /// assert!(world.opaque_components(entity).is_empty());
+    /// assert!(!world.save_scene(&[entity]).unwrap().contains("Removed"));
+    /// ```
+    pub fn take_opaque_components(&mut self, entity: EntityId) -> Vec<OpaqueComponent> {
+        self.scenes.opaque.remove(&entity).unwrap_or_default()
+    }
+
+    /// Spawn the entities of a scene (see the [`scene`](crate::scene) module for its format), and return them.
+    ///
+    /// The whole scene is read and checked before anything is spawned: if a component or tag name isn't registered,
+    /// a value can't be read, or a reference doesn't resolve, nothing is spawned and the error reports where the
+    /// problem is. The entities are spawned in a batch for each distinct set of components, and then the references
+    /// in their components are rewritten from scene ids to the spawned entities.
+    ///
+    /// The scene is spawned all-or-nothing within the quotas of the world (see [`World::set_entity_limit`]): if
+    /// it doesn't fit, nothing is spawned, and the error reports the first entity that doesn't fit.
+    ///
+    /// ```
+    /// use serde::{Deserialize, Serialize};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Serialize, Deserialize)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.register_scene_component::<Health>("Health").unwrap();
+    /// let spawned = world.load_scene(r#"[{ "components": { "Health": 10 } }, { "components": { "Health": 5 } }]"#).unwrap();
+    /// assert_eq!(spawned.entities().len(), 2);
+    /// // Nothing is spawned if a part of the scene can't be read.
+    /// assert!(world.load_scene(r#"[{ "components": { "Health": 10 } }, { "components": { "Mana": 5 } }]"#).is_err());
+    /// assert_eq!(world.component_count::<Health>(), 2);
+    /// ```
+    ///
+    /// # Panics
+    /// If the entities would break a component rule (see [`World::require_component`]), or a storage they would be
+    /// spawned into is pinned (see [`World::pin_storage`]).
+    pub fn load_scene(&mut self, src: &str) -> Result<SceneSpawned, SceneError> {
+        self.load_scene_with(src, UnknownComponentPolicy::Reject)
+    }
+
+    /// Like [`World::load_scene`], but components whose names aren't registered are handled by the policy instead
+    /// of failing the load, for example to load the saves of an older version of a game.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let scene = r#"[{ "components": { "Removed": 7 } }]"#;
+    /// assert!(world.load_scene(scene).is_err());
+    /// let spawned = world.load_scene_with(scene, UnknownComponentPolicy::Drop).unwrap();
+    /// assert_eq!(spawned.entities().len(), 1);
+    /// assert_eq!(spawned.unknown_components().len(), 1);
+    /// ```
+    ///
+    /// # Panics
+    /// Like [`World::load_scene`].
+    pub fn load_scene_with(
+        &mut self,
+        src: &str,
+        unknown: UnknownComponentPolicy,
+    ) -> Result<SceneSpawned, SceneError> {
+        let (mut parsed, ids) = SceneParser {
+            registry: &self.scenes,
+            comp_factory: &self.components,
+            tagf: self.storages.tag_storage.tag_factory(),
+            unknown,
+            ids: RefCell::default(),
+            problem: Cell::default(),
+        }
+        .parse(src)?;
+
+        // Check every reference before anything is spawned.
+        let mut with_refs = Vec::new();
+        for (entity, parsed_entity) in parsed.iter_mut().enumerate() {
+            for (index, component) in &mut parsed_entity.components {
+                let registered = &self.scenes.components[*index];
+                let Some((map_read, map_stored)) = registered.map_refs else {
+                    continue;
+                };
+                let mut unresolved = None;
+                map_read(component.as_mut(), &mut |target| {
+                    match SceneRef::of_placeholder(target) {
+                        Some(scene_ref) if !ids.contains_key(&scene_ref) => {
+                            unresolved.get_or_insert(scene_ref);
+                        }
+                        _ => {}
+                    }
+                    target
+                });
+                if let Some(target) = unresolved {
+                    return Err(SceneError::UnresolvedRef {
+                        entity,
+                        component: registered.name,
+                        target,
+                    });
+                }
+                with_refs.push((entity, registered.comp_id, map_stored));
+            }
+        }
+
+        // Spawn a batch of entities for each distinct set of components.
+        let mut batches: Vec<(Vec<ComponentId>, Vec<usize>)> = Vec::new();
+        let mut batch_of: HashMap<Vec<ComponentId>, usize> = HashMap::new();
+        for (entity, parsed_entity) in parsed.iter_mut().enumerate() {
+            parsed_entity
+                .components
+                .sort_by_key(|(index, _)| self.scenes.components[*index].comp_id);
+            let comp_ids: Vec<ComponentId> = parsed_entity
+                .components
+                .iter()
+                .map(|(index, _)| self.scenes.components[*index].comp_id)
+                .collect();
+            let batch = *batch_of.entry(comp_ids.clone()).or_insert_with(|| {
+                batches.push((comp_ids, Vec::new()));
+                batches.len() - 1
+            });
+            batches[batch].1.push(entity);
+        }
+
+        // Check the quotas before anything is spawned, with the archetypes that the entities will be stored in.
+        if !self.quotas.is_empty() {
+            let next = self.entities.next_entity_id();
+            let mut pending = 0;
+            for (comp_ids, members) in &batches {
+                let arch_info = ArchetypeInfo::from_component_ids(comp_ids.clone());
+                let key = match self
+                    .check_component_rules(next, arch_info.prime_key(), |_| arch_info.clone())
+                {
+                    Ok(Some(completion)) => completion.arch_info.prime_key(),
+                    // Breaking a rule panics when the batch is spawned.
+                    Ok(None) | Err(_) => arch_info.prime_key(),
+                };
+                if let Err(error) = self.check_quota_after(pending, key, members.len()) {
+                    let SpawnError::QuotaExceeded { limit, current, .. } = &error else {
+                        unreachable!("only quotas are checked")
+                    };
+                    return Err(SceneError::QuotaExceeded {
+                        entity: members[limit.saturating_sub(*current)],
+                        error,
+                    });
+                }
+                pending += members.len();
+            }
+        }
+
+        let mut entities = vec![EntityId::new(0); parsed.len()];
+        for (comp_ids, members) in batches {
+            let arch_info = ArchetypeInfo::from_component_ids(comp_ids);
+            let bundles: Vec<SceneBundle> = members
+                .iter()
+                .map(|&entity| {
+                    SceneBundle(
+                        std::mem::take(&mut parsed[entity].components)
+                            .into_iter()
+                            .map(|(index, component)| {
+                                let registered = &self.scenes.components[index];
+                                (registered.comp_id, registered.store, component)
+                            })
+                            .collect(),
+                    )
+                })
+                .collect();
+            // SAFETY: The components of every bundle were sorted into `arch_info`, and an entity can't have the same
+            // component twice.
+            let spawned = unsafe { self.spawn_batch_with_info(&arch_info, bundles) }
+                .expect("scene components are registered");
+            for (entity, spawned) in members.into_iter().zip(spawned) {
+                entities[entity] = spawned;
+            }
+        }
+
+        // Rewrite the references, and tag the entities.
+        let ids: HashMap<SceneRef, EntityId> = ids
+            .into_iter()
+            .map(|(scene_ref, entity)| (scene_ref, entities[entity]))
+            .collect();
+        for (entity, comp_id, map_stored) in with_refs {
+            let entity_meta = *self.entities.get_entity_meta(entities[entity]).unwrap();
+            let storage = self
+                .storages
+                .arch_storages
+                .get_storage_mut(entity_meta.archetype_storage_id)
+                .unwrap();
+            let ptr = storage
+                .get_component_mut(entity_meta.archetype_storage_index, comp_id)
+                .unwrap();
+            // SAFETY: The pointer was taken with the component id of the registered component.
+            unsafe {
+                map_stored(ptr, &mut |target| {
+                    SceneRef::of_placeholder(target).map_or(target, |scene_ref| ids[&scene_ref])
+                })
+            };
+        }
+        for (entity, parsed_entity) in entities.iter().zip(&parsed) {
+            let mut tag_tracker = self.tag_tracker_of(*entity);
+            for &tag_id in &parsed_entity.tags {
+                // SAFETY: The tag id was resolved from the tag factory of this world, and the tracker of a new entity
+                // isn't accessed anywhere else.
+                unsafe { tag_tracker.set_tag_id(tag_id, true) };
+            }
+        }
+
+        // Count the migrations, and report (or preserve) the unknown components.
+        let mut migrations: HashMap<ComponentId, usize> = HashMap::new();
+        let mut unknown_components = Vec::new();
+        if unknown == UnknownComponentPolicy::Preserve {
+            let entities = &self.entities;
+            self.scenes
+                .opaque
+                .retain(|entity, _| entities.verify_generation(*entity));
+        }
+        for (&entity, parsed_entity) in entities.iter().zip(parsed) {
+            for comp_id in parsed_entity.migrated {
+                *migrations.entry(comp_id).or_default() += 1;
+            }
+            unknown_components.extend(
+                parsed_entity
+                    .unknown
+                    .iter()
+                    .map(|opaque| (entity, opaque.name.clone())),
+            );
+            if unknown == UnknownComponentPolicy::Preserve && !parsed_entity.unknown.is_empty() {
+                self.scenes.opaque.insert(entity, parsed_entity.unknown);
+            }
+        }
+        Ok(SceneSpawned {
+            entities,
+            ids,
+            migrations,
+            unknown: unknown_components,
+        })
+    }
+
+    /// Write `entities` as a scene that [`World::load_scene`] can load, for example to save the changes of an
+    /// editor. The entities get the scene ids `0, 1, 2...` in order, and references between them are written as
+    /// scene ids.
+    ///
+    /// Every component of the entities must be registered as a scene component (see
+    /// [`World::register_scene_component`]), and their references must be to entities that are saved with them.
+    /// Components whose [`StableComponentKey`] is a schema hash are saved with it, and the preserved components of
+    /// the entities (see [`World::opaque_components`]) are saved as they were loaded.
+    ///
+    /// ```
+    /// use serde::{Deserialize, Serialize};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Serialize, Deserialize)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.register_scene_component::<Health>("Health").unwrap();
+    /// let entity = world.spawn(Health(10));
+    /// let saved = world.save_scene(&[entity]).unwrap();
+    /// let mut loaded = World::default();
+    /// loaded.register_scene_component::<Health>("Health").unwrap();
+    /// let spawned = loaded.load_scene(&saved).unwrap();
+    /// assert_eq!(loaded.get_component::<Health>(spawned.entities()[0]).unwrap().0, 10);
+    /// ```
+    pub fn save_scene(&self, entities: &[EntityId]) -> Result<String, SceneError> {
+        let scene_ids: HashMap<EntityId, u32> = entities.iter().copied().zip(0..).collect();
+        let tagf = self.storages.tag_storage.tag_factory();
+        let mut scene = Vec::with_capacity(entities.len());
+        for (&entity, id) in entities.iter().zip(0u32..) {
+            let entity_meta = self
+                .entities
+                .get_entity_meta(entity)
+                .ok_or(SceneError::NotAlive(entity))?;
+            let storage = self
+                .storages
+                .arch_storages
+                .get_storage(entity_meta.archetype_storage_id)
+                .unwrap();
+            let mut components = serde_json::Map::new();
+            let mut schemas = serde_json::Map::new();
+            for comp_id in storage.component_ids() {
+                let Some(&index) = self.scenes.by_id.get(&comp_id) else {
+                    return Err(SceneError::NotSaveable {
+                        entity,
+                        component: self
+                            .components
+                            .get_component_info_from_component_id(comp_id)
+                            .expect("stored components are registered")
+                            .name(),
+                    });
+                };
+                let registered = &self.scenes.components[index];
+                let ptr = storage
+                    .get_component(entity_meta.archetype_storage_index, comp_id)
+                    .unwrap();
+                let mut unsaved = None;
+                // SAFETY: The pointer was taken with the component id of the registered component.
+                let value = unsafe {
+                    (registered.serialize)(ptr, &mut |target| match scene_ids.get(&target) {
+                        Some(&id) => SceneRef(id).placeholder(),
+                        None => *unsaved.get_or_insert(target),
+                    })
+                };
+                if let Some(target) = unsaved {
+                    return Err(SceneError::UnsavedRef {
+                        entity,
+                        component: registered.name,
+                        target,
+                    });
+                }
+                let value = value.map_err(|error| SceneError::Serialize {
+                    entity,
+                    component: registered.name,
+                    message: error.to_string(),
+                })?;
+                components.insert(registered.name.to_string(), value);
+                if let Some(StableComponentKey::SchemaHash(schema)) =
+                    self.components.stable_key(comp_id)
+                {
+                    schemas.insert(registered.name.to_string(), Value::from(*schema));
+                }
+            }
+            for opaque in self.opaque_components(entity) {
+                components.insert(opaque.name.clone(), opaque.value.clone());
+                if let Some(schema) = opaque.schema {
+                    schemas.insert(opaque.name.clone(), Value::from(schema));
+                }
+            }
+            let mut saved = serde_json::Map::new();
+            saved.insert("id".to_string(), Value::from(id));
+            saved.insert("components".to_string(), Value::Object(components));
+            if !schemas.is_empty() {
+                saved.insert("schemas".to_string(), Value::Object(schemas));
+            }
+            let tags: Vec<Value> = self
+                .tag_tracker_of(entity)
+                .tagged_ids()
+                .map(|tag_id| Value::from(scene_name_of_tag(tagf, tag_id)))
+                .collect();
+            if !tags.is_empty() {
+                saved.insert("tags".to_string(), Value::Array(tags));
+            }
+            scene.push(Value::Object(saved));
+        }
+        Ok(serde_json::to_string_pretty(&scene).expect("JSON values can be written"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SceneError, SceneProblem, SceneRef};
+    use crate::{prelude::*, scene::MapEntities};
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+
+    #[derive(Component, Serialize, Deserialize, Debug, PartialEq, Clone)]
+    struct Name(String);
+    #[derive(Component, Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+    #[derive(Component, Serialize, Deserialize, Debug, PartialEq, Clone)]
+    struct Parent(EntityId);
+    #[derive(Component, Serialize, Deserialize, Debug, PartialEq, Clone)]
+    struct Targets(Vec<EntityId>);
+    #[derive(Tag)]
+    struct Selected;
+
+    impl MapEntities for Parent {
+        fn map_entities(&mut self, f: &mut dyn FnMut(EntityId) -> EntityId) {
+            self.0.map_entities(f);
+        }
+    }
+
+    impl MapEntities for Targets {
+        fn map_entities(&mut self, f: &mut dyn FnMut(EntityId) -> EntityId) {
+            self.0.map_entities(f);
+        }
+    }
+
+    const SCENE: &str = r#"[
+        { "id": 0, "components": { "Name": "root", "Position": { "x": 0.0, "y": 0.0 } } },
+        { "id": 1, "components": { "Name": "left", "Parent": 0, "Position": { "x": -1.0, "y": 0.0 } } },
+        { "id": 2, "components": { "Name": "right", "Parent": 0, "Position": { "x": 1.0, "y": 0.0 } }, "tags": ["Selected"] },
+        { "components": { "Name": "turret", "Targets": [1, 2] } }
+    ]"#;
+
+    fn world() -> World {
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Selected>();
+        let mut world = World::with_tags(tagf);
+        world.register_scene_component::<Name>("Name");
+        world.register_scene_component::<Position>("Position");
+        world.register_scene_component_with_refs::<Parent>("Parent");
+        world.register_scene_component_with_refs::<Targets>("Targets");
+        world
+    }
+
+    fn names(world: &mut World) -> Vec<(String, Option<String>, bool)> {
+        let entities: Vec<(EntityId, String, Option<EntityId>)> = world
+            .query::<(EntityId, &Name, Option<&Parent>)>()
+            .map(|(entity, name, parent)| (entity, name.0.clone(), parent.map(|parent| parent.0)))
+            .collect();
+        let mut names: Vec<_> = entities
+            .into_iter()
+            .map(|(entity, name, parent)| {
+                let parent =
+                    parent.map(|parent| world.get_component::<Name>(parent).unwrap().0.clone());
+                // SAFETY: `Selected` is registered.
+                let selected = unsafe {
+                    world
+                        .get_tag_tracker(entity)
+                        .unwrap()
+                        .is_tagged::<Selected>()
+                };
+                (name, parent, selected)
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_load_scene_with_references() {
+        let mut world = world();
+        world.spawn(Name("existing".to_string()));
+        let spawned = world.load_scene(SCENE).unwrap();
+        assert_eq!(spawned.entities().len(), 4);
+        assert_eq!(spawned.ids().len(), 3);
+        let [root, left, right, turret] = spawned.entities().try_into().unwrap();
+        assert_eq!(spawned.get(SceneRef(0)), Some(root));
+        assert_eq!(spawned.get(SceneRef(2)), Some(right));
+        assert_eq!(spawned.get(SceneRef(3)), None);
+
+        assert_eq!(world.get_component::<Parent>(left), Some(&Parent(root)));
+        assert_eq!(world.get_component::<Parent>(right), Some(&Parent(root)));
+        assert_eq!(
+            world.get_component::<Targets>(turret),
+            Some(&Targets(vec![left, right]))
+        );
+        assert_eq!(
+            world.get_component::<Position>(right),
+            Some(&Position { x: 1.0, y: 0.0 })
+        );
+        assert_eq!(
+            world.get_component::<Name>(root),
+            Some(&Name("root".to_string()))
+        );
+        assert!(unsafe {
+            world
+                .get_tag_tracker(right)
+                .unwrap()
+                .is_tagged::<Selected>()
+        });
+        assert!(!unsafe { world.get_tag_tracker(left).unwrap().is_tagged::<Selected>() });
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let mut world = world();
+        let spawned = world.load_scene(SCENE).unwrap();
+        // Saving refers to the entities by their position in the saved list.
+        let mut saved_order = spawned.entities().to_vec();
+        saved_order.reverse();
+        let saved = world.save_scene(&saved_order).unwrap();
+        assert!(saved.contains("\"Selected\""), "{saved}");
+
+        let mut loaded = self::world();
+        loaded.spawn(Position { x: 5.0, y: 5.0 });
+        let reloaded = loaded.load_scene(&saved).unwrap();
+        assert_eq!(names(&mut world), names(&mut loaded));
+        let turret = reloaded.entities()[0];
+        let targets = loaded.get_component::<Targets>(turret).unwrap().0.clone();
+        let target_names: Vec<&Name> = targets
+            .iter()
+            .map(|target| loaded.get_component::<Name>(*target).unwrap())
+            .collect();
+        assert_eq!(
+            target_names,
+            [&Name("left".to_string()), &Name("right".to_string())]
+        );
+        // Saving the loaded entities in the same order gives the same text.
+        assert_eq!(loaded.save_scene(reloaded.entities()).unwrap(), saved);
+    }
+
+    #[test]
+    fn test_load_scene_errors() {
+        let mut world = world();
+        let typo = "[\n    { \"components\": { \"Name\": \"a\" } },\n    { \"components\": { \"Positon\": { \"x\": 0.0, \"y\": 0.0 } } }\n]";
+        let error = world.load_scene(typo).unwrap_err();
+        let SceneError::Parse {
+            problem,
+            line,
+            column: _,
+        } = &error
+        else {
+            panic!("{error}");
+        };
+        assert_eq!(
+            problem,
+            &SceneProblem::UnknownComponent {
+                entity: 1,
+                name: "Positon".to_string()
+            }
+        );
+        assert_eq!(*line, 3);
+        assert!(error
+            .to_string()
+            .starts_with("unknown component `Positon` at entities[1].components (line 3"));
+        // Nothing was spawned.
+        assert_eq!(world.query::<&Name>().count(), 0);
+
+        let unknown_tag = r#"[{ "components": {}, "tags": ["Hidden"] }]"#;
+        assert!(matches!(
+            world.load_scene(unknown_tag),
+            Err(SceneError::Parse {
+                problem: SceneProblem::UnknownTag { entity: 0, .. },
+                ..
+            })
+        ));
+        let invalid = r#"[{ "components": { "Position": { "x": 1.0 } } }]"#;
+        assert!(matches!(
+            world.load_scene(invalid),
+            Err(SceneError::Parse {
+                problem: SceneProblem::InvalidComponent {
+                    component: "Position",
+                    ..
+                },
+                ..
+            })
+        ));
+        let duplicate = r#"[{ "id": 4, "components": {} }, { "id": 4, "components": {} }]"#;
+        assert!(matches!(
+            world.load_scene(duplicate),
+            Err(SceneError::Parse {
+                problem: SceneProblem::DuplicateId {
+                    entity: 1,
+                    id: SceneRef(4)
+                },
+                ..
+            })
+        ));
+        let syntax = r#"[{ "components": {} }"#;
+        assert!(matches!(
+            world.load_scene(syntax),
+            Err(SceneError::Parse {
+                problem: SceneProblem::Syntax(_),
+                line: 1,
+                ..
+            })
+        ));
+        let unresolved = r#"[{ "components": { "Parent": 7 } }]"#;
+        assert!(matches!(
+            world.load_scene(unresolved),
+            Err(SceneError::UnresolvedRef {
+                entity: 0,
+                component: "Parent",
+                target: SceneRef(7)
+            })
+        ));
+        assert_eq!(world.query::<()>().count(), 0);
+    }
+
+    #[test]
+    fn test_load_scene_within_quotas() {
+        let mut world = world();
+        world.spawn(Name("existing".to_string()));
+        world.spawn(Name("another".to_string()));
+        world.set_entity_limit(5);
+        // The entities are spawned in batches of ({Name, Position}, {Name, Parent, Position}, {Name, Targets}), and
+        // the turret is the first one that doesn't fit.
+        assert_eq!(
+            world.load_scene(SCENE).unwrap_err(),
+            SceneError::QuotaExceeded {
+                entity: 3,
+                error: SpawnError::QuotaExceeded {
+                    scope: QuotaScope::Entities,
+                    limit: 5,
+                    current: 5,
+                },
+            }
+        );
+        assert_eq!(world.query::<&Name>().count(), 2);
+
+        world.remove_entity_limit();
+        world.set_archetype_limit::<(Name, Parent, Position)>(1);
+        let error = world.load_scene(SCENE).unwrap_err();
+        assert!(matches!(error, SceneError::QuotaExceeded { entity: 2, .. }));
+        assert!(error
+            .to_string()
+            .starts_with("entities[2] can't be spawned"));
+        assert!(error.to_string().contains(std::any::type_name::<Parent>()));
+        assert_eq!(world.query::<&Name>().count(), 2);
+
+        world.set_archetype_limit::<(Name, Parent, Position)>(2);
+        assert_eq!(world.load_scene(SCENE).unwrap().entities().len(), 4);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_save_scene_errors() {
+        #[derive(Component)]
+        struct Unsaved;
+
+        let mut world = world();
+        let root = world.spawn(Name("root".to_string()));
+        let child = world.spawn(Parent(root));
+        let other = world.spawn((Name("other".to_string()), Unsaved));
+        assert_eq!(
+            world.save_scene(&[child]),
+            Err(SceneError::UnsavedRef {
+                entity: child,
+                component: "Parent",
+                target: root
+            })
+        );
+        assert!(world.save_scene(&[root, child]).is_ok());
+        assert_eq!(
+            world.save_scene(&[other]),
+            Err(SceneError::NotSaveable {
+                entity: other,
+                component: std::any::type_name::<Unsaved>()
+            })
+        );
+        world.despawn(root);
+        assert_eq!(world.save_scene(&[root]), Err(SceneError::NotAlive(root)));
+    }
+
+    /// Version 3 of a component: version 1 was a bare number, and version 2 was `{ "hp": n }`.
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Health {
+        current: u32,
+        max: u32,
+    }
+    impl Data for Health {}
+    impl Component for Health {
+        fn data_info() -> DataInfo {
+            DataInfo::for_component::<Self>().with_stable_key(StableComponentKey::SchemaHash(3))
+        }
+    }
+
+    fn health_v1_to_v2(value: Value) -> Result<Value, MigrationError> {
+        Ok(json!({ "hp": value }))
+    }
+
+    fn health_v2_to_v3(value: Value) -> Result<Value, MigrationError> {
+        let hp = value["hp"]
+            .as_u64()
+            .ok_or_else(|| MigrationError::Invalid("`hp` isn't a number".to_string()))?;
+        Ok(json!({ "current": hp, "max": hp }))
+    }
+
+    fn health_world() -> (World, ComponentId) {
+        let mut world = world();
+        let health = world.register_scene_component::<Health>("Health").unwrap();
+        (world, health)
+    }
+
+    #[test]
+    fn test_migration_chain() {
+        let (mut world, health) = health_world();
+        world.register_migration(2, 3, health, health_v2_to_v3);
+        world.register_migration(1, 2, health, health_v1_to_v2);
+        let old_save = r#"[
+            { "components": { "Name": "v1", "Health": 10 }, "schemas": { "Health": 1 } },
+            { "schemas": { "Health": 2 }, "components": { "Name": "v2", "Health": { "hp": 20 } } },
+            { "components": { "Name": "v3", "Health": { "current": 5, "max": 30 } }, "schemas": { "Health": 3 } },
+            { "components": { "Name": "unversioned", "Health": { "current": 40, "max": 40 } } }
+        ]"#;
+        let spawned = world.load_scene(old_save).unwrap();
+        assert_eq!(spawned.migrations().len(), 1);
+        assert_eq!(spawned.migrations()[&health], 2);
+        let healths: Vec<&Health> = spawned
+            .entities()
+            .iter()
+            .map(|entity| world.get_component::<Health>(*entity).unwrap())
+            .collect();
+        assert_eq!(
+            healths,
+            [
+                &Health {
+                    current: 10,
+                    max: 10
+                },
+                &Health {
+                    current: 20,
+                    max: 20
+                },
+                &Health {
+                    current: 5,
+                    max: 30
+                },
+                &Health {
+                    current: 40,
+                    max: 40
+                },
+            ]
+        );
+
+        // The current schema is saved, so the saved scene loads without migrations.
+        let saved = world.save_scene(spawned.entities()).unwrap();
+        assert_eq!(saved.matches("\"schemas\"").count(), 4, "{saved}");
+        let reloaded = world.load_scene(&saved).unwrap();
+        assert!(reloaded.migrations().is_empty());
+
+        // A failed migration is reported with the component.
+        let invalid =
+            r#"[{ "components": { "Health": { "mp": 1 } }, "schemas": { "Health": 2 } }]"#;
+        let error = world.load_scene(invalid).unwrap_err();
+        assert!(matches!(
+            error,
+            SceneError::Parse {
+                problem: SceneProblem::Migration {
+                    entity: 0,
+                    component: "Health",
+                    error: MigrationError::Invalid(_),
+                },
+                ..
+            }
+        ));
+        assert!(error
+            .to_string()
+            .starts_with("can't migrate entities[0].components.Health: `hp` isn't a number"));
+    }
+
+    #[test]
+    fn test_migration_gaps_and_cycles() {
+        let (mut world, health) = health_world();
+        world.register_migration(2, 3, health, health_v2_to_v3);
+        let old_save = r#"[
+            { "components": { "Name": "v2", "Health": { "hp": 20 } }, "schemas": { "Health": 2 } },
+            { "components": { "Name": "v1", "Health": 10 }, "schemas": { "Health": 1 } }
+        ]"#;
+        let error = world.load_scene(old_save).unwrap_err();
+        let SceneError::Parse { problem, .. } = &error else {
+            panic!("{error}");
+        };
+        assert_eq!(
+            problem,
+            &SceneProblem::Migration {
+                entity: 1,
+                component: "Health",
+                error: MigrationError::MissingLink {
+                    saved: 1,
+                    current: Some(3),
+                    missing: 1
+                },
+            }
+        );
+        assert!(
+            error.to_string().contains(
+                "no migration from schema 0x1, on the way from the saved schema 0x1 to the current schema 0x3"
+            ),
+            "{error}"
+        );
+        // Nothing was spawned.
+        assert_eq!(world.query::<&Name>().count(), 0);
+
+        // The migrations from 5 lead back to 5, and never reach 3.
+        world.register_migration(5, 6, health, health_v1_to_v2);
+        world.register_migration(6, 5, health, health_v1_to_v2);
+        let looping = r#"[{ "components": { "Health": 1 }, "schemas": { "Health": 6 } }]"#;
+        let error = world.load_scene(looping).unwrap_err();
+        assert!(matches!(
+            &error,
+            SceneError::Parse {
+                problem: SceneProblem::Migration {
+                    error: MigrationError::Cycle(schemas),
+                    ..
+                },
+                ..
+            } if schemas == &[6, 5, 6]
+        ));
+        assert!(error
+            .to_string()
+            .contains("the migrations loop through the schemas 0x6 -> 0x5 -> 0x6"));
+    }
+
+    #[test]
+    #[should_panic(expected = "a migration must change the schema")]
+    fn test_migration_must_change_the_schema() {
+        let (mut world, health) = health_world();
+        world.register_migration(3, 3, health, health_v1_to_v2);
+    }
+
+    #[test]
+    fn test_unknown_component_policies() {
+        const OLD_SAVE: &str = r#"[
+            { "components": { "Name": "knight", "Health": 10 }, "schemas": { "Health": 1 } },
+            { "components": { "Name": "squire" } }
+        ]"#;
+        let mut world = world();
+        assert!(matches!(
+            world.load_scene(OLD_SAVE),
+            Err(SceneError::Parse {
+                problem: SceneProblem::UnknownComponent { entity: 0, .. },
+                ..
+            })
+        ));
+        assert_eq!(world.query::<&Name>().count(), 0);
+
+        let dropped = world
+            .load_scene_with(OLD_SAVE, UnknownComponentPolicy::Drop)
+            .unwrap();
+        let knight = dropped.entities()[0];
+        assert_eq!(
+            dropped.unknown_components(),
+            [(knight, "Health".to_string())]
+        );
+        assert!(world.opaque_components(knight).is_empty());
+        assert!(!world.save_scene(&[knight]).unwrap().contains("Health"));
+
+        let preserved = world
+            .load_scene_with(OLD_SAVE, UnknownComponentPolicy::Preserve)
+            .unwrap();
+        let [knight, squire] = preserved.entities().try_into().unwrap();
+        assert_eq!(
+            preserved.unknown_components(),
+            [(knight, "Health".to_string())]
+        );
+        assert_eq!(
+            world.opaque_components(knight),
+            [OpaqueComponent {
+                name: "Health".to_string(),
+                schema: Some(1),
+                value: json!(10),
+            }]
+        );
+        assert!(world.opaque_components(squire).is_empty());
+
+        // The preserved component is saved with its schema, and is loaded by a world that knows it again.
+        let saved = world.save_scene(&[knight, squire]).unwrap();
+        let (mut upgraded, health) = health_world();
+        upgraded.register_migration(1, 2, health, health_v1_to_v2);
+        upgraded.register_migration(2, 3, health, health_v2_to_v3);
+        let reloaded = upgraded.load_scene(&saved).unwrap();
+        assert_eq!(reloaded.migrations()[&health], 1);
+        assert_eq!(
+            upgraded.get_component::<Health>(reloaded.entities()[0]),
+            Some(&Health {
+                current: 10,
+                max: 10
+            })
+        );
+
+        assert_eq!(world.take_opaque_components(knight).len(), 1);
+        assert!(world.opaque_components(knight).is_empty());
+        world.assert_invariants();
+    }
+}
@@ -0,0 +1,544 @@
+use crate::{prelude::Component, world::World};
+use std::{
+    any::{Any, TypeId},
+    fmt,
+    hash::Hash,
+};
+
+/// A label of a group of systems in a [`Schedule`]. Any small value that can be compared for equality can be
+/// a label, like a `&'static str` or a fieldless `enum`.
+pub trait ScheduleLabel: fmt::Debug + Send + Sync + 'static {
+    /// Returns `true` if the other label is equal to this label (labels of different types are never equal).
+    fn dyn_eq(&self, other: &dyn ScheduleLabel) -> bool;
+    /// Convert the label to [`Any`], to compare it with labels of other types.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<L: Copy + Eq + Hash + fmt::Debug + Send + Sync + 'static> ScheduleLabel for L {
+    fn dyn_eq(&self, other: &dyn ScheduleLabel) -> bool {
+        other.as_any().downcast_ref::<L>() == Some(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// The components that a system reads and writes, for a future parallel executor. See [`System::access`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemAccess {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+}
+
+impl SystemAccess {
+    /// Declare that the system reads the component `C`.
+    pub fn reads<C: Component>(mut self) -> Self {
+        if !self.reads.contains(&TypeId::of::<C>()) {
+            self.reads.push(TypeId::of::<C>());
+        }
+        self
+    }
+
+    /// Declare that the system writes to the component `C`.
+    pub fn writes<C: Component>(mut self) -> Self {
+        if !self.writes.contains(&TypeId::of::<C>()) {
+            self.writes.push(TypeId::of::<C>());
+        }
+        self
+    }
+
+    /// The components that are read (but not necessarily written).
+    pub fn read_components(&self) -> &[TypeId] {
+        &self.reads
+    }
+
+    /// The components that are written.
+    pub fn written_components(&self) -> &[TypeId] {
+        &self.writes
+    }
+
+    /// Returns `true` if a system with this access can't run at the same time as a system with the other access:
+    /// one of them writes to a component that the other reads or writes.
+    pub fn conflicts_with(&self, other: &SystemAccess) -> bool {
+        self.writes
+            .iter()
+            .any(|write| other.reads.contains(write) || other.writes.contains(write))
+            || other.writes.iter().any(|write| self.reads.contains(write))
+    }
+
+    /// Add the access of the other system to this access.
+    pub fn extend(&mut self, other: &SystemAccess) {
+        for read in &other.reads {
+            if !self.reads.contains(read) {
+                self.reads.push(*read);
+            }
+        }
+        for write in &other.writes {
+            if !self.writes.contains(write) {
+                self.writes.push(*write);
+            }
+        }
+    }
+
+    /// Attach this access to a system.
+    pub fn to<F: FnMut(&mut World) + Send + 'static>(self, system: F) -> WithAccess<F> {
+        WithAccess {
+            system,
+            access: self,
+        }
+    }
+}
+
+/// A system that runs on the [`World`], as part of a [`Schedule`].
+pub trait System: Send + 'static {
+    /// Run the system.
+    fn run(&mut self, world: &mut World);
+
+    /// The components that the system reads and writes. The default is an empty access, meaning the access
+    /// of the system is unknown.
+    fn access(&self) -> SystemAccess {
+        SystemAccess::default()
+    }
+}
+
+impl<F: FnMut(&mut World) + Send + 'static> System for F {
+    fn run(&mut self, world: &mut World) {
+        self(world)
+    }
+}
+
+/// A system with a declared [`SystemAccess`], see [`SystemAccess::to`].
+pub struct WithAccess<F> {
+    system: F,
+    access: SystemAccess,
+}
+
+impl<F: FnMut(&mut World) + Send + 'static> System for WithAccess<F> {
+    fn run(&mut self, world: &mut World) {
+        (self.system)(world)
+    }
+
+    fn access(&self) -> SystemAccess {
+        self.access.clone()
+    }
+}
+
+/// An error when building a [`Schedule`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleBuildError {
+    /// The ordering constraints have a cycle.
+    Cycle {
+        /// The labels on the cycle (or cycles), formatted with [`Debug`](fmt::Debug).
+        labels: Vec<String>,
+    },
+}
+
+impl fmt::Display for ScheduleBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleBuildError::Cycle { labels } => {
+                write!(
+                    f,
+                    "the ordering constraints of the labels {} have a cycle",
+                    labels.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScheduleBuildError {}
+
+/// An interned [`ScheduleLabel`]: its index in the labels of the [`Schedule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LabelId(usize);
+
+/// Interned labels, and everything that's known about them.
+struct LabelEntry {
+    label: Box<dyn ScheduleLabel>,
+    /// The label formatted with [`Debug`](fmt::Debug), which orders labels that aren't otherwise ordered.
+    name: String,
+    /// The access of all of the systems with this label.
+    access: SystemAccess,
+}
+
+/// Runs systems on a [`World`], in an order that is built from ordering constraints between their labels.
+///
+/// Every system is added with a label ([`Schedule::add`]), and [`Schedule::order`] declares that the systems
+/// with one label run before the systems with another label. [`Schedule::build`] sorts the labels by their
+/// constraints, and then [`Schedule::run`] runs the systems in that order. Systems with the same label run in
+/// the order in which they were added. Labels that aren't ordered relative to each other run in the order of
+/// their [`Debug`](fmt::Debug) output, so the order doesn't depend on the order in which the labels were
+/// first used (for example, by different plugins).
+#[derive(Default)]
+pub struct Schedule {
+    labels: Vec<LabelEntry>,
+    systems: Vec<(LabelId, Box<dyn System>)>,
+    /// `(before, after)` pairs.
+    constraints: Vec<(LabelId, LabelId)>,
+    /// The indexes of the systems, in the order in which they run. `None` if the schedule has to be built again.
+    execution_order: Option<Vec<usize>>,
+}
+
+impl Schedule {
+    /// Create a new empty [`Schedule`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn intern(&mut self, label: impl ScheduleLabel) -> LabelId {
+        if let Some(id) = self
+            .labels
+            .iter()
+            .position(|entry| entry.label.dyn_eq(&label))
+        {
+            return LabelId(id);
+        }
+        self.labels.push(LabelEntry {
+            name: format!("{label:?}"),
+            label: Box::new(label),
+            access: SystemAccess::default(),
+        });
+        LabelId(self.labels.len() - 1)
+    }
+
+    /// Add a system with a label. The schedule has to be built again before it runs.
+    pub fn add(&mut self, label: impl ScheduleLabel, system: impl System) -> &mut Self {
+        let label = self.intern(label);
+        self.labels[label.0].access.extend(&system.access());
+        self.systems.push((label, Box::new(system)));
+        self.execution_order = None;
+        self
+    }
+
+    /// Declare that the systems with the label `before` run before the systems with the label `after`.
+    /// The schedule has to be built again before it runs.
+    pub fn order(&mut self, before: impl ScheduleLabel, after: impl ScheduleLabel) -> &mut Self {
+        let constraint = (self.intern(before), self.intern(after));
+        if !self.constraints.contains(&constraint) {
+            self.constraints.push(constraint);
+        }
+        self.execution_order = None;
+        self
+    }
+
+    /// Sort the systems by the ordering constraints of their labels. Fails if the constraints have a cycle.
+    pub fn build(&mut self) -> Result<(), ScheduleBuildError> {
+        let labels = self.labels.len();
+        let mut incoming = vec![0usize; labels];
+        for (_, after) in &self.constraints {
+            incoming[after.0] += 1;
+        }
+        let mut sorted: Vec<usize> = Vec::with_capacity(labels);
+        let mut ready: Vec<usize> = (0..labels).filter(|label| incoming[*label] == 0).collect();
+        while !ready.is_empty() {
+            // Run the label with the smallest name first, and keep the rest ready.
+            let next = (0..ready.len())
+                .min_by(|a, b| {
+                    self.labels[ready[*a]]
+                        .name
+                        .cmp(&self.labels[ready[*b]].name)
+                })
+                .map(|i| ready.swap_remove(i))
+                .unwrap();
+            sorted.push(next);
+            for (before, after) in &self.constraints {
+                if before.0 == next {
+                    incoming[after.0] -= 1;
+                    if incoming[after.0] == 0 {
+                        ready.push(after.0);
+                    }
+                }
+            }
+        }
+        if sorted.len() < labels {
+            return Err(ScheduleBuildError::Cycle {
+                labels: self.cycle_labels(&sorted),
+            });
+        }
+
+        let mut execution_order = Vec::with_capacity(self.systems.len());
+        for label in sorted {
+            execution_order.extend(
+                self.systems
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (system_label, _))| system_label.0 == label)
+                    .map(|(i, _)| i),
+            );
+        }
+        self.execution_order = Some(execution_order);
+        Ok(())
+    }
+
+    /// The names of the labels on cycles, given the labels that could be sorted. The labels that weren't sorted
+    /// are either on a cycle, or after one, so the labels that are only after cycles are left out.
+    fn cycle_labels(&self, sorted: &[usize]) -> Vec<String> {
+        let mut remaining: Vec<usize> = (0..self.labels.len())
+            .filter(|label| !sorted.contains(label))
+            .collect();
+        // A label on a cycle is before another remaining label.
+        while let Some(i) = remaining.iter().position(|label| {
+            !self
+                .constraints
+                .iter()
+                .any(|(before, after)| before.0 == *label && remaining.contains(&after.0))
+        }) {
+            remaining.remove(i);
+        }
+        remaining
+            .into_iter()
+            .map(|label| self.labels[label].name.clone())
+            .collect()
+    }
+
+    /// Returns `true` if the schedule was built since systems or constraints were last added.
+    pub fn is_built(&self) -> bool {
+        self.execution_order.is_some()
+    }
+
+    /// Run the systems, in the order that was built by [`Schedule::build`].
+    ///
+    /// Panics if the schedule wasn't built since systems or constraints were last added.
+    pub fn run(&mut self, world: &mut World) {
+        let execution_order = self
+            .execution_order
+            .as_ref()
+            .expect("The schedule must be built (with `Schedule::build`) before it runs.");
+        for system in execution_order {
+            self.systems[*system].1.run(world);
+        }
+    }
+
+    /// The label of each system, in the order in which the systems run (if the schedule is built).
+    pub fn execution_order(&self) -> Option<impl Iterator<Item = &dyn ScheduleLabel> + '_> {
+        self.execution_order.as_ref().map(|execution_order| {
+            execution_order
+                .iter()
+                .map(|system| &*self.labels[self.systems[*system].0 .0].label)
+        })
+    }
+
+    /// The combined access of all of the systems with the label, or `None` if the label isn't used.
+    pub fn label_access(&self, label: impl ScheduleLabel) -> Option<&SystemAccess> {
+        self.labels
+            .iter()
+            .find(|entry| entry.label.dyn_eq(&label))
+            .map(|entry| &entry.access)
+    }
+}
+
+impl fmt::Debug for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Schedule")
+            .field("systems", &self.systems.len())
+            .field(
+                "labels",
+                &self
+                    .labels
+                    .iter()
+                    .map(|entry| &entry.name)
+                    .collect::<Vec<_>>(),
+            )
+            .field("is_built", &self.is_built())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Component)]
+    struct Position(f32);
+
+    #[derive(Component)]
+    struct Velocity(f32);
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Stage {
+        Input,
+        Physics,
+        Collision,
+        Render,
+    }
+
+    /// A system that logs its name when it runs.
+    fn logger(log: &Arc<Mutex<Vec<&'static str>>>, name: &'static str) -> impl System {
+        let log = log.clone();
+        move |_: &mut World| log.lock().unwrap().push(name)
+    }
+
+    fn labels(schedule: &Schedule) -> Vec<String> {
+        schedule
+            .execution_order()
+            .unwrap()
+            .map(|label| format!("{label:?}"))
+            .collect()
+    }
+
+    #[test]
+    fn test_diamond_ordering() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule
+            .add(Stage::Render, logger(&log, "render"))
+            .add(Stage::Collision, logger(&log, "collision"))
+            .add(Stage::Physics, logger(&log, "physics 1"))
+            .add("audio", logger(&log, "audio"))
+            .add(Stage::Input, logger(&log, "input"))
+            .add(Stage::Physics, logger(&log, "physics 2"))
+            .order(Stage::Input, Stage::Physics)
+            .order(Stage::Input, "audio")
+            .order(Stage::Physics, Stage::Collision)
+            .order("audio", Stage::Render)
+            .order(Stage::Collision, Stage::Render);
+        assert!(!schedule.is_built());
+        schedule.build().unwrap();
+        let mut world = World::default();
+        schedule.run(&mut world);
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "input",
+                "audio",
+                "physics 1",
+                "physics 2",
+                "collision",
+                "render"
+            ]
+        );
+
+        // Adding a system requires building the schedule again.
+        schedule.add(Stage::Input, logger(&log, "input 2"));
+        assert!(!schedule.is_built());
+        schedule.build().unwrap();
+        log.lock().unwrap().clear();
+        schedule.run(&mut world);
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "input",
+                "input 2",
+                "audio",
+                "physics 1",
+                "physics 2",
+                "collision",
+                "render"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cycle_error() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add(Stage::Input, |_: &mut World| {})
+            .order(Stage::Input, Stage::Physics)
+            .order(Stage::Physics, Stage::Collision)
+            .order(Stage::Collision, Stage::Physics)
+            .order(Stage::Collision, Stage::Render);
+        let error = schedule.build().unwrap_err();
+        assert_eq!(
+            error,
+            ScheduleBuildError::Cycle {
+                labels: vec!["Physics".into(), "Collision".into()]
+            }
+        );
+        assert!(error.to_string().contains("Physics, Collision"));
+        assert!(!schedule.is_built());
+    }
+
+    #[test]
+    fn test_registration_order_independence() {
+        // Each plugin adds its systems, and its own constraints.
+        fn physics_plugin(schedule: &mut Schedule) {
+            schedule
+                .add(Stage::Physics, |_: &mut World| {})
+                .add(Stage::Collision, |_: &mut World| {})
+                .order(Stage::Physics, Stage::Collision);
+        }
+        fn render_plugin(schedule: &mut Schedule) {
+            schedule
+                .add(Stage::Render, |_: &mut World| {})
+                .add("ui", |_: &mut World| {})
+                .order(Stage::Physics, Stage::Render)
+                .order("ui", Stage::Render);
+        }
+        fn input_plugin(schedule: &mut Schedule) {
+            schedule
+                .add(Stage::Input, |_: &mut World| {})
+                .order(Stage::Input, Stage::Physics);
+        }
+
+        let mut first = Schedule::new();
+        physics_plugin(&mut first);
+        render_plugin(&mut first);
+        input_plugin(&mut first);
+        first.build().unwrap();
+        let mut second = Schedule::new();
+        input_plugin(&mut second);
+        render_plugin(&mut second);
+        physics_plugin(&mut second);
+        second.build().unwrap();
+
+        assert_eq!(labels(&first), labels(&second));
+        assert_eq!(
+            labels(&first),
+            ["\"ui\"", "Input", "Physics", "Collision", "Render"]
+        );
+    }
+
+    #[test]
+    fn test_label_access() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add(
+                Stage::Physics,
+                SystemAccess::default()
+                    .reads::<Velocity>()
+                    .writes::<Position>()
+                    .to(|world: &mut World| {
+                        world
+                            .query::<(&mut Position, &Velocity)>()
+                            .for_each(|(position, velocity)| position.0 += velocity.0);
+                    }),
+            )
+            .add(
+                Stage::Physics,
+                SystemAccess::default().writes::<Velocity>().to(|_| {}),
+            )
+            .add(
+                Stage::Render,
+                SystemAccess::default().reads::<Position>().to(|_| {}),
+            );
+
+        let physics = schedule.label_access(Stage::Physics).unwrap();
+        assert_eq!(physics.read_components(), [TypeId::of::<Velocity>()]);
+        assert_eq!(
+            physics.written_components(),
+            [TypeId::of::<Position>(), TypeId::of::<Velocity>()]
+        );
+        let render = schedule.label_access(Stage::Render).unwrap();
+        assert!(physics.conflicts_with(render));
+        assert!(!render.conflicts_with(render));
+        assert!(schedule.label_access(Stage::Input).is_none());
+
+        let mut world = World::default();
+        let entity = world.spawn((Position(0.0), Velocity(2.0)));
+        schedule.build().unwrap();
+        schedule.run(&mut world);
+        assert_eq!(world.get_component::<Position>(entity).unwrap().0, 2.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "must be built")]
+    fn test_run_before_build() {
+        let mut schedule = Schedule::new();
+        schedule.add(Stage::Input, |_: &mut World| {});
+        schedule.run(&mut World::default());
+    }
+}
@@ -0,0 +1,433 @@
+//! The allocator that the storages of components allocate their memory with.
+
+use crate::{
+    archetype::key::PrimeArchKey, component::ComponentId, world::latency::LatencyRecorder,
+};
+use std::{
+    alloc::Layout,
+    collections::{BTreeSet, HashMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// An allocator for the memory of the storages of components (the [`BlobVec`](super::blob_vec::BlobVec)s and
+/// [`InlineColumns`](super::columns::InlineColumns) of the [`World`](crate::world::World)), for example an arena
+/// on platforms where general-purpose heap allocation is expensive. See
+/// [`World::with_allocator`](crate::world::World::with_allocator).
+///
+/// # Safety
+/// The methods must uphold the contracts of their [`GlobalAlloc`](std::alloc::GlobalAlloc) counterparts:
+/// a returned block must fit the requested layout (or be null if the allocation failed), and must stay valid
+/// until it's passed to [`StorageAlloc::dealloc`] or [`StorageAlloc::realloc`].
+pub unsafe trait StorageAlloc: Send + Sync {
+    /// Allocate a block of memory that fits `layout`. See [`GlobalAlloc::alloc`](std::alloc::GlobalAlloc::alloc).
+    ///
+    /// # Safety
+    /// `layout` must have a non-zero size.
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8;
+
+    /// Shrink or grow a block of memory to `new_size` bytes. See
+    /// [`GlobalAlloc::realloc`](std::alloc::GlobalAlloc::realloc).
+    ///
+    /// # Safety
+    /// `ptr` must have been allocated by this allocator with `layout`, and `new_size` must be non-zero
+    /// (and not overflow when rounded up to the alignment of `layout`).
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8;
+
+    /// Deallocate a block of memory. See [`GlobalAlloc::dealloc`](std::alloc::GlobalAlloc::dealloc).
+    ///
+    /// # Safety
+    /// `ptr` must have been allocated by this allocator with `layout`.
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout);
+}
+
+/// The default [`StorageAlloc`], which uses the global allocator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GlobalStorageAlloc;
+
+// SAFETY: The global allocator upholds the same contracts.
+unsafe impl StorageAlloc for GlobalStorageAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        std::alloc::alloc(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        std::alloc::realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        std::alloc::dealloc(ptr, layout)
+    }
+}
+
+/// Why the memory of a storage was allocated, reallocated or deallocated (see [`AllocEvent`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AllocReason {
+    /// A storage made room for more components.
+    Growth,
+    /// A storage reserved room for a batch of entities up front (see
+    /// [`World::spawn_batch_with_info`](crate::world::World::spawn_batch_with_info)).
+    SpawnBatch,
+    /// A storage released the room it didn't use (see [`World::shrink_to_fit`](crate::world::World::shrink_to_fit)).
+    Shrink,
+    /// A storage was dropped, with the world or its teardown.
+    Teardown,
+}
+
+/// An allocation, reallocation or deallocation of the memory of a storage, recorded while allocation tracking is
+/// enabled (see [`World::track_allocations`](crate::world::World::track_allocations)).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocEvent {
+    /// The key of the archetype of the storage.
+    pub archetype: PrimeArchKey,
+    /// The component of the column, or `None` if the memory is shared by every column of a tiny storage (see
+    /// [`InlineColumns`](super::columns::InlineColumns)).
+    pub component: Option<ComponentId>,
+    /// The size of the memory before, `0` for an allocation.
+    pub old_bytes: usize,
+    /// The size of the memory after, `0` for a deallocation.
+    pub new_bytes: usize,
+    /// Why the memory was (re)allocated.
+    pub reason: AllocReason,
+}
+
+/// Records the allocations of the storages of a world, while allocation tracking is enabled.
+pub(crate) struct AllocTracker {
+    log: Mutex<AllocLog>,
+}
+
+struct AllocLog {
+    /// The latest events, oldest first.
+    events: VecDeque<AllocEvent>,
+    max_events: usize,
+    /// The bytes allocated by the storages of each archetype.
+    bytes: HashMap<PrimeArchKey, usize>,
+    /// The bytes allocated by all of the storages.
+    total_bytes: usize,
+    /// The archetypes that allocated any bytes, ordered by their bytes, so the largest one is the last.
+    by_bytes: BTreeSet<(usize, PrimeArchKey)>,
+    /// The reason of the allocations that happen now, if it was set (see [`StorageAllocHandle::scope`]).
+    reason: Option<AllocReason>,
+}
+
+impl AllocTracker {
+    /// A tracker that keeps the latest `max_events` events.
+    pub(crate) fn new(max_events: usize) -> Self {
+        AllocTracker {
+            log: Mutex::new(AllocLog {
+                events: VecDeque::new(),
+                max_events,
+                bytes: HashMap::new(),
+                total_bytes: 0,
+                by_bytes: BTreeSet::new(),
+                reason: None,
+            }),
+        }
+    }
+
+    fn record(&self, mut event: AllocEvent) {
+        let mut log = self.log.lock().unwrap();
+        if let Some(reason) = log.reason {
+            event.reason = reason;
+        }
+        let bytes = log.bytes.entry(event.archetype).or_default();
+        let old_bytes = *bytes;
+        *bytes = *bytes + event.new_bytes - event.old_bytes;
+        let new_bytes = *bytes;
+        if old_bytes != new_bytes {
+            log.by_bytes.remove(&(old_bytes, event.archetype));
+            if new_bytes > 0 {
+                log.by_bytes.insert((new_bytes, event.archetype));
+            }
+        }
+        log.total_bytes = log.total_bytes + event.new_bytes - event.old_bytes;
+        if log.max_events > 0 {
+            if log.events.len() == log.max_events {
+                log.events.pop_front();
+            }
+            log.events.push_back(event);
+        }
+    }
+
+    /// Take the recorded events, oldest first.
+    pub(crate) fn take_events(&self) -> Vec<AllocEvent> {
+        self.log.lock().unwrap().events.drain(..).collect()
+    }
+
+    /// The bytes allocated by the storage of the archetype with the key `archetype`.
+    pub(crate) fn bytes_for(&self, archetype: PrimeArchKey) -> usize {
+        self.log
+            .lock()
+            .unwrap()
+            .bytes
+            .get(&archetype)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// The bytes allocated by all of the storages.
+    pub(crate) fn total_bytes(&self) -> usize {
+        self.log.lock().unwrap().total_bytes
+    }
+
+    /// The bytes allocated by all of the storages, and the archetype whose storage allocated the most bytes (with
+    /// its bytes), if any storage allocated anything.
+    pub(crate) fn totals(&self) -> (usize, Option<(PrimeArchKey, usize)>) {
+        let log = self.log.lock().unwrap();
+        let largest = log
+            .by_bytes
+            .last()
+            .map(|(bytes, archetype)| (*archetype, *bytes));
+        (log.total_bytes, largest)
+    }
+}
+
+/// The storage (and column) whose allocations a [`StorageAllocHandle`] records.
+#[derive(Clone)]
+struct AllocTag {
+    storage: Arc<TrackedStorage>,
+    /// The column of the handle, `None` for the memory of every column of a tiny storage.
+    column: Option<usize>,
+}
+
+struct TrackedStorage {
+    tracker: Arc<AllocTracker>,
+    archetype: PrimeArchKey,
+    /// The component of each column.
+    components: Box<[ComponentId]>,
+}
+
+impl AllocTag {
+    #[cold]
+    fn record(&self, old_bytes: usize, new_bytes: usize, reason: AllocReason) {
+        self.storage.tracker.record(AllocEvent {
+            archetype: self.storage.archetype,
+            component: self.column.map(|column| self.storage.components[column]),
+            old_bytes,
+            new_bytes,
+            reason,
+        });
+    }
+}
+
+/// Sets the reason of the allocations of a tracker until it's dropped, see [`StorageAllocHandle::scope`].
+pub(crate) struct AllocScope(Option<Arc<AllocTracker>>);
+
+impl Drop for AllocScope {
+    fn drop(&mut self) {
+        if let Some(tracker) = &self.0 {
+            tracker.log.lock().unwrap().reason = None;
+        }
+    }
+}
+
+/// A cheap handle to the [`StorageAlloc`] of a storage. Every storage keeps the handle of the allocator that
+/// allocated its memory, so its memory is always returned to the same allocator.
+/// The default handle uses the global allocator directly, without an indirection.
+///
+/// While allocation tracking is enabled (see [`World::track_allocations`](crate::world::World::track_allocations)),
+/// the handle of each storage also records every allocation, reallocation and deallocation it makes. Otherwise,
+/// that costs a single branch. The handles of a world also carry its latency histograms, so the time that storages
+/// spend growing is recorded where it's spent (see `World::record_latency`).
+#[derive(Clone, Default)]
+pub struct StorageAllocHandle {
+    alloc: Option<Arc<dyn StorageAlloc>>,
+    tracking: Option<AllocTag>,
+    latency: LatencyRecorder,
+}
+
+impl StorageAllocHandle {
+    /// A handle to a custom [`StorageAlloc`].
+    pub fn new(alloc: Arc<dyn StorageAlloc>) -> Self {
+        StorageAllocHandle {
+            alloc: Some(alloc),
+            tracking: None,
+            latency: LatencyRecorder::default(),
+        }
+    }
+
+    /// Returns `true` if the handle uses the global allocator.
+    pub fn is_global(&self) -> bool {
+        self.alloc.is_none()
+    }
+
+    /// Record the allocations of this handle (and the handles that are derived from it) in `tracker`.
+    pub(crate) fn track(&mut self, tracker: Arc<AllocTracker>) {
+        self.tracking = Some(AllocTag {
+            storage: Arc::new(TrackedStorage {
+                tracker,
+                archetype: PrimeArchKey::IDENTITY,
+                components: Box::new([]),
+            }),
+            column: None,
+        });
+    }
+
+    /// The tracker that the allocations of this handle are recorded in, if they are tracked.
+    pub(crate) fn tracker(&self) -> Option<&Arc<AllocTracker>> {
+        self.tracking.as_ref().map(|tag| &tag.storage.tracker)
+    }
+
+    /// A handle for the storage of the archetype `archetype`, with the component `components[i]` in column `i`.
+    pub(crate) fn for_storage(&self, archetype: PrimeArchKey, components: &[ComponentId]) -> Self {
+        StorageAllocHandle {
+            alloc: self.alloc.clone(),
+            tracking: self.tracking.as_ref().map(|tag| AllocTag {
+                storage: Arc::new(TrackedStorage {
+                    tracker: tag.storage.tracker.clone(),
+                    archetype,
+                    components: components.into(),
+                }),
+                column: None,
+            }),
+            latency: self.latency.clone(),
+        }
+    }
+
+    /// A handle for a single column of the same storage (or for all of its columns, if `column` is `None`).
+    pub(crate) fn for_column(&self, column: Option<usize>) -> Self {
+        StorageAllocHandle {
+            alloc: self.alloc.clone(),
+            tracking: self.tracking.as_ref().map(|tag| AllocTag {
+                storage: tag.storage.clone(),
+                column,
+            }),
+            latency: self.latency.clone(),
+        }
+    }
+
+    /// The latency histograms of the world of the handle.
+    pub(crate) fn latency(&self) -> &LatencyRecorder {
+        &self.latency
+    }
+
+    /// Record the allocations that happen until the returned scope is dropped with `reason`, unless a reason was
+    /// already set by an enclosing scope.
+    pub(crate) fn scope(&self, reason: AllocReason) -> AllocScope {
+        let Some(tracker) = self.tracker() else {
+            return AllocScope(None);
+        };
+        let mut log = tracker.log.lock().unwrap();
+        if log.reason.is_some() {
+            return AllocScope(None);
+        }
+        log.reason = Some(reason);
+        AllocScope(Some(tracker.clone()))
+    }
+
+    /// See [`StorageAlloc::alloc`].
+    ///
+    /// # Safety
+    /// See [`StorageAlloc::alloc`].
+    #[inline]
+    pub unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if let Some(tag) = &self.tracking {
+            tag.record(0, layout.size(), AllocReason::Growth);
+        }
+        match &self.alloc {
+            Some(alloc) => alloc.alloc(layout),
+            None => std::alloc::alloc(layout),
+        }
+    }
+
+    /// See [`StorageAlloc::realloc`].
+    ///
+    /// # Safety
+    /// See [`StorageAlloc::realloc`].
+    #[inline]
+    pub unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if let Some(tag) = &self.tracking {
+            let reason = if new_size < layout.size() {
+                AllocReason::Shrink
+            } else {
+                AllocReason::Growth
+            };
+            tag.record(layout.size(), new_size, reason);
+        }
+        match &self.alloc {
+            Some(alloc) => alloc.realloc(ptr, layout, new_size),
+            None => std::alloc::realloc(ptr, layout, new_size),
+        }
+    }
+
+    /// See [`StorageAlloc::dealloc`].
+    ///
+    /// # Safety
+    /// See [`StorageAlloc::dealloc`].
+    #[inline]
+    pub unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(tag) = &self.tracking {
+            tag.record(layout.size(), 0, AllocReason::Teardown);
+        }
+        match &self.alloc {
+            Some(alloc) => alloc.dealloc(ptr, layout),
+            None => std::alloc::dealloc(ptr, layout),
+        }
+    }
+}
+
+impl fmt::Debug for StorageAllocHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.alloc, &self.tracking) {
+            (Some(_), None) => f.write_str("StorageAllocHandle(custom)"),
+            (None, None) => f.write_str("StorageAllocHandle(global)"),
+            (Some(_), Some(_)) => f.write_str("StorageAllocHandle(custom, tracked)"),
+            (None, Some(_)) => f.write_str("StorageAllocHandle(global, tracked)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StorageAllocHandle;
+    use crate::{prelude::*, storage::blob_vec::BlobVec, test_utils::*};
+    use bevy_ptr::OwningPtr;
+    use std::{alloc::Layout, sync::Arc};
+
+    #[test]
+    fn test_blob_vec_allocator() {
+        let alloc = Arc::new(BumpAlloc::new(1 << 16));
+        let handle = StorageAllocHandle::new(alloc.clone());
+        // SAFETY: `u64` doesn't need to be dropped.
+        let mut blob_vec = unsafe { BlobVec::new_in(Layout::new::<u64>(), None, 2, handle) };
+        assert_eq!(alloc.live_bytes(), 16);
+        for i in 0..100u64 {
+            // SAFETY: The value is a `u64`.
+            OwningPtr::make(i, |ptr| unsafe { blob_vec.push(ptr) });
+        }
+        assert_eq!(alloc.live_blocks(), 1);
+        assert_eq!(alloc.live_bytes(), blob_vec.capacity() * 8);
+        // SAFETY: The items are `u64`s.
+        assert!(unsafe { blob_vec.as_slice::<u64>() }
+            .iter()
+            .copied()
+            .eq(0..100));
+
+        drop(blob_vec);
+        assert!(alloc.is_balanced());
+        assert!(alloc.allocated_bytes() > 800);
+    }
+
+    #[test]
+    fn test_world_returns_storage_memory() {
+        let alloc = Arc::new(BumpAlloc::new(1 << 24));
+        let mut world = World::with_allocator(alloc.clone());
+        let script = ChurnScript::new(0x2545_f491_4f6c_dd1d)
+            .steps(5000)
+            .initial_entities(500)
+            .archetypes(&[&[0], &[0, 1], &[1, 2], &[2, 3, 4], &[5], &[5, 6, 7]]);
+        let alive = script.run(&mut world);
+        assert!(alloc.live_bytes() > 0);
+        assert_eq!(alloc.bad_frees(), 0);
+
+        // Tiny archetypes stay inline, and are freed the same way.
+        world.spawn((Fx::<4>(0), Fx::<7>(0)));
+        assert_eq!(world.clear(), alive.len() + 1);
+        script.run(&mut world);
+        world.despawn_matching::<Has<Fx<0>>>();
+
+        drop(world);
+        assert_eq!(alloc.live_bytes(), 0);
+        assert!(alloc.is_balanced());
+    }
+}
@@ -13,7 +13,11 @@ use std::{
 
 use bevy_ptr::{OwningPtr, Ptr, PtrMut};
 
-use crate::world::data::DataInfo;
+use super::alloc::StorageAllocHandle;
+use crate::{
+    utils::paranoid,
+    world::{data::DataInfo, latency::LatencyCategory},
+};
 
 /// Item that's generic over some function. That function will be called when the item is dropped.
 pub struct OnDrop<F: FnOnce()> {
@@ -37,6 +41,66 @@ impl<F: FnOnce()> Drop for OnDrop<F> {
     }
 }
 
+/// Move the item at `from` to `to`, in a contiguous run of items of `size` bytes that starts at `base`. The items
+/// in between are shifted by one towards `from`, so the order of the other items is kept.
+///
+/// # Safety
+/// `from` and `to` must both be indices of initialized items of the run.
+pub(crate) unsafe fn move_item(base: *mut u8, size: usize, from: usize, to: usize) {
+    if from == to || size == 0 {
+        return;
+    }
+    let mut item = Vec::<std::mem::MaybeUninit<u8>>::with_capacity(size);
+    let item = item.as_mut_ptr() as *mut u8;
+    std::ptr::copy_nonoverlapping(base.add(from * size), item, size);
+    if from < to {
+        std::ptr::copy(
+            base.add((from + 1) * size),
+            base.add(from * size),
+            (to - from) * size,
+        );
+    } else {
+        std::ptr::copy(
+            base.add(to * size),
+            base.add((to + 1) * size),
+            (from - to) * size,
+        );
+    }
+    std::ptr::copy_nonoverlapping(item, base.add(to * size), size);
+}
+
+/// Permute a contiguous run of items of `size` bytes that starts at `base`, so the item at `i` is the item that was
+/// at `order[i]`. The items are moved as bytes (nothing is dropped or cloned), by following the cycles of the
+/// permutation with a single item of scratch space.
+///
+/// # Safety
+/// `order` must be a permutation of `0..order.len()`, and the run must have at least `order.len()` initialized items.
+pub(crate) unsafe fn permute_items(base: *mut u8, size: usize, order: &[usize]) {
+    if size == 0 {
+        return;
+    }
+    let mut item = Vec::<std::mem::MaybeUninit<u8>>::with_capacity(size);
+    let item = item.as_mut_ptr() as *mut u8;
+    let mut visited = vec![false; order.len()];
+    for start in 0..order.len() {
+        if visited[start] || order[start] == start {
+            continue;
+        }
+        std::ptr::copy_nonoverlapping(base.add(start * size), item, size);
+        let mut hole = start;
+        loop {
+            visited[hole] = true;
+            let next = order[hole];
+            if next == start {
+                break;
+            }
+            std::ptr::copy_nonoverlapping(base.add(next * size), base.add(hole * size), size);
+            hole = next;
+        }
+        std::ptr::copy_nonoverlapping(item, base.add(hole * size), size);
+    }
+}
+
 /// A flat, type-erased data storage type
 ///
 /// Used to densely store homogeneous ECS data. A blob is usually just an arbitrary block of contiguous memory without any identity, and
@@ -52,6 +116,8 @@ pub struct BlobVec {
     data: NonNull<u8>,
     // None if the underlying type doesn't need to be dropped
     drop: Option<unsafe fn(OwningPtr<'_>)>,
+    // the allocator that allocated `data`, which is also the one that frees it
+    alloc: StorageAllocHandle,
 }
 
 // We want to ignore the `drop` field in our `Debug` impl
@@ -62,6 +128,7 @@ impl std::fmt::Debug for BlobVec {
             .field("capacity", &self.capacity)
             .field("len", &self.len)
             .field("data", &self.data)
+            .field("alloc", &self.alloc)
             .finish()
     }
 }
@@ -85,6 +152,19 @@ impl BlobVec {
         item_layout: Layout,
         drop: Option<unsafe fn(OwningPtr<'_>)>,
         capacity: usize,
+    ) -> BlobVec {
+        BlobVec::new_in(item_layout, drop, capacity, StorageAllocHandle::default())
+    }
+
+    /// Creates a new [`BlobVec`] like [`BlobVec::new`], whose memory is allocated (and freed) by `alloc`.
+    ///
+    /// # Safety
+    /// See [`BlobVec::new`].
+    pub unsafe fn new_in(
+        item_layout: Layout,
+        drop: Option<unsafe fn(OwningPtr<'_>)>,
+        capacity: usize,
+        alloc: StorageAllocHandle,
     ) -> BlobVec {
         let align = NonZeroUsize::new(item_layout.align()).expect("alignment must be > 0");
         let data = bevy_ptr::dangling_with_align(align);
@@ -97,6 +177,7 @@ impl BlobVec {
                 len: 0,
                 item_layout,
                 drop,
+                alloc,
             }
         } else {
             let mut blob_vec = BlobVec {
@@ -105,6 +186,7 @@ impl BlobVec {
                 len: 0,
                 item_layout,
                 drop,
+                alloc,
             };
             blob_vec.reserve_exact(capacity);
             blob_vec
@@ -124,6 +206,18 @@ impl BlobVec {
         BlobVec::new(data_info.layout(), data_info.drop_fn(), capacity)
     }
 
+    /// Creates a new [`BlobVec`] like [`BlobVec::new_for_data`], whose memory is allocated (and freed) by `alloc`.
+    ///
+    /// # Safety
+    /// See [`BlobVec::new_for_data`].
+    pub unsafe fn new_for_data_in(
+        data_info: &DataInfo,
+        capacity: usize,
+        alloc: StorageAllocHandle,
+    ) -> BlobVec {
+        BlobVec::new_in(data_info.layout(), data_info.drop_fn(), capacity, alloc)
+    }
+
     /// Returns the number of elements in the vector.
     #[inline]
     pub fn len(&self) -> usize {
@@ -148,6 +242,18 @@ impl BlobVec {
         self.item_layout
     }
 
+    /// Returns the function that drops the elements stored in the vector, if they need to be dropped.
+    #[inline]
+    pub fn drop_fn(&self) -> Option<unsafe fn(OwningPtr<'_>)> {
+        self.drop
+    }
+
+    /// Returns the handle of the allocator that allocates (and frees) the memory of the vector.
+    #[inline]
+    pub fn alloc(&self) -> &StorageAllocHandle {
+        &self.alloc
+    }
+
     /// Reserves the minimum capacity for at least `additional` more elements to be inserted in the given `BlobVec`.
     /// After calling `reserve_exact`, capacity will be greater than or equal to `self.len() + additional`. Does nothing if
     /// the capacity is already sufficient.
@@ -184,6 +290,60 @@ impl BlobVec {
         }
     }
 
+    /// Shrinks the capacity to the length of the vector, releasing the rest of the memory (all of it, if the vector
+    /// is empty).
+    pub fn shrink_to_fit(&mut self) {
+        if self.item_layout.size() == 0 || self.capacity == self.len {
+            return;
+        }
+        let layout =
+            array_layout(&self.item_layout, self.capacity).expect("array layout should be valid");
+        if self.len == 0 {
+            // SAFETY: `data` was allocated by `self.alloc` with `layout`, and it's replaced right away.
+            unsafe { self.alloc.dealloc(self.data.as_ptr(), layout) };
+            let align = NonZeroUsize::new(self.item_layout.align()).expect("alignment must be > 0");
+            self.data = bevy_ptr::dangling_with_align(align);
+        } else {
+            let new_layout =
+                array_layout(&self.item_layout, self.len).expect("array layout should be valid");
+            // SAFETY:
+            // - `data` was allocated by `self.alloc` with `layout`
+            // - `item_layout.size() > 0` and `len > 0`, so the new size is non-zero (and it's smaller than the
+            //   size of `layout`, so it doesn't overflow)
+            let new_data = unsafe {
+                self.alloc
+                    .realloc(self.data.as_ptr(), layout, new_layout.size())
+            };
+            self.data = NonNull::new(new_data).unwrap_or_else(|| handle_alloc_error(new_layout));
+        }
+        self.capacity = self.len;
+    }
+
+    /// Moves the items to a new allocation with the same capacity, so every pointer to them changes (see
+    /// [`World::enable_chaos`](crate::world::World::enable_chaos)).
+    #[cfg(feature = "chaos")]
+    pub fn relocate(&mut self) {
+        if self.item_layout.size() == 0 || self.capacity == 0 {
+            return;
+        }
+        let layout =
+            array_layout(&self.item_layout, self.capacity).expect("array layout should be valid");
+        // SAFETY: `item_layout.size() > 0` and `capacity > 0`, so the layout has a non-zero size.
+        let new_data = unsafe { self.alloc.alloc(layout) };
+        let new_data = NonNull::new(new_data).unwrap_or_else(|| handle_alloc_error(layout));
+        // SAFETY: Both allocations have room for `len` items, and they don't overlap, since the old one is only
+        // freed after the copy. `data` was allocated by `self.alloc` with `layout`.
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                self.data.as_ptr(),
+                new_data.as_ptr(),
+                self.item_layout.size() * self.len,
+            );
+            self.alloc.dealloc(self.data.as_ptr(), layout);
+        }
+        self.data = new_data;
+    }
+
     /// Grows the capacity by `increment` elements.
     ///
     /// # Panics
@@ -198,27 +358,31 @@ impl BlobVec {
             .expect("capacity overflow");
         let new_layout =
             array_layout(&self.item_layout, new_capacity).expect("array layout should be valid");
+        let latency = self.alloc.latency().start();
         let new_data = if self.capacity == 0 {
             // SAFETY:
             // - layout has non-zero size as per safety requirement
-            unsafe { std::alloc::alloc(new_layout) }
+            unsafe { self.alloc.alloc(new_layout) }
         } else {
             // SAFETY:
-            // - ptr was be allocated via this allocator
+            // - ptr was be allocated via this allocator (a `BlobVec` never changes its allocator)
             // - the layout of the ptr was `array_layout(self.item_layout, self.capacity)`
             // - `item_layout.size() > 0` and `new_capacity > 0`, so the layout size is non-zero
             // - "new_size, when rounded up to the nearest multiple of layout.align(), must not overflow (i.e., the rounded value must be less than usize::MAX)",
             // since the item size is always a multiple of its align, the rounding cannot happen
             // here and the overflow is handled in `array_layout`
             unsafe {
-                std::alloc::realloc(
-                    self.get_ptr_mut().as_ptr(),
+                self.alloc.realloc(
+                    self.data.as_ptr(),
                     array_layout(&self.item_layout, self.capacity)
                         .expect("array layout should be valid"),
                     new_layout.size(),
                 )
             }
         };
+        self.alloc
+            .latency()
+            .finish(LatencyCategory::StorageGrowth, latency);
 
         self.data = NonNull::new(new_data).unwrap_or_else(|| handle_alloc_error(new_layout));
         self.capacity = new_capacity;
@@ -229,10 +393,12 @@ impl BlobVec {
     /// # Safety
     /// - index must be in bounds
     /// - the memory in the [`BlobVec`] starting at index `index`, of a size matching this [`BlobVec`]'s
-    /// `item_layout`, must have been previously allocated.
+    ///   `item_layout`, must have been previously allocated.
     #[inline]
     pub unsafe fn initialize_unchecked(&mut self, index: usize, value: OwningPtr<'_>) {
-        debug_assert!(index < self.len());
+        paranoid::debug_check("BlobVec::initialize_unchecked", || {
+            paranoid::index("index", index, self.len)
+        });
         let ptr = self.get_mut_unchecked(index);
         std::ptr::copy_nonoverlapping::<u8>(value.as_ptr(), ptr.as_ptr(), self.item_layout.size());
     }
@@ -242,12 +408,14 @@ impl BlobVec {
     /// # Safety
     /// - index must be in-bounds
     /// - the memory in the [`BlobVec`] starting at index `index`, of a size matching this
-    /// [`BlobVec`]'s `item_layout`, must have been previously initialized with an item matching
-    /// this [`BlobVec`]'s `item_layout`
+    ///   [`BlobVec`]'s `item_layout`, must have been previously initialized with an item matching
+    ///   this [`BlobVec`]'s `item_layout`
     /// - the memory at `*value` must also be previously initialized with an item matching this
-    /// [`BlobVec`]'s `item_layout`
+    ///   [`BlobVec`]'s `item_layout`
     pub unsafe fn replace_unchecked(&mut self, index: usize, value: OwningPtr<'_>) {
-        debug_assert!(index < self.len());
+        paranoid::debug_check("BlobVec::replace_unchecked", || {
+            paranoid::index("index", index, self.len)
+        });
 
         // Pointer to the value in the vector that will get replaced.
         // SAFETY: The caller ensures that `index` fits in this vector.
@@ -307,6 +475,21 @@ impl BlobVec {
         self.initialize_unchecked(index, value);
     }
 
+    /// Appends an element to the back of the vector, without reserving space for it first.
+    ///
+    /// # Safety
+    /// - The `value` must match the [`layout`](`BlobVec::layout`) of the elements in the [`BlobVec`].
+    /// - There must be room for the element: `self.len() < self.capacity()` (see [`BlobVec::reserve`]).
+    #[inline]
+    pub unsafe fn push_unchecked(&mut self, value: OwningPtr<'_>) {
+        paranoid::debug_check("BlobVec::push_unchecked", || {
+            paranoid::room(self.len, self.capacity)
+        });
+        let index = self.len;
+        self.len += 1;
+        self.initialize_unchecked(index, value);
+    }
+
     /// Forces the length of the vector to `len`.
     ///
     /// # Safety
@@ -329,7 +512,9 @@ impl BlobVec {
     #[inline]
     #[must_use = "The returned pointer should be used to dropped the removed element"]
     pub unsafe fn swap_remove_and_forget_unchecked(&mut self, index: usize) -> OwningPtr<'_> {
-        debug_assert!(index < self.len());
+        paranoid::debug_check("BlobVec::swap_remove_and_forget_unchecked", || {
+            paranoid::index("index", index, self.len)
+        });
         // Since `index` must be strictly less than `self.len` and `index` is at least zero,
         // `self.len` must be at least one. Thus, this cannot underflow.
         let new_len = self.len - 1;
@@ -359,7 +544,9 @@ impl BlobVec {
     /// and that `self[index]` has been properly initialized.
     #[inline]
     pub unsafe fn swap_remove_unchecked(&mut self, index: usize, ptr: PtrMut<'_>) {
-        debug_assert!(index < self.len());
+        paranoid::debug_check("BlobVec::swap_remove_unchecked", || {
+            paranoid::index("index", index, self.len)
+        });
         let last = self.get_mut_unchecked(self.len - 1).as_ptr();
         let target = self.get_mut_unchecked(index).as_ptr();
         // Copy the item at the index into the provided ptr
@@ -372,6 +559,31 @@ impl BlobVec {
         self.len -= 1;
     }
 
+    /// Moves the value at `from` to `to`, shifting the values in between by one (unlike a swap, the order of
+    /// the other values is kept). Does not do any bounds checking.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `from` and `to` are `< self.len()`.
+    #[inline]
+    pub unsafe fn move_unchecked(&mut self, from: usize, to: usize) {
+        paranoid::debug_check("BlobVec::move_unchecked", || {
+            paranoid::index("from", from, self.len)?;
+            paranoid::index("to", to, self.len)
+        });
+        move_item(self.data.as_ptr(), self.item_layout.size(), from, to);
+    }
+
+    /// Permutes the values, so the value at `i` is the value that was at `order[i]`.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `order` is a permutation of `0..self.len()`.
+    pub unsafe fn permute_unchecked(&mut self, order: &[usize]) {
+        paranoid::debug_check("BlobVec::permute_unchecked", || {
+            paranoid::permutation(order, self.len)
+        });
+        permute_items(self.data.as_ptr(), self.item_layout.size(), order);
+    }
+
     /// Removes the value at `index` and drops it.
     /// Does not do any bounds checking on `index`.
     /// The removed element is replaced by the last element of the `BlobVec`.
@@ -380,7 +592,9 @@ impl BlobVec {
     /// It is the caller's responsibility to ensure that `index` is `< self.len()`.
     #[inline]
     pub unsafe fn swap_remove_and_drop_unchecked(&mut self, index: usize) {
-        debug_assert!(index < self.len());
+        paranoid::debug_check("BlobVec::swap_remove_and_drop_unchecked", || {
+            paranoid::index("index", index, self.len)
+        });
         let drop = self.drop;
         let value = self.swap_remove_and_forget_unchecked(index);
         if let Some(drop) = drop {
@@ -394,7 +608,9 @@ impl BlobVec {
     /// It is the caller's responsibility to ensure that `index < self.len()`.
     #[inline]
     pub unsafe fn get_unchecked(&self, index: usize) -> Ptr<'_> {
-        debug_assert!(index < self.len());
+        paranoid::debug_check("BlobVec::get_unchecked", || {
+            paranoid::index("index", index, self.len)
+        });
         let size = self.item_layout.size();
         // SAFETY:
         // - The caller ensures that `index` fits in this vector,
@@ -410,7 +626,9 @@ impl BlobVec {
     /// It is the caller's responsibility to ensure that `index < self.len()`.
     #[inline]
     pub unsafe fn get_mut_unchecked(&mut self, index: usize) -> PtrMut<'_> {
-        debug_assert!(index < self.len());
+        paranoid::debug_check("BlobVec::get_mut_unchecked", || {
+            paranoid::index("index", index, self.len)
+        });
         let size = self.item_layout.size();
         // SAFETY:
         // - The caller ensures that `index` fits in this vector,
@@ -439,10 +657,62 @@ impl BlobVec {
     /// # Safety
     /// The type `T` must be the type of the items in this [`BlobVec`].
     pub unsafe fn get_slice<T>(&self) -> &[UnsafeCell<T>] {
+        paranoid::check("BlobVec::get_slice", || {
+            paranoid::layout::<T>(self.item_layout)
+        });
         // SAFETY: the inner data will remain valid for as long as 'self.
         std::slice::from_raw_parts(self.data.as_ptr() as *const UnsafeCell<T>, self.len)
     }
 
+    /// Get the entire [`BlobVec`] as a typed slice of `T`.
+    ///
+    /// # Safety
+    /// The type `T` must be the type of the items in this [`BlobVec`].
+    pub unsafe fn as_slice<T>(&self) -> &[T] {
+        paranoid::check("BlobVec::as_slice", || {
+            paranoid::layout::<T>(self.item_layout)
+        });
+        // SAFETY: the inner data will remain valid for as long as 'self.
+        std::slice::from_raw_parts(self.data.as_ptr() as *const T, self.len)
+    }
+
+    /// Get the entire [`BlobVec`] as a typed mutable slice of `T`.
+    ///
+    /// # Safety
+    /// The type `T` must be the type of the items in this [`BlobVec`].
+    pub unsafe fn as_mut_slice<T>(&mut self) -> &mut [T] {
+        paranoid::check("BlobVec::as_mut_slice", || {
+            paranoid::layout::<T>(self.item_layout)
+        });
+        // SAFETY: the inner data will remain valid for as long as 'self, and we have exclusive access.
+        std::slice::from_raw_parts_mut(self.data.as_ptr() as *mut T, self.len)
+    }
+
+    /// Shortens the vector to `len` elements, dropping the rest. Does nothing if the vector is not longer than `len`.
+    ///
+    /// Note that this method has no effect on the allocated capacity of the vector.
+    pub fn truncate(&mut self, len: usize) {
+        let old_len = self.len;
+        if len >= old_len {
+            return;
+        }
+        // We set len _before_ dropping elements for unwind safety, like in `clear`.
+        self.len = len;
+        if let Some(drop) = self.drop {
+            let size = self.item_layout.size();
+            for i in len..old_len {
+                // SAFETY:
+                // * `len` <= `i` < `old_len`, so `i * size` must be in bounds for the allocation.
+                // * `size` is a multiple of the erased type's alignment,
+                //   so adding a multiple of `size` will preserve alignment.
+                // * The item is left unreachable so it can be safely promoted to an `OwningPtr`.
+                let item = unsafe { self.get_ptr_mut().byte_add(i * size).promote() };
+                // SAFETY: `item` was obtained from this `BlobVec`, so its underlying type must match `drop`.
+                unsafe { drop(item) };
+            }
+        }
+    }
+
     /// Clears the vector, removing (and dropping) all values.
     ///
     /// Note that this method has no effect on the allocated capacity of the vector.
@@ -475,9 +745,9 @@ impl Drop for BlobVec {
         let array_layout =
             array_layout(&self.item_layout, self.capacity).expect("array layout should be valid");
         if array_layout.size() > 0 {
-            // SAFETY: data ptr layout is correct, swap_scratch ptr layout is correct
+            // SAFETY: data ptr layout is correct, and it was allocated by `self.alloc`
             unsafe {
-                std::alloc::dealloc(self.get_ptr_mut().as_ptr(), array_layout);
+                self.alloc.dealloc(self.data.as_ptr(), array_layout);
             }
         }
     }
@@ -0,0 +1,957 @@
+//! The column storage of archetypes, which keeps the components of tiny archetypes inline.
+
+use super::{
+    alloc::{AllocReason, StorageAllocHandle},
+    blob_vec::{move_item, permute_items, BlobVec},
+};
+use crate::{
+    utils::paranoid,
+    world::{
+        data::{CloneFn, DataInfo},
+        latency::LatencyCategory,
+    },
+};
+use bevy_ptr::{OwningPtr, Ptr, PtrMut};
+use std::{
+    alloc::{handle_alloc_error, Layout},
+    num::NonZeroUsize,
+    ptr::NonNull,
+};
+
+/// The amount of rows that the components of an archetype can have while they are stored inline
+/// (see [`InlineColumns`]). Storing one more row moves the components to a [`BlobVec`] for each component.
+pub const INLINE_ROWS: usize = 8;
+
+/// The function that drops an item of a column.
+type DropFn = unsafe fn(OwningPtr<'_>);
+
+/// A single column of [`InlineColumns`].
+struct InlineColumn {
+    /// The offset (in bytes) of the column from the start of the allocation.
+    offset: usize,
+    item_layout: Layout,
+    /// Number of elements, not bytes
+    len: usize,
+    // None if the underlying type doesn't need to be dropped
+    drop: Option<DropFn>,
+}
+
+/// The type-erased components of a tiny archetype, with room for [`INLINE_ROWS`] rows.
+///
+/// Unlike a [`BlobVec`] for each component, all of the components share a single allocation. Each column takes
+/// a fixed segment of the allocation, so every column is still contiguous, and can be viewed as a typed slice.
+pub struct InlineColumns {
+    // the `data` ptr's layout is always `layout`
+    data: NonNull<u8>,
+    layout: Layout,
+    columns: Box<[InlineColumn]>,
+    // the allocator that allocated `data`, which is also the one that frees it
+    alloc: StorageAllocHandle,
+}
+
+impl InlineColumns {
+    /// Creates new [`InlineColumns`] with a column for each [`DataInfo`], whose memory is allocated (and freed)
+    /// by `alloc`.
+    ///
+    /// # Safety
+    /// The `drop_fn` of each [`DataInfo`] should be safe to call with an [`OwningPtr`] pointing to any item that's
+    /// been pushed into its column (see [`BlobVec::new`]).
+    pub unsafe fn new<'a>(
+        data_infos: impl IntoIterator<Item = &'a DataInfo>,
+        alloc: StorageAllocHandle,
+    ) -> InlineColumns {
+        InlineColumns::with_layouts(
+            data_infos
+                .into_iter()
+                .map(|data_info| (data_info.layout(), data_info.drop_fn())),
+            alloc,
+        )
+    }
+
+    /// Creates new [`InlineColumns`] with a column for each item layout and drop function.
+    ///
+    /// # Safety
+    /// See [`InlineColumns::new`].
+    unsafe fn with_layouts(
+        layouts: impl IntoIterator<Item = (Layout, Option<DropFn>)>,
+        alloc: StorageAllocHandle,
+    ) -> InlineColumns {
+        let mut columns = Vec::new();
+        let mut size = 0usize;
+        let mut align = 1;
+        for (item_layout, drop) in layouts {
+            let offset = size.next_multiple_of(item_layout.align());
+            size = item_layout
+                .size()
+                .checked_mul(INLINE_ROWS)
+                .and_then(|column_size| offset.checked_add(column_size))
+                .expect("inline columns should fit in memory");
+            align = align.max(item_layout.align());
+            columns.push(InlineColumn {
+                offset,
+                item_layout,
+                len: 0,
+                drop,
+            });
+        }
+        let layout = Layout::from_size_align(size, align).expect("layout should be valid");
+        let data = if size == 0 {
+            bevy_ptr::dangling_with_align(NonZeroUsize::new(align).expect("alignment must be > 0"))
+        } else {
+            // SAFETY: `layout` has a non-zero size.
+            NonNull::new(alloc.alloc(layout)).unwrap_or_else(|| handle_alloc_error(layout))
+        };
+        InlineColumns {
+            data,
+            layout,
+            columns: columns.into_boxed_slice(),
+            alloc,
+        }
+    }
+
+    /// Returns the number of columns.
+    #[inline]
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Moves the rows to a new allocation, so every pointer to them changes (see
+    /// [`World::enable_chaos`](crate::world::World::enable_chaos)).
+    #[cfg(feature = "chaos")]
+    fn relocate(&mut self) {
+        if self.layout.size() == 0 {
+            return;
+        }
+        // SAFETY: `layout` has a non-zero size.
+        let new_data = unsafe { self.alloc.alloc(self.layout) };
+        let new_data = NonNull::new(new_data).unwrap_or_else(|| handle_alloc_error(self.layout));
+        for column in self.columns.iter() {
+            // SAFETY: Both allocations have the layout of the columns, and they don't overlap, since the old one is
+            // only freed after the copy.
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    self.data.as_ptr().add(column.offset),
+                    new_data.as_ptr().add(column.offset),
+                    column.item_layout.size() * column.len,
+                );
+            }
+        }
+        // SAFETY: `data` was allocated by `self.alloc` with `layout`.
+        unsafe { self.alloc.dealloc(self.data.as_ptr(), self.layout) };
+        self.data = new_data;
+    }
+
+    /// Returns the number of elements in the column.
+    #[inline]
+    pub fn len(&self, column: usize) -> usize {
+        self.columns[column].len
+    }
+
+    /// Returns a pointer to the slot of the element at `index` in the column, without doing bounds checking.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `column` is in bounds and `index < INLINE_ROWS`.
+    #[inline]
+    unsafe fn slot(&self, column: usize, index: usize) -> NonNull<u8> {
+        let column = self.columns.get_unchecked(column);
+        // SAFETY:
+        // - The column has room for `INLINE_ROWS` elements, so this will not overflow the allocation.
+        // - The offset of the column is aligned to its items' alignment, and `size` is a multiple of it.
+        self.data
+            .byte_add(column.offset + index * column.item_layout.size())
+    }
+
+    /// Appends an element to the back of the column, without checking whether there is room for it.
+    ///
+    /// # Safety
+    /// The `value` must match the [`layout`](`Layout`) of the column's items, and the column must have less than
+    /// [`INLINE_ROWS`] elements.
+    #[inline]
+    pub unsafe fn push_unchecked(&mut self, column: usize, value: OwningPtr<'_>) {
+        let len = self.len(column);
+        paranoid::debug_check("InlineColumns::push_unchecked", || {
+            paranoid::room(len, INLINE_ROWS)
+        });
+        std::ptr::copy_nonoverlapping::<u8>(
+            value.as_ptr(),
+            self.slot(column, len).as_ptr(),
+            self.columns[column].item_layout.size(),
+        );
+        self.columns[column].len += 1;
+    }
+
+    /// Returns a reference to the element at `index` in the column, without doing bounds checking.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `index < self.len(column)`.
+    #[inline]
+    pub unsafe fn get_unchecked(&self, column: usize, index: usize) -> Ptr<'_> {
+        paranoid::debug_check("InlineColumns::get_unchecked", || {
+            paranoid::index("index", index, self.len(column))
+        });
+        Ptr::new(self.slot(column, index))
+    }
+
+    /// Returns a mutable reference to the element at `index` in the column, without doing bounds checking.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `index < self.len(column)`.
+    #[inline]
+    pub unsafe fn get_mut_unchecked(&mut self, column: usize, index: usize) -> PtrMut<'_> {
+        paranoid::debug_check("InlineColumns::get_mut_unchecked", || {
+            paranoid::index("index", index, self.len(column))
+        });
+        PtrMut::new(self.slot(column, index))
+    }
+
+    /// Get the entire column as a typed slice of `T`.
+    ///
+    /// # Safety
+    /// The type `T` must be the type of the items in the column.
+    pub unsafe fn as_slice<T>(&self, column: usize) -> &[T] {
+        paranoid::check("InlineColumns::as_slice", || {
+            paranoid::layout::<T>(self.columns[column].item_layout)
+        });
+        std::slice::from_raw_parts(self.slot(column, 0).as_ptr() as *const T, self.len(column))
+    }
+
+    /// Get the entire column as a typed mutable slice of `T`.
+    ///
+    /// # Safety
+    /// The type `T` must be the type of the items in the column.
+    pub unsafe fn as_mut_slice<T>(&mut self, column: usize) -> &mut [T] {
+        paranoid::check("InlineColumns::as_mut_slice", || {
+            paranoid::layout::<T>(self.columns[column].item_layout)
+        });
+        std::slice::from_raw_parts_mut(self.slot(column, 0).as_ptr() as *mut T, self.len(column))
+    }
+
+    /// Removes the element at `index` from the column and drops it. The removed element is replaced by the last
+    /// element of the column.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `index < self.len(column)`.
+    pub unsafe fn swap_remove_and_drop_unchecked(&mut self, column: usize, index: usize) {
+        paranoid::debug_check("InlineColumns::swap_remove_and_drop_unchecked", || {
+            paranoid::index("index", index, self.len(column))
+        });
+        let new_len = self.len(column) - 1;
+        let size = self.columns[column].item_layout.size();
+        if index != new_len {
+            std::ptr::swap_nonoverlapping::<u8>(
+                self.slot(column, index).as_ptr(),
+                self.slot(column, new_len).as_ptr(),
+                size,
+            );
+        }
+        self.columns[column].len = new_len;
+        if let Some(drop) = self.columns[column].drop {
+            // SAFETY: The removed element is out of bounds now, so it can be safely promoted to an `OwningPtr`.
+            drop(PtrMut::new(self.slot(column, new_len)).promote());
+        }
+    }
+
+    /// Removes the element at `index` from the column without dropping it, like
+    /// [`BlobVec::swap_remove_and_forget_unchecked`]. It is the caller's responsibility to drop the returned
+    /// pointer, if that is desirable.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `index < self.len(column)`.
+    #[must_use = "The returned pointer should be used to dropped the removed element"]
+    pub unsafe fn swap_remove_and_forget_unchecked(
+        &mut self,
+        column: usize,
+        index: usize,
+    ) -> OwningPtr<'_> {
+        paranoid::debug_check("InlineColumns::swap_remove_and_forget_unchecked", || {
+            paranoid::index("index", index, self.len(column))
+        });
+        let new_len = self.len(column) - 1;
+        let size = self.columns[column].item_layout.size();
+        if index != new_len {
+            std::ptr::swap_nonoverlapping::<u8>(
+                self.slot(column, index).as_ptr(),
+                self.slot(column, new_len).as_ptr(),
+                size,
+            );
+        }
+        self.columns[column].len = new_len;
+        // SAFETY: The removed element is out of bounds now, so it can be safely promoted to an `OwningPtr`.
+        PtrMut::new(self.slot(column, new_len)).promote()
+    }
+
+    /// Moves the element at `from` in the column to `to`, shifting the elements in between by one.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `from` and `to` are `< self.len(column)`.
+    pub unsafe fn move_unchecked(&mut self, column: usize, from: usize, to: usize) {
+        paranoid::debug_check("InlineColumns::move_unchecked", || {
+            paranoid::index("from", from, self.len(column))?;
+            paranoid::index("to", to, self.len(column))
+        });
+        let size = self.columns[column].item_layout.size();
+        move_item(self.slot(column, 0).as_ptr(), size, from, to);
+    }
+
+    /// Permutes the elements of the column, so the element at `i` is the element that was at `order[i]`.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `order` is a permutation of `0..self.len(column)`.
+    pub unsafe fn permute_unchecked(&mut self, column: usize, order: &[usize]) {
+        paranoid::debug_check("InlineColumns::permute_unchecked", || {
+            paranoid::permutation(order, self.len(column))
+        });
+        let size = self.columns[column].item_layout.size();
+        permute_items(self.slot(column, 0).as_ptr(), size, order);
+    }
+
+    /// Shortens every column to `len` elements, dropping the rest.
+    pub fn truncate(&mut self, len: usize) {
+        for column in 0..self.columns.len() {
+            self.truncate_column(column, len);
+        }
+    }
+
+    /// Shortens the column to `len` elements, dropping the rest.
+    pub fn truncate_column(&mut self, column: usize, len: usize) {
+        let old_len = self.columns[column].len;
+        if len >= old_len {
+            return;
+        }
+        // We set len _before_ dropping elements for unwind safety, like in `BlobVec::clear`.
+        self.columns[column].len = len;
+        if let Some(drop) = self.columns[column].drop {
+            for i in len..old_len {
+                // SAFETY: `i < old_len <= INLINE_ROWS`, and the item is left unreachable so it can
+                // be safely promoted to an `OwningPtr`.
+                unsafe { drop(PtrMut::new(self.slot(column, i)).promote()) };
+            }
+        }
+    }
+
+    /// Move the elements of every column to a [`BlobVec`] with room for at least `capacity` elements. The [`BlobVec`]s
+    /// use the same allocator as `self`.
+    pub fn into_blob_vecs(mut self, capacity: usize) -> Vec<BlobVec> {
+        let mut blob_vecs = Vec::with_capacity(self.columns.len());
+        for column in 0..self.columns.len() {
+            let len = self.columns[column].len;
+            // The elements are moved out, so they must not be dropped with `self`.
+            self.columns[column].len = 0;
+            let InlineColumn {
+                item_layout, drop, ..
+            } = self.columns[column];
+            // SAFETY: `drop` was safe to call with the elements of the column.
+            let mut blob_vec = unsafe {
+                BlobVec::new_in(
+                    item_layout,
+                    drop,
+                    capacity.max(len),
+                    self.alloc.for_column(Some(column)),
+                )
+            };
+            for i in 0..len {
+                // SAFETY: `i < len`, the element is moved into the `BlobVec` and it's unreachable from `self`.
+                unsafe { blob_vec.push_unchecked(PtrMut::new(self.slot(column, i)).promote()) };
+            }
+            blob_vecs.push(blob_vec);
+        }
+        blob_vecs
+    }
+}
+
+impl Drop for InlineColumns {
+    fn drop(&mut self) {
+        self.truncate(0);
+        if self.layout.size() > 0 {
+            // SAFETY: `data` was allocated by `self.alloc` with `layout`.
+            unsafe { self.alloc.dealloc(self.data.as_ptr(), self.layout) };
+        }
+    }
+}
+
+// SAFETY: Like a `BlobVec`, the columns only store components, which are `Send` and `Sync`.
+unsafe impl Send for InlineColumns {}
+// SAFETY: See above.
+unsafe impl Sync for InlineColumns {}
+
+/// The type-erased storage of the components of an archetype: a column for each component.
+///
+/// Tiny archetypes (with no more than [`INLINE_ROWS`] rows) keep their components in [`InlineColumns`], to avoid
+/// an allocation for each of their components. Once they grow past that, each component gets its own [`BlobVec`].
+/// Either way, the rows of every column are contiguous.
+pub enum Columns {
+    /// All of the columns share a single allocation, with room for [`INLINE_ROWS`] rows.
+    Inline(InlineColumns),
+    /// Each column is a [`BlobVec`].
+    Blobs(Vec<BlobVec>),
+}
+
+// SAFETY: The columns only store components, which are `Send` and `Sync`. This lets storages share their columns
+// across threads (see `World::clone_cow`).
+unsafe impl Send for Columns {}
+// SAFETY: See above.
+unsafe impl Sync for Columns {}
+
+impl Columns {
+    /// Creates new [`Columns`] with a column for each [`DataInfo`], stored inline. All of their memory is
+    /// allocated (and freed) by `alloc`, even after they move out of line.
+    ///
+    /// # Safety
+    /// See [`InlineColumns::new`].
+    pub unsafe fn new<'a>(
+        data_infos: impl IntoIterator<Item = &'a DataInfo>,
+        alloc: StorageAllocHandle,
+    ) -> Columns {
+        Columns::Inline(InlineColumns::new(data_infos, alloc))
+    }
+
+    /// Returns `true` if the columns are stored inline.
+    pub fn is_inline(&self) -> bool {
+        matches!(self, Columns::Inline(_))
+    }
+
+    /// Returns the number of columns.
+    pub fn column_count(&self) -> usize {
+        match self {
+            Columns::Inline(inline) => inline.column_count(),
+            Columns::Blobs(blob_vecs) => blob_vecs.len(),
+        }
+    }
+
+    /// Returns the number of elements in the column.
+    pub fn len(&self, column: usize) -> usize {
+        match self {
+            Columns::Inline(inline) => inline.len(column),
+            Columns::Blobs(blob_vecs) => blob_vecs[column].len(),
+        }
+    }
+
+    /// Check that `column` is in bounds, for the unchecked `method` (see [`paranoid::check`]).
+    #[inline(always)]
+    #[track_caller]
+    fn check_column(&self, method: &str, column: usize) {
+        paranoid::check(method, || {
+            paranoid::index("column", column, self.column_count())
+        });
+    }
+
+    /// The item layout and drop function of each column.
+    fn layouts(&self) -> Vec<(Layout, Option<DropFn>)> {
+        match self {
+            Columns::Inline(inline) => inline
+                .columns
+                .iter()
+                .map(|column| (column.item_layout, column.drop))
+                .collect(),
+            Columns::Blobs(blob_vecs) => blob_vecs
+                .iter()
+                .map(|bvec| (bvec.layout(), bvec.drop_fn()))
+                .collect(),
+        }
+    }
+
+    /// The handle of the allocator of the columns.
+    fn alloc(&self) -> StorageAllocHandle {
+        match self {
+            Columns::Inline(inline) => inline.alloc.clone(),
+            // Without any columns, nothing is allocated anyway.
+            Columns::Blobs(blob_vecs) => blob_vecs
+                .first()
+                .map(|bvec| bvec.alloc().for_column(None))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Creates new, empty [`Columns`] with the same columns (and allocator) as `self`, stored inline.
+    pub fn empty_like(&self) -> Columns {
+        // SAFETY: The drop functions were safe to call with the items of `self`, which have the same types.
+        Columns::Inline(unsafe { InlineColumns::with_layouts(self.layouts(), self.alloc()) })
+    }
+
+    /// Creates a deep copy of the columns, cloning every element with the [`CloneFn`] of its column. The copy is
+    /// stored the same way (inline or not) and uses the same allocator.
+    ///
+    /// If cloning an element panics, the elements that were already cloned are dropped.
+    ///
+    /// # Safety
+    /// There must be a [`CloneFn`] for each column, that is safe to call with the column's items.
+    pub unsafe fn clone_with(&self, clone_fns: &[CloneFn]) -> Columns {
+        debug_assert_eq!(clone_fns.len(), self.layouts().len());
+        match self {
+            Columns::Inline(inline) => {
+                let mut cloned = InlineColumns::with_layouts(self.layouts(), self.alloc());
+                for (column, clone_fn) in clone_fns.iter().enumerate() {
+                    for index in 0..inline.len(column) {
+                        clone_fn(inline.get_unchecked(column, index), &mut |ptr| {
+                            cloned.push_unchecked(column, ptr)
+                        });
+                    }
+                }
+                Columns::Inline(cloned)
+            }
+            Columns::Blobs(blob_vecs) => {
+                let mut cloned = Vec::with_capacity(blob_vecs.len());
+                for (bvec, clone_fn) in blob_vecs.iter().zip(clone_fns) {
+                    let mut cloned_bvec = BlobVec::new_in(
+                        bvec.layout(),
+                        bvec.drop_fn(),
+                        bvec.len(),
+                        bvec.alloc().clone(),
+                    );
+                    for index in 0..bvec.len() {
+                        clone_fn(bvec.get_unchecked(index), &mut |ptr| {
+                            cloned_bvec.push_unchecked(ptr)
+                        });
+                    }
+                    cloned.push(cloned_bvec);
+                }
+                Columns::Blobs(cloned)
+            }
+        }
+    }
+
+    /// Move the columns to a [`BlobVec`] for each column (if they're stored inline), with room for at least
+    /// `capacity` rows.
+    fn spill(&mut self, capacity: usize) -> &mut Vec<BlobVec> {
+        if let Columns::Inline(_) = self {
+            let Columns::Inline(inline) = std::mem::replace(self, Columns::Blobs(Vec::new()))
+            else {
+                unreachable!()
+            };
+            // Freeing the inline memory is part of the growth.
+            let _scope = inline.alloc.scope(AllocReason::Growth);
+            let latency = inline.alloc.latency().clone();
+            *self = latency.time(LatencyCategory::StorageGrowth, || {
+                Columns::Blobs(inline.into_blob_vecs(capacity))
+            });
+        }
+        match self {
+            Columns::Blobs(blob_vecs) => blob_vecs,
+            Columns::Inline(_) => unreachable!(),
+        }
+    }
+
+    /// Reserve room for at least `additional` more rows in each column, when each column has `len` rows.
+    pub fn reserve(&mut self, len: usize, additional: usize) {
+        match self {
+            Columns::Inline(_) if len + additional <= INLINE_ROWS => {}
+            Columns::Inline(_) => {
+                self.spill(len + additional);
+            }
+            Columns::Blobs(blob_vecs) => blob_vecs
+                .iter_mut()
+                .for_each(|bvec| bvec.reserve(additional)),
+        }
+    }
+
+    /// Shrinks the capacity of every column to its length. Inline columns are left as they are, since they always
+    /// have room for [`INLINE_ROWS`] rows.
+    pub fn shrink_to_fit(&mut self) {
+        if let Columns::Blobs(blob_vecs) = self {
+            blob_vecs.iter_mut().for_each(BlobVec::shrink_to_fit);
+        }
+    }
+
+    /// Moves every column to a new allocation, so every pointer to the rows changes (see
+    /// [`World::enable_chaos`](crate::world::World::enable_chaos)).
+    #[cfg(feature = "chaos")]
+    pub fn relocate(&mut self) {
+        match self {
+            Columns::Inline(inline) => inline.relocate(),
+            Columns::Blobs(blob_vecs) => blob_vecs.iter_mut().for_each(BlobVec::relocate),
+        }
+    }
+
+    /// Returns `true` if there is room for at least `additional` more rows in the column.
+    pub fn has_room_for(&self, column: usize, additional: usize) -> bool {
+        match self {
+            Columns::Inline(inline) => INLINE_ROWS - inline.len(column) >= additional,
+            Columns::Blobs(blob_vecs) => {
+                blob_vecs[column].capacity() - blob_vecs[column].len() >= additional
+            }
+        }
+    }
+
+    /// Appends an element to the back of the column, making room for it if needed.
+    ///
+    /// # Safety
+    /// The `value` must match the [`layout`](`Layout`) of the column's items, and `column` must be in bounds.
+    #[inline]
+    pub unsafe fn push(&mut self, column: usize, value: OwningPtr<'_>) {
+        self.check_column("Columns::push", column);
+        match self {
+            Columns::Inline(inline) if inline.len(column) < INLINE_ROWS => {
+                inline.push_unchecked(column, value)
+            }
+            Columns::Inline(_) => self.spill(INLINE_ROWS * 2)[column].push(value),
+            Columns::Blobs(blob_vecs) => blob_vecs.get_unchecked_mut(column).push(value),
+        }
+    }
+
+    /// Appends an element to the back of the column, without checking whether there is room for it.
+    ///
+    /// # Safety
+    /// The `value` must match the [`layout`](`Layout`) of the column's items, `column` must be in bounds,
+    /// and there must be room for the element (see [`Self::reserve`]).
+    #[inline]
+    pub unsafe fn push_unchecked(&mut self, column: usize, value: OwningPtr<'_>) {
+        self.check_column("Columns::push_unchecked", column);
+        match self {
+            Columns::Inline(inline) => inline.push_unchecked(column, value),
+            Columns::Blobs(blob_vecs) => blob_vecs.get_unchecked_mut(column).push_unchecked(value),
+        }
+    }
+
+    /// Returns a reference to the element at `index` in the column, without doing bounds checking.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `column` is in bounds, and that `index` is less than
+    /// its length.
+    #[inline]
+    pub unsafe fn get_unchecked(&self, column: usize, index: usize) -> Ptr<'_> {
+        self.check_column("Columns::get_unchecked", column);
+        match self {
+            Columns::Inline(inline) => inline.get_unchecked(column, index),
+            Columns::Blobs(blob_vecs) => blob_vecs.get_unchecked(column).get_unchecked(index),
+        }
+    }
+
+    /// Returns a mutable reference to the element at `index` in the column, without doing bounds checking.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `column` is in bounds, and that `index` is less than
+    /// its length.
+    #[inline]
+    pub unsafe fn get_mut_unchecked(&mut self, column: usize, index: usize) -> PtrMut<'_> {
+        self.check_column("Columns::get_mut_unchecked", column);
+        match self {
+            Columns::Inline(inline) => inline.get_mut_unchecked(column, index),
+            Columns::Blobs(blob_vecs) => {
+                blob_vecs.get_unchecked_mut(column).get_mut_unchecked(index)
+            }
+        }
+    }
+
+    /// Get the entire column as a typed slice of `T`.
+    ///
+    /// # Safety
+    /// The type `T` must be the type of the items in the column.
+    pub unsafe fn as_slice<T>(&self, column: usize) -> &[T] {
+        match self {
+            Columns::Inline(inline) => inline.as_slice(column),
+            Columns::Blobs(blob_vecs) => blob_vecs[column].as_slice(),
+        }
+    }
+
+    /// Get the entire column as a typed mutable slice of `T`.
+    ///
+    /// # Safety
+    /// The type `T` must be the type of the items in the column.
+    pub unsafe fn as_mut_slice<T>(&mut self, column: usize) -> &mut [T] {
+        match self {
+            Columns::Inline(inline) => inline.as_mut_slice(column),
+            Columns::Blobs(blob_vecs) => blob_vecs[column].as_mut_slice(),
+        }
+    }
+
+    /// Removes the element at `index` from every column and drops it. The removed elements are replaced by
+    /// the last element of their column.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `index` is less than the length of every column.
+    pub unsafe fn swap_remove_and_drop_unchecked(&mut self, index: usize) {
+        match self {
+            Columns::Inline(inline) => (0..inline.columns.len())
+                .for_each(|column| inline.swap_remove_and_drop_unchecked(column, index)),
+            Columns::Blobs(blob_vecs) => blob_vecs
+                .iter_mut()
+                .for_each(|bvec| bvec.swap_remove_and_drop_unchecked(index)),
+        }
+    }
+
+    /// Removes the element at `index` from the column without dropping it, and replaces it with the last element
+    /// of the column. It is the caller's responsibility to drop the returned pointer, if that is desirable.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `column` is in bounds, and that `index` is less than
+    /// its length.
+    #[must_use = "The returned pointer should be used to dropped the removed element"]
+    pub unsafe fn swap_remove_and_forget_unchecked(
+        &mut self,
+        column: usize,
+        index: usize,
+    ) -> OwningPtr<'_> {
+        self.check_column("Columns::swap_remove_and_forget_unchecked", column);
+        match self {
+            Columns::Inline(inline) => inline.swap_remove_and_forget_unchecked(column, index),
+            Columns::Blobs(blob_vecs) => blob_vecs
+                .get_unchecked_mut(column)
+                .swap_remove_and_forget_unchecked(index),
+        }
+    }
+
+    /// Like [`Self::swap_remove_and_drop_unchecked`], but the columns are visited in `order` (a permutation of the
+    /// columns), so the elements of the removed row are dropped in that order.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `index` is less than the length of every column, and that
+    /// `order` is a permutation of the columns.
+    pub unsafe fn swap_remove_and_drop_in_order_unchecked(
+        &mut self,
+        index: usize,
+        order: &[usize],
+    ) {
+        match self {
+            Columns::Inline(inline) => order
+                .iter()
+                .for_each(|column| inline.swap_remove_and_drop_unchecked(*column, index)),
+            Columns::Blobs(blob_vecs) => order
+                .iter()
+                .for_each(|column| blob_vecs[*column].swap_remove_and_drop_unchecked(index)),
+        }
+    }
+
+    /// Moves the row at `from` to `to` in every column, shifting the rows in between by one (so, unlike a swap,
+    /// the order of the other rows is kept).
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `from` and `to` are less than the length of every column.
+    pub unsafe fn move_row_unchecked(&mut self, from: usize, to: usize) {
+        match self {
+            Columns::Inline(inline) => {
+                (0..inline.columns.len()).for_each(|column| inline.move_unchecked(column, from, to))
+            }
+            Columns::Blobs(blob_vecs) => blob_vecs
+                .iter_mut()
+                .for_each(|bvec| bvec.move_unchecked(from, to)),
+        }
+    }
+
+    /// Permutes the rows of every column, so the row at `i` is the row that was at `order[i]`.
+    ///
+    /// # Safety
+    /// It is the caller's responsibility to ensure that `order` is a permutation of `0..len`, where `len` is the
+    /// length of every column.
+    pub unsafe fn permute_rows_unchecked(&mut self, order: &[usize]) {
+        match self {
+            Columns::Inline(inline) => {
+                (0..inline.columns.len()).for_each(|column| inline.permute_unchecked(column, order))
+            }
+            Columns::Blobs(blob_vecs) => blob_vecs
+                .iter_mut()
+                .for_each(|bvec| bvec.permute_unchecked(order)),
+        }
+    }
+
+    /// Shortens every column to `len` elements, dropping the rest.
+    pub fn truncate(&mut self, len: usize) {
+        match self {
+            Columns::Inline(inline) => inline.truncate(len),
+            Columns::Blobs(blob_vecs) => blob_vecs.iter_mut().for_each(|bvec| bvec.truncate(len)),
+        }
+    }
+
+    /// Shortens the column to `len` elements, dropping the rest.
+    pub fn truncate_column(&mut self, column: usize, len: usize) {
+        match self {
+            Columns::Inline(inline) => inline.truncate_column(column, len),
+            Columns::Blobs(blob_vecs) => blob_vecs[column].truncate(len),
+        }
+    }
+
+    /// Set the length of a column, without dropping anything, to corrupt a storage in tests.
+    ///
+    /// # Safety
+    /// `len` must not be greater than the length of the column. The elements after it are leaked.
+    #[cfg(test)]
+    pub(crate) unsafe fn set_column_len(&mut self, column: usize, len: usize) {
+        match self {
+            Columns::Inline(inline) => inline.columns[column].len = len,
+            Columns::Blobs(blob_vecs) => blob_vecs[column].set_len(len),
+        }
+    }
+
+    /// Like [`Self::truncate`], but the columns are truncated in `order` (a permutation of the columns), so in
+    /// each row, the elements are dropped in that order.
+    pub fn truncate_in_order(&mut self, len: usize, order: &[usize]) {
+        match self {
+            Columns::Inline(inline) => order
+                .iter()
+                .for_each(|column| inline.truncate_column(*column, len)),
+            Columns::Blobs(blob_vecs) => order
+                .iter()
+                .for_each(|column| blob_vecs[*column].truncate(len)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Columns, INLINE_ROWS};
+    use crate::prelude::*;
+    use bevy_ptr::OwningPtr;
+    use std::cell::Cell;
+
+    thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Counted(usize);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            DROPPED.set(DROPPED.get() + 1);
+        }
+    }
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Wide(u128, u8);
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Marker;
+
+    fn columns(comp_factory: &mut ComponentFactory) -> Columns {
+        let ids = [
+            comp_factory.register_component::<Counted>().unwrap(),
+            comp_factory.register_component::<Wide>().unwrap(),
+            comp_factory.register_component::<Marker>().unwrap(),
+        ];
+        // SAFETY: The `DataInfo`s were generated for these components.
+        unsafe {
+            Columns::new(
+                ids.iter().map(|id| {
+                    comp_factory
+                        .get_component_info_from_component_id(*id)
+                        .unwrap()
+                }),
+                comp_factory.storage_alloc().clone(),
+            )
+        }
+    }
+
+    fn push_row(columns: &mut Columns, i: usize) {
+        // SAFETY: The columns were created for these components, in this order.
+        unsafe {
+            OwningPtr::make(Counted(i), |ptr| columns.push(0, ptr));
+            OwningPtr::make(Wide(i as u128, i as u8), |ptr| columns.push(1, ptr));
+            OwningPtr::make(Marker, |ptr| columns.push(2, ptr));
+        }
+    }
+
+    fn assert_rows(columns: &Columns, expected: &[usize]) {
+        // SAFETY: The columns were created for these components, in this order.
+        unsafe {
+            let counted = columns.as_slice::<Counted>(0);
+            let wide = columns.as_slice::<Wide>(1);
+            assert_eq!(counted.len(), expected.len());
+            assert_eq!(wide.len(), expected.len());
+            assert_eq!(columns.as_slice::<Marker>(2).len(), expected.len());
+            for (row, i) in expected.iter().enumerate() {
+                assert_eq!(counted[row].0, *i);
+                assert_eq!(wide[row], Wide(*i as u128, *i as u8));
+                assert_eq!(
+                    columns.get_unchecked(1, row).deref::<Wide>(),
+                    &Wide(*i as u128, *i as u8)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_inline_columns_boundary() {
+        let mut comp_factory = ComponentFactory::default();
+        let mut columns = columns(&mut comp_factory);
+        DROPPED.set(0);
+
+        for i in 0..INLINE_ROWS {
+            push_row(&mut columns, i);
+        }
+        assert!(columns.is_inline());
+        assert!(!columns.has_room_for(0, 1));
+        assert_rows(&columns, &(0..INLINE_ROWS).collect::<Vec<_>>());
+
+        // Removing the first row moves the last row into its place.
+        // SAFETY: The row is in bounds.
+        unsafe { columns.swap_remove_and_drop_unchecked(0) };
+        assert_eq!(DROPPED.get(), 1);
+        let mut expected: Vec<usize> = (0..INLINE_ROWS - 1).collect();
+        expected[0] = INLINE_ROWS - 1;
+        assert_rows(&columns, &expected);
+
+        // Back to a full inline storage, and then one row past it.
+        push_row(&mut columns, 100);
+        assert!(columns.is_inline());
+        push_row(&mut columns, 101);
+        assert!(!columns.is_inline());
+        // Moving the rows doesn't drop them.
+        assert_eq!(DROPPED.get(), 1);
+        expected.extend([100, 101]);
+        assert_rows(&columns, &expected);
+
+        // SAFETY: The rows and the columns are in bounds, and the types are correct.
+        unsafe {
+            columns.get_mut_unchecked(0, 3).deref_mut::<Counted>().0 = 3000;
+            columns.as_mut_slice::<Wide>(1)[3] = Wide(3000, 3000_usize as u8);
+            columns.swap_remove_and_drop_unchecked(INLINE_ROWS);
+        }
+        expected[3] = 3000;
+        expected.pop();
+        assert_eq!(DROPPED.get(), 2);
+        assert_rows(&columns, &expected);
+
+        columns.truncate(2);
+        assert_eq!(DROPPED.get(), 2 + INLINE_ROWS - 2);
+        drop(columns);
+        assert_eq!(DROPPED.get(), INLINE_ROWS + 2);
+    }
+
+    #[test]
+    fn test_inline_columns_reserve() {
+        let mut comp_factory = ComponentFactory::default();
+        let mut columns = columns(&mut comp_factory);
+
+        columns.reserve(0, INLINE_ROWS);
+        assert!(columns.is_inline());
+        for i in 0..3 {
+            push_row(&mut columns, i);
+        }
+        columns.reserve(3, INLINE_ROWS - 3);
+        assert!(columns.is_inline());
+        columns.reserve(3, INLINE_ROWS - 2);
+        assert!(!columns.is_inline());
+        assert!(columns.has_room_for(0, INLINE_ROWS - 2));
+        assert!(columns.has_room_for(1, INLINE_ROWS - 2));
+        assert_rows(&columns, &[0, 1, 2]);
+    }
+
+    #[test]
+    fn test_move_row() {
+        let mut comp_factory = ComponentFactory::default();
+        let mut columns = columns(&mut comp_factory);
+        DROPPED.set(0);
+        let mut expected: Vec<usize> = (0..INLINE_ROWS).collect();
+        for i in 0..2 {
+            for row in expected.len() - INLINE_ROWS..expected.len() {
+                push_row(&mut columns, row);
+            }
+            assert_eq!(columns.is_inline(), i == 0);
+            // SAFETY: The rows are in bounds.
+            unsafe {
+                columns.move_row_unchecked(1, 5);
+                columns.move_row_unchecked(6, 0);
+                columns.move_row_unchecked(3, 3);
+            }
+            let row = expected.remove(1);
+            expected.insert(5, row);
+            let row = expected.remove(6);
+            expected.insert(0, row);
+            assert_rows(&columns, &expected);
+            expected.extend(INLINE_ROWS..2 * INLINE_ROWS);
+        }
+        // Moving the rows doesn't drop them.
+        assert_eq!(DROPPED.get(), 0);
+        drop(columns);
+        assert_eq!(DROPPED.get(), 2 * INLINE_ROWS);
+    }
+}
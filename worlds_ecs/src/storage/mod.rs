@@ -1 +1,3 @@
+pub mod alloc;
 pub mod blob_vec;
+pub mod columns;
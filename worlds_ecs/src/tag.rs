@@ -1,18 +1,37 @@
-use crate::utils::TypeIdMap;
-use std::{any::TypeId, sync::Arc};
+use crate::{
+    entity::EntityId,
+    utils::{
+        panics,
+        paranoid::{self, Violation},
+        TypeIdMap,
+    },
+};
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 /// A tag is a marker that can be added and removed from entities. It contains no data.
 pub trait Tag: 'static {}
 
 /// A data-strucutre that can be used to create and manage tags.
+#[derive(Default)]
 pub struct TagFactory {
     tag_id_map: TypeIdMap<u32>,
+    /// The name of each tag, indexed by its ID.
+    tag_names: Vec<&'static str>,
     next_id: u32,
 }
 
-/// Tracks which tags are present on an entity.
+/// Tracks which tags are present on an entity. The clones of a tracker track the same entity, so the flags are
+/// atomic: the callers of the unsafe methods make sure that the flags aren't accessed concurrently, and the
+/// accesses are relaxed.
 pub struct TagTracker {
-    tags: Arc<[bool]>,
+    tags: Arc<[AtomicBool]>,
     factory: Arc<TagFactory>,
 }
 
@@ -25,38 +44,172 @@ impl Clone for TagTracker {
     }
 }
 
-impl Default for TagFactory {
-    fn default() -> Self {
-        Self {
-            tag_id_map: TypeIdMap::default(),
-            next_id: 0,
-        }
-    }
-}
-
 impl TagFactory {
     /// Create a new tag.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// #[derive(Tag)]
+    /// struct Hidden;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// assert_eq!(tagf.register_tag::<Selected>(), 0);
+    /// assert_eq!(tagf.register_tag::<Hidden>(), 1);
+    /// assert_eq!(tagf.tag_id::<Hidden>(), Some(1));
+    /// ```
     pub fn register_tag<T: Tag>(&mut self) -> u32 {
         let id = self.next_id;
         self.next_id += 1;
         self.tag_id_map.insert(TypeId::of::<T>(), id);
+        self.tag_names.push(type_name::<T>());
         id
     }
 
+    /// Get the name of a tag from its ID.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// let selected = tagf.register_tag::<Selected>();
+    /// assert_eq!(tagf.tag_name(selected), Some(std::any::type_name::<Selected>()));
+    /// assert_eq!(tagf.tag_name(selected + 1), None);
+    /// ```
+    pub fn tag_name(&self, id: u32) -> Option<&'static str> {
+        self.tag_names.get(id as usize).copied()
+    }
+
+    /// Get the ID of a tag from its name.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// let selected = tagf.register_tag::<Selected>();
+    /// let name = std::any::type_name::<Selected>();
+    /// assert_eq!(tagf.tag_id_from_name(name), Some(selected));
+    /// assert_eq!(tagf.tag_id_from_name("Selected"), None);
+    /// ```
+    pub fn tag_id_from_name(&self, name: &str) -> Option<u32> {
+        self.tag_names
+            .iter()
+            .position(|tag_name| *tag_name == name)
+            .map(|id| id as u32)
+    }
+
+    /// Get the names of all the registered tags, indexed by their ID.
+    ///
+    /// ```
+    /// use std::any::type_name;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// #[derive(Tag)]
+    /// struct Hidden;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// tagf.register_tag::<Hidden>();
+    /// assert_eq!(tagf.tag_names(), [type_name::<Selected>(), type_name::<Hidden>()]);
+    /// ```
+    pub fn tag_names(&self) -> &[&'static str] {
+        &self.tag_names
+    }
+
+    /// Returns how many tags are registered.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// assert_eq!(tagf.tag_count(), 0);
+    /// tagf.register_tag::<Selected>();
+    /// assert_eq!(tagf.tag_count(), 1);
+    /// ```
+    pub fn tag_count(&self) -> u32 {
+        self.next_id
+    }
+
     /// Get the ID of a tag.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// assert_eq!(tagf.tag_id::<Selected>(), None);
+    /// let selected = tagf.register_tag::<Selected>();
+    /// assert_eq!(tagf.tag_id::<Selected>(), Some(selected));
+    /// ```
     pub fn tag_id<T: Tag>(&self) -> Option<u32> {
         self.tag_id_map.get(&TypeId::of::<T>()).copied()
     }
 
     /// Get the ID of a tag, without checking whether it exists.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// let selected = tagf.register_tag::<Selected>();
+    /// // SAFETY: `Selected` is registered.
+    /// assert_eq!(unsafe { tagf.tag_id_unchecked::<Selected>() }, selected);
+    /// ```
+    ///
+    /// # Safety
+    /// The caller must ensure that the tag is registered.
     pub unsafe fn tag_id_unchecked<T: Tag>(&self) -> u32 {
-        *self.tag_id_map.get(&TypeId::of::<T>()).unwrap_unchecked()
+        let id = self.tag_id_map.get(&TypeId::of::<T>());
+        paranoid::check("TagFactory::tag_id_unchecked", || {
+            id.map(drop)
+                .ok_or(Violation::UnregisteredTag(type_name::<T>()))
+        });
+        *id.unwrap_unchecked()
     }
 
     /// Produce a new [`TagTracker`] to track which tags are present on an entity.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let tagf = Arc::new(tagf);
+    /// let mut tracker = TagFactory::new_tracker(&tagf);
+    /// // SAFETY: `Selected` is registered, and the tracker isn't shared.
+    /// unsafe {
+    ///     assert!(!tracker.is_tagged::<Selected>());
+    ///     tracker.tag::<Selected>();
+    ///     assert!(tracker.is_tagged::<Selected>());
+    /// }
+    /// ```
     pub fn new_tracker(this: &Arc<TagFactory>) -> TagTracker {
         TagTracker {
-            tags: vec![false; this.next_id as usize].into(),
+            tags: (0..this.next_id).map(|_| AtomicBool::new(false)).collect(),
             factory: Arc::clone(this),
         }
     }
@@ -64,26 +217,85 @@ impl TagFactory {
 
 impl TagTracker {
     /// Set this [`Tag`] as present.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// let mut tracker = world.get_tag_tracker(entity).unwrap();
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { tracker.tag::<Selected>() };
+    /// // The trackers of an entity share its tags.
+    /// assert!(unsafe { world.get_tag_tracker(entity).unwrap().is_tagged::<Selected>() });
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that:
     /// - The tag is registered.
     /// - No other [`TagTracker`]s of the same entity are being accessed.
     pub unsafe fn tag<T: Tag>(&mut self) {
         let id = self.factory.tag_id_unchecked::<T>();
-        Arc::get_mut_unchecked(&mut self.tags)[id as usize] = true;
+        self.tags[id as usize].store(true, Ordering::Relaxed);
     }
 
     /// Set this [`Tag`] as not present.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// let mut tracker = world.get_tag_tracker(entity).unwrap();
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe {
+    ///     tracker.tag::<Selected>();
+    ///     tracker.untag::<Selected>();
+    ///     assert!(!tracker.is_tagged::<Selected>());
+    /// }
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that:
     /// - The tag is registered.
     /// - No other [`TagTracker`]s of the same entity are being accessed.
     pub unsafe fn untag<T: Tag>(&mut self) {
         let id = self.factory.tag_id_unchecked::<T>();
-        Arc::get_mut_unchecked(&mut self.tags)[id as usize] = false;
+        self.tags[id as usize].store(false, Ordering::Relaxed);
     }
 
     /// Toggle this [`Tag`]. (If it is present, remove it; if it is not present, add it.)
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// let mut tracker = world.get_tag_tracker(entity).unwrap();
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe {
+    ///     tracker.toggle_unchecked::<Selected>();
+    ///     assert!(tracker.is_tagged::<Selected>());
+    ///     tracker.toggle_unchecked::<Selected>();
+    ///     assert!(!tracker.is_tagged::<Selected>());
+    /// }
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that:
     /// - The tag is registered.
@@ -91,37 +303,495 @@ impl TagTracker {
     pub unsafe fn toggle_unchecked<T: Tag>(&mut self) {
         let id = self.factory.tag_id_unchecked::<T>();
         let current = self.is_tagged::<T>();
-        Arc::get_mut_unchecked(&mut self.tags)[id as usize] = !current;
+        self.tags[id as usize].store(!current, Ordering::Relaxed);
+    }
+
+    /// Create a copy of this tracker, that doesn't track the same entity (unlike [`Clone::clone`], changes to
+    /// one of the trackers don't show up in the other).
+    pub(crate) fn deep_clone(&self) -> TagTracker {
+        TagTracker {
+            tags: (self.tags.iter())
+                .map(|tagged| AtomicBool::new(tagged.load(Ordering::Relaxed)))
+                .collect(),
+            factory: Arc::clone(&self.factory),
+        }
     }
 
     /// Check if this [`Tag`] is registered.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// #[derive(Tag)]
+    /// struct Hidden;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// let tracker = world.get_tag_tracker(entity).unwrap();
+    /// assert!(tracker.is_tag_registered::<Selected>());
+    /// assert!(!tracker.is_tag_registered::<Hidden>());
+    /// ```
     pub fn is_tag_registered<T: Tag>(&self) -> bool {
         self.factory.tag_id::<T>().is_some()
     }
 
-    /// Check if this [`Tag`] is present in this tracker.
+    /// Check if this [`Tag`] is present in this tracker. Panics if the tag isn't registered.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// // SAFETY: No other tracker of the entity is mutated.
+    /// assert!(!unsafe { world.get_tag_tracker(entity).unwrap().is_tagged::<Selected>() });
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that:
     /// - No other [`TagTracker`]s of the same entity are being mutated.
+    #[track_caller]
     pub unsafe fn is_tagged<T: Tag>(&self) -> bool {
-        let id = self.factory.tag_id::<T>().unwrap();
-        self.tags[id as usize]
+        let Some(id) = self.factory.tag_id::<T>() else {
+            panics::fail(
+                "is_tagged",
+                "the tag isn't registered",
+                &[("tag", &type_name::<T>())],
+            );
+        };
+        self.tags[id as usize].load(Ordering::Relaxed)
     }
 
     /// Check if this [`Tag`] is present in this tracker, without checking whether it exists.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// let mut tracker = world.get_tag_tracker(entity).unwrap();
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe {
+    ///     tracker.tag::<Selected>();
+    ///     assert!(tracker.is_tagged_unchecked::<Selected>());
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    /// The caller must ensure that:
+    /// - The tag is registered.
+    /// - No other [`TagTracker`]s of the same entity are being mutated.
     pub unsafe fn is_tagged_unchecked<T: Tag>(&self) -> bool {
         let id = self.factory.tag_id_unchecked::<T>();
-        self.tags[id as usize]
+        self.tags[id as usize].load(Ordering::Relaxed)
+    }
+
+    /// Check if the tag with this ID is present in this tracker. Returns `false` if no tag has this ID.
+    /// # Safety
+    /// The caller must ensure that:
+    /// - No other [`TagTracker`]s of the same entity are being mutated.
+    pub(crate) unsafe fn is_tagged_id(&self, id: u32) -> bool {
+        (self.tags.get(id as usize)).is_some_and(|tagged| tagged.load(Ordering::Relaxed))
+    }
+
+    /// Get the IDs of all the tags that are present in this tracker.
+    pub(crate) fn tagged_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.tags
+            .iter()
+            .enumerate()
+            .filter_map(|(id, tagged)| tagged.load(Ordering::Relaxed).then_some(id as u32))
+    }
+
+    /// Set the tag with this ID as present (or not present).
+    /// # Safety
+    /// The caller must ensure that:
+    /// - `id` is the ID of a registered tag.
+    /// - No other [`TagTracker`]s of the same entity are being accessed.
+    pub(crate) unsafe fn set_tag_id(&mut self, id: u32, tagged: bool) {
+        self.tags[id as usize].store(tagged, Ordering::Relaxed);
     }
 
     /// Remove all tags from this tracker.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// #[derive(Tag)]
+    /// struct Hidden;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// tagf.register_tag::<Hidden>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// let mut tracker = world.get_tag_tracker(entity).unwrap();
+    /// // SAFETY: The tags are registered, and no other tracker of the entity is accessed.
+    /// unsafe {
+    ///     tracker.tag::<Selected>();
+    ///     tracker.tag::<Hidden>();
+    ///     tracker.untag_all();
+    ///     assert!(!tracker.is_tagged::<Selected>());
+    ///     assert!(!tracker.is_tagged::<Hidden>());
+    /// }
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that:
     /// - No other [`TagTracker`]s of the same entity are being accessed.
     pub unsafe fn untag_all(&mut self) {
-        Arc::get_mut_unchecked(&mut self.tags)
-            .iter_mut()
-            .for_each(|tag| *tag = false);
+        (self.tags.iter()).for_each(|tag| tag.store(false, Ordering::Relaxed));
+    }
+}
+
+/// A snapshot of the tags of every entity in a [`World`](crate::world::World). Tags are stored by name,
+/// so the snapshot can be applied to a world whose tags were registered in a different order.
+/// Only entities with at least one tag are recorded.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TagSnapshot {
+    /// The names of the tags that were registered when the snapshot was taken, indexed by their ID at that time.
+    tag_names: Vec<String>,
+    /// Every entity with at least one tag, and a bitset of its tags (indexed by the IDs in `tag_names`).
+    records: Vec<(EntityId, Box<[u64]>)>,
+}
+
+/// What to do when applying a [`TagSnapshot`] that contains tags that aren't registered in the [`World`](crate::world::World).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownTagPolicy {
+    /// Skip the unknown tags, and report them in the [`TagSnapshotReport`].
+    #[default]
+    Skip,
+    /// Fail without applying anything.
+    Fail,
+}
+
+/// The result of successfully applying a [`TagSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TagSnapshotReport {
+    /// How many live entities had their tags set from the snapshot.
+    pub applied_entities: usize,
+    /// The names of the tags in the snapshot that aren't registered in the world, and were skipped.
+    pub unknown_tags: Vec<String>,
+    /// Entities that had tags in the snapshot, but aren't alive in the world.
+    pub missing_entities: Vec<EntityId>,
+}
+
+/// The error returned when a [`TagSnapshot`] couldn't be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagSnapshotError {
+    /// The snapshot contains tags that aren't registered in the world (with [`UnknownTagPolicy::Fail`]).
+    UnknownTags(Vec<String>),
+}
+
+impl std::fmt::Display for TagSnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagSnapshotError::UnknownTags(names) => {
+                write!(f, "the snapshot contains unregistered tags: {:?}", names)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TagSnapshotError {}
+
+impl TagSnapshot {
+    /// Create a new [`TagSnapshot`] from the tags of each entity.
+    pub(crate) fn capture(
+        tagf: &TagFactory,
+        entities: impl Iterator<Item = (EntityId, TagTracker)>,
+    ) -> TagSnapshot {
+        let words = Self::words_per_record(tagf.tag_count() as usize);
+        let records = entities
+            .filter_map(|(entity, tracker)| {
+                let mut bits = vec![0u64; words].into_boxed_slice();
+                let mut any = false;
+                for id in tracker.tagged_ids() {
+                    bits[id as usize / 64] |= 1 << (id % 64);
+                    any = true;
+                }
+                any.then_some((entity, bits))
+            })
+            .collect();
+        TagSnapshot {
+            tag_names: tagf
+                .tag_names()
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            records,
+        }
+    }
+
+    /// Apply this snapshot to the tags of each entity. Every entity is untagged first, so tags that
+    /// aren't in the snapshot end up unset.
+    pub(crate) fn apply(
+        &self,
+        tagf: &TagFactory,
+        entities: impl Iterator<Item = (EntityId, TagTracker)>,
+        policy: UnknownTagPolicy,
+    ) -> Result<TagSnapshotReport, TagSnapshotError> {
+        let id_map: Vec<Option<u32>> = self
+            .tag_names
+            .iter()
+            .map(|name| tagf.tag_id_from_name(name))
+            .collect();
+        let unknown_tags: Vec<String> = self
+            .tag_names
+            .iter()
+            .zip(&id_map)
+            .filter(|(_, id)| id.is_none())
+            .map(|(name, _)| name.clone())
+            .collect();
+        if policy == UnknownTagPolicy::Fail && !unknown_tags.is_empty() {
+            return Err(TagSnapshotError::UnknownTags(unknown_tags));
+        }
+
+        let mut records: HashMap<EntityId, &[u64]> = self
+            .records
+            .iter()
+            .map(|(entity, bits)| (*entity, &bits[..]))
+            .collect();
+        let mut applied_entities = 0;
+        for (entity, mut tracker) in entities {
+            // SAFETY: The caller has exclusive access to the world, so no other trackers are being accessed.
+            unsafe { tracker.untag_all() };
+            let Some(bits) = records.remove(&entity) else {
+                continue;
+            };
+            for (snapshot_id, local_id) in id_map.iter().enumerate() {
+                if let Some(local_id) = local_id {
+                    if bits[snapshot_id / 64] & (1 << (snapshot_id % 64)) != 0 {
+                        // SAFETY: `local_id` was resolved from the tag factory, and we have exclusive access.
+                        unsafe { tracker.set_tag_id(*local_id, true) };
+                    }
+                }
+            }
+            applied_entities += 1;
+        }
+
+        let mut missing_entities: Vec<EntityId> = records.into_keys().collect();
+        missing_entities.sort_by_key(|entity| (entity.id(), entity.generation()));
+        Ok(TagSnapshotReport {
+            applied_entities,
+            unknown_tags,
+            missing_entities,
+        })
+    }
+
+    /// The names of the tags in this snapshot, indexed by their ID at the time the snapshot was taken.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let world = World::with_tags(tagf);
+    /// assert_eq!(world.snapshot_tags().tag_names(), [std::any::type_name::<Selected>()]);
+    /// ```
+    pub fn tag_names(&self) -> &[String] {
+        &self.tag_names
+    }
+
+    /// How many entities have at least one tag in this snapshot.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let selected = world.spawn(());
+    /// world.spawn(());
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { world.get_tag_tracker(selected).unwrap().tag::<Selected>() };
+    /// // Only the entities with tags are recorded.
+    /// assert_eq!(world.snapshot_tags().len(), 1);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if no entity had any tag when this snapshot was taken.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// world.spawn(());
+    /// assert!(world.snapshot_tags().is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Get the names of the tags an entity had when this snapshot was taken.
+    ///
+    /// ```
+    /// use std::any::type_name;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// #[derive(Tag)]
+    /// struct Hidden;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// tagf.register_tag::<Hidden>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// // SAFETY: `Hidden` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { world.get_tag_tracker(entity).unwrap().tag::<Hidden>() };
+    /// let snapshot = world.snapshot_tags();
+    /// assert_eq!(snapshot.tags_of(entity).collect::<Vec<_>>(), [type_name::<Hidden>()]);
+    /// ```
+    pub fn tags_of(&self, entity: EntityId) -> impl Iterator<Item = &str> + '_ {
+        self.records
+            .iter()
+            .find(|(e, _)| *e == entity)
+            .into_iter()
+            .flat_map(move |(_, bits)| {
+                self.tag_names
+                    .iter()
+                    .enumerate()
+                    .filter(|(id, _)| bits[id / 64] & (1 << (id % 64)) != 0)
+                    .map(|(_, name)| name.as_str())
+            })
+    }
+
+    /// Serialize this snapshot into a compact little-endian binary format.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { world.get_tag_tracker(entity).unwrap().tag::<Selected>() };
+    /// let snapshot = world.snapshot_tags();
+    /// let bytes = snapshot.to_bytes();
+    /// assert_eq!(TagSnapshot::from_bytes(&bytes), Some(snapshot));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend((self.tag_names.len() as u32).to_le_bytes());
+        for name in &self.tag_names {
+            bytes.extend((name.len() as u32).to_le_bytes());
+            bytes.extend(name.as_bytes());
+        }
+        bytes.extend((self.records.len() as u32).to_le_bytes());
+        for (entity, bits) in &self.records {
+            bytes.extend(entity.id().to_le_bytes());
+            bytes.extend(entity.generation().to_le_bytes());
+            bits.iter()
+                .for_each(|word| bytes.extend(word.to_le_bytes()));
+        }
+        bytes
+    }
+
+    /// Deserialize a snapshot that was serialized with [`Self::to_bytes`].
+    /// Returns `None` if the bytes are malformed.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { world.get_tag_tracker(entity).unwrap().tag::<Selected>() };
+    /// let bytes = world.snapshot_tags().to_bytes();
+    /// let snapshot = TagSnapshot::from_bytes(&bytes).unwrap();
+    /// assert_eq!(snapshot.len(), 1);
+    /// // Truncated bytes are rejected.
+    /// assert_eq!(TagSnapshot::from_bytes(&bytes[..bytes.len() - 1]), None);
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Option<TagSnapshot> {
+        let mut reader = ByteReader(bytes);
+        let tag_count = reader.read_u32()? as usize;
+        let tag_names = (0..tag_count)
+            .map(|_| {
+                let len = reader.read_u32()? as usize;
+                String::from_utf8(reader.read(len)?.to_vec()).ok()
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let words = Self::words_per_record(tag_count);
+        let record_count = reader.read_u32()? as usize;
+        let records = (0..record_count)
+            .map(|_| {
+                let entity = EntityId::new(reader.read_u32()?).with_generation(reader.read_u32()?);
+                let bits = (0..words)
+                    .map(|_| reader.read_u64())
+                    .collect::<Option<Box<[u64]>>>()?;
+                Some((entity, bits))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        reader
+            .0
+            .is_empty()
+            .then_some(TagSnapshot { tag_names, records })
+    }
+
+    fn words_per_record(tag_count: usize) -> usize {
+        tag_count.div_ceil(64)
+    }
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn read(&mut self, len: usize) -> Option<&'a [u8]> {
+        (self.0.len() >= len).then(|| {
+            let (head, tail) = self.0.split_at(len);
+            self.0 = tail;
+            head
+        })
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.read(4)?.try_into().ok()?))
+    }
+
+    fn read_u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.read(8)?.try_into().ok()?))
     }
 }
 
@@ -136,10 +806,10 @@ mod tests {
     struct HasWings;
 
     #[derive(Component)]
-    struct Bird(&'static str);
+    struct Bird(#[allow(dead_code)] &'static str);
 
     #[derive(Component)]
-    struct FlyingSpeed(f32);
+    struct FlyingSpeed(#[allow(dead_code)] f32);
 
     #[test]
     fn test_tags() {
@@ -151,7 +821,7 @@ mod tests {
 
         let eagle = world.spawn((Bird("Eagle"), FlyingSpeed(10.0)));
 
-        let mut eagle_tracker = world.get_tag_tracker(eagle);
+        let mut eagle_tracker = world.get_tag_tracker(eagle).unwrap();
 
         unsafe {
             eagle_tracker.tag::<Flying>();
@@ -188,4 +858,159 @@ mod tests {
 
     #[test]
     fn test_tags_query() {}
+
+    #[derive(Tag)]
+    struct Swimming;
+
+    fn world_with_tags(register: impl FnOnce(&mut TagFactory)) -> World {
+        let mut tagf = TagFactory::default();
+        register(&mut tagf);
+        World::with_tags(tagf)
+    }
+
+    #[test]
+    fn test_tag_names() {
+        let mut tagf = TagFactory::default();
+        let flying = tagf.register_tag::<Flying>();
+        let wings = tagf.register_tag::<HasWings>();
+        assert_eq!(tagf.tag_count(), 2);
+        assert_eq!(
+            tagf.tag_name(flying),
+            Some("worlds_ecs::tag::tests::Flying")
+        );
+        assert_eq!(
+            tagf.tag_name(wings),
+            Some("worlds_ecs::tag::tests::HasWings")
+        );
+        assert_eq!(tagf.tag_name(2), None);
+        assert_eq!(
+            tagf.tag_id_from_name("worlds_ecs::tag::tests::HasWings"),
+            Some(wings)
+        );
+        assert_eq!(tagf.tag_id_from_name("Swimming"), None);
+    }
+
+    #[test]
+    fn test_tag_snapshot_round_trip() {
+        let mut world = world_with_tags(|tagf| {
+            tagf.register_tag::<Flying>();
+            tagf.register_tag::<HasWings>();
+            tagf.register_tag::<Swimming>();
+        });
+        let none = world.spawn(Bird("Penguin"));
+        let all = world.spawn((Bird("Duck"), FlyingSpeed(4.0)));
+        let some = world.spawn((Bird("Eagle"), FlyingSpeed(10.0)));
+        unsafe {
+            let mut tracker = world.get_tag_tracker(all).unwrap();
+            tracker.tag::<Flying>();
+            tracker.tag::<HasWings>();
+            tracker.tag::<Swimming>();
+            world.get_tag_tracker(some).unwrap().tag::<HasWings>();
+        }
+
+        let snapshot = world.snapshot_tags();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot.tags_of(none).count(), 0);
+        assert_eq!(snapshot.tags_of(all).count(), 3);
+        let bytes = snapshot.to_bytes();
+        assert_eq!(TagSnapshot::from_bytes(&bytes), Some(snapshot.clone()));
+        assert_eq!(TagSnapshot::from_bytes(&bytes[..bytes.len() - 1]), None);
+
+        // The loading world registers the tags in a different order.
+        let mut loaded = world_with_tags(|tagf| {
+            tagf.register_tag::<Swimming>();
+            tagf.register_tag::<HasWings>();
+            tagf.register_tag::<Flying>();
+        });
+        let loaded_none = loaded.spawn(Bird("Penguin"));
+        let loaded_all = loaded.spawn((Bird("Duck"), FlyingSpeed(4.0)));
+        let loaded_some = loaded.spawn((Bird("Eagle"), FlyingSpeed(10.0)));
+        unsafe {
+            loaded
+                .get_tag_tracker(loaded_none)
+                .unwrap()
+                .tag::<Swimming>()
+        };
+
+        let report = loaded
+            .apply_tag_snapshot(
+                &TagSnapshot::from_bytes(&bytes).unwrap(),
+                UnknownTagPolicy::Fail,
+            )
+            .unwrap();
+        assert_eq!(report.applied_entities, 2);
+        assert!(report.unknown_tags.is_empty());
+        assert!(report.missing_entities.is_empty());
+
+        unsafe {
+            let tracker = loaded.get_tag_tracker(loaded_none).unwrap();
+            assert!(!tracker.is_tagged::<Flying>());
+            assert!(!tracker.is_tagged::<HasWings>());
+            assert!(!tracker.is_tagged::<Swimming>());
+            let tracker = loaded.get_tag_tracker(loaded_all).unwrap();
+            assert!(tracker.is_tagged::<Flying>());
+            assert!(tracker.is_tagged::<HasWings>());
+            assert!(tracker.is_tagged::<Swimming>());
+            let tracker = loaded.get_tag_tracker(loaded_some).unwrap();
+            assert!(!tracker.is_tagged::<Flying>());
+            assert!(tracker.is_tagged::<HasWings>());
+            assert!(!tracker.is_tagged::<Swimming>());
+        }
+    }
+
+    #[test]
+    fn test_tag_snapshot_unknown_tags() {
+        let mut world = world_with_tags(|tagf| {
+            tagf.register_tag::<Flying>();
+            tagf.register_tag::<Swimming>();
+        });
+        let duck = world.spawn(Bird("Duck"));
+        let eagle = world.spawn(Bird("Eagle"));
+        unsafe {
+            world.get_tag_tracker(duck).unwrap().tag::<Swimming>();
+            world.get_tag_tracker(duck).unwrap().tag::<Flying>();
+            world.get_tag_tracker(eagle).unwrap().tag::<Flying>();
+        }
+        let snapshot = world.snapshot_tags();
+        world.despawn(eagle);
+
+        let mut loaded = world_with_tags(|tagf| {
+            tagf.register_tag::<Flying>();
+            tagf.register_tag::<HasWings>();
+        });
+        let loaded_duck = loaded.spawn(Bird("Duck"));
+
+        assert_eq!(
+            loaded.apply_tag_snapshot(&snapshot, UnknownTagPolicy::Fail),
+            Err(TagSnapshotError::UnknownTags(vec![String::from(
+                "worlds_ecs::tag::tests::Swimming"
+            )]))
+        );
+        unsafe {
+            assert!(!loaded
+                .get_tag_tracker(loaded_duck)
+                .unwrap()
+                .is_tagged::<Flying>())
+        };
+
+        let report = loaded
+            .apply_tag_snapshot(&snapshot, UnknownTagPolicy::Skip)
+            .unwrap();
+        assert_eq!(report.applied_entities, 1);
+        assert_eq!(
+            report.unknown_tags,
+            vec![String::from("worlds_ecs::tag::tests::Swimming")]
+        );
+        assert_eq!(report.missing_entities, vec![eagle]);
+        unsafe {
+            assert!(loaded
+                .get_tag_tracker(loaded_duck)
+                .unwrap()
+                .is_tagged::<Flying>());
+            assert!(!loaded
+                .get_tag_tracker(loaded_duck)
+                .unwrap()
+                .is_tagged::<HasWings>());
+        }
+    }
 }
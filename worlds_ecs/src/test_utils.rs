@@ -0,0 +1,492 @@
+use crate::{archetype::ArchetypeInfo, prelude::*, storage::alloc::StorageAlloc};
+use bevy_ptr::OwningPtr;
+use std::{
+    alloc::Layout, any::type_name, collections::HashMap, fmt::Debug, ptr::NonNull, sync::Mutex,
+};
+
+/// The amount of fixture components: [`Fx<0>`](Fx) up to [`Fx<7>`](Fx).
+pub const FIXTURES: usize = 8;
+
+/// A fixture component. Every `N` is a distinct component type, so tests can use as many components as they need
+/// without declaring them. An archetype spec (see [`WorldFixture::populated`]) refers to `Fx<N>` as `N`,
+/// for `N` < [`FIXTURES`].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Fx<const N: usize>(pub u64);
+
+/// The fixture components of an archetype, each one as its `N` (see [`Fx`]). For example, `&[0, 2]` is `(Fx<0>, Fx<2>)`.
+pub type ArchetypeSpec<'a> = &'a [usize];
+
+fn register_fixture<const N: usize>(world: &mut World) -> ComponentId {
+    world
+        .register_comparable_component::<Fx<N>>()
+        .expect("The maximum amount of components was reached")
+}
+
+fn bump_fixture<const N: usize>(world: &mut World, entity: EntityId) -> bool {
+    world
+        .get_component_mut::<Fx<N>>(entity)
+        .map(|fx| fx.0 += 1)
+        .is_some()
+}
+
+macro_rules! fixture_fns {
+    ($f:ident) => {
+        [
+            $f::<0>, $f::<1>, $f::<2>, $f::<3>, $f::<4>, $f::<5>, $f::<6>, $f::<7>,
+        ]
+    };
+}
+
+const REGISTER_FIXTURE: [fn(&mut World) -> ComponentId; FIXTURES] = fixture_fns!(register_fixture);
+const BUMP_FIXTURE: [fn(&mut World, EntityId) -> bool; FIXTURES] = fixture_fns!(bump_fixture);
+
+/// A bundle of fixture components with the same value.
+struct FixtureBundle<'a> {
+    comp_ids: &'a [ComponentId],
+    value: u64,
+}
+
+impl Bundle for FixtureBundle<'_> {
+    fn raw_components_scope(
+        self,
+        _comp_factory: &ComponentFactory,
+        f: &mut impl FnMut(ComponentId, OwningPtr<'_>),
+    ) {
+        for comp_id in self.comp_ids {
+            // Every fixture component is a `u64`.
+            OwningPtr::make(self.value, |ptr| f(*comp_id, ptr));
+        }
+    }
+}
+
+/// Register the fixture components of the spec, and return their [`ComponentId`]s.
+/// Panics if the spec is empty, has duplicates, or refers to a fixture that doesn't exist.
+fn resolve_spec(world: &mut World, spec: ArchetypeSpec<'_>) -> Vec<ComponentId> {
+    assert!(!spec.is_empty(), "An archetype spec can't be empty");
+    spec.iter()
+        .enumerate()
+        .map(|(i, n)| {
+            assert!(
+                *n < FIXTURES,
+                "Archetype spec {spec:?} refers to `Fx<{n}>`, but only `Fx<0>` to `Fx<{}>` exist",
+                FIXTURES - 1
+            );
+            assert!(
+                !spec[..i].contains(n),
+                "Archetype spec {spec:?} has `Fx<{n}>` more than once"
+            );
+            REGISTER_FIXTURE[*n](world)
+        })
+        .collect()
+}
+
+/// Spawn an entity with the fixture components of the spec, all with the same value.
+fn spawn_fixture(world: &mut World, comp_ids: &[ComponentId], value: u64) -> EntityId {
+    let arch_info = ArchetypeInfo::from_component_ids(comp_ids.to_vec());
+    let bundle = FixtureBundle { comp_ids, value };
+    // SAFETY: The bundle stores exactly the components of the archetype, which are distinct (see `resolve_spec`).
+    unsafe { world.spawn_batch_with_info(&arch_info, std::iter::once(bundle)) }
+        .expect("The fixture components were registered")[0]
+}
+
+/// A [`World`] populated from a declarative spec, see [`WorldFixture::populated`].
+pub struct WorldFixture {
+    world: World,
+    entities: Vec<Vec<EntityId>>,
+}
+
+impl WorldFixture {
+    /// Create a [`World`] with `count` entities for each archetype spec. Every fixture component of the `i`th spawned
+    /// entity (counting from 0, over all the specs) has the value `i`. For example:
+    /// `WorldFixture::populated(&[(&[0, 1], 100), (&[2], 5)])` spawns 100 entities with `(Fx<0>, Fx<1>)`,
+    /// and then 5 entities with `Fx<2>`.
+    pub fn populated(spec: &[(ArchetypeSpec<'_>, usize)]) -> Self {
+        let mut world = World::default();
+        let mut value = 0;
+        let entities = spec
+            .iter()
+            .map(|(archetype, count)| {
+                let comp_ids = resolve_spec(&mut world, archetype);
+                (0..*count)
+                    .map(|_| {
+                        value += 1;
+                        spawn_fixture(&mut world, &comp_ids, value - 1)
+                    })
+                    .collect()
+            })
+            .collect();
+        WorldFixture { world, entities }
+    }
+
+    /// The populated [`World`].
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// The populated [`World`], mutably.
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Take the populated [`World`].
+    pub fn into_world(self) -> World {
+        self.world
+    }
+
+    /// The entities that were spawned for the `i`th archetype spec, in order.
+    pub fn entities(&self, i: usize) -> &[EntityId] {
+        &self.entities[i]
+    }
+}
+
+/// Assert that the query `Q` matches exactly `expected` entities in the [`World`].
+#[track_caller]
+pub fn assert_query_count<Q: ArchQuery>(world: &mut World, expected: usize) {
+    let count = world.query::<Q>().count();
+    assert!(
+        count == expected,
+        "Expected the query `{}` to match {expected} entities, but it matched {count}",
+        type_name::<Q>()
+    );
+}
+
+/// Assert that an entity is alive, and that it has the component `C` with the expected value.
+#[track_caller]
+pub fn assert_entity_has<C: Component + PartialEq + Debug>(
+    world: &World,
+    entity: EntityId,
+    expected: C,
+) {
+    assert!(
+        world.entities.get_entity_meta(entity).is_some(),
+        "Expected {entity:?} to have `{}`, but it was despawned",
+        type_name::<C>()
+    );
+    match world.get_component::<C>(entity) {
+        Some(component) => assert!(
+            *component == expected,
+            "Expected {entity:?} to have `{expected:?}`, but it has `{component:?}`"
+        ),
+        None => panic!(
+            "Expected {entity:?} to have `{expected:?}`, but it doesn't have `{}`",
+            type_name::<C>()
+        ),
+    }
+}
+
+/// A single operation of a [`ChurnScript`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChurnOp {
+    /// An entity was spawned, with the fixture components of one of the script's archetypes.
+    Spawn(EntityId),
+    /// An entity that the script spawned was despawned.
+    Despawn(EntityId),
+    /// Every fixture component of an entity that the script spawned was incremented by 1.
+    Mutate(EntityId),
+}
+
+/// A deterministic sequence of spawn, despawn and mutate operations, for stress tests. The same seed always produces
+/// the same sequence (on a [`World`] in the same state).
+///
+/// The script first spawns its initial entities, and then churns: each step is a random operation on a random
+/// entity that the script spawned. Spawns during the churn only replace entities that the script despawned,
+/// so the population stays around its initial size.
+#[derive(Debug, Clone)]
+pub struct ChurnScript {
+    seed: u64,
+    steps: usize,
+    initial_entities: usize,
+    archetypes: Vec<Vec<usize>>,
+}
+
+impl ChurnScript {
+    /// Create a new [`ChurnScript`] with a seed, 1000 steps, 100 initial entities, and the archetypes `Fx<0>`,
+    /// `(Fx<0>, Fx<1>)` and `(Fx<1>, Fx<2>)`.
+    pub fn new(seed: u64) -> Self {
+        ChurnScript {
+            seed,
+            steps: 1000,
+            initial_entities: 100,
+            archetypes: vec![vec![0], vec![0, 1], vec![1, 2]],
+        }
+    }
+
+    /// Set the amount of churn steps.
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Set the amount of entities that are spawned before the churn.
+    pub fn initial_entities(mut self, initial_entities: usize) -> Self {
+        self.initial_entities = initial_entities;
+        self
+    }
+
+    /// Set the archetypes of the spawned entities (see [`ArchetypeSpec`]).
+    pub fn archetypes(mut self, archetypes: &[ArchetypeSpec<'_>]) -> Self {
+        self.archetypes = archetypes.iter().map(|spec| spec.to_vec()).collect();
+        self
+    }
+
+    /// Run the script on the [`World`], and return the entities that the script spawned and are still alive.
+    pub fn run(&self, world: &mut World) -> Vec<EntityId> {
+        self.run_with(world, |_, _| {})
+    }
+
+    /// Run the script on the [`World`], calling `on_step` after every operation (including the initial spawns),
+    /// and return the entities that the script spawned and are still alive.
+    pub fn run_with(
+        &self,
+        world: &mut World,
+        mut on_step: impl FnMut(&mut World, ChurnOp),
+    ) -> Vec<EntityId> {
+        assert!(
+            !self.archetypes.is_empty(),
+            "A churn script needs at least one archetype"
+        );
+        let archetypes: Vec<Vec<ComponentId>> = self
+            .archetypes
+            .iter()
+            .map(|spec| resolve_spec(world, spec))
+            .collect();
+        let mut seed = self.seed | 1;
+        let mut random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as usize
+        };
+        let mut alive = Vec::with_capacity(self.initial_entities);
+        let mut value = 0;
+        let mut spawn = |world: &mut World, archetype: &[ComponentId]| {
+            value += 1;
+            spawn_fixture(world, archetype, value)
+        };
+
+        for _ in 0..self.initial_entities {
+            let entity = spawn(world, &archetypes[random() % archetypes.len()]);
+            alive.push(entity);
+            on_step(world, ChurnOp::Spawn(entity));
+        }
+        let mut despawned = 0;
+        for _ in 0..self.steps {
+            let op = match random() % 3 {
+                0 if despawned > 0 => {
+                    despawned -= 1;
+                    let entity = spawn(world, &archetypes[random() % archetypes.len()]);
+                    alive.push(entity);
+                    ChurnOp::Spawn(entity)
+                }
+                1 if !alive.is_empty() => {
+                    let entity = alive.swap_remove(random() % alive.len());
+                    world.despawn(entity);
+                    despawned += 1;
+                    ChurnOp::Despawn(entity)
+                }
+                _ if !alive.is_empty() => {
+                    let entity = alive[random() % alive.len()];
+                    BUMP_FIXTURE.iter().for_each(|bump| {
+                        bump(world, entity);
+                    });
+                    ChurnOp::Mutate(entity)
+                }
+                _ => continue,
+            };
+            on_step(world, op);
+        }
+        alive
+    }
+}
+
+/// A bump [`StorageAlloc`] over a fixed arena, that accounts for every block it hands out, to verify that
+/// storages return all of their memory (see [`World::with_allocator`]). Freed memory is never reused.
+pub struct BumpAlloc {
+    arena: NonNull<u8>,
+    arena_layout: Layout,
+    state: Mutex<BumpState>,
+}
+
+#[derive(Default)]
+struct BumpState {
+    /// The offset of the next free byte in the arena.
+    next: usize,
+    /// The layouts of the blocks that weren't freed yet, by their address.
+    live: HashMap<usize, Layout>,
+    allocated_bytes: usize,
+    bad_frees: usize,
+}
+
+impl BumpAlloc {
+    /// Create a new [`BumpAlloc`] with an arena of `capacity` bytes. Allocations fail once the arena is used up.
+    pub fn new(capacity: usize) -> Self {
+        let arena_layout =
+            Layout::from_size_align(capacity.max(1), 64).expect("layout should be valid");
+        // SAFETY: The layout has a non-zero size.
+        let arena = NonNull::new(unsafe { std::alloc::alloc(arena_layout) })
+            .unwrap_or_else(|| std::alloc::handle_alloc_error(arena_layout));
+        BumpAlloc {
+            arena,
+            arena_layout,
+            state: Mutex::default(),
+        }
+    }
+
+    /// The amount of bytes in blocks that were allocated and not freed yet.
+    pub fn live_bytes(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .live
+            .values()
+            .map(Layout::size)
+            .sum()
+    }
+
+    /// The amount of blocks that were allocated and not freed yet.
+    pub fn live_blocks(&self) -> usize {
+        self.state.lock().unwrap().live.len()
+    }
+
+    /// The total amount of bytes that were ever allocated.
+    pub fn allocated_bytes(&self) -> usize {
+        self.state.lock().unwrap().allocated_bytes
+    }
+
+    /// The amount of times a block was freed that isn't live: it was freed already, it wasn't allocated by this
+    /// allocator, or it was freed with the wrong layout.
+    pub fn bad_frees(&self) -> usize {
+        self.state.lock().unwrap().bad_frees
+    }
+
+    /// Returns `true` if every block was freed exactly once.
+    pub fn is_balanced(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.live.is_empty() && state.bad_frees == 0
+    }
+
+    fn free(state: &mut BumpState, ptr: *mut u8, layout: Layout) {
+        if state.live.remove(&(ptr as usize)) != Some(layout) {
+            state.bad_frees += 1;
+        }
+    }
+}
+
+// SAFETY: Blocks never overlap, because the arena is never reused. Every block is aligned and fits its layout.
+unsafe impl StorageAlloc for BumpAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut state = self.state.lock().unwrap();
+        let base = self.arena.as_ptr() as usize;
+        let start = (base + state.next).next_multiple_of(layout.align()) - base;
+        if start + layout.size() > self.arena_layout.size() {
+            return std::ptr::null_mut();
+        }
+        state.next = start + layout.size();
+        state.allocated_bytes += layout.size();
+        let ptr = self.arena.as_ptr().add(start);
+        state.live.insert(ptr as usize, layout);
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.alloc(Layout::from_size_align_unchecked(new_size, layout.align()));
+        if !new_ptr.is_null() {
+            std::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            Self::free(&mut self.state.lock().unwrap(), ptr, layout);
+        }
+        new_ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        Self::free(&mut self.state.lock().unwrap(), ptr, layout);
+    }
+}
+
+impl Drop for BumpAlloc {
+    fn drop(&mut self) {
+        // SAFETY: The arena was allocated with `arena_layout`.
+        unsafe { std::alloc::dealloc(self.arena.as_ptr(), self.arena_layout) };
+    }
+}
+
+// SAFETY: The arena is only accessed through the blocks that were handed out, and the state is behind a `Mutex`.
+unsafe impl Send for BumpAlloc {}
+// SAFETY: See above.
+unsafe impl Sync for BumpAlloc {}
+
+/// Assert that the storages of a [`World`] allocated at most `bytes` bytes, for memory regression tests. The
+/// allocations of the world must be tracked (see [`World::track_allocations`]).
+#[macro_export]
+macro_rules! assert_memory_within {
+    ($world:expr, $bytes:expr) => {{
+        let tracked = $world
+            .tracked_bytes()
+            .expect("the allocations of the world aren't tracked, see `World::track_allocations`");
+        let bytes: usize = $bytes;
+        assert!(
+            tracked <= bytes,
+            "the storages allocated {tracked} bytes, more than the {bytes} bytes they are allowed",
+        );
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixtures_are_distinct_components() {
+        let mut world = World::default();
+        let ids: Vec<ComponentId> = REGISTER_FIXTURE
+            .iter()
+            .map(|register| register(&mut world))
+            .collect();
+        assert_eq!(ids.len(), FIXTURES);
+        assert!((1..FIXTURES).all(|i| !ids[..i].contains(&ids[i])));
+        assert_eq!(REGISTER_FIXTURE[3](&mut world), ids[3]);
+    }
+
+    #[test]
+    fn test_populated_world() {
+        let mut fixture = WorldFixture::populated(&[(&[0, 1], 3), (&[2], 2)]);
+        assert_eq!(fixture.entities(0).len(), 3);
+        let last = fixture.entities(1)[1];
+        assert_entity_has(fixture.world(), last, Fx::<2>(4));
+        assert_query_count::<&Fx<0>>(fixture.world_mut(), 3);
+        assert_query_count::<(&Fx<1>, &Fx<2>)>(fixture.world_mut(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "but it has `Fx(1)`")]
+    fn test_assert_entity_has_value() {
+        let fixture = WorldFixture::populated(&[(&[0], 2)]);
+        assert_entity_has(fixture.world(), fixture.entities(0)[1], Fx::<0>(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "but it was despawned")]
+    fn test_assert_entity_has_despawned() {
+        let mut world = WorldFixture::populated(&[(&[0], 2)]).into_world();
+        let entity = world.query::<EntityId>().next().unwrap();
+        world.despawn(entity);
+        assert_entity_has(&world, entity, Fx::<0>(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "refers to `Fx<8>`")]
+    fn test_invalid_spec() {
+        WorldFixture::populated(&[(&[0, 8], 1)]);
+    }
+
+    #[test]
+    fn test_churn_is_deterministic() {
+        let script = ChurnScript::new(7).steps(300).initial_entities(20);
+        let mut ops = [Vec::new(), Vec::new()];
+        for ops in &mut ops {
+            let mut world = World::default();
+            script.run_with(&mut world, |_, op| ops.push(op));
+        }
+        assert_eq!(ops[0], ops[1]);
+        assert!(ops[0].iter().any(|op| matches!(op, ChurnOp::Despawn(_))));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::archetype::key::MAX_COMPONENTS;
+use crate::component::ComponentId;
+
+/// A set of components, with a bit for each [`ComponentId`]. Unlike a [`PrimeArchKey`](crate::archetype::key::PrimeArchKey),
+/// checking whether a component is in the set is a single bit test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ComponentMask([u64; MAX_COMPONENTS / 64]);
+
+impl ComponentMask {
+    pub const EMPTY: ComponentMask = ComponentMask([0; MAX_COMPONENTS / 64]);
+
+    pub fn from_component_ids(comp_ids: impl IntoIterator<Item = ComponentId>) -> Self {
+        let mut mask = Self::EMPTY;
+        comp_ids
+            .into_iter()
+            .for_each(|comp_id| mask.insert(comp_id));
+        mask
+    }
+
+    #[inline(always)]
+    pub fn insert(&mut self, comp_id: ComponentId) {
+        self.0[comp_id.id() / 64] |= 1 << (comp_id.id() % 64);
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, comp_id: ComponentId) -> bool {
+        self.0[comp_id.id() / 64] & (1 << (comp_id.id() % 64)) != 0
+    }
+
+    pub fn union(mut self, other: ComponentMask) -> Self {
+        self.0
+            .iter_mut()
+            .zip(other.0)
+            .for_each(|(word, other)| *word |= other);
+        self
+    }
+
+    pub fn intersection(mut self, other: ComponentMask) -> Self {
+        self.0
+            .iter_mut()
+            .zip(other.0)
+            .for_each(|(word, other)| *word &= other);
+        self
+    }
+
+    pub fn is_subset(&self, other: &ComponentMask) -> bool {
+        self.0
+            .iter()
+            .zip(other.0)
+            .all(|(word, other)| word & !other == 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    /// Iterate over the components in the set, in the order of their ids.
+    pub fn iter(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.0.iter().enumerate().flat_map(|(i, word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| ComponentId::new(i * 64 + bit))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_component_mask() {
+        let ids = [0, 63, 64, MAX_COMPONENTS - 1].map(ComponentId::new);
+        let mask = ComponentMask::from_component_ids(ids);
+        for id in 0..MAX_COMPONENTS {
+            assert_eq!(
+                mask.contains(ComponentId::new(id)),
+                ids.contains(&ComponentId::new(id))
+            );
+        }
+        assert!(!ComponentMask::default().contains(ids[0]));
+        assert!(mask.iter().eq(ids));
+    }
+
+    #[test]
+    fn test_component_mask_set_operations() {
+        let mask = |ids: &[usize]| {
+            ComponentMask::from_component_ids(ids.iter().map(|id| ComponentId::new(*id)))
+        };
+        let (a, b) = (mask(&[1, 70, 200]), mask(&[70, 3]));
+        assert_eq!(a.union(b), mask(&[1, 3, 70, 200]));
+        assert_eq!(a.intersection(b), mask(&[70]));
+        assert!(a.intersection(mask(&[2])).is_empty());
+        assert!(mask(&[70]).is_subset(&a));
+        assert!(!b.is_subset(&a));
+        assert!(ComponentMask::EMPTY.is_subset(&b));
+    }
+}
@@ -1,5 +1,7 @@
+pub(crate) mod component_mask;
 pub(crate) mod macros;
-pub(crate) mod prime_key;
+pub(crate) mod panics;
+pub(crate) mod paranoid;
 
 /// A specialized hashmap type with Key of [`TypeId`]
 pub type TypeIdMap<V> =
@@ -0,0 +1,275 @@
+//! Helpers that raise the panics of the public entry points, so every message has the same format:
+//! `worlds_ecs: <operation> failed: <reason> (<key>=<value>, ...)`.
+//!
+//! The helpers are `#[track_caller]`, and so is every function between them and the public entry point, so the
+//! location of the panic is the user's call site.
+use crate::{
+    archetype::{ArchetypeInfo, MAX_COMPS_PER_ARCH},
+    component::{ComponentFactory, ComponentId},
+    entity::EntityId,
+};
+use std::fmt::{self, Display};
+
+/// An [`EntityId`] as it's written in panic messages: `<id>:<generation>`.
+pub(crate) struct EntityLabel(pub(crate) EntityId);
+
+impl Display for EntityLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.0.id(), self.0.generation())
+    }
+}
+
+/// The message of a failed operation.
+pub(crate) fn message(
+    operation: &str,
+    reason: impl Display,
+    context: &[(&str, &dyn Display)],
+) -> String {
+    let mut message = format!("worlds_ecs: {operation} failed: {reason}");
+    for (i, (key, value)) in context.iter().enumerate() {
+        let separator = if i == 0 { " (" } else { ", " };
+        message.push_str(&format!("{separator}{key}={value}"));
+    }
+    if !context.is_empty() {
+        message.push(')');
+    }
+    message
+}
+
+/// Panic because an operation failed.
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub(crate) fn fail(operation: &str, reason: impl Display, context: &[(&str, &dyn Display)]) -> ! {
+    panic!("{}", message(operation, reason, context))
+}
+
+/// Panic because an operation on an entity failed.
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub(crate) fn fail_entity(operation: &str, reason: impl Display, entity: EntityId) -> ! {
+    fail(operation, reason, &[("entity", &EntityLabel(entity))])
+}
+
+/// Panic because an operation on a component failed.
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub(crate) fn fail_component(operation: &str, reason: impl Display, component: &str) -> ! {
+    fail(operation, reason, &[("component", &component)])
+}
+
+/// Panic because the component `C` can't be registered, since the maximum amount of registered components has
+/// been reached.
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub(crate) fn fail_component_limit<C>() -> ! {
+    fail_component(
+        "register_component",
+        "the maximum amount of registered components has been reached",
+        std::any::type_name::<C>(),
+    )
+}
+
+/// Panic because a component appears more than once in the archetype `A`.
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub(crate) fn fail_duplicate_components<A>(operation: &str) -> ! {
+    fail(
+        operation,
+        "a component appears more than once in the archetype",
+        &[("archetype", &std::any::type_name::<A>())],
+    )
+}
+
+/// Panic because the archetype `A` has more than [`MAX_COMPS_PER_ARCH`] components. With the `bundle-provenance`
+/// feature, the components are listed by the sub-bundle that contributed them, so it's clear which one to split.
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub(crate) fn fail_too_many_components<A>(
+    operation: &str,
+    arch_info: &ArchetypeInfo,
+    components: &ComponentFactory,
+) -> ! {
+    let name = |comp_id: &ComponentId| {
+        components
+            .get_component_info_from_component_id(*comp_id)
+            .map_or("<unregistered>", |info| info.name())
+    };
+    #[cfg(feature = "bundle-provenance")]
+    let listed = {
+        let mut groups: Vec<(&str, Vec<&str>)> = Vec::new();
+        for (comp_id, source) in arch_info.component_ids().iter().zip(arch_info.sources()) {
+            match groups.iter_mut().find(|(group, _)| group == source) {
+                Some((_, names)) => names.push(name(comp_id)),
+                None => groups.push((source, vec![name(comp_id)])),
+            }
+        }
+        (groups.iter())
+            .map(|(source, names)| format!("{source}: [{}]", names.join(", ")))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+    #[cfg(not(feature = "bundle-provenance"))]
+    let listed = format!(
+        "[{}] (enable the `bundle-provenance` feature to group them by sub-bundle)",
+        (arch_info.component_ids().iter().map(name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    fail(
+        operation,
+        format!(
+            "the archetype has {} components, more than the maximum of {MAX_COMPS_PER_ARCH}",
+            arch_info.component_ids().len()
+        ),
+        &[
+            ("archetype", &std::any::type_name::<A>()),
+            ("components", &listed),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::message;
+    use crate::{prelude::*, world::storage::storages::ArchStorageId};
+    use std::{
+        cell::RefCell,
+        panic::{catch_unwind, AssertUnwindSafe, Location},
+        sync::Once,
+    };
+
+    thread_local! {
+        static LAST_PANIC: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+    }
+
+    /// Run `f`, which must panic, and return the panic message and the file that the panic points at.
+    fn panic_of(f: impl FnOnce()) -> (String, String) {
+        static HOOK: Once = Once::new();
+        HOOK.call_once(|| {
+            let default = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                let message = info
+                    .payload()
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| info.payload().downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_default();
+                let file = info
+                    .location()
+                    .map(|l| l.file().to_string())
+                    .unwrap_or_default();
+                LAST_PANIC.with(|last| *last.borrow_mut() = Some((message, file)));
+                default(info);
+            }));
+        });
+        assert!(
+            catch_unwind(AssertUnwindSafe(f)).is_err(),
+            "it didn't panic"
+        );
+        LAST_PANIC.with(|last| last.borrow_mut().take()).unwrap()
+    }
+
+    #[derive(Component)]
+    struct A;
+    #[derive(Component)]
+    struct B;
+    #[derive(Tag)]
+    struct Red;
+    #[derive(Tag)]
+    struct Blue;
+
+    #[test]
+    fn test_message_format() {
+        assert_eq!(
+            message("spawn", "no room", &[]),
+            "worlds_ecs: spawn failed: no room"
+        );
+        assert_eq!(
+            message(
+                "despawn",
+                "gone",
+                &[("entity", &"1:0"), ("component", &"A")]
+            ),
+            "worlds_ecs: despawn failed: gone (entity=1:0, component=A)"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: despawn failed: the entity isn't alive (entity=")]
+    fn test_despawn_twice() {
+        let mut world = World::default();
+        let entity = world.spawn(A);
+        world.despawn(entity);
+        world.despawn(entity);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "worlds_ecs: query failed: the query accesses a component more than once (component="
+    )]
+    fn test_query_duplicate() {
+        let mut world = World::default();
+        world.spawn(A);
+        world.query::<(&A, &A)>().for_each(drop);
+    }
+
+    type Case = Box<dyn FnOnce(&mut World)>;
+
+    #[test]
+    fn test_panics_point_at_the_caller() {
+        let this_file = Location::caller().file();
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Red>();
+        let mut world = World::with_tags(tagf);
+        let entity = world.spawn(A);
+        let despawned = world.spawn(B);
+        world.despawn(despawned);
+        let cases: Vec<(&str, Case)> = vec![
+            ("despawn", Box::new(move |world| world.despawn(despawned))),
+            (
+                "query",
+                Box::new(|world| {
+                    world.query::<(&A, &A)>();
+                }),
+            ),
+            (
+                "query_filtered",
+                Box::new(|world| {
+                    world.query_filtered::<(&A, &A), ()>();
+                }),
+            ),
+            (
+                "is_tagged",
+                Box::new(move |world| unsafe {
+                    world.get_tag_tracker(entity).unwrap().is_tagged::<Blue>();
+                }),
+            ),
+            (
+                "set_userdata",
+                Box::new(move |world| {
+                    world.set_userdata(despawned, UserdataKey::Namespace(0), Box::new(()));
+                }),
+            ),
+            (
+                "pin_storage",
+                Box::new(|world| {
+                    let _pin = world.pin_storage(ArchStorageId(9));
+                }),
+            ),
+        ];
+        for (operation, case) in cases {
+            let (message, file) = panic_of(|| case(&mut world));
+            assert!(
+                message.starts_with(&format!("worlds_ecs: {operation} failed: ")),
+                "{message}"
+            );
+            assert_eq!(file, this_file, "{message}");
+        }
+    }
+}
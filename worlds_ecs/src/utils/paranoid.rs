@@ -0,0 +1,433 @@
+//! Checks of the preconditions of the `*_unchecked` methods, for the `paranoid` feature.
+//!
+//! Without the feature, the unchecked methods stay zero-cost: only the preconditions that were always
+//! debug-asserted are checked, and only in debug builds (see [`debug_check`]). With the feature, every
+//! precondition is checked in every build, and a violated precondition panics right away, naming the method, the
+//! precondition, and the values involved, instead of corrupting memory far from the cause. It's meant for soak
+//! tests and bug hunts.
+//!
+//! The checked methods validate with the same helpers (like [`index`]) before they delegate to the unchecked
+//! ones, so each precondition is written once.
+use super::panics::{self, EntityLabel};
+use crate::{component::ComponentId, entity::EntityId};
+use std::{alloc::Layout, any::type_name, fmt::Display};
+
+/// Whether the preconditions of the unchecked methods are checked in every build.
+pub(crate) const ENABLED: bool = cfg!(feature = "paranoid");
+
+/// A violated precondition of an unchecked method.
+#[derive(Debug)]
+pub(crate) enum Violation {
+    /// An index is out of bounds.
+    OutOfBounds {
+        what: &'static str,
+        index: usize,
+        len: usize,
+    },
+    /// There is no room for another element.
+    NoRoom { len: usize, capacity: usize },
+    /// An order isn't a permutation of `0..len`.
+    NotAPermutation { len: usize },
+    /// A component isn't stored in the storage.
+    MissingComponent(ComponentId),
+    /// A type doesn't have the layout of the type-erased values.
+    LayoutMismatch {
+        type_name: &'static str,
+        expected: Layout,
+    },
+    /// A bundle didn't store a component for every column.
+    IncompleteBundle { column: usize },
+    /// A tag isn't registered.
+    UnregisteredTag(&'static str),
+    /// A storage can't be created for an archetype.
+    InvalidArchetype(&'static str),
+    /// The maximum amount of components was reached.
+    ComponentLimit,
+    /// An [`EntityId`] was despawned.
+    StaleEntity(EntityId),
+    /// An entity isn't stored in the row it was cached at.
+    MovedEntity { entity: EntityId, index: usize },
+}
+
+impl Violation {
+    /// Panic because the precondition of `method` was violated.
+    #[cold]
+    #[inline(never)]
+    #[track_caller]
+    pub(crate) fn fail(self, method: &str) -> ! {
+        let precondition = match &self {
+            Violation::OutOfBounds { what, .. } => format!("`{what}` is in bounds"),
+            Violation::NoRoom { .. } => "there is room for another element".to_string(),
+            Violation::NotAPermutation { .. } => "`order` is a permutation of `0..len`".to_string(),
+            Violation::MissingComponent(_) => "the component is stored in the storage".to_string(),
+            Violation::LayoutMismatch { .. } => "the type has the layout of the values".to_string(),
+            Violation::IncompleteBundle { .. } => {
+                "the bundle stores a component for every column".to_string()
+            }
+            Violation::UnregisteredTag(_) => "the tag is registered".to_string(),
+            Violation::InvalidArchetype(_) => {
+                "the components of the archetype are registered, without duplicates".to_string()
+            }
+            Violation::ComponentLimit => {
+                "the maximum amount of components wasn't reached".to_string()
+            }
+            Violation::StaleEntity(_) => "the entity is alive".to_string(),
+            Violation::MovedEntity { .. } => "the entity is stored in its cached row".to_string(),
+        };
+        let reason = format!("the precondition that {precondition} is violated");
+        match self {
+            Violation::OutOfBounds { what, index, len } => {
+                panics::fail(method, reason, &[(what, &index), ("len", &len)])
+            }
+            Violation::NoRoom { len, capacity } => {
+                panics::fail(method, reason, &[("len", &len), ("capacity", &capacity)])
+            }
+            Violation::NotAPermutation { len } => panics::fail(method, reason, &[("len", &len)]),
+            Violation::MissingComponent(comp_id) => {
+                panics::fail(method, reason, &[("component_id", &comp_id.id())])
+            }
+            Violation::LayoutMismatch {
+                type_name,
+                expected,
+            } => panics::fail(
+                method,
+                reason,
+                &[
+                    ("type", &type_name),
+                    ("expected_size", &expected.size()),
+                    ("expected_align", &expected.align()),
+                ],
+            ),
+            Violation::IncompleteBundle { column } => {
+                panics::fail(method, reason, &[("column", &column)])
+            }
+            Violation::UnregisteredTag(tag) => panics::fail(method, reason, &[("tag", &tag)]),
+            Violation::InvalidArchetype(archetype) => {
+                panics::fail(method, reason, &[("archetype", &archetype)])
+            }
+            Violation::ComponentLimit => panics::fail(method, reason, &[]),
+            Violation::StaleEntity(entity) => panics::fail(
+                method,
+                reason,
+                &[("entity", &EntityLabel(entity) as &dyn Display)],
+            ),
+            Violation::MovedEntity { entity, index } => panics::fail(
+                method,
+                reason,
+                &[("entity", &EntityLabel(entity)), ("index", &index)],
+            ),
+        }
+    }
+}
+
+/// Check a precondition of the unchecked `method` with the `paranoid` feature. Without it, this does nothing.
+#[inline(always)]
+#[track_caller]
+pub(crate) fn check(method: &str, validate: impl FnOnce() -> Result<(), Violation>) {
+    if ENABLED {
+        if let Err(violation) = validate() {
+            violation.fail(method);
+        }
+    }
+}
+
+/// Like [`check`], for the preconditions that are also checked in debug builds without the `paranoid` feature.
+#[inline(always)]
+#[track_caller]
+pub(crate) fn debug_check(method: &str, validate: impl FnOnce() -> Result<(), Violation>) {
+    if ENABLED || cfg!(debug_assertions) {
+        if let Err(violation) = validate() {
+            violation.fail(method);
+        }
+    }
+}
+
+/// Validate that `index < len`. `what` names the index in the panic message.
+#[inline]
+pub(crate) fn index(what: &'static str, index: usize, len: usize) -> Result<(), Violation> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(Violation::OutOfBounds { what, index, len })
+    }
+}
+
+/// Validate that there is room for another element.
+#[inline]
+pub(crate) fn room(len: usize, capacity: usize) -> Result<(), Violation> {
+    if len < capacity {
+        Ok(())
+    } else {
+        Err(Violation::NoRoom { len, capacity })
+    }
+}
+
+/// Validate that `order` is a permutation of `0..len`. The length is always validated, but the elements are only
+/// validated with the `paranoid` feature, since it allocates.
+pub(crate) fn permutation(order: &[usize], len: usize) -> Result<(), Violation> {
+    let violation = Violation::NotAPermutation { len };
+    if order.len() != len {
+        return Err(violation);
+    }
+    if ENABLED {
+        let mut seen = vec![false; len];
+        for i in order {
+            match seen.get_mut(*i) {
+                Some(seen) if !*seen => *seen = true,
+                _ => return Err(violation),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Validate that `T` has the layout of type-erased values with the layout `expected`.
+#[inline]
+pub(crate) fn layout<T>(expected: Layout) -> Result<(), Violation> {
+    if Layout::new::<T>() == expected {
+        Ok(())
+    } else {
+        Err(Violation::LayoutMismatch {
+            type_name: type_name::<T>(),
+            expected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        prelude::*,
+        storage::blob_vec::BlobVec,
+        world::storage::{arch_storage::ArchStorageIndex, storages::ArchStorageId},
+    };
+    use bevy_ptr::OwningPtr;
+    use std::alloc::Layout;
+
+    #[derive(Component)]
+    struct A(#[allow(dead_code)] u32);
+    #[derive(Component)]
+    struct B(#[allow(dead_code)] u64);
+    #[derive(Tag)]
+    struct Red;
+
+    fn world() -> (World, EntityId) {
+        let mut world = World::default();
+        let entity = world.spawn(A(1));
+        (world, entity)
+    }
+
+    fn read_a_component_that_isnt_stored() {
+        let (mut world, entity) = world();
+        let b = world.components.register_component::<B>().unwrap();
+        let sid = world
+            .entities
+            .get_entity_meta(entity)
+            .unwrap()
+            .archetype_storage_id;
+        let storage = world.storages.arch_storages.get_storage(sid).unwrap();
+        unsafe { storage.get_component_unchecked(ArchStorageIndex(0), b) };
+    }
+
+    fn read_a_row_out_of_bounds() {
+        let (mut world, _) = world();
+        let a = world.components.get_component_id::<A>().unwrap();
+        let storage = world
+            .storages
+            .arch_storages
+            .get_storage_mut(ArchStorageId(0))
+            .unwrap();
+        unsafe { storage.get_component_mut_unchecked(ArchStorageIndex(1), a) };
+    }
+
+    fn read_a_column_as_the_wrong_type() {
+        let (world, _) = world();
+        let a = world.components.get_component_id::<A>().unwrap();
+        let storage = world
+            .storages
+            .arch_storages
+            .get_storage(ArchStorageId(0))
+            .unwrap();
+        unsafe { storage.get_column::<B>(a) };
+    }
+
+    fn get_a_storage_that_doesnt_exist() {
+        let (world, _) = world();
+        unsafe {
+            world
+                .storages
+                .arch_storages
+                .get_storage_unchecked(ArchStorageId(1))
+        };
+    }
+
+    fn push_without_room() {
+        let mut bvec = unsafe { BlobVec::new(Layout::new::<u32>(), None, 1) };
+        OwningPtr::make(1u32, |ptr| unsafe { bvec.push_unchecked(ptr) });
+        OwningPtr::make(2u32, |ptr| unsafe { bvec.push_unchecked(ptr) });
+    }
+
+    fn permute_with_a_duplicate() {
+        let mut bvec = unsafe { BlobVec::new(Layout::new::<u32>(), None, 2) };
+        for i in 0..2u32 {
+            OwningPtr::make(i, |ptr| unsafe { bvec.push(ptr) });
+        }
+        unsafe { bvec.permute_unchecked(&[0, 0]) };
+    }
+
+    fn check_an_unregistered_tag() {
+        let (world, entity) = world();
+        let tracker = world.get_tag_tracker(entity).unwrap();
+        unsafe { tracker.is_tagged_unchecked::<Red>() };
+    }
+
+    fn move_a_despawned_entity() {
+        let (mut world, entity) = world();
+        let meta = *world.entities.get_entity_meta(entity).unwrap();
+        world.despawn(entity);
+        world.spawn(A(2));
+        world.entities.set_entity_meta(meta, entity);
+    }
+
+    #[cfg(feature = "paranoid")]
+    mod paranoid {
+        #[test]
+        #[should_panic(
+            expected = "worlds_ecs: ArchStorage::get_component_unchecked failed: the precondition that the \
+                        component is stored in the storage is violated (component_id=1)"
+        )]
+        fn test_missing_component() {
+            super::read_a_component_that_isnt_stored();
+        }
+
+        #[test]
+        #[should_panic(
+            expected = "worlds_ecs: ArchStorage::get_component_mut_unchecked failed: the precondition that \
+                        `index` is in bounds is violated (index=1, len=1)"
+        )]
+        fn test_row_out_of_bounds() {
+            super::read_a_row_out_of_bounds();
+        }
+
+        #[test]
+        #[should_panic(
+            expected = "the precondition that the type has the layout of the values is violated \
+                                   (type=worlds_ecs::utils::paranoid::tests::B, expected_size=4, expected_align=4)"
+        )]
+        fn test_layout_mismatch() {
+            super::read_a_column_as_the_wrong_type();
+        }
+
+        #[test]
+        #[should_panic(expected = "worlds_ecs: ArchStorages::get_storage_unchecked failed")]
+        fn test_storage_out_of_bounds() {
+            super::get_a_storage_that_doesnt_exist();
+        }
+
+        #[test]
+        #[should_panic(
+            expected = "worlds_ecs: BlobVec::push_unchecked failed: the precondition that there is \
+                                   room for another element is violated (len=1, capacity=1)"
+        )]
+        fn test_push_without_room() {
+            super::push_without_room();
+        }
+
+        #[test]
+        #[should_panic(
+            expected = "worlds_ecs: BlobVec::permute_unchecked failed: the precondition that \
+                                   `order` is a permutation of `0..len` is violated (len=2)"
+        )]
+        fn test_permute_with_a_duplicate() {
+            super::permute_with_a_duplicate();
+        }
+
+        #[test]
+        #[should_panic(
+            expected = "worlds_ecs: TagFactory::tag_id_unchecked failed: the precondition that the \
+                                   tag is registered is violated"
+        )]
+        fn test_unregistered_tag() {
+            super::check_an_unregistered_tag();
+        }
+
+        #[test]
+        #[should_panic(
+            expected = "worlds_ecs: EntityFactory::set_entity_meta failed: the precondition that the \
+                                   entity is alive is violated (entity="
+        )]
+        fn test_stale_entity() {
+            super::move_a_despawned_entity();
+        }
+    }
+
+    /// Without the `paranoid` feature, only the preconditions that were always debug-asserted are checked, and
+    /// only in debug builds. The other violations are undefined behavior: run them with
+    /// `cargo miri test -- --ignored paranoid` to see Miri report them.
+    #[cfg(not(feature = "paranoid"))]
+    mod unchecked {
+        #[test]
+        #[ignore = "undefined behavior without the `paranoid` feature, run it under Miri"]
+        fn test_missing_component() {
+            super::read_a_component_that_isnt_stored();
+        }
+
+        #[test]
+        #[cfg_attr(
+            debug_assertions,
+            should_panic(
+                expected = "the precondition that `index` is in bounds is violated (index=1, len=1)"
+            )
+        )]
+        #[cfg_attr(
+            not(debug_assertions),
+            ignore = "undefined behavior without the `paranoid` feature, run it under Miri"
+        )]
+        fn test_row_out_of_bounds() {
+            super::read_a_row_out_of_bounds();
+        }
+
+        #[test]
+        #[ignore = "undefined behavior without the `paranoid` feature, run it under Miri"]
+        fn test_layout_mismatch() {
+            super::read_a_column_as_the_wrong_type();
+        }
+
+        #[test]
+        #[ignore = "undefined behavior without the `paranoid` feature, run it under Miri"]
+        fn test_storage_out_of_bounds() {
+            super::get_a_storage_that_doesnt_exist();
+        }
+
+        #[test]
+        #[cfg_attr(
+            debug_assertions,
+            should_panic(expected = "worlds_ecs: BlobVec::push_unchecked failed")
+        )]
+        #[cfg_attr(
+            not(debug_assertions),
+            ignore = "undefined behavior without the `paranoid` feature, run it under Miri"
+        )]
+        fn test_push_without_room() {
+            super::push_without_room();
+        }
+
+        #[test]
+        #[ignore = "undefined behavior without the `paranoid` feature, run it under Miri"]
+        fn test_permute_with_a_duplicate() {
+            super::permute_with_a_duplicate();
+        }
+
+        #[test]
+        #[ignore = "undefined behavior without the `paranoid` feature, run it under Miri"]
+        fn test_unregistered_tag() {
+            super::check_an_unregistered_tag();
+        }
+
+        #[test]
+        #[ignore = "silently moves the entity that reused the id without the `paranoid` feature"]
+        fn test_stale_entity() {
+            super::move_a_despawned_entity();
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{
+    component::ComponentId,
+    entity::EntityId,
+    world::{archive::EntityState, World},
+};
+use serde_json::{json, Value};
+
+/// The version of `wasm-bindgen` that this crate is built with. Exporting the functions of this module through
+/// it (with `#[wasm_bindgen(wasm_bindgen = worlds_ecs::wasm::wasm_bindgen)]`) keeps the bindings of an app in
+/// sync with the crate.
+pub use wasm_bindgen;
+
+/// The statistics of the world (see [`World::stats`]) and its archetypes (see [`World::archetypes`]), as a JSON
+/// object, for debug tooling on the JS side. The world itself can't cross into JS, so an app exports this through
+/// a function of its own:
+///
+/// ```no_run
+/// use worlds_ecs::{prelude::*, wasm};
+/// use wasm::wasm_bindgen::prelude::wasm_bindgen;
+///
+/// # fn game_world() -> &'static World { unimplemented!() }
+/// #[wasm_bindgen(wasm_bindgen = worlds_ecs::wasm::wasm_bindgen)]
+/// pub fn debug_world_stats() -> String {
+///     wasm::world_stats_json(game_world())
+/// }
+/// ```
+///
+/// The byte counts are `null` unless the allocations are tracked (see [`World::track_allocations`]).
+pub fn world_stats_json(world: &World) -> String {
+    let stats = world.stats();
+    let archetypes: Vec<Value> = world
+        .archetypes()
+        .map(|archetype| {
+            json!({
+                "id": archetype.id().index(),
+                "components": component_names(world, &archetype.component_ids()),
+                "entities": archetype.len(),
+            })
+        })
+        .collect();
+    json!({
+        "entities": stats.entities,
+        "archived": stats.archived,
+        "storages": stats.storages,
+        "spawned": stats.spawned,
+        "despawned": stats.despawned,
+        "component_bytes": stats.component_bytes,
+        "largest_archetype_bytes": stats.largest_archetype.map(|(_, bytes)| bytes),
+        "shared_bytes": stats.shared_bytes,
+        "archetypes": archetypes,
+    })
+    .to_string()
+}
+
+/// An entity as a JSON object, for debug tooling on the JS side (see [`world_stats_json`] for how to export it).
+/// The entity is given by its bits (see [`EntityId::to_bits`]), since JS can't hold an [`EntityId`].
+///
+/// The object has the `state` of the entity (`"alive"`, `"archived"`, `"tombstone"` or `"dead"`), and unless it's
+/// dead, its `tags` and the names of its `components` (an archived entity or a tombstone has no components in the
+/// storages of the world). The
+/// values of the components aren't included, since they don't have to be serializable.
+pub fn dump_entity_json(world: &World, entity: u64) -> String {
+    let entity = EntityId::from_bits(entity);
+    let state = world.entity_state(entity);
+    let mut dump = json!({
+        "id": entity.id(),
+        "generation": entity.generation(),
+        "state": match state {
+            EntityState::Alive => "alive",
+            EntityState::Archived => "archived",
+            EntityState::Tombstone => "tombstone",
+            EntityState::Dead => "dead",
+        },
+    });
+    if state == EntityState::Dead {
+        return dump.to_string();
+    }
+    let archetype = world
+        .archetype_id_of(entity)
+        .and_then(|id| world.archetype(id));
+    let component_ids = archetype.map_or(Vec::new(), |archetype| archetype.component_ids());
+    let tagf = world.storages.tag_storage.tag_factory();
+    // Archived entities and tombstones have no tags.
+    let tags: Vec<&str> = world.get_tag_tracker(entity).map_or(Vec::new(), |tracker| {
+        tracker
+            .tagged_ids()
+            .filter_map(|id| tagf.tag_name(id))
+            .collect()
+    });
+    dump["archetype"] = json!(archetype.map(|archetype| archetype.id().index()));
+    dump["components"] = json!(component_names(world, &component_ids));
+    dump["tags"] = json!(tags);
+    dump.to_string()
+}
+
+fn component_names(world: &World, component_ids: &[ComponentId]) -> Vec<&'static str> {
+    component_ids
+        .iter()
+        .filter_map(|comp_id| {
+            world
+                .components
+                .get_component_info_from_component_id(*comp_id)
+        })
+        .map(|info| info.name())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dump_entity_json, world_stats_json};
+    use crate::prelude::*;
+    use serde_json::{json, Value};
+
+    #[derive(Component)]
+    struct Position;
+    #[derive(Component)]
+    struct Velocity;
+    #[derive(Tag)]
+    struct Selected;
+
+    fn parse(json: String) -> Value {
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_world_stats_json() {
+        let mut world = World::default();
+        world.spawn(Position);
+        world.spawn((Position, Velocity));
+        let despawned = world.spawn(Position);
+        world.despawn(despawned);
+        let stats = parse(world_stats_json(&world));
+        assert_eq!(stats["entities"], 2);
+        assert_eq!(stats["spawned"], 3);
+        assert_eq!(stats["despawned"], 1);
+        assert_eq!(stats["component_bytes"], Value::Null);
+        assert_eq!(stats["archetypes"][0]["entities"], 1);
+        assert_eq!(
+            stats["archetypes"][1]["components"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_dump_entity_json() {
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Selected>();
+        let mut world = World::with_tags(tagf);
+        let entity = world.spawn((Position, Velocity));
+        world.entity_mut(entity).unwrap().tag::<Selected>();
+        let dump = parse(dump_entity_json(&world, entity.to_bits()));
+        assert_eq!(dump["state"], "alive");
+        assert_eq!(dump["archetype"], 0);
+        assert_eq!(dump["tags"], json!([std::any::type_name::<Selected>()]));
+        let components = dump["components"].as_array().unwrap();
+        assert!(components.contains(&json!(std::any::type_name::<Velocity>())));
+
+        world.despawn(entity);
+        let dump = parse(dump_entity_json(&world, entity.to_bits()));
+        assert_eq!(dump["state"], "dead");
+        assert_eq!(dump.get("components"), None);
+    }
+}
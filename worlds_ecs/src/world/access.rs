@@ -0,0 +1,408 @@
+use super::World;
+use crate::prelude::{ArchFilter, ArchQuery, Component, ComponentFactory, ComponentId};
+use std::{collections::HashSet, fmt, panic::Location, sync::Mutex};
+
+/// The maximum amount of distinct accesses that are recorded in one frame, before new ones are only counted (see
+/// [`AccessReport::dropped`]).
+pub const MAX_RECORDED_ACCESSES: usize = 4096;
+
+/// How a component was accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessKind {
+    /// The component was only read.
+    Read,
+    /// The component was accessed mutably.
+    Write,
+}
+
+/// A component that was accessed from a call site, while recording accesses (see [`World::record_access`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Access {
+    /// The component that was accessed.
+    pub component: ComponentId,
+    /// How it was accessed.
+    pub kind: AccessKind,
+    /// Where the [`World`] API that accessed it was called.
+    pub location: &'static Location<'static>,
+}
+
+/// A suspicious pattern of accesses in one frame, found by [`World::access_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessWarning {
+    /// The component was written from two different call sites.
+    WriteWriteOverlap {
+        /// The name of the component.
+        component: &'static str,
+        /// The call site that wrote it first.
+        first: &'static Location<'static>,
+        /// Another call site that wrote it later.
+        second: &'static Location<'static>,
+    },
+    /// The component was read before anything wrote it, and was written later in the frame.
+    ReadBeforeWrite {
+        /// The name of the component.
+        component: &'static str,
+        /// The call site that read it.
+        read: &'static Location<'static>,
+        /// The call site that wrote it first.
+        write: &'static Location<'static>,
+    },
+    /// The component was written, and never read in the frame.
+    WrittenNeverRead {
+        /// The name of the component.
+        component: &'static str,
+        /// The call site that wrote it first.
+        write: &'static Location<'static>,
+    },
+}
+
+impl fmt::Display for AccessWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessWarning::WriteWriteOverlap {
+                component,
+                first,
+                second,
+            } => write!(f, "`{component}` was written at {first} and at {second}"),
+            AccessWarning::ReadBeforeWrite {
+                component,
+                read,
+                write,
+            } => write!(
+                f,
+                "`{component}` was read at {read} before it was written at {write}"
+            ),
+            AccessWarning::WrittenNeverRead { component, write } => {
+                write!(f, "`{component}` was written at {write}, but never read")
+            }
+        }
+    }
+}
+
+/// The accesses of one frame, and the [`AccessWarning`]s they raise. See [`World::access_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessReport {
+    /// The distinct accesses of the frame, in the order they first happened.
+    pub accesses: Vec<Access>,
+    /// How many distinct accesses weren't recorded, because [`MAX_RECORDED_ACCESSES`] were already recorded.
+    pub dropped: usize,
+    /// The suspicious patterns that were found.
+    pub warnings: Vec<AccessWarning>,
+}
+
+/// Records the accesses of a [`World`] during a frame, when enabled (see [`World::record_access`]).
+/// The log is behind a lock, so the APIs that only read the world can record through a shared reference.
+#[derive(Default)]
+pub(crate) struct AccessRecorder {
+    enabled: bool,
+    log: Mutex<AccessLog>,
+}
+
+#[derive(Default)]
+struct AccessLog {
+    accesses: Vec<Access>,
+    seen: HashSet<Access>,
+    dropped: usize,
+}
+
+impl AccessRecorder {
+    /// Record the accesses of the query `Q`, if recording is enabled.
+    #[inline]
+    pub(crate) fn record_query<Q: ArchQuery>(
+        &self,
+        comp_factory: &ComponentFactory,
+        location: &'static Location<'static>,
+    ) {
+        if self.enabled {
+            self.record_accesses(comp_factory, location, Q::for_each_access);
+        }
+    }
+
+    /// Record the accesses of the query `Q` with the filter `F`, if recording is enabled.
+    #[inline]
+    pub(crate) fn record_filtered_query<Q: ArchQuery, F: ArchFilter>(
+        &self,
+        comp_factory: &ComponentFactory,
+        location: &'static Location<'static>,
+    ) {
+        if self.enabled {
+            self.record_accesses(comp_factory, location, |comp_factory, f| {
+                Q::for_each_access(comp_factory, f);
+                F::for_each_access(comp_factory, f);
+            });
+        }
+    }
+
+    #[cold]
+    fn record_accesses(
+        &self,
+        comp_factory: &ComponentFactory,
+        location: &'static Location<'static>,
+        for_each_access: fn(&ComponentFactory, &mut dyn FnMut(ComponentId, AccessKind)),
+    ) {
+        let mut log = self.log.lock().unwrap();
+        for_each_access(comp_factory, &mut |component, kind| {
+            log.push(Access {
+                component,
+                kind,
+                location,
+            })
+        });
+    }
+
+    /// Record an access of the component `C`, if recording is enabled.
+    #[inline]
+    pub(crate) fn record_component<C: Component>(
+        &self,
+        comp_factory: &ComponentFactory,
+        kind: AccessKind,
+        location: &'static Location<'static>,
+    ) {
+        if self.enabled {
+            if let Some(component) = comp_factory.get_component_id::<C>() {
+                self.log.lock().unwrap().push(Access {
+                    component,
+                    kind,
+                    location,
+                });
+            }
+        }
+    }
+}
+
+impl AccessLog {
+    fn push(&mut self, access: Access) {
+        if self.seen.contains(&access) {
+            return;
+        }
+        if self.accesses.len() == MAX_RECORDED_ACCESSES {
+            self.dropped += 1;
+            return;
+        }
+        self.seen.insert(access);
+        self.accesses.push(access);
+    }
+}
+
+impl World {
+    /// Start (or stop) recording which components are accessed by the queries and the component getters of the
+    /// [`World`], and from where they were called. The accesses are analyzed, and cleared, by
+    /// [`World::access_report`], which should be called at the end of each frame.
+    /// Recording is off by default, and costs a single branch when off.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// world.record_access(true);
+    /// world.get_component::<Health>(entity);
+    /// assert_eq!(world.access_report().accesses.len(), 1);
+    /// world.record_access(false);
+    /// world.get_component::<Health>(entity);
+    /// assert!(world.access_report().accesses.is_empty());
+    /// ```
+    pub fn record_access(&mut self, enabled: bool) {
+        self.access.enabled = enabled;
+        if !enabled {
+            *self.access.log.get_mut().unwrap() = AccessLog::default();
+        }
+    }
+
+    /// Analyze the accesses that were recorded since the last report (see [`World::record_access`]), and clear
+    /// them. Reports every component that was written from two different call sites (against the first one that
+    /// wrote it), that was read before it was first written, or that was written and never read.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// world.record_access(true);
+    /// world.get_component_mut::<Health>(entity).unwrap().0 -= 1;
+    /// let report = world.access_report();
+    /// assert!(matches!(
+    ///     report.warnings[..],
+    ///     [AccessWarning::WrittenNeverRead { .. }]
+    /// ));
+    /// // The accesses were cleared by the report.
+    /// assert!(world.access_report().accesses.is_empty());
+    /// ```
+    pub fn access_report(&mut self) -> AccessReport {
+        let log = std::mem::take(self.access.log.get_mut().unwrap());
+        let mut components: Vec<ComponentId> = Vec::new();
+        for access in &log.accesses {
+            if !components.contains(&access.component) {
+                components.push(access.component);
+            }
+        }
+        let mut warnings = Vec::new();
+        for comp_id in components {
+            let name = self
+                .components
+                .get_component_info_from_component_id(comp_id)
+                .map_or("<unknown>", |info| info.name());
+            let accesses: Vec<&Access> = log
+                .accesses
+                .iter()
+                .filter(|access| access.component == comp_id)
+                .collect();
+            let is_write = |access: &Access| access.kind == AccessKind::Write;
+            let Some(first_write_at) = accesses.iter().position(|access| is_write(access)) else {
+                continue;
+            };
+            let first_write = accesses[first_write_at];
+            for write in accesses.iter().filter(|access| is_write(access)) {
+                if write.location != first_write.location {
+                    warnings.push(AccessWarning::WriteWriteOverlap {
+                        component: name,
+                        first: first_write.location,
+                        second: write.location,
+                    });
+                }
+            }
+            match accesses.iter().position(|access| !is_write(access)) {
+                Some(first_read_at) if first_read_at < first_write_at => {
+                    warnings.push(AccessWarning::ReadBeforeWrite {
+                        component: name,
+                        read: accesses[first_read_at].location,
+                        write: first_write.location,
+                    })
+                }
+                Some(_) => {}
+                None => warnings.push(AccessWarning::WrittenNeverRead {
+                    component: name,
+                    write: first_write.location,
+                }),
+            }
+        }
+        AccessReport {
+            accesses: log.accesses,
+            dropped: log.dropped,
+            warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Access, AccessKind, AccessLog, AccessWarning, MAX_RECORDED_ACCESSES};
+    use crate::prelude::*;
+    use std::panic::Location;
+
+    #[derive(Component)]
+    struct Transform;
+    #[derive(Component)]
+    struct Velocity;
+    #[derive(Component)]
+    struct Health(u32);
+
+    type Movement<'a> = (&'a mut Transform, &'a Velocity);
+
+    fn at(line: u32) -> impl Fn(&&'static Location<'static>) -> bool {
+        move |location| location.file() == file!() && location.line() == line
+    }
+
+    #[test]
+    fn test_access_report_of_scripted_frame() {
+        let mut world = World::default();
+        let e = world.spawn((Transform, Velocity, Health(10)));
+        world.record_access(true);
+
+        let read_at = (line!(), world.get_component::<Transform>(e).is_some()).0;
+        let mut written_at = 0;
+        for _ in 0..3 {
+            written_at = (line!(), world.query::<&mut Transform>().count()).0;
+        }
+        let moved_at = (line!(), world.query::<Movement>().count()).0;
+        let health_at = (line!(), world.get_component_mut::<Health>(e).is_some()).0;
+        let _ = world.query_shared::<&Velocity>().count();
+
+        let report = world.access_report();
+        // The same access from the same call site is only recorded once.
+        assert_eq!(report.accesses.len(), 6);
+        assert_eq!(report.dropped, 0);
+        assert_eq!(report.warnings.len(), 3, "{:?}", report.warnings);
+        let transform = std::any::type_name::<Transform>();
+        assert!(report.warnings.iter().any(|warning| matches!(
+            warning,
+            AccessWarning::WriteWriteOverlap { component, first, second }
+                if *component == transform && at(written_at)(first) && at(moved_at)(second)
+        )));
+        assert!(report.warnings.iter().any(|warning| matches!(
+            warning,
+            AccessWarning::ReadBeforeWrite { component, read, write }
+                if *component == transform && at(read_at)(read) && at(written_at)(write)
+        )));
+        assert!(report.warnings.iter().any(|warning| matches!(
+            warning,
+            AccessWarning::WrittenNeverRead { component, write }
+                if *component == std::any::type_name::<Health>() && at(health_at)(write)
+        )));
+        assert!(report.warnings[0].to_string().contains(file!()));
+
+        // The log is cleared by the report.
+        let written_at = (line!(), world.update_component_all::<Health>(|h| h.0 += 1)).0;
+        let read_at = (line!(), world.iter_component::<Health>().count()).0;
+        let report = world.access_report();
+        assert_eq!(report.warnings, vec![]);
+        assert_eq!(report.accesses.len(), 2);
+        assert!(at(written_at)(&report.accesses[0].location));
+        assert_eq!(report.accesses[0].kind, AccessKind::Write);
+        assert!(at(read_at)(&report.accesses[1].location));
+        assert_eq!(report.accesses[1].kind, AccessKind::Read);
+    }
+
+    #[test]
+    fn test_filtered_queries_and_query_states_are_recorded() {
+        let mut world = World::default();
+        world.spawn((Transform, Velocity));
+        world.record_access(true);
+        let _ = world
+            .query_filtered::<&mut Transform, Or<(Has<Velocity>, Has<Health>)>>()
+            .count();
+        let mut state = QueryState::<&Velocity>::new(&world);
+        let _ = state.iter(&mut world).count();
+        let report = world.access_report();
+        // Presence filters don't access components.
+        let kinds: Vec<AccessKind> = report.accesses.iter().map(|access| access.kind).collect();
+        assert_eq!(kinds, [AccessKind::Write, AccessKind::Read]);
+        assert_eq!(report.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_nothing_is_recorded_when_disabled() {
+        let mut world = World::default();
+        let e = world.spawn(Transform);
+        let _ = world.query::<&mut Transform>().count();
+        world.record_access(true);
+        let _ = world.get_component::<Transform>(e);
+        world.record_access(false);
+        let _ = world.get_component_mut::<Transform>(e);
+        world.record_access(true);
+        assert_eq!(world.access_report(), AccessReport::default());
+    }
+
+    #[test]
+    fn test_access_log_is_bounded() {
+        let mut log = AccessLog::default();
+        let location = Location::caller();
+        for i in 0..MAX_RECORDED_ACCESSES + 10 {
+            for _ in 0..2 {
+                log.push(Access {
+                    component: ComponentId::new(i),
+                    kind: AccessKind::Read,
+                    location,
+                });
+            }
+        }
+        assert_eq!(log.accesses.len(), MAX_RECORDED_ACCESSES);
+        assert_eq!(log.dropped, 20);
+    }
+}
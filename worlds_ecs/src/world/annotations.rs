@@ -0,0 +1,312 @@
+use super::World;
+use crate::{entity::EntityId, utils::panics};
+use std::{borrow::Cow, ops::Range};
+
+/// A debug annotation of an entity, see [`World::annotate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Annotation<'a> {
+    /// The annotated entity.
+    pub entity: EntityId,
+    /// The frame the annotation was made in (see [`World::end_annotation_frame`]).
+    pub frame: u64,
+    /// The message of the annotation.
+    pub message: &'a str,
+}
+
+/// Where the message of an annotation is stored.
+#[derive(Clone)]
+enum Message {
+    Static(&'static str),
+    /// A range of [`Annotations::text`].
+    Copied(Range<usize>),
+}
+
+/// The annotations of the current frame, when enabled (see [`World::record_annotations`]). The messages that
+/// aren't `'static` are copied into a single buffer, so annotating doesn't allocate per entity, and the memory of
+/// a frame is reused by the next one.
+#[derive(Default)]
+pub(crate) struct Annotations {
+    enabled: bool,
+    frame: u64,
+    log: Vec<(EntityId, Message)>,
+    text: String,
+}
+
+impl Annotations {
+    fn message(&self, message: &Message) -> &str {
+        match message {
+            Message::Static(message) => message,
+            Message::Copied(range) => &self.text[range.clone()],
+        }
+    }
+
+    /// The capacity of the log and of the buffer of the messages.
+    #[cfg(test)]
+    fn capacity(&self) -> (usize, usize) {
+        (self.log.capacity(), self.text.capacity())
+    }
+}
+
+impl World {
+    /// Start (or stop) recording debug annotations of entities (see [`World::annotate`]). Recording is off by
+    /// default, and annotating costs a single branch when off.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(());
+    /// world.annotate(entity, "ignored");
+    /// assert_eq!(world.iter_annotations().count(), 0);
+    /// world.record_annotations(true);
+    /// world.annotate(entity, "recorded");
+    /// assert_eq!(world.iter_annotations().count(), 1);
+    /// ```
+    pub fn record_annotations(&mut self, enabled: bool) {
+        self.annotations.enabled = enabled;
+        if !enabled {
+            self.annotations.log = Vec::new();
+            self.annotations.text = String::new();
+        }
+    }
+
+    /// Attach an ephemeral debug annotation to an entity, like "skipped: culled" or "took damage 14 from #381", for
+    /// frame capture tooling. Annotations don't affect the components of the entity. They accumulate during the
+    /// frame, and are cleared by [`World::end_annotation_frame`]. Does nothing unless annotations are recorded (see
+    /// [`World::record_annotations`]).
+    ///
+    /// `'static` messages are stored as they are, other messages are copied into a buffer that is shared by all the
+    /// annotations of the frame, and reused by the next frames.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// world.record_annotations(true);
+    /// let entity = world.spawn(());
+    /// world.annotate(entity, "skipped: culled");
+    /// world.annotate(entity, format!("took damage {}", 14));
+    /// let messages: Vec<&str> = world.annotations_for(entity).collect();
+    /// assert_eq!(messages, vec!["skipped: culled", "took damage 14"]);
+    /// ```
+    ///
+    /// # Panics
+    /// If annotations are recorded, and the entity isn't alive.
+    #[inline]
+    #[track_caller]
+    pub fn annotate(&mut self, entity: EntityId, message: impl Into<Cow<'static, str>>) {
+        if self.annotations.enabled {
+            self.record_annotation(entity, message.into());
+        }
+    }
+
+    #[cold]
+    #[track_caller]
+    fn record_annotation(&mut self, entity: EntityId, message: Cow<'static, str>) {
+        if self.entities.get_entity_meta(entity).is_none() {
+            panics::fail_entity("annotate", "the entity isn't alive", entity);
+        }
+        let annotations = &mut self.annotations;
+        let message = match message {
+            Cow::Borrowed(message) => Message::Static(message),
+            Cow::Owned(message) => {
+                let start = annotations.text.len();
+                annotations.text.push_str(&message);
+                Message::Copied(start..annotations.text.len())
+            }
+        };
+        annotations.log.push((entity, message));
+    }
+
+    /// The messages of the annotations of an entity in the current frame, in the order they were made.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// world.record_annotations(true);
+    /// let (a, b) = (world.spawn(()), world.spawn(()));
+    /// world.annotate(a, "selected");
+    /// assert_eq!(world.annotations_for(a).collect::<Vec<_>>(), vec!["selected"]);
+    /// assert_eq!(world.annotations_for(b).count(), 0);
+    /// ```
+    pub fn annotations_for(&self, entity: EntityId) -> impl Iterator<Item = &str> {
+        let annotations = &self.annotations;
+        annotations
+            .log
+            .iter()
+            .filter(move |(annotated, _)| *annotated == entity)
+            .map(|(_, message)| annotations.message(message))
+    }
+
+    /// The annotations of the current frame, in the order they were made.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// world.record_annotations(true);
+    /// let entity = world.spawn(());
+    /// world.annotate(entity, "spawned");
+    /// let annotation = world.iter_annotations().next().unwrap();
+    /// assert_eq!(
+    ///     annotation,
+    ///     Annotation {
+    ///         entity,
+    ///         frame: 0,
+    ///         message: "spawned"
+    ///     }
+    /// );
+    /// ```
+    pub fn iter_annotations(&self) -> impl Iterator<Item = Annotation<'_>> {
+        let annotations = &self.annotations;
+        annotations.log.iter().map(|(entity, message)| Annotation {
+            entity: *entity,
+            frame: annotations.frame,
+            message: annotations.message(message),
+        })
+    }
+
+    /// The current annotation frame: how many times [`World::end_annotation_frame`] was called.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// assert_eq!(world.annotation_frame(), 0);
+    /// world.end_annotation_frame();
+    /// assert_eq!(world.annotation_frame(), 1);
+    /// ```
+    pub fn annotation_frame(&self) -> u64 {
+        self.annotations.frame
+    }
+
+    /// Clear the annotations of the frame (see [`World::annotate`]), and start the next one. This should be called
+    /// at the end of each frame, after the frame was captured. The memory of the annotations is kept for the next
+    /// frame.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// world.record_annotations(true);
+    /// let entity = world.spawn(());
+    /// world.annotate(entity, "spawned");
+    /// world.end_annotation_frame();
+    /// assert_eq!(world.iter_annotations().count(), 0);
+    /// ```
+    pub fn end_annotation_frame(&mut self) {
+        let annotations = &mut self.annotations;
+        annotations.frame += 1;
+        annotations.log.clear();
+        annotations.text.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Annotation;
+    use crate::prelude::*;
+
+    #[derive(Component)]
+    struct Unit;
+
+    #[test]
+    fn test_annotations() {
+        let mut world = World::default();
+        world.record_annotations(true);
+        let (a, b) = (world.spawn(Unit), world.spawn(Unit));
+        world.annotate(a, "skipped: culled");
+        let damage = format!("took damage {} from #{}", 14, a.id());
+        world.annotate(b, damage.clone());
+        world.annotate(a, String::from("moved"));
+        assert_eq!(
+            world.annotations_for(a).collect::<Vec<_>>(),
+            ["skipped: culled", "moved"]
+        );
+        assert_eq!(
+            world.iter_annotations().collect::<Vec<_>>(),
+            [
+                Annotation {
+                    entity: a,
+                    frame: 0,
+                    message: "skipped: culled"
+                },
+                Annotation {
+                    entity: b,
+                    frame: 0,
+                    message: &damage
+                },
+                Annotation {
+                    entity: a,
+                    frame: 0,
+                    message: "moved"
+                },
+            ]
+        );
+
+        world.end_annotation_frame();
+        assert_eq!(world.annotation_frame(), 1);
+        assert_eq!(world.iter_annotations().count(), 0);
+        world.annotate(b, "respawned");
+        assert_eq!(
+            world
+                .iter_annotations()
+                .next()
+                .map(|annotation| annotation.frame),
+            Some(1)
+        );
+        assert_eq!(world.annotations_for(a).count(), 0);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut world = World::default();
+        let entity = world.spawn(Unit);
+        world.annotate(entity, "ignored");
+        world.despawn(entity);
+        // Nothing is checked while annotations aren't recorded.
+        world.annotate(entity, "ignored");
+        assert_eq!(world.iter_annotations().count(), 0);
+        assert_eq!(world.annotations.capacity(), (0, 0));
+
+        world.record_annotations(true);
+        let entity = world.spawn(Unit);
+        world.annotate(entity, "recorded".to_string());
+        world.record_annotations(false);
+        world.annotate(entity, "ignored");
+        assert_eq!(world.iter_annotations().count(), 0);
+        assert_eq!(world.annotations.capacity(), (0, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: annotate failed: the entity isn't alive (entity=")]
+    fn test_annotate_despawned() {
+        let mut world = World::default();
+        world.record_annotations(true);
+        let entity = world.spawn(Unit);
+        world.despawn(entity);
+        world.annotate(entity, "too late");
+    }
+
+    #[test]
+    fn test_memory_is_reused() {
+        let mut world = World::default();
+        world.record_annotations(true);
+        let entities: Vec<EntityId> = (0..50).map(|_| world.spawn(Unit)).collect();
+        let mut capacities = Vec::new();
+        for frame in 0..20 {
+            for (i, entity) in entities.iter().enumerate() {
+                world.annotate(*entity, format!("frame {frame}: {i}"));
+                world.annotate(*entity, "visited");
+            }
+            assert_eq!(world.iter_annotations().count(), 100);
+            world.end_annotation_frame();
+            capacities.push(world.annotations.capacity());
+        }
+        // The frames are the same size, so after the first few frames the buffers stop growing.
+        assert!(capacities[3..]
+            .iter()
+            .all(|capacity| *capacity == capacities[3]));
+    }
+}
@@ -0,0 +1,288 @@
+use super::{
+    storage::{storages::ArchStorageId, ArchEntityStorage},
+    World,
+};
+use crate::{archetype::key::PrimeArchKey, component::ComponentId, entity::EntityId};
+use std::fmt;
+
+pub use super::storage::storages::ArchetypeId;
+
+/// A read-only view of an archetype of the [`World`], and of the storage that its entities are in (see
+/// [`World::archetypes`]).
+#[derive(Clone, Copy)]
+pub struct ArchetypeView<'w> {
+    id: ArchetypeId,
+    storage_id: ArchStorageId,
+    storage: &'w ArchEntityStorage,
+}
+
+impl<'w> ArchetypeView<'w> {
+    /// The id of the archetype, which stays the same for as long as the world lives.
+    pub fn id(&self) -> ArchetypeId {
+        self.id
+    }
+
+    /// The [`ArchStorageId`] of the storage of the archetype. Unlike [`Self::id`], it only locates the storage for
+    /// now, so it shouldn't be kept across frames.
+    pub fn storage_id(&self) -> ArchStorageId {
+        self.storage_id
+    }
+
+    /// The [`PrimeArchKey`] of the archetype.
+    pub fn prime_key(&self) -> PrimeArchKey {
+        self.storage.prime_key()
+    }
+
+    /// The [`ComponentId`]s of the components of the archetype, in ascending order.
+    pub fn component_ids(&self) -> Vec<ComponentId> {
+        let mut component_ids: Vec<ComponentId> = self.storage.component_ids().collect();
+        component_ids.sort_unstable();
+        component_ids
+    }
+
+    /// The entities of the archetype, in the order of their rows.
+    pub fn entities(&self) -> &'w [EntityId] {
+        self.storage.entities()
+    }
+
+    /// The amount of entities of the archetype.
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// Returns `true` if no entity has the archetype (its storage is empty).
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+}
+
+impl fmt::Debug for ArchetypeView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArchetypeView")
+            .field("id", &self.id)
+            .field("storage_id", &self.storage_id)
+            .field("components", &self.component_ids())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl World {
+    /// Iterate over the archetypes that are stored in the [`World`] (including the ones without entities), in
+    /// ascending order of their [`ArchetypeId`]s, which is the order they were first stored in.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh;
+    /// #[derive(Component)]
+    /// struct Skin;
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Mesh);
+    /// world.spawn((Mesh, Skin));
+    /// world.spawn((Mesh, Skin));
+    /// let lens: Vec<usize> = world.archetypes().map(|archetype| archetype.len()).collect();
+    /// assert_eq!(lens, vec![1, 2]);
+    /// ```
+    pub fn archetypes(&self) -> impl Iterator<Item = ArchetypeView<'_>> + '_ {
+        let arch_storages = &self.storages.arch_storages;
+        arch_storages
+            .archetype_ids()
+            .filter_map(|id| self.archetype(id))
+    }
+
+    /// The amount of archetypes that are stored in the [`World`] (including the ones without entities), like
+    /// counting [`World::archetypes`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh;
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Mesh);
+    /// assert_eq!(world.archetype_count(), 1);
+    /// // The archetype stays stored after its last entity is despawned.
+    /// world.despawn(entity);
+    /// assert_eq!(world.archetype_count(), 1);
+    /// ```
+    pub fn archetype_count(&self) -> usize {
+        self.storages.arch_storages.archetype_count()
+    }
+
+    /// A view of the archetype with this [`ArchetypeId`], or `None` if it isn't stored (or if the id is from
+    /// another world).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh;
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Mesh);
+    /// let id = world.archetype_id_of(entity).unwrap();
+    /// let archetype = world.archetype(id).unwrap();
+    /// assert_eq!(archetype.entities(), &[entity]);
+    /// assert_eq!(archetype.component_ids().len(), 1);
+    /// ```
+    pub fn archetype(&self, id: ArchetypeId) -> Option<ArchetypeView<'_>> {
+        let arch_storages = &self.storages.arch_storages;
+        let storage_id = arch_storages.storage_for_archetype(id)?;
+        Some(ArchetypeView {
+            id,
+            storage_id,
+            storage: arch_storages.get_storage(storage_id)?,
+        })
+    }
+
+    /// The [`ArchetypeId`] of the archetype of an entity, or `None` if the entity isn't alive, or is archived (see
+    /// [`World::archive`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh;
+    /// #[derive(Component)]
+    /// struct Skin;
+    ///
+    /// let mut world = World::default();
+    /// let (a, b) = (world.spawn(Mesh), world.spawn(Mesh));
+    /// let c = world.spawn((Mesh, Skin));
+    /// assert_eq!(world.archetype_id_of(a), world.archetype_id_of(b));
+    /// assert_ne!(world.archetype_id_of(a), world.archetype_id_of(c));
+    /// world.despawn(a);
+    /// assert_eq!(world.archetype_id_of(a), None);
+    /// ```
+    pub fn archetype_id_of(&self, entity: EntityId) -> Option<ArchetypeId> {
+        let entity_meta = self.entities.get_entity_meta(entity)?;
+        self.storages
+            .arch_storages
+            .archetype_of_storage(entity_meta.archetype_storage_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArchetypeId;
+    use crate::{
+        archetype::{key::PrimeArchKey, Archetype},
+        prelude::*,
+    };
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Mesh(u32);
+    #[derive(Component, Debug, PartialEq)]
+    struct Skin(u32);
+    #[derive(Component, Debug, PartialEq)]
+    struct Light;
+
+    #[test]
+    fn test_ids_are_assigned_in_order() {
+        let mut world = World::default();
+        let mesh = world.spawn(Mesh(0));
+        let skinned = world.spawn((Mesh(1), Skin(1)));
+        world.spawn(Mesh(2));
+        let ids: Vec<ArchetypeId> = world.archetypes().map(|view| view.id()).collect();
+        assert_eq!(ids, [ArchetypeId(0), ArchetypeId(1)]);
+        assert_eq!(world.archetype_id_of(mesh), Some(ArchetypeId(0)));
+        assert_eq!(world.archetype_id_of(skinned), Some(ArchetypeId(1)));
+
+        let view = world.archetype(ArchetypeId(1)).unwrap();
+        assert_eq!(view.len(), 1);
+        assert_eq!(view.entities(), [skinned]);
+        assert_eq!(
+            view.prime_key(),
+            <(Mesh, Skin)>::prime_key(&world.components).unwrap()
+        );
+        assert_eq!(view.component_ids().len(), 2);
+        assert!(world.archetype(ArchetypeId(2)).is_none());
+
+        world.despawn(mesh);
+        assert_eq!(world.archetype_id_of(mesh), None);
+    }
+
+    #[test]
+    fn test_ids_are_stable_across_storage_moves() {
+        let mut world = World::default();
+        world.spawn(Mesh(0));
+        world.spawn((Mesh(1), Skin(1)));
+        world.spawn(Light);
+        let before: Vec<(ArchetypeId, PrimeArchKey)> = world
+            .archetypes()
+            .map(|view| (view.id(), view.prime_key()))
+            .collect();
+        let arch_storages = &mut world.storages.arch_storages;
+        let (first, last) = (
+            arch_storages.storage_for_archetype(ArchetypeId(0)).unwrap(),
+            arch_storages.storage_for_archetype(ArchetypeId(2)).unwrap(),
+        );
+        // A compaction of the storages moves them to other storage ids, but not to other archetype ids.
+        arch_storages.swap_entries(first, last);
+        assert_eq!(
+            arch_storages.storage_for_archetype(ArchetypeId(0)),
+            Some(last)
+        );
+        assert_eq!(
+            arch_storages.archetype_of_storage(first),
+            Some(ArchetypeId(2))
+        );
+        let after: Vec<(ArchetypeId, PrimeArchKey)> = world
+            .archetypes()
+            .map(|view| (view.id(), view.prime_key()))
+            .collect();
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn test_ids_are_local_to_their_world() {
+        let mut first = World::default();
+        first.spawn(Mesh(0));
+        let light = first.spawn(Light);
+        let mut second = World::default();
+        let second_light = second.spawn(Light);
+        second.spawn(Mesh(0));
+        // The same archetype gets a different id in each world.
+        assert_eq!(first.archetype_id_of(light), Some(ArchetypeId(1)));
+        assert_eq!(second.archetype_id_of(second_light), Some(ArchetypeId(0)));
+    }
+
+    #[test]
+    fn test_lookups_after_churn() {
+        let mut world = World::default();
+        let mut entities = Vec::new();
+        for i in 0..40 {
+            let entity = match i % 3 {
+                0 => world.spawn(Mesh(i)),
+                1 => world.spawn((Mesh(i), Skin(i))),
+                _ => world.spawn((Skin(i), Light)),
+            };
+            entities.push(entity);
+            if i % 4 == 0 {
+                world.despawn(entities.swap_remove(i as usize / 2));
+            }
+        }
+        for entity in &entities[..10] {
+            world.remove_component::<Skin>(*entity);
+        }
+        for entity in &entities {
+            let id = world.archetype_id_of(*entity).unwrap();
+            let view = world.archetype(id).unwrap();
+            assert!(view.entities().contains(entity));
+            assert_eq!(
+                world.storages.arch_storages.storage_for_archetype(id),
+                Some(view.storage_id())
+            );
+        }
+        let ids: Vec<ArchetypeId> = world.archetypes().map(|view| view.id()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            world.archetypes().map(|view| view.len()).sum::<usize>(),
+            entities.len()
+        );
+    }
+}
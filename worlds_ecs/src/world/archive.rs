@@ -0,0 +1,810 @@
+use super::{
+    data::Data,
+    pin::StoragePinned,
+    quota::SpawnError,
+    storage::{arch_storage::ArchStorageIndex, storages::ArchStorageId, ArchEntityStorage},
+    World,
+};
+use crate::{
+    bundle::Bundle,
+    component::{ComponentFactory, ComponentId},
+    entity::{EntityId, EntityMeta},
+    prelude::{ArchFilter, FetchContext, FilterResult, StorageFilterResult},
+};
+use bevy_ptr::OwningPtr;
+use std::{
+    alloc::{self, Layout},
+    collections::HashMap,
+    fmt,
+    ptr::NonNull,
+};
+
+/// A [`Data`] that can be serialized into bytes and restored from them, so it can be archived
+/// (see [`World::archive`]). Register it with
+/// [`ComponentFactory::register_archivable_component`].
+pub trait Archivable: Data + Sized {
+    /// Append the bytes of this value to `bytes`.
+    fn archive(&self, bytes: &mut Vec<u8>);
+
+    /// Restore a value from the bytes that [`Archivable::archive`] wrote, or return `None` if they are malformed.
+    fn restore(bytes: &[u8]) -> Option<Self>;
+}
+
+/// The state of an [`EntityId`] in a [`World`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityState {
+    /// The entity is in the world, and its components can be accessed and queried.
+    Alive,
+    /// The entity was archived (see [`World::archive`]): its id is reserved, but it has no components until it's
+    /// restored.
+    Archived,
+    /// The entity is a tombstone (see [`World::despawn_retaining`]): its id is reserved, and only its retained
+    /// components can be accessed, until it's destroyed.
+    Tombstone,
+    /// The entity was despawned (or never existed).
+    Dead,
+}
+
+/// An error when archiving or restoring an entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveError {
+    /// The entity was despawned.
+    Despawned(EntityId),
+    /// The entity can't be archived, because it's already archived.
+    AlreadyArchived(EntityId),
+    /// The entity can't be restored, because it isn't archived.
+    NotArchived(EntityId),
+    /// The entity has a component that wasn't registered as archivable (see
+    /// [`ComponentFactory::register_archivable_component`]).
+    NotArchivable {
+        /// The entity.
+        entity: EntityId,
+        /// The name of the component.
+        component: &'static str,
+    },
+    /// A component of the entity couldn't be restored from its bytes. The entity stays archived.
+    Malformed {
+        /// The entity.
+        entity: EntityId,
+        /// The name of the component.
+        component: &'static str,
+    },
+    /// The storage that the entity would be moved out of (or into) is pinned (see [`World::pin_storage`]).
+    Pinned(StoragePinned),
+    /// Restoring the entity would exceed a quota (see [`World::set_entity_limit`]). Always a
+    /// [`SpawnError::QuotaExceeded`]. The entity stays archived.
+    Quota(SpawnError),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::Despawned(entity) => write!(f, "{entity:?} was despawned"),
+            ArchiveError::AlreadyArchived(entity) => write!(f, "{entity:?} is already archived"),
+            ArchiveError::NotArchived(entity) => write!(f, "{entity:?} isn't archived"),
+            ArchiveError::NotArchivable { entity, component } => write!(
+                f,
+                "{entity:?} can't be archived: `{component}` isn't registered as an archivable component"
+            ),
+            ArchiveError::Malformed { entity, component } => {
+                write!(f, "{entity:?} can't be restored: `{component}` is malformed")
+            }
+            ArchiveError::Pinned(error) => error.fmt(f),
+            ArchiveError::Quota(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+/// The components of an archived entity, serialized back to back.
+#[derive(Clone)]
+struct ArchivedEntity {
+    /// The storage that the entity is restored to.
+    storage: ArchStorageId,
+    /// Each component, with the offset in [`Self::bytes`] where its bytes end.
+    components: Box<[(ComponentId, usize)]>,
+    bytes: Box<[u8]>,
+}
+
+impl ArchivedEntity {
+    /// Serialize the components of the entity at `index`.
+    ///
+    /// # Panics
+    /// If some of the components aren't archivable (see [`ColdStore::check_archivable`]), or if the index is out of
+    /// bounds.
+    fn archive(
+        storage: &ArchEntityStorage,
+        sid: ArchStorageId,
+        index: ArchStorageIndex,
+        comp_factory: &ComponentFactory,
+    ) -> Self {
+        let mut bytes = Vec::new();
+        let components = storage
+            .component_ids()
+            .map(|comp_id| {
+                let (archive_fn, _) = comp_factory
+                    .get_component_info_from_component_id(comp_id)
+                    .and_then(|info| info.archive_fns())
+                    .expect("The components of an archived entity must be archivable");
+                let component = storage.get_component(index, comp_id).unwrap();
+                // SAFETY: The function archives the component with `comp_id`.
+                unsafe { archive_fn(component, &mut bytes) };
+                (comp_id, bytes.len())
+            })
+            .collect();
+        ArchivedEntity {
+            storage: sid,
+            components,
+            bytes: bytes.into_boxed_slice(),
+        }
+    }
+
+    /// Deserialize the components, or return the name of the first one that's malformed.
+    fn restore(&self, comp_factory: &ComponentFactory) -> Result<RestoredBundle, &'static str> {
+        let mut restored = RestoredBundle(Vec::with_capacity(self.components.len()));
+        let mut start = 0;
+        for &(comp_id, end) in self.components.iter() {
+            let info = comp_factory
+                .get_component_info_from_component_id(comp_id)
+                .unwrap();
+            let (_, restore_fn) = info.archive_fns().unwrap();
+            let layout = info.layout();
+            let ptr = match layout.size() {
+                0 => NonNull::new(layout.align() as *mut u8).unwrap(),
+                // SAFETY: The layout isn't zero-sized.
+                _ => NonNull::new(unsafe { alloc::alloc(layout) })
+                    .unwrap_or_else(|| alloc::handle_alloc_error(layout)),
+            };
+            let mut value = ErasedValue {
+                comp_id,
+                ptr,
+                layout,
+                drop_fn: info.drop_fn(),
+                is_initialized: false,
+            };
+            let is_restored = restore_fn(&self.bytes[start..end], &mut |data| {
+                // SAFETY: The restored data has the layout of the component, and it's moved into the allocation.
+                unsafe {
+                    std::ptr::copy_nonoverlapping(data.as_ptr(), ptr.as_ptr(), layout.size())
+                };
+            });
+            if !is_restored {
+                return Err(info.name());
+            }
+            value.is_initialized = true;
+            restored.0.push(value);
+            start = end;
+        }
+        Ok(restored)
+    }
+}
+
+/// A component that was restored into its own allocation.
+struct ErasedValue {
+    comp_id: ComponentId,
+    ptr: NonNull<u8>,
+    layout: Layout,
+    drop_fn: Option<unsafe fn(OwningPtr<'_>)>,
+    /// Whether the allocation holds the component: it was restored, and wasn't moved out.
+    is_initialized: bool,
+}
+
+impl Drop for ErasedValue {
+    fn drop(&mut self) {
+        if let (true, Some(drop_fn)) = (self.is_initialized, self.drop_fn) {
+            // SAFETY: The allocation holds the component.
+            unsafe { drop_fn(OwningPtr::new(self.ptr)) };
+        }
+        if self.layout.size() != 0 {
+            // SAFETY: The allocation was made with this layout.
+            unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+        }
+    }
+}
+
+/// The restored components of an archived entity, which are moved into its storage.
+struct RestoredBundle(Vec<ErasedValue>);
+
+impl Bundle for RestoredBundle {
+    fn raw_components_scope(
+        mut self,
+        _comp_factory: &ComponentFactory,
+        f: &mut impl FnMut(ComponentId, OwningPtr<'_>),
+    ) {
+        for value in &mut self.0 {
+            // The component is moved out, so it isn't dropped with the allocation.
+            value.is_initialized = false;
+            // SAFETY: The allocation holds the component.
+            f(value.comp_id, unsafe { OwningPtr::new(value.ptr) });
+        }
+    }
+}
+
+/// Where the archived entities of a [`World`] are kept (see [`World::archive`]), out of its storages.
+#[derive(Default, Clone)]
+pub(crate) struct ColdStore {
+    records: HashMap<EntityId, ArchivedEntity>,
+}
+
+impl ColdStore {
+    /// The archived entities.
+    pub(crate) fn entities(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.records.keys().copied()
+    }
+
+    /// How many entities are archived.
+    pub(crate) fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Forget the record of a despawned entity.
+    pub(crate) fn remove(&mut self, entity: EntityId) {
+        self.records.remove(&entity);
+    }
+
+    /// Returns an error if the entities of the storage can't be archived, because some of its components aren't
+    /// archivable.
+    fn check_archivable(
+        storage: &ArchEntityStorage,
+        entity: EntityId,
+        comp_factory: &ComponentFactory,
+    ) -> Result<(), ArchiveError> {
+        storage.component_ids().try_for_each(|comp_id| {
+            let info = comp_factory
+                .get_component_info_from_component_id(comp_id)
+                .unwrap();
+            match info.archive_fns() {
+                Some(_) => Ok(()),
+                None => Err(ArchiveError::NotArchivable {
+                    entity,
+                    component: info.name(),
+                }),
+            }
+        })
+    }
+}
+
+impl World {
+    /// Move an entity out of its storage, into a cold store of serialized components, so it doesn't take part in
+    /// queries (or slow them down) until it's restored with [`World::restore`]. Its [`EntityId`] stays reserved,
+    /// so ids of it that are held elsewhere stay valid (see [`World::entity_state`]). Its tags and userdata are
+    /// kept, and its components can't be accessed until it's restored.
+    ///
+    /// Every component of the entity must be registered as archivable, with
+    /// [`ComponentFactory::register_archivable_component`] or [`ComponentFactory::register_pod_component`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Position(i32, i32);
+    ///
+    /// let mut world = World::default();
+    /// // SAFETY: `Position` has no padding.
+    /// unsafe { world.register_pod_component::<Position>() };
+    /// let entity = world.spawn(Position(1, 2));
+    /// world.archive(entity).unwrap();
+    /// assert!(world.get_component::<Position>(entity).is_none());
+    /// assert_eq!(world.query::<&Position>().count(), 0);
+    /// world.restore(entity).unwrap();
+    /// assert_eq!(world.get_component::<Position>(entity).unwrap().0, 1);
+    /// ```
+    pub fn archive(&mut self, entity: EntityId) -> Result<(), ArchiveError> {
+        let entity_meta = *self
+            .entities
+            .get_entity_meta(entity)
+            .ok_or(ArchiveError::Despawned(entity))?;
+        if entity_meta.is_archived() {
+            return Err(ArchiveError::AlreadyArchived(entity));
+        }
+        // A tombstone was already despawned from the gameplay (see `World::despawn_retaining`).
+        if entity_meta.is_tombstone() {
+            return Err(ArchiveError::Despawned(entity));
+        }
+        let sid = entity_meta.archetype_storage_id;
+        self.check_pin_for_remove(sid)
+            .map_err(ArchiveError::Pinned)?;
+        let storage = self.storages.arch_storages.get_storage(sid).unwrap();
+        ColdStore::check_archivable(storage, entity, &self.components)?;
+        let record = ArchivedEntity::archive(
+            storage,
+            sid,
+            entity_meta.archetype_storage_index,
+            &self.components,
+        );
+        self.detach_from_storage(entity_meta);
+        self.entities.set_entity_meta(EntityMeta::ARCHIVED, entity);
+        self.ordered.removed(entity);
+        self.archive.records.insert(entity, record);
+        Ok(())
+    }
+
+    /// Archive every entity that passes the filter `F` (see [`World::archive`]), and return how many entities were
+    /// archived. If some of them can't be archived, none are.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Position(i32, i32);
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Asleep;
+    ///
+    /// let mut world = World::default();
+    /// // SAFETY: `Position` has no padding, and `Asleep` has no bytes.
+    /// unsafe { world.register_pod_component::<Position>() };
+    /// unsafe { world.register_pod_component::<Asleep>() };
+    /// world.spawn(Position(0, 0));
+    /// for i in 0..3 {
+    ///     world.spawn((Position(i, i), Asleep));
+    /// }
+    /// assert_eq!(world.archive_matching::<With<Asleep>>(), Ok(3));
+    /// assert_eq!(world.archived_count(), 3);
+    /// ```
+    pub fn archive_matching<F: ArchFilter>(&mut self) -> Result<usize, ArchiveError> {
+        let mut entities = Vec::new();
+        let mut sid = ArchStorageId(0);
+        while let Some(storage) = self.storages.arch_storages.get_storage(sid) {
+            sid = ArchStorageId(sid.0 + 1);
+            let first = entities.len();
+            match F::filter_storage(storage, &self.components) {
+                StorageFilterResult::NoneMatch => {}
+                StorageFilterResult::AllMatch => entities.extend_from_slice(storage.entities()),
+                StorageFilterResult::PerEntity => {
+                    let ctx = FetchContext::new(self.entity_tags())
+                        .for_filter::<F>(storage, &self.components);
+                    entities.extend(
+                        storage
+                            .iter_indices()
+                            // SAFETY: The index came from the storage itself.
+                            .filter(|index| unsafe {
+                                F::filter(storage, *index, &self.components, ctx).collapse()
+                            })
+                            .map(|index| storage.entities()[index.0]),
+                    )
+                }
+            }
+            if let Some(entity) = entities.get(first) {
+                ColdStore::check_archivable(storage, *entity, &self.components)?;
+                self.check_pin_for_remove(ArchStorageId(sid.0 - 1))
+                    .map_err(ArchiveError::Pinned)?;
+            }
+        }
+        for entity in &entities {
+            self.archive(*entity)?;
+        }
+        Ok(entities.len())
+    }
+
+    /// Move an archived entity (see [`World::archive`]) back into the storage it was archived from, with the same
+    /// [`EntityId`]. If one of its components can't be restored, the entity stays archived.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Position(i32, i32);
+    ///
+    /// let mut world = World::default();
+    /// // SAFETY: `Position` has no padding.
+    /// unsafe { world.register_pod_component::<Position>() };
+    /// let entity = world.spawn(Position(1, 2));
+    /// assert_eq!(world.restore(entity), Err(ArchiveError::NotArchived(entity)));
+    /// world.archive(entity).unwrap();
+    /// assert_eq!(world.restore(entity), Ok(()));
+    /// assert!(world.contains_component::<Position>(entity));
+    /// ```
+    pub fn restore(&mut self, entity: EntityId) -> Result<(), ArchiveError> {
+        let entity_meta = self
+            .entities
+            .get_entity_meta(entity)
+            .ok_or(ArchiveError::Despawned(entity))?;
+        if !entity_meta.is_archived() {
+            return Err(ArchiveError::NotArchived(entity));
+        }
+        let record = &self.archive.records[&entity];
+        self.check_pin_for_store(record.storage, 1)
+            .map_err(ArchiveError::Pinned)?;
+        let key = self
+            .storages
+            .arch_storages
+            .storage_key(record.storage)
+            .unwrap();
+        // The entity doesn't count towards the quota while it's archived.
+        self.check_quota(key, 1).map_err(ArchiveError::Quota)?;
+        let bundle = record
+            .restore(&self.components)
+            .map_err(|component| ArchiveError::Malformed { entity, component })?;
+        let sid = record.storage;
+        self.archive.remove(entity);
+        let storage = self.storages.arch_storages.get_storage_mut(sid).unwrap();
+        // SAFETY: The bundle stores exactly the components of the storage that it was archived from.
+        let index = unsafe { storage.store_entity_unchecked(entity, bundle, &self.components) };
+        self.entities.set_entity_meta(
+            EntityMeta {
+                archetype_storage_id: sid,
+                archetype_storage_index: index,
+                component_mask: storage.component_mask(),
+            },
+            entity,
+        );
+        self.ordered.stored(entity, storage.component_mask());
+        if storage.is_sort_maintained() {
+            self.sort_rows_from(sid, index.0);
+        }
+        Ok(())
+    }
+
+    /// Restore every archived entity (see [`World::restore`]), and return how many entities were restored. Stops at
+    /// the first entity that can't be restored.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Position(i32, i32);
+    ///
+    /// let mut world = World::default();
+    /// // SAFETY: `Position` has no padding.
+    /// unsafe { world.register_pod_component::<Position>() };
+    /// for i in 0..3 {
+    ///     let entity = world.spawn(Position(i, i));
+    ///     world.archive(entity).unwrap();
+    /// }
+    /// assert_eq!(world.restore_all(), Ok(3));
+    /// assert_eq!(world.query::<&Position>().count(), 3);
+    /// ```
+    pub fn restore_all(&mut self) -> Result<usize, ArchiveError> {
+        let entities: Vec<EntityId> = self.archive.entities().collect();
+        for entity in &entities {
+            self.restore(*entity)?;
+        }
+        Ok(entities.len())
+    }
+
+    /// Returns `true` if the entity is archived (see [`World::archive`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Position(i32, i32);
+    ///
+    /// let mut world = World::default();
+    /// // SAFETY: `Position` has no padding.
+    /// unsafe { world.register_pod_component::<Position>() };
+    /// let entity = world.spawn(Position(1, 2));
+    /// assert!(!world.is_archived(entity));
+    /// world.archive(entity).unwrap();
+    /// assert!(world.is_archived(entity));
+    /// ```
+    pub fn is_archived(&self, entity: EntityId) -> bool {
+        self.entities
+            .get_entity_meta(entity)
+            .is_some_and(EntityMeta::is_archived)
+    }
+
+    /// Returns how many entities are archived (see [`World::archive`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Position(i32, i32);
+    ///
+    /// let mut world = World::default();
+    /// // SAFETY: `Position` has no padding.
+    /// unsafe { world.register_pod_component::<Position>() };
+    /// let entity = world.spawn(Position(1, 2));
+    /// world.archive(entity).unwrap();
+    /// assert_eq!(world.archived_count(), 1);
+    /// world.restore(entity).unwrap();
+    /// assert_eq!(world.archived_count(), 0);
+    /// ```
+    pub fn archived_count(&self) -> usize {
+        self.archive.len()
+    }
+
+    /// Whether the entity is alive, archived (see [`World::archive`]), or dead.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Position(i32, i32);
+    ///
+    /// let mut world = World::default();
+    /// // SAFETY: `Position` has no padding.
+    /// unsafe { world.register_pod_component::<Position>() };
+    /// let entity = world.spawn(Position(1, 2));
+    /// assert_eq!(world.entity_state(entity), EntityState::Alive);
+    /// world.archive(entity).unwrap();
+    /// assert_eq!(world.entity_state(entity), EntityState::Archived);
+    /// world.despawn(entity);
+    /// assert_eq!(world.entity_state(entity), EntityState::Dead);
+    /// ```
+    pub fn entity_state(&self, entity: EntityId) -> EntityState {
+        match self.entities.get_entity_meta(entity) {
+            Some(entity_meta) if entity_meta.is_archived() => EntityState::Archived,
+            Some(entity_meta) if entity_meta.is_tombstone() => EntityState::Tombstone,
+            Some(_) => EntityState::Alive,
+            None => EntityState::Dead,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Archivable, ArchiveError, EntityState};
+    use crate::{prelude::*, world::userdata::UserdataKey};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Component, Debug, PartialEq, Clone, Copy)]
+    struct Position([f32; 3]);
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Name(String);
+
+    impl Archivable for Name {
+        fn archive(&self, bytes: &mut Vec<u8>) {
+            bytes.extend_from_slice(self.0.as_bytes());
+        }
+
+        fn restore(bytes: &[u8]) -> Option<Self> {
+            String::from_utf8(bytes.to_vec()).ok().map(Name)
+        }
+    }
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Marker;
+
+    impl Archivable for Marker {
+        fn archive(&self, _bytes: &mut Vec<u8>) {}
+
+        fn restore(_bytes: &[u8]) -> Option<Self> {
+            Some(Marker)
+        }
+    }
+
+    /// Can't be archived.
+    #[derive(Component)]
+    struct Socket;
+
+    /// Archives fine, but can never be restored.
+    #[derive(Component)]
+    struct Broken(Arc<AtomicUsize>);
+
+    impl Drop for Broken {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl Archivable for Broken {
+        fn archive(&self, _bytes: &mut Vec<u8>) {}
+
+        fn restore(_bytes: &[u8]) -> Option<Self> {
+            None
+        }
+    }
+
+    #[derive(Tag)]
+    struct Distant;
+
+    fn world() -> World {
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Distant>();
+        let mut world = World::with_tags(tagf);
+        // SAFETY: `Position` has no padding.
+        unsafe { world.register_pod_component::<Position>() };
+        world.register_archivable_component::<Name>();
+        world.register_archivable_component::<Marker>();
+        world.register_archivable_component::<Broken>();
+        world
+    }
+
+    #[test]
+    fn test_archive_restore_round_trip() {
+        let mut world = world();
+        let entities: Vec<EntityId> = (0..10)
+            .map(|i| match i % 3 {
+                0 => world.spawn((Position([i as f32, 1.0, 2.0]), Name(format!("e{i}")))),
+                1 => world.spawn((Position([i as f32, 0.0, 0.0]), Marker)),
+                _ => world.spawn(Name(format!("e{i}"))),
+            })
+            .collect();
+        let archived = [entities[0], entities[4], entities[5]];
+        for entity in archived {
+            world.archive(entity).unwrap();
+        }
+        world.assert_invariants();
+        assert_eq!(world.archived_count(), 3);
+        assert_eq!(world.entity_state(entities[0]), EntityState::Archived);
+        assert_eq!(world.entity_state(entities[1]), EntityState::Alive);
+        // Archived entities are invisible, and their components can't be accessed.
+        assert_eq!(world.query::<&Position>().count(), 5);
+        assert_eq!(world.query::<&Name>().count(), 5);
+        assert!(world.get_component::<Name>(entities[0]).is_none());
+        assert!(!world.contains_component::<Name>(entities[0]));
+        assert_eq!(
+            world.archive(entities[0]),
+            Err(ArchiveError::AlreadyArchived(entities[0]))
+        );
+        // Their ids stay reserved.
+        let spawned = world.spawn(Marker);
+        assert!(!archived.iter().any(|entity| entity.id() == spawned.id()));
+
+        world.restore(entities[4]).unwrap();
+        assert_eq!(world.restore_all(), Ok(2));
+        world.assert_invariants();
+        assert_eq!(world.archived_count(), 0);
+        assert_eq!(
+            world.restore(entities[0]),
+            Err(ArchiveError::NotArchived(entities[0]))
+        );
+        for (i, entity) in entities.iter().enumerate() {
+            assert_eq!(world.entity_state(*entity), EntityState::Alive);
+            match i % 3 {
+                0 => {
+                    assert_eq!(
+                        world.get_component::<Position>(*entity),
+                        Some(&Position([i as f32, 1.0, 2.0]))
+                    );
+                    assert_eq!(
+                        world.get_component::<Name>(*entity),
+                        Some(&Name(format!("e{i}")))
+                    );
+                }
+                1 => assert_eq!(
+                    world.get_component::<Position>(*entity),
+                    Some(&Position([i as f32, 0.0, 0.0]))
+                ),
+                _ => assert_eq!(
+                    world.get_component::<Name>(*entity),
+                    Some(&Name(format!("e{i}")))
+                ),
+            }
+        }
+        assert_eq!(world.query::<(&Position, &Marker)>().count(), 3);
+    }
+
+    #[test]
+    fn test_archive_keeps_tags_and_userdata() {
+        let mut world = world();
+        let cleaned = Arc::new(AtomicUsize::new(0));
+        let cleanup = cleaned.clone();
+        let key = UserdataKey::Named("script");
+        world.set_userdata_cleanup(key, move |_, _| {
+            cleanup.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let entity = world.spawn(Name(String::from("far away")));
+        unsafe { world.get_tag_tracker(entity).unwrap().tag::<Distant>() };
+        world.set_userdata(entity, key, Box::new(7u32));
+        world.archive(entity).unwrap();
+        assert!(unsafe {
+            world
+                .get_tag_tracker(entity)
+                .unwrap()
+                .is_tagged::<Distant>()
+        });
+        assert_eq!(world.query::<&Name>().count(), 0);
+        assert_eq!(cleaned.load(Ordering::SeqCst), 0);
+
+        world.restore(entity).unwrap();
+        assert!(unsafe {
+            world
+                .get_tag_tracker(entity)
+                .unwrap()
+                .is_tagged::<Distant>()
+        });
+        assert!(world.get_userdata(entity, key).is_some());
+
+        // Despawning an archived entity drops its record, tags and userdata.
+        world.archive(entity).unwrap();
+        world.despawn(entity);
+        assert_eq!(world.entity_state(entity), EntityState::Dead);
+        assert_eq!(world.archived_count(), 0);
+        assert_eq!(cleaned.load(Ordering::SeqCst), 1);
+        assert_eq!(world.restore(entity), Err(ArchiveError::Despawned(entity)));
+        let revived = world.spawn(Name(String::from("new")));
+        assert_eq!(revived.id(), entity.id());
+        assert!(!unsafe {
+            world
+                .get_tag_tracker(revived)
+                .unwrap()
+                .is_tagged::<Distant>()
+        });
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_archive_errors() {
+        let mut world = world();
+        let socket = world.spawn((Name(String::from("socket")), Socket));
+        let named = world.spawn(Name(String::from("named")));
+        assert_eq!(
+            world.archive(socket),
+            Err(ArchiveError::NotArchivable {
+                entity: socket,
+                component: std::any::type_name::<Socket>()
+            })
+        );
+        assert_eq!(world.entity_state(socket), EntityState::Alive);
+        // Nothing is archived if some of the entities can't be.
+        assert!(matches!(
+            world.archive_matching::<Has<Name>>(),
+            Err(ArchiveError::NotArchivable { .. })
+        ));
+        assert_eq!(world.archived_count(), 0);
+        assert_eq!(world.archive_matching::<Not<Has<Socket>>>(), Ok(1));
+        assert!(world.is_archived(named));
+
+        // A component that can't be restored keeps the entity archived, with its components.
+        let drops = Arc::new(AtomicUsize::new(0));
+        let broken = world.spawn((Name(String::from("broken")), Broken(drops.clone())));
+        world.archive(broken).unwrap();
+        assert_eq!(
+            world.restore(broken),
+            Err(ArchiveError::Malformed {
+                entity: broken,
+                component: std::any::type_name::<Broken>()
+            })
+        );
+        assert!(world.is_archived(broken));
+        world.assert_invariants();
+
+        // The archived record doesn't hold a live value, only the original was dropped.
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert_eq!(world.clear(), 3);
+        assert_eq!(world.entity_state(named), EntityState::Dead);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_restore_within_quotas() {
+        let mut world = world();
+        world.set_entity_limit(2);
+        let archived = world.spawn(Position([0.0; 3]));
+        world.archive(archived).unwrap();
+        // Archived entities don't count towards the limit, until they are restored.
+        let first = world.spawn(Position([1.0; 3]));
+        world.spawn(Position([2.0; 3]));
+        assert!(matches!(
+            world.restore(archived),
+            Err(ArchiveError::Quota(SpawnError::QuotaExceeded {
+                limit: 2,
+                current: 2,
+                ..
+            }))
+        ));
+        assert!(world.is_archived(archived));
+        world.despawn(first);
+        world.restore(archived).unwrap();
+        assert_eq!(world.entity_state(archived), EntityState::Alive);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_archive_sorted_storage() {
+        let mut world = world();
+        world.maintain_sort::<(Position, Name), Position, i32>(|position| position.0[0] as i32);
+        let entities: Vec<EntityId> = (0..8)
+            .map(|i| world.spawn((Position([(i * 5 % 8) as f32; 3]), Name(format!("{i}")))))
+            .collect();
+        assert_eq!(world.archive_matching::<()>(), Ok(8));
+        for entity in entities.iter().rev() {
+            world.restore(*entity).unwrap();
+        }
+        let order: Vec<f32> = world
+            .query::<&Position>()
+            .map(|position| position.0[0])
+            .collect();
+        assert!(order.is_sorted());
+        world.assert_invariants();
+    }
+}
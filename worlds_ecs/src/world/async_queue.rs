@@ -0,0 +1,496 @@
+use super::World;
+use crate::{archetype::Archetype, bundle::Bundle, entity::EntityId, utils::panics};
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+/// An operation that was queued for the world, and runs when the queue is pumped.
+type Job = Box<dyn FnOnce(&mut World) + Send>;
+
+/// The operations that were queued from async code, and wait for [`World::pump_async_queue`]. Every
+/// [`WorldAsyncQueue`] of the world pushes to the same queue.
+#[derive(Default)]
+pub(crate) struct AsyncJobs {
+    shared: Arc<Mutex<SharedJobs>>,
+}
+
+#[derive(Default)]
+struct SharedJobs {
+    jobs: VecDeque<Job>,
+    /// Set when the world is dropped: the queued operations are dropped, and new ones are dropped right away.
+    closed: bool,
+}
+
+impl Drop for AsyncJobs {
+    fn drop(&mut self) {
+        let jobs = {
+            let mut shared = lock(&self.shared);
+            shared.closed = true;
+            std::mem::take(&mut shared.jobs)
+        };
+        // Dropped outside of the lock, since it wakes the futures.
+        drop(jobs);
+    }
+}
+
+/// Lock a mutex of the queue. The mutex only guards pushes and pops, which can't panic halfway, so a poisoned mutex
+/// is still consistent.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// A handle to queue operations on a [`World`] from async code, that may run on other threads (see
+/// [`World::async_queue`]). Each operation returns a [`WorldFuture`], which completes once the main thread ran it with
+/// [`World::pump_async_queue`]. The handle is cheap to clone, and the futures work under any executor.
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+/// use std::{future::Future, pin::pin, task::{Context, Poll, Waker}};
+///
+/// #[derive(Component)]
+/// struct Mesh(u32);
+///
+/// let mut world = World::default();
+/// let queue = world.async_queue();
+/// // Usually in an async task of the asset loader.
+/// let mut spawned = pin!(queue.spawn(Mesh(7)));
+/// let mut cx = Context::from_waker(Waker::noop());
+/// assert!(spawned.as_mut().poll(&mut cx).is_pending());
+///
+/// // At the frame boundary of the main loop.
+/// world.pump_async_queue(64);
+/// let Poll::Ready(entity) = spawned.as_mut().poll(&mut cx) else { unreachable!() };
+/// assert_eq!(world.get_component::<Mesh>(entity).unwrap().0, 7);
+/// ```
+#[derive(Clone)]
+pub struct WorldAsyncQueue {
+    shared: Arc<Mutex<SharedJobs>>,
+}
+
+impl WorldAsyncQueue {
+    /// Queue a spawn of `bundle` (see [`World::spawn`]), and return a future of the [`EntityId`] of the spawned entity.
+    ///
+    /// ```
+    /// use std::{future::Future, pin::pin, task::{Context, Poll, Waker}};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh(u32);
+    ///
+    /// let mut world = World::default();
+    /// let mut spawned = pin!(world.async_queue().spawn(Mesh(7)));
+    /// world.pump_async_queue(1);
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// let Poll::Ready(entity) = spawned.as_mut().poll(&mut cx) else { unreachable!() };
+    /// assert!(world.contains_component::<Mesh>(entity));
+    /// ```
+    pub fn spawn<B: Bundle + Archetype + Send + 'static>(
+        &self,
+        bundle: B,
+    ) -> WorldFuture<EntityId> {
+        self.run(move |world| world.spawn(bundle))
+    }
+
+    /// Queue a closure that runs with the world, and return a future of its result. If the world was dropped, the
+    /// closure is dropped right away, and the future panics when it's polled.
+    ///
+    /// ```
+    /// use std::{future::Future, pin::pin, task::{Context, Poll, Waker}};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh(u32);
+    ///
+    /// let mut world = World::default();
+    /// let mut count = pin!(world.async_queue().run(|world| world.component_count::<Mesh>()));
+    /// world.spawn(Mesh(7));
+    /// world.pump_async_queue(1);
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// assert_eq!(count.as_mut().poll(&mut cx), Poll::Ready(1));
+    /// ```
+    pub fn run<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut World) -> R + Send + 'static,
+    ) -> WorldFuture<R> {
+        let completion = Arc::new(Mutex::new(Completion {
+            state: State::Queued,
+            waker: None,
+        }));
+        let guard = CompletionGuard {
+            completion: Some(Arc::clone(&completion)),
+        };
+        let job: Job = Box::new(move |world| guard.complete(f(world)));
+        let mut shared = lock(&self.shared);
+        if shared.closed {
+            drop(shared);
+            drop(job);
+        } else {
+            shared.jobs.push_back(job);
+        }
+        WorldFuture { completion }
+    }
+
+    /// The amount of operations that are waiting to run.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let queue = world.async_queue();
+    /// let _spawned = queue.spawn(());
+    /// assert_eq!(queue.len(), 1);
+    /// world.pump_async_queue(1);
+    /// assert_eq!(queue.len(), 0);
+    /// ```
+    pub fn len(&self) -> usize {
+        lock(&self.shared).jobs.len()
+    }
+
+    /// Returns `true` if no operation is waiting to run.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let queue = world.async_queue();
+    /// assert!(queue.is_empty());
+    /// let _spawned = queue.spawn(());
+    /// assert!(!queue.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for WorldAsyncQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorldAsyncQueue")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+enum State<R> {
+    Queued,
+    Done(R),
+    Taken,
+    /// The operation was dropped without running: the world was dropped, or the operation panicked.
+    Dropped,
+}
+
+struct Completion<R> {
+    state: State<R>,
+    waker: Option<Waker>,
+}
+
+/// The sending side of a [`Completion`], owned by its queued operation. If the operation is dropped without
+/// completing, the future learns about it instead of waiting forever.
+struct CompletionGuard<R> {
+    completion: Option<Arc<Mutex<Completion<R>>>>,
+}
+
+impl<R> CompletionGuard<R> {
+    fn complete(mut self, value: R) {
+        let completion = self.completion.take().unwrap();
+        finish(&completion, State::Done(value));
+    }
+}
+
+impl<R> Drop for CompletionGuard<R> {
+    fn drop(&mut self) {
+        if let Some(completion) = self.completion.take() {
+            finish(&completion, State::Dropped);
+        }
+    }
+}
+
+/// Set the final state of a completion, and wake its future (outside of the lock).
+fn finish<R>(completion: &Mutex<Completion<R>>, state: State<R>) {
+    let waker = {
+        let mut completion = lock(completion);
+        completion.state = state;
+        completion.waker.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// The result of an operation that was queued with a [`WorldAsyncQueue`]. It completes once the operation ran (see
+/// [`World::pump_async_queue`]), and wakes the task that awaits it.
+///
+/// # Panics
+/// When it's polled, if the operation was dropped without running: if the world was dropped before it ran, or the
+/// operation panicked. It also panics if it's polled after it completed.
+#[must_use = "the operation runs anyway, but its result is lost"]
+pub struct WorldFuture<R> {
+    completion: Arc<Mutex<Completion<R>>>,
+}
+
+impl<R> Future for WorldFuture<R> {
+    type Output = R;
+
+    #[track_caller]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut completion = lock(&self.completion);
+        match std::mem::replace(&mut completion.state, State::Taken) {
+            State::Done(value) => Poll::Ready(value),
+            State::Queued => {
+                completion.state = State::Queued;
+                match &mut completion.waker {
+                    Some(waker) => waker.clone_from(cx.waker()),
+                    waker => *waker = Some(cx.waker().clone()),
+                }
+                Poll::Pending
+            }
+            State::Taken => panics::fail(
+                "WorldFuture::poll",
+                "the future was polled after it completed",
+                &[],
+            ),
+            State::Dropped => panics::fail(
+                "WorldFuture::poll",
+                "the operation was dropped without running, because the world was dropped or the operation panicked",
+                &[],
+            ),
+        }
+    }
+}
+
+impl<R> fmt::Debug for WorldFuture<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match lock(&self.completion).state {
+            State::Queued => "queued",
+            State::Done(_) => "done",
+            State::Taken => "taken",
+            State::Dropped => "dropped",
+        };
+        f.debug_struct("WorldFuture")
+            .field("state", &state)
+            .finish()
+    }
+}
+
+impl World {
+    /// A handle to queue operations on this world from async code (see [`WorldAsyncQueue`]). The operations run when
+    /// the main thread calls [`World::pump_async_queue`], typically at the frame boundary. Every handle of the world
+    /// queues to the same queue, in order.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let world = World::default();
+    /// let (a, b) = (world.async_queue(), world.async_queue());
+    /// let _spawned = a.spawn(());
+    /// // Both handles queue to the queue of the world.
+    /// assert_eq!(b.len(), 1);
+    /// ```
+    pub fn async_queue(&self) -> WorldAsyncQueue {
+        WorldAsyncQueue {
+            shared: Arc::clone(&self.async_jobs.shared),
+        }
+    }
+
+    /// Run up to `budget` of the operations that were queued with [`World::async_queue`], in the order they were
+    /// queued, and wake the futures that await them. Operations that are queued while the queue is pumped (by the
+    /// operations themselves, or from other threads) run in the same call if the budget allows. Returns how many
+    /// operations ran.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh(u32);
+    ///
+    /// let mut world = World::default();
+    /// let queue = world.async_queue();
+    /// for i in 0..3 {
+    ///     let _spawned = queue.spawn(Mesh(i));
+    /// }
+    /// assert_eq!(world.pump_async_queue(2), 2);
+    /// assert_eq!(world.pump_async_queue(64), 1);
+    /// assert_eq!(world.component_count::<Mesh>(), 3);
+    /// ```
+    ///
+    /// # Panics
+    /// If an operation panics, the panic is resumed after its future learned that it was dropped. The operations
+    /// after it stay queued.
+    pub fn pump_async_queue(&mut self, budget: usize) -> usize {
+        let shared = Arc::clone(&self.async_jobs.shared);
+        for ran in 0..budget {
+            // The lock isn't held while the operation runs, so it can queue more operations.
+            let Some(job) = lock(&shared).jobs.pop_front() else {
+                return ran;
+            };
+            job(self);
+        }
+        budget
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WorldFuture;
+    use crate::prelude::*;
+    use std::{
+        future::Future,
+        panic::{catch_unwind, AssertUnwindSafe},
+        pin::pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc,
+        },
+        task::{Context, Poll, Wake, Waker},
+        thread::{self, Thread},
+        time::Duration,
+    };
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Mesh(u32);
+
+    /// Wakes a parked thread.
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Counts how many times it was woken.
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Poll the future on this thread until it completes, parking in between.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn poll<R>(future: &mut std::pin::Pin<&mut WorldFuture<R>>, waker: &Waker) -> Poll<R> {
+        future.as_mut().poll(&mut Context::from_waker(waker))
+    }
+
+    #[test]
+    fn test_futures_complete_when_pumped() {
+        let mut world = World::default();
+        let queue = world.async_queue();
+        let counter = Arc::new(Counter::default());
+        let waker = Waker::from(Arc::clone(&counter));
+
+        let mut spawned = pin!(queue.clone().spawn(Mesh(1)));
+        let mut count = pin!(queue.run(|world| world.query::<&Mesh>().count()));
+        assert!(poll(&mut spawned, &waker).is_pending());
+        assert!(poll(&mut count, &waker).is_pending());
+        assert_eq!(queue.len(), 2);
+        assert_eq!(world.entities(), 0);
+
+        // The budget limits how many operations run.
+        assert_eq!(world.pump_async_queue(1), 1);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        let Poll::Ready(entity) = poll(&mut spawned, &waker) else {
+            panic!("the spawn ran");
+        };
+        assert!(world.is_alive(entity));
+        assert!(poll(&mut count, &waker).is_pending());
+
+        assert_eq!(world.pump_async_queue(10), 1);
+        assert_eq!(poll(&mut count, &waker), Poll::Ready(1));
+        assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+        assert!(queue.is_empty());
+        assert_eq!(world.pump_async_queue(10), 0);
+
+        // A future that wasn't polled before the pump is ready right away.
+        let future = queue.run(|world| world.spawn(Mesh(2)));
+        world.pump_async_queue(1);
+        let entity = block_on(future);
+        assert_eq!(world.get_component::<Mesh>(entity), Some(&Mesh(2)));
+    }
+
+    #[test]
+    fn test_operations_queue_operations() {
+        let mut world = World::default();
+        let queue = world.async_queue();
+        let inner = queue.clone();
+        let outer = queue.run(move |world| {
+            world.spawn(Mesh(0));
+            inner.spawn(Mesh(1))
+        });
+        assert_eq!(world.pump_async_queue(10), 2);
+        let entity = block_on(block_on(outer));
+        assert_eq!(world.get_component::<Mesh>(entity), Some(&Mesh(1)));
+    }
+
+    #[test]
+    fn test_await_from_another_thread() {
+        let mut world = World::default();
+        let queue = world.async_queue();
+        let (sender, receiver) = mpsc::channel();
+        let loader = thread::spawn(move || {
+            let entities: Vec<EntityId> = (0..10).map(|i| block_on(queue.spawn(Mesh(i)))).collect();
+            sender.send(entities).unwrap();
+        });
+        // The main loop pumps the queue every frame, until the loader is done.
+        let entities = loop {
+            world.pump_async_queue(4);
+            match receiver.recv_timeout(Duration::from_millis(1)) {
+                Ok(entities) => break entities,
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(error) => panic!("{error}"),
+            }
+        };
+        loader.join().unwrap();
+        for (i, entity) in entities.iter().enumerate() {
+            assert!(world.is_alive(*entity));
+            assert_eq!(world.get_component::<Mesh>(*entity), Some(&Mesh(i as u32)));
+        }
+    }
+
+    #[test]
+    fn test_dropped_operations() {
+        let mut world = World::default();
+        let queue = world.async_queue();
+        let panicking = queue.run(|_| -> u32 { panic!("loading failed") });
+        let after = queue.run(|_| 7);
+        assert!(catch_unwind(AssertUnwindSafe(|| world.pump_async_queue(10))).is_err());
+        let message = *catch_unwind(AssertUnwindSafe(|| block_on(panicking)))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert!(message.starts_with(
+            "worlds_ecs: WorldFuture::poll failed: the operation was dropped without running"
+        ));
+        // The operations after the panic stay queued.
+        assert_eq!(world.pump_async_queue(10), 1);
+        assert_eq!(block_on(after), 7);
+
+        // Dropping the world drops the queued operations, and the operations that are queued after it.
+        let never = queue.spawn(Mesh(0));
+        drop(world);
+        assert!(queue.is_empty());
+        assert!(catch_unwind(AssertUnwindSafe(|| block_on(never))).is_err());
+        let late = queue.spawn(Mesh(1));
+        assert!(queue.is_empty());
+        assert!(catch_unwind(AssertUnwindSafe(|| block_on(late))).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use super::{storage::storages::ArchStorageId, World};
+use crate::{entity::ReusePolicy, storage::alloc::AllocReason};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The perturbations of [`World::enable_chaos`], and how often they happen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChaosConfig {
+    /// The chance (between `0.0` and `1.0`) that the columns of a storage are moved to new allocations after an
+    /// entity is spawned in it, so every pointer into them changes.
+    pub relocate_after_spawn: f64,
+    /// Reuse the id of the entity that was despawned most recently (see [`ReusePolicy::Lifo`]), instead of the
+    /// reuse policy of the world.
+    pub reuse_ids_immediately: bool,
+    /// The chance (between `0.0` and `1.0`) that an empty storage is created right before a new storage, so the
+    /// new storage gets a different [`ArchStorageId`]. The empty storage stores a sub-archetype of the new one.
+    pub empty_storages: f64,
+    /// Start each query at a random storage (and wrap around to the first one), instead of the first one.
+    pub permute_queries: bool,
+}
+
+impl Default for ChaosConfig {
+    /// Every perturbation, often enough that a short test goes through many of them.
+    fn default() -> Self {
+        ChaosConfig {
+            relocate_after_spawn: 0.25,
+            reuse_ids_immediately: true,
+            empty_storages: 0.5,
+            permute_queries: true,
+        }
+    }
+}
+
+/// The state of [`World::enable_chaos`]: the configuration, and a pseudo-random generator that decides each
+/// perturbation. The generator is atomic, so queries through a shared reference can roll it too.
+pub(crate) struct Chaos {
+    pub(crate) config: ChaosConfig,
+    state: AtomicU64,
+}
+
+impl Clone for Chaos {
+    fn clone(&self) -> Self {
+        Chaos {
+            config: self.config,
+            state: AtomicU64::new(self.state.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Chaos {
+    pub(crate) fn new(seed: u64, config: ChaosConfig) -> Chaos {
+        Chaos {
+            config,
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    /// The next pseudo-random number (splitmix64).
+    fn next(&self) -> u64 {
+        let mut bits = (self.state)
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        bits = (bits ^ (bits >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        bits = (bits ^ (bits >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        bits ^ (bits >> 31)
+    }
+
+    /// Returns `true` with a chance of `chance` (between `0.0` and `1.0`).
+    pub(crate) fn roll(&self, chance: f64) -> bool {
+        chance > 0.0 && ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < chance
+    }
+
+    /// A pseudo-random number below `n`, which must not be `0`.
+    pub(crate) fn below(&self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+impl World {
+    /// Make the world perturb itself at random (but deterministically from the `seed`) in ways that are legal, but
+    /// that well-behaved code never notices: see [`ChaosConfig`]. This is a testing aid for code that holds on to
+    /// pointers into the storages, or to [`ArchStorageId`]s and ids of despawned entities, past the point where
+    /// they can go stale, or that relies on an order of the storages that isn't documented (like the order of
+    /// [`World::query`] across storages). That code happens to work until a storage reallocates or an id is
+    /// reused at an unlucky moment, and under chaos it fails right away, and reproducibly. Together with
+    /// [`World::assert_invariants`] and the `paranoid` feature, this catches such bugs both inside and outside of
+    /// the crate. Only available with the `chaos` feature.
+    ///
+    /// The perturbations never break a documented guarantee: the rows of a pinned storage (see
+    /// [`World::pin_storage`]) never move, the entities in each storage keep their order, and an [`EntityId`]
+    /// is only reused after it was despawned. If [`ChaosConfig::reuse_ids_immediately`] is set, the reuse policy
+    /// of the world is set to [`ReusePolicy::Lifo`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.enable_chaos(7, ChaosConfig::default());
+    /// let entity = world.spawn(Health(10));
+    /// world.despawn(entity);
+    /// // The id of the despawned entity is reused right away.
+    /// assert_eq!(world.spawn(Health(20)).id(), entity.id());
+    /// ```
+    ///
+    /// [`EntityId`]: crate::entity::EntityId
+    pub fn enable_chaos(&mut self, seed: u64, config: ChaosConfig) {
+        if config.reuse_ids_immediately {
+            self.entities.set_reuse_policy(ReusePolicy::Lifo);
+        }
+        self.storages.arch_storages.chaos = Some(Chaos::new(seed, config));
+    }
+
+    /// Stop perturbing the world (see [`World::enable_chaos`]). The reuse policy of the world is left as it is.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// world.enable_chaos(7, ChaosConfig::default());
+    /// world.disable_chaos();
+    /// let entity = world.spawn(());
+    /// world.despawn(entity);
+    /// // The reuse policy that the chaos set is kept.
+    /// assert_eq!(world.spawn(()).id(), entity.id());
+    /// ```
+    pub fn disable_chaos(&mut self) {
+        self.storages.arch_storages.chaos = None;
+    }
+
+    /// Move the columns of a storage that an entity was just spawned in to new allocations, if the chaos rolls it
+    /// (see [`ChaosConfig::relocate_after_spawn`]). Pinned storages are never moved.
+    pub(crate) fn chaos_after_spawn(&mut self, sid: ArchStorageId) {
+        let arch_storages = &mut self.storages.arch_storages;
+        let Some(chaos) = &arch_storages.chaos else {
+            return;
+        };
+        if !chaos.roll(chaos.config.relocate_after_spawn) {
+            return;
+        }
+        let storage = arch_storages.get_storage_mut(sid).unwrap();
+        if !storage.is_pinned() {
+            let _scope = self.components.storage_alloc().scope(AllocReason::Growth);
+            storage.relocate();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChaosConfig;
+    use crate::{prelude::*, test_utils::*};
+
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Health(u32);
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Armor(u32);
+    #[derive(Component)]
+    struct Loot;
+
+    fn only(config: impl FnOnce(&mut ChaosConfig)) -> ChaosConfig {
+        let mut only = ChaosConfig {
+            relocate_after_spawn: 0.0,
+            reuse_ids_immediately: false,
+            empty_storages: 0.0,
+            permute_queries: false,
+        };
+        config(&mut only);
+        only
+    }
+
+    #[test]
+    fn test_columns_are_relocated_after_spawns() {
+        let mut world = World::default();
+        world.enable_chaos(1, only(|config| config.relocate_after_spawn = 1.0));
+        let first = world.spawn_batch((0..100).map(Health))[0];
+        let before: *const Health = world.get_component::<Health>(first).unwrap();
+        world.spawn(Health(100));
+        let after: *const Health = world.get_component::<Health>(first).unwrap();
+        assert_ne!(before, after);
+        let healths: Vec<u32> = world.query::<&Health>().map(|health| health.0).collect();
+        assert_eq!(healths, (0..=100).collect::<Vec<_>>());
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_pinned_storages_are_not_relocated() {
+        let mut world = World::default();
+        world.enable_chaos(2, only(|config| config.relocate_after_spawn = 1.0));
+        let first = world.spawn_batch((0..100).map(Health))[0];
+        let sid = world
+            .entities
+            .get_entity_meta(first)
+            .unwrap()
+            .archetype_storage_id;
+        let storage = world.storages.arch_storages.get_storage_mut(sid).unwrap();
+        storage.reserve(10);
+        let before: *const Health = world.get_component::<Health>(first).unwrap();
+        let pin = world.pin_storage(sid);
+        world.spawn(Health(100));
+        assert_eq!(before, world.get_component::<Health>(first).unwrap());
+        drop(pin);
+    }
+
+    #[test]
+    fn test_empty_storages_shift_storage_ids() {
+        let mut world = World::default();
+        world.enable_chaos(3, only(|config| config.empty_storages = 1.0));
+        let entity = world.spawn((Health(1), Armor(2), Loot));
+        // Each new storage is preceded by an empty storage of one of its sub-archetypes, until they all exist.
+        let meta = world.entities.get_entity_meta(entity).unwrap();
+        assert!(meta.archetype_storage_id.0 > 0);
+        assert!(world.storages.arch_storages.storage_count() > 1);
+        assert_query_count::<(&Health, &Armor)>(&mut world, 1);
+        assert_entity_has(&world, entity, Armor(2));
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_queries_are_permuted() {
+        let mut world = World::default();
+        world.enable_chaos(4, only(|config| config.permute_queries = true));
+        for i in 0..4 {
+            world.spawn(Health(i));
+            world.spawn((Health(i + 10), Armor(i)));
+            world.spawn((Health(i + 20), Loot));
+        }
+        let mut orders = Vec::new();
+        for _ in 0..16 {
+            let order: Vec<u32> = world.query::<&Health>().map(|health| health.0).collect();
+            let mut sorted = order.clone();
+            sorted.sort();
+            assert_eq!(sorted, [0, 1, 2, 3, 10, 11, 12, 13, 20, 21, 22, 23]);
+            orders.push(order);
+        }
+        orders.dedup();
+        assert!(orders.len() > 1);
+    }
+
+    #[test]
+    fn test_churn_under_chaos() {
+        let script = ChurnScript::new(0x5EED).steps(2000).archetypes(&[
+            &[0],
+            &[0, 1],
+            &[1, 2],
+            &[0, 1, 2, 3],
+            &[3],
+        ]);
+        let mut calm = World::default();
+        let alive = script.run(&mut calm);
+        let mut chaotic = World::default();
+        chaotic.enable_chaos(5, ChaosConfig::default());
+        let chaotic_alive = script.run_with(&mut chaotic, |world, _| world.assert_invariants());
+        assert_eq!(chaotic_alive.len(), alive.len());
+        for (entity, chaotic_entity) in alive.iter().zip(&chaotic_alive) {
+            assert_eq!(
+                calm.get_component::<Fx<1>>(*entity),
+                chaotic.get_component::<Fx<1>>(*chaotic_entity)
+            );
+        }
+    }
+}
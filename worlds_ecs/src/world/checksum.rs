@@ -0,0 +1,332 @@
+use super::{fingerprint::Fnv1a, storage::arch_storage::ArchStorageIndex, World};
+use crate::{component::ComponentId, utils::panics};
+use std::hash::Hasher;
+
+/// Which components [`World::checksum`] hashes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChecksumConfig {
+    components: Vec<ComponentId>,
+    all_pod: bool,
+}
+
+impl ChecksumConfig {
+    /// Hash the given components.
+    pub fn components(components: impl IntoIterator<Item = ComponentId>) -> Self {
+        ChecksumConfig {
+            components: components.into_iter().collect(),
+            all_pod: false,
+        }
+    }
+
+    /// Hash every component that was registered with
+    /// [`ComponentFactory::register_pod_component`](crate::prelude::ComponentFactory::register_pod_component).
+    pub fn all_pod() -> Self {
+        ChecksumConfig {
+            components: Vec::new(),
+            all_pod: true,
+        }
+    }
+
+    /// Hash the component too.
+    pub fn with(mut self, comp_id: ComponentId) -> Self {
+        self.components.push(comp_id);
+        self
+    }
+}
+
+/// The checksum of a single component (see [`WorldChecksum`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentChecksum {
+    /// The [`ComponentId`] of the component.
+    pub component: ComponentId,
+    /// The name of the component.
+    pub name: &'static str,
+    /// The hash of the entities that have the component, and their values of it.
+    pub hash: u64,
+    /// How many entities have the component.
+    pub entities: usize,
+}
+
+/// A deterministic hash of the values of some components in a [`World`], to detect that the simulations of the peers
+/// of a lockstep session diverged (see [`World::checksum`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldChecksum {
+    /// The hash of every component checksum.
+    pub hash: u64,
+    /// The checksum of each hashed component, ordered by [`ComponentId`], to find which component diverged.
+    pub components: Vec<ComponentChecksum>,
+}
+
+impl WorldChecksum {
+    /// The checksum of the component, if it was hashed.
+    pub fn get(&self, comp_id: ComponentId) -> Option<&ComponentChecksum> {
+        self.components
+            .iter()
+            .find(|checksum| checksum.component == comp_id)
+    }
+
+    /// The names of the components whose checksums are different in the two checksums, or that were only hashed in
+    /// one of them. Returns an empty list if the checksums are the same.
+    pub fn mismatches(&self, other: &WorldChecksum) -> Vec<&'static str> {
+        let mut mismatches: Vec<&'static str> = self
+            .components
+            .iter()
+            .filter(|ours| other.get(ours.component) != Some(*ours))
+            .chain(
+                other
+                    .components
+                    .iter()
+                    .filter(|theirs| self.get(theirs.component).is_none()),
+            )
+            .map(|checksum| checksum.name)
+            .collect();
+        mismatches.sort_unstable();
+        mismatches.dedup();
+        mismatches
+    }
+}
+
+impl World {
+    /// Compute a deterministic hash of the components that the config selects, which is cheap enough to compare
+    /// between the peers of a lockstep session every few frames. It's the same for two worlds whose entities have the
+    /// same [`EntityId`](crate::prelude::EntityId)s and the same values of the components, regardless of the
+    /// storages of the entities and their order in the storages. Peers should also have the same
+    /// [`World::config_fingerprint`], so the [`ComponentId`]s match.
+    ///
+    /// Components are hashed with the hash function of their [`DataInfo`](crate::prelude::DataInfo) (see
+    /// [`ComponentFactory::register_hashable_component`](crate::prelude::ComponentFactory::register_hashable_component)),
+    /// which writes integers in little-endian. Components that were registered with
+    /// [`ComponentFactory::register_pod_component`](crate::prelude::ComponentFactory::register_pod_component) (and
+    /// aren't hashable) are hashed by their bytes in memory, so they can only be compared between platforms with the
+    /// same endianness.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Position(i32, i32);
+    ///
+    /// let mut ours = World::default();
+    /// let mut theirs = World::default();
+    /// for world in [&mut ours, &mut theirs] {
+    ///     // SAFETY: `Position` has no padding.
+    ///     unsafe { world.register_pod_component::<Position>() };
+    ///     world.spawn(Position(1, 2));
+    /// }
+    /// let config = ChecksumConfig::all_pod();
+    /// assert_eq!(ours.checksum(&config), theirs.checksum(&config));
+    /// ```
+    ///
+    /// # Panics
+    /// If a selected component isn't registered, or can't be hashed.
+    #[track_caller]
+    pub fn checksum(&self, config: &ChecksumConfig) -> WorldChecksum {
+        let mut selected = config.components.clone();
+        if config.all_pod {
+            selected.extend(
+                (0..self.components.component_count())
+                    .map(ComponentId::new)
+                    .filter(|comp_id| {
+                        self.components
+                            .get_component_info_from_component_id(*comp_id)
+                            .is_some_and(|info| info.is_pod())
+                    }),
+            );
+        }
+        selected.sort_unstable();
+        selected.dedup();
+
+        let components: Vec<ComponentChecksum> = selected
+            .into_iter()
+            .map(|comp_id| self.component_checksum(comp_id))
+            .collect();
+        let mut hasher = Fnv1a::default();
+        for checksum in &components {
+            hasher.write_field(checksum.name.as_bytes());
+            hasher.write_u64(checksum.hash);
+        }
+        WorldChecksum {
+            hash: hasher.finish(),
+            components,
+        }
+    }
+
+    #[track_caller]
+    fn component_checksum(&self, comp_id: ComponentId) -> ComponentChecksum {
+        let Some(info) = self
+            .components
+            .get_component_info_from_component_id(comp_id)
+        else {
+            panics::fail(
+                "checksum",
+                "the component isn't registered",
+                &[("component", &comp_id.id())],
+            )
+        };
+        let Some(hash_fn) = info.hash_fn() else {
+            panics::fail_component(
+                "checksum",
+                "the component can't be hashed, register it as hashable or as pod",
+                info.name(),
+            )
+        };
+        // Each entity is hashed with its value on its own, and then they are combined in the order of their ids, so
+        // the order of the rows doesn't matter.
+        let mut rows: Vec<(u64, u64)> = self
+            .storages
+            .arch_storages
+            .iter_storages_with_matching_archetype(comp_id.prime_key())
+            .flat_map(|storage| {
+                storage
+                    .entities()
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, entity)| {
+                        let mut hasher = Fnv1a::default();
+                        // SAFETY: The storage stores the component, and the `hash_fn` is of its type.
+                        unsafe {
+                            hash_fn(
+                                storage.get_component_unchecked(ArchStorageIndex(index), comp_id),
+                                &mut hasher,
+                            )
+                        };
+                        (entity.to_bits(), hasher.finish())
+                    })
+            })
+            .collect();
+        rows.sort_unstable();
+
+        let mut hasher = Fnv1a::default();
+        for (entity, value) in &rows {
+            hasher.write_u64(*entity);
+            hasher.write_u64(*value);
+        }
+        ComponentChecksum {
+            component: comp_id,
+            name: info.name(),
+            hash: hasher.finish(),
+            entities: rows.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChecksumConfig;
+    use crate::prelude::*;
+    use std::any::type_name;
+
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Position(i32, i32);
+
+    #[derive(Component, Hash)]
+    struct Name(String);
+
+    #[derive(Component)]
+    struct Opaque;
+
+    fn world() -> World {
+        let mut world = World::default();
+        // SAFETY: `Position` has no padding.
+        unsafe { world.register_pod_component::<Position>() };
+        world.register_hashable_component::<Name>();
+        world.components.register_component::<Opaque>();
+        world
+    }
+
+    /// Spawn entities in different archetypes, and change some of them.
+    fn simulate(world: &mut World) -> Vec<EntityId> {
+        let entities: Vec<EntityId> = (0..6)
+            .map(|i| match i % 3 {
+                0 => world.spawn(Position(i, i)),
+                1 => world.spawn((Position(i, -i), Name(format!("unit {i}")))),
+                _ => world.spawn((Name(format!("prop {i}")), Opaque)),
+            })
+            .collect();
+        world.despawn(entities[3]);
+        for position in world.query::<&mut Position>() {
+            position.0 += 10;
+        }
+        entities
+    }
+
+    fn id<C: Component>(world: &World) -> ComponentId {
+        world.components.get_component_id::<C>().unwrap()
+    }
+
+    #[test]
+    fn test_identical_worlds() {
+        let (mut ours, mut theirs) = (world(), world());
+        simulate(&mut ours);
+        simulate(&mut theirs);
+        let config = ChecksumConfig::all_pod().with(id::<Name>(&ours));
+        let checksum = ours.checksum(&config);
+        assert_eq!(checksum, theirs.checksum(&config));
+        assert!(checksum.mismatches(&theirs.checksum(&config)).is_empty());
+        assert_eq!(checksum.components.len(), 2);
+        assert_eq!(checksum.get(id::<Position>(&ours)).unwrap().entities, 3);
+        assert_eq!(checksum.get(id::<Name>(&ours)).unwrap().entities, 4);
+
+        // The checksum follows the world.
+        let entity = ours.spawn(Position(0, 0));
+        assert_ne!(ours.checksum(&config), checksum);
+        ours.despawn(entity);
+        theirs.spawn(Position(0, 0));
+        theirs.despawn(entity);
+        assert_eq!(ours.checksum(&config), theirs.checksum(&config));
+    }
+
+    #[test]
+    fn test_mismatches_are_localized() {
+        let (mut ours, mut theirs) = (world(), world());
+        let entities = simulate(&mut ours);
+        simulate(&mut theirs);
+        let (position, name) = (id::<Position>(&ours), id::<Name>(&ours));
+        let config = ChecksumConfig::components([name, position]);
+        let before = theirs.checksum(&config);
+
+        theirs.get_component_mut::<Position>(entities[1]).unwrap().1 += 1;
+        let after = theirs.checksum(&config);
+        assert_ne!(before.hash, after.hash);
+        assert_ne!(before.get(position), after.get(position));
+        assert_eq!(before.get(name), after.get(name));
+        assert_eq!(
+            ours.checksum(&config).mismatches(&after),
+            vec![type_name::<Position>()]
+        );
+
+        // A component that is only hashed on one side is a mismatch too.
+        let pod = ours.checksum(&ChecksumConfig::all_pod());
+        assert_eq!(
+            ours.checksum(&config).mismatches(&pod),
+            vec![type_name::<Name>()]
+        );
+    }
+
+    #[test]
+    fn test_row_order_is_ignored() {
+        let (mut ours, mut theirs) = (world(), world());
+        simulate(&mut ours);
+        simulate(&mut theirs);
+        let config = ChecksumConfig::all_pod().with(id::<Name>(&ours));
+        let checksum = ours.checksum(&config);
+        // The same entities and values, stored in the opposite order.
+        assert!(
+            theirs
+                .reorder_matching::<Position>(|entity| u64::MAX - entity.to_bits())
+                .unwrap()
+                > 0
+        );
+        assert_eq!(theirs.checksum(&config), checksum);
+        ours.reorder_matching::<Name>(|entity| u64::MAX - entity.to_bits())
+            .unwrap();
+        assert_eq!(ours.checksum(&config), checksum);
+    }
+
+    #[test]
+    #[should_panic(expected = "the component can't be hashed")]
+    fn test_unhashable_component() {
+        let world = world();
+        world.checksum(&ChecksumConfig::components([id::<Opaque>(&world)]));
+    }
+}
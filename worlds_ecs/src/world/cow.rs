@@ -0,0 +1,390 @@
+use super::World;
+use crate::archetype::key::PrimeArchKey;
+use std::fmt;
+
+/// An error when creating a copy-on-write copy of a [`World`] (see [`World::clone_cow`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CloneCowError {
+    /// A component is stored in the world, but it wasn't registered as cloneable
+    /// (see [`World::register_cloneable_component`]).
+    NotCloneable {
+        /// The name of the component.
+        component: &'static str,
+    },
+    /// The world has state that can't be copied.
+    Unsupported {
+        /// What can't be copied.
+        state: &'static str,
+    },
+}
+
+impl fmt::Display for CloneCowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloneCowError::NotCloneable { component } => {
+                write!(f, "`{component}` isn't registered as a cloneable component")
+            }
+            CloneCowError::Unsupported { state } => {
+                write!(f, "a world with {state} can't be copied")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CloneCowError {}
+
+impl World {
+    /// Create a copy of the [`World`] that shares the components of each storage with `self`, until either of the
+    /// worlds mutates the storage. The first mutation (like [`World::get_component_mut`], a query that fetches
+    /// `&mut C`, or spawning and despawning) copies only the components of the storage that is mutated, so a copy
+    /// costs about as much as what is changed in it. This makes copies cheap for rollback and speculative simulation.
+    ///
+    /// The entities, tags, groups, ordered indexes (see [`World::track_ordered`]) and components are copied. The copy
+    /// is a different world, so a [`ComponentHandle`](super::handle::ComponentHandle) of one world can't be used with
+    /// the other.
+    ///
+    /// Components that can be mutated through a shared reference (like a component with an atomic or a `Mutex`) would
+    /// be mutated in every world that shares their storage, so the storages of the components that are registered
+    /// with [`World::register_interior_mutable_component`] are copied right away instead. A component like that which
+    /// isn't registered as interior-mutable must only be mutated through `&mut`, in either world.
+    ///
+    /// Fails if a stored component wasn't registered with [`World::register_cloneable_component`], or if the world
+    /// has derived components, spatial indexes, userdata, resources or tombstones, which can't be copied.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// world.register_cloneable_component::<Position>();
+    /// let entity = world.spawn(Position(0));
+    /// let mut speculative = world.clone_cow().unwrap();
+    /// speculative.get_component_mut::<Position>(entity).unwrap().0 = 10;
+    /// assert_eq!(world.get_component::<Position>(entity).unwrap().0, 0);
+    /// assert_eq!(speculative.get_component::<Position>(entity).unwrap().0, 10);
+    /// ```
+    pub fn clone_cow(&self) -> Result<World, CloneCowError> {
+        for (is_empty, state) in [
+            (self.derived.is_empty(), "derived components"),
+            (self.spatial.is_empty(), "spatial indexes"),
+            (self.userdata.is_empty(), "userdata"),
+            (self.resources.is_empty(), "resources"),
+            (self.tombstones.is_empty(), "tombstones"),
+        ] {
+            if !is_empty {
+                return Err(CloneCowError::Unsupported { state });
+            }
+        }
+        if let Some(comp_id) = self
+            .storages
+            .arch_storages
+            .iter_storages_with_matching_archetype(PrimeArchKey::IDENTITY)
+            .filter(|storage| !storage.is_empty())
+            .find_map(|storage| storage.uncloneable_component())
+        {
+            return Err(CloneCowError::NotCloneable {
+                component: self
+                    .components
+                    .get_component_info_from_component_id(comp_id)
+                    .expect("stored components are registered")
+                    .name(),
+            });
+        }
+        let mut storages = self.storages.share();
+        for storage in storages
+            .arch_storages
+            .iter_storages_with_matching_archetype_mut(PrimeArchKey::IDENTITY)
+        {
+            if storage.component_ids().any(|comp_id| {
+                self.components
+                    .get_component_info_from_component_id(comp_id)
+                    .is_some_and(|info| info.is_interior_mutable())
+            }) {
+                storage.make_unique();
+            }
+        }
+        Ok(World {
+            components: self.components.clone(),
+            entities: self.entities.clone(),
+            storages,
+            warnings: self.warnings.clone(),
+            archive: self.archive.clone(),
+            groups: self.groups.clone(),
+            ordered: self.ordered.clone(),
+            quotas: self.quotas.clone(),
+            #[cfg(feature = "scene")]
+            scenes: self.scenes.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CloneCowError;
+    use crate::{
+        prelude::*,
+        storage::columns::INLINE_ROWS,
+        world::storage::{storages::ArchStorageId, ArchEntityStorage},
+    };
+    use std::{
+        cell::Cell,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    #[derive(Component, Clone, Copy, PartialEq, Debug)]
+    struct A(usize);
+    #[derive(Component, Clone, Copy, PartialEq, Debug)]
+    struct B(usize);
+    #[derive(Component, PartialEq, Debug)]
+    struct Unique(usize);
+
+    thread_local! {
+        static LIVE: Cell<isize> = const { Cell::new(0) };
+    }
+
+    /// Counts how many instances are alive, so leaks and double drops are caught.
+    #[derive(Component, Debug, PartialEq)]
+    struct Counted(Box<usize>);
+
+    impl Counted {
+        fn new(value: usize) -> Self {
+            LIVE.set(LIVE.get() + 1);
+            Counted(Box::new(value))
+        }
+    }
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            Counted::new(*self.0)
+        }
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            LIVE.set(LIVE.get() - 1);
+        }
+    }
+
+    fn storage(world: &World, id: ArchStorageId) -> &ArchEntityStorage {
+        world.storages.arch_storages.get_storage(id).unwrap()
+    }
+
+    fn storage_of(world: &World, entity: EntityId) -> ArchStorageId {
+        world
+            .entities
+            .get_entity_meta(entity)
+            .unwrap()
+            .archetype_storage_id
+    }
+
+    /// A world with an inline storage (`A`), and two storages that aren't inline (`(A, B)` and `B`).
+    fn world() -> (World, [ArchStorageId; 3]) {
+        let mut world = World::default();
+        world.register_cloneable_component::<A>();
+        world.register_cloneable_component::<B>();
+        let a = world.spawn(A(0));
+        for i in 0..INLINE_ROWS * 4 {
+            world.spawn((A(i), B(i)));
+            world.spawn(B(i));
+        }
+        let ab = world.query::<(EntityId, &B, &A)>().next().unwrap().0;
+        let b = world.query::<(EntityId, &B)>().last().unwrap().0;
+        let ids = [a, ab, b].map(|entity| storage_of(&world, entity));
+        (world, ids)
+    }
+
+    #[test]
+    fn test_clone_cow_unshares_on_write() {
+        let (mut world, [a, ab, b]) = world();
+        let mut copy = world.clone_cow().unwrap();
+        for id in [a, ab, b] {
+            assert!(storage(&world, id).is_shared());
+            assert!(storage(&copy, id).is_shared());
+        }
+        copy.assert_invariants();
+
+        // Mutating a component in the copy only copies its storage.
+        let first = world.query::<(EntityId, &A)>().next().unwrap().0;
+        copy.get_component_mut::<A>(first).unwrap().0 = 100;
+        assert_eq!(copy.get_component::<A>(first), Some(&A(100)));
+        assert_eq!(world.get_component::<A>(first), Some(&A(0)));
+        assert_eq!(storage(&copy, a).unshare_count(), 1);
+        assert!(!storage(&copy, a).is_shared());
+        assert!(!storage(&world, a).is_shared());
+        assert_eq!(storage(&world, a).unshare_count(), 0);
+        for id in [ab, b] {
+            assert!(storage(&world, id).is_shared());
+            assert_eq!(storage(&copy, id).unshare_count(), 0);
+        }
+
+        // A mutable query unshares every storage it visits, once.
+        for (a, b) in copy.query::<(&A, &mut B)>() {
+            b.0 += a.0 + 1;
+        }
+        assert_eq!(storage(&copy, ab).unshare_count(), 1);
+        assert!(storage(&world, b).is_shared());
+        assert!(world.query::<(&A, &B)>().all(|(a, b)| a.0 == b.0));
+        assert!(copy.query::<(&A, &B)>().all(|(a, b)| b.0 == 2 * a.0 + 1));
+
+        // Structural changes in the original unshare it too.
+        let last_b = world.query::<(EntityId, &B)>().last().unwrap().0;
+        world.despawn(last_b);
+        assert_eq!(storage(&world, b).unshare_count(), 1);
+        assert_eq!(storage(&copy, b).len(), INLINE_ROWS * 4);
+        assert_eq!(storage(&world, b).len(), INLINE_ROWS * 4 - 1);
+        assert_eq!(storage(&copy, b).unshare_count(), 0);
+
+        // Reading never unshares.
+        let copy2 = copy.clone_cow().unwrap();
+        assert_eq!(copy2.iter_component::<B>().count(), INLINE_ROWS * 8);
+        assert!(storage(&copy2, ab).is_shared());
+        world.assert_invariants();
+        copy.assert_invariants();
+        copy2.assert_invariants();
+    }
+
+    #[test]
+    fn test_clone_cow_copies_tags() {
+        struct Red;
+        impl Tag for Red {}
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Red>();
+        let mut world = World::with_tags(tagf);
+        world.register_cloneable_component::<A>();
+        let entity = world.spawn(A(0));
+
+        let copy = world.clone_cow().unwrap();
+        // SAFETY: The tag is registered, and no other tracker of the entity is used.
+        unsafe { copy.get_tag_tracker(entity).unwrap().tag::<Red>() };
+        assert!(unsafe { copy.get_tag_tracker(entity).unwrap().is_tagged::<Red>() });
+        assert!(!unsafe { world.get_tag_tracker(entity).unwrap().is_tagged::<Red>() });
+    }
+
+    #[test]
+    fn test_clone_cow_requires_clone_fns() {
+        let (mut world, _) = world();
+        world.components.register_component::<Unique>();
+        // Nothing is stored yet, so there's nothing to clone.
+        assert!(world.clone_cow().is_ok());
+
+        let entity = world.spawn((Unique(0), A(0)));
+        assert_eq!(
+            world.clone_cow().err(),
+            Some(CloneCowError::NotCloneable {
+                component: std::any::type_name::<Unique>()
+            })
+        );
+        world.despawn(entity);
+        assert!(world.clone_cow().is_ok());
+
+        let first = world.query::<(EntityId, &A)>().next().unwrap().0;
+        world.set_userdata(first, UserdataKey::Named("lua"), Box::new(0));
+        assert_eq!(
+            world.clone_cow().err(),
+            Some(CloneCowError::Unsupported { state: "userdata" })
+        );
+        world.remove_userdata(first, UserdataKey::Named("lua"));
+        world.insert_resource(A(0));
+        assert_eq!(
+            world.clone_cow().err(),
+            Some(CloneCowError::Unsupported { state: "resources" })
+        );
+    }
+
+    /// A component that is mutated through shared references.
+    #[derive(Component, Debug)]
+    struct Hits(AtomicUsize);
+
+    impl Clone for Hits {
+        fn clone(&self) -> Self {
+            Hits(AtomicUsize::new(self.0.load(Ordering::Relaxed)))
+        }
+    }
+
+    #[test]
+    fn test_clone_cow_copies_interior_mutable_storages() {
+        let (mut world, [a, ab, b]) = world();
+        world.register_cloneable_component::<Hits>();
+        world.register_interior_mutable_component::<Hits>();
+        let entity = world.spawn((Hits(AtomicUsize::new(0)), A(0)));
+        let hits = storage_of(&world, entity);
+
+        let copy = world.clone_cow().unwrap();
+        assert!(!storage(&copy, hits).is_shared());
+        assert!(!storage(&world, hits).is_shared());
+        assert_eq!(storage(&copy, hits).unshare_count(), 1);
+        for id in [a, ab, b] {
+            assert!(storage(&copy, id).is_shared());
+        }
+
+        let hits = copy.get_component::<Hits>(entity).unwrap();
+        hits.0.fetch_add(1, Ordering::Relaxed);
+        let load = |world: &World| {
+            world
+                .get_component::<Hits>(entity)
+                .unwrap()
+                .0
+                .load(Ordering::Relaxed)
+        };
+        assert_eq!(load(&copy), 1);
+        assert_eq!(load(&world), 0);
+        world.assert_invariants();
+        copy.assert_invariants();
+    }
+
+    #[test]
+    fn test_register_cloneable_after_storing() {
+        let mut world = World::default();
+        world.components.register_component::<A>();
+        let entity = world.spawn(A(7));
+        assert!(world.clone_cow().is_err());
+
+        world.register_cloneable_component::<A>();
+        let mut copy = world.clone_cow().unwrap();
+        copy.get_component_mut::<A>(entity).unwrap().0 = 8;
+        assert_eq!(world.get_component::<A>(entity), Some(&A(7)));
+    }
+
+    #[test]
+    fn test_clone_cow_drops_shared_blocks() {
+        LIVE.set(0);
+        {
+            let mut world = World::default();
+            world.register_cloneable_component::<Counted>();
+            world.register_cloneable_component::<A>();
+            let entities: Vec<_> = (0..INLINE_ROWS * 2)
+                .map(|i| world.spawn((Counted::new(i), A(i))))
+                .collect();
+            let tiny = world.spawn(Counted::new(100));
+            let live = entities.len() as isize + 1;
+            assert_eq!(LIVE.get(), live);
+
+            let mut copies: Vec<World> = (0..3).map(|_| world.clone_cow().unwrap()).collect();
+            assert_eq!(LIVE.get(), live);
+
+            // Dropping the original doesn't drop the shared components.
+            drop(world);
+            assert_eq!(LIVE.get(), live);
+            assert_eq!(*copies[2].get_component::<Counted>(tiny).unwrap().0, 100);
+
+            // Each unshare clones a single storage.
+            *copies[0].get_component_mut::<Counted>(tiny).unwrap().0 = 101;
+            assert_eq!(LIVE.get(), live + 1);
+            copies[1].despawn(entities[3]);
+            assert_eq!(LIVE.get(), live + 1 + entities.len() as isize - 1);
+            copies[1].clear();
+            assert_eq!(LIVE.get(), live + 1);
+            // The last world that holds the components drops them in place.
+            copies[2].despawn(tiny);
+            assert_eq!(LIVE.get(), live);
+
+            drop(copies.pop());
+            assert_eq!(LIVE.get(), live);
+            assert_eq!(*copies[0].get_component::<Counted>(tiny).unwrap().0, 101);
+        }
+        assert_eq!(LIVE.get(), 0);
+    }
+}
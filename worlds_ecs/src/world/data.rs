@@ -1,12 +1,47 @@
 #[allow(unused_imports)] // For the docs
 use crate::world::World;
-use bevy_ptr::OwningPtr;
-use std::{alloc::Layout, any::type_name};
+use crate::world::{
+    archive::Archivable,
+    shared::{drop_shared, eq_shared, hash_shared, SharedHandle},
+};
+use crate::{
+    component::{component_info, Component, StorageDrop},
+    utils::panics,
+};
+use bevy_ptr::{OwningPtr, Ptr};
+use std::{
+    alloc::Layout,
+    any::type_name,
+    hash::{Hash, Hasher},
+    mem::size_of,
+};
 
 /// Piece of Data in the [`World`]
 pub trait Data: 'static + Send + Sync {}
 
+/// A type-erased function that clones a piece of [`Data`]: it's called with a [`Ptr`] to the data, and a function
+/// that takes the clone (see [`DataInfo::clone_fn`]).
+pub type CloneFn = unsafe fn(Ptr<'_>, &mut dyn FnMut(OwningPtr<'_>));
+
+/// A type-erased function that creates the default value of a piece of [`Data`]: it calls the function it's given
+/// with the new value (see [`DataInfo::default_fn`]).
+pub type DefaultFn = fn(&mut dyn FnMut(OwningPtr<'_>));
+
+/// A type-erased function that serializes a piece of [`Data`]: it's called with a [`Ptr`] to the data, and appends
+/// its bytes to the buffer (see [`DataInfo::archive_fns`]).
+pub type ArchiveFn = unsafe fn(Ptr<'_>, &mut Vec<u8>);
+
+/// A type-erased function that deserializes a piece of [`Data`] from the bytes that its [`ArchiveFn`] wrote: it calls
+/// the function it's given with the restored value, or returns `false` if the bytes are malformed
+/// (see [`DataInfo::archive_fns`]).
+pub type RestoreFn = fn(&[u8], &mut dyn FnMut(OwningPtr<'_>)) -> bool;
+
+/// A type-erased function that hashes a piece of [`Data`]: it's called with a [`Ptr`] to the data, and feeds it to
+/// the hasher (see [`DataInfo::hash_fn`]).
+pub type HashFn = unsafe fn(Ptr<'_>, &mut dyn Hasher);
+
 #[allow(unused)]
+#[derive(Clone)]
 /// Information for a data. Some of it is critical for storage, such as the memory [`Layout`], some is less important, like the name.
 pub struct DataInfo {
     /// The name of the [`Data`].
@@ -17,27 +52,486 @@ pub struct DataInfo {
     /// it is represented in this function. The function takes an [`OwningPtr`] to this data, which is
     /// guarenteed to match the data's type.
     drop_fn: Option<unsafe fn(OwningPtr<'_>)>,
+    /// If the data can be compared for equality, it is represented in this function. The function takes two
+    /// [`Ptr`]s to this data, which are guarenteed to match the data's type.
+    eq_fn: Option<unsafe fn(Ptr<'_>, Ptr<'_>) -> bool>,
+    /// If the data can be cloned, it is represented in this function. See [`CloneFn`].
+    clone_fn: Option<CloneFn>,
+    /// If the data has a default value, it is represented in this function. See [`DefaultFn`].
+    default_fn: Option<DefaultFn>,
+    /// If the data can be archived, it is represented in these functions. See [`ArchiveFn`] and [`RestoreFn`].
+    archive_fns: Option<(ArchiveFn, RestoreFn)>,
+    /// If the data can be hashed, it is represented in this function. See [`HashFn`].
+    hash_fn: Option<HashFn>,
+    /// Whether every byte of the data is initialized, so it can be archived and hashed by its bytes.
+    pod: bool,
+    /// Whether the data can be mutated through a shared reference, like an atomic or a `Mutex`.
+    interior_mutable: bool,
+    /// The identity of the [`Data`] that stays the same across reloads of the code that defines it.
+    stable_key: StableComponentKey,
+    /// Whether the data is stored behind a [`Box`], so [`Self::layout`] is the layout of the box, and the functions
+    /// are called with pointers to the box (see [`Self::boxed_for`]).
+    boxed: bool,
+    /// Whether the data is stored as a handle to a shared value, so [`Self::layout`] is the layout of the handle, and
+    /// the functions are called with pointers to the handle (see [`Component::SHARED`](crate::prelude::Component::SHARED)).
+    shared: bool,
+}
+
+/// A [`DataInfo`] with a custom name that is being built, see [`DataInfo::named`]. It's turned into a [`DataInfo`]
+/// with [`Self::build`] (or [`Into`]), which [`ComponentFactory::register_component_from_data`] does by itself.
+///
+/// [`ComponentFactory::register_component_from_data`]: crate::prelude::ComponentFactory::register_component_from_data
+#[derive(Debug, Clone, Copy)]
+pub struct DataInfoBuilder {
+    name: &'static str,
+    layout: Layout,
+    drop_fn: Option<unsafe fn(OwningPtr<'_>)>,
+}
+
+impl DataInfoBuilder {
+    /// Set the memory layout of the data.
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set the type-erased drop function of the data. The function must be safe to call with an [`OwningPtr`] to
+    /// the data.
+    pub fn drop(mut self, drop_fn: unsafe fn(OwningPtr<'_>)) -> Self {
+        self.drop_fn = Some(drop_fn);
+        self
+    }
+
+    /// Finish building the [`DataInfo`]. Its other functions can be set on it, like with [`DataInfo::with_clone_fn`].
+    pub fn build(self) -> DataInfo {
+        DataInfo::new(self.name, self.layout, self.drop_fn)
+    }
 }
 
-unsafe fn drop_data<T: Data>(ptr: OwningPtr<'_>) {
+impl From<DataInfoBuilder> for DataInfo {
+    fn from(builder: DataInfoBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// An identity of a piece of [`Data`] that (unlike its [`TypeId`](std::any::TypeId)) stays the same when the code
+/// that defines it is reloaded, for example when hot-reloading a game's dynamic library.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StableComponentKey {
+    /// Identified by the name of the type.
+    Name(Box<str>),
+    /// Identified by a hash of the type's schema, computed by the user.
+    SchemaHash(u64),
+}
+
+unsafe fn drop_data<T>(ptr: OwningPtr<'_>) {
     OwningPtr::drop_as::<T>(ptr)
 }
 
+unsafe fn eq_data<T: PartialEq>(a: Ptr<'_>, b: Ptr<'_>) -> bool {
+    a.deref::<T>() == b.deref::<T>()
+}
+
+unsafe fn storage_drop<C: StorageDrop>(ptr: OwningPtr<'_>) {
+    ptr.read::<C>().storage_drop()
+}
+
+unsafe fn storage_drop_boxed<C: StorageDrop>(ptr: OwningPtr<'_>) {
+    ptr.read::<Box<C>>().storage_drop()
+}
+
+unsafe fn hash_data<T: Hash>(ptr: Ptr<'_>, mut state: &mut dyn Hasher) {
+    ptr.deref::<T>().hash(&mut state)
+}
+
+unsafe fn clone_data<T: Clone>(ptr: Ptr<'_>, f: &mut dyn FnMut(OwningPtr<'_>)) {
+    OwningPtr::make(ptr.deref::<T>().clone(), f)
+}
+
+fn default_data<T: Default>(f: &mut dyn FnMut(OwningPtr<'_>)) {
+    OwningPtr::make(T::default(), f)
+}
+
+unsafe fn archive_data<T: Archivable>(ptr: Ptr<'_>, bytes: &mut Vec<u8>) {
+    ptr.deref::<T>().archive(bytes)
+}
+
+unsafe fn archive_boxed<T: Archivable>(ptr: Ptr<'_>, bytes: &mut Vec<u8>) {
+    ptr.deref::<Box<T>>().archive(bytes)
+}
+
+fn restore_boxed<T: Archivable>(bytes: &[u8], f: &mut dyn FnMut(OwningPtr<'_>)) -> bool {
+    T::restore(bytes)
+        .map(|data| OwningPtr::make(Box::new(data), f))
+        .is_some()
+}
+
+fn restore_data<T: Archivable>(bytes: &[u8], f: &mut dyn FnMut(OwningPtr<'_>)) -> bool {
+    T::restore(bytes)
+        .map(|data| OwningPtr::make(data, f))
+        .is_some()
+}
+
+/// # Safety
+/// Every byte of `T` must be initialized (it can't have padding).
+unsafe fn archive_pod<T: Data + Copy>(ptr: Ptr<'_>, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(std::slice::from_raw_parts(ptr.as_ptr(), size_of::<T>()))
+}
+
+/// # Safety
+/// Every byte of `T` must be initialized (it can't have padding).
+unsafe fn hash_pod<T: Data + Copy>(ptr: Ptr<'_>, state: &mut dyn Hasher) {
+    state.write(std::slice::from_raw_parts(ptr.as_ptr(), size_of::<T>()))
+}
+
+fn restore_pod<T: Data + Copy>(bytes: &[u8], f: &mut dyn FnMut(OwningPtr<'_>)) -> bool {
+    if bytes.len() != size_of::<T>() {
+        return false;
+    }
+    // SAFETY: The bytes were copied from a `T` by `archive_pod`.
+    let data = unsafe { bytes.as_ptr().cast::<T>().read_unaligned() };
+    OwningPtr::make(data, f);
+    true
+}
+
+/// # Safety
+/// Every byte of `T` must be initialized (it can't have padding).
+unsafe fn archive_pod_boxed<T: Data + Copy>(ptr: Ptr<'_>, bytes: &mut Vec<u8>) {
+    let data: &T = ptr.deref::<Box<T>>();
+    bytes.extend_from_slice(std::slice::from_raw_parts(
+        (data as *const T).cast::<u8>(),
+        size_of::<T>(),
+    ))
+}
+
+/// # Safety
+/// Every byte of `T` must be initialized (it can't have padding).
+unsafe fn hash_pod_boxed<T: Data + Copy>(ptr: Ptr<'_>, state: &mut dyn Hasher) {
+    let data: &T = ptr.deref::<Box<T>>();
+    state.write(std::slice::from_raw_parts(
+        (data as *const T).cast::<u8>(),
+        size_of::<T>(),
+    ))
+}
+
+fn restore_pod_boxed<T: Data + Copy>(bytes: &[u8], f: &mut dyn FnMut(OwningPtr<'_>)) -> bool {
+    if bytes.len() != size_of::<T>() {
+        return false;
+    }
+    // SAFETY: The bytes were copied from a `T` by `archive_pod_boxed`.
+    let data = unsafe { bytes.as_ptr().cast::<T>().read_unaligned() };
+    OwningPtr::make(Box::new(data), f);
+    true
+}
+
 impl DataInfo {
-    /// Create a new [`DataInfo`] for a value based on its default values.
-    pub fn deafult_for<T: Data>() -> Self {
+    /// Create a new [`DataInfo`] for a value based on its default values. It's named after the type of `T`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// struct Health(u32);
+    /// impl Data for Health {}
+    ///
+    /// let info = DataInfo::default_for::<Health>();
+    /// assert_eq!(info.name(), std::any::type_name::<Health>());
+    /// assert_eq!(info.layout(), std::alloc::Layout::new::<Health>());
+    /// assert!(info.drop_fn().is_some());
+    /// ```
+    pub fn default_for<T: Data>() -> Self {
         Self {
             name: type_name::<T>(),
             layout: Layout::new::<T>(),
             drop_fn: Some(drop_data::<T>),
+            eq_fn: None,
+            clone_fn: None,
+            default_fn: None,
+            archive_fns: None,
+            hash_fn: None,
+            pod: false,
+            interior_mutable: false,
+            stable_key: StableComponentKey::Name(type_name::<T>().into()),
+            boxed: false,
+            shared: false,
+        }
+    }
+
+    /// Create a new [`DataInfo`] for a value based on its default values.
+    #[deprecated(note = "renamed to `DataInfo::default_for`")]
+    pub fn deafult_for<T: Data>() -> Self {
+        Self::default_for::<T>()
+    }
+
+    /// Start building a [`DataInfo`] with a custom name, for data that isn't described by a Rust type, or that
+    /// should be presented with a human-readable name (like the components of scripts). The data is zero-sized, and
+    /// isn't dropped, unless its [`layout`](DataInfoBuilder::layout) and [`drop`](DataInfoBuilder::drop) function
+    /// are set.
+    ///
+    /// ```
+    /// use std::{alloc::Layout, any::TypeId};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let info = DataInfo::named("Health")
+    ///     .layout(Layout::new::<Health>())
+    ///     .drop(DataInfo::for_component::<Health>().drop_fn().unwrap());
+    /// // SAFETY: The layout and the drop function are of `Health`.
+    /// unsafe { components.register_component_from_data(TypeId::of::<Health>(), info) }.unwrap();
+    /// assert_eq!(components.get_component_info::<Health>().unwrap().name(), "Health");
+    /// ```
+    pub fn named(name: &'static str) -> DataInfoBuilder {
+        DataInfoBuilder {
+            name,
+            layout: Layout::new::<()>(),
+            drop_fn: None,
         }
     }
 
+    /// Create a new [`DataInfo`] for a value that is stored behind a [`Box`], based on its default values. It has the
+    /// name of `T`, but the layout of `Box<T>`, so moving it only moves a pointer, and its functions (including the
+    /// ones that are set later, like with [`ComponentFactory::register_comparable_component`]) go through the box.
+    ///
+    /// [`ComponentFactory::register_comparable_component`]: crate::prelude::ComponentFactory::register_comparable_component
+    pub fn boxed_for<T: Data>() -> Self {
+        Self {
+            layout: Layout::new::<Box<T>>(),
+            drop_fn: Some(drop_data::<Box<T>>),
+            boxed: true,
+            ..Self::default_for::<T>()
+        }
+    }
+
+    /// Create the default [`DataInfo`] of a component: stored inline, behind a [`Box`] if it's boxed (see
+    /// [`Component::BOXED`]), or as a handle if it's shared (see [`Component::SHARED`]).
+    pub fn for_component<C: Component>() -> Self {
+        component_info::<C>()
+    }
+
+    /// Drop the stored values of the component with its [`StorageDrop`] implementation, instead of in place.
+    ///
+    /// # Panics
+    /// If the component is shared (see [`Component::SHARED`]).
+    #[track_caller]
+    pub fn with_storage_drop<C: StorageDrop>(mut self) -> Self {
+        self.fail_shared("set storage drop");
+        self.drop_fn = Some(match self.boxed {
+            true => storage_drop_boxed::<C>,
+            false => storage_drop::<C>,
+        });
+        self
+    }
+
+    /// Returns `true` if the data is stored behind a [`Box`] (see [`Self::boxed_for`]).
+    pub fn is_boxed(&self) -> bool {
+        self.boxed
+    }
+
+    /// Create a new [`DataInfo`] for the values of a shared component, which are stored as handles to the interned
+    /// values. It has the name of `T`, but the layout of the handle, and it can always be cloned, which clones the
+    /// handle (see [`Component::SHARED`](crate::prelude::Component::SHARED)).
+    pub(crate) fn shared_for<T: Data>() -> Self {
+        Self {
+            layout: Layout::new::<SharedHandle<T>>(),
+            drop_fn: Some(drop_shared::<T>),
+            clone_fn: Some(clone_data::<SharedHandle<T>>),
+            shared: true,
+            ..Self::default_for::<T>()
+        }
+    }
+
+    /// Returns `true` if the data is stored as a handle to a shared value (see
+    /// [`Component::SHARED`]).
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Panic because the operation isn't supported for the values of shared components.
+    #[track_caller]
+    fn fail_shared(&self, operation: &str) {
+        if self.shared {
+            panics::fail_component(
+                operation,
+                "the component is shared, and its values can only be made by interning them in the world",
+                self.name,
+            )
+        }
+    }
+
+    /// Create a new [`DataInfo`] for a value that can be compared for equality, based on its default values.
+    pub fn comparable_for<T: Data + PartialEq>() -> Self {
+        Self {
+            eq_fn: Some(eq_data::<T>),
+            ..Self::default_for::<T>()
+        }
+    }
+
+    /// Set the type-erased equality function of this [`Data`]. The function must be safe to call with
+    /// two [`Ptr`]s to this data.
+    pub fn with_eq_fn(mut self, eq_fn: unsafe fn(Ptr<'_>, Ptr<'_>) -> bool) -> Self {
+        self.eq_fn = Some(eq_fn);
+        self
+    }
+
     /// Get this [`Data`]'s type-erased drop function
     pub fn drop_fn(&self) -> Option<unsafe fn(OwningPtr<'_>)> {
         self.drop_fn
     }
 
+    /// Set the [`StableComponentKey`] of this [`Data`] (by default, it's identified by its name).
+    pub fn with_stable_key(mut self, stable_key: StableComponentKey) -> Self {
+        self.stable_key = stable_key;
+        self
+    }
+
+    /// Get this [`Data`]'s [`StableComponentKey`]
+    pub fn stable_key(&self) -> &StableComponentKey {
+        &self.stable_key
+    }
+
+    /// Make this [`Data`] comparable, using the [`PartialEq`] implementation of `T`.
+    pub(crate) fn set_comparable<T: Data + PartialEq>(&mut self) {
+        self.eq_fn = Some(match (self.shared, self.boxed) {
+            (true, _) => eq_shared::<T>,
+            (false, true) => eq_data::<Box<T>>,
+            (false, false) => eq_data::<T>,
+        });
+    }
+
+    /// Make this [`Data`] cloneable, using the [`Clone`] implementation of `T`.
+    pub(crate) fn set_cloneable<T: Data + Clone>(&mut self) {
+        if self.shared {
+            // Cloning the handle shares the value.
+            return;
+        }
+        self.clone_fn = Some(match self.boxed {
+            true => clone_data::<Box<T>>,
+            false => clone_data::<T>,
+        });
+    }
+
+    /// Give this [`Data`] a default value, using the [`Default`] implementation of `T`.
+    #[track_caller]
+    pub(crate) fn set_default<T: Data + Default>(&mut self) {
+        self.fail_shared("set default");
+        self.default_fn = Some(match self.boxed {
+            true => default_data::<Box<T>>,
+            false => default_data::<T>,
+        });
+    }
+
+    /// Make this [`Data`] archivable, using the [`Archivable`] implementation of `T`.
+    #[track_caller]
+    pub(crate) fn set_archivable<T: Archivable>(&mut self) {
+        self.fail_shared("set archivable");
+        self.archive_fns = Some(match self.boxed {
+            true => (archive_boxed::<T>, restore_boxed::<T>),
+            false => (archive_data::<T>, restore_data::<T>),
+        });
+    }
+
+    /// Make this [`Data`] archivable by copying its bytes, and hashable by its bytes (unless it can already be
+    /// hashed, see [`Self::set_hashable`]).
+    ///
+    /// # Safety
+    /// Every byte of `T` must be initialized (it can't have padding).
+    #[track_caller]
+    pub(crate) unsafe fn set_pod<T: Data + Copy>(&mut self) {
+        self.fail_shared("set pod");
+        self.archive_fns = Some(match self.boxed {
+            true => (archive_pod_boxed::<T>, restore_pod_boxed::<T>),
+            false => (archive_pod::<T>, restore_pod::<T>),
+        });
+        self.hash_fn.get_or_insert(match self.boxed {
+            true => hash_pod_boxed::<T>,
+            false => hash_pod::<T>,
+        });
+        self.pod = true;
+    }
+
+    /// Make this [`Data`] hashable, using the [`Hash`] implementation of `T`.
+    pub(crate) fn set_hashable<T: Data + Hash>(&mut self) {
+        self.hash_fn = Some(match (self.shared, self.boxed) {
+            (true, _) => hash_shared::<T>,
+            (false, true) => hash_data::<Box<T>>,
+            (false, false) => hash_data::<T>,
+        });
+    }
+
+    /// Set the type-erased clone function of this [`Data`]. The function must be safe to call with a [`Ptr`]
+    /// to this data, and it must call the function it's given with an [`OwningPtr`] to a clone of the data.
+    pub fn with_clone_fn(mut self, clone_fn: CloneFn) -> Self {
+        self.clone_fn = Some(clone_fn);
+        self
+    }
+
+    /// Get this [`Data`]'s type-erased clone function, if it can be cloned.
+    pub fn clone_fn(&self) -> Option<CloneFn> {
+        self.clone_fn
+    }
+
+    /// Set the type-erased default function of this [`Data`]. The function must call the function it's given
+    /// with an [`OwningPtr`] to a new value of this data.
+    pub fn with_default_fn(mut self, default_fn: DefaultFn) -> Self {
+        self.default_fn = Some(default_fn);
+        self
+    }
+
+    /// Get this [`Data`]'s type-erased default function, if it has a default value.
+    pub fn default_fn(&self) -> Option<DefaultFn> {
+        self.default_fn
+    }
+
+    /// Set the type-erased functions that archive this [`Data`] and restore it. The [`ArchiveFn`] must be safe to call
+    /// with a [`Ptr`] to this data, and the [`RestoreFn`] must call the function it's given with an [`OwningPtr`] to
+    /// this data (it's only called with bytes that the [`ArchiveFn`] wrote).
+    pub fn with_archive_fns(mut self, archive_fn: ArchiveFn, restore_fn: RestoreFn) -> Self {
+        self.archive_fns = Some((archive_fn, restore_fn));
+        self
+    }
+
+    /// Get this [`Data`]'s type-erased archive and restore functions, if it can be archived
+    /// (see [`World::archive`]).
+    pub fn archive_fns(&self) -> Option<(ArchiveFn, RestoreFn)> {
+        self.archive_fns
+    }
+
+    /// Set the type-erased hash function of this [`Data`]. The function must be safe to call with a [`Ptr`] to this
+    /// data, and it must feed the same bytes to the hasher on every platform (see [`World::checksum`]).
+    pub fn with_hash_fn(mut self, hash_fn: HashFn) -> Self {
+        self.hash_fn = Some(hash_fn);
+        self
+    }
+
+    /// Get this [`Data`]'s type-erased hash function, if it can be hashed.
+    pub fn hash_fn(&self) -> Option<HashFn> {
+        self.hash_fn
+    }
+
+    /// Returns `true` if every byte of the data is initialized, so it's archived and hashed by its bytes (see
+    /// [`ComponentFactory::register_pod_component`](crate::prelude::ComponentFactory::register_pod_component)).
+    pub fn is_pod(&self) -> bool {
+        self.pod
+    }
+
+    /// Mark this [`Data`] as mutable through a shared reference.
+    pub(crate) fn set_interior_mutable(&mut self) {
+        self.interior_mutable = true;
+    }
+
+    /// Returns `true` if the data can be mutated through a shared reference (see
+    /// [`ComponentFactory::register_interior_mutable_component`](crate::prelude::ComponentFactory::register_interior_mutable_component)).
+    pub fn is_interior_mutable(&self) -> bool {
+        self.interior_mutable
+    }
+
+    /// Get this [`Data`]'s type-erased equality function, if it can be compared.
+    pub fn eq_fn(&self) -> Option<unsafe fn(Ptr<'_>, Ptr<'_>) -> bool> {
+        self.eq_fn
+    }
+
     /// Get this [`Data`]'s memory layout
     pub fn layout(&self) -> Layout {
         self.layout
@@ -57,7 +551,17 @@ impl DataInfo {
         Self {
             layout,
             drop_fn,
+            eq_fn: None,
+            clone_fn: None,
+            default_fn: None,
+            archive_fns: None,
+            hash_fn: None,
+            pod: false,
+            interior_mutable: false,
+            stable_key: StableComponentKey::Name(name.into()),
             name,
+            boxed: false,
+            shared: false,
         }
     }
 }
@@ -0,0 +1,423 @@
+use super::World;
+use crate::{
+    entity::EntityId,
+    prelude::{ArchQuery, Component},
+};
+use std::{
+    any::{type_name, TypeId},
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+/// The source components of a derived component, see [`World::register_derived`].
+/// This is implemented for a single [`Component`], and for tuples of up to 4 components.
+pub trait DerivedSources: 'static {
+    /// The query that fetches the sources.
+    type Query: ArchQuery;
+    /// The values of the sources that are passed to the computation, and that are remembered to detect changes.
+    type Values: PartialEq + Send + Sync + 'static;
+
+    /// The [`TypeId`]s of the source components.
+    fn type_ids() -> Vec<TypeId>;
+
+    /// Copy the values of the sources out of the query's item.
+    fn values(item: <Self::Query as ArchQuery>::Item<'_>) -> Self::Values;
+}
+
+impl<S: Component + Clone + PartialEq> DerivedSources for S {
+    type Query = &'static S;
+    type Values = S;
+
+    fn type_ids() -> Vec<TypeId> {
+        vec![TypeId::of::<S>()]
+    }
+
+    fn values(item: &S) -> S {
+        item.clone()
+    }
+}
+
+macro_rules! impl_derived_sources_for_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($name: Component + Clone + PartialEq),*> DerivedSources for ($($name,)*) {
+            type Query = ($(&'static $name,)*);
+            type Values = ($($name,)*);
+
+            fn type_ids() -> Vec<TypeId> {
+                vec![$(TypeId::of::<$name>()),*]
+            }
+
+            fn values(($($name,)*): ($(&$name,)*)) -> Self::Values {
+                ($($name.clone(),)*)
+            }
+        }
+    };
+}
+
+impl_derived_sources_for_tuple!(S1, S2);
+impl_derived_sources_for_tuple!(S1, S2, S3);
+impl_derived_sources_for_tuple!(S1, S2, S3, S4);
+
+/// An error when registering a derived component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DerivedRegistrationError {
+    /// The derived component is (directly or transitively) one of its own sources.
+    Cycle {
+        /// The name of the derived component.
+        derived: &'static str,
+    },
+    /// The component is already derived from other sources.
+    AlreadyDerived {
+        /// The name of the derived component.
+        derived: &'static str,
+    },
+}
+
+impl fmt::Display for DerivedRegistrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DerivedRegistrationError::Cycle { derived } => {
+                write!(f, "deriving `{derived}` would create a dependency cycle")
+            }
+            DerivedRegistrationError::AlreadyDerived { derived } => {
+                write!(f, "`{derived}` is already derived from other components")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DerivedRegistrationError {}
+
+/// A type-erased derivation, so derivations with different sources can be stored together.
+trait Derivation: Send + Sync {
+    /// Recompute the derived component of every entity whose sources changed. Returns how many were recomputed.
+    fn refresh(&mut self, world: &mut World) -> usize;
+}
+
+struct DerivationOf<S: DerivedSources, D: Component, F> {
+    compute: F,
+    /// The values of the sources when the derived component was last computed, for each entity.
+    last_values: HashMap<EntityId, S::Values>,
+    _derived: std::marker::PhantomData<fn() -> D>,
+}
+
+impl<S, D, F> Derivation for DerivationOf<S, D, F>
+where
+    S: DerivedSources,
+    D: Component,
+    F: Fn(&S::Values) -> D + Send + Sync + 'static,
+{
+    fn refresh(&mut self, world: &mut World) -> usize {
+        if !<(S::Query, &D)>::is_resolvable(&world.components) {
+            return 0;
+        }
+        let mut recomputed = 0;
+        let mut seen = 0;
+        for (entity, sources, derived) in world.query::<(EntityId, S::Query, &mut D)>() {
+            seen += 1;
+            let values = S::values(sources);
+            if self.last_values.get(&entity) == Some(&values) {
+                continue;
+            }
+            *derived = (self.compute)(&values);
+            self.last_values.insert(entity, values);
+            recomputed += 1;
+        }
+        // Forget the entities that were despawned (or lost a component) since the last refresh.
+        if self.last_values.len() > seen {
+            let alive: HashSet<EntityId> = world
+                .query::<(EntityId, S::Query, &D)>()
+                .map(|(entity, _, _)| entity)
+                .collect();
+            self.last_values.retain(|entity, _| alive.contains(entity));
+        }
+        recomputed
+    }
+}
+
+/// A registered derivation, alongside the [`TypeId`]s of its derived component and of its sources.
+struct DerivationEntry {
+    derived: TypeId,
+    sources: Vec<TypeId>,
+    derivation: Box<dyn Derivation>,
+}
+
+/// The derived components that are registered in a [`World`], in an order where every derived component is
+/// computed after its sources.
+#[derive(Default)]
+pub(crate) struct DerivedRegistry {
+    entries: Vec<DerivationEntry>,
+}
+
+impl DerivedRegistry {
+    /// Returns `true` if no derived components are registered.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns `true` if `from` is `to`, or if `to` is (transitively) derived from `from`.
+    fn reaches(&self, from: TypeId, to: TypeId) -> bool {
+        let mut stack = vec![from];
+        let mut visited = HashSet::new();
+        while let Some(current) = stack.pop() {
+            if current == to {
+                return true;
+            }
+            if !visited.insert(current) {
+                continue;
+            }
+            stack.extend(
+                self.entries
+                    .iter()
+                    .filter(|entry| entry.sources.contains(&current))
+                    .map(|entry| entry.derived),
+            );
+        }
+        false
+    }
+
+    fn register<S, D>(
+        &mut self,
+        compute: impl Fn(&S::Values) -> D + Send + Sync + 'static,
+    ) -> Result<(), DerivedRegistrationError>
+    where
+        S: DerivedSources,
+        D: Component,
+    {
+        let derived = TypeId::of::<D>();
+        let sources = S::type_ids();
+        if self.entries.iter().any(|entry| entry.derived == derived) {
+            return Err(DerivedRegistrationError::AlreadyDerived {
+                derived: type_name::<D>(),
+            });
+        }
+        if sources.iter().any(|source| self.reaches(derived, *source)) {
+            return Err(DerivedRegistrationError::Cycle {
+                derived: type_name::<D>(),
+            });
+        }
+        self.entries.push(DerivationEntry {
+            derived,
+            sources,
+            derivation: Box::new(DerivationOf::<S, D, _> {
+                compute,
+                last_values: HashMap::new(),
+                _derived: std::marker::PhantomData,
+            }),
+        });
+        self.sort_by_dependencies();
+        Ok(())
+    }
+
+    /// Order the entries so every entry comes after the entries that derive its sources. Entries that don't
+    /// depend on each other keep their relative order.
+    fn sort_by_dependencies(&mut self) {
+        let mut remaining = std::mem::take(&mut self.entries);
+        while !remaining.is_empty() {
+            // There are no cycles, so some entry doesn't depend on any of the remaining entries.
+            let next = remaining
+                .iter()
+                .position(|entry| {
+                    !remaining
+                        .iter()
+                        .any(|other| entry.sources.contains(&other.derived))
+                })
+                .expect("Derived components can't have dependency cycles");
+            self.entries.push(remaining.remove(next));
+        }
+    }
+}
+
+impl World {
+    /// Register a component `D` that is derived from the source component(s) `S`, using `compute`.
+    /// `S` is either a single component (and `compute` takes `&S`), or a tuple of up to 4 components
+    /// (and `compute` takes a reference to a tuple of their values). The sources must be [`Clone`] and [`PartialEq`].
+    ///
+    /// The derived components are recomputed by [`World::refresh_derived`]. Registering a derivation that
+    /// would make a component (transitively) derived from itself, or a second derivation for the same component,
+    /// is rejected.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, PartialEq)]
+    /// struct Health(u32);
+    /// #[derive(Component, Clone, PartialEq)]
+    /// struct Armor(u32);
+    /// #[derive(Component)]
+    /// struct Toughness(u32);
+    ///
+    /// let mut world = World::default();
+    /// world
+    ///     .register_derived::<(Health, Armor), Toughness>(|(health, armor)| Toughness(health.0 + armor.0))
+    ///     .unwrap();
+    /// // A second derivation of the same component is rejected.
+    /// assert!(world.register_derived::<Health, Toughness>(|health| Toughness(health.0)).is_err());
+    /// let entity = world.spawn((Health(10), Armor(5), Toughness(0)));
+    /// world.refresh_derived();
+    /// assert_eq!(world.get_component::<Toughness>(entity).unwrap().0, 15);
+    /// ```
+    pub fn register_derived<S: DerivedSources, D: Component>(
+        &mut self,
+        compute: impl Fn(&S::Values) -> D + Send + Sync + 'static,
+    ) -> Result<(), DerivedRegistrationError> {
+        self.derived.register::<S, D>(compute)
+    }
+
+    /// Recompute the derived components (see [`World::register_derived`]) of the entities whose sources changed
+    /// since the last refresh, in dependency order. Returns how many derived components were recomputed.
+    ///
+    /// Only entities that already have both the sources and the derived component are refreshed:
+    /// the derived component isn't inserted on entities that lack it. Changes are detected by comparing the
+    /// sources with their values from the last refresh, so setting a source to an equal value isn't a change.
+    /// The first refresh of an entity always computes its derived component.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, PartialEq)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct IsAlive(bool);
+    ///
+    /// let mut world = World::default();
+    /// world.register_derived::<Health, IsAlive>(|health| IsAlive(health.0 > 0)).unwrap();
+    /// let entity = world.spawn((Health(10), IsAlive(false)));
+    /// assert_eq!(world.refresh_derived(), 1);
+    /// assert!(world.get_component::<IsAlive>(entity).unwrap().0);
+    /// // Nothing changed since the last refresh.
+    /// assert_eq!(world.refresh_derived(), 0);
+    /// ```
+    pub fn refresh_derived(&mut self) -> usize {
+        let mut derived = std::mem::take(&mut self.derived);
+        let recomputed = derived
+            .entries
+            .iter_mut()
+            .map(|entry| entry.derivation.refresh(self))
+            .sum();
+        self.derived = derived;
+        recomputed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DerivedRegistrationError;
+    use crate::prelude::*;
+
+    #[derive(Component, Clone, PartialEq, Debug)]
+    struct Position(i32);
+
+    #[derive(Component, Clone, PartialEq, Debug)]
+    struct Offset(i32);
+
+    #[derive(Component, Clone, PartialEq, Debug)]
+    struct GlobalPosition(i32);
+
+    #[derive(Component, Clone, PartialEq, Debug)]
+    struct Doubled(i32);
+
+    #[test]
+    fn test_refresh_derived() {
+        let mut world = World::default();
+        world
+            .register_derived::<Doubled, GlobalPosition>(|doubled| GlobalPosition(doubled.0 + 1))
+            .unwrap();
+        world
+            .register_derived::<(Position, Offset), Doubled>(|(pos, offset)| {
+                Doubled((pos.0 + offset.0) * 2)
+            })
+            .unwrap();
+        let entities: Vec<EntityId> = (0..4)
+            .map(|i| world.spawn((Position(i), Offset(10), Doubled(0), GlobalPosition(0))))
+            .collect();
+        let without_derived = world.spawn((Position(100), Offset(100)));
+
+        // `Doubled` is computed first, even though it was registered second.
+        assert_eq!(world.refresh_derived(), 8);
+        for (i, entity) in entities.iter().enumerate() {
+            let expected = (i as i32 + 10) * 2;
+            assert_eq!(world.get_component::<Doubled>(*entity).unwrap().0, expected);
+            assert_eq!(
+                world.get_component::<GlobalPosition>(*entity).unwrap().0,
+                expected + 1
+            );
+        }
+        assert!(world.get_component::<Doubled>(without_derived).is_none());
+        assert_eq!(world.refresh_derived(), 0);
+
+        // Overwrite derived components whose sources don't change, to prove that they aren't rewritten.
+        // Overwriting `Doubled` is a change of `GlobalPosition`'s source, so that is recomputed.
+        world.get_component_mut::<Doubled>(entities[0]).unwrap().0 = -1;
+        world
+            .get_component_mut::<GlobalPosition>(entities[1])
+            .unwrap()
+            .0 = -1;
+        world.get_component_mut::<Position>(entities[2]).unwrap().0 = 50;
+        world.get_component_mut::<Offset>(entities[3]).unwrap().0 = 10;
+
+        assert_eq!(world.refresh_derived(), 3);
+        assert_eq!(world.get_component::<Doubled>(entities[0]).unwrap().0, -1);
+        assert_eq!(
+            world
+                .get_component::<GlobalPosition>(entities[0])
+                .unwrap()
+                .0,
+            0
+        );
+        assert_eq!(
+            world
+                .get_component::<GlobalPosition>(entities[1])
+                .unwrap()
+                .0,
+            -1
+        );
+        assert_eq!(world.get_component::<Doubled>(entities[1]).unwrap().0, 22);
+        assert_eq!(world.get_component::<Doubled>(entities[2]).unwrap().0, 120);
+        assert_eq!(
+            world
+                .get_component::<GlobalPosition>(entities[2])
+                .unwrap()
+                .0,
+            121
+        );
+        assert_eq!(world.get_component::<Doubled>(entities[3]).unwrap().0, 26);
+    }
+
+    #[test]
+    fn test_derived_cycles() {
+        let mut world = World::default();
+        world
+            .register_derived::<Position, Doubled>(|pos| Doubled(pos.0 * 2))
+            .unwrap();
+        world
+            .register_derived::<Doubled, GlobalPosition>(|doubled| GlobalPosition(doubled.0))
+            .unwrap();
+        assert_eq!(
+            world.register_derived::<GlobalPosition, Position>(|global| Position(global.0)),
+            Err(DerivedRegistrationError::Cycle {
+                derived: std::any::type_name::<Position>()
+            })
+        );
+        assert_eq!(
+            world.register_derived::<(Offset, Offset), Offset>(|(offset, _)| offset.clone()),
+            Err(DerivedRegistrationError::Cycle {
+                derived: std::any::type_name::<Offset>()
+            })
+        );
+        assert_eq!(
+            world.register_derived::<Offset, Doubled>(|offset| Doubled(offset.0)),
+            Err(DerivedRegistrationError::AlreadyDerived {
+                derived: std::any::type_name::<Doubled>()
+            })
+        );
+        // Rejected registrations don't affect the registered ones.
+        world
+            .register_derived::<Offset, Position>(|offset| Position(offset.0))
+            .unwrap();
+        let entity = world.spawn((Offset(3), Position(0), Doubled(0), GlobalPosition(0)));
+        assert_eq!(world.refresh_derived(), 3);
+        assert_eq!(world.get_component::<GlobalPosition>(entity).unwrap().0, 6);
+    }
+}
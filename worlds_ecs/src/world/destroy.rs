@@ -0,0 +1,236 @@
+//! Every entity that is despawned is destroyed by [`World::destroy_entity`] (or, for whole storages that are
+//! cleared at once, by [`World::destroy_cleared`], and for storages that are quarantined by
+//! [`World::check_and_quarantine`], by `World::destroy_quarantined`). The stages of the destruction run in this order:
+//!
+//! 1. Checks: the entity must be alive, and its storage must not be pinned. Nothing changed if they fail.
+//! 2. Components: the components of the entity are dropped (from its storage, from the archive, or from the
+//!    tombstones).
+//! 3. Side tables: the entity is untagged, its component histories are flagged as dead, and it's removed from the
+//!    ordered indexes.
+//! 4. Release: the [`EntityId`] is released to the entity factory, so it's stale from now on, and its
+//!    [`FfiHandle`](crate::world::ffi_handle::FfiHandle) (if it has one) stops resolving.
+//! 5. Userdata: the userdata of the entity is removed, and the cleanups of its keys are called with the stale id.
+//!
+//! Stages 3 to 5 run even if a component panics while it's dropped (the panic is resumed after them), and the userdata of an entity is removed
+//! before any cleanup is called, so a panicking cleanup doesn't leave the other side tables (or the other
+//! cleanups' userdata) referencing a dead entity. Groups aren't purged here: they drop their despawned members
+//! lazily (see [`World::create_group`]).
+use super::{storage::storages::ArchStorageId, World};
+use crate::{entity::EntityId, utils::panics};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+/// Which public operation destroyed an entity, to name it in panic messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DespawnReason {
+    /// [`World::despawn`].
+    Despawn,
+    /// [`World::despawn_matching`].
+    DespawnMatching,
+    /// [`World::clear`].
+    Clear,
+    /// [`World::end_tombstone_frame`].
+    EndTombstoneFrame,
+}
+
+impl DespawnReason {
+    /// The name of the operation.
+    pub(crate) fn operation(self) -> &'static str {
+        match self {
+            DespawnReason::Despawn => "despawn",
+            DespawnReason::DespawnMatching => "despawn_matching",
+            DespawnReason::Clear => "clear",
+            DespawnReason::EndTombstoneFrame => "end_tombstone_frame",
+        }
+    }
+}
+
+impl World {
+    /// Destroy an entity, running every stage of the destruction (see the [module docs](self)).
+    ///
+    /// # Panics
+    /// If the entity isn't alive, or if its storage is pinned (see [`World::pin_storage`]).
+    #[track_caller]
+    pub(crate) fn destroy_entity(&mut self, entity: EntityId, reason: DespawnReason) {
+        let Some(&entity_meta) = self.entities.get_entity_meta(entity) else {
+            panics::fail_entity(reason.operation(), "the entity isn't alive", entity);
+        };
+        if !entity_meta.is_archived() && !entity_meta.is_tombstone() {
+            if let Err(error) = self.check_pin_for_remove(entity_meta.archetype_storage_id) {
+                panics::fail_entity(reason.operation(), error, entity);
+            }
+        }
+        let dropped = catch_unwind(AssertUnwindSafe(|| {
+            if entity_meta.is_archived() {
+                self.archive.remove(entity);
+            } else if entity_meta.is_tombstone() {
+                self.tombstones.remove(entity);
+            } else {
+                self.detach_from_storage(entity_meta);
+            }
+        }));
+        self.purge_and_release(&[entity], dropped);
+    }
+
+    /// Destroy every entity of the storage with the [`ArchStorageId`] `sid` at once, running the stages of the
+    /// destruction (see the [module docs](self)) for all of them together. Returns how many entities were destroyed.
+    ///
+    /// # Panics
+    /// If the storage isn't empty and is pinned (see [`World::pin_storage`]).
+    #[track_caller]
+    pub(crate) fn destroy_cleared(&mut self, sid: ArchStorageId, reason: DespawnReason) -> usize {
+        let storage = self.storages.arch_storages.get_storage_mut(sid).unwrap();
+        if storage.is_pinned() && !storage.is_empty() {
+            panics::fail(reason.operation(), self.storage_pinned(sid), &[]);
+        }
+        let entities = storage.entities().to_vec();
+        let dropped = catch_unwind(AssertUnwindSafe(|| {
+            self.storages
+                .arch_storages
+                .get_storage_mut(sid)
+                .unwrap()
+                .clear();
+        }));
+        self.purge_and_release(&entities, dropped);
+        entities.len()
+    }
+
+    /// Destroy the entities of a storage that was quarantined (see [`World::check_and_quarantine`]), running the
+    /// stages of the destruction after the components (see the [module docs](self)). Their components aren't
+    /// dropped: the storage was already detached from the world.
+    pub(crate) fn destroy_quarantined(&mut self, entities: &[EntityId]) {
+        self.purge_and_release(entities, Ok(()));
+    }
+
+    /// The stages of the destruction that come after the components of the entities were dropped (or panicked
+    /// while they were dropped, in which case the panic is resumed at the end).
+    fn purge_and_release(&mut self, entities: &[EntityId], dropped: std::thread::Result<()>) {
+        for entity in entities {
+            self.storages
+                .tag_storage
+                .untag_all(self.entities.dense_index(*entity));
+            self.histories.despawned(*entity);
+            self.ordered.removed(*entity);
+        }
+        if let [entity] = entities {
+            self.entities.remove_entity(*entity);
+        } else {
+            self.entities.remove_many(entities);
+        }
+        self.ffi_handles.despawned(entities);
+        self.userdata.despawned(entities);
+        if let Err(payload) = dropped {
+            resume_unwind(payload);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::{
+        panic::{catch_unwind, AssertUnwindSafe},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    const KEY: UserdataKey = UserdataKey::Named("script");
+
+    #[derive(Component, Clone, Copy)]
+    struct A(#[allow(dead_code)] u32);
+    #[derive(Component, Clone)]
+    struct B;
+    #[derive(Tag)]
+    struct Red;
+
+    /// A world whose entities have userdata, a tag, and a recorded history, with a cleanup that counts the
+    /// userdata it was called with, and panics on the userdata `0`.
+    fn world(cleanups: Arc<AtomicUsize>) -> (World, Vec<EntityId>) {
+        let mut tags = TagFactory::default();
+        tags.register_tag::<Red>();
+        let mut world = World::with_tags(tags);
+        // SAFETY: `A` is plain data, so it can be archived by copying its bytes.
+        unsafe { world.register_pod_component::<A>() };
+        world.set_userdata_cleanup(KEY, move |_, userdata| {
+            cleanups.fetch_add(1, Ordering::Relaxed);
+            if userdata.downcast_ref::<u32>() == Some(&0) {
+                panic!("cleanup panicked");
+            }
+        });
+        let entities: Vec<EntityId> = (0..6u32)
+            .map(|i| {
+                let entity = if i % 2 == 0 {
+                    world.spawn(A(i))
+                } else {
+                    world.spawn((A(i), B))
+                };
+                world.set_userdata(entity, KEY, Box::new(i + 1));
+                unsafe { world.get_tag_tracker(entity).unwrap().tag::<Red>() };
+                world.record_history::<A>(entity, 4);
+                entity
+            })
+            .collect();
+        world.archive(entities[4]).unwrap();
+        (world, entities)
+    }
+
+    /// Panic if a despawned entity is left in a side table.
+    fn assert_destroyed(world: &World, entity: EntityId) {
+        assert!(world.entities.get_entity_meta(entity).is_none());
+        assert!(world.get_userdata(entity, KEY).is_none());
+        assert!(world.history::<A>(entity).unwrap().is_dead());
+        assert!(!unsafe {
+            world
+                .storages
+                .tag_storage
+                .get_tag_tracker(world.entities.dense_index(entity))
+                .unwrap()
+                .is_tagged::<Red>()
+        });
+    }
+
+    #[test]
+    fn test_every_despawn_goes_through_the_pipeline() {
+        let cleanups = Arc::new(AtomicUsize::new(0));
+        let (mut world, entities) = world(cleanups.clone());
+        world.despawn(entities[0]);
+        assert_destroyed(&world, entities[0]);
+        assert_eq!(world.despawn_matching::<Has<B>>(), 3);
+        assert_eq!(cleanups.load(Ordering::Relaxed), 4);
+        for entity in [entities[1], entities[3], entities[5]] {
+            assert_destroyed(&world, entity);
+        }
+        // The archived entity, and the entity left in the storage of `A`.
+        assert_eq!(world.clear(), 2);
+        assert_eq!(cleanups.load(Ordering::Relaxed), 6);
+        for entity in &entities {
+            assert_destroyed(&world, *entity);
+        }
+        assert_eq!(world.iter_entities().count(), 0);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_panicking_cleanup() {
+        let cleanups = Arc::new(AtomicUsize::new(0));
+        let (mut world, entities) = world(cleanups.clone());
+        world.set_userdata(entities[2], KEY, Box::new(0u32));
+        let result = catch_unwind(AssertUnwindSafe(|| world.despawn(entities[2])));
+        assert!(result.is_err());
+        assert_destroyed(&world, entities[2]);
+        world.assert_invariants();
+
+        // A cleanup that panics while a whole storage is cleared leaves the other entities destroyed too, but
+        // their cleanups aren't called.
+        world.set_userdata(entities[1], KEY, Box::new(0u32));
+        let result = catch_unwind(AssertUnwindSafe(|| world.despawn_matching::<Has<B>>()));
+        assert!(result.is_err());
+        for entity in [entities[1], entities[3], entities[5]] {
+            assert_destroyed(&world, entity);
+        }
+        world.assert_invariants();
+        assert_eq!(world.entity_state(entities[0]), EntityState::Alive);
+        assert_eq!(world.entity_state(entities[4]), EntityState::Archived);
+    }
+}
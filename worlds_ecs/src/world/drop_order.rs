@@ -0,0 +1,346 @@
+use super::World;
+use crate::{
+    archetype::key::PrimeArchKey,
+    component::{Component, ComponentId},
+    utils::panics,
+};
+use std::{collections::HashMap, fmt, sync::Arc};
+
+/// An error when declaring the order that two components are dropped in (see [`World::drop_order`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DropOrderError {
+    /// The declaration contradicts the ones before it: `second` is already dropped before `first` (directly, or
+    /// through other components), or they are the same component.
+    Cycle {
+        /// The name of the component that was declared to be dropped first.
+        first: &'static str,
+        /// The name of the component that was declared to be dropped second.
+        second: &'static str,
+    },
+}
+
+impl fmt::Display for DropOrderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DropOrderError::Cycle { first, second } => write!(
+                f,
+                "dropping `{first}` before `{second}` would make a cycle, `{second}` is already dropped before `{first}`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DropOrderError {}
+
+/// The order that components are dropped in, within an entity. Components are dropped in the order of the columns of
+/// their storage (ascending [`ComponentId`]s), unless an order was declared between them.
+#[derive(Default, Clone)]
+pub(crate) struct DropOrder {
+    /// The components that each component is dropped before.
+    before: HashMap<ComponentId, Vec<ComponentId>>,
+}
+
+impl DropOrder {
+    /// Returns `true` if `to` is dropped after `from`, directly or through other components.
+    fn is_dropped_after(&self, from: ComponentId, to: ComponentId) -> bool {
+        let mut stack = vec![from];
+        let mut visited = vec![from];
+        while let Some(component) = stack.pop() {
+            for next in self.before.get(&component).into_iter().flatten() {
+                if *next == to {
+                    return true;
+                }
+                if !visited.contains(next) {
+                    visited.push(*next);
+                    stack.push(*next);
+                }
+            }
+        }
+        false
+    }
+
+    /// Declare that `first` is dropped before `second`. Returns `false` (and declares nothing) if that would make a
+    /// cycle.
+    fn declare(&mut self, first: ComponentId, second: ComponentId) -> bool {
+        if first == second || self.is_dropped_after(second, first) {
+            return false;
+        }
+        let before = self.before.entry(first).or_default();
+        if !before.contains(&second) {
+            before.push(second);
+        }
+        true
+    }
+
+    /// The order to drop the columns of a storage in, given the [`ComponentId`] of each column. `None` if it's the
+    /// order of the columns: the declarations are sorted topologically, and components with no order between them
+    /// keep the order of their columns. The declarations never make a cycle, so there is always such an order.
+    pub(crate) fn sequence(&self, components: &[ComponentId]) -> Option<Arc<[usize]>> {
+        if self.before.is_empty() {
+            return None;
+        }
+        // `after[i][j]` if column `j` must be dropped after column `i`.
+        let after: Vec<Vec<bool>> = components
+            .iter()
+            .map(|a| {
+                components
+                    .iter()
+                    .map(|b| a != b && self.is_dropped_after(*a, *b))
+                    .collect()
+            })
+            .collect();
+        let mut dropped = vec![false; components.len()];
+        let mut order = Vec::with_capacity(components.len());
+        while order.len() < components.len() {
+            let next = (0..components.len())
+                .find(|column| {
+                    !dropped[*column]
+                        && (0..components.len())
+                            .all(|other| dropped[other] || !after[other][*column])
+                })
+                .expect("the drop order has no cycles");
+            dropped[next] = true;
+            order.push(next);
+        }
+        (!order.iter().enumerate().all(|(i, column)| i == *column)).then(|| order.into())
+    }
+}
+
+impl World {
+    /// Declare that when an entity with both of the [`Component`]s `First` and `Second` is removed, `First` is
+    /// dropped before `Second`. This is for components whose [`Drop`] depends on each other, like a body that
+    /// deregisters itself from a solver through the shape it references.
+    ///
+    /// The order is honored by every path that drops the components of entities: [`World::despawn`],
+    /// [`World::despawn_matching`], [`World::clear`], dropping the world and [`World::into_teardown`]. It's
+    /// transitive: if `A` is dropped before `B`, and `B` before `C`, then `A` is dropped before `C`, even in
+    /// entities without a `B`. Components with no declared order between them are dropped in the order of the
+    /// columns of their storage (ascending [`ComponentId`]s), see
+    /// [`ArchStorage::drop_sequence`](crate::world::storage::arch_storage::ArchStorage::drop_sequence).
+    ///
+    /// Returns an error (and declares nothing) if the order contradicts the orders that were declared before.
+    ///
+    /// ```
+    /// use std::sync::Mutex;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// static DROPPED: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+    ///
+    /// #[derive(Component)]
+    /// struct Shape;
+    /// #[derive(Component)]
+    /// struct Body;
+    ///
+    /// impl Drop for Shape {
+    ///     fn drop(&mut self) {
+    ///         DROPPED.lock().unwrap().push("shape");
+    ///     }
+    /// }
+    /// impl Drop for Body {
+    ///     fn drop(&mut self) {
+    ///         DROPPED.lock().unwrap().push("body");
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.drop_order::<Body, Shape>().unwrap();
+    /// assert!(world.drop_order::<Shape, Body>().is_err());
+    /// let entity = world.spawn((Shape, Body));
+    /// world.despawn(entity);
+    /// assert_eq!(*DROPPED.lock().unwrap(), vec!["body", "shape"]);
+    /// ```
+    #[track_caller]
+    pub fn drop_order<First: Component, Second: Component>(
+        &mut self,
+    ) -> Result<(), DropOrderError> {
+        let Some(first) = self.components.register_component::<First>() else {
+            panics::fail_component_limit::<First>();
+        };
+        let Some(second) = self.components.register_component::<Second>() else {
+            panics::fail_component_limit::<Second>();
+        };
+        if !self.components.drop_order.declare(first, second) {
+            let name = |comp_id| {
+                self.components
+                    .get_component_info_from_component_id(comp_id)
+                    .expect("the components were registered")
+                    .name()
+            };
+            return Err(DropOrderError::Cycle {
+                first: name(first),
+                second: name(second),
+            });
+        }
+        // The declaration can change the order of any storage, through the components that are already ordered.
+        self.storages
+            .arch_storages
+            .iter_storages_with_matching_archetype_mut(PrimeArchKey::IDENTITY)
+            .for_each(|storage| storage.refresh_drop_order(&self.components));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DropOrderError;
+    use crate::prelude::*;
+    use std::{cell::RefCell, time::Duration};
+
+    thread_local! {
+        static DROPPED: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Components that record the order they are dropped in.
+    macro_rules! recorded {
+        ($($name:ident),*) => {
+            $(
+                #[derive(Component)]
+                struct $name(#[allow(unused)] u32);
+
+                impl Drop for $name {
+                    fn drop(&mut self) {
+                        DROPPED.with_borrow_mut(|dropped| dropped.push(stringify!($name)));
+                    }
+                }
+            )*
+        };
+    }
+
+    recorded!(Body, Shape, Sensor, Joint);
+
+    fn take_dropped() -> Vec<&'static str> {
+        DROPPED.take()
+    }
+
+    /// A world where the components are registered in the order `Body`, `Shape`, `Sensor`, `Joint`, so that's the
+    /// order of the columns.
+    fn physics_world() -> World {
+        let mut world = World::default();
+        world.components.register_component::<Body>();
+        world.components.register_component::<Shape>();
+        world.components.register_component::<Sensor>();
+        world.components.register_component::<Joint>();
+        world
+    }
+
+    #[test]
+    fn test_column_order_by_default() {
+        let mut world = physics_world();
+        let entity = world.spawn((Sensor(0), Body(0), Shape(0)));
+        world.despawn(entity);
+        assert_eq!(take_dropped(), ["Body", "Shape", "Sensor"]);
+    }
+
+    #[test]
+    fn test_declared_order() {
+        let mut world = physics_world();
+        world.drop_order::<Sensor, Body>().unwrap();
+        let entity = world.spawn((Body(0), Shape(0), Sensor(0)));
+        let sid = world
+            .entities
+            .get_entity_meta(entity)
+            .unwrap()
+            .archetype_storage_id;
+        let storage = world.storages.arch_storages.get_storage(sid).unwrap();
+        let sequence = storage.drop_sequence();
+        world.despawn(entity);
+        // Components with no declared order keep the order of their columns.
+        assert_eq!(take_dropped(), ["Shape", "Sensor", "Body"]);
+        let components = &world.components;
+        assert_eq!(
+            sequence,
+            [
+                components.get_component_id::<Shape>().unwrap(),
+                components.get_component_id::<Sensor>().unwrap(),
+                components.get_component_id::<Body>().unwrap(),
+            ]
+        );
+
+        // The order is transitive, even without the component in between.
+        world.drop_order::<Joint, Sensor>().unwrap();
+        let entity = world.spawn((Body(1), Joint(1)));
+        world.despawn(entity);
+        assert_eq!(take_dropped(), ["Joint", "Body"]);
+    }
+
+    #[test]
+    fn test_declared_after_spawn() {
+        let mut world = physics_world();
+        let entities: Vec<EntityId> = (0..40).map(|i| world.spawn((Body(i), Shape(i)))).collect();
+        world.drop_order::<Shape, Body>().unwrap();
+        world.despawn(entities[3]);
+        assert_eq!(take_dropped(), ["Shape", "Body"]);
+    }
+
+    #[test]
+    fn test_cycles() {
+        let mut world = physics_world();
+        world.drop_order::<Body, Shape>().unwrap();
+        world.drop_order::<Shape, Sensor>().unwrap();
+        // Declaring the same order again is fine.
+        world.drop_order::<Body, Shape>().unwrap();
+        let err = world.drop_order::<Sensor, Body>().unwrap_err();
+        assert!(matches!(
+            err,
+            DropOrderError::Cycle { first, second } if first.ends_with("Sensor") && second.ends_with("Body")
+        ));
+        assert!(world.drop_order::<Shape, Body>().is_err());
+        assert!(world.drop_order::<Joint, Joint>().is_err());
+        // Nothing was declared by the failed declarations.
+        let entity = world.spawn((Body(0), Shape(0), Sensor(0)));
+        world.despawn(entity);
+        assert_eq!(take_dropped(), ["Body", "Shape", "Sensor"]);
+    }
+
+    #[test]
+    fn test_bulk_paths() {
+        let spawn = |world: &mut World, amount: u32| {
+            for i in 0..amount {
+                world.spawn((Body(i), Shape(i), Joint(i)));
+            }
+        };
+        let assert_order = |amount: usize| {
+            let dropped = take_dropped();
+            assert_eq!(dropped.len(), amount * 3);
+            for entity in 0..amount {
+                // Every `Joint` is dropped before the `Body` of its entity, and every `Body` before its `Shape`.
+                let nth = |name| {
+                    dropped
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, dropped)| **dropped == name)
+                        .nth(entity)
+                        .unwrap()
+                        .0
+                };
+                assert!(nth("Joint") < nth("Body") && nth("Body") < nth("Shape"));
+            }
+        };
+        // Both tiny storages (with inline columns) and larger ones.
+        for amount in [3, 100] {
+            let mut world = physics_world();
+            world.drop_order::<Joint, Body>().unwrap();
+            world.drop_order::<Body, Shape>().unwrap();
+
+            spawn(&mut world, amount);
+            world.despawn_matching::<Has<Body>>();
+            assert_order(amount as usize);
+
+            spawn(&mut world, amount);
+            world.clear();
+            assert_order(amount as usize);
+
+            spawn(&mut world, amount);
+            drop(world);
+            assert_order(amount as usize);
+
+            let mut world = physics_world();
+            world.drop_order::<Joint, Body>().unwrap();
+            world.drop_order::<Body, Shape>().unwrap();
+            spawn(&mut world, amount);
+            let mut teardown = world.into_teardown();
+            while !teardown.run(Duration::ZERO).is_done() {}
+            assert_order(amount as usize);
+        }
+    }
+}
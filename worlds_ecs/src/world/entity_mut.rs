@@ -0,0 +1,378 @@
+use super::{
+    access::AccessKind,
+    archive::ArchiveError,
+    group::GroupId,
+    storage::{arch_storage::ArchStorageIndex, storages::ArchStorageId},
+    userdata::{Userdata, UserdataKey},
+    World,
+};
+use crate::{
+    archetype::Archetype,
+    bundle::Bundle,
+    component::{deref_component, deref_component_mut},
+    entity::EntityId,
+    prelude::{Component, Tag},
+    utils::{
+        panics::{self, EntityLabel},
+        paranoid::{self, Violation},
+    },
+};
+use std::{any::type_name, borrow::Cow, panic::Location};
+
+/// A mutable handle to an entity, that borrows the [`World`], for configuring the entity with a chain of calls
+/// (see [`World::spawn_builder`] and [`World::entity_mut`]).
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Tag)]
+/// struct Boss;
+///
+/// let mut tags = TagFactory::default();
+/// tags.register_tag::<Boss>();
+/// let mut world = World::with_tags(tags);
+/// let boss = world
+///     .spawn_builder(Health(100))
+///     .tag::<Boss>()
+///     .annotate("spawned by the arena")
+///     .id();
+/// assert_eq!(world.get_component::<Health>(boss).unwrap().0, 100);
+/// ```
+///
+/// The handle caches the row of the entity in its storage, so its getters don't look the entity up again. The
+/// chained calls that move the entity to another row (like [`Self::archive`] and [`Self::restore`], or anything
+/// done through [`Self::world_scope`]) refresh the cached row before they return.
+pub struct EntityWorldMut<'w> {
+    world: &'w mut World,
+    entity: EntityId,
+    /// The storage and the row of the entity, or `None` if it isn't stored (it's archived, or was despawned).
+    row: Option<(ArchStorageId, ArchStorageIndex)>,
+}
+
+impl<'w> EntityWorldMut<'w> {
+    fn new(world: &'w mut World, entity: EntityId) -> Self {
+        let mut handle = EntityWorldMut {
+            world,
+            entity,
+            row: None,
+        };
+        handle.refresh();
+        handle
+    }
+
+    /// Read the row of the entity again, after it may have moved.
+    fn refresh(&mut self) {
+        self.row = self
+            .world
+            .entities
+            .get_entity_meta(self.entity)
+            .filter(|entity_meta| !entity_meta.is_archived() && !entity_meta.is_tombstone())
+            .map(|entity_meta| {
+                (
+                    entity_meta.archetype_storage_id,
+                    entity_meta.archetype_storage_index,
+                )
+            });
+    }
+
+    /// The storage of the entity, and its cached row, or `None` if the entity isn't stored.
+    #[track_caller]
+    fn storage(&self, method: &str) -> Option<(ArchStorageId, ArchStorageIndex)> {
+        let (sid, index) = self.row?;
+        let storage = self.world.storages.arch_storages.get_storage(sid);
+        paranoid::debug_check(method, || {
+            match storage.and_then(|storage| storage.get_entity_at(index)) {
+                Some(entity) if entity == self.entity => Ok(()),
+                _ => Err(Violation::MovedEntity {
+                    entity: self.entity,
+                    index: index.0,
+                }),
+            }
+        });
+        Some((sid, index))
+    }
+
+    /// The [`EntityId`] of the entity.
+    pub fn id(&self) -> EntityId {
+        self.entity
+    }
+
+    /// The [`World`] of the entity.
+    pub fn world(&self) -> &World {
+        self.world
+    }
+
+    /// Get a reference to a [`Component`] of the entity. See [`World::get_component`].
+    #[track_caller]
+    pub fn get<C: Component>(&self) -> Option<&C> {
+        self.world.access.record_component::<C>(
+            &self.world.components,
+            AccessKind::Read,
+            Location::caller(),
+        );
+        let (sid, index) = self.storage("EntityWorldMut::get")?;
+        let comp_id = self.world.components.get_component_id::<C>()?;
+        let component = self
+            .world
+            .storages
+            .arch_storages
+            .get_storage(sid)?
+            .get_component(index, comp_id)?;
+        // SAFETY: This type-erased pointer was fetched using this component id.
+        Some(unsafe { deref_component::<C>(component) })
+    }
+
+    /// Get a mutable reference to a [`Component`] of the entity. See [`World::get_component_mut`].
+    #[track_caller]
+    pub fn get_mut<C: Component>(&mut self) -> Option<&mut C> {
+        self.world.access.record_component::<C>(
+            &self.world.components,
+            AccessKind::Write,
+            Location::caller(),
+        );
+        let (sid, index) = self.storage("EntityWorldMut::get_mut")?;
+        let comp_id = self.world.components.get_component_id::<C>()?;
+        let component = self
+            .world
+            .storages
+            .arch_storages
+            .get_storage_mut(sid)?
+            .get_component_mut(index, comp_id)?;
+        // SAFETY: This type-erased pointer was fetched using this component id.
+        Some(unsafe { deref_component_mut::<C>(component) })
+    }
+
+    /// Returns `true` if the entity has the [`Component`] `C`. See [`World::contains_component`].
+    pub fn contains<C: Component>(&self) -> bool {
+        self.world.contains_component::<C>(self.entity)
+    }
+
+    /// Tag the entity with the [`Tag`] `T`.
+    ///
+    /// # Panics
+    /// If the entity was despawned, or if the tag isn't registered.
+    #[track_caller]
+    pub fn tag<T: Tag>(&mut self) -> &mut Self {
+        let Some(mut tracker) = self.world.get_tag_tracker(self.entity) else {
+            panics::fail_entity("tag", "the entity isn't alive", self.entity)
+        };
+        if !tracker.is_tag_registered::<T>() {
+            panics::fail(
+                "tag",
+                "the tag isn't registered",
+                &[
+                    ("entity", &EntityLabel(self.entity)),
+                    ("tag", &type_name::<T>()),
+                ],
+            );
+        }
+        // SAFETY: The tag is registered, and the world is borrowed mutably, so no other tracker of the entity is
+        // being accessed.
+        unsafe { tracker.tag::<T>() };
+        self
+    }
+
+    /// Annotate the entity. See [`World::annotate`].
+    #[track_caller]
+    pub fn annotate(&mut self, message: impl Into<Cow<'static, str>>) -> &mut Self {
+        self.world.annotate(self.entity, message);
+        self
+    }
+
+    /// Attach a type-erased value to the entity, in the slot of the [`UserdataKey`]. The value that was there
+    /// before is dropped. See [`World::set_userdata`].
+    #[track_caller]
+    pub fn set_userdata(&mut self, key: UserdataKey, value: Userdata) -> &mut Self {
+        self.world.set_userdata(self.entity, key, value);
+        self
+    }
+
+    /// Add the entity to a group. See [`World::group_insert`].
+    #[track_caller]
+    pub fn join_group(&mut self, group: GroupId) -> &mut Self {
+        self.world.group_insert(group, self.entity);
+        self
+    }
+
+    /// Start recording the last values of the [`Component`] `C` of the entity. See [`World::record_history`].
+    #[track_caller]
+    pub fn record_history<C: Component + Clone>(&mut self, capacity: usize) -> &mut Self {
+        self.world.record_history::<C>(self.entity, capacity);
+        self
+    }
+
+    /// Archive the entity. See [`World::archive`].
+    pub fn archive(&mut self) -> Result<&mut Self, ArchiveError> {
+        self.world.archive(self.entity)?;
+        self.refresh();
+        Ok(self)
+    }
+
+    /// Restore the entity from the archive, into a new row of its storage. See [`World::restore`].
+    pub fn restore(&mut self) -> Result<&mut Self, ArchiveError> {
+        self.world.restore(self.entity)?;
+        self.refresh();
+        Ok(self)
+    }
+
+    /// Call `f` with the [`World`], for what the handle doesn't expose, and refresh the cached row of the entity
+    /// afterwards (since `f` may move it).
+    pub fn world_scope<R>(&mut self, f: impl FnOnce(&mut World) -> R) -> R {
+        let result = f(self.world);
+        self.refresh();
+        result
+    }
+
+    /// Despawn the entity. See [`World::despawn`].
+    #[track_caller]
+    pub fn despawn(self) {
+        self.world.despawn(self.entity);
+    }
+}
+
+impl World {
+    /// Spawn a new entity with a bundle of components, like [`World::spawn`], and return an [`EntityWorldMut`] to
+    /// keep configuring it.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let selected = world.create_group("selected");
+    /// let entity = world.spawn_builder(Health(10)).join_group(selected).id();
+    /// assert!(world.group_contains(selected, entity));
+    /// ```
+    ///
+    /// # Panics
+    /// For the same reasons as [`World::spawn`].
+    #[track_caller]
+    pub fn spawn_builder<B: Bundle + Archetype>(&mut self, bundle: B) -> EntityWorldMut<'_> {
+        let entity = self.spawn(bundle);
+        EntityWorldMut::new(self, entity)
+    }
+
+    /// Get an [`EntityWorldMut`] of an entity. Returns `None` if the entity was despawned.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// world.entity_mut(entity).unwrap().get_mut::<Health>().unwrap().0 = 5;
+    /// assert_eq!(world.get_component::<Health>(entity).unwrap().0, 5);
+    /// world.despawn(entity);
+    /// assert!(world.entity_mut(entity).is_none());
+    /// ```
+    pub fn entity_mut(&mut self, entity: EntityId) -> Option<EntityWorldMut<'_>> {
+        self.entities.get_entity_meta(entity)?;
+        Some(EntityWorldMut::new(self, entity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Health(u32);
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Armor(u32);
+    #[derive(Tag)]
+    struct Boss;
+
+    const KEY: UserdataKey = UserdataKey::Named("script");
+
+    fn world() -> World {
+        let mut tags = TagFactory::default();
+        tags.register_tag::<Boss>();
+        let mut world = World::with_tags(tags);
+        // SAFETY: The components are plain data, so they can be archived by copying their bytes.
+        unsafe {
+            world.register_pod_component::<Health>();
+            world.register_pod_component::<Armor>();
+        }
+        world
+    }
+
+    #[test]
+    fn test_chained_configuration() {
+        let mut world = world();
+        let group = world.create_group("bosses");
+        let others: Vec<EntityId> = (0..4).map(|i| world.spawn((Health(i), Armor(i)))).collect();
+
+        let mut boss = world.spawn_builder((Health(100), Armor(50)));
+        boss.tag::<Boss>()
+            .set_userdata(KEY, Box::new("boss.lua"))
+            .join_group(group)
+            .record_history::<Health>(4)
+            .archive()
+            .unwrap();
+        assert_eq!(boss.get::<Health>(), None);
+        // The entity is restored into a different row, after the first entity of its storage is despawned.
+        boss.world_scope(|world| world.despawn(others[0]));
+        boss.restore().unwrap().get_mut::<Armor>().unwrap().0 += 1;
+        assert_eq!(boss.get::<Health>(), Some(&Health(100)));
+        let boss = boss.id();
+
+        assert_eq!(world.get_component::<Armor>(boss), Some(&Armor(51)));
+        assert!(unsafe { world.get_tag_tracker(boss).unwrap().is_tagged::<Boss>() });
+        assert_eq!(
+            world
+                .get_userdata(boss, KEY)
+                .and_then(|userdata| userdata.downcast_ref::<&str>()),
+            Some(&"boss.lua")
+        );
+        assert!(world.group_contains(group, boss));
+        assert!(world.history::<Health>(boss).is_some());
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_rows_are_refreshed() {
+        let mut world = world();
+        let first = world.spawn(Health(0));
+        let mut entity = world.spawn_builder(Health(1));
+        // Despawning the first entity moves this entity into its row.
+        entity.world_scope(|world| world.despawn(first));
+        entity.get_mut::<Health>().unwrap().0 += 10;
+        assert_eq!(entity.get::<Health>(), Some(&Health(11)));
+        assert!(!entity.contains::<Armor>());
+        entity.despawn();
+        assert_eq!(world.iter_component::<Health>().count(), 0);
+        assert!(world.entity_mut(first).is_none());
+        world.assert_invariants();
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(any(debug_assertions, feature = "paranoid")),
+        ignore = "stale rows are only checked in debug builds, or with the `paranoid` feature"
+    )]
+    #[should_panic(expected = "the precondition that the entity is stored in its cached row")]
+    fn test_stale_row_is_caught() {
+        let mut world = world();
+        let first = world.spawn(Health(0));
+        let entity = world.spawn_builder(Health(1));
+        // Move the entity behind the handle's back.
+        entity.world.despawn(first);
+        entity.get::<Health>();
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: tag failed: the tag isn't registered")]
+    fn test_unregistered_tag() {
+        #[derive(Tag)]
+        struct Unregistered;
+
+        let mut world = World::default();
+        world.spawn_builder(Health(0)).tag::<Unregistered>();
+    }
+}
@@ -0,0 +1,280 @@
+use super::{
+    data::CloneFn,
+    storage::{arch_storage::ArchStorageIndex, storages::ArchStorageId, ArchEntityStorage},
+    World,
+};
+use crate::{
+    archetype::Archetype,
+    bundle::FromComponents,
+    component::ComponentId,
+    entity::EntityId,
+    prelude::{ArchFilter, FetchContext, FilterResult, StorageFilterResult},
+    utils::panics,
+};
+
+impl World {
+    /// Clone the components of the bundle `B` out of every entity that has all of them and passes the filter `F`,
+    /// for save systems, snapshots of selected entities, and undo buffers. Entities with more components than `B`
+    /// match too, and only the components of `B` are cloned. Nothing in the world is mutated, so the generations
+    /// of the storages (see [`ArchStorage::generation`](crate::world::storage::arch_storage::ArchStorage::generation))
+    /// don't change.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Debug, PartialEq)]
+    /// struct Health(u32);
+    /// #[derive(Component, Clone, Debug, PartialEq)]
+    /// struct Name(&'static str);
+    /// #[derive(Component)]
+    /// struct Player;
+    ///
+    /// let mut world = World::default();
+    /// world.register_cloneable_component::<Health>();
+    /// world.register_cloneable_component::<Name>();
+    /// let player = world.spawn((Health(10), Name("hero"), Player));
+    /// world.spawn((Health(3), Name("goblin")));
+    /// let saved = world.extract_bundles::<(Health, Name), With<Player>>();
+    /// assert_eq!(saved, [(player, (Health(10), Name("hero")))]);
+    /// ```
+    ///
+    /// # Panics
+    /// Before anything is cloned, if one of the components of `B` wasn't registered with
+    /// [`World::register_cloneable_component`], or is shared (see [`Component::SHARED`](crate::prelude::Component::SHARED)),
+    /// since its value can't be moved out of its handle.
+    #[track_caller]
+    pub fn extract_bundles<B: FromComponents + Archetype, F: ArchFilter>(
+        &self,
+    ) -> Vec<(EntityId, B)> {
+        let Some(clone_fns) = self.bundle_clone_fns::<B>("extract_bundles") else {
+            return Vec::new();
+        };
+        let mut bundles = Vec::new();
+        let mut sid = ArchStorageId(0);
+        while let Some(storage) = self.storages.arch_storages.get_storage(sid) {
+            sid = ArchStorageId(sid.0 + 1);
+            if !storage.contains_archetype::<B>(&self.components) {
+                continue;
+            }
+            let per_entity = match F::filter_storage(storage, &self.components) {
+                StorageFilterResult::NoneMatch => continue,
+                StorageFilterResult::AllMatch => false,
+                StorageFilterResult::PerEntity => true,
+            };
+            let ctx =
+                FetchContext::new(self.entity_tags()).for_filter::<F>(storage, &self.components);
+            for index in storage.iter_indices() {
+                // SAFETY: The index came from the storage itself.
+                if per_entity
+                    && !unsafe { F::filter(storage, index, &self.components, ctx) }.collapse()
+                {
+                    continue;
+                }
+                if let Some(bundle) = self.clone_bundle(storage, index, &clone_fns) {
+                    bundles.push((storage.entities()[index.0], bundle));
+                }
+            }
+        }
+        bundles
+    }
+
+    /// Clone the components of the bundle `B` out of an entity (see [`World::extract_bundles`]). Returns `None` if
+    /// the entity isn't alive (or is archived, see [`World::archive`]), or doesn't have all of the components of `B`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Debug, PartialEq)]
+    /// struct Health(u32);
+    /// #[derive(Component, Clone, Debug, PartialEq)]
+    /// struct Name(&'static str);
+    ///
+    /// let mut world = World::default();
+    /// world.register_cloneable_component::<Health>();
+    /// world.register_cloneable_component::<Name>();
+    /// let hero = world.spawn((Health(10), Name("hero")));
+    /// let rock = world.spawn(Health(99));
+    /// assert_eq!(world.extract_bundle::<(Health, Name)>(hero), Some((Health(10), Name("hero"))));
+    /// assert_eq!(world.extract_bundle::<(Health, Name)>(rock), None);
+    /// ```
+    ///
+    /// # Panics
+    /// Like [`World::extract_bundles`].
+    #[track_caller]
+    pub fn extract_bundle<B: FromComponents + Archetype>(&self, entity: EntityId) -> Option<B> {
+        let clone_fns = self.bundle_clone_fns::<B>("extract_bundle")?;
+        let entity_meta = self.entities.get_entity_meta(entity)?;
+        let storage =
+            (self.storages.arch_storages).get_storage(entity_meta.archetype_storage_id)?;
+        self.clone_bundle(storage, entity_meta.archetype_storage_index, &clone_fns)
+    }
+
+    /// The clone function of each component of `B`, or `None` if some of them aren't registered, so no entity has
+    /// all of them.
+    #[track_caller]
+    fn bundle_clone_fns<B: Archetype>(
+        &self,
+        operation: &str,
+    ) -> Option<Vec<(ComponentId, CloneFn)>> {
+        let arch_info = B::arch_info(&self.components)?;
+        if arch_info.check_for_duplicates() {
+            panics::fail_duplicate_components::<B>(operation);
+        }
+        let clone_fns = (arch_info.component_ids().iter()).map(|comp_id| {
+            let info = (self.components)
+                .get_component_info_from_component_id(*comp_id)
+                .expect("the components of the archetype are registered");
+            match info.clone_fn() {
+                _ if info.is_shared() => panics::fail_component(
+                    operation,
+                    "shared components can't be moved out of their handles, so they can't be extracted",
+                    info.name(),
+                ),
+                Some(clone_fn) => (*comp_id, clone_fn),
+                None => panics::fail_component(
+                    operation,
+                    "the component isn't registered as cloneable (see World::register_cloneable_component)",
+                    info.name(),
+                ),
+            }
+        });
+        Some(clone_fns.collect())
+    }
+
+    /// Clone the bundle `B` out of the row of a storage, or return `None` if the storage doesn't have all of the
+    /// components of `B`.
+    fn clone_bundle<B: FromComponents>(
+        &self,
+        storage: &ArchEntityStorage,
+        index: ArchStorageIndex,
+        clone_fns: &[(ComponentId, CloneFn)],
+    ) -> Option<B> {
+        // SAFETY: Each component is cloned with its own clone function, and the clone is handed to `B`, which moves
+        // it out.
+        unsafe {
+            B::from_raw_components(&self.components, &mut |comp_id, take| {
+                let clone_fn = clone_fns.iter().find(|(id, _)| *id == comp_id);
+                if let Some(((_, clone_fn), raw_comp)) =
+                    clone_fn.zip(storage.get_component(index, comp_id))
+                {
+                    clone_fn(raw_comp, take);
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{archetype::key::PrimeArchKey, prelude::*};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Transform(i32, i32);
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Health(u32);
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Inventory(Vec<String>);
+    #[derive(Component)]
+    struct Enemy;
+    #[derive(Component)]
+    struct Unique;
+
+    type Saved = (Transform, Health, Inventory);
+
+    fn empty_world() -> World {
+        let mut world = World::default();
+        world.register_cloneable_component::<Transform>();
+        world.register_cloneable_component::<Health>();
+        world.register_cloneable_component::<Inventory>();
+        world
+    }
+
+    fn world() -> World {
+        let mut world = empty_world();
+        for i in 0..30 {
+            let bundle = (
+                Transform(i, -i),
+                Health(i as u32),
+                Inventory(vec![format!("item {i}")]),
+            );
+            match i % 3 {
+                0 => world.spawn(bundle),
+                1 => world.spawn((bundle, Enemy)),
+                _ => world.spawn((Transform(i, -i), Health(i as u32))),
+            };
+        }
+        world
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut world = world();
+        let extracted = world.extract_bundles::<Saved, ()>();
+        assert_eq!(extracted.len(), 20);
+        let mut copy = empty_world();
+        let spawned = copy.spawn_batch(extracted.iter().map(|(_, bundle)| bundle.clone()));
+        for ((entity, _), copied) in extracted.iter().zip(&spawned) {
+            assert_eq!(
+                world.extract_bundle::<Saved>(*entity),
+                copy.extract_bundle::<Saved>(*copied)
+            );
+        }
+        assert_eq!(copy.extract_bundles::<Saved, ()>().len(), extracted.len());
+
+        // The filter applies, and entities without all of the components are skipped.
+        let enemies = world.extract_bundles::<(Health, Inventory), With<Enemy>>();
+        assert_eq!(enemies.len(), 10);
+        assert!(enemies
+            .iter()
+            .all(|(entity, (health, _))| health.0 % 3 == 1
+                && world.contains_component::<Enemy>(*entity)));
+        let entity = world
+            .query_filtered::<EntityId, Without<Inventory>>()
+            .next()
+            .unwrap();
+        assert_eq!(world.extract_bundle::<Saved>(entity), None);
+        assert!(world
+            .extract_bundle::<(Transform, Health)>(entity)
+            .is_some());
+    }
+
+    #[test]
+    fn test_uncloneable_components() {
+        let mut world = world();
+        world.spawn((Transform(0, 0), Unique));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            world.extract_bundles::<(Transform, Unique), ()>()
+        }));
+        let message = *result.err().unwrap().downcast::<String>().unwrap();
+        assert!(message.starts_with(
+            "worlds_ecs: extract_bundles failed: the component isn't registered as cloneable"
+        ));
+        assert!(message.contains("Unique"), "{message}");
+    }
+
+    #[test]
+    fn test_extraction_leaves_the_world_untouched() {
+        let world = world();
+        let generations = |world: &World| {
+            world
+                .storages
+                .arch_storages
+                .iter_storages_with_matching_archetype(PrimeArchKey::IDENTITY)
+                .map(|storage| storage.generation())
+                .collect::<Vec<u64>>()
+        };
+        let before = generations(&world);
+        let extracted = world.extract_bundles::<Saved, ()>();
+        assert_eq!(generations(&world), before);
+        // The extracted bundles are owned: mutating them doesn't change the world.
+        for (entity, (mut transform, _, mut inventory)) in extracted {
+            transform.0 += 100;
+            inventory.0.clear();
+            let stored = world.get_component::<Transform>(entity).unwrap();
+            assert_ne!(stored, &transform);
+            assert_eq!(world.get_component::<Inventory>(entity).unwrap().0.len(), 1);
+        }
+        assert_eq!(generations(&world), before);
+    }
+}
@@ -0,0 +1,357 @@
+use super::World;
+use crate::entity::{EntityFactory, EntityId};
+use std::{collections::HashMap, fmt};
+
+/// How many bits of an [`FfiHandle`] index its slot in the table. The rest hold the generation of the slot.
+const INDEX_BITS: u32 = 20;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+/// The last generation of a slot. A slot that reaches it is retired instead of being reused, so a handle is never
+/// reused for another entity.
+const MAX_GENERATION: u32 = u32::MAX >> INDEX_BITS;
+
+/// The most handles that can be acquired at once, and the default limit (see [`FfiHandleTable::set_max_handles`]).
+pub const MAX_FFI_HANDLES: u32 = INDEX_MASK;
+
+/// A compact handle to an entity, for scripting VMs and C code, which prefer a plain `u32` over an [`EntityId`]
+/// (see [`World::ffi_handles`]). The handle `0` is never valid, so it can be used as a null handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct FfiHandle(u32);
+
+impl FfiHandle {
+    /// The handle that never resolves to an entity.
+    pub const NULL: FfiHandle = FfiHandle(0);
+
+    /// The handle as a plain `u32`, to hand to a script or to C code.
+    pub fn to_raw(self) -> u32 {
+        self.0
+    }
+
+    /// A handle from a plain `u32`. Any value can be resolved: unknown handles resolve to nothing.
+    pub fn from_raw(raw: u32) -> FfiHandle {
+        FfiHandle(raw)
+    }
+
+    fn new(index: usize, generation: u32) -> FfiHandle {
+        FfiHandle((generation << INDEX_BITS) | index as u32)
+    }
+
+    fn index(self) -> usize {
+        (self.0 & INDEX_MASK) as usize
+    }
+
+    fn generation(self) -> u32 {
+        self.0 >> INDEX_BITS
+    }
+}
+
+/// An error when an [`FfiHandle`] can't be acquired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FfiHandleError {
+    /// The entity isn't alive.
+    DeadEntity(EntityId),
+    /// As many handles as the limit allows are acquired (see [`FfiHandleTable::set_max_handles`]).
+    Exhausted {
+        /// The limit.
+        max: u32,
+    },
+}
+
+impl fmt::Display for FfiHandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FfiHandleError::DeadEntity(entity) => {
+                write!(f, "can't acquire a handle to the dead entity {entity:?}")
+            }
+            FfiHandleError::Exhausted { max } => {
+                write!(f, "can't acquire more than {max} handles")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FfiHandleError {}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    entity: Option<EntityId>,
+    refs: u32,
+    generation: u32,
+}
+
+/// The handles of the [`World`]. The table is empty until a handle is acquired, and the destruction of an entity
+/// only looks it up then (see [`World::ffi_handles`]).
+#[derive(Default)]
+pub(crate) struct FfiHandles {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    /// The slot of each entity with a handle, by [`EntityId::to_bits`].
+    by_entity: HashMap<u64, u32>,
+    /// The limit of the live handles, or [`MAX_FFI_HANDLES`] if it's `None`.
+    max: Option<u32>,
+}
+
+impl FfiHandles {
+    /// The entity of a handle, or `None` if it was released, or its entity died.
+    pub(crate) fn resolve(&self, handle: FfiHandle) -> Option<EntityId> {
+        let slot = self.slots.get(handle.index())?;
+        (slot.generation == handle.generation())
+            .then_some(slot.entity)
+            .flatten()
+    }
+
+    /// An entity died: its handle (if it has one) resolves to nothing from now on, and its slot is freed.
+    pub(crate) fn despawned(&mut self, entities: &[EntityId]) {
+        if self.by_entity.is_empty() {
+            return;
+        }
+        for entity in entities {
+            if let Some(index) = self.by_entity.remove(&entity.to_bits()) {
+                self.free_slot(index);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.by_entity.len()
+    }
+
+    fn max(&self) -> u32 {
+        self.max.unwrap_or(MAX_FFI_HANDLES)
+    }
+
+    fn free_slot(&mut self, index: u32) {
+        let slot = &mut self.slots[index as usize];
+        slot.entity = None;
+        slot.refs = 0;
+        if slot.generation < MAX_GENERATION {
+            slot.generation += 1;
+            self.free.push(index);
+        }
+    }
+}
+
+/// The table of the [`FfiHandle`]s of a [`World`] (see [`World::ffi_handles`]).
+pub struct FfiHandleTable<'w> {
+    handles: &'w mut FfiHandles,
+    entities: &'w EntityFactory,
+}
+
+impl<'w> FfiHandleTable<'w> {
+    /// The handle of an entity. The handle stays the same for as long as the entity lives: acquiring it again
+    /// returns the same handle, and counts another reference to it (see [`Self::release`]).
+    pub fn acquire(&mut self, entity: EntityId) -> Result<FfiHandle, FfiHandleError> {
+        if self.entities.get_entity_meta(entity).is_none() {
+            return Err(FfiHandleError::DeadEntity(entity));
+        }
+        let handles = &mut *self.handles;
+        if let Some(&index) = handles.by_entity.get(&entity.to_bits()) {
+            let slot = &mut handles.slots[index as usize];
+            slot.refs += 1;
+            return Ok(FfiHandle::new(index as usize, slot.generation));
+        }
+        if handles.len() >= handles.max() as usize {
+            return Err(FfiHandleError::Exhausted { max: handles.max() });
+        }
+        let index = match handles.free.pop() {
+            Some(index) => index,
+            None if handles.slots.len() < MAX_FFI_HANDLES as usize => {
+                // The generations start at 1, so no handle is 0.
+                handles.slots.push(Slot {
+                    entity: None,
+                    refs: 0,
+                    generation: 1,
+                });
+                handles.slots.len() as u32 - 1
+            }
+            // Every slot is live or retired.
+            None => return Err(FfiHandleError::Exhausted { max: handles.max() }),
+        };
+        let slot = &mut handles.slots[index as usize];
+        slot.entity = Some(entity);
+        slot.refs = 1;
+        let generation = slot.generation;
+        handles.by_entity.insert(entity.to_bits(), index);
+        Ok(FfiHandle::new(index as usize, generation))
+    }
+
+    /// The entity of a handle, or `None` if the handle was released, or its entity died.
+    pub fn resolve(&self, handle: FfiHandle) -> Option<EntityId> {
+        self.handles.resolve(handle)
+    }
+
+    /// Resolve every handle of `handles`, like [`Self::resolve`].
+    pub fn resolve_many<'a>(
+        &'a self,
+        handles: &'a [FfiHandle],
+    ) -> impl Iterator<Item = Option<EntityId>> + 'a {
+        handles.iter().map(|handle| self.resolve(*handle))
+    }
+
+    /// Release a reference to a handle. Once every reference that was acquired is released, the handle resolves to
+    /// nothing, and its slot can be used for another entity. Returns `false` if the handle didn't resolve to an
+    /// entity (it was released already, or its entity died).
+    pub fn release(&mut self, handle: FfiHandle) -> bool {
+        let handles = &mut *self.handles;
+        let Some(entity) = handles.resolve(handle) else {
+            return false;
+        };
+        let slot = &mut handles.slots[handle.index()];
+        slot.refs -= 1;
+        if slot.refs == 0 {
+            handles.by_entity.remove(&entity.to_bits());
+            handles.free_slot(handle.index() as u32);
+        }
+        true
+    }
+
+    /// The amount of live handles.
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Returns `true` if there are no live handles.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Limit the amount of live handles to `max` (at most [`MAX_FFI_HANDLES`]), so [`Self::acquire`] fails with
+    /// [`FfiHandleError::Exhausted`] once that many are live. The handles that are already live stay valid.
+    pub fn set_max_handles(&mut self, max: u32) {
+        self.handles.max = Some(max.min(MAX_FFI_HANDLES));
+    }
+}
+
+impl World {
+    /// The table of compact [`FfiHandle`]s to the entities of the world, for scripting VMs and C code (see the `ffi`
+    /// feature for C functions that use it). A handle is a plain `u32` that is validated every time it's resolved:
+    /// once its entity dies, it resolves to nothing, even if the id of the entity is reused.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// let handle = world.ffi_handles().acquire(entity).unwrap();
+    /// assert_eq!(world.ffi_handles().resolve(handle), Some(entity));
+    /// world.despawn(entity);
+    /// assert_eq!(world.ffi_handles().resolve(handle), None);
+    /// ```
+    pub fn ffi_handles(&mut self) -> FfiHandleTable<'_> {
+        FfiHandleTable {
+            handles: &mut self.ffi_handles,
+            entities: &self.entities,
+        }
+    }
+
+    /// The entity of a handle, like [`FfiHandleTable::resolve`], through a shared reference.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(());
+    /// let handle = world.ffi_handles().acquire(entity).unwrap();
+    /// let world = &world;
+    /// assert_eq!(world.resolve_ffi_handle(handle), Some(entity));
+    /// assert_eq!(world.resolve_ffi_handle(FfiHandle::NULL), None);
+    /// ```
+    pub fn resolve_ffi_handle(&self, handle: FfiHandle) -> Option<EntityId> {
+        self.ffi_handles.resolve(handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FfiHandle, FfiHandleError, MAX_GENERATION};
+    use crate::{prelude::*, test_utils::Fx};
+
+    #[test]
+    fn test_acquire_is_reference_counted() {
+        let mut world = World::default();
+        let entity = world.spawn(Fx::<0>(0));
+        let mut handles = world.ffi_handles();
+        let handle = handles.acquire(entity).unwrap();
+        assert_ne!(handle, FfiHandle::NULL);
+        assert_eq!(handles.acquire(entity), Ok(handle));
+        assert_eq!(handles.len(), 1);
+        assert!(handles.release(handle));
+        assert_eq!(handles.resolve(handle), Some(entity));
+        assert!(handles.release(handle));
+        assert_eq!(handles.resolve(handle), None);
+        assert!(!handles.release(handle));
+        assert!(handles.is_empty());
+        assert_eq!(handles.resolve(FfiHandle::NULL), None);
+        assert_eq!(handles.resolve(FfiHandle::from_raw(u32::MAX)), None);
+    }
+
+    #[test]
+    fn test_despawn_invalidates_eagerly() {
+        let mut world = World::default();
+        let entities: Vec<EntityId> = (0..4).map(|_| world.spawn(Fx::<0>(0))).collect();
+        let handles: Vec<FfiHandle> = entities
+            .iter()
+            .map(|entity| world.ffi_handles().acquire(*entity).unwrap())
+            .collect();
+        world.despawn(entities[0]);
+        world.despawn_matching::<Has<Fx<0>>>();
+        assert!(world.ffi_handles().is_empty());
+        assert!(world
+            .ffi_handles()
+            .resolve_many(&handles)
+            .all(|entity| entity.is_none()));
+        assert_eq!(
+            world.ffi_handles().acquire(entities[1]),
+            Err(FfiHandleError::DeadEntity(entities[1]))
+        );
+    }
+
+    #[test]
+    fn test_reused_ids_dont_resolve() {
+        let mut world = World::default();
+        let entity = world.spawn(Fx::<0>(0));
+        let handle = world.ffi_handles().acquire(entity).unwrap();
+        world.despawn(entity);
+        let reused = world.spawn(Fx::<0>(0));
+        assert_eq!(reused.id(), entity.id());
+        // The new entity gets the slot of the old handle, with another generation.
+        let new_handle = world.ffi_handles().acquire(reused).unwrap();
+        assert_ne!(new_handle, handle);
+        assert_eq!(world.resolve_ffi_handle(handle), None);
+        assert_eq!(world.resolve_ffi_handle(new_handle), Some(reused));
+        // Releasing the stale handle doesn't release the new one.
+        assert!(!world.ffi_handles().release(handle));
+        assert_eq!(world.resolve_ffi_handle(new_handle), Some(reused));
+    }
+
+    #[test]
+    fn test_exhaustion() {
+        let mut world = World::default();
+        world.ffi_handles().set_max_handles(2);
+        let entities: Vec<EntityId> = (0..3).map(|_| world.spawn(Fx::<0>(0))).collect();
+        let first = world.ffi_handles().acquire(entities[0]).unwrap();
+        world.ffi_handles().acquire(entities[1]).unwrap();
+        let error = world.ffi_handles().acquire(entities[2]).unwrap_err();
+        assert_eq!(error, FfiHandleError::Exhausted { max: 2 });
+        assert_eq!(error.to_string(), "can't acquire more than 2 handles");
+        // Acquiring a live handle again doesn't need a slot.
+        assert_eq!(world.ffi_handles().acquire(entities[0]), Ok(first));
+        world.despawn(entities[0]);
+        assert!(world.ffi_handles().acquire(entities[2]).is_ok());
+    }
+
+    #[test]
+    fn test_exhausted_generations_retire_the_slot() {
+        let mut world = World::default();
+        let entity = world.spawn(Fx::<0>(0));
+        let handle = world.ffi_handles().acquire(entity).unwrap();
+        world.ffi_handles.slots[0].generation = MAX_GENERATION;
+        world.ffi_handles.by_entity.clear();
+        world.ffi_handles.free_slot(0);
+        let new_handle = world.ffi_handles().acquire(entity).unwrap();
+        assert_ne!(new_handle.index(), handle.index());
+    }
+}
@@ -0,0 +1,590 @@
+use super::{storage::storages::ArchStorageId, World};
+use crate::{
+    archetype::key::MAX_COMPONENTS, archetype::MAX_COMPS_PER_ARCH, component::ComponentId,
+    entity::ReusePolicy, storage::columns::INLINE_ROWS,
+};
+use std::{fmt, hash::Hasher};
+
+/// How entities are removed from a storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalPolicy {
+    /// The last entity of the storage is moved into the row of the removed entity (the default).
+    Swap,
+    /// Every entity after the removed entity moves up a row, so their order is kept
+    /// (see [`World::maintain_sort`]).
+    Shift,
+}
+
+/// The policies of a storage that aren't the default ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoragePolicy {
+    /// The names of the components of the storage's archetype, in the order of their [`ComponentId`]s.
+    pub archetype: Vec<String>,
+    /// How entities are removed from the storage.
+    pub removal: RemovalPolicy,
+    /// The name of the component that the storage is sorted by, if it's sort-maintained.
+    pub sort_key: Option<String>,
+}
+
+/// A description of everything in the configuration of a [`World`] that must be identical on every peer of a
+/// lockstep session (see [`World::config_fingerprint`]). It only contains names, numbers and policy enums, so
+/// identical setup code produces an identical fingerprint in every process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigFingerprint {
+    /// The name of each registered component, indexed by [`ComponentId`].
+    pub components: Vec<String>,
+    /// The compile-time constants that affect the layout and limits of the ECS, by name.
+    pub constants: Vec<(String, u64)>,
+    /// The order in which the ids of despawned entities are reused.
+    pub reuse_policy: ReusePolicy,
+    /// The storages whose policies aren't the default ones, ordered by their archetypes.
+    pub storage_policies: Vec<StoragePolicy>,
+    /// A description of each component rule (see [`World::require_component`]), sorted.
+    pub rules: Vec<String>,
+}
+
+/// Which side of a comparison between two [`ConfigFingerprint`]s something is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The fingerprint that [`ConfigFingerprint::diff`] was called on.
+    Ours,
+    /// The fingerprint that was passed to [`ConfigFingerprint::diff`].
+    Theirs,
+}
+
+/// A difference between the configurations of two [`World`]s (see [`ConfigFingerprint::diff`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigMismatch {
+    /// The same [`ComponentId`] was registered for different components.
+    RegistrationOrder {
+        /// The [`ComponentId`].
+        id: usize,
+        /// The name of our component with that id.
+        ours: String,
+        /// The name of their component with that id.
+        theirs: String,
+    },
+    /// A component is only registered on one side.
+    ExtraComponent {
+        /// The [`ComponentId`] of the component.
+        id: usize,
+        /// The name of the component.
+        name: String,
+        /// The side that registered it.
+        registered_by: Side,
+    },
+    /// A compile-time constant is different (the worlds were compiled with different features).
+    Constant {
+        /// The name of the constant.
+        name: String,
+        /// Our value of the constant.
+        ours: Option<u64>,
+        /// Their value of the constant.
+        theirs: Option<u64>,
+    },
+    /// The ids of despawned entities are reused in a different order.
+    ReusePolicy {
+        /// Our reuse policy.
+        ours: ReusePolicy,
+        /// Their reuse policy.
+        theirs: ReusePolicy,
+    },
+    /// A storage has different policies.
+    StoragePolicy {
+        /// The names of the components of the storage's archetype.
+        archetype: Vec<String>,
+        /// Our policies of the storage, or `None` if they are the default ones.
+        ours: Option<StoragePolicy>,
+        /// Their policies of the storage, or `None` if they are the default ones.
+        theirs: Option<StoragePolicy>,
+    },
+    /// A component rule was only declared on one side.
+    Rule {
+        /// The description of the rule.
+        rule: String,
+        /// The side that declared it.
+        declared_by: Side,
+    },
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Ours => f.write_str("us"),
+            Side::Theirs => f.write_str("them"),
+        }
+    }
+}
+
+impl fmt::Display for ConfigMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |policy: &Option<StoragePolicy>| match policy {
+            None => "the default policies".to_string(),
+            Some(StoragePolicy {
+                removal,
+                sort_key: Some(key),
+                ..
+            }) => format!("{removal:?} removal, sorted by `{key}`"),
+            Some(StoragePolicy { removal, .. }) => format!("{removal:?} removal"),
+        };
+        match self {
+            ConfigMismatch::RegistrationOrder { id, ours, theirs } => write!(
+                f,
+                "component #{id} is `{ours}` for us, but `{theirs}` for them"
+            ),
+            ConfigMismatch::ExtraComponent {
+                id,
+                name,
+                registered_by,
+            } => write!(
+                f,
+                "component #{id} (`{name}`) is only registered by {registered_by}"
+            ),
+            ConfigMismatch::Constant { name, ours, theirs } => {
+                write!(f, "`{name}` is {ours:?} for us, but {theirs:?} for them")
+            }
+            ConfigMismatch::ReusePolicy { ours, theirs } => write!(
+                f,
+                "entity ids are reused in {ours:?} order by us, but in {theirs:?} order by them"
+            ),
+            ConfigMismatch::StoragePolicy {
+                archetype,
+                ours,
+                theirs,
+            } => write!(
+                f,
+                "the storage of {archetype:?} has {} for us, but {} for them",
+                describe(ours),
+                describe(theirs)
+            ),
+            ConfigMismatch::Rule { rule, declared_by } => {
+                write!(f, "the rule {rule} is only declared by {declared_by}")
+            }
+        }
+    }
+}
+
+impl ConfigFingerprint {
+    /// A hash of the whole fingerprint, to compare cheaply during a handshake. It is computed from the fingerprint
+    /// alone (with FNV-1a, not with [`std::hash::Hash`]), so it's the same in every process and on every platform.
+    pub fn hash(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        hasher.write_list(self.components.iter().map(String::as_bytes));
+        for (name, value) in &self.constants {
+            hasher.write_field(name.as_bytes());
+            hasher.write_field(&value.to_le_bytes());
+        }
+        hasher.write_field(match self.reuse_policy {
+            ReusePolicy::Fifo => b"fifo",
+            ReusePolicy::Lifo => b"lifo",
+        });
+        for policy in &self.storage_policies {
+            hasher.write_list(policy.archetype.iter().map(String::as_bytes));
+            hasher.write_field(match policy.removal {
+                RemovalPolicy::Swap => b"swap",
+                RemovalPolicy::Shift => b"shift",
+            });
+            hasher.write_field(policy.sort_key.as_deref().unwrap_or_default().as_bytes());
+        }
+        hasher.write_list(self.rules.iter().map(String::as_bytes));
+        hasher.finish()
+    }
+
+    /// List every difference between two fingerprints, to report why two peers can't play together.
+    /// Returns an empty list if the fingerprints are the same.
+    pub fn diff(&self, other: &ConfigFingerprint) -> Vec<ConfigMismatch> {
+        let mut mismatches = Vec::new();
+        for id in 0..self.components.len().max(other.components.len()) {
+            let mismatch = match (self.components.get(id), other.components.get(id)) {
+                (Some(ours), Some(theirs)) if ours != theirs => ConfigMismatch::RegistrationOrder {
+                    id,
+                    ours: ours.clone(),
+                    theirs: theirs.clone(),
+                },
+                (Some(name), None) | (None, Some(name)) => ConfigMismatch::ExtraComponent {
+                    id,
+                    name: name.clone(),
+                    registered_by: if id < self.components.len() {
+                        Side::Ours
+                    } else {
+                        Side::Theirs
+                    },
+                },
+                _ => continue,
+            };
+            mismatches.push(mismatch);
+        }
+
+        let mut constants: Vec<&str> = self
+            .constants
+            .iter()
+            .chain(&other.constants)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        constants.sort_unstable();
+        constants.dedup();
+        let value = |fingerprint: &ConfigFingerprint, name: &str| {
+            fingerprint
+                .constants
+                .iter()
+                .find(|(constant, _)| constant == name)
+                .map(|(_, value)| *value)
+        };
+        for name in constants {
+            let (ours, theirs) = (value(self, name), value(other, name));
+            if ours != theirs {
+                mismatches.push(ConfigMismatch::Constant {
+                    name: name.to_string(),
+                    ours,
+                    theirs,
+                });
+            }
+        }
+
+        if self.reuse_policy != other.reuse_policy {
+            mismatches.push(ConfigMismatch::ReusePolicy {
+                ours: self.reuse_policy,
+                theirs: other.reuse_policy,
+            });
+        }
+
+        let mut archetypes: Vec<&Vec<String>> = self
+            .storage_policies
+            .iter()
+            .chain(&other.storage_policies)
+            .map(|policy| &policy.archetype)
+            .collect();
+        archetypes.sort_unstable();
+        archetypes.dedup();
+        let policy = |fingerprint: &ConfigFingerprint, archetype: &Vec<String>| {
+            fingerprint
+                .storage_policies
+                .iter()
+                .find(|policy| &policy.archetype == archetype)
+                .cloned()
+        };
+        for archetype in archetypes {
+            let (ours, theirs) = (policy(self, archetype), policy(other, archetype));
+            if ours != theirs {
+                mismatches.push(ConfigMismatch::StoragePolicy {
+                    archetype: archetype.clone(),
+                    ours,
+                    theirs,
+                });
+            }
+        }
+
+        for (rules, others, declared_by) in [
+            (&self.rules, &other.rules, Side::Ours),
+            (&other.rules, &self.rules, Side::Theirs),
+        ] {
+            mismatches.extend(
+                rules
+                    .iter()
+                    .filter(|rule| !others.contains(rule))
+                    .map(|rule| ConfigMismatch::Rule {
+                        rule: rule.clone(),
+                        declared_by,
+                    }),
+            );
+        }
+        mismatches
+    }
+}
+
+/// The 64-bit FNV-1a hash, which (unlike [`std::collections::hash_map::DefaultHasher`]) is specified,
+/// so it's stable across Rust versions. As a [`Hasher`], it writes integers in little-endian, and `usize`s as `u64`s,
+/// so it's also the same on every platform.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    /// Hash the bytes, and their length (so consecutive writes can't be confused with each other).
+    pub(crate) fn write_field(&mut self, bytes: &[u8]) {
+        self.write_u64(bytes.len() as u64);
+        self.write(bytes);
+    }
+
+    fn write_list<'a>(&mut self, items: impl ExactSizeIterator<Item = &'a [u8]>) {
+        self.write_field(&(items.len() as u64).to_le_bytes());
+        items.for_each(|item| self.write_field(item));
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes())
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16)
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32)
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64)
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128)
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64)
+    }
+}
+
+impl World {
+    /// Describe the configuration of the [`World`] that must be identical on every peer of a lockstep session:
+    /// the registered components (in the order of their [`ComponentId`]s), the compile-time constants, and the
+    /// runtime policies that affect determinism (the [`ReusePolicy`], the sort-maintained storages and the
+    /// component rules). Peers can compare [`ConfigFingerprint::hash`] during the handshake, and exchange the
+    /// [`ConfigFingerprint::diff`] when the hashes don't match.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32, i32);
+    /// #[derive(Component)]
+    /// struct Velocity(i32, i32);
+    ///
+    /// let (mut ours, mut theirs) = (World::default(), World::default());
+    /// ours.register_component::<Position>();
+    /// ours.register_component::<Velocity>();
+    /// theirs.register_component::<Velocity>();
+    /// theirs.register_component::<Position>();
+    /// let (ours, theirs) = (ours.config_fingerprint(), theirs.config_fingerprint());
+    /// // The components were registered in a different order.
+    /// assert_ne!(ours.hash(), theirs.hash());
+    /// assert!(!ours.diff(&theirs).is_empty());
+    /// ```
+    pub fn config_fingerprint(&self) -> ConfigFingerprint {
+        let name = |comp_id: ComponentId| {
+            self.components
+                .get_component_info_from_component_id(comp_id)
+                .expect("stored components are registered")
+                .name()
+        };
+        let components = (0..self.components.component_count())
+            .map(|id| name(ComponentId::new(id)).to_string())
+            .collect();
+        let constants = [
+            ("MAX_COMPONENTS", MAX_COMPONENTS as u64),
+            ("MAX_COMPS_PER_ARCH", MAX_COMPS_PER_ARCH as u64),
+            ("INLINE_ROWS", INLINE_ROWS as u64),
+            ("ENTITY_ID_BITS", u32::BITS as u64),
+            ("ENTITY_GENERATION_BITS", u32::BITS as u64),
+            // The auto-registered components take the first ids, so a peer without them registers differently.
+            ("AUTO_REGISTER", cfg!(feature = "auto-register") as u64),
+        ]
+        .map(|(name, value)| (name.to_string(), value))
+        .to_vec();
+
+        let mut storage_policies = Vec::new();
+        let mut sid = ArchStorageId(0);
+        while let Some(storage) = self.storages.arch_storages.get_storage(sid) {
+            sid = ArchStorageId(sid.0 + 1);
+            let Some(sort) = &storage.sort else {
+                continue;
+            };
+            let mut archetype: Vec<ComponentId> = storage.component_ids().collect();
+            archetype.sort_unstable();
+            storage_policies.push(StoragePolicy {
+                archetype: archetype
+                    .into_iter()
+                    .map(|id| name(id).to_string())
+                    .collect(),
+                removal: RemovalPolicy::Shift,
+                sort_key: Some(name(sort.key_component()).to_string()),
+            });
+        }
+        storage_policies.sort_unstable_by(|a, b| a.archetype.cmp(&b.archetype));
+
+        ConfigFingerprint {
+            components,
+            constants,
+            reuse_policy: self.entities.reuse_policy(),
+            storage_policies,
+            rules: self.components.rules.describe(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigMismatch, RemovalPolicy, Side, StoragePolicy};
+    use crate::prelude::*;
+    use std::any::type_name;
+
+    #[derive(Component)]
+    struct Position([i32; 2]);
+    #[derive(Component)]
+    struct Velocity;
+    #[derive(Component)]
+    struct Health;
+
+    fn setup() -> World {
+        let mut world = World::default();
+        world.components.register_component::<Position>();
+        world.components.register_component::<Velocity>();
+        world.maintain_sort::<(Position, Velocity), Position, [i32; 2]>(|position| position.0);
+        world.conflict_components::<Velocity, Health>();
+        world
+    }
+
+    #[test]
+    fn test_identical_setups_match() {
+        let (a, mut b) = (setup(), setup());
+        // Spawning doesn't change the configuration.
+        b.spawn((Position([0, 0]), Velocity));
+        let (a, b) = (a.config_fingerprint(), b.config_fingerprint());
+        assert_eq!(a, b);
+        assert_eq!(a.hash(), b.hash());
+        assert!(a.diff(&b).is_empty());
+        assert_eq!(a.components.len(), 3);
+    }
+
+    #[test]
+    fn test_registration_order_mismatch() {
+        let ours = setup();
+        let mut theirs = World::default();
+        theirs.components.register_component::<Velocity>();
+        theirs.components.register_component::<Position>();
+        theirs.maintain_sort::<(Position, Velocity), Position, [i32; 2]>(|position| position.0);
+        theirs.conflict_components::<Velocity, Health>();
+        let (ours, theirs) = (ours.config_fingerprint(), theirs.config_fingerprint());
+        assert_ne!(ours.hash(), theirs.hash());
+        let mismatches = ours.diff(&theirs);
+        assert_eq!(
+            mismatches[..2],
+            [
+                ConfigMismatch::RegistrationOrder {
+                    id: 0,
+                    ours: type_name::<Position>().to_string(),
+                    theirs: type_name::<Velocity>().to_string(),
+                },
+                ConfigMismatch::RegistrationOrder {
+                    id: 1,
+                    ours: type_name::<Velocity>().to_string(),
+                    theirs: type_name::<Position>().to_string(),
+                },
+            ]
+        );
+        assert!(mismatches[0].to_string().contains("component #0"));
+    }
+
+    #[test]
+    fn test_policy_mismatch() {
+        let ours = setup();
+        let mut theirs = setup();
+        theirs.set_entity_reuse_policy(ReusePolicy::Lifo);
+        let mismatches = ours.config_fingerprint().diff(&theirs.config_fingerprint());
+        assert_eq!(
+            mismatches,
+            [ConfigMismatch::ReusePolicy {
+                ours: ReusePolicy::Fifo,
+                theirs: ReusePolicy::Lifo,
+            }]
+        );
+
+        let mut theirs = World::default();
+        theirs.components.register_component::<Position>();
+        theirs.components.register_component::<Velocity>();
+        theirs.conflict_components::<Velocity, Health>();
+        let mismatches = ours.config_fingerprint().diff(&theirs.config_fingerprint());
+        let archetype = vec![
+            type_name::<Position>().to_string(),
+            type_name::<Velocity>().to_string(),
+        ];
+        assert_eq!(
+            mismatches,
+            [ConfigMismatch::StoragePolicy {
+                archetype: archetype.clone(),
+                ours: Some(StoragePolicy {
+                    archetype,
+                    removal: RemovalPolicy::Shift,
+                    sort_key: Some(type_name::<Position>().to_string()),
+                }),
+                theirs: None,
+            }]
+        );
+        assert!(mismatches[0]
+            .to_string()
+            .contains("for us, but the default policies for them"));
+    }
+
+    #[test]
+    fn test_extra_component_and_rule_mismatch() {
+        let ours = setup();
+        let mut theirs = setup();
+        theirs.components.register_component::<Health>();
+        theirs.require_component::<Position, Velocity>();
+        let (ours, theirs) = (ours.config_fingerprint(), theirs.config_fingerprint());
+        assert_ne!(ours.hash(), theirs.hash());
+        // `Health` was registered by the conflict rule on both sides, so nothing is extra yet.
+        assert_eq!(
+            ours.diff(&theirs),
+            [ConfigMismatch::Rule {
+                rule: format!(
+                    "`{}` requires `{}`",
+                    type_name::<Position>(),
+                    type_name::<Velocity>()
+                ),
+                declared_by: Side::Theirs,
+            }]
+        );
+
+        #[derive(Component)]
+        struct Shield;
+        let mut theirs = setup();
+        theirs.components.register_component::<Shield>();
+        let mismatches = ours.diff(&theirs.config_fingerprint());
+        assert_eq!(
+            mismatches,
+            [ConfigMismatch::ExtraComponent {
+                id: 3,
+                name: type_name::<Shield>().to_string(),
+                registered_by: Side::Theirs,
+            }]
+        );
+        assert!(mismatches[0]
+            .to_string()
+            .ends_with("is only registered by them"));
+    }
+}
@@ -0,0 +1,299 @@
+use super::{
+    storage::{
+        storages::{ArchStorageId, ArchStorages},
+        ArchEntityStorage,
+    },
+    World,
+};
+use crate::{
+    archetype::key::PrimeArchKey,
+    entity::EntityId,
+    prelude::{FetchContext, ReadOnlyArchQuery},
+};
+use std::{any::TypeId, collections::HashMap, marker::PhantomData, panic::Location};
+
+/// The results of the read-only queries that were cached in the current frame, see [`World::cached_frame_query`].
+#[derive(Default)]
+pub(crate) struct FrameQueries {
+    frame: u64,
+    cached: HashMap<TypeId, CachedQuery>,
+    /// How many times the matches of a query were collected, instead of reused from the cache.
+    materialized: usize,
+}
+
+/// The matches of a query, and what they were collected from, to tell when they are stale.
+struct CachedQuery {
+    /// The amount of storages when the matches were collected, so a new storage that matches the query is noticed.
+    storage_count: usize,
+    /// The storages that match the query (including empty ones), with their generations when the matches were
+    /// collected.
+    storages: Vec<(ArchStorageId, u64)>,
+    /// The matching entities, in the order of the storages and their rows.
+    entities: Vec<EntityId>,
+}
+
+impl CachedQuery {
+    fn collect(pkey: Option<PrimeArchKey>, arch_storages: &ArchStorages) -> Self {
+        let mut cached = CachedQuery {
+            storage_count: arch_storages.storage_count(),
+            storages: Vec::new(),
+            entities: Vec::new(),
+        };
+        let Some(pkey) = pkey else {
+            return cached;
+        };
+        let mut sid = ArchStorageId(0);
+        while let Some(id) = arch_storages.next_storage_with_matching_archetype(sid, pkey) {
+            sid = ArchStorageId(id.0 + 1);
+            let storage = arch_storages.get_storage(id).unwrap();
+            cached.storages.push((id, storage.generation()));
+            cached.entities.extend_from_slice(storage.entities());
+        }
+        cached
+    }
+
+    /// Returns `true` if no storage that the matches were collected from changed since, and no storage was created.
+    fn is_fresh(&self, arch_storages: &ArchStorages) -> bool {
+        self.storage_count == arch_storages.storage_count()
+            && self.storages.iter().all(|(sid, generation)| {
+                arch_storages
+                    .get_storage(*sid)
+                    .is_some_and(|storage| storage.generation() == *generation)
+            })
+    }
+}
+
+/// The matches of a read-only query, cached for the rest of the frame (see [`World::cached_frame_query`]).
+/// The entities are cached, and their components are fetched again from their rows (without matching the query
+/// again) when the results are iterated.
+pub struct FrameQueryResult<'w, Q: ReadOnlyArchQuery> {
+    cached: &'w CachedQuery,
+    world: &'w World,
+    _query: PhantomData<fn() -> Q>,
+}
+
+impl<'w, Q: ReadOnlyArchQuery> FrameQueryResult<'w, Q> {
+    /// The amount of matches.
+    pub fn len(&self) -> usize {
+        self.cached.entities.len()
+    }
+
+    /// Returns `true` if nothing matches the query.
+    pub fn is_empty(&self) -> bool {
+        self.cached.entities.is_empty()
+    }
+
+    /// The matching entities, in the same order as [`Self::iter`].
+    pub fn entities(&self) -> &'w [EntityId] {
+        &self.cached.entities
+    }
+
+    /// Iterate over the items of the query.
+    pub fn iter(&self) -> impl Iterator<Item = Q::Item<'w>> + 'w {
+        let (arch_storages, components) =
+            (&self.world.storages.arch_storages, &self.world.components);
+        let tags = self.world.entity_tags();
+        self.cached.storages.iter().flat_map(move |(sid, _)| {
+            let storage = arch_storages.get_storage(*sid).unwrap();
+            let ctx = FetchContext::new(tags).for_query::<Q>(storage, components);
+            storage.iter_indices().map(move |index| {
+                // SAFETY: The storage didn't change since the matches were collected (or the cache would be stale),
+                // so the index is in bounds, and `Q` only reads, so nothing is mutated through the pointer.
+                unsafe {
+                    Q::fetch(
+                        storage as *const ArchEntityStorage as *mut ArchEntityStorage,
+                        index,
+                        components,
+                        ctx,
+                    )
+                }
+            })
+        })
+    }
+}
+
+impl World {
+    /// Query the world like [`World::query_shared`], and cache the matches until the end of the frame (see
+    /// [`World::end_query_cache_frame`]), so running the same query again in the frame (like a list of units that
+    /// is shown in several debug panels) doesn't match and iterate the storages again. The cache is keyed by the
+    /// type of the query.
+    ///
+    /// The cache is invalidated conservatively: the matches are collected again if any storage that matches the
+    /// query changed since (see [`ArchStorage::generation`](super::storage::arch_storage::ArchStorage::generation)),
+    /// which happens whenever an entity is stored in it or removed from it, and whenever mutable access to any of
+    /// its components is handed out, even to components that the query doesn't read, and even if nothing is
+    /// written. A new storage that could match the query also invalidates it. Mutations through interior
+    /// mutability (see [`WorldReadScope`](super::read_scope::WorldReadScope)) don't invalidate the cache, but they are
+    /// seen anyway, because the components are fetched again when the results are iterated.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Unit(u32);
+    ///
+    /// let mut world = World::default();
+    /// for i in 0..10 {
+    ///     world.spawn(Unit(i));
+    /// }
+    /// for _panel in 0..3 {
+    ///     let units = world.cached_frame_query::<&Unit>();
+    ///     assert_eq!(units.iter().map(|unit| unit.0).sum::<u32>(), 45);
+    /// }
+    /// world.end_query_cache_frame();
+    /// ```
+    #[track_caller]
+    pub fn cached_frame_query<Q: ReadOnlyArchQuery + 'static>(
+        &mut self,
+    ) -> FrameQueryResult<'_, Q> {
+        self.access
+            .record_query::<Q>(&self.components, Location::caller());
+        let arch_storages = &self.storages.arch_storages;
+        let key = TypeId::of::<Q>();
+        let fresh = self
+            .frame_queries
+            .cached
+            .get(&key)
+            .is_some_and(|cached| cached.is_fresh(arch_storages));
+        if !fresh {
+            let cached = CachedQuery::collect(self.resolve_query_key::<Q>(), arch_storages);
+            self.frame_queries.materialized += 1;
+            self.frame_queries.cached.insert(key, cached);
+        }
+        FrameQueryResult {
+            cached: &self.frame_queries.cached[&key],
+            world: self,
+            _query: PhantomData,
+        }
+    }
+
+    /// The current query cache frame: how many times [`World::end_query_cache_frame`] was called.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// assert_eq!(world.query_cache_frame(), 0);
+    /// world.end_query_cache_frame();
+    /// assert_eq!(world.query_cache_frame(), 1);
+    /// ```
+    pub fn query_cache_frame(&self) -> u64 {
+        self.frame_queries.frame
+    }
+
+    /// Clear the cached results of the queries of the frame (see [`World::cached_frame_query`]), and start the next
+    /// one. This should be called at the end of each frame.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Unit(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Unit(1));
+    /// assert_eq!(world.cached_frame_query::<&Unit>().len(), 1);
+    /// world.end_query_cache_frame();
+    /// world.spawn(Unit(2));
+    /// assert_eq!(world.cached_frame_query::<&Unit>().len(), 2);
+    /// ```
+    pub fn end_query_cache_frame(&mut self) {
+        let frame_queries = &mut self.frame_queries;
+        frame_queries.frame += 1;
+        frame_queries.cached.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Unit(u32);
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Health(u32);
+    #[derive(Component)]
+    struct Building;
+
+    fn world() -> World {
+        let mut world = World::default();
+        for i in 0..10 {
+            match i % 2 {
+                0 => world.spawn(Unit(i)),
+                _ => world.spawn((Unit(i), Health(i * 10))),
+            };
+        }
+        world.spawn(Building);
+        world
+    }
+
+    fn units(world: &mut World) -> Vec<(EntityId, u32)> {
+        world
+            .cached_frame_query::<(EntityId, &Unit)>()
+            .iter()
+            .map(|(entity, unit)| (entity, unit.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_repeated_queries_are_materialized_once() {
+        let mut world = world();
+        let first = units(&mut world);
+        assert_eq!(first.len(), 10);
+        assert_eq!(units(&mut world), first);
+        let cached = world.cached_frame_query::<(EntityId, &Unit)>();
+        assert_eq!(cached.len(), 10);
+        assert_eq!(
+            cached.entities(),
+            first.iter().map(|(entity, _)| *entity).collect::<Vec<_>>()
+        );
+        assert_eq!(world.frame_queries.materialized, 1);
+        // Each query type is cached separately.
+        world.cached_frame_query::<&Health>();
+        assert_eq!(world.frame_queries.materialized, 2);
+    }
+
+    #[test]
+    fn test_writes_and_structural_changes_invalidate() {
+        let mut world = world();
+        let entity = units(&mut world)[3].0;
+        world.get_component_mut::<Unit>(entity).unwrap().0 = 100;
+        assert!(units(&mut world).contains(&(entity, 100)));
+        assert_eq!(world.frame_queries.materialized, 2);
+
+        // Writes to storages that don't match the query don't invalidate it.
+        world.query::<&mut Building>().for_each(drop);
+        units(&mut world);
+        assert_eq!(world.frame_queries.materialized, 2);
+
+        // A new matching storage, and a despawn from a matching storage, do.
+        let building = world.spawn((Unit(7), Building));
+        assert!(units(&mut world).contains(&(building, 7)));
+        world.despawn(entity);
+        assert_eq!(units(&mut world).len(), 10);
+        assert_eq!(world.frame_queries.materialized, 4);
+    }
+
+    #[test]
+    fn test_frame_boundary_clears_the_cache() {
+        let mut world = world();
+        units(&mut world);
+        units(&mut world);
+        world.end_query_cache_frame();
+        assert_eq!(world.query_cache_frame(), 1);
+        assert!(world.frame_queries.cached.is_empty());
+        units(&mut world);
+        assert_eq!(world.frame_queries.materialized, 2);
+    }
+
+    #[test]
+    fn test_unregistered_components() {
+        #[derive(Component)]
+        struct Unknown;
+
+        let mut world = world();
+        assert!(world.cached_frame_query::<&Unknown>().is_empty());
+        world.spawn(Unknown);
+        assert_eq!(world.cached_frame_query::<&Unknown>().len(), 1);
+    }
+}
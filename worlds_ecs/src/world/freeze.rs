@@ -0,0 +1,449 @@
+use super::{storage::storages::ArchetypeId, World};
+use crate::{
+    archetype::Archetype,
+    component::Component,
+    entity::EntityId,
+    storage::columns::Columns,
+    world::{shared::SharedHandle, storage::arch_storage::fail_indirect_column},
+};
+use std::{any::TypeId, collections::HashMap, fmt, sync::Arc};
+
+/// A handle to the components of a frozen archetype (see [`World::freeze_archetype`]).
+///
+/// The handle doesn't borrow the [`World`], it's cheap to clone, and it's [`Send`] and [`Sync`], so worker threads
+/// can read the components without any coordination while the rest of the world is mutated. The components stay
+/// readable for as long as the handle is alive, even if the world is dropped.
+#[derive(Clone)]
+pub struct FrozenColumns {
+    inner: Arc<FrozenInner>,
+}
+
+struct FrozenInner {
+    columns: Arc<Columns>,
+    /// The columns in the order that the components of a row are dropped in, if this is the last handle of the
+    /// columns when it's dropped.
+    drop_order: Option<Arc<[usize]>>,
+    archetype: ArchetypeId,
+    entities: Box<[EntityId]>,
+    rows: HashMap<EntityId, usize>,
+    /// The column of each component, by the [`TypeId`] that it was registered with.
+    columns_of_types: HashMap<TypeId, usize>,
+}
+
+impl Drop for FrozenInner {
+    fn drop(&mut self) {
+        // Like the drop of a storage, the declared drop order is applied first.
+        if let (Some(order), Some(columns)) = (&self.drop_order, Arc::get_mut(&mut self.columns)) {
+            columns.truncate_in_order(0, order);
+        }
+    }
+}
+
+impl FrozenColumns {
+    /// The archetype whose components are frozen.
+    pub fn archetype(&self) -> ArchetypeId {
+        self.inner.archetype
+    }
+
+    /// The entities of the archetype, in the order of their rows.
+    pub fn entities(&self) -> &[EntityId] {
+        &self.inner.entities
+    }
+
+    /// The amount of entities of the archetype.
+    pub fn len(&self) -> usize {
+        self.inner.entities.len()
+    }
+
+    /// Returns `true` if the archetype has no entities.
+    pub fn is_empty(&self) -> bool {
+        self.inner.entities.is_empty()
+    }
+
+    /// All of the components `C` of the archetype, in the order of [`Self::entities`]. Returns `None` if the
+    /// archetype doesn't have the component.
+    ///
+    /// # Panics
+    /// If `C` is boxed (see [`Component::BOXED`]) or shared (see [`Component::SHARED`]), because its values aren't
+    /// stored contiguously. Use [`Self::get`] instead.
+    #[track_caller]
+    pub fn column<C: Component>(&self) -> Option<&[C]> {
+        if C::BOXED || C::SHARED {
+            fail_indirect_column::<C>("FrozenColumns::column");
+        }
+        let column = *self.inner.columns_of_types.get(&TypeId::of::<C>())?;
+        // SAFETY: The column was registered with the type of `C`.
+        Some(unsafe { self.inner.columns.as_slice::<C>(column) })
+    }
+
+    /// The component `C` of an entity, or `None` if the entity isn't one of [`Self::entities`] (or the archetype
+    /// doesn't have the component).
+    pub fn get<C: Component>(&self, entity: EntityId) -> Option<&C> {
+        let row = *self.inner.rows.get(&entity)?;
+        let column = *self.inner.columns_of_types.get(&TypeId::of::<C>())?;
+        let columns = &self.inner.columns;
+        // SAFETY: The column was registered with the type of `C`, which decides how its values are stored, and the
+        // row is in its bounds.
+        unsafe {
+            Some(match (C::SHARED, C::BOXED) {
+                (true, _) => columns.as_slice::<SharedHandle<C>>(column)[row].value(),
+                (false, true) => &columns.as_slice::<Box<C>>(column)[row],
+                (false, false) => &columns.as_slice::<C>(column)[row],
+            })
+        }
+    }
+
+    /// The amount of handles of the same frozen components, including this one (and the one that the world keeps).
+    fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.inner)
+    }
+}
+
+impl fmt::Debug for FrozenColumns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrozenColumns")
+            .field("archetype", &self.inner.archetype)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// An error when unfreezing an archetype (see [`World::unfreeze_archetype`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnfreezeError {
+    /// The archetype isn't frozen.
+    NotFrozen,
+    /// Handles of the frozen components are still alive, so they may still be read.
+    HandlesAlive {
+        /// The amount of handles that are alive.
+        handles: usize,
+    },
+}
+
+impl fmt::Display for UnfreezeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnfreezeError::NotFrozen => write!(f, "the archetype isn't frozen"),
+            UnfreezeError::HandlesAlive { handles } => {
+                write!(
+                    f,
+                    "{handles} handles of the frozen components are still alive"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for UnfreezeError {}
+
+impl World {
+    /// Freeze the storage of an archetype, so its components can be read through the returned [`FrozenColumns`]
+    /// without borrowing the world, from any thread. This is meant for data that is spawned once and never
+    /// mutated, but read a lot, like the geometry of a level. If the archetype isn't stored yet, an empty storage
+    /// is created for it. If it's already frozen, another handle of its components is returned.
+    ///
+    /// While the storage is frozen, it's pinned (see [`World::pin_storage`]), and none of its entities can be
+    /// spawned into it: operations that would store or remove rows return a [`StoragePinned`] error if they are
+    /// fallible (like [`World::archive`]), and panic otherwise (like [`World::spawn`] and [`World::despawn`]).
+    /// Mutable access to its components panics (like [`World::get_component_mut`], and a mutable query that
+    /// matches it), but reading them works as usual, including through read-only queries.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Wall(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn_batch((0..100).map(Wall));
+    /// let walls = world.freeze_archetype::<Wall>();
+    /// let total = std::thread::spawn(move || walls.column::<Wall>().unwrap().iter().map(|w| w.0).sum::<u32>());
+    /// assert_eq!(total.join().unwrap(), 4950);
+    /// world.unfreeze_archetype::<Wall>().unwrap();
+    /// ```
+    ///
+    /// [`StoragePinned`]: super::pin::StoragePinned
+    #[track_caller]
+    pub fn freeze_archetype<A: Archetype>(&mut self) -> FrozenColumns {
+        let arch_storages = &mut self.storages.arch_storages;
+        let (sid, _) =
+            arch_storages.get_mut_or_create_storage_with_exact_archetype::<A>(&mut self.components);
+        let archetype = arch_storages.archetype_of_storage(sid).unwrap();
+        let storage = arch_storages.get_storage_mut(sid).unwrap();
+        if let Some(frozen) = &storage.frozen {
+            return frozen.clone();
+        }
+        let columns_of_types = self
+            .components
+            .iter_type_ids()
+            .filter_map(|(type_id, comp_id)| Some((type_id, storage.column_of(comp_id)?)))
+            .collect();
+        let (columns, drop_order) = storage.freeze();
+        let entities: Box<[EntityId]> = storage.entities().into();
+        let frozen = FrozenColumns {
+            inner: Arc::new(FrozenInner {
+                columns,
+                drop_order,
+                archetype,
+                rows: entities
+                    .iter()
+                    .enumerate()
+                    .map(|(row, e)| (*e, row))
+                    .collect(),
+                entities,
+                columns_of_types,
+            }),
+        };
+        storage.frozen = Some(frozen.clone());
+        frozen
+    }
+
+    /// Unfreeze the storage of an archetype (see [`World::freeze_archetype`]), so it can be mutated again. Every
+    /// [`FrozenColumns`] of it must be dropped first: if some are still alive, their amount is returned in an
+    /// error, and the archetype stays frozen.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Wall(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Wall(1));
+    /// let walls = world.freeze_archetype::<Wall>();
+    /// assert_eq!(
+    ///     world.unfreeze_archetype::<Wall>(),
+    ///     Err(UnfreezeError::HandlesAlive { handles: 1 })
+    /// );
+    /// drop(walls);
+    /// assert_eq!(world.unfreeze_archetype::<Wall>(), Ok(()));
+    /// world.spawn(Wall(2));
+    /// ```
+    pub fn unfreeze_archetype<A: Archetype>(&mut self) -> Result<(), UnfreezeError> {
+        let storage = A::prime_key(&self.components)
+            .and_then(|pkey| {
+                (self.storages.arch_storages).get_mut_storage_with_exact_archetype(pkey)
+            })
+            .filter(|storage| storage.frozen.is_some())
+            .ok_or(UnfreezeError::NotFrozen)?;
+        let handles = storage.frozen.as_ref().unwrap().handle_count() - 1;
+        if handles > 0 {
+            return Err(UnfreezeError::HandlesAlive { handles });
+        }
+        storage.unfreeze();
+        Ok(())
+    }
+
+    /// Returns `true` if the archetype is frozen (see [`World::freeze_archetype`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Wall(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Wall(1));
+    /// assert!(!world.is_archetype_frozen::<Wall>());
+    /// drop(world.freeze_archetype::<Wall>());
+    /// assert!(world.is_archetype_frozen::<Wall>());
+    /// world.unfreeze_archetype::<Wall>().unwrap();
+    /// assert!(!world.is_archetype_frozen::<Wall>());
+    /// ```
+    pub fn is_archetype_frozen<A: Archetype>(&self) -> bool {
+        A::prime_key(&self.components)
+            .and_then(|pkey| (self.storages.arch_storages).get_storage_with_exact_archetype(pkey))
+            .is_some_and(|storage| storage.frozen.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnfreezeError;
+    use crate::{prelude::*, test_utils::*};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Wall(u32);
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Height(u32);
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Velocity(u32);
+
+    fn level(walls: u32) -> (World, Vec<EntityId>) {
+        let mut world = World::default();
+        world.register_cloneable_component::<Wall>();
+        world.register_cloneable_component::<Height>();
+        let entities = world.spawn_batch((0..walls).map(|i| (Wall(i), Height(i * 2))));
+        (world, entities)
+    }
+
+    #[test]
+    fn test_concurrent_reads_while_the_world_churns() {
+        let (mut world, walls) = level(1000);
+        let frozen = world.freeze_archetype::<(Wall, Height)>();
+        assert_eq!(frozen.entities(), walls);
+        let done = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let readers: Vec<_> = (0..4)
+                .map(|_| {
+                    let (frozen, done) = (frozen.clone(), &done);
+                    scope.spawn(move || {
+                        let mut reads = 0;
+                        while !done.load(Ordering::Relaxed) || reads == 0 {
+                            let walls = frozen.column::<Wall>().unwrap();
+                            assert_eq!(walls.iter().map(|wall| wall.0).sum::<u32>(), 499500);
+                            assert_eq!(
+                                frozen.get::<Height>(frozen.entities()[10]),
+                                Some(&Height(20))
+                            );
+                            reads += 1;
+                        }
+                    })
+                })
+                .collect();
+            // Other archetypes, including ones with the same components, churn while the readers read.
+            let script = ChurnScript::new(7)
+                .steps(3000)
+                .archetypes(&[&[0], &[0, 1], &[2]]);
+            script.run(&mut world);
+            for i in 0..500 {
+                let entity = world.spawn((Wall(i), Velocity(i)));
+                world.get_component_mut::<Wall>(entity).unwrap().0 += 1;
+                if i % 2 == 0 {
+                    world.despawn(entity);
+                }
+            }
+            done.store(true, Ordering::Relaxed);
+            readers
+                .into_iter()
+                .for_each(|reader| reader.join().unwrap());
+        });
+        assert_eq!(frozen.get::<Wall>(walls[999]), Some(&Wall(999)));
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_mutations_are_rejected_while_frozen() {
+        let (mut world, walls) = level(3);
+        let _frozen = world.freeze_archetype::<(Wall, Height)>();
+        let archetype = world.archetype_id_of(walls[0]).unwrap();
+        let ArchiveError::Pinned(error) = world.archive(walls[0]).unwrap_err() else {
+            panic!("the storage is frozen")
+        };
+        assert!(error.frozen);
+        assert_eq!(error.archetype, archetype);
+        assert!(error.to_string().ends_with("is frozen"));
+        let storage_id = error.storage;
+        assert_eq!(
+            world.reorder_storage(storage_id, |entity| u64::MAX - entity.to_bits()),
+            Err(ReorderError::Pinned(error))
+        );
+        // A copy of the world isn't frozen.
+        let mut copy = world.clone_cow().unwrap();
+        copy.get_component_mut::<Wall>(walls[0]).unwrap().0 = 10;
+        copy.despawn(walls[1]);
+        assert_entity_has(&world, walls[0], Wall(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: spawn failed: the storage")]
+    fn test_spawn_panics() {
+        let (mut world, _) = level(3);
+        let _frozen = world.freeze_archetype::<(Wall, Height)>();
+        world.spawn((Wall(3), Height(6)));
+    }
+
+    #[test]
+    #[should_panic(expected = "is frozen")]
+    fn test_despawn_panics() {
+        let (mut world, walls) = level(3);
+        let _frozen = world.freeze_archetype::<(Wall, Height)>();
+        world.despawn(walls[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: write failed: the storage is frozen")]
+    fn test_get_component_mut_panics() {
+        let (mut world, walls) = level(3);
+        let _frozen = world.freeze_archetype::<(Wall, Height)>();
+        world.get_component_mut::<Height>(walls[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: write failed: the storage is frozen")]
+    fn test_mutable_query_panics() {
+        let (mut world, _) = level(3);
+        let _frozen = world.freeze_archetype::<(Wall, Height)>();
+        world.query::<&mut Wall>().for_each(|wall| wall.0 += 1);
+    }
+
+    #[test]
+    fn test_unfreeze_waits_for_the_handles() {
+        let (mut world, walls) = level(3);
+        assert_eq!(
+            world.unfreeze_archetype::<(Wall, Height)>(),
+            Err(UnfreezeError::NotFrozen)
+        );
+        let first = world.freeze_archetype::<(Wall, Height)>();
+        let second = world.freeze_archetype::<(Height, Wall)>();
+        let third = second.clone();
+        assert!(world.is_archetype_frozen::<(Wall, Height)>());
+        assert!(!world.is_archetype_frozen::<Wall>());
+        assert_eq!(
+            world.unfreeze_archetype::<(Wall, Height)>(),
+            Err(UnfreezeError::HandlesAlive { handles: 3 })
+        );
+        drop(first);
+        std::thread::spawn(move || drop(second)).join().unwrap();
+        assert_eq!(
+            world.unfreeze_archetype::<(Wall, Height)>(),
+            Err(UnfreezeError::HandlesAlive { handles: 1 })
+        );
+        drop(third);
+        assert_eq!(world.unfreeze_archetype::<(Wall, Height)>(), Ok(()));
+        assert!(!world.is_archetype_frozen::<(Wall, Height)>());
+
+        // The storage can be mutated again, without copying its components.
+        world.get_component_mut::<Wall>(walls[0]).unwrap().0 = 10;
+        world.despawn(walls[1]);
+        world.spawn((Wall(3), Height(6)));
+        let storage_id = world
+            .archetype(world.archetype_id_of(walls[0]).unwrap())
+            .unwrap()
+            .storage_id();
+        assert_eq!(
+            world
+                .storages
+                .arch_storages
+                .get_storage(storage_id)
+                .unwrap()
+                .unshare_count(),
+            0
+        );
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_read_only_queries_over_frozen_storages() {
+        let (mut world, walls) = level(10);
+        world.spawn((Wall(100), Velocity(1)));
+        let frozen = world.freeze_archetype::<(Wall, Height)>();
+        let mut total: Vec<u32> = world.query::<&Wall>().map(|wall| wall.0).collect();
+        total.sort();
+        assert_eq!(total, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 100]);
+        assert_query_count::<(&Wall, &Height)>(&mut world, 10);
+        assert_eq!(world.query_shared::<&Height>().count(), 10);
+        assert_eq!(world.get_component::<Height>(walls[4]), Some(&Height(8)));
+        // Mutable queries over other storages work as usual.
+        world
+            .query::<(&mut Wall, &Velocity)>()
+            .for_each(|(wall, _)| wall.0 += 1);
+        assert_eq!(world.query_view::<&Wall>().get(walls[9]), Some(&Wall(9)));
+        assert_eq!(frozen.get::<Wall>(walls[9]), Some(&Wall(9)));
+
+        // The handle outlives the world.
+        drop(world);
+        assert_eq!(frozen.column::<Height>().unwrap()[9], Height(18));
+    }
+}
@@ -0,0 +1,505 @@
+use super::World;
+use crate::{
+    entity::{EntityId, EntityIdSet},
+    impl_id_struct,
+    utils::panics,
+};
+use std::collections::HashMap;
+
+/// Identifies a group of entities in a [`World`], see [`World::create_group`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GroupId(usize);
+
+impl_id_struct!(GroupId, usize, pub(crate));
+
+/// The groups of a [`World`], by their [`GroupId`].
+#[derive(Default, Clone)]
+pub(crate) struct Groups {
+    names: HashMap<String, GroupId>,
+    sets: Vec<EntityIdSet>,
+}
+
+impl Groups {
+    /// The members of a group, including the members that were despawned and weren't purged yet.
+    #[track_caller]
+    pub(crate) fn members(&self, group: GroupId) -> &EntityIdSet {
+        self.sets.get(group.0).unwrap_or_else(|| fail_group(group))
+    }
+
+    #[track_caller]
+    fn members_mut(&mut self, group: GroupId) -> &mut EntityIdSet {
+        self.sets
+            .get_mut(group.0)
+            .unwrap_or_else(|| fail_group(group))
+    }
+}
+
+#[cold]
+#[track_caller]
+fn fail_group(group: GroupId) -> ! {
+    panics::fail(
+        "group",
+        "the group wasn't created in this world",
+        &[("group", &group.0)],
+    )
+}
+
+impl World {
+    /// Create a named group of entities, or get the group with this name if it was already created. Groups are
+    /// persistent sets of entities (like "selected units", or "entities in trigger volume 7") with fast membership
+    /// checks, iteration and set operations, which can also filter queries (see
+    /// [`QueryIter::in_group`](crate::query::query_iter::QueryIter::in_group)).
+    ///
+    /// Despawned entities leave their groups lazily: they are never yielded or reported as members, and they are
+    /// purged from a group the next time it's iterated. A group is never cleaned when entities are despawned, so
+    /// despawning costs the same however many groups there are. An entity that reuses the id of a despawned member
+    /// isn't a member.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let selected = world.create_group("selected");
+    /// assert_eq!(world.create_group("selected"), selected);
+    /// let unit = world.spawn(());
+    /// world.group_insert(selected, unit);
+    /// world.despawn(unit);
+    /// assert_eq!(world.group_len(selected), 0);
+    /// ```
+    pub fn create_group(&mut self, name: &str) -> GroupId {
+        if let Some(group) = self.groups.names.get(name) {
+            return *group;
+        }
+        let group = GroupId(self.groups.sets.len());
+        self.groups.sets.push(EntityIdSet::default());
+        self.groups.names.insert(name.to_owned(), group);
+        group
+    }
+
+    /// The group with this name, if it was created (see [`World::create_group`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let selected = world.create_group("selected");
+    /// assert_eq!(world.group("selected"), Some(selected));
+    /// assert_eq!(world.group("hovered"), None);
+    /// ```
+    pub fn group(&self, name: &str) -> Option<GroupId> {
+        self.groups.names.get(name).copied()
+    }
+
+    /// Add an entity to a group. Returns `false` if it's already a member.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let selected = world.create_group("selected");
+    /// let unit = world.spawn(());
+    /// assert!(world.group_insert(selected, unit));
+    /// assert!(!world.group_insert(selected, unit));
+    /// ```
+    ///
+    /// # Panics
+    /// If the entity isn't alive, or the group wasn't created in this world.
+    #[track_caller]
+    pub fn group_insert(&mut self, group: GroupId, entity: EntityId) -> bool {
+        if self.entities.get_entity_meta(entity).is_none() {
+            panics::fail_entity("group_insert", "the entity isn't alive", entity);
+        }
+        self.groups.members_mut(group).insert(entity)
+    }
+
+    /// Remove an entity from a group. Returns `false` if it isn't a member.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let selected = world.create_group("selected");
+    /// let unit = world.spawn(());
+    /// world.group_insert(selected, unit);
+    /// assert!(world.group_remove(selected, unit));
+    /// assert!(!world.group_remove(selected, unit));
+    /// ```
+    ///
+    /// # Panics
+    /// If the group wasn't created in this world.
+    #[track_caller]
+    pub fn group_remove(&mut self, group: GroupId, entity: EntityId) -> bool {
+        self.groups.members_mut(group).remove(entity) && self.is_alive(entity)
+    }
+
+    /// Returns `true` if the entity is alive, and a member of the group.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let selected = world.create_group("selected");
+    /// let unit = world.spawn(());
+    /// world.group_insert(selected, unit);
+    /// assert!(world.group_contains(selected, unit));
+    /// world.despawn(unit);
+    /// assert!(!world.group_contains(selected, unit));
+    /// ```
+    ///
+    /// # Panics
+    /// If the group wasn't created in this world.
+    #[track_caller]
+    pub fn group_contains(&self, group: GroupId, entity: EntityId) -> bool {
+        self.groups.members(group).contains(entity) && self.is_alive(entity)
+    }
+
+    /// The amount of alive members of the group.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let selected = world.create_group("selected");
+    /// for _ in 0..3 {
+    ///     let unit = world.spawn(());
+    ///     world.group_insert(selected, unit);
+    /// }
+    /// assert_eq!(world.group_len(selected), 3);
+    /// ```
+    ///
+    /// # Panics
+    /// If the group wasn't created in this world.
+    #[track_caller]
+    pub fn group_len(&mut self, group: GroupId) -> usize {
+        self.purge_group(group);
+        self.groups.members(group).len()
+    }
+
+    /// Iterate over the alive members of the group, in the order they were added (until a member is removed).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let selected = world.create_group("selected");
+    /// let (a, b) = (world.spawn(()), world.spawn(()));
+    /// world.group_insert(selected, a);
+    /// world.group_insert(selected, b);
+    /// assert_eq!(world.iter_group(selected).collect::<Vec<_>>(), vec![a, b]);
+    /// ```
+    ///
+    /// # Panics
+    /// If the group wasn't created in this world.
+    #[track_caller]
+    pub fn iter_group(&mut self, group: GroupId) -> impl Iterator<Item = EntityId> + '_ {
+        self.purge_group(group);
+        self.groups.members(group).iter()
+    }
+
+    /// Iterate over the alive members of `a` that are also members of `b`, in the order of `a`. Nothing is
+    /// collected: each member of `a` is looked up in `b`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let (selected, visible) = (world.create_group("selected"), world.create_group("visible"));
+    /// let (a, b) = (world.spawn(()), world.spawn(()));
+    /// world.group_insert(selected, a);
+    /// world.group_insert(selected, b);
+    /// world.group_insert(visible, b);
+    /// let both: Vec<EntityId> = world.iter_group_intersection(selected, visible).collect();
+    /// assert_eq!(both, vec![b]);
+    /// ```
+    ///
+    /// # Panics
+    /// If either group wasn't created in this world.
+    #[track_caller]
+    pub fn iter_group_intersection(
+        &mut self,
+        a: GroupId,
+        b: GroupId,
+    ) -> impl Iterator<Item = EntityId> + '_ {
+        let (a, b) = self.purge_groups(a, b);
+        a.iter().filter(|entity| b.contains(*entity))
+    }
+
+    /// Iterate over the alive members of `a` that aren't members of `b`, in the order of `a`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let (selected, visible) = (world.create_group("selected"), world.create_group("visible"));
+    /// let (a, b) = (world.spawn(()), world.spawn(()));
+    /// world.group_insert(selected, a);
+    /// world.group_insert(selected, b);
+    /// world.group_insert(visible, b);
+    /// let hidden: Vec<EntityId> = world.iter_group_difference(selected, visible).collect();
+    /// assert_eq!(hidden, vec![a]);
+    /// ```
+    ///
+    /// # Panics
+    /// If either group wasn't created in this world.
+    #[track_caller]
+    pub fn iter_group_difference(
+        &mut self,
+        a: GroupId,
+        b: GroupId,
+    ) -> impl Iterator<Item = EntityId> + '_ {
+        let (a, b) = self.purge_groups(a, b);
+        a.iter().filter(|entity| !b.contains(*entity))
+    }
+
+    /// Iterate over the alive members of either group, each one once: the members of `a`, and then the members of
+    /// `b` that aren't members of `a`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let (selected, visible) = (world.create_group("selected"), world.create_group("visible"));
+    /// let (a, b) = (world.spawn(()), world.spawn(()));
+    /// world.group_insert(selected, a);
+    /// world.group_insert(selected, b);
+    /// world.group_insert(visible, b);
+    /// let either: Vec<EntityId> = world.iter_group_union(selected, visible).collect();
+    /// assert_eq!(either, vec![a, b]);
+    /// ```
+    ///
+    /// # Panics
+    /// If either group wasn't created in this world.
+    #[track_caller]
+    pub fn iter_group_union(
+        &mut self,
+        a: GroupId,
+        b: GroupId,
+    ) -> impl Iterator<Item = EntityId> + '_ {
+        let (a, b) = self.purge_groups(a, b);
+        a.iter()
+            .chain(b.iter().filter(|entity| !a.contains(*entity)))
+    }
+
+    /// Despawn every alive member of the group (including the archived members, see [`World::archive`]), and
+    /// return how many entities were despawned. The group itself stays.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let wave = world.create_group("wave");
+    /// for _ in 0..3 {
+    ///     let enemy = world.spawn(());
+    ///     world.group_insert(wave, enemy);
+    /// }
+    /// assert_eq!(world.despawn_group(wave), 3);
+    /// assert_eq!(world.group_len(wave), 0);
+    /// ```
+    ///
+    /// # Panics
+    /// If the group wasn't created in this world, or like [`World::despawn`].
+    #[track_caller]
+    pub fn despawn_group(&mut self, group: GroupId) -> usize {
+        let members: Vec<EntityId> = self.iter_group(group).collect();
+        for entity in &members {
+            self.despawn(*entity);
+        }
+        members.len()
+    }
+
+    /// Remove the despawned members of a group.
+    #[track_caller]
+    fn purge_group(&mut self, group: GroupId) {
+        let entities = &self.entities;
+        self.groups
+            .members_mut(group)
+            .retain(|entity| entities.get_entity_meta(entity).is_some());
+    }
+
+    #[track_caller]
+    fn purge_groups(&mut self, a: GroupId, b: GroupId) -> (&EntityIdSet, &EntityIdSet) {
+        self.purge_group(a);
+        self.purge_group(b);
+        (self.groups.members(a), self.groups.members(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[derive(Component)]
+    struct Unit(u32);
+    #[derive(Component)]
+    struct Enemy;
+
+    fn sorted(iter: impl Iterator<Item = EntityId>) -> Vec<EntityId> {
+        let mut entities: Vec<EntityId> = iter.collect();
+        entities.sort_by_key(|entity| entity.id());
+        entities
+    }
+
+    #[test]
+    fn test_membership() {
+        let mut world = World::default();
+        let selected = world.create_group("selected");
+        assert_eq!(world.create_group("selected"), selected);
+        assert_eq!(world.group("selected"), Some(selected));
+        assert_eq!(world.group("aggroed"), None);
+
+        let units: Vec<EntityId> = (0..4).map(|i| world.spawn(Unit(i))).collect();
+        assert!(world.group_insert(selected, units[0]));
+        assert!(world.group_insert(selected, units[2]));
+        assert!(!world.group_insert(selected, units[2]));
+        assert!(world.group_contains(selected, units[2]));
+        assert!(!world.group_contains(selected, units[1]));
+        assert!(world.group_remove(selected, units[0]));
+        assert!(!world.group_remove(selected, units[0]));
+        assert_eq!(world.iter_group(selected).collect::<Vec<_>>(), [units[2]]);
+    }
+
+    #[test]
+    fn test_despawn_and_id_reuse() {
+        let mut world = World::default();
+        let group = world.create_group("trigger volume 7");
+        let old = world.spawn(Unit(0));
+        let other = world.spawn(Unit(1));
+        world.group_insert(group, old);
+        world.group_insert(group, other);
+        world.despawn(old);
+        // The despawned member is gone, even before the group was purged.
+        assert!(!world.group_contains(group, old));
+        assert!(!world.group_remove(group, old));
+
+        let new = world.spawn(Unit(2));
+        assert_eq!(new.id(), old.id());
+        assert!(!world.group_contains(group, new));
+        assert_eq!(world.group_len(group), 1);
+        assert_eq!(world.iter_group(group).collect::<Vec<_>>(), [other]);
+        // The reused id can join the group on its own.
+        assert!(world.group_insert(group, new));
+        assert_eq!(
+            sorted(world.iter_group(group)),
+            sorted([new, other].into_iter())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: group_insert failed: the entity isn't alive (entity=")]
+    fn test_insert_despawned() {
+        let mut world = World::default();
+        let group = world.create_group("selected");
+        let entity = world.spawn(Unit(0));
+        world.despawn(entity);
+        world.group_insert(group, entity);
+    }
+
+    #[test]
+    #[should_panic(expected = "the group wasn't created in this world")]
+    fn test_foreign_group() {
+        let mut other = World::default();
+        other.create_group("a");
+        let group = other.create_group("b");
+        World::default().iter_group(group).count();
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let mut world = World::default();
+        let units: Vec<EntityId> = (0..10).map(|i| world.spawn(Unit(i))).collect();
+        let (evens, thirds) = (world.create_group("evens"), world.create_group("thirds"));
+        for (i, unit) in units.iter().enumerate() {
+            if i % 2 == 0 {
+                world.group_insert(evens, *unit);
+            }
+            if i % 3 == 0 {
+                world.group_insert(thirds, *unit);
+            }
+        }
+        world.despawn(units[6]);
+        world.despawn(units[9]);
+        let expected = |f: fn(bool, bool) -> bool| -> Vec<EntityId> {
+            sorted(
+                (0..10)
+                    .filter(|i| *i != 6 && *i != 9 && f(i % 2 == 0, i % 3 == 0))
+                    .map(|i| units[i]),
+            )
+        };
+        assert_eq!(
+            sorted(world.iter_group_intersection(evens, thirds)),
+            expected(|a, b| a && b)
+        );
+        assert_eq!(
+            sorted(world.iter_group_difference(evens, thirds)),
+            expected(|a, b| a && !b)
+        );
+        assert_eq!(
+            sorted(world.iter_group_difference(thirds, evens)),
+            expected(|a, b| !a && b)
+        );
+        assert_eq!(
+            sorted(world.iter_group_union(evens, thirds)),
+            expected(|a, b| a || b)
+        );
+        assert_eq!(
+            world.iter_group_union(evens, evens).collect::<Vec<_>>(),
+            world.iter_group(evens).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_query_in_group() {
+        let mut world = World::default();
+        let aggroed = world.create_group("aggroed");
+        let mut expected = Vec::new();
+        // Members across several storages, and storages without members.
+        for i in 0..60 {
+            let unit = match i % 3 {
+                0 => world.spawn(Unit(i)),
+                1 => world.spawn((Unit(i), Enemy)),
+                _ => world.spawn(Enemy),
+            };
+            if i % 4 == 0 && i % 3 != 2 {
+                world.group_insert(aggroed, unit);
+                expected.push(i);
+            }
+        }
+        let despawned = world
+            .query::<(EntityId, &Unit)>()
+            .find(|(_, unit)| unit.0 == 12)
+            .unwrap()
+            .0;
+        world.despawn(despawned);
+        expected.retain(|i| *i != 12);
+        // The id is reused by an entity that isn't a member.
+        world.spawn(Unit(100));
+
+        let mut matched: Vec<u32> = world
+            .query::<&Unit>()
+            .in_group(aggroed)
+            .map(|unit| unit.0)
+            .collect();
+        matched.sort();
+        assert_eq!(matched, expected);
+        let iter = world.query::<&Unit>().in_group(aggroed);
+        assert_eq!(iter.total_matched_exact(), expected.len());
+        assert_eq!(iter.size_hint().0, 0);
+        let enemies = world
+            .query_filtered::<&Unit, Has<Enemy>>()
+            .in_group(aggroed)
+            .count();
+        assert_eq!(enemies, expected.iter().filter(|i| *i % 3 == 1).count());
+        for unit in world.query::<&mut Unit>().in_group(aggroed) {
+            unit.0 += 1000;
+        }
+        assert_eq!(
+            world.query::<&Unit>().filter(|unit| unit.0 >= 1000).count(),
+            expected.len()
+        );
+        let mut state = QueryState::<&Unit>::new(&world);
+        assert_eq!(
+            state.iter(&mut world).in_group(aggroed).count(),
+            expected.len()
+        );
+    }
+}
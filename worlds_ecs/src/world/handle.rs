@@ -0,0 +1,316 @@
+use super::{
+    storage::{arch_storage::ArchStorageIndex, storages::ArchStorageId},
+    World, WorldId,
+};
+use crate::{
+    component::deref_component_mut,
+    entity::EntityId,
+    prelude::{Component, ComponentId},
+    utils::panics,
+};
+use std::{fmt, marker::PhantomData};
+
+/// A resolved location of a component of an entity, for deferred writes: see [`World::handle`].
+///
+/// The handle doesn't borrow the [`World`]. Before it's used, it's validated against the entity's generation
+/// (so a handle of a despawned entity is rejected), and against the row generation of the entity's storage
+/// (so a handle whose row may have moved is resolved again).
+pub struct ComponentHandle<C: Component> {
+    world: WorldId,
+    entity: EntityId,
+    comp_id: ComponentId,
+    storage_id: ArchStorageId,
+    index: ArchStorageIndex,
+    column: usize,
+    row_generation: u64,
+    _component: PhantomData<fn() -> C>,
+}
+
+impl<C: Component> ComponentHandle<C> {
+    /// The entity whose component the handle points to.
+    pub fn entity(&self) -> EntityId {
+        self.entity
+    }
+}
+
+impl<C: Component> Clone for ComponentHandle<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: Component> Copy for ComponentHandle<C> {}
+
+impl<C: Component> fmt::Debug for ComponentHandle<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComponentHandle")
+            .field("entity", &self.entity)
+            .field("storage_id", &self.storage_id)
+            .field("row", &self.index.0)
+            .field("row_generation", &self.row_generation)
+            .finish()
+    }
+}
+
+impl World {
+    /// Resolve the location of the component `C` of an entity, to write to it later with [`World::apply_handle`]
+    /// (or [`World::apply_handles`]) without looking it up again.
+    /// Returns `None` if the entity was despawned, or if it doesn't have the component.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Armor(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// let handle = world.handle::<Health>(entity).unwrap();
+    /// assert_eq!(handle.entity(), entity);
+    /// assert!(world.handle::<Armor>(entity).is_none());
+    /// ```
+    pub fn handle<C: Component>(&self, entity: EntityId) -> Option<ComponentHandle<C>> {
+        let entity_meta = self.entities.get_entity_meta(entity)?;
+        let comp_id = self.components.get_component_id::<C>()?;
+        let storage = self
+            .storages
+            .arch_storages
+            .get_storage(entity_meta.archetype_storage_id)?;
+        Some(ComponentHandle {
+            world: self.id,
+            entity,
+            comp_id,
+            storage_id: entity_meta.archetype_storage_id,
+            index: entity_meta.archetype_storage_index,
+            column: storage.column_of(comp_id)?,
+            row_generation: storage.row_generation(),
+            _component: PhantomData,
+        })
+    }
+
+    /// Returns `true` if the handle still points to the row of its entity, meaning it can be applied without
+    /// resolving it again.
+    fn is_handle_fresh<C: Component>(&self, handle: &ComponentHandle<C>) -> bool {
+        self.entities.verify_generation(handle.entity)
+            && self
+                .storages
+                .arch_storages
+                .get_storage(handle.storage_id)
+                .is_some_and(|storage| storage.row_generation() == handle.row_generation)
+    }
+
+    /// Call `f` with the component that the handle points to, and return `true` if the write landed, or `false` if
+    /// the entity was despawned (or lost the component) since the handle was created.
+    ///
+    /// If no entity was removed from the entity's storage since the handle was created, the component is accessed
+    /// directly by its row and column. Otherwise, its row may have moved, so the handle is resolved again from
+    /// the [`EntityId`].
+    ///
+    /// Panics if the handle was created by another [`World`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// let handle = world.handle::<Health>(entity).unwrap();
+    /// assert!(world.apply_handle(handle, |health| health.0 -= 3));
+    /// assert_eq!(world.get_component::<Health>(entity).unwrap().0, 7);
+    /// world.despawn(entity);
+    /// assert!(!world.apply_handle(handle, |health| health.0 -= 3));
+    /// ```
+    #[track_caller]
+    pub fn apply_handle<C: Component>(
+        &mut self,
+        handle: ComponentHandle<C>,
+        f: impl FnOnce(&mut C),
+    ) -> bool {
+        if handle.world != self.id {
+            panics::fail_entity(
+                "apply_handle",
+                "the handle was created by another world",
+                handle.entity,
+            );
+        }
+        if self.is_handle_fresh(&handle) {
+            // SAFETY: The entity is alive and no row of its storage moved since the handle was created, so the row
+            // and the column are in bounds, and the column stores `C`.
+            let component = unsafe {
+                deref_component_mut::<C>(
+                    self.storages
+                        .arch_storages
+                        .get_storage_mut(handle.storage_id)
+                        .unwrap_unchecked()
+                        .get_component_mut_in_column_unchecked(handle.index, handle.column),
+                )
+            };
+            f(component);
+            return true;
+        }
+        let Some(entity_meta) = self.entities.get_entity_meta(handle.entity).copied() else {
+            return false;
+        };
+        match self
+            .storages
+            .arch_storages
+            .get_storage_mut(entity_meta.archetype_storage_id)
+            .and_then(|storage| {
+                storage.get_component_mut(entity_meta.archetype_storage_index, handle.comp_id)
+            }) {
+            Some(raw_comp) => {
+                // SAFETY: The pointer was fetched using the component id of `C`.
+                f(unsafe { deref_component_mut::<C>(raw_comp) });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Apply a batch of handles, each with a value: `f` is called with the component that each handle points to,
+    /// and the handle's value. The handles are applied in the order of their storages and rows, for locality.
+    /// Returns how many of the writes landed (see [`World::apply_handle`]).
+    ///
+    /// Panics if any of the handles was created by another [`World`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let (a, b) = (world.spawn(Health(10)), world.spawn(Health(10)));
+    /// let damage = [
+    ///     (world.handle::<Health>(a).unwrap(), 3),
+    ///     (world.handle::<Health>(b).unwrap(), 4),
+    /// ];
+    /// world.despawn(b);
+    /// assert_eq!(world.apply_handles(damage, |health, damage| health.0 -= damage), 1);
+    /// assert_eq!(world.get_component::<Health>(a).unwrap().0, 7);
+    /// ```
+    pub fn apply_handles<C: Component, V>(
+        &mut self,
+        handles: impl IntoIterator<Item = (ComponentHandle<C>, V)>,
+        mut f: impl FnMut(&mut C, V),
+    ) -> usize {
+        let mut handles: Vec<(ComponentHandle<C>, V)> = handles.into_iter().collect();
+        handles.sort_by_key(|(handle, _)| (handle.storage_id.0, handle.index.0));
+        let mut landed = 0;
+        for (handle, value) in handles {
+            if self.apply_handle(handle, |component| f(component, value)) {
+                landed += 1;
+            }
+        }
+        landed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Health(i32);
+
+    #[derive(Component)]
+    struct Armor;
+
+    #[test]
+    fn test_handle_fast_path() {
+        let mut world = World::default();
+        let entities: Vec<EntityId> = (0..4).map(|i| world.spawn(Health(i))).collect();
+        let handles: Vec<ComponentHandle<Health>> = entities
+            .iter()
+            .map(|entity| world.handle::<Health>(*entity).unwrap())
+            .collect();
+        assert!(world.handle::<Armor>(entities[0]).is_none());
+
+        // Writes and spawns don't move rows, so the handles stay fresh.
+        world.get_component_mut::<Health>(entities[1]).unwrap().0 = 10;
+        world.spawn(Health(100));
+        for handle in &handles {
+            assert!(world.is_handle_fresh(handle));
+        }
+        assert!(world.apply_handle(handles[1], |health| health.0 += 1));
+        assert!(world.is_handle_fresh(&handles[2]));
+        assert!(world.apply_handle(handles[2], |health| health.0 *= 10));
+        assert_eq!(
+            world.get_component::<Health>(entities[1]),
+            Some(&Health(11))
+        );
+        assert_eq!(
+            world.get_component::<Health>(entities[2]),
+            Some(&Health(20))
+        );
+    }
+
+    #[test]
+    fn test_handle_moved_row() {
+        let mut world = World::default();
+        let entities: Vec<EntityId> = (0..4).map(|i| world.spawn(Health(i))).collect();
+        let last = world.handle::<Health>(entities[3]).unwrap();
+        let dead = world.handle::<Health>(entities[0]).unwrap();
+
+        // Despawning the first entity moves the last entity into its row.
+        world.despawn(entities[0]);
+        assert!(!world.is_handle_fresh(&last));
+        assert!(world.apply_handle(last, |health| health.0 = 30));
+        assert_eq!(
+            world.get_component::<Health>(entities[3]),
+            Some(&Health(30))
+        );
+
+        // The entity that was despawned is rejected, even when its id is reused.
+        assert!(!world.apply_handle(dead, |health| health.0 = -1));
+        let revived = world.spawn(Health(5));
+        assert_eq!(revived.id(), entities[0].id());
+        assert!(!world.apply_handle(dead, |health| health.0 = -1));
+        assert_eq!(world.get_component::<Health>(revived), Some(&Health(5)));
+    }
+
+    #[test]
+    fn test_apply_handles() {
+        let mut world = World::default();
+        let plain: Vec<EntityId> = (0..3).map(|i| world.spawn(Health(i))).collect();
+        let armored: Vec<EntityId> = (0..3).map(|i| world.spawn((Health(i), Armor))).collect();
+        let updates: Vec<(ComponentHandle<Health>, i32)> = armored
+            .iter()
+            .chain(&plain)
+            .rev()
+            .map(|entity| (world.handle::<Health>(*entity).unwrap(), 100))
+            .collect();
+        world.despawn(plain[0]);
+        world.despawn(armored[2]);
+
+        assert_eq!(
+            world.apply_handles(updates, |health, bonus| health.0 += bonus),
+            4
+        );
+        for (i, entity) in plain.iter().chain(&armored).enumerate() {
+            if *entity != plain[0] && *entity != armored[2] {
+                assert_eq!(
+                    world.get_component::<Health>(*entity),
+                    Some(&Health(i as i32 % 3 + 100))
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "worlds_ecs: apply_handle failed: the handle was created by another world"
+    )]
+    fn test_handle_of_another_world() {
+        let mut world = World::default();
+        let mut other = World::default();
+        let entity = world.spawn(Health(0));
+        other.spawn(Health(0));
+        let handle = world.handle::<Health>(entity).unwrap();
+        other.apply_handle(handle, |health| health.0 = 1);
+    }
+}
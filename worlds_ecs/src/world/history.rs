@@ -0,0 +1,499 @@
+use super::{storage::storages::ArchStorageId, World};
+use crate::{component::Component, entity::EntityId, utils::panics};
+use std::{
+    any::{Any, TypeId},
+    collections::VecDeque,
+};
+
+/// The last values of a component of an entity, with the frame each one was recorded in, see
+/// [`World::record_history`]. Index `0` is the oldest value.
+pub struct HistoryRing<C> {
+    capacity: usize,
+    values: VecDeque<(u64, C)>,
+    dead: bool,
+    /// The storage of the entity, and its generation, when the last value was recorded.
+    last_seen: Option<(ArchStorageId, u64)>,
+}
+
+impl<C> HistoryRing<C> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            values: VecDeque::with_capacity(capacity),
+            dead: false,
+            last_seen: None,
+        }
+    }
+
+    /// The maximum amount of values in the ring. When it's full, recording a value evicts the oldest one.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The amount of values in the ring.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if no value was recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Get the value at `index` (`0` is the oldest value), with the frame it was recorded in.
+    pub fn get(&self, index: usize) -> Option<(u64, &C)> {
+        self.values.get(index).map(|(frame, value)| (*frame, value))
+    }
+
+    /// Get the most recent value, with the frame it was recorded in.
+    pub fn latest(&self) -> Option<(u64, &C)> {
+        self.values.back().map(|(frame, value)| (*frame, value))
+    }
+
+    /// Get the most recent value that was recorded in `frame` or before it.
+    pub fn at_frame(&self, frame: u64) -> Option<&C> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(recorded, _)| *recorded <= frame)
+            .map(|(_, value)| value)
+    }
+
+    /// Iterate over the values from the oldest to the most recent, with the frame each one was recorded in.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (u64, &C)> {
+        self.values.iter().map(|(frame, value)| (*frame, value))
+    }
+
+    /// Returns `true` if the entity was despawned. The ring keeps the values it recorded until the recording is
+    /// stopped (see [`World::stop_recording`]).
+    pub fn is_dead(&self) -> bool {
+        self.dead
+    }
+}
+
+/// A [`HistoryRing`] with its component type erased.
+trait ErasedRing: Send + Sync {
+    /// Record the value of the component of the entity, if its storage changed since the last recorded value.
+    fn sample(&mut self, world: &World, entity: EntityId, frame: u64);
+    fn mark_dead(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<C: Component + Clone> ErasedRing for HistoryRing<C> {
+    fn sample(&mut self, world: &World, entity: EntityId, frame: u64) {
+        let Some(entity_meta) = world.entities.get_entity_meta(entity) else {
+            self.dead = true;
+            return;
+        };
+        let sid = entity_meta.archetype_storage_id;
+        let Some(storage) = world.storages.arch_storages.get_storage(sid) else {
+            return;
+        };
+        let seen = Some((sid, storage.generation()));
+        if self.last_seen == seen {
+            return;
+        }
+        let Some(value) = world.get_component::<C>(entity) else {
+            return;
+        };
+        if self.values.len() == self.capacity {
+            self.values.pop_front();
+        }
+        self.values.push_back((frame, value.clone()));
+        self.last_seen = seen;
+    }
+
+    fn mark_dead(&mut self) {
+        self.dead = true;
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+struct Recording {
+    entity: EntityId,
+    component: TypeId,
+    /// The memory that the ring reserved for its values.
+    bytes: usize,
+    ring: Box<dyn ErasedRing>,
+}
+
+/// The component histories that are recorded (see [`World::record_history`]), from the oldest recording to the
+/// most recent one. Recording is meant for a handful of entities, so the recordings are looked up linearly.
+pub(crate) struct Histories {
+    frame: u64,
+    budget: usize,
+    bytes: usize,
+    recordings: Vec<Recording>,
+}
+
+impl Default for Histories {
+    fn default() -> Self {
+        Self {
+            frame: 0,
+            budget: usize::MAX,
+            bytes: 0,
+            recordings: Vec::new(),
+        }
+    }
+}
+
+impl Histories {
+    fn position(&self, entity: EntityId, component: TypeId) -> Option<usize> {
+        self.recordings
+            .iter()
+            .position(|recording| recording.entity == entity && recording.component == component)
+    }
+
+    fn remove(&mut self, index: usize) -> Recording {
+        let recording = self.recordings.remove(index);
+        self.bytes -= recording.bytes;
+        recording
+    }
+
+    /// Evict the oldest recordings until `bytes` more fit in the budget.
+    fn make_room(&mut self, bytes: usize) {
+        while !self.recordings.is_empty() && self.bytes + bytes > self.budget {
+            self.remove(0);
+        }
+    }
+
+    /// Flag the recordings of a despawned entity as dead.
+    pub(crate) fn despawned(&mut self, entity: EntityId) {
+        self.recordings
+            .iter_mut()
+            .filter(|recording| recording.entity == entity)
+            .for_each(|recording| recording.ring.mark_dead());
+    }
+}
+
+impl World {
+    /// Start recording the last `capacity` values of the [`Component`] `C` of an entity, for scrubbing through them
+    /// while debugging (see [`World::history`]). A value is recorded by [`World::end_history_frame`] when the
+    /// storage of the entity changed since the last recorded value (see
+    /// [`ArchStorage::generation`](super::storage::arch_storage::ArchStorage::generation)), so a value may be
+    /// recorded again when only other entities of its storage changed.
+    ///
+    /// The recording is kept outside of the storages, so it doesn't affect the archetype of the entity. Recording
+    /// the same component of the same entity again restarts its recording. When the memory of the recordings
+    /// exceeds the budget (see [`World::set_history_budget`]), the oldest recordings are evicted.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(0));
+    /// world.record_history::<Position>(entity, 2);
+    /// for x in 1..=3 {
+    ///     world.get_component_mut::<Position>(entity).unwrap().0 = x;
+    ///     world.end_history_frame();
+    /// }
+    /// // Only the last 2 values are kept.
+    /// let history = world.history::<Position>(entity).unwrap();
+    /// let values: Vec<(u64, i32)> = history.iter().map(|(frame, position)| (frame, position.0)).collect();
+    /// assert_eq!(values, vec![(1, 2), (2, 3)]);
+    /// ```
+    ///
+    /// # Panics
+    /// If the entity isn't alive, if `capacity` is `0`, or if the recording alone exceeds the budget.
+    #[track_caller]
+    pub fn record_history<C: Component + Clone>(&mut self, entity: EntityId, capacity: usize) {
+        if self.entities.get_entity_meta(entity).is_none() {
+            panics::fail_entity("record_history", "the entity isn't alive", entity);
+        }
+        if capacity == 0 {
+            panics::fail_entity("record_history", "the capacity is 0", entity);
+        }
+        let bytes = capacity.saturating_mul(std::mem::size_of::<(u64, C)>());
+        let histories = &mut self.histories;
+        if bytes > histories.budget {
+            panics::fail_entity(
+                "record_history",
+                "the recording exceeds the history budget",
+                entity,
+            );
+        }
+        if let Some(index) = histories.position(entity, TypeId::of::<C>()) {
+            histories.remove(index);
+        }
+        histories.make_room(bytes);
+        histories.bytes += bytes;
+        histories.recordings.push(Recording {
+            entity,
+            component: TypeId::of::<C>(),
+            bytes,
+            ring: Box::new(HistoryRing::<C>::new(capacity)),
+        });
+    }
+
+    /// Get the recorded values of the [`Component`] `C` of an entity (see [`World::record_history`]). The recording
+    /// of a despawned entity is kept until it's stopped, flagged as dead (see [`HistoryRing::is_dead`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(0));
+    /// assert!(world.history::<Position>(entity).is_none());
+    /// world.record_history::<Position>(entity, 8);
+    /// world.end_history_frame();
+    /// world.despawn(entity);
+    /// world.end_history_frame();
+    /// let history = world.history::<Position>(entity).unwrap();
+    /// assert!(history.is_dead());
+    /// assert_eq!(history.latest().map(|(frame, position)| (frame, position.0)), Some((0, 0)));
+    /// ```
+    pub fn history<C: Component>(&self, entity: EntityId) -> Option<&HistoryRing<C>> {
+        let histories = &self.histories;
+        histories
+            .position(entity, TypeId::of::<C>())
+            .and_then(|index| histories.recordings[index].ring.as_any().downcast_ref())
+    }
+
+    /// Stop recording the [`Component`] `C` of an entity, and return its recorded values.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(0));
+    /// world.record_history::<Position>(entity, 8);
+    /// world.end_history_frame();
+    /// let history = world.stop_recording::<Position>(entity).unwrap();
+    /// assert_eq!(history.len(), 1);
+    /// assert!(world.history::<Position>(entity).is_none());
+    /// ```
+    pub fn stop_recording<C: Component>(&mut self, entity: EntityId) -> Option<HistoryRing<C>> {
+        let histories = &mut self.histories;
+        let index = histories.position(entity, TypeId::of::<C>())?;
+        histories
+            .remove(index)
+            .ring
+            .into_any()
+            .downcast()
+            .ok()
+            .map(|ring| *ring)
+    }
+
+    /// Set how much memory (in bytes) the component histories may reserve (see [`World::record_history`]), and
+    /// evict the oldest recordings until they fit. There is no budget by default.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let (a, b) = (world.spawn(Position(0)), world.spawn(Position(0)));
+    /// world.record_history::<Position>(a, 8);
+    /// world.record_history::<Position>(b, 8);
+    /// // Only one of the recordings fits, so the oldest one is evicted.
+    /// world.set_history_budget(world.history_bytes() / 2);
+    /// assert!(world.history::<Position>(a).is_none());
+    /// assert!(world.history::<Position>(b).is_some());
+    /// ```
+    pub fn set_history_budget(&mut self, bytes: usize) {
+        self.histories.budget = bytes;
+        self.histories.make_room(0);
+    }
+
+    /// The memory (in bytes) that the component histories reserved.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(0));
+    /// assert_eq!(world.history_bytes(), 0);
+    /// world.record_history::<Position>(entity, 8);
+    /// assert!(world.history_bytes() >= 8 * std::mem::size_of::<Position>());
+    /// world.stop_recording::<Position>(entity);
+    /// assert_eq!(world.history_bytes(), 0);
+    /// ```
+    pub fn history_bytes(&self) -> usize {
+        self.histories.bytes
+    }
+
+    /// The current history frame: how many times [`World::end_history_frame`] was called.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// assert_eq!(world.history_frame(), 0);
+    /// world.end_history_frame();
+    /// assert_eq!(world.history_frame(), 1);
+    /// ```
+    pub fn history_frame(&self) -> u64 {
+        self.histories.frame
+    }
+
+    /// Record the values of the recorded components that changed during the frame (see
+    /// [`World::record_history`]), stamped with the current frame, and start the next one. This should be called
+    /// at the end of each frame.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(0));
+    /// world.record_history::<Position>(entity, 8);
+    /// world.end_history_frame();
+    /// // Nothing changed in this frame, so nothing is recorded.
+    /// world.end_history_frame();
+    /// assert_eq!(world.history::<Position>(entity).unwrap().len(), 1);
+    /// ```
+    pub fn end_history_frame(&mut self) {
+        let mut histories = std::mem::take(&mut self.histories);
+        for recording in &mut histories.recordings {
+            recording
+                .ring
+                .sample(self, recording.entity, histories.frame);
+        }
+        histories.frame += 1;
+        self.histories = histories;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[derive(Component, Clone, Debug, PartialEq)]
+    struct Position(i64);
+
+    #[derive(Component, Clone)]
+    struct Velocity(#[allow(dead_code)] i64);
+
+    #[test]
+    fn test_ring_keeps_the_last_values() {
+        let mut world = World::default();
+        let entity = world.spawn((Position(0), Velocity(1)));
+        world.record_history::<Position>(entity, 16);
+        for frame in 0..100 {
+            world.get_component_mut::<Position>(entity).unwrap().0 = frame * 10;
+            world.end_history_frame();
+        }
+        let ring = world.history::<Position>(entity).unwrap();
+        assert_eq!(ring.len(), 16);
+        assert_eq!(ring.capacity(), 16);
+        assert_eq!(
+            ring.iter()
+                .map(|(frame, value)| (frame, value.0))
+                .collect::<Vec<_>>(),
+            (84..100)
+                .map(|frame| (frame, frame as i64 * 10))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(ring.get(0), Some((84, &Position(840))));
+        assert_eq!(ring.latest(), Some((99, &Position(990))));
+        assert_eq!(ring.at_frame(90), Some(&Position(900)));
+        assert_eq!(ring.at_frame(10), None);
+        // Recording doesn't touch the archetype of the entity.
+        assert!(world.contains_component::<Velocity>(entity));
+        assert!(world.history::<Velocity>(entity).is_none());
+    }
+
+    #[test]
+    fn test_unchanged_values_are_not_recorded() {
+        let mut world = World::default();
+        let entity = world.spawn(Position(0));
+        world.record_history::<Position>(entity, 8);
+        for frame in 0..10 {
+            if frame % 3 == 0 {
+                world.get_component_mut::<Position>(entity).unwrap().0 = frame;
+            }
+            world.end_history_frame();
+        }
+        assert_eq!(
+            world
+                .history::<Position>(entity)
+                .unwrap()
+                .iter()
+                .map(|(frame, _)| frame)
+                .collect::<Vec<_>>(),
+            [0, 3, 6, 9]
+        );
+    }
+
+    #[test]
+    fn test_despawned_entity() {
+        let mut world = World::default();
+        let entity = world.spawn(Position(1));
+        world.record_history::<Position>(entity, 4);
+        world.end_history_frame();
+        world.despawn(entity);
+        let ring = world.history::<Position>(entity).unwrap();
+        assert!(ring.is_dead());
+        assert_eq!(ring.latest(), Some((0, &Position(1))));
+
+        // The recording is kept through the next frames, until it's stopped.
+        world.spawn(Position(2));
+        world.end_history_frame();
+        assert_eq!(world.history::<Position>(entity).unwrap().len(), 1);
+        let ring = world.stop_recording::<Position>(entity).unwrap();
+        assert!(ring.is_dead());
+        assert!(world.history::<Position>(entity).is_none());
+        assert_eq!(world.history_bytes(), 0);
+    }
+
+    #[test]
+    fn test_budget_evicts_the_oldest_recordings() {
+        let mut world = World::default();
+        let entities: Vec<EntityId> = (0..4).map(|i| world.spawn(Position(i))).collect();
+        let bytes = 4 * std::mem::size_of::<(u64, Position)>();
+        world.set_history_budget(3 * bytes);
+        for entity in &entities[..3] {
+            world.record_history::<Position>(*entity, 4);
+        }
+        assert_eq!(world.history_bytes(), 3 * bytes);
+
+        world.record_history::<Position>(entities[3], 4);
+        assert!(world.history::<Position>(entities[0]).is_none());
+        assert!(entities[1..]
+            .iter()
+            .all(|entity| world.history::<Position>(*entity).is_some()));
+
+        // Restarting a recording makes it the most recent one.
+        world.record_history::<Position>(entities[1], 4);
+        world.set_history_budget(2 * bytes);
+        assert!(world.history::<Position>(entities[2]).is_none());
+        assert!(world.history::<Position>(entities[3]).is_some());
+        assert!(world.history::<Position>(entities[1]).is_some());
+        assert_eq!(world.history_bytes(), 2 * bytes);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "worlds_ecs: record_history failed: the recording exceeds the history budget"
+    )]
+    fn test_recording_over_budget() {
+        let mut world = World::default();
+        let entity = world.spawn(Position(0));
+        world.set_history_budget(8);
+        world.record_history::<Position>(entity, 16);
+    }
+}
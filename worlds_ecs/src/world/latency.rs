@@ -0,0 +1,408 @@
+#[cfg(feature = "diagnostics")]
+use super::World;
+#[cfg(feature = "diagnostics")]
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// How many buckets each latency histogram has (see [`World::record_latency`]).
+#[cfg(feature = "diagnostics")]
+pub const LATENCY_BUCKETS: usize = 32;
+
+/// The operations of the world whose latency can spike, and is recorded in a histogram of its own
+/// (see `World::record_latency`, with the `diagnostics` feature).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LatencyCategory {
+    /// A column of a storage reallocated its memory to make room for more components, or moved out of the inline
+    /// memory of a tiny storage.
+    StorageGrowth,
+    /// The storage of a new archetype was created.
+    ArchetypeCreation,
+    /// The matching entities of a storage were despawned at once (by `World::despawn_matching`, or `World::clear`).
+    BulkDespawn,
+    /// A snapshot of the tags of every entity was captured (by `World::snapshot_tags`).
+    SnapshotCapture,
+    /// A chunk of a teardown was dropped (by `WorldTeardown::run`).
+    TeardownChunk,
+}
+
+impl LatencyCategory {
+    /// Every category, in the order of the reports.
+    pub const ALL: [LatencyCategory; 5] = [
+        LatencyCategory::StorageGrowth,
+        LatencyCategory::ArchetypeCreation,
+        LatencyCategory::BulkDespawn,
+        LatencyCategory::SnapshotCapture,
+        LatencyCategory::TeardownChunk,
+    ];
+
+    /// A short name of the category, for reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            LatencyCategory::StorageGrowth => "storage growth",
+            LatencyCategory::ArchetypeCreation => "archetype creation",
+            LatencyCategory::BulkDespawn => "bulk despawn",
+            LatencyCategory::SnapshotCapture => "snapshot capture",
+            LatencyCategory::TeardownChunk => "teardown chunk",
+        }
+    }
+}
+
+/// The upper bounds (exclusive, in nanoseconds) of every bucket but the last: log-scale from 1µs to 100ms. The first
+/// bucket holds everything below 1µs, and the last one everything from 100ms.
+#[cfg(feature = "diagnostics")]
+fn bucket_bounds() -> &'static [u64; LATENCY_BUCKETS - 1] {
+    static BOUNDS: OnceLock<[u64; LATENCY_BUCKETS - 1]> = OnceLock::new();
+    BOUNDS.get_or_init(|| {
+        let steps = (LATENCY_BUCKETS - 2) as f64;
+        std::array::from_fn(|i| (1_000.0 * 100_000f64.powf(i as f64 / steps)).round() as u64)
+    })
+}
+
+/// The bucket of a latency of `nanos` nanoseconds.
+#[cfg(feature = "diagnostics")]
+fn bucket_of(nanos: u64) -> usize {
+    bucket_bounds().partition_point(|bound| *bound <= nanos)
+}
+
+/// A fixed-bucket histogram of the latencies of one [`LatencyCategory`].
+#[cfg(feature = "diagnostics")]
+#[derive(Default)]
+struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS],
+    /// The longest latency, in nanoseconds.
+    max: AtomicU64,
+}
+
+#[cfg(feature = "diagnostics")]
+impl Histogram {
+    fn record(&self, latency: Duration) {
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.buckets[bucket_of(nanos)].fetch_add(1, Ordering::Relaxed);
+        self.max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        self.buckets
+            .iter()
+            .chain([&self.max])
+            .for_each(|counter| counter.store(0, Ordering::Relaxed));
+    }
+
+    fn stats(&self, category: LatencyCategory) -> LatencyStats {
+        let buckets: [u64; LATENCY_BUCKETS] =
+            std::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed));
+        let count = buckets.iter().sum();
+        let max = Duration::from_nanos(self.max.load(Ordering::Relaxed));
+        // The percentile is the upper bound of the bucket it falls in, but never more than the longest latency.
+        let percentile = |quantile: f64| {
+            if count == 0 {
+                return Duration::ZERO;
+            }
+            let rank = ((count as f64 * quantile).ceil() as u64).max(1);
+            let mut seen = 0;
+            let bucket = buckets
+                .iter()
+                .position(|in_bucket| {
+                    seen += in_bucket;
+                    seen >= rank
+                })
+                .unwrap();
+            bucket_bounds()
+                .get(bucket)
+                .map_or(max, |bound| Duration::from_nanos(*bound).min(max))
+        };
+        LatencyStats {
+            category,
+            count,
+            p50: percentile(0.5),
+            p99: percentile(0.99),
+            max,
+        }
+    }
+}
+
+/// The latency histograms of a [`World`], shared with the handles of its storages, so the time is recorded where
+/// it's spent. Copies of the world (see [`World::clone_cow`]) record in the same histograms.
+#[cfg(feature = "diagnostics")]
+#[derive(Default)]
+struct Histograms {
+    enabled: AtomicBool,
+    categories: [Histogram; LatencyCategory::ALL.len()],
+}
+
+/// Records the latency of the operations of each [`LatencyCategory`] while recording is enabled (see
+/// `World::record_latency`). Without the `diagnostics` feature it records nothing, and costs nothing.
+#[derive(Clone, Default)]
+pub(crate) struct LatencyRecorder {
+    #[cfg(feature = "diagnostics")]
+    histograms: Arc<Histograms>,
+}
+
+/// When an operation started, if its latency is recorded (see [`LatencyRecorder::start`]).
+pub(crate) struct LatencyStart {
+    #[cfg(feature = "diagnostics")]
+    start: Option<Instant>,
+}
+
+impl LatencyRecorder {
+    /// Start timing an operation, if recording is enabled. Finish it with [`Self::finish`].
+    #[inline]
+    pub(crate) fn start(&self) -> LatencyStart {
+        LatencyStart {
+            #[cfg(feature = "diagnostics")]
+            start: self
+                .histograms
+                .enabled
+                .load(Ordering::Relaxed)
+                .then(Instant::now),
+        }
+    }
+
+    /// Record the latency of an operation that started at `start`, if it was timed.
+    #[inline]
+    pub(crate) fn finish(&self, category: LatencyCategory, start: LatencyStart) {
+        #[cfg(feature = "diagnostics")]
+        if let Some(start) = start.start {
+            self.histograms.categories[category as usize].record(start.elapsed());
+        }
+        #[cfg(not(feature = "diagnostics"))]
+        let _ = (category, start);
+    }
+
+    /// Call `f`, and record its latency if recording is enabled.
+    #[inline]
+    pub(crate) fn time<R>(&self, category: LatencyCategory, f: impl FnOnce() -> R) -> R {
+        let start = self.start();
+        let result = f();
+        self.finish(category, start);
+        result
+    }
+}
+
+/// The latency of the operations of one [`LatencyCategory`], in [`LatencyReport`].
+#[cfg(feature = "diagnostics")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// The operations.
+    pub category: LatencyCategory,
+    /// How many operations were recorded.
+    pub count: u64,
+    /// The median latency, rounded up to the bucket it falls in.
+    pub p50: Duration,
+    /// The 99th percentile of the latency, rounded up to the bucket it falls in.
+    pub p99: Duration,
+    /// The longest latency.
+    pub max: Duration,
+}
+
+/// The latency of the operations of a [`World`], for every [`LatencyCategory`] (see [`World::latency_report`]).
+/// Its [`Display`](fmt::Display) is a table, with a row for each category.
+#[cfg(feature = "diagnostics")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyReport {
+    /// The latency of each category, in the order of [`LatencyCategory::ALL`].
+    pub categories: Vec<LatencyStats>,
+}
+
+#[cfg(feature = "diagnostics")]
+impl LatencyReport {
+    /// The latency of the operations of `category`.
+    pub fn get(&self, category: LatencyCategory) -> &LatencyStats {
+        &self.categories[category as usize]
+    }
+}
+
+#[cfg(feature = "diagnostics")]
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let micros = |duration: Duration| duration.as_secs_f64() * 1e6;
+        writeln!(
+            f,
+            "{:<20} {:>8} {:>12} {:>12} {:>12}",
+            "operation", "count", "p50 (µs)", "p99 (µs)", "max (µs)"
+        )?;
+        for stats in &self.categories {
+            writeln!(
+                f,
+                "{:<20} {:>8} {:>12.1} {:>12.1} {:>12.1}",
+                stats.category.name(),
+                stats.count,
+                micros(stats.p50),
+                micros(stats.p99),
+                micros(stats.max)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "diagnostics")]
+impl World {
+    /// Start (or stop) recording the latency of the operations of the world that can spike (see
+    /// [`LatencyCategory`]), in a histogram for each category, to find the hitches that averages hide. The time is
+    /// recorded inside the operations, where it's spent (for example, around the reallocation when a storage
+    /// grows), so it's attributed to the cause rather than to the method that was called.
+    ///
+    /// While recording is disabled, each operation only checks that it is. While it's enabled, each operation
+    /// reads the clock twice. Stopping keeps what was recorded, see [`World::reset_latency`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.record_latency(true);
+    /// world.spawn(Mesh(1));
+    /// let report = world.latency_report();
+    /// assert_eq!(report.get(LatencyCategory::ArchetypeCreation).count, 1);
+    /// ```
+    pub fn record_latency(&mut self, enabled: bool) {
+        self.components
+            .storage_alloc()
+            .latency()
+            .histograms
+            .enabled
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    /// The count, median, 99th percentile and longest latency of the operations of each [`LatencyCategory`], since
+    /// the latency started being recorded (see [`World::record_latency`]), or was last reset.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.record_latency(true);
+    /// world.spawn(Mesh(1));
+    /// world.spawn(Mesh(2));
+    /// let report = world.latency_report();
+    /// assert_eq!(report.categories.len(), LatencyCategory::ALL.len());
+    /// let creation = report.get(LatencyCategory::ArchetypeCreation);
+    /// assert_eq!(creation.count, 1);
+    /// assert!(creation.p50 <= creation.max);
+    /// ```
+    pub fn latency_report(&self) -> LatencyReport {
+        let histograms = &self.components.storage_alloc().latency().histograms;
+        LatencyReport {
+            categories: LatencyCategory::ALL
+                .iter()
+                .map(|category| histograms.categories[*category as usize].stats(*category))
+                .collect(),
+        }
+    }
+
+    /// Clear the latency histograms (see [`World::record_latency`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.record_latency(true);
+    /// world.spawn(Mesh(1));
+    /// world.reset_latency();
+    /// assert_eq!(world.latency_report().get(LatencyCategory::ArchetypeCreation).count, 0);
+    /// ```
+    pub fn reset_latency(&mut self) {
+        let histograms = &self.components.storage_alloc().latency().histograms;
+        histograms.categories.iter().for_each(Histogram::reset);
+    }
+}
+
+#[cfg(all(test, feature = "diagnostics"))]
+mod tests {
+    use super::{bucket_bounds, bucket_of, LatencyCategory, LATENCY_BUCKETS};
+    use crate::prelude::*;
+    use std::time::Duration;
+
+    #[derive(Component)]
+    #[allow(dead_code)]
+    struct Name(String);
+    #[derive(Component)]
+    #[allow(dead_code)]
+    struct Level(u32);
+    #[derive(Tag)]
+    struct Selected;
+
+    #[test]
+    fn test_bucket_math() {
+        let bounds = bucket_bounds();
+        assert_eq!(bounds[0], 1_000);
+        assert_eq!(bounds[LATENCY_BUCKETS - 2], 100_000_000);
+        assert!(bounds.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(bucket_of(0), 0);
+        assert_eq!(bucket_of(999), 0);
+        assert_eq!(bucket_of(1_000), 1);
+        for (i, bound) in bounds.iter().enumerate() {
+            assert_eq!(bucket_of(bound - 1), i);
+            assert_eq!(bucket_of(*bound), i + 1);
+        }
+        assert_eq!(bucket_of(u64::MAX), LATENCY_BUCKETS - 1);
+    }
+
+    #[test]
+    fn test_each_category_is_recorded() {
+        let mut tags = TagFactory::default();
+        tags.register_tag::<Selected>();
+        let mut world = World::with_tags(tags);
+        world.spawn(Name(String::new()));
+        let report = world.latency_report();
+        assert!(report.categories.iter().all(|stats| stats.count == 0));
+
+        world.record_latency(true);
+        for i in 0..5000 {
+            world.spawn((Name(i.to_string()), Level(i)));
+        }
+        world.snapshot_tags();
+        world.despawn_matching::<Has<Level>>();
+        let report = world.latency_report();
+        for category in [
+            LatencyCategory::StorageGrowth,
+            LatencyCategory::ArchetypeCreation,
+            LatencyCategory::BulkDespawn,
+            LatencyCategory::SnapshotCapture,
+        ] {
+            let stats = report.get(category);
+            assert!(stats.count > 0, "{category:?} wasn't recorded");
+            assert!(stats.p50 <= stats.p99 && stats.p99 <= stats.max);
+        }
+        assert_eq!(report.get(LatencyCategory::ArchetypeCreation).count, 1);
+        assert_eq!(report.get(LatencyCategory::TeardownChunk).count, 0);
+        assert!(report.to_string().contains("archetype creation"));
+
+        world.reset_latency();
+        world.record_latency(false);
+        world.spawn(Level(0));
+        assert!(world
+            .latency_report()
+            .categories
+            .iter()
+            .all(|stats| stats.count == 0 && stats.max == Duration::ZERO));
+
+        world.record_latency(true);
+        for i in 0..5000 {
+            world.spawn(Name(i.to_string()));
+        }
+        let recorder = world.components.storage_alloc().latency().clone();
+        world.into_teardown().run_to_completion();
+        assert!(
+            recorder.histograms.categories[LatencyCategory::TeardownChunk as usize]
+                .stats(LatencyCategory::TeardownChunk)
+                .count
+                > 0
+        );
+    }
+}
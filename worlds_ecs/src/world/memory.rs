@@ -0,0 +1,387 @@
+use super::World;
+use crate::{
+    archetype::{key::PrimeArchKey, Archetype},
+    storage::alloc::{AllocEvent, AllocReason, AllocTracker},
+    utils::panics,
+};
+use std::sync::Arc;
+
+impl World {
+    /// Start recording every allocation, reallocation and deallocation of the memory of the component storages, for
+    /// memory regression tests: the latest `max_events` of them can be taken with [`World::take_allocation_events`],
+    /// and the bytes that the storages allocated are counted as they go (see [`World::bytes_for_archetype`] and
+    /// [`World::tracked_bytes`]), so reading them doesn't scan the storages.
+    ///
+    /// Copies of the world (see [`World::clone_cow`]) record their allocations in the same log.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(u64);
+    ///
+    /// let mut world = World::default();
+    /// world.track_allocations(64);
+    /// for i in 0..100 {
+    ///     world.spawn(Position(i));
+    /// }
+    /// assert!(world.tracked_bytes().unwrap() >= 100 * std::mem::size_of::<Position>());
+    /// ```
+    ///
+    /// # Panics
+    /// If a storage was already created (for example, if an entity was already spawned), since its memory wasn't
+    /// tracked, or if the allocations are already tracked.
+    #[track_caller]
+    pub fn track_allocations(&mut self, max_events: usize) {
+        if self.components.storage_alloc().tracker().is_some() {
+            panics::fail("track_allocations", "allocations are already tracked", &[]);
+        }
+        let storages = self.storages.arch_storages.storage_count();
+        if storages > 0 {
+            panics::fail(
+                "track_allocations",
+                "storages were created before the allocations were tracked",
+                &[("storages", &storages)],
+            );
+        }
+        self.components
+            .track_allocations(Arc::new(AllocTracker::new(max_events)));
+    }
+
+    /// Take the allocation events that were recorded since the last call, oldest first (see
+    /// [`World::track_allocations`]). Only the latest events are kept, up to the amount that was given when the
+    /// tracking was enabled. Empty if the allocations aren't tracked.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(u64);
+    ///
+    /// let mut world = World::default();
+    /// world.track_allocations(64);
+    /// for i in 0..100 {
+    ///     world.spawn(Position(i));
+    /// }
+    /// let events = world.take_allocation_events();
+    /// assert!(events.iter().any(|event| event.new_bytes > event.old_bytes));
+    /// assert!(world.take_allocation_events().is_empty());
+    /// ```
+    pub fn take_allocation_events(&mut self) -> Vec<AllocEvent> {
+        self.components
+            .storage_alloc()
+            .tracker()
+            .map(|tracker| tracker.take_events())
+            .unwrap_or_default()
+    }
+
+    /// The bytes that the storage of the exact archetype `A` allocated, or `None` if the allocations aren't tracked
+    /// (see [`World::track_allocations`]). This is a lookup of a counter, it doesn't scan the storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(u64);
+    /// #[derive(Component)]
+    /// struct Velocity(u64);
+    ///
+    /// let mut world = World::default();
+    /// assert_eq!(world.bytes_for_archetype::<Position>(), None);
+    /// world.track_allocations(64);
+    /// for i in 0..100 {
+    ///     world.spawn(Position(i));
+    /// }
+    /// assert!(world.bytes_for_archetype::<Position>().unwrap() > 0);
+    /// assert_eq!(world.bytes_for_archetype::<(Position, Velocity)>(), Some(0));
+    /// ```
+    pub fn bytes_for_archetype<A: Archetype>(&self) -> Option<usize> {
+        let tracker = self.components.storage_alloc().tracker()?;
+        Some(
+            A::prime_key(&self.components)
+                .map(|prime_key| tracker.bytes_for(prime_key))
+                .unwrap_or(0),
+        )
+    }
+
+    /// The bytes that every storage of the world allocated, or `None` if the allocations aren't tracked (see
+    /// [`World::track_allocations`] and [`assert_memory_within!`](crate::assert_memory_within)).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(u64);
+    ///
+    /// let mut world = World::default();
+    /// assert_eq!(world.tracked_bytes(), None);
+    /// world.track_allocations(64);
+    /// assert_eq!(world.tracked_bytes(), Some(0));
+    /// world.spawn(Position(0));
+    /// assert_eq!(world.tracked_bytes(), world.bytes_for_archetype::<Position>());
+    /// ```
+    pub fn tracked_bytes(&self) -> Option<usize> {
+        self.components
+            .storage_alloc()
+            .tracker()
+            .map(|tracker| tracker.total_bytes())
+    }
+
+    /// Release the memory that the storages reserved for more entities than they store. Pinned storages (see
+    /// [`World::pin_storage`]) are skipped, since their components can't move.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(u64);
+    ///
+    /// let mut world = World::default();
+    /// world.track_allocations(64);
+    /// let entities: Vec<EntityId> = (0..100).map(|i| world.spawn(Position(i))).collect();
+    /// for entity in &entities[10..] {
+    ///     world.despawn(*entity);
+    /// }
+    /// let before = world.tracked_bytes().unwrap();
+    /// world.shrink_to_fit();
+    /// assert!(world.tracked_bytes().unwrap() < before);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        let _scope = self.components.storage_alloc().scope(AllocReason::Shrink);
+        self.storages
+            .arch_storages
+            .iter_storages_with_matching_archetype_mut(PrimeArchKey::IDENTITY)
+            .filter(|storage| !storage.is_pinned())
+            .for_each(|storage| storage.shrink_to_fit());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        archetype::Archetype,
+        assert_memory_within,
+        prelude::*,
+        storage::{
+            alloc::{AllocEvent, AllocReason},
+            columns::INLINE_ROWS,
+        },
+        test_utils::BumpAlloc,
+    };
+    use std::{sync::Arc, time::Duration};
+
+    #[derive(Component)]
+    struct Position(#[allow(unused)] [f32; 3]);
+    #[derive(Component)]
+    struct Health(#[allow(unused)] u16);
+    #[derive(Component)]
+    struct Name(#[allow(unused)] String);
+
+    const POSITION: usize = std::mem::size_of::<Position>();
+    const HEALTH: usize = std::mem::size_of::<Health>();
+
+    fn spawn(world: &mut World, amount: usize) -> Vec<EntityId> {
+        (0..amount)
+            .map(|i| world.spawn((Position([i as f32; 3]), Health(i as u16))))
+            .collect()
+    }
+
+    #[test]
+    fn test_growth_and_shrink() {
+        let mut world = World::default();
+        world.track_allocations(1000);
+        let entities = spawn(&mut world, 1);
+        let key = <(Position, Health)>::prime_key(&world.components).unwrap();
+        let (position, health) = (
+            world.components.get_component_id::<Position>().unwrap(),
+            world.components.get_component_id::<Health>().unwrap(),
+        );
+        let event = |component, old_bytes, new_bytes, reason| AllocEvent {
+            archetype: key,
+            component,
+            old_bytes,
+            new_bytes,
+            reason,
+        };
+        // The columns of a tiny storage share a single block, the `Health` column starts after the `Position` one.
+        let inline = POSITION * INLINE_ROWS + HEALTH * INLINE_ROWS;
+        assert_eq!(
+            world.take_allocation_events(),
+            [event(None, 0, inline, AllocReason::Growth)]
+        );
+        assert_eq!(
+            world.bytes_for_archetype::<(Position, Health)>(),
+            Some(inline)
+        );
+
+        // Outgrowing the inline block moves every column to its own block, with room for twice the rows.
+        let mut entities = [entities, spawn(&mut world, INLINE_ROWS)].concat();
+        let capacity = INLINE_ROWS * 2;
+        assert_eq!(
+            world.take_allocation_events(),
+            [
+                event(Some(position), 0, POSITION * capacity, AllocReason::Growth),
+                event(Some(health), 0, HEALTH * capacity, AllocReason::Growth),
+                event(None, inline, 0, AllocReason::Growth),
+            ]
+        );
+        // Then each column doubles.
+        entities.extend(spawn(&mut world, capacity - entities.len() + 1));
+        assert_eq!(
+            world.take_allocation_events(),
+            [
+                event(
+                    Some(position),
+                    POSITION * capacity,
+                    POSITION * capacity * 2,
+                    AllocReason::Growth
+                ),
+                event(
+                    Some(health),
+                    HEALTH * capacity,
+                    HEALTH * capacity * 2,
+                    AllocReason::Growth
+                ),
+            ]
+        );
+        let len = entities.len();
+        assert_eq!(
+            world.bytes_for_archetype::<(Position, Health)>(),
+            Some((POSITION + HEALTH) * capacity * 2)
+        );
+
+        for entity in entities.drain(..5) {
+            world.despawn(entity);
+        }
+        world.shrink_to_fit();
+        let shrunk = len - 5;
+        assert_eq!(
+            world.take_allocation_events(),
+            [
+                event(
+                    Some(position),
+                    POSITION * capacity * 2,
+                    POSITION * shrunk,
+                    AllocReason::Shrink
+                ),
+                event(
+                    Some(health),
+                    HEALTH * capacity * 2,
+                    HEALTH * shrunk,
+                    AllocReason::Shrink
+                ),
+            ]
+        );
+        assert_eq!(world.tracked_bytes(), Some((POSITION + HEALTH) * shrunk));
+        // Shrinking again does nothing, and an empty column releases all of its memory.
+        world.shrink_to_fit();
+        assert_eq!(world.take_allocation_events(), []);
+        world.clear();
+        world.shrink_to_fit();
+        assert_eq!(
+            world.take_allocation_events(),
+            [
+                event(Some(position), POSITION * shrunk, 0, AllocReason::Shrink),
+                event(Some(health), HEALTH * shrunk, 0, AllocReason::Shrink),
+            ]
+        );
+        assert_eq!(world.bytes_for_archetype::<(Position, Health)>(), Some(0));
+        assert_eq!(world.tracked_bytes(), Some(0));
+    }
+
+    #[test]
+    fn test_spawn_batch_and_teardown() {
+        let mut world = World::default();
+        world.track_allocations(1000);
+        world.components.register_component::<Position>();
+        world.components.register_component::<Health>();
+        let info = <(Position, Health)>::arch_info(&world.components).unwrap();
+        let bundles = (0..100).map(|i| (Position([i as f32; 3]), Health(i)));
+        // SAFETY: Every bundle stores exactly the components of `info`.
+        unsafe { world.spawn_batch_with_info(&info, bundles) }.unwrap();
+        let events = world.take_allocation_events();
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.component.is_some(), event.reason))
+                .collect::<Vec<_>>(),
+            [
+                (false, AllocReason::Growth),
+                (true, AllocReason::SpawnBatch),
+                (true, AllocReason::SpawnBatch),
+                (false, AllocReason::SpawnBatch),
+            ]
+        );
+        assert_eq!(world.tracked_bytes(), Some((POSITION + HEALTH) * 100));
+        // Spawning after the batch is growth again.
+        spawn(&mut world, 1);
+        assert!(world
+            .take_allocation_events()
+            .iter()
+            .all(|event| event.reason == AllocReason::Growth));
+
+        // Tearing the world down returns the memory of every storage, even the ones that didn't outgrow their
+        // inline block.
+        world.spawn(Name("tiny".into()));
+        world.take_allocation_events();
+        let tracker = world.components.storage_alloc().tracker().unwrap().clone();
+        let mut teardown = world.into_teardown();
+        while !teardown.run(Duration::ZERO).is_done() {}
+        drop(teardown);
+        let events = tracker.take_events();
+        assert_eq!(events.len(), 3);
+        assert!(events
+            .iter()
+            .all(|event| event.reason == AllocReason::Teardown && event.new_bytes == 0));
+        assert_eq!(tracker.total_bytes(), 0);
+    }
+
+    #[test]
+    fn test_counters_match_the_allocator() {
+        let alloc = Arc::new(BumpAlloc::new(1 << 20));
+        let mut world = World::with_allocator(alloc.clone());
+        world.track_allocations(0);
+        let mut entities = Vec::new();
+        for round in 0..6 {
+            entities.extend(spawn(&mut world, 37 * round));
+            for _ in 0..round * 5 {
+                world.spawn(Name(round.to_string()));
+            }
+            for entity in entities.drain(..entities.len() / 3) {
+                world.despawn(entity);
+            }
+            if round % 2 == 1 {
+                world.shrink_to_fit();
+            }
+            assert_eq!(world.tracked_bytes(), Some(alloc.live_bytes()));
+            assert_eq!(
+                world.bytes_for_archetype::<(Position, Health)>().unwrap()
+                    + world.bytes_for_archetype::<Name>().unwrap(),
+                alloc.live_bytes()
+            );
+            assert_memory_within!(world, alloc.live_bytes());
+        }
+        // The log is bounded, so nothing was kept.
+        assert_eq!(world.take_allocation_events(), []);
+        drop(world);
+        assert!(alloc.is_balanced());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_track_after_spawn() {
+        let mut world = World::default();
+        spawn(&mut world, 1);
+        world.track_allocations(10);
+    }
+
+    #[test]
+    fn test_untracked() {
+        let mut world = World::default();
+        spawn(&mut world, 100);
+        world.shrink_to_fit();
+        assert_eq!(world.tracked_bytes(), None);
+        assert_eq!(world.bytes_for_archetype::<Name>(), None);
+        assert_eq!(world.take_allocation_events(), []);
+    }
+}
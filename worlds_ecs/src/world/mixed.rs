@@ -0,0 +1,373 @@
+use super::World;
+use crate::{
+    archetype::{key::PrimeArchKey, Archetype, ArchetypeInfo, MAX_COMPS_PER_ARCH},
+    component::ComponentFactory,
+    entity::EntityId,
+    utils::panics,
+};
+
+/// An item of a stream of entities with different archetypes, like the entities of a level, that can be spawned in
+/// batches grouped by archetype (see [`World::spawn_batch_mixed`]). Usually implemented by an enum of bundles,
+/// generated by [`mixed_bundles!`](crate::mixed_bundles).
+pub trait SpawnInto: Sized {
+    /// How many kinds of items there are. The items of a kind are all spawned with the same archetype.
+    const KINDS: usize;
+
+    /// The kind of the item, less than [`Self::KINDS`].
+    fn kind(&self) -> usize;
+
+    /// The archetype that the items of `kind` are spawned with, registering its components if they aren't
+    /// registered.
+    fn arch_info(kind: usize, components: &mut ComponentFactory) -> ArchetypeInfo;
+
+    /// Spawn items that are all of `kind` as one batch (for example, with [`World::spawn_batch`]), and return the
+    /// ids of the spawned entities, in order.
+    fn spawn_kind(world: &mut World, kind: usize, items: Vec<Self>) -> Vec<EntityId>;
+}
+
+/// The [`ArchetypeInfo`] of `B`, registering its components if they aren't registered.
+///
+/// # Panics
+/// If `B` has the same component more than once, or more than [`MAX_COMPS_PER_ARCH`] components.
+#[doc(hidden)]
+#[track_caller]
+pub fn arch_info_or_register<B: Archetype>(
+    components: &mut ComponentFactory,
+    operation: &str,
+) -> ArchetypeInfo {
+    let mut prime_key = PrimeArchKey::IDENTITY;
+    if !B::merge_prime_key_or_register(components, &mut prime_key) {
+        panics::fail_duplicate_components::<B>(operation);
+    }
+    let arch_info = B::arch_info(components).expect("The components were just registered");
+    if arch_info.component_ids().len() > MAX_COMPS_PER_ARCH {
+        panics::fail_too_many_components::<B>(operation, &arch_info, components);
+    }
+    arch_info
+}
+
+impl World {
+    /// Spawn a stream of entities with different archetypes, like calling [`World::spawn`] for each of them, but
+    /// grouping them by archetype first, and spawning each group as one batch (see [`World::spawn_batch`]), so the
+    /// storage of each archetype is resolved and reserved once. Returns the ids of the spawned entities in the order
+    /// of the items.
+    ///
+    /// The items are buffered until the stream ends, and the groups are spawned one after the other, so the ids
+    /// aren't allocated in the order of the items (but they are returned in it).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Wall;
+    /// #[derive(Component)]
+    /// struct Door { locked: bool }
+    /// #[derive(Component)]
+    /// struct Position(i32, i32);
+    ///
+    /// worlds_ecs::mixed_bundles! {
+    ///     enum LevelEntity {
+    ///         Wall((Wall, Position)),
+    ///         Door((Door, Position)),
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// let level = (0..100).map(|x| match x % 10 {
+    ///     0 => LevelEntity::Door((Door { locked: x > 50 }, Position(x, 0))),
+    ///     _ => LevelEntity::Wall((Wall, Position(x, 0))),
+    /// });
+    /// let entities = world.spawn_batch_mixed(level);
+    /// assert_eq!(world.get_component::<Position>(entities[42]).unwrap().0, 42);
+    /// assert!(world.get_component::<Door>(entities[60]).unwrap().locked);
+    /// ```
+    ///
+    /// # Panics
+    /// If the kind of an item isn't less than [`SpawnInto::KINDS`], or if the entities would break a component
+    /// rule (see [`World::require_component`]), or if the whole stream doesn't fit in a quota (see
+    /// [`World::set_entity_limit`]). The rules and quotas are checked for every group before any of them is
+    /// spawned, so nothing is spawned when they fail.
+    #[track_caller]
+    pub fn spawn_batch_mixed<T: SpawnInto>(
+        &mut self,
+        items: impl IntoIterator<Item = T>,
+    ) -> Vec<EntityId> {
+        // The positions of the items of each kind in the stream, and the items.
+        let mut groups: Vec<(Vec<usize>, Vec<T>)> =
+            (0..T::KINDS).map(|_| (Vec::new(), Vec::new())).collect();
+        let mut len = 0;
+        for item in items {
+            let kind = item.kind();
+            let Some((positions, group)) = groups.get_mut(kind) else {
+                panics::fail(
+                    "spawn_batch_mixed",
+                    "the kind of an item is out of range",
+                    &[("kind", &kind), ("kinds", &T::KINDS)],
+                );
+            };
+            positions.push(len);
+            group.push(item);
+            len += 1;
+        }
+
+        let next = self.entities.next_entity_id();
+        let mut pending = 0;
+        for (kind, (positions, _)) in groups.iter().enumerate() {
+            if positions.is_empty() {
+                continue;
+            }
+            let arch_info = T::arch_info(kind, &mut self.components);
+            let completion = match self
+                .check_component_rules(next, arch_info.prime_key(), |_| arch_info.clone())
+            {
+                Ok(completion) => completion,
+                Err(error) => panics::fail("spawn_batch_mixed", error, &[("kind", &kind)]),
+            };
+            let key = completion
+                .as_ref()
+                .map_or(arch_info.prime_key(), |completion| {
+                    completion.arch_info.prime_key()
+                });
+            if let Err(error) = self.check_quota_after(pending, key, positions.len()) {
+                panics::fail("spawn_batch_mixed", error, &[("kind", &kind)]);
+            }
+            pending += positions.len();
+        }
+
+        let mut entities = vec![None; len];
+        for (kind, (positions, group)) in groups.into_iter().enumerate() {
+            if group.is_empty() {
+                continue;
+            }
+            let spawned = T::spawn_kind(self, kind, group);
+            for (position, entity) in positions.into_iter().zip(spawned) {
+                entities[position] = Some(entity);
+            }
+        }
+        entities
+            .into_iter()
+            .map(|entity| entity.expect("Every item of a group is spawned"))
+            .collect()
+    }
+}
+
+/// Define an enum whose variants are bundles (tuples of components, or single components) and implement
+/// [`SpawnInto`] for it, so a stream of its items can be spawned in batches grouped by archetype, with
+/// [`World::spawn_batch_mixed`]. Every variant is a kind of its own.
+///
+/// ```
+/// # use worlds_ecs::prelude::*;
+/// #[derive(Component, Clone)]
+/// struct Wall;
+/// #[derive(Component, Clone)]
+/// struct Health(u32);
+///
+/// worlds_ecs::mixed_bundles! {
+///     #[derive(Clone)]
+///     pub enum Spawnable {
+///         Wall(Wall),
+///         Destructible((Wall, Health)),
+///     }
+/// }
+///
+/// let mut world = World::default();
+/// let entities = world.spawn_batch_mixed([
+///     Spawnable::Wall(Wall),
+///     Spawnable::Destructible((Wall, Health(10))),
+/// ]);
+/// assert!(world.get_component::<Health>(entities[0]).is_none());
+/// assert!(world.get_component::<Health>(entities[1]).is_some());
+/// ```
+#[macro_export]
+macro_rules! mixed_bundles {
+    ($(#[$attr:meta])* $vis:vis enum $name:ident { $($variant:ident($bundle:ty)),+ $(,)? }) => {
+        $(#[$attr])*
+        $vis enum $name {
+            $(
+                #[allow(missing_docs)]
+                $variant($bundle),
+            )+
+        }
+
+        const _: () = {
+            #[allow(non_camel_case_types, clippy::enum_variant_names)]
+            enum Kind {
+                $($variant,)+
+            }
+
+            impl $crate::world::mixed::SpawnInto for $name {
+                const KINDS: usize = [$(Kind::$variant,)+].len();
+
+                fn kind(&self) -> usize {
+                    match self {
+                        $($name::$variant(_) => Kind::$variant as usize,)+
+                    }
+                }
+
+                #[track_caller]
+                fn arch_info(
+                    kind: usize,
+                    components: &mut $crate::component::ComponentFactory,
+                ) -> $crate::archetype::ArchetypeInfo {
+                    $(
+                        if kind == Kind::$variant as usize {
+                            return $crate::world::mixed::arch_info_or_register::<$bundle>(
+                                components,
+                                "spawn_batch_mixed",
+                            );
+                        }
+                    )+
+                    unreachable!("`{}` has no kind {}", stringify!($name), kind)
+                }
+
+                #[track_caller]
+                fn spawn_kind(
+                    world: &mut $crate::world::World,
+                    kind: usize,
+                    items: ::std::vec::Vec<Self>,
+                ) -> ::std::vec::Vec<$crate::entity::EntityId> {
+                    $(
+                        if kind == Kind::$variant as usize {
+                            return world.spawn_batch(items.into_iter().map(|item| match item {
+                                $name::$variant(bundle) => bundle,
+                                #[allow(unreachable_patterns)]
+                                _ => unreachable!("the items of a kind are all of its variant"),
+                            }));
+                        }
+                    )+
+                    unreachable!("`{}` has no kind {}", stringify!($name), kind)
+                }
+            }
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpawnInto;
+    use crate::prelude::*;
+
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Wall(u32);
+    #[derive(Component, Clone, Copy, Debug, PartialEq, Default)]
+    struct Door(bool);
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Position(i32, i32);
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Torch;
+
+    mixed_bundles! {
+        #[derive(Clone)]
+        enum LevelEntity {
+            Wall((Wall, Position)),
+            Door((Door, Position)),
+            LockedDoor((Door, Position, Wall)),
+            Torch(Torch),
+        }
+    }
+
+    mixed_bundles! {
+        enum Single {
+            Wall(Wall),
+        }
+    }
+
+    fn level(len: i32) -> Vec<LevelEntity> {
+        (0..len)
+            .map(|i| match i % 7 {
+                0 | 3 => LevelEntity::Door((Door(i % 2 == 0), Position(i, -i))),
+                1 => LevelEntity::LockedDoor((Door(true), Position(i, 0), Wall(i as u32))),
+                2 => LevelEntity::Torch(Torch),
+                _ => LevelEntity::Wall((Wall(i as u32), Position(0, i))),
+            })
+            .collect()
+    }
+
+    fn components(
+        world: &World,
+        entity: EntityId,
+    ) -> (Option<Wall>, Option<Door>, Option<Position>, Option<Torch>) {
+        (
+            world.get_component::<Wall>(entity).copied(),
+            world.get_component::<Door>(entity).copied(),
+            world.get_component::<Position>(entity).copied(),
+            world.get_component::<Torch>(entity).copied(),
+        )
+    }
+
+    #[test]
+    fn test_matches_spawning_each_item() {
+        let level = level(1000);
+        let mut naive = World::default();
+        let naive_entities: Vec<EntityId> = level
+            .clone()
+            .into_iter()
+            .map(|item| match item {
+                LevelEntity::Wall(bundle) => naive.spawn(bundle),
+                LevelEntity::Door(bundle) => naive.spawn(bundle),
+                LevelEntity::LockedDoor(bundle) => naive.spawn(bundle),
+                LevelEntity::Torch(bundle) => naive.spawn(bundle),
+            })
+            .collect();
+        let mut world = World::default();
+        let entities = world.spawn_batch_mixed(level);
+
+        assert_eq!(entities.len(), naive_entities.len());
+        for (entity, naive_entity) in entities.iter().zip(&naive_entities) {
+            assert_eq!(
+                components(&world, *entity),
+                components(&naive, *naive_entity)
+            );
+        }
+        assert_eq!(world.iter_entities().count(), naive.iter_entities().count());
+        assert_eq!(
+            world.storages.arch_storages.storage_keys().len(),
+            naive.storages.arch_storages.storage_keys().len()
+        );
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_single_kind_and_empty_streams() {
+        let mut world = World::default();
+        assert_eq!(<Single as SpawnInto>::KINDS, 1);
+        assert!(world.spawn_batch_mixed(Vec::<Single>::new()).is_empty());
+        let entities = world.spawn_batch_mixed((0..10).map(|i| Single::Wall(Wall(i))));
+        let walls: Vec<u32> = entities
+            .iter()
+            .map(|entity| world.get_component::<Wall>(*entity).unwrap().0)
+            .collect();
+        assert_eq!(walls, (0..10).collect::<Vec<u32>>());
+        // More items join the existing storages.
+        world.spawn_batch_mixed(level(14));
+        assert_eq!(world.iter_component::<Wall>().count(), 10 + 8);
+    }
+
+    #[test]
+    fn test_rules_and_quotas_are_checked_before_spawning() {
+        let mut world = World::default();
+        world.set_archetype_limit::<Torch>(2);
+        // The walls and doors fit, but the torches don't, so nothing is spawned.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            world.spawn_batch_mixed(level(21));
+        }));
+        assert!(result.is_err());
+        assert_eq!(world.iter_entities().count(), 0);
+        world.assert_invariants();
+
+        world.set_archetype_limit::<Torch>(3);
+        world.require_component_with_default::<Wall, Door>();
+        let entities = world.spawn_batch_mixed(level(21));
+        assert_eq!(entities.len(), 21);
+        // Required defaults are inserted into every group.
+        assert_eq!(world.get_component::<Door>(entities[4]), Some(&Door(false)));
+        world.assert_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: spawn_batch failed: a component appears more than once")]
+    fn test_duplicate_components() {
+        let mut world = World::default();
+        world.spawn_batch([(Wall(0), Wall(1))]);
+    }
+}
@@ -1,25 +1,201 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
 use crate::{
-    archetype::Archetype,
+    archetype::key::PrimeArchKey,
+    archetype::{Archetype, ArchetypeInfo},
+    component::{deref_component, deref_component_mut},
     entity::{EntityId, EntityMeta},
-    prelude::{ArchFilter, ArchQuery, Bundle, Component},
-    tag::{TagFactory, TagTracker},
+    prelude::{
+        ArchFilter, ArchQuery, Bundle, Component, ComponentId, FetchContext, FilterResult,
+        QueryIter, ReadOnlyArchQuery, StorageFilterResult,
+    },
+    query::arch_query::check_duplicate_accesses,
+    storage::{alloc::AllocReason, blob_vec::OnDrop},
+    tag::{
+        TagFactory, TagSnapshot, TagSnapshotError, TagSnapshotReport, TagTracker, UnknownTagPolicy,
+    },
+    utils::panics,
+};
+use access::AccessKind;
+use destroy::DespawnReason;
+use latency::LatencyCategory;
+use quota::SpawnError;
+use std::{any::Any, panic::Location};
+use storage::{
+    arch_storage::{ArchStorageIndex, ColumnIterMut},
+    storages::{ArchStorageId, ArchStorages},
+    tag_storage::EntityTags,
+    ArchEntityStorage,
 };
+use userdata::{Userdata, UserdataKey};
+use warnings::{EcsWarning, WarnLevel, EMPTY_BUNDLE_SPAWN_THRESHOLD};
 
+/// Module responsible for recording the accesses of the World, to diagnose ordering bugs.
+pub mod access;
+/// Module responsible for debug annotations of entities, for frame capture tooling.
+pub mod annotations;
+/// Module responsible for identifying the archetypes of the World across frames.
+pub mod archetypes;
+/// Module responsible for archiving entities out of the storages of the World.
+pub mod archive;
+/// Module responsible for queueing operations on the World from async code, and completing their futures.
+pub mod async_queue;
+/// Module responsible for perturbing the World at random, to surface stale pointers and ids in tests.
+#[cfg(feature = "chaos")]
+pub mod chaos;
+/// Module responsible for computing deterministic checksums of components, to detect desyncs between peers.
+pub mod checksum;
+/// Module responsible for copy-on-write copies of the World.
+pub mod cow;
 /// Module responsible for any data that can be stored in the World.
 pub mod data;
+/// Module responsible for components that are derived from other components.
+pub mod derived;
+/// Module responsible for destroying entities, in the same order whichever way they are despawned.
+pub mod destroy;
+/// Module responsible for the order that the components of an entity are dropped in.
+pub mod drop_order;
+/// Module responsible for handles to entities, for configuring them with a chain of calls.
+pub mod entity_mut;
+/// Module responsible for extracting owned clones of bundles out of the World.
+pub mod extract;
+/// Module responsible for compact handles to entities, for scripting VMs and C code.
+pub mod ffi_handle;
+/// Module responsible for fingerprinting the configuration of the World, for lockstep sessions.
+pub mod fingerprint;
+/// Module responsible for caching the results of read-only queries within a frame.
+pub mod frame_query;
+/// Module responsible for freezing storages, so their components can be read without borrowing the World.
+pub mod freeze;
+/// Module responsible for named groups of entities.
+pub mod group;
+/// Module responsible for handles to components, for deferred writes.
+pub mod handle;
+/// Module responsible for recording the last values of components of entities, for debugging.
+pub mod history;
+/// Module responsible for histograms of the latency of the operations that can spike.
+pub mod latency;
+/// Module responsible for tracking the memory of the storages.
+pub mod memory;
+/// Module responsible for spawning batches of entities with different archetypes.
+pub mod mixed;
+/// Module responsible for iterating components in a stable order, for replication.
+pub mod ordered;
+/// Module responsible for splitting the World into partitions with disjoint accesses, for external schedulers.
+pub mod partition;
+/// Module responsible for patching several components of an entity at once.
+pub mod patch;
+/// Module responsible for pinning storages, so their rows aren't moved while they are referenced externally.
+pub mod pin;
+/// Module responsible for creating storages ahead of time, before the first spawn of their archetype.
+pub mod precreate;
+/// Module responsible for quarantining inconsistent storages, so shipped builds recover instead of crashing.
+pub mod quarantine;
+/// Module responsible for limiting how many entities can be spawned, for untrusted content.
+pub mod quota;
+/// Module responsible for sharing the World with scoped threads that only read from it.
+pub mod read_scope;
+/// Module responsible for removing single components from entities, moving them to a smaller archetype.
+pub mod remove;
+/// Module responsible for reordering the rows of storages, to defragment them.
+pub mod reorder;
+/// Module responsible for resources, the values of the World that aren't attached to any entity.
+pub mod resource;
+/// Module responsible for the rules that components declare about each other.
+pub mod rules;
+/// Module responsible for views of the World that only access whitelisted components, for sandboxing plugins.
+pub mod scoped;
+/// Module responsible for shared components, whose equal values are stored once.
+pub mod shared;
+/// Module responsible for keeping storages sorted by a component key.
+pub mod sort;
+/// Module responsible for keeping spatial indexes of components in sync with the World.
+pub mod spatial;
+/// Module responsible for cheap statistics of the World, for debug overlays.
+pub mod stats;
 /// Module responsible for storage in the World.
 pub mod storage;
+/// Module responsible for tearing the World down a chunk at a time.
+pub mod teardown;
+/// Module responsible for tombstones, despawned entities whose selected components stay readable for a few frames.
+pub mod tombstone;
+/// Module responsible for queries that read and write the same components, in two sequential passes.
+pub mod two_pass;
+/// Module responsible for attaching type-erased data to entities.
+pub mod userdata;
+/// Module responsible for warning about suspicious usage of the World.
+pub mod warnings;
 
 /// This type stores everything that is offered by this crate. It is the main type of the ECS.
 /// It exposes the API for the ECS, it is the bedrock of the engine.
-// TODO: Better docs
+///
+/// Entities are spawned with a bundle of components (see [`World::spawn`]), and the entities that have the same set
+/// of components (the same archetype) are stored together, in the same storage. A query (see [`World::query`])
+/// matches every storage whose archetype has at least the components of the query, so an entity with more
+/// components than the query asks for is still matched. An [`EntityId`] stays valid until its entity is despawned:
+/// after that, every lookup with it returns `None`, even when its id is reused by a new entity.
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Position(f32);
+/// #[derive(Component)]
+/// struct Velocity(f32);
+///
+/// let mut world = World::default();
+/// let ship = world.spawn((Position(0.0), Velocity(1.5)));
+/// let rock = world.spawn(Position(10.0));
+///
+/// for (position, velocity) in world.query::<(&mut Position, &Velocity)>() {
+///     position.0 += velocity.0;
+/// }
+/// assert_eq!(world.get_component::<Position>(ship).unwrap().0, 1.5);
+/// assert_eq!(world.get_component::<Position>(rock).unwrap().0, 10.0);
+///
+/// world.despawn(ship);
+/// assert!(world.get_component::<Position>(ship).is_none());
+/// ```
 #[derive(Default)]
 pub struct World {
     pub(crate) components: crate::component::ComponentFactory,
     pub(crate) entities: crate::entity::EntityFactory,
     pub(crate) storages: storage::storages::StorageFactory,
+    pub(crate) warnings: warnings::WarningsChannel,
+    pub(crate) derived: derived::DerivedRegistry,
+    pub(crate) spatial: spatial::SpatialRegistry,
+    pub(crate) userdata: userdata::UserdataStorage,
+    pub(crate) resources: resource::Resources,
+    pub(crate) tombstones: tombstone::Tombstones,
+    pub(crate) async_jobs: async_queue::AsyncJobs,
+    pub(crate) archive: archive::ColdStore,
+    pub(crate) access: access::AccessRecorder,
+    pub(crate) annotations: annotations::Annotations,
+    pub(crate) ffi_handles: ffi_handle::FfiHandles,
+    pub(crate) groups: group::Groups,
+    pub(crate) histories: history::Histories,
+    pub(crate) ordered: ordered::OrderedIndexes,
+    pub(crate) quotas: quota::Quotas,
+    pub(crate) frame_queries: frame_query::FrameQueries,
+    pub(crate) scratch: two_pass::ScratchBuffers,
+    #[cfg(feature = "scene")]
+    pub(crate) scenes: crate::scene::SceneRegistry,
+    pub(crate) id: WorldId,
+}
+
+/// Uniquely identifies a [`World`], so things that were resolved in one world (like a
+/// [`ComponentHandle`](handle::ComponentHandle)) can't be used with another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct WorldId(u64);
+
+impl Default for WorldId {
+    fn default() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        WorldId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
 }
 
 // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
@@ -29,15 +205,308 @@ pub struct World {
 impl World {
     /// Create a new empty [`World`] (just like `World::default`), but with a custom tag factory (instead of an empty one).
     /// This is useful because you can't change the tag factory after assigning it to the world.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// let mut tracker = world.get_tag_tracker(entity).unwrap();
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { tracker.tag::<Selected>() };
+    /// assert!(unsafe { world.get_tag_tracker(entity).unwrap().is_tagged::<Selected>() });
+    /// ```
     pub fn with_tags(tagf: TagFactory) -> Self {
+        let mut world = World::default();
+        world.storages.tag_storage = storage::tag_storage::TagStorage::new(Arc::new(tagf));
+        world
+    }
+
+    /// Create a new empty [`World`] (just like `World::default`), whose component storages allocate their memory
+    /// with `alloc` (instead of the global allocator), for example an arena.
+    /// The allocator can't be changed after the world is created: every storage returns its memory to the
+    /// allocator that allocated it.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use worlds_ecs::{prelude::*, storage::alloc::GlobalStorageAlloc};
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32, f32);
+    ///
+    /// // An arena would go here; the global allocator stands in for it.
+    /// let mut world = World::with_allocator(Arc::new(GlobalStorageAlloc));
+    /// let entity = world.spawn(Position(1.0, 2.0));
+    /// assert_eq!(world.get_component::<Position>(entity).unwrap().1, 2.0);
+    /// ```
+    pub fn with_allocator(alloc: Arc<dyn crate::storage::alloc::StorageAlloc>) -> Self {
         Self {
-            storages: storage::storages::StorageFactory {
-                tag_storage: storage::tag_storage::TagStorage::new(Arc::new(tagf)),
-                ..Default::default()
-            },
+            components: crate::component::ComponentFactory::with_allocator(alloc),
             ..Default::default()
         }
     }
+
+    /// Set the order in which the ids of despawned entities are reused. See [`ReusePolicy`](crate::entity::ReusePolicy).
+    ///
+    /// ```
+    /// use worlds_ecs::{entity::ReusePolicy, prelude::*};
+    ///
+    /// let mut world = World::default();
+    /// world.set_entity_reuse_policy(ReusePolicy::Lifo);
+    /// let first = world.spawn(());
+    /// let second = world.spawn(());
+    /// world.despawn(first);
+    /// world.despawn(second);
+    /// // The id that was freed last is reused first.
+    /// assert_eq!(world.spawn(()).id(), second.id());
+    /// ```
+    pub fn set_entity_reuse_policy(&mut self, reuse_policy: crate::entity::ReusePolicy) {
+        self.entities.set_reuse_policy(reuse_policy);
+    }
+
+    /// Hand out the ids of the entities (see [`EntityId::id`]) in a pseudo-random permutation, determined by the
+    /// `seed`, instead of counting them up from 0. This is a debugging aid for code that (wrongly) uses the ids as
+    /// indexes into dense arrays: that code happens to work until the ids are reused in a different pattern, and
+    /// with salted ids it breaks right away. Nothing else changes, since the world still stores its entities
+    /// densely. Only available with the `salted-ids` feature.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let (mut plain, mut salted) = (World::default(), World::default());
+    /// salted.salt_entity_ids(42);
+    /// let plain_ids: Vec<u32> = (0..8).map(|_| plain.spawn(()).id()).collect();
+    /// let salted_ids: Vec<u32> = (0..8).map(|_| salted.spawn(()).id()).collect();
+    /// assert_eq!(plain_ids, [0, 1, 2, 3, 4, 5, 6, 7]);
+    /// assert_ne!(salted_ids, plain_ids);
+    /// ```
+    ///
+    /// # Panics
+    /// If an entity was already spawned in the world.
+    #[cfg(feature = "salted-ids")]
+    #[track_caller]
+    pub fn salt_entity_ids(&mut self, seed: u64) {
+        if !self.entities.salt_ids(seed) {
+            panics::fail(
+                "salt_entity_ids",
+                "an entity was already spawned in the world",
+                &[],
+            );
+        }
+    }
+
+    /// The [`ComponentFactory`](crate::component::ComponentFactory) of the world, to look up the components that are
+    /// registered in it (like their names).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(10));
+    /// let components = world.components();
+    /// let comp_id = components.get_component_id::<Health>().unwrap();
+    /// let info = components.get_component_info_from_component_id(comp_id).unwrap();
+    /// assert_eq!(info.name(), std::any::type_name::<Health>());
+    /// ```
+    pub fn components(&self) -> &crate::component::ComponentFactory {
+        &self.components
+    }
+
+    /// The [`StorageFactory`](storage::storages::StorageFactory) of the world, to inspect the storages that its
+    /// entities are stored in. The storages can't be mutated through it, since that would desynchronize them from
+    /// the bookkeeping of the entities.
+    ///
+    /// ```
+    /// use worlds_ecs::{archetype::Archetype, prelude::*};
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh(u32);
+    /// #[derive(Component)]
+    /// struct Skin(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Mesh(0));
+    /// world.spawn((Mesh(1), Skin(1)));
+    /// let mesh = Mesh::prime_key(world.components()).unwrap();
+    /// // Both archetypes have a `Mesh`, so both of their storages match.
+    /// let storages = world.storages().arch_storages();
+    /// let lens: Vec<usize> = storages
+    ///     .iter_storages_with_matching_archetype(mesh)
+    ///     .map(|storage| storage.len())
+    ///     .collect();
+    /// assert_eq!(lens, [1, 1]);
+    /// ```
+    pub fn storages(&self) -> &storage::storages::StorageFactory {
+        &self.storages
+    }
+}
+
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+//                               WARNINGS API
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+
+impl World {
+    /// Set which [`EcsWarning`]s the [`World`] emits. Warnings are off by default.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// world.set_warning_level(WarnLevel::All);
+    /// for _ in 0..1000 {
+    ///     world.spawn(());
+    /// }
+    /// assert!(matches!(
+    ///     world.take_warnings()[..],
+    ///     [EcsWarning::EmptyBundleSpawned { .. }]
+    /// ));
+    /// ```
+    pub fn set_warning_level(&mut self, level: WarnLevel) {
+        self.warnings.set_level(level);
+    }
+
+    /// Take all of the [`EcsWarning`]s that were emitted since the last time they were taken.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// #[allow(dead_code)]
+    /// struct Heightmap([f32; 4096]);
+    ///
+    /// let mut world = World::default();
+    /// world.set_warning_level(WarnLevel::All);
+    /// world.spawn(Heightmap([0.0; 4096]));
+    /// let warnings = world.take_warnings();
+    /// assert!(matches!(warnings[..], [EcsWarning::LargeComponent { size: 16384, .. }]));
+    /// // Taking the warnings clears them.
+    /// assert!(world.take_warnings().is_empty());
+    /// ```
+    pub fn take_warnings(&mut self) -> Vec<EcsWarning> {
+        self.warnings.take()
+    }
+
+    /// Set the size (in bytes) above which a component that isn't boxed (see [`Component::BOXED`]) emits an
+    /// [`EcsWarning::LargeComponent`] when it's first spawned. The default is
+    /// [`LARGE_COMPONENT_THRESHOLD`](warnings::LARGE_COMPONENT_THRESHOLD).
+    /// Components that were already checked aren't checked again.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// #[allow(dead_code)]
+    /// struct Transform([f32; 16]);
+    ///
+    /// let mut world = World::default();
+    /// world.set_warning_level(WarnLevel::All);
+    /// world.set_large_component_threshold(32);
+    /// world.spawn(Transform([0.0; 16]));
+    /// assert!(matches!(
+    ///     world.take_warnings()[..],
+    ///     [EcsWarning::LargeComponent { size: 64, .. }]
+    /// ));
+    /// ```
+    pub fn set_large_component_threshold(&mut self, bytes: usize) {
+        self.warnings.large_component_threshold = bytes;
+    }
+
+    /// Set how many storages can be created while a [`QueryState`](crate::prelude::QueryState) isn't iterated,
+    /// before it emits an [`EcsWarning::StaleQueryState`] when it finally is. The default is
+    /// [`STALE_QUERY_STATE_THRESHOLD`](warnings::STALE_QUERY_STATE_THRESHOLD).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Armor(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.set_warning_level(WarnLevel::All);
+    /// world.set_stale_query_state_threshold(1);
+    /// let mut healths = QueryState::<&Health>::new(&world);
+    /// world.spawn(Health(10));
+    /// world.spawn((Health(20), Armor(5)));
+    /// assert_eq!(healths.iter(&mut world).count(), 2);
+    /// assert!(matches!(
+    ///     world.take_warnings()[..],
+    ///     [EcsWarning::StaleQueryState { storages_created: 2, .. }]
+    /// ));
+    /// ```
+    pub fn set_stale_query_state_threshold(&mut self, storages: usize) {
+        self.warnings.stale_query_state_threshold = storages;
+    }
+
+    /// Warn that the query `Q` didn't match anything, because some of its components were never registered.
+    pub(crate) fn warn_unregistered_query<Q>(&mut self) {
+        if self.warnings.is_enabled() {
+            self.warnings
+                .emit(EcsWarning::UnregisteredComponentQueried {
+                    query: std::any::type_name::<Q>(),
+                });
+        }
+    }
+
+    /// Check the components that were registered since the last check.
+    fn check_component_registrations(&mut self) {
+        let registered = self.components.component_count();
+        for second in self.warnings.checked_components..registered {
+            let second = ComponentId::new(second);
+            let info = self
+                .components
+                .get_component_info_from_component_id(second)
+                .unwrap();
+            let (name, size) = (info.name(), info.layout().size());
+            if !info.is_boxed() && size > self.warnings.large_component_threshold {
+                self.warnings
+                    .emit(EcsWarning::LargeComponent { name, size });
+            }
+            if let Some(first) = (0..second.id()).map(ComponentId::new).find(|first| {
+                self.components
+                    .get_component_info_from_component_id(*first)
+                    .is_some_and(|info| info.name() == name)
+            }) {
+                self.warnings.emit(EcsWarning::DuplicateComponentName {
+                    name,
+                    first,
+                    second,
+                });
+            }
+        }
+        self.warnings.checked_components = registered;
+    }
+
+    /// Check an entity that was just spawned into the storage with the [`ArchStorageId`] `sid`.
+    fn check_spawn(&mut self, sid: ArchStorageId) {
+        self.check_component_registrations();
+        let is_empty_bundle = self
+            .storages
+            .arch_storages
+            .get_storage(sid)
+            .is_some_and(|storage| {
+                storage
+                    .prime_key()
+                    .is_exact_archetype(PrimeArchKey::IDENTITY)
+            });
+        if is_empty_bundle {
+            self.warnings.empty_bundle_spawns += 1;
+            if self.warnings.empty_bundle_spawns >= EMPTY_BUNDLE_SPAWN_THRESHOLD {
+                self.warnings.emit(EcsWarning::EmptyBundleSpawned {
+                    count: self.warnings.empty_bundle_spawns,
+                });
+            }
+        }
+    }
 }
 
 // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
@@ -45,9 +514,300 @@ impl World {
 // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
 
 impl World {
-    /// Get the [`TagTracker`] of an entity.
-    pub fn get_tag_tracker(&self, entity: EntityId) -> TagTracker {
-        self.storages.tag_storage.get_tag_tracker(entity)
+    /// Get the [`TagTracker`] of an entity, or `None` if the entity isn't alive (it was despawned, was never
+    /// allocated, or is from another world).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Hidden;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Hidden>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// // SAFETY: `Hidden` is registered, and no other tracker of the entity is accessed at the same time.
+    /// unsafe {
+    ///     world.get_tag_tracker(entity).unwrap().tag::<Hidden>();
+    ///     assert!(world.get_tag_tracker(entity).unwrap().is_tagged::<Hidden>());
+    ///     world.get_tag_tracker(entity).unwrap().untag::<Hidden>();
+    ///     assert!(!world.get_tag_tracker(entity).unwrap().is_tagged::<Hidden>());
+    /// }
+    /// world.despawn(entity);
+    /// assert!(world.get_tag_tracker(entity).is_none());
+    /// ```
+    pub fn get_tag_tracker(&self, entity: EntityId) -> Option<TagTracker> {
+        self.entities.get_entity_meta(entity)?;
+        Some(self.tag_tracker_of(entity))
+    }
+
+    /// The [`TagTracker`] of an entity that is alive. A tracker is created for every entity when it's spawned.
+    pub(crate) fn tag_tracker_of(&self, entity: EntityId) -> TagTracker {
+        self.storages
+            .tag_storage
+            .get_tag_tracker(self.entities.dense_index(entity))
+            .expect("A tag tracker is created for every entity that is spawned")
+    }
+
+    /// The tags of the entities, as the filters of queries see them (see [`FetchContext::tags`]).
+    pub(crate) fn entity_tags(&self) -> EntityTags<'_> {
+        self.storages.tag_storage.entity_tags(&self.entities)
+    }
+
+    /// Take a [`TagSnapshot`] of the tags of every entity in the [`World`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { world.get_tag_tracker(entity).unwrap().tag::<Selected>() };
+    /// let snapshot = world.snapshot_tags();
+    /// assert_eq!(snapshot.len(), 1);
+    /// assert_eq!(
+    ///     snapshot.tags_of(entity).collect::<Vec<_>>(),
+    ///     [std::any::type_name::<Selected>()]
+    /// );
+    /// ```
+    pub fn snapshot_tags(&self) -> TagSnapshot {
+        let tag_storage = &self.storages.tag_storage;
+        self.components
+            .storage_alloc()
+            .latency()
+            .time(LatencyCategory::SnapshotCapture, || {
+                TagSnapshot::capture(
+                    tag_storage.tag_factory(),
+                    self.iter_entities()
+                        .map(|entity| (entity, self.tag_tracker_of(entity))),
+                )
+            })
+    }
+
+    /// Apply a [`TagSnapshot`] to the entities in the [`World`]. Tags are matched by name, so the
+    /// snapshot can come from a world whose tags were registered in a different order. The tags of every
+    /// live entity are overwritten: tags that aren't in the snapshot end up unset.
+    /// Tags in the snapshot that aren't registered in this world are handled according to the [`UnknownTagPolicy`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut world = World::with_tags(tagf);
+    /// let entity = world.spawn(());
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { world.get_tag_tracker(entity).unwrap().tag::<Selected>() };
+    /// let snapshot = world.snapshot_tags();
+    ///
+    /// // Undo the selection by restoring the snapshot.
+    /// unsafe { world.get_tag_tracker(entity).unwrap().untag::<Selected>() };
+    /// let report = world
+    ///     .apply_tag_snapshot(&snapshot, UnknownTagPolicy::Fail)
+    ///     .unwrap();
+    /// assert_eq!(report.applied_entities, 1);
+    /// assert!(unsafe { world.get_tag_tracker(entity).unwrap().is_tagged::<Selected>() });
+    /// ```
+    pub fn apply_tag_snapshot(
+        &mut self,
+        snapshot: &TagSnapshot,
+        policy: UnknownTagPolicy,
+    ) -> Result<TagSnapshotReport, TagSnapshotError> {
+        let tag_storage = &self.storages.tag_storage;
+        snapshot.apply(
+            tag_storage.tag_factory(),
+            self.iter_entities()
+                .map(|entity| (entity, self.tag_tracker_of(entity))),
+            policy,
+        )
+    }
+}
+
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+//                               USERDATA API
+// ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
+
+impl World {
+    /// Attach a type-erased value to an entity, in the slot of the [`UserdataKey`], and return the value that was
+    /// there before. This is meant for attaching data that doesn't have its own [`Component`] type, like references
+    /// to objects of a scripting runtime. The userdata of an entity is removed when it's despawned (see
+    /// [`Self::set_userdata_cleanup`]). Panics if the entity was despawned.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// const SCRIPT: UserdataKey = UserdataKey::Named("script");
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(());
+    /// assert!(world.set_userdata(entity, SCRIPT, Box::new("door.lua")).is_none());
+    /// let previous = world.set_userdata(entity, SCRIPT, Box::new("chest.lua")).unwrap();
+    /// assert_eq!(previous.downcast_ref::<&str>(), Some(&"door.lua"));
+    /// ```
+    #[track_caller]
+    pub fn set_userdata(
+        &mut self,
+        entity: EntityId,
+        key: UserdataKey,
+        value: Userdata,
+    ) -> Option<Userdata> {
+        if self.entities.get_entity_meta(entity).is_none() {
+            panics::fail_entity("set_userdata", "the entity isn't alive", entity);
+        }
+        self.userdata.set(entity, key, value)
+    }
+
+    /// Get the userdata of an entity, in the slot of the [`UserdataKey`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// const SCRIPT: UserdataKey = UserdataKey::Named("script");
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(());
+    /// world.set_userdata(entity, SCRIPT, Box::new(7u64));
+    /// let handle = world.get_userdata(entity, SCRIPT).unwrap();
+    /// assert_eq!(handle.downcast_ref::<u64>(), Some(&7));
+    /// assert!(world.get_userdata(entity, UserdataKey::Namespace(1)).is_none());
+    /// ```
+    pub fn get_userdata(
+        &self,
+        entity: EntityId,
+        key: UserdataKey,
+    ) -> Option<&(dyn Any + Send + Sync)> {
+        self.userdata.get(entity, key)
+    }
+
+    /// Get mutable access to the userdata of an entity, in the slot of the [`UserdataKey`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// const CALLS: UserdataKey = UserdataKey::Named("calls");
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(());
+    /// world.set_userdata(entity, CALLS, Box::new(0u32));
+    /// *world
+    ///     .get_userdata_mut(entity, CALLS)
+    ///     .unwrap()
+    ///     .downcast_mut::<u32>()
+    ///     .unwrap() += 1;
+    /// assert_eq!(world.get_userdata(entity, CALLS).unwrap().downcast_ref::<u32>(), Some(&1));
+    /// ```
+    pub fn get_userdata_mut(
+        &mut self,
+        entity: EntityId,
+        key: UserdataKey,
+    ) -> Option<&mut (dyn Any + Send + Sync)> {
+        self.userdata.get_mut(entity, key)
+    }
+
+    /// Remove the userdata of an entity from the slot of the [`UserdataKey`], and return it.
+    /// The cleanup of the key isn't called.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// const SCRIPT: UserdataKey = UserdataKey::Named("script");
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(());
+    /// world.set_userdata(entity, SCRIPT, Box::new("door.lua"));
+    /// let removed = world.remove_userdata(entity, SCRIPT).unwrap();
+    /// assert_eq!(removed.downcast_ref::<&str>(), Some(&"door.lua"));
+    /// assert!(world.get_userdata(entity, SCRIPT).is_none());
+    /// ```
+    pub fn remove_userdata(&mut self, entity: EntityId, key: UserdataKey) -> Option<Userdata> {
+        self.userdata.remove(entity, key)
+    }
+
+    /// Remove all of the userdata of an entity, and return it with the key of each slot.
+    /// The cleanups of the keys aren't called.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(());
+    /// world.set_userdata(entity, UserdataKey::Named("script"), Box::new("door.lua"));
+    /// world.set_userdata(entity, UserdataKey::Namespace(7), Box::new(7u64));
+    /// let mut keys: Vec<UserdataKey> = world
+    ///     .take_all_userdata(entity)
+    ///     .into_iter()
+    ///     .map(|(key, _)| key)
+    ///     .collect();
+    /// keys.sort_by_key(|key| format!("{key:?}"));
+    /// assert_eq!(keys, [UserdataKey::Named("script"), UserdataKey::Namespace(7)]);
+    /// assert!(world.take_all_userdata(entity).is_empty());
+    /// ```
+    pub fn take_all_userdata(&mut self, entity: EntityId) -> Vec<(UserdataKey, Userdata)> {
+        self.userdata.take_all(entity)
+    }
+
+    /// Iterate over the userdata in the slot of the [`UserdataKey`], alongside the entity it's attached to
+    /// (in no particular order).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// const WEIGHT: UserdataKey = UserdataKey::Named("weight");
+    ///
+    /// let mut world = World::default();
+    /// for weight in [3u32, 4] {
+    ///     let entity = world.spawn(());
+    ///     world.set_userdata(entity, WEIGHT, Box::new(weight));
+    /// }
+    /// let total: u32 = world
+    ///     .iter_userdata(WEIGHT)
+    ///     .map(|(_, weight)| weight.downcast_ref::<u32>().unwrap())
+    ///     .sum();
+    /// assert_eq!(total, 7);
+    /// ```
+    pub fn iter_userdata(
+        &self,
+        key: UserdataKey,
+    ) -> impl Iterator<Item = (EntityId, &(dyn Any + Send + Sync))> + '_ {
+        self.userdata.iter(key)
+    }
+
+    /// Set the function that is called with the userdata of the [`UserdataKey`] when its entity is despawned
+    /// (including by [`Self::despawn_matching`] and [`Self::clear`]). The entity's [`EntityId`] is already stale
+    /// when it's called. Without a cleanup, the userdata is just dropped.
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// const SCRIPT: UserdataKey = UserdataKey::Named("script");
+    ///
+    /// let released = Arc::new(Mutex::new(Vec::new()));
+    /// let mut world = World::default();
+    /// let log = Arc::clone(&released);
+    /// world.set_userdata_cleanup(SCRIPT, move |_, userdata| {
+    ///     log.lock().unwrap().push(*userdata.downcast::<&str>().unwrap());
+    /// });
+    /// let entity = world.spawn(());
+    /// world.set_userdata(entity, SCRIPT, Box::new("door.lua"));
+    /// world.despawn(entity);
+    /// assert_eq!(*released.lock().unwrap(), ["door.lua"]);
+    /// ```
+    pub fn set_userdata_cleanup(
+        &mut self,
+        key: UserdataKey,
+        cleanup: impl Fn(EntityId, Userdata) + Send + Sync + 'static,
+    ) {
+        self.userdata.set_cleanup(key, Box::new(cleanup));
     }
 }
 
@@ -55,29 +815,733 @@ impl World {
 //                               COMPONENTS API
 // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
 
-impl World {}
+impl World {
+    /// Register a [`Component`], and return its [`ComponentId`], see
+    /// [`ComponentFactory::register_component`](crate::prelude::ComponentFactory::register_component). Components are
+    /// registered when they are first spawned, so this is only needed to know their ids in advance.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let health = world.register_component::<Health>().unwrap();
+    /// assert_eq!(world.components().get_component_id::<Health>(), Some(health));
+    /// // Registering it again returns the same id.
+    /// assert_eq!(world.register_component::<Health>(), Some(health));
+    /// ```
+    pub fn register_component<C: Component>(&mut self) -> Option<ComponentId> {
+        let comp_id = self.components.register_component::<C>();
+        if self.warnings.is_enabled() {
+            self.check_component_registrations();
+        }
+        comp_id
+    }
+
+    /// Register a [`Component`] that can be compared for equality, see
+    /// [`ComponentFactory::register_comparable_component`](crate::prelude::ComponentFactory::register_comparable_component).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, PartialEq)]
+    /// struct Team(u8);
+    ///
+    /// let mut world = World::default();
+    /// let team = world.register_comparable_component::<Team>().unwrap();
+    /// let info = world.components().get_component_info_from_component_id(team).unwrap();
+    /// assert!(info.eq_fn().is_some());
+    /// ```
+    pub fn register_comparable_component<C: Component + PartialEq>(
+        &mut self,
+    ) -> Option<crate::prelude::ComponentId> {
+        let comp_id = self.components.register_comparable_component::<C>();
+        if self.warnings.is_enabled() {
+            self.check_component_registrations();
+        }
+        comp_id
+    }
+
+    /// Register a [`Component`] that can be hashed, see
+    /// [`ComponentFactory::register_hashable_component`](crate::prelude::ComponentFactory::register_hashable_component).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Hash)]
+    /// struct Name(String);
+    ///
+    /// let mut world = World::default();
+    /// let name = world.register_hashable_component::<Name>().unwrap();
+    /// world.spawn(Name("player".to_string()));
+    /// // Hashable components can be checksummed.
+    /// let checksum = world.checksum(&ChecksumConfig::components([name]));
+    /// assert_eq!(checksum.get(name).unwrap().entities, 1);
+    /// ```
+    pub fn register_hashable_component<C: Component + std::hash::Hash>(
+        &mut self,
+    ) -> Option<crate::prelude::ComponentId> {
+        let comp_id = self.components.register_hashable_component::<C>();
+        if self.warnings.is_enabled() {
+            self.check_component_registrations();
+        }
+        comp_id
+    }
+
+    /// Register a [`Component`] that can be archived, see
+    /// [`ComponentFactory::register_archivable_component`](crate::prelude::ComponentFactory::register_archivable_component).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Debug, PartialEq)]
+    /// struct Health(u32);
+    ///
+    /// impl Archivable for Health {
+    ///     fn archive(&self, bytes: &mut Vec<u8>) {
+    ///         bytes.extend(self.0.to_le_bytes());
+    ///     }
+    ///
+    ///     fn restore(bytes: &[u8]) -> Option<Self> {
+    ///         Some(Health(u32::from_le_bytes(bytes.try_into().ok()?)))
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.register_archivable_component::<Health>();
+    /// let entity = world.spawn(Health(10));
+    /// world.archive(entity).unwrap();
+    /// assert_eq!(world.get_component::<Health>(entity), None);
+    /// world.restore(entity).unwrap();
+    /// assert_eq!(world.get_component::<Health>(entity), Some(&Health(10)));
+    /// ```
+    pub fn register_archivable_component<C: Component + archive::Archivable>(
+        &mut self,
+    ) -> Option<crate::prelude::ComponentId> {
+        let comp_id = self.components.register_archivable_component::<C>();
+        if self.warnings.is_enabled() {
+            self.check_component_registrations();
+        }
+        comp_id
+    }
+
+    /// Register a [`Component`] that can be archived by copying its bytes, see
+    /// [`ComponentFactory::register_pod_component`](crate::prelude::ComponentFactory::register_pod_component).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    /// struct Position(i32, i32);
+    ///
+    /// let mut world = World::default();
+    /// // SAFETY: `Position` has no padding, and no pointers.
+    /// unsafe { world.register_pod_component::<Position>() };
+    /// let entity = world.spawn(Position(1, 2));
+    /// world.archive(entity).unwrap();
+    /// world.restore(entity).unwrap();
+    /// assert_eq!(world.get_component::<Position>(entity), Some(&Position(1, 2)));
+    /// ```
+    ///
+    /// # Safety
+    /// See [`ComponentFactory::register_pod_component`](crate::prelude::ComponentFactory::register_pod_component).
+    pub unsafe fn register_pod_component<C: Component + Copy>(
+        &mut self,
+    ) -> Option<crate::prelude::ComponentId> {
+        let comp_id = self.components.register_pod_component::<C>();
+        if self.warnings.is_enabled() {
+            self.check_component_registrations();
+        }
+        comp_id
+    }
+
+    /// Register a [`Component`] that can be cloned, see
+    /// [`ComponentFactory::register_cloneable_component`](crate::prelude::ComponentFactory::register_cloneable_component).
+    /// The storages that already store the component can be cloned from now on (see [`World::clone_cow`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Debug, PartialEq)]
+    /// struct Name(String);
+    ///
+    /// let mut world = World::default();
+    /// world.register_cloneable_component::<Name>();
+    /// let entity = world.spawn(Name("original".to_string()));
+    /// let mut copy = world.clone_cow().unwrap();
+    /// copy.get_component_mut::<Name>(entity).unwrap().0.push_str(" (edited)");
+    /// assert_eq!(world.get_component::<Name>(entity).unwrap().0, "original");
+    /// assert_eq!(copy.get_component::<Name>(entity).unwrap().0, "original (edited)");
+    /// ```
+    pub fn register_cloneable_component<C: Component + Clone>(
+        &mut self,
+    ) -> Option<crate::prelude::ComponentId> {
+        let comp_id = self.components.register_cloneable_component::<C>()?;
+        self.storages
+            .arch_storages
+            .iter_storages_with_matching_archetype_mut(comp_id.prime_key())
+            .for_each(|storage| storage.refresh_clone_fns(&self.components));
+        if self.warnings.is_enabled() {
+            self.check_component_registrations();
+        }
+        Some(comp_id)
+    }
+
+    /// Register a [`Component`] that can be mutated through a shared reference, see
+    /// [`ComponentFactory::register_interior_mutable_component`](crate::prelude::ComponentFactory::register_interior_mutable_component).
+    /// Copies of the world (see [`World::clone_cow`]) don't share the storages of the component from now on.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// #[derive(Component)]
+    /// struct Hits(AtomicU32);
+    ///
+    /// impl Clone for Hits {
+    ///     fn clone(&self) -> Self {
+    ///         Hits(AtomicU32::new(self.0.load(Ordering::Relaxed)))
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.register_cloneable_component::<Hits>();
+    /// world.register_interior_mutable_component::<Hits>();
+    /// let entity = world.spawn(Hits(AtomicU32::new(0)));
+    /// let copy = world.clone_cow().unwrap();
+    /// copy.get_component::<Hits>(entity).unwrap().0.fetch_add(1, Ordering::Relaxed);
+    /// assert_eq!(world.get_component::<Hits>(entity).unwrap().0.load(Ordering::Relaxed), 0);
+    /// ```
+    pub fn register_interior_mutable_component<C: Component>(
+        &mut self,
+    ) -> Option<crate::prelude::ComponentId> {
+        let comp_id = self.components.register_interior_mutable_component::<C>()?;
+        if self.warnings.is_enabled() {
+            self.check_component_registrations();
+        }
+        Some(comp_id)
+    }
+
+    /// Re-associate the registered components with their [`TypeId`](std::any::TypeId)s, after the code that defines
+    /// them was reloaded. See [`ComponentFactory::rebind_types`](crate::prelude::ComponentFactory::rebind_types).
+    ///
+    /// ```
+    /// use std::any::{type_name, TypeId};
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Debug, PartialEq)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// // After reloading the code that defines `Health`, its components are found by their names again.
+    /// world.rebind_components(|info| {
+    ///     (info.name() == type_name::<Health>()).then(TypeId::of::<Health>)
+    /// });
+    /// assert_eq!(world.get_component::<Health>(entity), Some(&Health(10)));
+    /// ```
+    pub fn rebind_components(
+        &mut self,
+        resolver: impl Fn(&crate::prelude::DataInfo) -> Option<std::any::TypeId>,
+    ) {
+        self.components.rebind_types(resolver)
+    }
+
+    /// Iterate over every instance of a [`Component`] in the [`World`], alongside the [`EntityId`] of its entity.
+    /// This is equivalent to `world.query::<(EntityId, &C)>()`, but it doesn't require the query machinery,
+    /// so it can be used from generic code with only a `C: Component` bound.
+    /// If the component isn't registered, the iterator is empty.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Armor(u32);
+    ///
+    /// /// Generic code only needs `C: Component`.
+    /// fn total<C: Component>(world: &World, value: impl Fn(&C) -> u32) -> u32 {
+    ///     world.iter_component::<C>().map(|(_, c)| value(c)).sum()
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(10));
+    /// world.spawn((Health(20), Armor(5)));
+    /// assert_eq!(total::<Health>(&world, |health| health.0), 30);
+    /// assert_eq!(total::<Armor>(&world, |armor| armor.0), 5);
+    /// ```
+    #[track_caller]
+    pub fn iter_component<C: Component>(&self) -> impl Iterator<Item = (EntityId, &C)> + '_ {
+        self.access
+            .record_component::<C>(&self.components, AccessKind::Read, Location::caller());
+        self.components
+            .get_component_id::<C>()
+            .into_iter()
+            .flat_map(move |comp_id| {
+                self.storages
+                    .arch_storages
+                    .iter_storages_with_matching_archetype(comp_id.prime_key())
+                    .flat_map(move |storage| {
+                        // SAFETY: The column is fetched using `C`'s component id, and the storage contains `C`
+                        // because its archetype matched.
+                        let column =
+                            unsafe { storage.iter_column::<C>(comp_id).unwrap_unchecked() };
+                        storage.entities().iter().copied().zip(column)
+                    })
+            })
+    }
+
+    /// Iterate mutably over every instance of a [`Component`] in the [`World`], alongside the [`EntityId`] of its entity.
+    /// This is equivalent to `world.query::<(EntityId, &mut C)>()`. See [`Self::iter_component`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// world.spawn(Health(20));
+    /// for (owner, health) in world.iter_component_mut::<Health>() {
+    ///     if owner == entity {
+    ///         health.0 = 0;
+    ///     }
+    /// }
+    /// assert_eq!(world.get_component::<Health>(entity).unwrap().0, 0);
+    /// ```
+    #[track_caller]
+    pub fn iter_component_mut<C: Component>(
+        &mut self,
+    ) -> impl Iterator<Item = (EntityId, &mut C)> + '_ {
+        self.iter_component_columns::<C>(Location::caller())
+            .flat_map(|(entities, column)| entities.iter().copied().zip(column))
+    }
+
+    /// Iterate over every [`ArchStorage`](storage::arch_storage::ArchStorage) that stores a [`Component`],
+    /// yielding its [`ArchStorageId`](storage::storages::ArchStorageId), the entities stored in it, and
+    /// a mutable slice of the component's values. Both slices are indexed by the entities' storage index,
+    /// so `entities[i]` is the owner of `column[i]`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy)]
+    /// struct Velocity(f32);
+    /// #[derive(Component)]
+    /// struct Player;
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Velocity(1.0));
+    /// world.spawn(Velocity(2.0));
+    /// world.spawn((Velocity(3.0), Player));
+    /// // One contiguous slice per storage, for bulk (or SIMD) processing.
+    /// let mut groups = 0;
+    /// for (_, entities, velocities) in world.iter_component_grouped::<Velocity>() {
+    ///     assert_eq!(entities.len(), velocities.len());
+    ///     velocities.iter_mut().for_each(|velocity| velocity.0 *= 2.0);
+    ///     groups += 1;
+    /// }
+    /// assert_eq!(groups, 2);
+    /// assert_eq!(world.iter_component::<Velocity>().map(|(_, v)| v.0).sum::<f32>(), 12.0);
+    /// ```
+    ///
+    /// # Panics
+    /// If `C` is boxed (see [`Component::BOXED`]), because its values aren't stored contiguously.
+    #[track_caller]
+    pub fn iter_component_grouped<C: Component>(
+        &mut self,
+    ) -> impl Iterator<Item = (storage::storages::ArchStorageId, &[EntityId], &mut [C])> + '_ {
+        self.access
+            .record_component::<C>(&self.components, AccessKind::Write, Location::caller());
+        let arch_storages = &mut self.storages.arch_storages;
+        self.components
+            .get_component_id::<C>()
+            .map(move |comp_id| {
+                arch_storages
+                    .iter_ids_and_storages_with_matching_archetype_mut(comp_id.prime_key())
+                    .map(move |(sid, storage)| {
+                        // SAFETY: The column is fetched using `C`'s component id, and the storage contains `C`
+                        // because its archetype matched.
+                        let (entities, column) = unsafe {
+                            storage
+                                .entities_and_column_mut::<C>(comp_id)
+                                .unwrap_unchecked()
+                        };
+                        (sid, entities, column)
+                    })
+            })
+            .into_iter()
+            .flatten()
+    }
+
+    /// Like [`Self::iter_component_grouped`], with iterators over the columns instead of slices, so it works for
+    /// boxed components too (see [`Component::BOXED`]).
+    fn iter_component_columns<C: Component>(
+        &mut self,
+        caller: &'static Location<'static>,
+    ) -> impl Iterator<Item = (&[EntityId], ColumnIterMut<'_, C>)> + '_ {
+        self.access
+            .record_component::<C>(&self.components, AccessKind::Write, caller);
+        let arch_storages = &mut self.storages.arch_storages;
+        self.components
+            .get_component_id::<C>()
+            .map(move |comp_id| {
+                arch_storages
+                    .iter_ids_and_storages_with_matching_archetype_mut(comp_id.prime_key())
+                    .map(move |(_, storage)| {
+                        // SAFETY: The column is fetched using `C`'s component id, and the storage contains `C`
+                        // because its archetype matched.
+                        unsafe {
+                            storage
+                                .entities_and_iter_column_mut::<C>(comp_id)
+                                .unwrap_unchecked()
+                        }
+                    })
+            })
+            .into_iter()
+            .flatten()
+    }
+
+    /// Overwrite every instance of a [`Component`] in the [`World`] with a clone of `value`, and return how many
+    /// instances were written. Each column is written in bulk instead of fetching each entity: `Copy` components are
+    /// filled with plain copies, and other components are cloned into each slot, dropping the value they replace.
+    /// If cloning panics, every slot still holds either its old value or a new one, so nothing is dropped twice.
+    ///
+    /// Changes are tracked per storage: the [`generation`](storage::arch_storage::ArchStorage::generation) of each
+    /// storage that stores `C` is bumped once, not once per entity.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    /// struct Velocity(f32);
+    /// #[derive(Component)]
+    /// struct Frozen;
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Velocity(1.0));
+    /// world.spawn((Velocity(2.0), Frozen));
+    /// assert_eq!(world.write_component_to_all(Velocity(0.0)), 2);
+    /// assert!(world.iter_component::<Velocity>().all(|(_, v)| *v == Velocity(0.0)));
+    /// ```
+    #[track_caller]
+    pub fn write_component_to_all<C: Component + Clone>(&mut self, value: C) -> usize {
+        self.iter_component_columns::<C>(Location::caller())
+            .map(|(_, column)| match column {
+                ColumnIterMut::Inline(column) => {
+                    let column = column.into_slice();
+                    column.fill(value.clone());
+                    column.len()
+                }
+                ColumnIterMut::Boxed(column) => column.map(|slot| **slot = value.clone()).count(),
+            })
+            .sum()
+    }
+
+    /// Call `f` on every instance of a [`Component`] in the [`World`], and return how many instances it was called
+    /// on. Like [`Self::write_component_to_all`], this walks each column directly, and bumps the
+    /// [`generation`](storage::arch_storage::ArchStorage::generation) of each storage once.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Cooldown(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Cooldown(3));
+    /// world.spawn(Cooldown(0));
+    /// let ticked = world.update_component_all::<Cooldown>(|cooldown| {
+    ///     cooldown.0 = cooldown.0.saturating_sub(1)
+    /// });
+    /// assert_eq!(ticked, 2);
+    /// let mut cooldowns: Vec<u32> = world.iter_component::<Cooldown>().map(|(_, c)| c.0).collect();
+    /// cooldowns.sort();
+    /// assert_eq!(cooldowns, [0, 2]);
+    /// ```
+    #[track_caller]
+    pub fn update_component_all<C: Component>(&mut self, mut f: impl FnMut(&mut C)) -> usize {
+        self.iter_component_columns::<C>(Location::caller())
+            .map(|(_, column)| column.map(&mut f).count())
+            .sum()
+    }
+
+    /// Returns how many entities in the [`World`] have this [`Component`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Enemy;
+    /// #[derive(Component)]
+    /// struct Boss;
+    ///
+    /// let mut world = World::default();
+    /// assert_eq!(world.component_count::<Enemy>(), 0);
+    /// world.spawn(Enemy);
+    /// world.spawn(Enemy);
+    /// world.spawn((Enemy, Boss));
+    /// assert_eq!(world.component_count::<Enemy>(), 3);
+    /// assert_eq!(world.component_count::<Boss>(), 1);
+    /// ```
+    pub fn component_count<C: Component>(&self) -> usize {
+        self.components
+            .get_component_id::<C>()
+            .map_or(0, |comp_id| {
+                self.storages
+                    .arch_storages
+                    .iter_storages_with_matching_archetype(comp_id.prime_key())
+                    .map(|storage| storage.len())
+                    .sum()
+            })
+    }
+}
 
 // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
 //                               QUERIES API
 // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
 
 impl World {
-    /// Query the world for components.
-    // TODO: Better docs + examples
-    pub fn query<Q: ArchQuery>(&mut self) -> impl Iterator<Item = Q::Item<'_>> + '_ {
+    /// Query the world for components. If some of the required components aren't registered (no entity with
+    /// them was ever spawned), the iterator is empty. Optional components (`Option<&C>`) that aren't registered are
+    /// `None`, and [`Has`](crate::query::Has) is `false`. An empty query like that emits an
+    /// [`EcsWarning::UnregisteredComponentQueried`] (see [`World::set_warning_level`]).
+    ///
+    /// # Panics
+    /// If a component is accessed more than once. To write components based on the same components of other
+    /// entities, see [`World::query_two_pass`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    /// #[derive(Component)]
+    /// struct Velocity(f32);
+    ///
+    /// let mut world = World::default();
+    /// let moving = world.spawn((Position(0.0), Velocity(2.0)));
+    /// let still = world.spawn(Position(5.0));
+    /// // A query matches every entity that has its components, whatever else it has (`still` isn't matched, it has no
+    /// // `Velocity`).
+    /// for (position, velocity) in world.query::<(&mut Position, &Velocity)>() {
+    ///     position.0 += velocity.0;
+    /// }
+    /// assert_eq!(world.get_component::<Position>(moving).unwrap().0, 2.0);
+    /// assert_eq!(world.get_component::<Position>(still).unwrap().0, 5.0);
+    /// // Optional components don't narrow the query, and `EntityId` fetches the entity.
+    /// let mut velocities: Vec<(EntityId, Option<f32>)> = world
+    ///     .query::<(EntityId, Option<&Velocity>)>()
+    ///     .map(|(entity, velocity)| (entity, velocity.map(|v| v.0)))
+    ///     .collect();
+    /// velocities.sort_by_key(|(entity, _)| entity.id());
+    /// assert_eq!(velocities, [(moving, Some(2.0)), (still, None)]);
+    /// ```
+    #[track_caller]
+    pub fn query<Q: ArchQuery>(&mut self) -> QueryIter<'_, Q> {
+        check_duplicate_accesses::<Q>("query");
+        self.access
+            .record_query::<Q>(&self.components, Location::caller());
+        let resolvable = Q::is_resolvable(&self.components);
+        if !resolvable {
+            self.warn_unregistered_query::<Q>();
+        }
+        let tags = self.storages.tag_storage.entity_tags(&self.entities);
+        let arch_storages = &mut self.storages.arch_storages;
         // SAFETY: The query is safe to use, because the pointer to the storages came from a &mut.
-        unsafe { Q::iter_query_matches(&mut self.storages.arch_storages, &self.components) }
+        unsafe {
+            if resolvable {
+                Q::iter_query_matches(arch_storages, &self.components, tags)
+            } else {
+                QueryIter::empty(arch_storages, &self.components, tags, false)
+            }
+        }
+        .with_groups(&self.groups)
     }
 
     /// Query the world for components, with a filter.
-    // TODO: Better docs + examples
-    pub fn query_filtered<Q: ArchQuery, F: ArchFilter>(
-        &mut self,
-    ) -> impl Iterator<Item = Q::Item<'_>> + '_ {
+    ///
+    /// # Panics
+    /// Like [`World::query`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Dead;
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(10));
+    /// world.spawn((Health(0), Dead));
+    /// // Filters narrow the query without fetching anything.
+    /// for health in world.query_filtered::<&mut Health, Without<Dead>>() {
+    ///     health.0 += 1;
+    /// }
+    /// let alive: Vec<u32> = world
+    ///     .query_filtered::<&Health, Without<Dead>>()
+    ///     .map(|health| health.0)
+    ///     .collect();
+    /// assert_eq!(alive, [11]);
+    /// assert_eq!(world.query_filtered::<&Health, With<Dead>>().count(), 1);
+    /// ```
+    #[track_caller]
+    pub fn query_filtered<Q: ArchQuery, F: ArchFilter>(&mut self) -> QueryIter<'_, Q, F> {
+        check_duplicate_accesses::<Q>("query_filtered");
+        self.access
+            .record_filtered_query::<Q, F>(&self.components, Location::caller());
+        let resolvable = Q::is_resolvable(&self.components);
+        if !resolvable {
+            self.warn_unregistered_query::<Q>();
+        }
+        let tags = self.storages.tag_storage.entity_tags(&self.entities);
+        let arch_storages = &mut self.storages.arch_storages;
         // SAFETY: The query is safe to use, because the pointer to the storages came from a &mut.
         unsafe {
-            Q::iter_filtered_query_matches::<F>(&mut self.storages.arch_storages, &self.components)
+            if resolvable {
+                Q::iter_filtered_query_matches::<F>(arch_storages, &self.components, tags)
+            } else {
+                QueryIter::empty(arch_storages, &self.components, tags, true)
+            }
+        }
+        .with_groups(&self.groups)
+    }
+
+    /// Query the world for components that are only read, through a shared reference. Unlike [`World::query`],
+    /// the world can be queried like this from several threads at once (see [`World::read_scope`]).
+    /// If some of the components aren't registered, the iterator is empty. Components with interior mutability can
+    /// be mutated through the query, which isn't tracked, so they must be registered with
+    /// [`World::register_interior_mutable_component`] (see [`WorldReadScope`](read_scope::WorldReadScope)).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(10));
+    /// world.spawn(Health(20));
+    /// let world = &world;
+    /// // Only `&World` is needed, so several readers can query at once.
+    /// let (a, b) = std::thread::scope(|scope| {
+    ///     let a = scope.spawn(|| world.query_shared::<&Health>().map(|h| h.0).sum::<u32>());
+    ///     let b = scope.spawn(|| world.query_shared::<&Health>().count());
+    ///     (a.join().unwrap(), b.join().unwrap())
+    /// });
+    /// assert_eq!((a, b), (30, 2));
+    /// ```
+    #[track_caller]
+    pub fn query_shared<Q: ReadOnlyArchQuery>(&self) -> impl Iterator<Item = Q::Item<'_>> + '_ {
+        self.access
+            .record_query::<Q>(&self.components, Location::caller());
+        let (components, tags) = (&self.components, self.entity_tags());
+        self.resolve_query_key::<Q>()
+            .into_iter()
+            .flat_map(move |pkey| {
+                self.storages
+                    .arch_storages
+                    .iter_storages_with_matching_archetype(pkey)
+                    .flat_map(move |storage| {
+                        let ctx = FetchContext::new(tags).for_query::<Q>(storage, components);
+                        storage.iter_indices().map(move |index| {
+                            // SAFETY: The index is in bounds, and `Q` only reads, so nothing is mutated through
+                            // the pointer.
+                            unsafe {
+                                Q::fetch(
+                                    storage as *const ArchEntityStorage as *mut ArchEntityStorage,
+                                    index,
+                                    components,
+                                    ctx,
+                                )
+                            }
+                        })
+                    })
+            })
+    }
+
+    /// The [`PrimeArchKey`] of a query, or `None` if some of its components aren't registered.
+    #[track_caller]
+    pub(crate) fn resolve_query_key<Q: ArchQuery>(&self) -> Option<PrimeArchKey> {
+        if !Q::is_resolvable(&self.components) {
+            return None;
         }
+        Some(Q::resolve_prime_arch_key(&self.components))
+    }
+
+    /// Query a single entity for components. Returns `None` if the entity was despawned, or if it doesn't
+    /// match the query.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Armor(u32);
+    ///
+    /// let mut world = World::default();
+    /// let knight = world.spawn((Health(10), Armor(5)));
+    /// let peasant = world.spawn(Health(5));
+    /// let (health, armor) = world.query_one::<(&mut Health, &Armor)>(knight).unwrap();
+    /// health.0 += armor.0;
+    /// assert_eq!(world.get_component::<Health>(knight).unwrap().0, 15);
+    /// // The peasant doesn't match the query.
+    /// assert!(world.query_one::<(&mut Health, &Armor)>(peasant).is_none());
+    /// ```
+    #[track_caller]
+    pub fn query_one<Q: ArchQuery>(&mut self, entity: EntityId) -> Option<Q::Item<'_>> {
+        self.access
+            .record_query::<Q>(&self.components, Location::caller());
+        // SAFETY: A single entity is fetched.
+        unsafe { self.query_many::<Q, _>([entity]) }.next()
+    }
+
+    /// Query each of the entities for components, skipping the entities that were despawned or that don't match
+    /// the query.
+    ///
+    /// # Safety
+    /// The caller must ensure that the entities are distinct (when `Q` accesses components mutably).
+    pub(crate) unsafe fn query_many<Q: ArchQuery, I>(
+        &mut self,
+        entities: I,
+    ) -> impl Iterator<Item = Q::Item<'_>> + '_
+    where
+        I: IntoIterator<Item = EntityId>,
+        I::IntoIter: 'static,
+    {
+        let pkey = self.resolve_query_key::<Q>();
+        let arch_storages: *mut ArchStorages = &mut self.storages.arch_storages;
+        let (entity_factory, components) = (&self.entities, &self.components);
+        let tags = self.storages.tag_storage.entity_tags(entity_factory);
+        entities.into_iter().filter_map(move |entity| {
+            let entity_meta = entity_factory.get_entity_meta(entity)?;
+            // SAFETY: The pointer came from a &mut that lives as long as the iterator, and the caller ensures that
+            // every entity (and so every component) is fetched at most once.
+            unsafe {
+                let storage: *mut ArchEntityStorage =
+                    (*arch_storages).get_storage_mut(entity_meta.archetype_storage_id)?;
+                (*storage).prime_key().is_sub_archetype(pkey?).then(|| {
+                    if Q::IS_MUTABLE {
+                        // Unshare before the first fetch from this storage, so the items that were already
+                        // fetched from it never point into shared components.
+                        (*storage).make_unique();
+                    }
+                    let ctx = FetchContext::new(tags).for_query::<Q>(&*storage, components);
+                    Q::fetch(
+                        storage,
+                        entity_meta.archetype_storage_index,
+                        components,
+                        ctx,
+                    )
+                })
+            }
+        })
     }
 }
 
@@ -86,94 +1550,736 @@ impl World {
 // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
 
 impl World {
+    /// The amount of entities that are alive in the [`World`] (including the archived entities, see
+    /// [`World::archive`], and the tombstones, see [`World::despawn_retaining`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let a = world.spawn(());
+    /// world.spawn(());
+    /// assert_eq!(world.entities(), 2);
+    /// world.despawn(a);
+    /// assert_eq!(world.entities(), 1);
+    /// ```
+    pub fn entities(&self) -> u32 {
+        self.entities.entities()
+    }
+
+    /// Returns `true` if the entity is alive (including if it's archived, see [`World::archive`], or a tombstone, see
+    /// [`World::despawn_retaining`]). Returns `false` for a despawned entity, and for an id that was never handed out
+    /// by this [`World`], without panicking.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(());
+    /// assert!(world.is_alive(entity));
+    /// world.despawn(entity);
+    /// assert!(!world.is_alive(entity));
+    /// // The id is reused with a new generation, so the stale id stays dead.
+    /// let reused = world.spawn(());
+    /// assert_eq!(reused.id(), entity.id());
+    /// assert!(!world.is_alive(entity));
+    /// ```
+    pub fn is_alive(&self, entity: EntityId) -> bool {
+        self.entities.verify_generation(entity)
+    }
+
+    /// The storage that an entity is stored in, and its row in the storage (see [`World::storages`]), or `None` if the
+    /// entity isn't alive, or is archived (see [`World::archive`]). The location changes when other entities are
+    /// despawned from the storage, so it shouldn't be kept.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Debug, PartialEq)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let first = world.spawn(Health(10));
+    /// let second = world.spawn(Health(20));
+    /// let (sid, row) = world.entity_location(second).unwrap();
+    /// let storage = world.storages().arch_storages().get_storage(sid).unwrap();
+    /// assert_eq!(storage.get_entity_at(row), Some(second));
+    /// // Despawning the first entity moves the last row into its place.
+    /// world.despawn(first);
+    /// let (_, moved) = world.entity_location(second).unwrap();
+    /// assert_ne!(moved, row);
+    /// assert_eq!(world.entity_location(first), None);
+    /// ```
+    pub fn entity_location(&self, entity: EntityId) -> Option<(ArchStorageId, ArchStorageIndex)> {
+        let entity_meta = self.entities.get_entity_meta(entity)?;
+        Some((
+            entity_meta.archetype_storage_id,
+            entity_meta.archetype_storage_index,
+        ))
+    }
+
+    /// Iterate over the [`EntityId`]s of all the entities in the [`World`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let a = world.spawn(());
+    /// let b = world.spawn(Health(10));
+    /// let mut entities: Vec<EntityId> = world.iter_entities().collect();
+    /// entities.sort_by_key(|entity| entity.id());
+    /// assert_eq!(entities, [a, b]);
+    /// ```
+    pub fn iter_entities(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.storages
+            .arch_storages
+            .iter_storages_with_matching_archetype(PrimeArchKey::IDENTITY)
+            .flat_map(|storage| storage.entities().iter().copied())
+    }
+
     /// Spawn a new entity with a bundle of components.
+    ///
+    /// This is panic-safe: if storing the bundle panics midway (for example, in a custom [`Bundle`] implementation
+    /// that constructs its components lazily), the components that were already stored are dropped, the entity's
+    /// id is freed, and the [`World`] is left as if the entity was never spawned.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Debug, PartialEq)]
+    /// struct Position(f32, f32);
+    /// #[derive(Component, Debug, PartialEq)]
+    /// struct Name(&'static str);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn((Name("player"), Position(0.0, 0.0)));
+    /// assert_eq!(world.get_component::<Name>(entity), Some(&Name("player")));
+    /// // Bundles nest, and a single component is a bundle too.
+    /// let other = world.spawn(((Name("tree"),), Position(1.0, 2.0)));
+    /// assert_eq!(world.get_component::<Position>(other), Some(&Position(1.0, 2.0)));
+    /// ```
+    ///
+    /// # Panics
+    /// If the entity would break a component rule (see [`World::require_component`]). Use [`World::try_spawn`]
+    /// to handle the error instead. If the storage of the entity is pinned (see [`World::pin_storage`]).
+    #[track_caller]
     pub fn spawn<B: Bundle + Archetype>(&mut self, bundle: B) -> EntityId {
+        match self.try_spawn(bundle) {
+            Ok(entity) => entity,
+            Err(error) => panics::fail("spawn", error, &[]),
+        }
+    }
+
+    /// Spawn a new entity with a bundle of components, like [`World::spawn`], or return an error if the entity would
+    /// break a component rule (see [`World::require_component`] and [`World::conflict_components`]), or exceed a
+    /// quota (see [`World::set_entity_limit`] and [`World::set_archetype_limit`]).
+    /// Missing components that are required with a default value are inserted
+    /// (see [`World::require_component_with_default`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Enemy;
+    ///
+    /// let mut world = World::default();
+    /// world.set_entity_limit(1);
+    /// assert!(world.try_spawn(Enemy).is_ok());
+    /// assert!(matches!(
+    ///     world.try_spawn(Enemy),
+    ///     Err(SpawnError::QuotaExceeded { limit: 1, .. })
+    /// ));
+    /// assert_eq!(world.entities(), 1);
+    /// ```
+    #[track_caller]
+    pub fn try_spawn<B: Bundle + Archetype>(&mut self, bundle: B) -> Result<EntityId, SpawnError> {
+        if self.components.rules.is_empty() && self.quotas.is_empty() {
+            return Ok(self.spawn_with_exact_archetype(bundle));
+        }
+        let mut prime_key = PrimeArchKey::IDENTITY;
+        if !B::merge_prime_key_or_register(&mut self.components, &mut prime_key) {
+            panics::fail_duplicate_components::<B>("store_archetype");
+        }
+        let entity = self.entities.next_entity_id();
+        let completion = self.check_component_rules(entity, prime_key, |components| {
+            B::arch_info(components).expect("The components were just registered")
+        })?;
+        self.check_quota(
+            completion
+                .as_ref()
+                .map_or(prime_key, |completion| completion.arch_info.prime_key()),
+            1,
+        )?;
+        Ok(match completion {
+            None => self.spawn_with_exact_archetype(bundle),
+            // SAFETY: The completed archetype is made up of the components of `B` and of the inserted defaults.
+            Some(completion) => unsafe {
+                self.spawn_batch_with_exact_info(
+                    &completion.arch_info,
+                    std::iter::once(completion.complete(bundle)),
+                )
+            }
+            .expect("The components of a completed archetype are registered")[0],
+        })
+    }
+
+    #[track_caller]
+    fn spawn_with_exact_archetype<B: Bundle + Archetype>(&mut self, bundle: B) -> EntityId {
         let (sid, storage) = self
             .storages
             .arch_storages
             .get_mut_or_create_storage_with_exact_archetype::<B>(&mut self.components);
+        if !storage.can_store_while_pinned(1) {
+            panics::fail("spawn", self.storage_pinned(sid), &[]);
+        }
         let index = storage.next_index();
         let entity_id = self.entities.new_entity(EntityMeta {
             archetype_storage_id: sid,
             archetype_storage_index: index,
+            component_mask: storage.component_mask(),
         });
+        let sorted = storage.is_sort_maintained();
+        let entities = &mut self.entities;
+        let on_unwind = OnDrop::new(|| entities.remove_entity(entity_id));
         storage.store_entity(entity_id, bundle, &self.components);
-        self.storages.tag_storage.new_entity();
+        std::mem::forget(on_unwind);
+        self.ordered.stored(entity_id, storage.component_mask());
+        if sorted {
+            self.sort_rows_from(sid, index.0);
+        }
+        self.storages
+            .tag_storage
+            .new_entity(self.entities.dense_index(entity_id));
+        if self.warnings.is_enabled() {
+            self.check_spawn(sid);
+        }
+        #[cfg(feature = "chaos")]
+        self.chaos_after_spawn(sid);
         entity_id
     }
 
+    /// Spawn a batch of entities with the same bundle type, like calling [`World::spawn`] for each of them, but
+    /// resolving their storage once and reserving it for the whole batch. Returns the ids of the spawned entities,
+    /// in order. To spawn entities of different archetypes in batches, see [`World::spawn_batch_mixed`].
+    ///
+    /// The bundles are streamed into the storage, which is reserved by the lower bound of the iterator's
+    /// [`size_hint`](Iterator::size_hint), see [`World::spawn_batch_with_info`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Debug, PartialEq)]
+    /// struct Particle(u32);
+    ///
+    /// let mut world = World::default();
+    /// let particles = world.spawn_batch((0..100).map(Particle));
+    /// assert_eq!(particles.len(), 100);
+    /// assert_eq!(world.get_component::<Particle>(particles[42]), Some(&Particle(42)));
+    /// ```
+    ///
+    /// # Panics
+    /// Like [`World::spawn_batch_with_info`], or if `B` has the same component more than once.
+    #[track_caller]
+    pub fn spawn_batch<B: Bundle + Archetype>(
+        &mut self,
+        bundles: impl IntoIterator<Item = B>,
+    ) -> Vec<EntityId> {
+        let arch_info = mixed::arch_info_or_register::<B>(&mut self.components, "spawn_batch");
+        // SAFETY: Every bundle of type `B` stores exactly the components of `B`, without duplicates.
+        unsafe { self.spawn_batch_with_info(&arch_info, bundles) }
+            .expect("The components were just registered")
+    }
+
+    /// Spawn a batch of entities whose archetype is only known at runtime (as an [`ArchetypeInfo`]), for example
+    /// with a custom [`Bundle`] that decides which components it stores when it's constructed.
+    /// Returns the ids of the spawned entities, in order, or `None` if some of the components aren't registered.
+    ///
+    /// ```
+    /// use worlds_ecs::{archetype::Archetype, prelude::*};
+    ///
+    /// #[derive(Component, Debug, PartialEq)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Enemy;
+    ///
+    /// let mut world = World::default();
+    /// world.register_component::<Health>();
+    /// world.register_component::<Enemy>();
+    /// let arch_info = <(Health, Enemy)>::arch_info(world.components()).unwrap();
+    /// // SAFETY: Every bundle stores exactly `Health` and `Enemy`.
+    /// let enemies = unsafe {
+    ///     world.spawn_batch_with_info(&arch_info, (0..3).map(|i| (Health(i), Enemy)))
+    /// }
+    /// .unwrap();
+    /// assert_eq!(world.get_component::<Health>(enemies[2]), Some(&Health(2)));
+    /// ```
+    ///
+    /// The storage is reserved, and the quotas (see [`World::set_entity_limit`]) and the room in a pinned storage are
+    /// checked, for the bundles that the lower bound of the iterator's [`size_hint`](Iterator::size_hint) promises,
+    /// before they're stored. Bundles beyond it are checked as they come.
+    ///
+    /// # Panics
+    /// If the entities would break a component rule (see [`World::require_component`]), or don't fit in a quota or
+    /// in a pinned storage. A batch whose size is known up front (like an [`ExactSizeIterator`]) is spawned
+    /// all-or-nothing, so nothing is spawned when it panics. Otherwise, the entities that were stored before the
+    /// panic stay spawned.
+    ///
+    /// # Safety
+    /// The caller must ensure that every bundle stores exactly the components in `arch_info`, without duplicates.
+    #[track_caller]
+    pub unsafe fn spawn_batch_with_info<B: Bundle>(
+        &mut self,
+        arch_info: &ArchetypeInfo,
+        bundles: impl IntoIterator<Item = B>,
+    ) -> Option<Vec<EntityId>> {
+        let entity = self.entities.next_entity_id();
+        let completion = match self
+            .check_component_rules(entity, arch_info.prime_key(), |_| arch_info.clone())
+        {
+            Ok(completion) => completion,
+            Err(error) => panics::fail("spawn_batch", error, &[]),
+        };
+        match completion {
+            None => self.spawn_batch_with_exact_info(arch_info, bundles),
+            Some(completion) => self.spawn_batch_with_exact_info(
+                &completion.arch_info,
+                bundles
+                    .into_iter()
+                    .map(|bundle| completion.complete(bundle)),
+            ),
+        }
+    }
+
+    /// Spawn a batch of entities like [`World::spawn_batch_with_info`], without checking the component rules.
+    ///
+    /// # Safety
+    /// See [`World::spawn_batch_with_info`].
+    #[track_caller]
+    unsafe fn spawn_batch_with_exact_info<B: Bundle>(
+        &mut self,
+        arch_info: &ArchetypeInfo,
+        bundles: impl IntoIterator<Item = B>,
+    ) -> Option<Vec<EntityId>> {
+        let (sid, storage) = self
+            .storages
+            .arch_storages
+            .get_mut_or_create_storage_with_info(arch_info, &self.components)?;
+        let component_mask = storage.component_mask();
+        let (first, sorted) = (storage.next_index(), storage.is_sort_maintained());
+        let mut bundles = bundles.into_iter();
+        let mut entity_ids = Vec::with_capacity(bundles.size_hint().0);
+        // Each round checks and reserves the bundles that the size hint promises, and stores them in a tight loop.
+        // An iterator whose size is known is spawned in a single round.
+        while let Some(bundle) = bundles.next() {
+            let known = bundles.size_hint().0.saturating_add(1);
+            if let Err(error) = self.check_quota(arch_info.prime_key(), known) {
+                panics::fail("spawn_batch", error, &[]);
+            }
+            let storage = self
+                .storages
+                .arch_storages
+                .get_storage_mut(sid)
+                .expect("The storage was just resolved");
+            if !storage.can_store_while_pinned(known) {
+                panics::fail("spawn_batch", self.storage_pinned(sid), &[]);
+            }
+            {
+                let _scope = self
+                    .components
+                    .storage_alloc()
+                    .scope(AllocReason::SpawnBatch);
+                storage.reserve(known);
+            }
+            entity_ids.reserve(known);
+            for bundle in std::iter::once(bundle).chain(bundles.by_ref().take(known - 1)) {
+                let entity_id = self.entities.new_entity(EntityMeta {
+                    archetype_storage_id: sid,
+                    archetype_storage_index: storage.next_index(),
+                    component_mask,
+                });
+                let entities = &mut self.entities;
+                let on_unwind = OnDrop::new(|| entities.remove_entity(entity_id));
+                storage.store_entity_unchecked(entity_id, bundle, &self.components);
+                std::mem::forget(on_unwind);
+                self.ordered.stored(entity_id, component_mask);
+                self.storages
+                    .tag_storage
+                    .new_entity(self.entities.dense_index(entity_id));
+                entity_ids.push(entity_id);
+            }
+        }
+        if sorted {
+            self.sort_rows_from(sid, first.0);
+        }
+        #[cfg(feature = "chaos")]
+        self.chaos_after_spawn(sid);
+        Some(entity_ids)
+    }
+
     /// Get a reference to a [`Component`] of an entity.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Debug, PartialEq)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Armor(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// assert_eq!(world.get_component::<Health>(entity), Some(&Health(10)));
+    /// assert!(world.get_component::<Armor>(entity).is_none());
+    /// world.despawn(entity);
+    /// assert!(world.get_component::<Health>(entity).is_none());
+    /// ```
+    #[track_caller]
     pub fn get_component<C: Component>(&self, entity: EntityId) -> Option<&C> {
+        self.access
+            .record_component::<C>(&self.components, AccessKind::Read, Location::caller());
         let entity_meta = self.entities.get_entity_meta(entity)?;
         self.storages
             .arch_storages
             .get_storage(entity_meta.archetype_storage_id)
-            .map(|storage| {
+            .and_then(|storage| {
                 self.components
                     .get_component_id::<C>()
-                    .map(|comp_id| {
+                    .and_then(|comp_id| {
                         storage.get_component(entity_meta.archetype_storage_index, comp_id)
                     })
-                    .flatten()
                     // SAFETY: This type-erased pointer was fetched using this component id.
-                    .map(|raw_comp| unsafe { raw_comp.deref::<C>() })
+                    .map(|raw_comp| unsafe { deref_component::<C>(raw_comp) })
             })
-            .flatten()
+    }
+
+    /// Returns `true` if the entity has the [`Component`] `C`, or `false` if it doesn't (or if it was despawned).
+    /// This only reads the entity's [`EntityMeta`], without looking at its storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Armor(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// assert!(world.contains_component::<Health>(entity));
+    /// assert!(!world.contains_component::<Armor>(entity));
+    /// ```
+    pub fn contains_component<C: Component>(&self, entity: EntityId) -> bool {
+        self.entities
+            .get_entity_meta(entity)
+            .zip(self.components.get_component_id::<C>())
+            .is_some_and(|(entity_meta, comp_id)| entity_meta.component_mask.contains(comp_id))
+    }
+
+    /// Iterate over the marker components (the zero-sized components) of an entity, in ascending order of their
+    /// [`ComponentId`]s. Nothing is yielded if the entity was despawned, or if it's archived (see [`World::archive`]).
+    /// See [`Markers`](crate::query::Markers) for the same information during a query.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Player;
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn((Health(10), Player));
+    /// let player = world.components().get_component_id::<Player>().unwrap();
+    /// // `Health` has data, so only `Player` is a marker.
+    /// assert_eq!(world.marker_components_of(entity).collect::<Vec<_>>(), [player]);
+    /// ```
+    pub fn marker_components_of(&self, entity: EntityId) -> impl Iterator<Item = ComponentId> + '_ {
+        self.entities
+            .get_entity_meta(entity)
+            .and_then(|entity_meta| {
+                self.storages
+                    .arch_storages
+                    .get_storage(entity_meta.archetype_storage_id)
+            })
+            .into_iter()
+            .flat_map(|storage| storage.markers().iter().copied())
     }
 
     /// Get a mutable reference to a [`Component`] of an entity.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Debug, PartialEq)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// world.get_component_mut::<Health>(entity).unwrap().0 -= 3;
+    /// assert_eq!(world.get_component::<Health>(entity), Some(&Health(7)));
+    /// ```
+    #[track_caller]
     pub fn get_component_mut<C: Component>(&mut self, entity: EntityId) -> Option<&mut C> {
+        self.access
+            .record_component::<C>(&self.components, AccessKind::Write, Location::caller());
         let entity_meta = self.entities.get_entity_meta(entity)?;
         self.storages
             .arch_storages
             .get_storage_mut(entity_meta.archetype_storage_id)
-            .map(|storage| {
+            .and_then(|storage| {
                 self.components
                     .get_component_id::<C>()
-                    .map(|comp_id| {
+                    .and_then(|comp_id| {
                         storage.get_component_mut(entity_meta.archetype_storage_index, comp_id)
                     })
-                    .flatten()
                     // SAFETY: This type-erased pointer was fetched using this component id.
-                    .map(|raw_comp| unsafe { raw_comp.deref_mut::<C>() })
+                    .map(|raw_comp| unsafe { deref_component_mut::<C>(raw_comp) })
             })
-            .flatten()
     }
 
-    /// Despawn an entity from the [`World`].
+    /// Write a [`Component`] of an entity only if `value` differs from its current value, and return whether it was
+    /// written. Returns `None` if the entity isn't alive, or doesn't have the component.
+    ///
+    /// Unlike a write through [`World::get_component_mut`], an equal value doesn't change the
+    /// [`generation`](storage::arch_storage::ArchStorage::generation) of the entity's storage, so it isn't seen as a
+    /// change (by [`Cached`](crate::query::Cached) filters, or by replication that re-serializes the storages that
+    /// changed). The comparison isn't free, so this pays off when equal writes are common, like a position that
+    /// snaps to the same cell, or health that is set to the same number.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, PartialEq)]
+    /// struct Cell(i32, i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Cell(0, 0));
+    /// let (sid, _) = world.entity_location(entity).unwrap();
+    /// let generation = |world: &World| world.storages().arch_storages().get_storage(sid).unwrap().generation();
+    /// let before = generation(&world);
+    /// assert_eq!(world.set_component_if_changed(entity, Cell(0, 0)), Some(false));
+    /// assert_eq!(generation(&world), before);
+    /// assert_eq!(world.set_component_if_changed(entity, Cell(1, 0)), Some(true));
+    /// assert_ne!(generation(&world), before);
+    /// ```
+    #[track_caller]
+    pub fn set_component_if_changed<C: Component + PartialEq>(
+        &mut self,
+        entity: EntityId,
+        value: C,
+    ) -> Option<bool> {
+        self.access
+            .record_component::<C>(&self.components, AccessKind::Write, Location::caller());
+        let entity_meta = *self.entities.get_entity_meta(entity)?;
+        let comp_id = self.components.get_component_id::<C>()?;
+        let index = entity_meta.archetype_storage_index;
+        let storage = self
+            .storages
+            .arch_storages
+            .get_storage_mut(entity_meta.archetype_storage_id)?;
+        // SAFETY: This type-erased pointer was fetched using this component id.
+        if unsafe { deref_component::<C>(storage.get_component(index, comp_id)?) } == &value {
+            return Some(false);
+        }
+        // SAFETY: This type-erased pointer was fetched using this component id.
+        *unsafe { deref_component_mut::<C>(storage.get_component_mut(index, comp_id)?) } = value;
+        Some(true)
+    }
+
+    /// Despawn an entity from the [`World`]. An archived entity (see [`World::archive`]) is despawned with its
+    /// archived components, and a tombstone (see [`World::despawn_retaining`]) with its retained components. See
+    /// [`destroy`] for the order in which the entity is cleaned up.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// world.despawn(entity);
+    /// assert!(!world.is_alive(entity));
+    /// assert_eq!(world.component_count::<Health>(), 0);
+    /// ```
+    ///
+    /// # Panics
+    /// If the entity was already despawned, or if its storage is pinned (see [`World::pin_storage`]).
+    #[track_caller]
     pub fn despawn(&mut self, entity: EntityId) {
-        let entity_meta = self
-            .entities
-            .get_entity_meta(entity)
-            .expect("Can't despawn already despawned entity.");
-        if let Some(entity_to_update) = self
+        self.destroy_entity(entity, DespawnReason::Despawn);
+    }
+
+    /// Remove the row of an entity from its storage (dropping its components), and update the metas of the entities
+    /// that moved because of it. The entity's own meta isn't updated.
+    pub(crate) fn detach_from_storage(&mut self, entity_meta: EntityMeta) {
+        let index = entity_meta.archetype_storage_index;
+        let storage = self
             .storages
             .arch_storages
             .get_storage_mut(entity_meta.archetype_storage_id)
-            .unwrap()
-            .swap_remove(entity_meta.archetype_storage_index)
-        {
-            self.entities.set_entity_arch_storage_index(
-                entity_meta.archetype_storage_index,
-                entity_to_update,
+            .unwrap();
+        if storage.is_sort_maintained() {
+            storage.shift_remove(index);
+            for (row, entity_to_update) in storage.entities().iter().enumerate().skip(index.0) {
+                self.entities
+                    .set_entity_arch_storage_index(ArchStorageIndex(row), *entity_to_update);
+            }
+        } else if let Some(entity_to_update) = storage.swap_remove(index) {
+            self.entities
+                .set_entity_arch_storage_index(index, entity_to_update);
+        }
+    }
+
+    /// Despawn every entity that passes the filter `F`, and return how many entities were despawned.
+    /// Storages in which every entity passes the filter (like the storages matching `Has<A>`) are cleared at once,
+    /// and the other storages are despawned from entity-by-entity (like [`Self::despawn`]). Either way, the entities
+    /// are cleaned up in the order of [`destroy`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Bullet;
+    /// #[derive(Component)]
+    /// struct Expired;
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Bullet);
+    /// world.spawn((Bullet, Expired));
+    /// world.spawn((Bullet, Expired));
+    /// assert_eq!(world.despawn_matching::<With<Expired>>(), 2);
+    /// assert_eq!(world.entities(), 1);
+    /// ```
+    #[track_caller]
+    pub fn despawn_matching<F: ArchFilter>(&mut self) -> usize {
+        self.despawn_matching_for::<F>(DespawnReason::DespawnMatching)
+    }
+
+    #[track_caller]
+    fn despawn_matching_for<F: ArchFilter>(&mut self, reason: DespawnReason) -> usize {
+        let mut despawned = 0;
+        let mut sid = ArchStorageId(0);
+        while let Some(storage) = self.storages.arch_storages.get_storage_mut(sid) {
+            let latency = self.components.storage_alloc().latency().start();
+            match F::filter_storage(storage, &self.components) {
+                StorageFilterResult::NoneMatch => {}
+                StorageFilterResult::AllMatch => despawned += self.destroy_cleared(sid, reason),
+                StorageFilterResult::PerEntity => {
+                    let ctx =
+                        FetchContext::new(self.storages.tag_storage.entity_tags(&self.entities))
+                            .for_filter::<F>(storage, &self.components);
+                    let entities: Vec<EntityId> = storage
+                        .iter_indices()
+                        // SAFETY: The index came from the storage itself.
+                        .filter(|index| unsafe {
+                            F::filter(storage, *index, &self.components, ctx).collapse()
+                        })
+                        .map(|index| storage.entities()[index.0])
+                        .collect();
+                    for entity in &entities {
+                        self.destroy_entity(*entity, reason);
+                    }
+                    despawned += entities.len();
+                }
+            }
+            self.components
+                .storage_alloc()
+                .latency()
+                .finish(LatencyCategory::BulkDespawn, latency);
+            sid = ArchStorageId(sid.0 + 1);
+        }
+        despawned
+    }
+
+    /// Despawn every entity in the [`World`] (including the archived entities, see [`World::archive`], and the
+    /// tombstones, see [`World::despawn_retaining`]), and return how many entities were despawned.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(10));
+    /// world.spawn(());
+    /// assert_eq!(world.clear(), 2);
+    /// assert_eq!(world.entities(), 0);
+    /// ```
+    #[track_caller]
+    pub fn clear(&mut self) -> usize {
+        let archived: Vec<EntityId> = self.archive.entities().collect();
+        for entity in &archived {
+            self.destroy_entity(*entity, DespawnReason::Clear);
+        }
+        archived.len()
+            + self.clear_tombstones(DespawnReason::Clear)
+            + self.despawn_matching_for::<()>(DespawnReason::Clear)
+    }
+
+    /// Panic if the bookkeeping of the entities is inconsistent: every entity in a storage must be alive, and its
+    /// [`EntityMeta`] must point to its row, and have the components of its storage, and every column must have a
+    /// component for every row. Meant for tests and debugging, see [`World::check_and_quarantine`] to recover from
+    /// an inconsistent storage instead.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entities = world.spawn_batch((0..10).map(Health));
+    /// for entity in entities.iter().step_by(2) {
+    ///     world.despawn(*entity);
+    /// }
+    /// world.assert_invariants();
+    /// ```
+    pub fn assert_invariants(&self) {
+        let mut stored = 0;
+        let mut sid = ArchStorageId(0);
+        while let Some(storage) = self.storages.arch_storages.get_storage(sid) {
+            if let Some(inconsistency) = self.storage_inconsistencies(sid).first() {
+                panic!("The storage {sid:?} is inconsistent: {inconsistency}");
+            }
+            stored += storage.len();
+            sid = ArchStorageId(sid.0 + 1);
+        }
+        for entity in self.archive.entities() {
+            assert!(
+                self.is_archived(entity),
+                "{entity:?} is in the cold store, but it isn't archived"
             );
         }
-        self.storages.tag_storage.untag_all(entity);
-        self.entities.remove_entity(entity);
+        let (archived, tombstones) = (self.archive.len(), self.tombstones.len());
+        assert_eq!(
+            stored + archived + tombstones,
+            self.entities.entities() as usize,
+            "The storages have {stored} entities, {archived} are archived and {tombstones} are tombstones, but {} \
+             are alive",
+            self.entities.entities()
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{entity::EntityId, prelude::*, world::storage::storages::ArchStorageId};
+    use crate::{
+        archetype::key::PrimeArchKey, entity::EntityId, prelude::*, test_utils::*,
+        world::storage::storages::ArchStorageId,
+    };
 
-    #[derive(Component)]
+    #[derive(Component, PartialEq)]
     struct A(usize);
 
-    #[derive(Component)]
+    #[derive(Component, Clone)]
     struct B(Box<[u8]>);
 
-    #[derive(Component)]
+    #[derive(Component, Clone)]
     struct C(String);
 
     #[test]
@@ -236,7 +2342,7 @@ mod tests {
                 .len(),
             3
         );
-        assert_eq!(world.query::<(&A, &C)>().into_iter().count(), 3);
+        assert_eq!(world.query::<(&A, &C)>().count(), 3);
 
         world.despawn(a_cart);
         assert_eq!(world.get_component::<A>(a_alice).unwrap().0, 2);
@@ -254,9 +2360,8 @@ mod tests {
         );
         world
             .query_filtered::<EntityId, Has<(A, C)>>()
-            .into_iter()
             .for_each(|eid| assert_ne!(eid, a_cart));
-        assert_eq!(world.query::<(&A, &C)>().into_iter().count(), 2);
+        assert_eq!(world.query::<(&A, &C)>().count(), 2);
     }
 
     #[test]
@@ -270,7 +2375,1006 @@ mod tests {
             .filter(|i| i % 2 == 0)
             .for_each(|i| world.despawn(entities[i]));
 
-        assert_eq!(world.query::<&A>().into_iter().count(), 500);
+        assert_eq!(world.query::<&A>().count(), 500);
         world.query::<&A>().for_each(|A(i)| assert!(i % 2 == 1));
     }
+
+    #[test]
+    fn test_iter_component() {
+        let mut world = World::default();
+        world.spawn((A(1), C(String::from("Cart"))));
+        world.spawn((A(2), B(Box::new([1, 2]))));
+        world.spawn(A(3));
+        world.spawn(C(String::from("Alice")));
+        world.spawn((A(4), C(String::from("James"))));
+
+        assert_eq!(world.component_count::<A>(), 4);
+        assert_eq!(world.component_count::<B>(), 1);
+        assert_eq!(world.component_count::<C>(), 3);
+
+        let from_iter: Vec<(EntityId, usize)> =
+            world.iter_component::<A>().map(|(e, a)| (e, a.0)).collect();
+        let from_query: Vec<(EntityId, usize)> = world
+            .query::<(EntityId, &A)>()
+            .map(|(e, a)| (e, a.0))
+            .collect();
+        assert_eq!(from_iter, from_query);
+
+        world.iter_component_mut::<A>().for_each(|(_, a)| a.0 *= 10);
+        let from_iter: Vec<(EntityId, usize)> =
+            world.iter_component::<A>().map(|(e, a)| (e, a.0)).collect();
+        let from_query: Vec<(EntityId, usize)> = world
+            .query::<(EntityId, &mut A)>()
+            .map(|(e, a)| (e, a.0))
+            .collect();
+        assert_eq!(from_iter, from_query);
+        assert!(from_iter.iter().all(|(_, a)| a % 10 == 0));
+    }
+
+    #[test]
+    fn test_iter_component_unregistered() {
+        let mut world = World::default();
+        world.spawn(A(1));
+        assert_eq!(world.iter_component::<B>().count(), 0);
+        assert_eq!(world.iter_component_mut::<B>().count(), 0);
+        assert_eq!(world.iter_component_grouped::<B>().count(), 0);
+        assert_eq!(world.component_count::<B>(), 0);
+    }
+
+    #[test]
+    fn test_iter_component_grouped() {
+        let mut world = World::default();
+        let a1 = world.spawn((A(1), C(String::from("Cart"))));
+        let a2 = world.spawn((A(2), C(String::from("Alice"))));
+        let a3 = world.spawn(A(3));
+
+        let mut groups = Vec::new();
+        for (sid, entities, column) in world.iter_component_grouped::<A>() {
+            assert_eq!(entities.len(), column.len());
+            for a in column.iter_mut() {
+                a.0 += 100;
+            }
+            groups.push((
+                sid,
+                entities.to_vec(),
+                column.iter().map(|a| a.0).collect::<Vec<_>>(),
+            ));
+        }
+
+        assert_eq!(
+            groups,
+            vec![
+                (ArchStorageId(0), vec![a1, a2], vec![101, 102]),
+                (ArchStorageId(1), vec![a3], vec![103]),
+            ]
+        );
+        assert_eq!(world.get_component::<A>(a2).unwrap().0, 102);
+    }
+
+    #[test]
+    fn test_set_component_if_changed() {
+        let mut world = World::default();
+        let entity = world.spawn((A(1), C(String::new())));
+        let other = world.spawn(C(String::new()));
+        let generation = |world: &World| {
+            let entity_meta = world.entities.get_entity_meta(entity).unwrap();
+            world
+                .storages
+                .arch_storages
+                .get_storage(entity_meta.archetype_storage_id)
+                .unwrap()
+                .generation()
+        };
+        let before = generation(&world);
+        assert_eq!(world.set_component_if_changed(entity, A(1)), Some(false));
+        assert_eq!(generation(&world), before);
+        assert_eq!(world.set_component_if_changed(entity, A(2)), Some(true));
+        assert_ne!(generation(&world), before);
+        assert_eq!(world.get_component::<A>(entity).unwrap().0, 2);
+
+        assert_eq!(world.set_component_if_changed(other, A(2)), None);
+        world.despawn(entity);
+        assert_eq!(world.set_component_if_changed(entity, A(2)), None);
+    }
+
+    #[test]
+    fn test_set_component_if_changed_replication() {
+        // Positions that snap to a grid of 10, where only a few entities cross a cell each frame. Every frame,
+        // the storages that changed are re-serialized.
+        fn replicate(equality_aware: bool) -> usize {
+            let mut world = World::default();
+            let entities: Vec<EntityId> = (0..60)
+                .map(|i| match i % 3 {
+                    0 => world.spawn(A(i)),
+                    1 => world.spawn((A(i), C(String::new()))),
+                    _ => world.spawn((A(i), B(Box::new([])))),
+                })
+                .collect();
+            let mut serialized = 0;
+            let mut generations: Vec<u64> = Vec::new();
+            for frame in 0..10 {
+                for (i, entity) in entities.iter().enumerate() {
+                    // Only the entities of the first storage move.
+                    let position = (i + if i % 3 == 0 { frame } else { 0 }) / 10 * 10;
+                    if equality_aware {
+                        world.set_component_if_changed(*entity, A(position));
+                    } else {
+                        world.get_component_mut::<A>(*entity).unwrap().0 = position;
+                    }
+                }
+                let current: Vec<(u64, usize)> = world
+                    .storages
+                    .arch_storages
+                    .iter_storages_with_matching_archetype(PrimeArchKey::IDENTITY)
+                    .map(|storage| (storage.generation(), storage.len()))
+                    .collect();
+                serialized += current
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, (generation, _))| generations.get(*i) != Some(generation))
+                    .map(|(_, (_, len))| len)
+                    .sum::<usize>();
+                generations = current.iter().map(|(generation, _)| *generation).collect();
+            }
+            serialized
+        }
+        assert_eq!(replicate(false), 60 * 10);
+        // The first frame snaps everything, after that only the moving storage is serialized.
+        assert_eq!(replicate(true), 60 + 20 * 9);
+    }
+
+    #[test]
+    fn test_write_component_to_all() {
+        let mut world = World::default();
+        let entities: Vec<EntityId> = (0..10)
+            .map(|i| match i % 3 {
+                0 => world.spawn((A(i), C(format!("{i}")))),
+                1 => world.spawn(C(format!("{i}"))),
+                _ => world.spawn(A(i)),
+            })
+            .collect();
+        let generations: Vec<u64> = world
+            .storages
+            .arch_storages
+            .iter_storages_with_matching_archetype(PrimeArchKey::IDENTITY)
+            .map(|storage| storage.generation())
+            .collect();
+
+        assert_eq!(world.write_component_to_all(C(String::from("reset"))), 7);
+        assert!(world.query::<&C>().all(|c| c.0 == "reset"));
+        // `A` wasn't touched.
+        assert!(entities
+            .iter()
+            .enumerate()
+            .all(|(i, e)| world.get_component::<A>(*e).is_none_or(|a| a.0 == i)));
+        // Each storage that stores `C` was marked as changed once.
+        let changed: Vec<u64> = world
+            .storages
+            .arch_storages
+            .iter_storages_with_matching_archetype(PrimeArchKey::IDENTITY)
+            .map(|storage| storage.generation())
+            .collect();
+        assert_eq!(changed[0], generations[0].wrapping_add(1));
+        assert_eq!(changed[1], generations[1].wrapping_add(1));
+        assert_eq!(changed[2], generations[2]);
+
+        assert_eq!(world.update_component_all::<A>(|a| a.0 *= 10), 7);
+        assert!(entities
+            .iter()
+            .enumerate()
+            .all(|(i, e)| world.get_component::<A>(*e).is_none_or(|a| a.0 == i * 10)));
+        assert_eq!(world.write_component_to_all(B(Box::new([]))), 0);
+        assert_eq!(world.update_component_all::<B>(|_| unreachable!()), 0);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_write_component_to_all_drops() {
+        use std::cell::Cell;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        thread_local! {
+            static CREATED: Cell<usize> = const { Cell::new(0) };
+            static DROPPED: Cell<usize> = const { Cell::new(0) };
+            static CLONES_UNTIL_PANIC: Cell<usize> = const { Cell::new(usize::MAX) };
+        }
+
+        #[derive(Component)]
+        struct Name(String);
+
+        impl Name {
+            fn new(name: &str) -> Self {
+                CREATED.with(|created| created.set(created.get() + 1));
+                Name(name.to_string())
+            }
+        }
+
+        impl Clone for Name {
+            fn clone(&self) -> Self {
+                let left =
+                    CLONES_UNTIL_PANIC.with(|left| left.replace(left.get().saturating_sub(1)));
+                assert_ne!(left, 0, "Failed to clone");
+                Name::new(&self.0)
+            }
+        }
+
+        impl Drop for Name {
+            fn drop(&mut self) {
+                DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+            }
+        }
+
+        let created = || CREATED.with(Cell::get);
+        let dropped = || DROPPED.with(Cell::get);
+
+        let mut world = World::default();
+        for i in 0..20 {
+            if i % 2 == 0 {
+                world.spawn(Name::new("old"));
+            } else {
+                world.spawn((Name::new("old"), A(i)));
+            }
+        }
+        assert_eq!(world.write_component_to_all(Name::new("new")), 20);
+        // The old values, and the value that was written.
+        assert_eq!(dropped(), 21);
+        assert_eq!(created() - dropped(), 20);
+        assert!(world.query::<&Name>().all(|name| name.0 == "new"));
+
+        // A panicking clone leaves every slot with a valid value.
+        CLONES_UNTIL_PANIC.with(|left| left.set(12));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            world.write_component_to_all(Name::new("newer"))
+        }));
+        assert!(result.is_err());
+        CLONES_UNTIL_PANIC.with(|left| left.set(usize::MAX));
+        assert_eq!(created() - dropped(), 20);
+        let newer = world
+            .query::<&Name>()
+            .filter(|name| name.0 == "newer")
+            .count();
+        assert!(newer > 0 && newer < 20);
+        world.assert_invariants();
+
+        drop(world);
+        assert_eq!(created(), dropped());
+    }
+
+    #[test]
+    fn test_rebind_components() {
+        use std::any::{type_name, TypeId};
+
+        /// Stands in for the `TypeId` that `A` had before the code was reloaded.
+        struct StaleA;
+
+        let mut world = World::default();
+        let a1 = world.spawn((A(1), C("First".into())));
+        let a2 = world.spawn(A(2));
+
+        // Simulate a reload: `A` is no longer associated with its current `TypeId`.
+        let a_id = world.components.get_component_id::<A>().unwrap();
+        let key = world.components.stable_key(a_id).unwrap().clone();
+        assert_eq!(
+            world.components.rebind_type(&key, TypeId::of::<StaleA>()),
+            Some(a_id)
+        );
+        assert!(world.get_component::<A>(a1).is_none());
+        assert!(world.components.get_component_id::<A>().is_none());
+
+        world.rebind_components(|data_info| {
+            (data_info.name() == type_name::<A>()).then(TypeId::of::<A>)
+        });
+
+        assert_eq!(world.components.get_component_id::<A>(), Some(a_id));
+        assert!(world
+            .components
+            .get_component_id_from_type_id(TypeId::of::<StaleA>())
+            .is_none());
+        assert_eq!(world.get_component::<A>(a1).unwrap().0, 1);
+        assert_eq!(world.get_component::<A>(a2).unwrap().0, 2);
+        assert_eq!(&world.get_component::<C>(a1).unwrap().0, "First");
+        assert_eq!(world.query::<&A>().map(|a| a.0).sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn test_stale_ids_under_reuse_policies() {
+        use crate::entity::ReusePolicy;
+
+        for reuse_policy in [ReusePolicy::Fifo, ReusePolicy::Lifo] {
+            let mut world = World::default();
+            world.set_entity_reuse_policy(reuse_policy);
+            let entities: Vec<EntityId> = (0..10).map(|i| world.spawn(A(i))).collect();
+            for entity in &entities {
+                world.despawn(*entity);
+            }
+            let revived: Vec<EntityId> = (10..20).map(|i| world.spawn(A(i))).collect();
+
+            for stale in &entities {
+                assert!(world.get_component::<A>(*stale).is_none());
+                assert!(world.get_component_mut::<A>(*stale).is_none());
+            }
+            for (i, entity) in revived.iter().enumerate() {
+                assert_eq!(world.get_component::<A>(*entity).unwrap().0, 10 + i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_new_ids_after_despawns() {
+        let mut world = World::default();
+        let mut alive: Vec<(EntityId, usize)> = (0..100).map(|i| (world.spawn(A(i)), i)).collect();
+        for (entity, _) in alive.drain(..60) {
+            world.despawn(entity);
+        }
+        // The first 60 reuse the removed ids, the other 20 are allocated.
+        alive.extend((100..180).map(|i| (world.spawn(A(i)), i)));
+
+        let mut slots: Vec<usize> = (alive.iter())
+            .map(|(entity, _)| world.entities.dense_index(*entity))
+            .collect();
+        slots.sort();
+        assert_eq!(slots, (0..120).collect::<Vec<_>>());
+        for (entity, value) in &alive {
+            assert_eq!(world.get_component::<A>(*entity).unwrap().0, *value);
+        }
+        assert_query_count::<&A>(&mut world, 120);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_stale_id_of_a_reused_slot() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut world = World::default();
+        world.set_entity_reuse_policy(crate::entity::ReusePolicy::Lifo);
+        let stale = world.spawn(A(1));
+        world.spawn(A(2));
+        world.despawn(stale);
+        // The id of the next entity is known before it's spawned, but it isn't alive yet.
+        let next = world.entities.next_entity_id();
+        assert_eq!(next.id(), stale.id());
+        assert!(world.get_component::<A>(next).is_none());
+        let revived = world.spawn(A(3));
+        assert_eq!(revived, next);
+
+        assert!(world.get_component::<A>(stale).is_none());
+        assert!(world.get_component_mut::<A>(stale).is_none());
+        assert!(!world.contains_component::<A>(stale));
+        // An id that was never handed out isn't alive either.
+        assert!(world
+            .get_component::<A>(EntityId::from_bits(1 << 40 | 7))
+            .is_none());
+        let despawn = catch_unwind(AssertUnwindSafe(|| world.despawn(stale)));
+        assert!(despawn.is_err());
+        assert_eq!(world.get_component::<A>(revived).unwrap().0, 3);
+    }
+
+    #[test]
+    fn test_warnings_are_off_by_default() {
+        let mut world = World::default();
+        (0..super::EMPTY_BUNDLE_SPAWN_THRESHOLD * 2).for_each(|_| {
+            world.spawn(());
+        });
+        assert!(world.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_empty_bundle_warning() {
+        let mut world = World::default();
+        world.set_warning_level(WarnLevel::All);
+        (0..super::EMPTY_BUNDLE_SPAWN_THRESHOLD - 1).for_each(|_| {
+            world.spawn(());
+        });
+        world.spawn(A(0));
+        assert!(world.take_warnings().is_empty());
+
+        (0..super::EMPTY_BUNDLE_SPAWN_THRESHOLD * 2).for_each(|_| {
+            world.spawn(());
+        });
+        assert_eq!(
+            world.take_warnings(),
+            vec![EcsWarning::EmptyBundleSpawned {
+                count: super::EMPTY_BUNDLE_SPAWN_THRESHOLD
+            }]
+        );
+        world.spawn(());
+        assert!(world.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_component_name_warning() {
+        use crate::world::data::DataInfo;
+        use std::any::{type_name, TypeId};
+
+        let mut world = World::default();
+        world.set_warning_level(WarnLevel::All);
+        // SAFETY: The `DataInfo` matches `B` (apart from the name, which isn't used for safety).
+        let b_id = unsafe {
+            world.components.register_component_from_data(
+                TypeId::of::<B>(),
+                DataInfo::named(type_name::<A>())
+                    .layout(std::alloc::Layout::new::<B>())
+                    .drop(DataInfo::default_for::<B>().drop_fn().unwrap()),
+            )
+        }
+        .unwrap();
+        world.spawn(A(1));
+        world.spawn((A(2), B(Box::new([]))));
+        let a_id = world.components.get_component_id::<A>().unwrap();
+
+        assert_eq!(
+            world.take_warnings(),
+            vec![EcsWarning::DuplicateComponentName {
+                name: type_name::<A>(),
+                first: b_id,
+                second: a_id,
+            }]
+        );
+    }
+
+    #[test]
+    #[allow(dead_code)]
+    fn test_large_component_warning() {
+        #[derive(Component)]
+        struct Small([u8; 64]);
+        #[derive(Component)]
+        struct Medium([u8; 64]);
+        #[derive(Component)]
+        struct Large([u8; 8192]);
+        #[derive(Component)]
+        #[component(boxed)]
+        struct Boxed([u8; 8192]);
+
+        let mut world = World::default();
+        world.set_warning_level(WarnLevel::All);
+        world.spawn((Small([0; 64]), Large([0; 8192]), Boxed([0; 8192])));
+        world.spawn(Large([1; 8192]));
+        let warnings = world.take_warnings();
+        assert_eq!(
+            warnings,
+            vec![EcsWarning::LargeComponent {
+                name: std::any::type_name::<Large>(),
+                size: 8192,
+            }]
+        );
+        assert!(warnings[0].to_string().contains("#[component(boxed)]"));
+
+        world.set_large_component_threshold(32);
+        world.spawn(Medium([0; 64]));
+        assert_eq!(
+            world.take_warnings(),
+            vec![EcsWarning::LargeComponent {
+                name: std::any::type_name::<Medium>(),
+                size: 64,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unregistered_component_queried_warning() {
+        #[derive(Component)]
+        struct Unregistered;
+
+        let mut world = World::default();
+        world.spawn(A(0));
+        assert_eq!(world.query::<&Unregistered>().count(), 0);
+        assert!(world.take_warnings().is_empty());
+
+        world.set_warning_level(WarnLevel::All);
+        assert_eq!(world.query::<&A>().count(), 1);
+        assert!(world.take_warnings().is_empty());
+        for _ in 0..3 {
+            assert_eq!(world.query::<(&A, &Unregistered)>().count(), 0);
+            assert_eq!(world.query_view::<(&A, &Unregistered)>().iter().count(), 0);
+        }
+        assert_eq!(world.query_filtered::<&Unregistered, ()>().count(), 0);
+        assert_eq!(
+            world.take_warnings(),
+            vec![
+                EcsWarning::UnregisteredComponentQueried {
+                    query: std::any::type_name::<(&A, &Unregistered)>()
+                },
+                EcsWarning::UnregisteredComponentQueried {
+                    query: std::any::type_name::<&Unregistered>()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stale_query_state_warning() {
+        let mut world = World::default();
+        world.set_warning_level(WarnLevel::All);
+        world.set_stale_query_state_threshold(2);
+        let mut state = QueryState::<&A>::new(&world);
+        world.spawn(A(0));
+        world.spawn((A(1), B(Box::new([]))));
+        assert_eq!(state.iter(&mut world).count(), 2);
+        assert!(world.take_warnings().is_empty());
+
+        world.spawn(C("c".into()));
+        world.spawn((A(2), C("ac".into())));
+        world.spawn((B(Box::new([])), C("bc".into())));
+        assert_eq!(state.iter(&mut world).count(), 3);
+        assert_eq!(
+            world.take_warnings(),
+            vec![EcsWarning::StaleQueryState {
+                query: std::any::type_name::<&A>(),
+                storages_created: 3,
+            }]
+        );
+        assert_eq!(state.iter(&mut world).count(), 3);
+        assert!(world.take_warnings().is_empty());
+    }
+
+    /// A filter that can only be evaluated per-entity: passes entities with an even [`A`].
+    struct EvenA;
+
+    unsafe impl ArchQuery for EvenA {
+        type Item<'a> = bool;
+
+        unsafe fn fetch(
+            arch_storage: *mut crate::world::storage::ArchEntityStorage,
+            index: crate::world::storage::arch_storage::ArchStorageIndex,
+            comp_factory: &ComponentFactory,
+            _ctx: FetchContext<'_>,
+        ) -> bool {
+            comp_factory
+                .get_component_id::<A>()
+                .and_then(|comp_id| (*arch_storage).get_component(index, comp_id))
+                .is_some_and(|a| a.deref::<A>().0 % 2 == 0)
+        }
+    }
+
+    #[test]
+    fn test_despawn_matching() {
+        type Filter = Or<(Has<C>, EvenA)>;
+
+        fn world() -> World {
+            let mut world = World::default();
+            world.register_comparable_component::<A>();
+            for i in 0..30 {
+                world.spawn(A(i));
+                world.spawn((A(i), B(Box::new([i as u8]))));
+                world.spawn(C(i.to_string()));
+            }
+            world
+        }
+
+        let mut naive = world();
+        let mut fast = world();
+
+        let to_despawn: Vec<EntityId> = naive.query_filtered::<EntityId, Filter>().collect();
+        to_despawn.iter().for_each(|entity| naive.despawn(*entity));
+        assert_eq!(fast.despawn_matching::<Filter>(), to_despawn.len());
+        assert_eq!(to_despawn.len(), 60);
+        crate::assert_worlds_eq!(naive, fast);
+
+        // The released ids are reused, with their generation bumped.
+        let respawned = fast.spawn(A(100));
+        assert!(to_despawn
+            .iter()
+            .any(|entity| entity.id() == respawned.id()
+                && entity.generation() < respawned.generation()));
+        assert_eq!(fast.get_component::<A>(respawned).unwrap().0, 100);
+        assert!(to_despawn
+            .iter()
+            .all(|entity| fast.get_component::<A>(*entity).is_none()));
+    }
+
+    #[test]
+    fn test_despawn_untags() {
+        #[derive(Tag)]
+        struct Marked;
+
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Marked>();
+        let mut world = World::with_tags(tagf);
+        let a = world.spawn(A(0));
+        unsafe { world.get_tag_tracker(a).unwrap().tag::<Marked>() };
+        world.despawn(a);
+        // The new entity reuses the slot of the despawned one, and its tag tracker.
+        let revived = world.spawn(A(1));
+        assert_eq!(revived.id(), a.id());
+        assert!(!unsafe {
+            world
+                .get_tag_tracker(revived)
+                .unwrap()
+                .is_tagged::<Marked>()
+        });
+    }
+
+    #[test]
+    fn test_tag_trackers_follow_the_entity_lifecycle() {
+        #[derive(Tag)]
+        struct Marked;
+
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Marked>();
+        let mut world = World::with_tags(tagf);
+        let is_marked = |world: &World, entity| unsafe {
+            world.get_tag_tracker(entity).unwrap().is_tagged::<Marked>()
+        };
+        let mut entities: Vec<EntityId> = (0..100).map(|i| world.spawn(A(i))).collect();
+        entities.extend(world.spawn_batch((100..200).map(|i| (A(i), C(i.to_string())))));
+        for entity in entities.iter().step_by(3) {
+            unsafe { world.get_tag_tracker(*entity).unwrap().tag::<Marked>() };
+        }
+        // Every entity has its own tracker.
+        for (i, entity) in entities.iter().enumerate() {
+            assert_eq!(is_marked(&world, *entity), i % 3 == 0);
+        }
+        for entity in entities.iter().skip(1).step_by(2) {
+            world.despawn(*entity);
+        }
+        // The respawned entities reuse the slots (and trackers) of the despawned ones, untagged, and the new slots
+        // get new trackers.
+        let respawned: Vec<EntityId> = (0..150).map(|i| world.spawn(A(i))).collect();
+        assert!(respawned.iter().all(|entity| !is_marked(&world, *entity)));
+        for (i, entity) in entities.iter().enumerate().step_by(2) {
+            assert_eq!(is_marked(&world, *entity), i % 3 == 0);
+        }
+        let tag_storage = &world.storages.tag_storage;
+        for entity in entities.iter().step_by(2).chain(&respawned) {
+            let index = world.entities.dense_index(*entity);
+            assert!(tag_storage.get_tag_tracker(index).is_some());
+        }
+        assert!(tag_storage.get_tag_tracker(250).is_none());
+
+        // Dead ids, ids that were never allocated, and ids from another world have no tracker.
+        let mut other = World::default();
+        let foreign = (0..300).map(|i| other.spawn(A(i))).last().unwrap();
+        assert!(world.get_tag_tracker(entities[1]).is_none());
+        assert!(world.get_tag_tracker(foreign).is_none());
+        assert!(world
+            .get_tag_tracker(EntityId::from_bits(u64::MAX))
+            .is_none());
+    }
+
+    #[test]
+    fn test_despawn_matching_untags() {
+        #[derive(Tag)]
+        struct Marked;
+
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Marked>();
+        let mut world = World::with_tags(tagf);
+        let c = world.spawn(C(String::from("Marked")));
+        unsafe { world.get_tag_tracker(c).unwrap().tag::<Marked>() };
+
+        assert_eq!(world.despawn_matching::<Has<C>>(), 1);
+        let revived = world.spawn(C(String::from("Revived")));
+        assert_eq!(revived.id(), c.id());
+        assert!(!unsafe {
+            world
+                .get_tag_tracker(revived)
+                .unwrap()
+                .is_tagged::<Marked>()
+        });
+    }
+
+    #[test]
+    fn test_spawn_panic_safety() {
+        use crate::archetype::key::PrimeArchKey;
+        use crate::archetype::{Archetype, ArchetypeInfo};
+        use bevy_ptr::OwningPtr;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        #[derive(Component)]
+        struct Counted(Arc<AtomicUsize>);
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        /// Stores an `A` and a `Counted`, and panics before constructing its `C`.
+        struct PanickingBundle(A, Counted);
+
+        impl Bundle for PanickingBundle {
+            fn raw_components_scope(
+                self,
+                comp_factory: &ComponentFactory,
+                f: &mut impl FnMut(ComponentId, OwningPtr<'_>),
+            ) {
+                (self.0, self.1).raw_components_scope(comp_factory, f);
+                panic!("Failed to construct C");
+            }
+        }
+
+        unsafe impl Archetype for PanickingBundle {
+            fn get_info_or_register(comp_factory: &mut ComponentFactory) -> ArchetypeInfo {
+                <(A, Counted, C)>::get_info_or_register(comp_factory)
+            }
+
+            fn arch_info(comp_factory: &ComponentFactory) -> Option<ArchetypeInfo> {
+                <(A, Counted, C)>::arch_info(comp_factory)
+            }
+
+            fn get_prime_key_or_register(comp_factory: &mut ComponentFactory) -> PrimeArchKey {
+                <(A, Counted, C)>::get_prime_key_or_register(comp_factory)
+            }
+
+            fn prime_key(comp_factory: &ComponentFactory) -> Option<PrimeArchKey> {
+                <(A, Counted, C)>::prime_key(comp_factory)
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut world = World::default();
+        let first = world.spawn((A(1), Counted(drops.clone()), C("First".into())));
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            world.spawn(PanickingBundle(A(2), Counted(drops.clone())))
+        }));
+        assert!(result.is_err());
+        // The `Counted` that was already stored was dropped, and nothing else was.
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert_eq!(world.iter_entities().collect::<Vec<_>>(), vec![first]);
+        assert_eq!(world.query::<&A>().count(), 1);
+
+        let second = world.spawn((A(3), Counted(drops.clone()), C("Second".into())));
+        assert_eq!(world.iter_entities().count(), 2);
+        assert_eq!(world.get_component::<A>(first).unwrap().0, 1);
+        assert_eq!(world.get_component::<A>(second).unwrap().0, 3);
+        assert_eq!(&world.get_component::<C>(second).unwrap().0, "Second");
+        assert_eq!(
+            world
+                .query::<(&A, &C)>()
+                .map(|(a, c)| (a.0, c.0.clone()))
+                .collect::<Vec<_>>(),
+            vec![(1, "First".to_string()), (3, "Second".to_string())]
+        );
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_spawn_batch() {
+        let mut world = World::default();
+        let existing = world.spawn((A(0), C("Existing".into())));
+        let first = world.spawn_batch((1..=100).map(|i| (A(i), C(format!("Spawned {i}")))));
+        // Into the storage that already has entities, and from an iterator without an exact size.
+        let second = world.spawn_batch(
+            (101..=300)
+                .filter(|i| i % 2 == 0)
+                .map(|i| (A(i), C(format!("Spawned {i}")))),
+        );
+        assert_eq!((first.len(), second.len()), (100, 100));
+        assert_eq!(
+            world
+                .storages
+                .arch_storages
+                .iter_storages_with_matching_archetype(PrimeArchKey::IDENTITY)
+                .count(),
+            1
+        );
+        for entity in first.iter().chain(&second) {
+            assert!(world.is_alive(*entity));
+            let a = world.get_component::<A>(*entity).unwrap().0;
+            assert_eq!(
+                world.get_component::<C>(*entity).unwrap().0,
+                format!("Spawned {a}")
+            );
+        }
+        let mut queried: Vec<(EntityId, usize)> = world
+            .query::<(EntityId, &A)>()
+            .map(|(entity, a)| (entity, a.0))
+            .collect();
+        queried.sort_unstable_by_key(|(_, a)| *a);
+        let mut spawned = vec![existing];
+        spawned.extend(first.iter().chain(&second));
+        assert_eq!(
+            queried
+                .into_iter()
+                .map(|(entity, _)| entity)
+                .collect::<Vec<_>>(),
+            spawned
+        );
+        assert!(world.spawn_batch(std::iter::empty::<A>()).is_empty());
+    }
+
+    #[test]
+    fn test_spawn_batch_with_info() {
+        use crate::archetype::ArchetypeInfo;
+
+        let mut world = World::default();
+        let existing = world.spawn((A(0), C("Existing".into())));
+        let arch_info = ArchetypeInfo::from_component_ids(vec![
+            world.components.get_component_id::<C>().unwrap(),
+            world.components.get_component_id::<A>().unwrap(),
+        ]);
+        let spawned = unsafe {
+            world.spawn_batch_with_info(
+                &arch_info,
+                (1..4).map(|i| (A(i), C(format!("Spawned {i}")))),
+            )
+        }
+        .unwrap();
+        assert_eq!(spawned.len(), 3);
+        // They're stored with the entities that have the same archetype.
+        assert_eq!(
+            world
+                .storages
+                .arch_storages
+                .iter_storages_with_matching_archetype(PrimeArchKey::IDENTITY)
+                .count(),
+            1
+        );
+        assert_eq!(world.get_component::<A>(existing).unwrap().0, 0);
+        for (i, entity) in spawned.iter().enumerate() {
+            assert_eq!(world.get_component::<A>(*entity).unwrap().0, i + 1);
+            assert_eq!(
+                world.get_component::<C>(*entity).unwrap().0,
+                format!("Spawned {}", i + 1)
+            );
+        }
+
+        let unregistered = ArchetypeInfo::from_component_ids(vec![ComponentId::new(10)]);
+        assert!(
+            unsafe { world.spawn_batch_with_info(&unregistered, std::iter::empty::<A>()) }
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_entities_and_is_alive() {
+        #[derive(Component, Clone, Copy)]
+        struct Pod;
+
+        let mut world = World::default();
+        // SAFETY: `Pod` is plain data, so it can be archived by copying its bytes.
+        unsafe { world.register_pod_component::<Pod>() };
+        assert_eq!((world.entities(), world.archetype_count()), (0, 0));
+        let first = world.spawn(A(0));
+        let second = world.spawn((A(1), C("Second".into())));
+        let archived = world.spawn(Pod);
+        world.archive(archived).unwrap();
+        assert_eq!((world.entities(), world.archetype_count()), (3, 3));
+        assert!(world.is_alive(first) && world.is_alive(second) && world.is_alive(archived));
+
+        world.despawn(first);
+        assert_eq!(world.entities(), 2);
+        assert!(!world.is_alive(first));
+        // The id is reused with a newer generation, which doesn't revive the stale id.
+        let reused = world.spawn(A(3));
+        assert_eq!(reused.id(), first.id());
+        assert!(world.is_alive(reused) && !world.is_alive(first));
+        // Ids that were never handed out, even with an index out of range.
+        assert!(!world.is_alive(first.with_generation(first.generation() + 5)));
+        assert!(!world.is_alive(EntityId::from_bits(u64::MAX)));
+        assert!(!world.is_alive(EntityId::from_bits(u32::MAX as u64)));
+        // Archetypes without entities are still stored.
+        world.despawn(second);
+        assert_eq!(world.archetype_count(), 3);
+    }
+
+    #[test]
+    fn test_contains_component() {
+        let mut world = World::default();
+        let a = world.spawn(A(0));
+        let ac = world.spawn((A(1), C("AC".into())));
+        world.components.register_component::<B>();
+
+        assert!(world.contains_component::<A>(a));
+        assert!(!world.contains_component::<C>(a));
+        assert!(!world.contains_component::<B>(ac));
+        assert!(world.contains_component::<A>(ac) && world.contains_component::<C>(ac));
+
+        world.despawn(ac);
+        assert!(!world.contains_component::<A>(ac));
+        let revived = world.spawn(C("C".into()));
+        assert_eq!(revived.id(), ac.id());
+        assert!(!world.contains_component::<A>(revived));
+        assert!(world.contains_component::<C>(revived));
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_entity_meta_keys_under_churn() {
+        // Once as is, and once under chaos (see `World::enable_chaos`).
+        for chaos in [None, Some(0x2545)] {
+            let mut world = World::default();
+            if let Some(seed) = chaos {
+                world.enable_chaos(seed, ChaosConfig::default());
+            }
+            let script = ChurnScript::new(0x2545_f491_4f6c_dd1d)
+                .steps(3000)
+                .archetypes(&[&[0], &[0, 1], &[1, 2], &[0, 1, 2, 3], &[3]]);
+            script.run_with(&mut world, |world, op| {
+                if let ChurnOp::Spawn(entity) | ChurnOp::Mutate(entity) = op {
+                    assert_eq!(
+                        world.contains_component::<Fx<1>>(entity),
+                        world.get_component::<Fx<1>>(entity).is_some()
+                    );
+                    assert_eq!(
+                        world.contains_component::<Fx<3>>(entity),
+                        world.get_component::<Fx<3>>(entity).is_some()
+                    );
+                }
+            });
+            world.assert_invariants();
+            world.despawn_matching::<Has<Fx<1>>>();
+            world.assert_invariants();
+            assert_query_count::<&Fx<1>>(&mut world, 0);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "salted-ids")]
+    fn test_salted_entity_ids() {
+        /// The components of every entity, in the order of the query.
+        fn components(world: &mut World) -> Vec<(Option<Fx<0>>, Option<Fx<1>>, Option<Fx<2>>)> {
+            world
+                .query::<(Option<&Fx<0>>, Option<&Fx<1>>, Option<&Fx<2>>)>()
+                .map(|(a, b, c)| (a.copied(), b.copied(), c.copied()))
+                .collect()
+        }
+
+        #[derive(Tag)]
+        struct Marked;
+
+        /// Run the same script on a world (salted with the seed, if there is one), and tag every third entity.
+        fn churned(seed: Option<u64>) -> (World, Vec<EntityId>) {
+            let mut tags = TagFactory::default();
+            tags.register_tag::<Marked>();
+            let mut world = World::with_tags(tags);
+            if let Some(seed) = seed {
+                world.salt_entity_ids(seed);
+            }
+            let alive = ChurnScript::new(31)
+                .steps(2000)
+                .initial_entities(50)
+                .run(&mut world);
+            for entity in alive.iter().step_by(3) {
+                unsafe { world.get_tag_tracker(*entity).unwrap().tag::<Marked>() };
+            }
+            (world, alive)
+        }
+
+        let (mut world, alive) = churned(None);
+        let expected = components(&mut world);
+        for seed in [1, 2] {
+            let (mut salted, salted_alive) = churned(Some(seed));
+            assert_ne!(salted_alive, alive);
+            assert_eq!(components(&mut salted), expected);
+            for (entity, salted_entity) in alive.iter().zip(&salted_alive) {
+                assert_eq!(
+                    world.get_component::<Fx<0>>(*entity),
+                    salted.get_component::<Fx<0>>(*salted_entity)
+                );
+                assert_eq!(
+                    unsafe {
+                        world
+                            .get_tag_tracker(*entity)
+                            .unwrap()
+                            .is_tagged::<Marked>()
+                    },
+                    unsafe {
+                        salted
+                            .get_tag_tracker(*salted_entity)
+                            .unwrap()
+                            .is_tagged::<Marked>()
+                    }
+                );
+            }
+            salted.assert_invariants();
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "salted-ids")]
+    #[should_panic(
+        expected = "worlds_ecs: salt_entity_ids failed: an entity was already spawned in the world"
+    )]
+    fn test_salt_after_spawn() {
+        let mut world = World::default();
+        let entity = world.spawn(A(0));
+        world.despawn(entity);
+        world.salt_entity_ids(1);
+    }
 }
@@ -0,0 +1,248 @@
+use super::{access::AccessKind, World};
+use crate::{
+    component::{deref_component, Component, ComponentId},
+    entity::EntityId,
+    utils::{component_mask::ComponentMask, panics},
+};
+use std::{any::type_name, collections::BTreeSet, panic::Location};
+
+/// The sorted indexes of the components that are iterated in order (see [`World::track_ordered`]). Each index
+/// holds the [`EntityId::to_bits`] of the stored entities with its component. The rows of the entities are looked
+/// up when they are iterated, so moving rows (when a storage is sorted or reordered, or when an entity is
+/// swap-removed from it) doesn't touch the indexes.
+#[derive(Default, Clone)]
+pub(crate) struct OrderedIndexes {
+    indexes: Vec<(ComponentId, BTreeSet<u64>)>,
+}
+
+impl OrderedIndexes {
+    fn get(&self, comp_id: ComponentId) -> Option<&BTreeSet<u64>> {
+        self.indexes
+            .iter()
+            .find(|(tracked, _)| *tracked == comp_id)
+            .map(|(_, index)| index)
+    }
+
+    /// An entity with the components in `mask` was stored (spawned, or restored from the archive).
+    pub(crate) fn stored(&mut self, entity: EntityId, mask: ComponentMask) {
+        for (comp_id, index) in &mut self.indexes {
+            if mask.contains(*comp_id) {
+                index.insert(entity.to_bits());
+            }
+        }
+    }
+
+    /// An entity was removed from the storages (despawned, or archived).
+    pub(crate) fn removed(&mut self, entity: EntityId) {
+        for (_, index) in &mut self.indexes {
+            index.remove(&entity.to_bits());
+        }
+    }
+}
+
+impl World {
+    /// Start keeping a sorted index of the entities with the [`Component`] `C`, so they can be iterated in a stable
+    /// order with [`World::iter_component_ordered`]. The index is updated as entities are spawned, despawned,
+    /// archived and restored, which costs a `BTreeSet` insertion or removal for each of them. Tracking a component
+    /// that is already tracked does nothing.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Replicated(u32);
+    ///
+    /// let mut world = World::default();
+    /// let early = world.spawn(Replicated(1));
+    /// world.track_ordered::<Replicated>();
+    /// // The entities that were spawned before are indexed too.
+    /// let late = world.spawn(Replicated(2));
+    /// let entities: Vec<EntityId> = world.iter_component_ordered::<Replicated>().map(|(entity, _)| entity).collect();
+    /// assert_eq!(entities, vec![early, late]);
+    /// ```
+    #[track_caller]
+    pub fn track_ordered<C: Component>(&mut self) {
+        let Some(comp_id) = self.components.register_component::<C>() else {
+            panics::fail_component_limit::<C>();
+        };
+        if self.ordered.get(comp_id).is_some() {
+            return;
+        }
+        let index = self
+            .storages
+            .arch_storages
+            .iter_storages_with_matching_archetype(comp_id.prime_key())
+            .flat_map(|storage| storage.entities())
+            .map(EntityId::to_bits)
+            .collect();
+        self.ordered.indexes.push((comp_id, index));
+    }
+
+    /// Iterate over every instance of a [`Component`] in the [`World`], alongside the [`EntityId`] of its entity,
+    /// in ascending [`EntityId::to_bits`] order. Unlike [`World::iter_component`], the order doesn't depend on which
+    /// storages the entities are in, or on the order of their rows, so the same entities are always yielded in the
+    /// same order (for example, when they are replicated over the network every frame).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Replicated(u32);
+    /// #[derive(Component)]
+    /// struct Hidden;
+    ///
+    /// let mut world = World::default();
+    /// world.track_ordered::<Replicated>();
+    /// let a = world.spawn((Replicated(1), Hidden));
+    /// let b = world.spawn(Replicated(2));
+    /// // `b` is stored before `a`, in another storage, but they are still yielded in the order of their ids.
+    /// let values: Vec<(EntityId, u32)> = world
+    ///     .iter_component_ordered::<Replicated>()
+    ///     .map(|(entity, replicated)| (entity, replicated.0))
+    ///     .collect();
+    /// assert_eq!(values, vec![(a, 1), (b, 2)]);
+    /// ```
+    ///
+    /// # Panics
+    /// If the component isn't tracked with [`World::track_ordered`].
+    #[track_caller]
+    pub fn iter_component_ordered<C: Component>(
+        &self,
+    ) -> impl Iterator<Item = (EntityId, &C)> + '_ {
+        self.access
+            .record_component::<C>(&self.components, AccessKind::Read, Location::caller());
+        let Some((comp_id, index)) = self
+            .components
+            .get_component_id::<C>()
+            .and_then(|comp_id| Some((comp_id, self.ordered.get(comp_id)?)))
+        else {
+            panics::fail_component(
+                "iter_component_ordered",
+                "the component isn't tracked (see `World::track_ordered`)",
+                type_name::<C>(),
+            );
+        };
+        index.iter().map(move |bits| {
+            let entity = EntityId::from_bits(*bits);
+            let entity_meta = self
+                .entities
+                .get_entity_meta(entity)
+                .expect("Indexed entities are alive");
+            let component = self
+                .storages
+                .arch_storages
+                .get_storage(entity_meta.archetype_storage_id)
+                .and_then(|storage| {
+                    storage.get_component(entity_meta.archetype_storage_index, comp_id)
+                })
+                .expect("Indexed entities are stored with the component");
+            // SAFETY: This type-erased pointer was fetched using this component id.
+            (entity, unsafe { deref_component::<C>(component) })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        prelude::*,
+        test_utils::{ChurnScript, Fx},
+    };
+
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Replicated(u32);
+    #[derive(Component, Clone, Copy)]
+    struct Other;
+
+    /// Every entity with `Replicated`, sorted from scratch.
+    fn sorted(world: &World) -> Vec<(EntityId, Replicated)> {
+        let mut sorted: Vec<_> = world
+            .iter_component::<Replicated>()
+            .map(|(entity, replicated)| (entity, *replicated))
+            .collect();
+        sorted.sort_by_key(|(entity, _)| entity.to_bits());
+        sorted
+    }
+
+    fn ordered(world: &World) -> Vec<(EntityId, Replicated)> {
+        world
+            .iter_component_ordered::<Replicated>()
+            .map(|(entity, replicated)| (entity, *replicated))
+            .collect()
+    }
+
+    #[test]
+    fn test_order_is_stable_under_unrelated_churn() {
+        let mut world = World::default();
+        for i in 0..30 {
+            match i % 3 {
+                0 => world.spawn(Replicated(i)),
+                1 => world.spawn((Replicated(i), Other)),
+                _ => world.spawn((Replicated(i), Fx::<0>(0))),
+            };
+        }
+        world.track_ordered::<Replicated>();
+        let frame = ordered(&world);
+        assert_eq!(frame.len(), 30);
+        assert_eq!(frame, sorted(&world));
+        ChurnScript::new(3)
+            .steps(500)
+            .initial_entities(20)
+            .archetypes(&[&[0], &[0, 1], &[2]])
+            .run_with(&mut world, |world, _| assert_eq!(ordered(world), frame));
+    }
+
+    #[test]
+    fn test_order_after_migration() {
+        let mut world = World::default();
+        // SAFETY: The components are plain data, so they can be archived by copying their bytes.
+        unsafe {
+            world.register_pod_component::<Replicated>();
+            world.register_pod_component::<Other>();
+        }
+        world.track_ordered::<Replicated>();
+        let entities: Vec<EntityId> = (0..20)
+            .map(|i| {
+                if i % 2 == 0 {
+                    world.spawn(Replicated(i))
+                } else {
+                    world.spawn((Replicated(i), Other))
+                }
+            })
+            .collect();
+        assert_eq!(ordered(&world), sorted(&world));
+
+        // Archived entities leave the index, and come back to it (at a different row) when they are restored.
+        world.archive(entities[2]).unwrap();
+        world.archive(entities[5]).unwrap();
+        assert_eq!(ordered(&world), sorted(&world));
+        assert_eq!(ordered(&world).len(), 18);
+        world.despawn(entities[0]);
+        world.restore(entities[2]).unwrap();
+        world.restore(entities[5]).unwrap();
+        assert_eq!(ordered(&world), sorted(&world));
+
+        // Despawned ids are reused with a greater generation, so the new entities come last.
+        world.despawn(entities[1]);
+        let revived = world.spawn((Replicated(100), Other));
+        assert_eq!(revived.id(), entities[0].id());
+        let ordered = ordered(&world);
+        assert_eq!(ordered, sorted(&world));
+        assert_eq!(ordered.last(), Some(&(revived, Replicated(100))));
+
+        world.despawn_matching::<Has<Other>>();
+        assert!(world
+            .iter_component_ordered::<Replicated>()
+            .all(|(_, replicated)| replicated.0 % 2 == 0));
+        world.clear();
+        assert_eq!(world.iter_component_ordered::<Replicated>().count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "the component isn't tracked")]
+    fn test_untracked_component() {
+        let mut world = World::default();
+        world.spawn(Replicated(0));
+        world.iter_component_ordered::<Replicated>().for_each(drop);
+    }
+}
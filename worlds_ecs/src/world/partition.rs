@@ -0,0 +1,426 @@
+use super::{access::AccessKind, World};
+use crate::{
+    archetype::key::PrimeArchKey,
+    component::{deref_component_mut, ComponentId},
+    entity::EntityId,
+    prelude::{ArchQuery, Component, FetchContext},
+    utils::{component_mask::ComponentMask, panics},
+    world::storage::storages::ArchStorageId,
+};
+use std::{fmt, panic::Location};
+
+/// The components that a query (or a job that runs several queries) reads and writes, see
+/// [`World::describe_query`]. The accesses of several queries are combined with [`QueryAccess::merge`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueryAccess {
+    reads: ComponentMask,
+    writes: ComponentMask,
+}
+
+impl QueryAccess {
+    /// The accesses of both `self` and `other`.
+    pub fn merge(self, other: QueryAccess) -> Self {
+        QueryAccess {
+            reads: self.reads.union(other.reads),
+            writes: self.writes.union(other.writes),
+        }
+    }
+
+    /// Returns `true` if the component is read or written.
+    pub fn reads(&self, comp_id: ComponentId) -> bool {
+        self.reads.contains(comp_id) || self.writes.contains(comp_id)
+    }
+
+    /// Returns `true` if the component is written.
+    pub fn writes(&self, comp_id: ComponentId) -> bool {
+        self.writes.contains(comp_id)
+    }
+
+    /// A component that `self` and `other` can't access at the same time (because one of them writes it, and the
+    /// other one accesses it), or `None` if they are compatible.
+    pub fn conflict(&self, other: &QueryAccess) -> Option<ComponentId> {
+        let (accessed, other_accessed) = (
+            self.reads.union(self.writes),
+            other.reads.union(other.writes),
+        );
+        self.writes
+            .intersection(other_accessed)
+            .union(other.writes.intersection(accessed))
+            .iter()
+            .next()
+    }
+
+    /// The first access of `requested` that `self` doesn't allow, or `None` if `requested` is a subset of `self`.
+    fn first_disallowed(&self, requested: &QueryAccess) -> Option<(ComponentId, AccessKind)> {
+        if requested.writes.is_subset(&self.writes)
+            && requested.reads.is_subset(&self.reads.union(self.writes))
+        {
+            return None;
+        }
+        let write = requested
+            .writes
+            .iter()
+            .find(|comp_id| !self.writes(*comp_id));
+        let read = || requested.reads.iter().find(|comp_id| !self.reads(*comp_id));
+        match write {
+            Some(comp_id) => Some((comp_id, AccessKind::Write)),
+            None => read().map(|comp_id| (comp_id, AccessKind::Read)),
+        }
+    }
+}
+
+/// An error when the [`World`] can't be partitioned, see [`World::partition`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartitionError {
+    /// Two of the accesses conflict: one of them writes a component that the other one accesses.
+    Conflict {
+        /// The index of the first access of the pair.
+        first: usize,
+        /// The index of the second access of the pair.
+        second: usize,
+        /// The name of the component that they conflict on.
+        component: &'static str,
+    },
+}
+
+impl fmt::Display for PartitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionError::Conflict {
+                first,
+                second,
+                component,
+            } => write!(
+                f,
+                "the accesses {first} and {second} conflict on `{component}`, which one of them writes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PartitionError {}
+
+/// A view of a [`World`] that can only access the components of its [`QueryAccess`] (see [`World::partition`]).
+/// The partitions of a world access disjoint components, so each of them can be used from a different thread.
+pub struct WorldPartition<'w> {
+    world: &'w World,
+    access: QueryAccess,
+}
+
+impl<'w> WorldPartition<'w> {
+    /// The access that the partition was declared with.
+    pub fn access(&self) -> &QueryAccess {
+        &self.access
+    }
+
+    /// Query the components of the partition, like [`World::query`].
+    ///
+    /// # Panics
+    /// If the query accesses components outside of the access of the partition, or like [`World::query`].
+    #[track_caller]
+    pub fn query<Q: ArchQuery>(&mut self) -> impl Iterator<Item = Q::Item<'_>> + '_ {
+        let requested = self.world.describe_query::<Q>();
+        self.check_access("WorldPartition::query", &requested);
+        let world = self.world;
+        world
+            .access
+            .record_query::<Q>(&world.components, Location::caller());
+        let (arch_storages, tags) = (&world.storages.arch_storages, world.entity_tags());
+        let mut sid = ArchStorageId(0);
+        world
+            .resolve_query_key::<Q>()
+            .into_iter()
+            .flat_map(move |pkey: PrimeArchKey| {
+                std::iter::from_fn(move || {
+                    let id = arch_storages.next_storage_with_matching_archetype(sid, pkey)?;
+                    sid = ArchStorageId(id.0 + 1);
+                    arch_storages.get_storage(id)
+                })
+            })
+            .flat_map(move |storage| {
+                let ctx = FetchContext::new(tags).for_query::<Q>(storage, &world.components);
+                storage.iter_indices().map(move |index| {
+                    // SAFETY: The index is in bounds. The storages of the components that the partition writes were
+                    // unshared when the world was partitioned, no other partition accesses them, and the partition
+                    // is borrowed mutably while the items are alive.
+                    unsafe { Q::fetch_shared(storage, index, &world.components, ctx) }
+                })
+            })
+    }
+
+    /// Get a [`Component`] of an entity, like [`World::get_component`].
+    ///
+    /// # Panics
+    /// If the partition doesn't read `C`.
+    #[track_caller]
+    pub fn get_component<C: Component>(&self, entity: EntityId) -> Option<&C> {
+        let world = self.world;
+        let comp_id = world.components.get_component_id::<C>()?;
+        let requested = QueryAccess {
+            reads: ComponentMask::from_component_ids([comp_id]),
+            writes: ComponentMask::EMPTY,
+        };
+        self.check_access("WorldPartition::get_component", &requested);
+        world.get_component::<C>(entity)
+    }
+
+    /// Get a [`Component`] of an entity mutably, like [`World::get_component_mut`].
+    ///
+    /// # Panics
+    /// If the partition doesn't write `C`.
+    #[track_caller]
+    pub fn get_component_mut<C: Component>(&mut self, entity: EntityId) -> Option<&mut C> {
+        let world = self.world;
+        let comp_id = world.components.get_component_id::<C>()?;
+        let requested = QueryAccess {
+            reads: ComponentMask::EMPTY,
+            writes: ComponentMask::from_component_ids([comp_id]),
+        };
+        self.check_access("WorldPartition::get_component_mut", &requested);
+        world.access.record_component::<C>(
+            &world.components,
+            AccessKind::Write,
+            Location::caller(),
+        );
+        let entity_meta = world.entities.get_entity_meta(entity)?;
+        let storage = world
+            .storages
+            .arch_storages
+            .get_storage(entity_meta.archetype_storage_id)?;
+        let raw_comp = storage.get_component(entity_meta.archetype_storage_index, comp_id)?;
+        // SAFETY: This type-erased pointer was fetched using this component id. The storage was unshared when the
+        // world was partitioned, no other partition accesses `C`, and the partition is borrowed mutably.
+        Some(unsafe { deref_component_mut::<C>(raw_comp.assert_unique()) })
+    }
+
+    /// Panics if `requested` isn't a subset of the access of the partition.
+    #[track_caller]
+    fn check_access(&self, op: &str, requested: &QueryAccess) {
+        if let Some((comp_id, kind)) = self.access.first_disallowed(requested) {
+            let name = self
+                .world
+                .components
+                .get_component_info_from_component_id(comp_id)
+                .map_or("?", |info| info.name());
+            panics::fail(
+                op,
+                "the partition accesses a component outside of its declared access",
+                &[("component", &name), ("access", &format!("{kind:?}"))],
+            )
+        }
+    }
+}
+
+impl World {
+    /// The components that the query `Q` reads and writes, to declare the access of a job up front (see
+    /// [`World::partition`]). Components that aren't registered are left out, because they can't be accessed.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    /// #[derive(Component)]
+    /// struct Velocity(f32);
+    ///
+    /// let mut world = World::default();
+    /// let position = world.register_component::<Position>().unwrap();
+    /// let velocity = world.register_component::<Velocity>().unwrap();
+    /// let movement = world.describe_query::<(&mut Position, &Velocity)>();
+    /// assert!(movement.writes(position));
+    /// assert!(movement.reads(velocity) && !movement.writes(velocity));
+    /// let rendering = world.describe_query::<&Position>();
+    /// assert_eq!(movement.conflict(&rendering), Some(position));
+    /// ```
+    pub fn describe_query<Q: ArchQuery>(&self) -> QueryAccess {
+        let mut access = QueryAccess::default();
+        Q::for_each_access(&self.components, &mut |comp_id, kind| match kind {
+            AccessKind::Read => access.reads.insert(comp_id),
+            AccessKind::Write => access.writes.insert(comp_id),
+        });
+        access
+    }
+
+    /// Split the world into one [`WorldPartition`] for each of `accesses`, so an external scheduler can run jobs
+    /// with compatible accesses at the same time, from different threads. Every pair of accesses must be
+    /// compatible: components that are only read can be in any of them, but a component that is written can't be
+    /// accessed by any other one. Each partition can only query and get the components of its own access, which is
+    /// checked whenever it's used.
+    ///
+    /// The storages of the written components are prepared up front: they are unshared (see
+    /// [`World::clone_cow`]), and their [`generation`](super::storage::arch_storage::ArchStorage::generation) is
+    /// changed, even if the partitions end up not writing anything.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn((Position(0.0), Health(10)));
+    /// let accesses = [
+    ///     world.describe_query::<&mut Position>(),
+    ///     world.describe_query::<&mut Health>(),
+    /// ];
+    /// let partitions = world.partition(&accesses).unwrap();
+    /// std::thread::scope(|s| {
+    ///     for (i, mut partition) in partitions.into_iter().enumerate() {
+    ///         s.spawn(move || match i {
+    ///             0 => partition.query::<&mut Position>().for_each(|position| position.0 += 1.0),
+    ///             _ => partition.query::<&mut Health>().for_each(|health| health.0 -= 1),
+    ///         });
+    ///     }
+    /// });
+    /// assert_eq!(world.query::<&Health>().next().unwrap().0, 9);
+    /// ```
+    ///
+    /// # Errors
+    /// If a pair of the accesses conflict, with the first pair and component that conflict.
+    pub fn partition(
+        &mut self,
+        accesses: &[QueryAccess],
+    ) -> Result<Vec<WorldPartition<'_>>, PartitionError> {
+        for (first, access) in accesses.iter().enumerate() {
+            for (second, other) in accesses.iter().enumerate().skip(first + 1) {
+                if let Some(comp_id) = access.conflict(other) {
+                    return Err(PartitionError::Conflict {
+                        first,
+                        second,
+                        component: self
+                            .components
+                            .get_component_info_from_component_id(comp_id)
+                            .expect("the accessed components are registered")
+                            .name(),
+                    });
+                }
+            }
+        }
+        let writes = accesses
+            .iter()
+            .fold(ComponentMask::EMPTY, |writes, access| {
+                writes.union(access.writes)
+            });
+        if !writes.is_empty() {
+            for storage in self
+                .storages
+                .arch_storages
+                .iter_storages_with_matching_archetype_mut(PrimeArchKey::IDENTITY)
+            {
+                if !storage.component_mask().intersection(writes).is_empty() {
+                    // The partitions write through shared references, so the storages are prepared for it here,
+                    // while the world is still borrowed mutably.
+                    storage.make_unique();
+                    storage.bump_generation();
+                }
+            }
+        }
+        let world: &World = self;
+        Ok(accesses
+            .iter()
+            .map(|access| WorldPartition {
+                world,
+                access: *access,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PartitionError;
+    use crate::prelude::*;
+
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Health(i32);
+
+    fn world() -> World {
+        let mut world = World::default();
+        for i in 0..100 {
+            match i % 2 {
+                0 => world.spawn((Position(i), Velocity(1), Health(i))),
+                _ => world.spawn((Position(i), Velocity(2))),
+            };
+        }
+        world
+    }
+
+    #[test]
+    fn test_compatible_partitions_from_threads() {
+        let mut world = world();
+        let entity = world.query::<(EntityId, &Health)>().next().unwrap().0;
+        let accesses = [
+            world.describe_query::<(&mut Position, &Velocity)>(),
+            world
+                .describe_query::<&mut Health>()
+                .merge(world.describe_query::<&Velocity>()),
+        ];
+        let mut partitions = world.partition(&accesses).unwrap();
+        let (mut health, mut movement) = (partitions.pop().unwrap(), partitions.pop().unwrap());
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for (position, velocity) in movement.query::<(&mut Position, &Velocity)>() {
+                    position.0 += velocity.0;
+                }
+            });
+            s.spawn(move || {
+                for (health, velocity) in health.query::<(&mut Health, &Velocity)>() {
+                    health.0 -= velocity.0;
+                }
+                health.get_component_mut::<Health>(entity).unwrap().0 = -1;
+                assert_eq!(health.get_component::<Velocity>(entity), Some(&Velocity(1)));
+            });
+        });
+
+        let mut positions: Vec<i32> = world.query::<&Position>().map(|p| p.0).collect();
+        positions.sort();
+        let mut expected: Vec<i32> = (0..100).map(|i| i + 1 + i % 2).collect();
+        expected.sort();
+        assert_eq!(positions, expected);
+        for (entity_id, health) in world.query::<(EntityId, &Health)>() {
+            assert!(entity_id == entity && health.0 == -1 || health.0 % 2 == 1);
+        }
+    }
+
+    #[test]
+    fn test_overlapping_accesses_are_rejected() {
+        let mut world = world();
+        let accesses = [
+            world.describe_query::<&Health>(),
+            world.describe_query::<&Position>(),
+            world.describe_query::<(&Velocity, &mut Position)>(),
+        ];
+        let error = world.partition(&accesses).err().unwrap();
+        assert_eq!(
+            error,
+            PartitionError::Conflict {
+                first: 1,
+                second: 2,
+                component: std::any::type_name::<Position>(),
+            }
+        );
+        assert!(error
+            .to_string()
+            .contains("the accesses 1 and 2 conflict on"));
+        // Reads don't conflict with each other.
+        assert_eq!(world.partition(&accesses[..2]).unwrap().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "the partition accesses a component outside of its declared access (component=worlds_ecs::world::partition::tests::Velocity, access=Write)"
+    )]
+    fn test_out_of_declaration_access() {
+        let mut world = world();
+        let accesses = [world.describe_query::<(&mut Position, &Velocity)>()];
+        let mut partition = world.partition(&accesses).unwrap().pop().unwrap();
+        partition.query::<(&Position, &Velocity)>().for_each(drop);
+        partition.query::<&mut Velocity>().for_each(drop);
+    }
+}
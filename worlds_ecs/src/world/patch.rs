@@ -0,0 +1,331 @@
+use super::{
+    access::{AccessKind, AccessRecorder},
+    storage::{arch_storage::ArchStorageIndex, ArchEntityStorage},
+    World,
+};
+use crate::{
+    component::deref_component_mut,
+    entity::EntityId,
+    prelude::{Component, ComponentFactory, ComponentId},
+};
+use std::{any::TypeId, panic::Location};
+
+/// Several writes to the components of one entity, that resolve the entity and its storage only once: see
+/// [`World::patch`].
+///
+/// The column of each component is looked up once per patch, no matter how many times it's written. The writes
+/// don't change the [`generation`](super::storage::arch_storage::ArchStorage::generation) of the storage as they
+/// happen: when the patch is committed (or dropped), the generation is bumped once for each component that was
+/// written, so sort-maintained storages and spatial indexes pick up the changes on their next sync.
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component, Debug, PartialEq)]
+/// struct Position(f32, f32);
+/// #[derive(Component, Debug, PartialEq)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Shield;
+///
+/// let mut world = World::default();
+/// let entity = world.spawn((Position(0.0, 0.0), Health(100)));
+/// world.spawn(Shield);
+///
+/// let result = world
+///     .patch(entity)
+///     .unwrap()
+///     .set(Position(4.0, 2.0))
+///     .update(|health: &mut Health| health.0 -= 30)
+///     .set(Shield)
+///     .commit();
+/// assert_eq!(result.touched, 2);
+/// assert_eq!(result.missing, vec![std::any::type_name::<Shield>()]);
+/// assert_eq!(world.get_component::<Position>(entity), Some(&Position(4.0, 2.0)));
+/// assert_eq!(world.get_component::<Health>(entity), Some(&Health(70)));
+/// ```
+pub struct EntityPatch<'w> {
+    storage: &'w mut ArchEntityStorage,
+    index: ArchStorageIndex,
+    entity: EntityId,
+    components: &'w ComponentFactory,
+    access: &'w AccessRecorder,
+    location: &'static Location<'static>,
+    /// The column of each component that was looked up, or `None` if the entity doesn't have it.
+    columns: Vec<(TypeId, Option<usize>)>,
+    /// The columns that were written, and weren't committed yet.
+    touched: Vec<usize>,
+    committed: usize,
+    missing: Vec<&'static str>,
+}
+
+/// What an [`EntityPatch`] did, returned by [`EntityPatch::commit`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PatchResult {
+    /// How many distinct components were written.
+    pub touched: usize,
+    /// The names of the components that were set (or updated) with [`EntityPatch::set`] (or
+    /// [`EntityPatch::update`]), but that the entity doesn't have, so they weren't written.
+    pub missing: Vec<&'static str>,
+}
+
+impl World {
+    /// Start a patch of several components of an entity (see [`EntityPatch`]), resolving the entity and its storage
+    /// once. Returns `None` if the entity was despawned (or archived, see [`World::archive`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Shield;
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Health(10));
+    /// let result = world.patch(entity).unwrap().set(Health(5)).set(Shield).commit();
+    /// assert_eq!(result.touched, 1);
+    /// assert_eq!(result.missing.len(), 1);
+    /// assert_eq!(world.get_component::<Health>(entity).unwrap().0, 5);
+    /// world.despawn(entity);
+    /// assert!(world.patch(entity).is_none());
+    /// ```
+    #[track_caller]
+    pub fn patch(&mut self, entity: EntityId) -> Option<EntityPatch<'_>> {
+        let entity_meta = *self.entities.get_entity_meta(entity)?;
+        let storage = self
+            .storages
+            .arch_storages
+            .get_storage_mut(entity_meta.archetype_storage_id)?;
+        Some(EntityPatch {
+            storage,
+            index: entity_meta.archetype_storage_index,
+            entity,
+            components: &self.components,
+            access: &self.access,
+            location: Location::caller(),
+            columns: Vec::new(),
+            touched: Vec::new(),
+            committed: 0,
+            missing: Vec::new(),
+        })
+    }
+}
+
+impl<'w> EntityPatch<'w> {
+    /// The entity that is patched.
+    pub fn entity(&self) -> EntityId {
+        self.entity
+    }
+
+    /// How many components were looked up in the storage of the entity. Writing the same component again doesn't
+    /// look it up again.
+    pub fn component_lookups(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Overwrite the component `C` of the entity with `value`. If the entity doesn't have `C`, `value` is dropped and
+    /// `C` is reported in [`PatchResult::missing`].
+    pub fn set<C: Component>(mut self, value: C) -> Self {
+        if !self.try_set(value) {
+            self.missing.push(std::any::type_name::<C>());
+        }
+        self
+    }
+
+    /// Call `f` with the component `C` of the entity. If the entity doesn't have `C`, `f` isn't called, and `C` is
+    /// reported in [`PatchResult::missing`].
+    pub fn update<C: Component>(mut self, f: impl FnOnce(&mut C)) -> Self {
+        if !self.try_update(f) {
+            self.missing.push(std::any::type_name::<C>());
+        }
+        self
+    }
+
+    /// Like [`EntityPatch::set`], but returns whether the entity has `C` instead of reporting it when it doesn't.
+    pub fn try_set<C: Component>(&mut self, value: C) -> bool {
+        self.try_update(|component: &mut C| *component = value)
+    }
+
+    /// Like [`EntityPatch::update`], but returns whether the entity has `C` instead of reporting it when it
+    /// doesn't.
+    pub fn try_update<C: Component>(&mut self, f: impl FnOnce(&mut C)) -> bool {
+        self.access
+            .record_component::<C>(self.components, AccessKind::Write, self.location);
+        let Some(column) = self.column_of::<C>() else {
+            return false;
+        };
+        // Marked before `f` is called, so the write is committed even if `f` panics.
+        if !self.touched.contains(&column) {
+            self.touched.push(column);
+        }
+        // SAFETY: The entity is alive, so its index is in bounds, and the column was looked up with the component id
+        // of `C`. The generation is bumped when the patch is committed.
+        f(unsafe {
+            deref_component_mut::<C>(
+                self.storage
+                    .get_component_mut_in_column_untracked(self.index, column),
+            )
+        });
+        true
+    }
+
+    /// The column of `C` in the storage, or `None` if the entity doesn't have it.
+    fn column_of<C: Component>(&mut self) -> Option<usize> {
+        let type_id = TypeId::of::<C>();
+        if let Some((_, column)) = self.columns.iter().find(|(id, _)| *id == type_id) {
+            return *column;
+        }
+        let column = self
+            .components
+            .get_component_id::<C>()
+            .and_then(|comp_id: ComponentId| self.storage.column_of(comp_id));
+        self.columns.push((type_id, column));
+        column
+    }
+
+    /// Finish the patch, and return which components were written and which were missing. Dropping the patch
+    /// finishes it too, without the result.
+    pub fn commit(mut self) -> PatchResult {
+        self.finish();
+        PatchResult {
+            touched: self.committed,
+            missing: std::mem::take(&mut self.missing),
+        }
+    }
+
+    /// Bump the generation of the storage once for each component that was written since the last time.
+    fn finish(&mut self) {
+        for _ in self.touched.drain(..) {
+            self.storage.bump_generation();
+            self.committed += 1;
+        }
+    }
+}
+
+impl Drop for EntityPatch<'_> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PatchResult;
+    use crate::prelude::*;
+
+    #[derive(Component, Debug, PartialEq, Clone, Copy)]
+    struct Position(f32, f32);
+    #[derive(Component, Debug, PartialEq, Clone, Copy)]
+    struct Rotation(f32);
+    #[derive(Component, Debug, PartialEq)]
+    struct Health(u32);
+    #[derive(Component)]
+    struct Shield;
+
+    fn generation(world: &World, entity: EntityId) -> u64 {
+        let sid = world
+            .entities
+            .get_entity_meta(entity)
+            .unwrap()
+            .archetype_storage_id;
+        world
+            .storages
+            .arch_storages
+            .get_storage(sid)
+            .unwrap()
+            .generation()
+    }
+
+    #[test]
+    fn test_patch_three_components() {
+        let mut world = World::default();
+        let others: Vec<EntityId> = (0..4)
+            .map(|i| world.spawn((Position(i as f32, 0.0), Rotation(0.0), Health(i))))
+            .collect();
+        let entity = world.spawn((Position(0.0, 0.0), Rotation(0.0), Health(100)));
+        world.components.register_component::<Shield>();
+        let before = generation(&world, entity);
+
+        let mut patch = world.patch(entity).unwrap();
+        assert_eq!(patch.entity(), entity);
+        assert!(patch.try_set(Position(1.0, 2.0)));
+        assert!(!patch.try_set(Shield));
+        let result = patch
+            .set(Rotation(0.5))
+            .update(|health: &mut Health| health.0 -= 25)
+            .update(|health: &mut Health| health.0 -= 25)
+            .set(Position(3.0, 4.0))
+            .set(Shield);
+        // Each component was looked up once, no matter how many times it was written.
+        assert_eq!(result.component_lookups(), 4);
+        assert_eq!(
+            result.commit(),
+            PatchResult {
+                touched: 3,
+                missing: vec![std::any::type_name::<Shield>()]
+            }
+        );
+
+        // One bump per touched component.
+        assert_eq!(generation(&world, entity), before + 3);
+        assert_eq!(
+            world.get_component::<Position>(entity),
+            Some(&Position(3.0, 4.0))
+        );
+        assert_eq!(
+            world.get_component::<Rotation>(entity),
+            Some(&Rotation(0.5))
+        );
+        assert_eq!(world.get_component::<Health>(entity), Some(&Health(50)));
+        for (i, other) in others.into_iter().enumerate() {
+            assert_eq!(
+                world.get_component::<Health>(other),
+                Some(&Health(i as u32))
+            );
+        }
+    }
+
+    #[test]
+    fn test_patch_change_detection() {
+        let mut world = World::default();
+        let entity = world.spawn((Position(0.0, 0.0), Health(1)));
+        let before = generation(&world, entity);
+        // Nothing was written, so nothing changed.
+        let result = world.patch(entity).unwrap().set(Rotation(1.0)).commit();
+        assert_eq!(result.touched, 0);
+        assert_eq!(result.missing, vec![std::any::type_name::<Rotation>()]);
+        assert_eq!(generation(&world, entity), before);
+        // Dropping the patch commits it too.
+        {
+            let mut patch = world.patch(entity).unwrap();
+            patch.try_update(|health: &mut Health| health.0 += 1);
+        }
+        assert_eq!(generation(&world, entity), before + 1);
+        assert_eq!(world.get_component::<Health>(entity), Some(&Health(2)));
+
+        world.despawn(entity);
+        assert!(world.patch(entity).is_none());
+    }
+
+    #[test]
+    fn test_patch_unshares_cow_copies() {
+        let mut world = World::default();
+        world.register_cloneable_component::<Position>();
+        let entity = world.spawn(Position(0.0, 0.0));
+        let copy = world.clone_cow().unwrap();
+        world
+            .patch(entity)
+            .unwrap()
+            .set(Position(1.0, 1.0))
+            .commit();
+        assert_eq!(
+            world.get_component::<Position>(entity),
+            Some(&Position(1.0, 1.0))
+        );
+        assert_eq!(
+            copy.get_component::<Position>(entity),
+            Some(&Position(0.0, 0.0))
+        );
+    }
+}
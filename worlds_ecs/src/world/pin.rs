@@ -0,0 +1,398 @@
+use super::{
+    storage::storages::{ArchStorageId, ArchetypeId},
+    World,
+};
+use crate::utils::panics;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// A guard that keeps the rows of a storage in place while it's alive: see [`World::pin_storage`].
+///
+/// The guard doesn't borrow the [`World`], and it's [`Send`], so it can accompany an external job (like an audio
+/// mix callback, or an asynchronous copy to the GPU) that holds raw pointers into the storage's columns.
+#[derive(Debug)]
+#[must_use = "The storage is unpinned as soon as the pin is dropped"]
+pub struct StoragePin {
+    storage: ArchStorageId,
+    archetype: ArchetypeId,
+    pins: Arc<AtomicUsize>,
+}
+
+impl StoragePin {
+    /// The storage that is pinned.
+    pub fn storage(&self) -> ArchStorageId {
+        self.storage
+    }
+
+    /// The archetype of the storage that is pinned.
+    pub fn archetype(&self) -> ArchetypeId {
+        self.archetype
+    }
+}
+
+impl Drop for StoragePin {
+    fn drop(&mut self) {
+        self.pins.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// An error when an operation would move the rows of a pinned storage (see [`World::pin_storage`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoragePinned {
+    /// The pinned storage.
+    pub storage: ArchStorageId,
+    /// The archetype of the pinned storage.
+    pub archetype: ArchetypeId,
+    /// The names of the components that the storage stores.
+    pub components: Vec<&'static str>,
+    /// Whether the storage is pinned because it's frozen (see [`World::freeze_archetype`]).
+    pub frozen: bool,
+}
+
+impl fmt::Display for StoragePinned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the storage {:?} of ({}) is {}",
+            self.storage,
+            self.components.join(", "),
+            if self.frozen { "frozen" } else { "pinned" }
+        )
+    }
+}
+
+impl std::error::Error for StoragePinned {}
+
+impl World {
+    /// Pin a storage, so its columns aren't reallocated and its rows aren't moved while the returned guard is
+    /// alive. Several pins of the same storage can be alive at once: the storage is unpinned when all of them are
+    /// dropped.
+    ///
+    /// While the storage is pinned, the components stored in it can be mutated in place, and entities can be
+    /// spawned into it as long as they fit its current capacity (and it isn't sort-maintained, see
+    /// [`World::maintain_sort`]). Operations that would move its rows return a [`StoragePinned`] error if they are
+    /// fallible (like [`World::archive`]), and panic otherwise (like [`World::despawn`]).
+    /// [`World::resort_dirty`] skips pinned storages, and sorts them once they are unpinned.
+    ///
+    /// If the storage shares its components with a copy of the world (see [`World::clone_cow`]), they are copied
+    /// before it's pinned, so mutating them later doesn't move them. A frozen storage (see
+    /// [`World::freeze_archetype`]) is pinned for as long as it's frozen.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Particle(f32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Particle(0.0));
+    /// let storage = world.entity_location(entity).unwrap().0;
+    /// let pin = world.pin_storage(storage);
+    /// assert_eq!(pin.storage(), storage);
+    /// // The components can be mutated in place while the storage is pinned.
+    /// world.get_component_mut::<Particle>(entity).unwrap().0 = 1.0;
+    /// assert!(world.archive(entity).is_err());
+    /// ```
+    ///
+    /// # Panics
+    /// If there is no storage with this id.
+    #[track_caller]
+    pub fn pin_storage(&mut self, id: ArchStorageId) -> StoragePin {
+        let arch_storages = &mut self.storages.arch_storages;
+        let archetype = arch_storages.archetype_of_storage(id);
+        let (Some(storage), Some(archetype)) = (arch_storages.get_storage_mut(id), archetype)
+        else {
+            panics::fail(
+                "pin_storage",
+                "the storage doesn't exist",
+                &[("storage", &id.0)],
+            );
+        };
+        storage.make_unique();
+        storage.pins.fetch_add(1, Ordering::Acquire);
+        StoragePin {
+            storage: id,
+            archetype,
+            pins: storage.pins.clone(),
+        }
+    }
+
+    /// Returns `true` if a [`StoragePin`] of the storage is alive (see [`World::pin_storage`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Particle(f32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Particle(0.0));
+    /// let storage = world.entity_location(entity).unwrap().0;
+    /// let pin = world.pin_storage(storage);
+    /// assert!(world.is_storage_pinned(storage));
+    /// drop(pin);
+    /// assert!(!world.is_storage_pinned(storage));
+    /// ```
+    pub fn is_storage_pinned(&self, id: ArchStorageId) -> bool {
+        self.storages
+            .arch_storages
+            .get_storage(id)
+            .is_some_and(|storage| storage.is_pinned())
+    }
+
+    /// Returns an error if storing `incoming` more entities in the storage would move its rows while it's pinned.
+    pub(crate) fn check_pin_for_store(
+        &self,
+        id: ArchStorageId,
+        incoming: usize,
+    ) -> Result<(), StoragePinned> {
+        match self.storages.arch_storages.get_storage(id) {
+            Some(storage) if !storage.can_store_while_pinned(incoming) => {
+                Err(self.storage_pinned(id))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if the storage is pinned, so rows can't be removed from it.
+    pub(crate) fn check_pin_for_remove(&self, id: ArchStorageId) -> Result<(), StoragePinned> {
+        if self.is_storage_pinned(id) {
+            return Err(self.storage_pinned(id));
+        }
+        Ok(())
+    }
+
+    /// The [`StoragePinned`] error of a pinned storage.
+    pub(crate) fn storage_pinned(&self, id: ArchStorageId) -> StoragePinned {
+        let storage = self.storages.arch_storages.get_storage(id).unwrap();
+        StoragePinned {
+            storage: id,
+            archetype: self
+                .storages
+                .arch_storages
+                .archetype_of_storage(id)
+                .unwrap(),
+            components: storage
+                .component_ids()
+                .map(|comp_id| {
+                    self.components
+                        .get_component_info_from_component_id(comp_id)
+                        .expect("stored components are registered")
+                        .name()
+                })
+                .collect(),
+            frozen: storage.is_frozen(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StoragePinned;
+    use crate::{
+        archetype::Archetype,
+        prelude::*,
+        world::{archive::Archivable, storage::storages::ArchStorageId},
+    };
+
+    #[derive(Component, Debug, PartialEq, Clone, Copy)]
+    struct Sample(u32);
+
+    impl Archivable for Sample {
+        fn archive(&self, bytes: &mut Vec<u8>) {
+            bytes.extend_from_slice(&self.0.to_le_bytes());
+        }
+
+        fn restore(bytes: &[u8]) -> Option<Self> {
+            Some(Sample(u32::from_le_bytes(bytes.try_into().ok()?)))
+        }
+    }
+
+    #[derive(Component)]
+    struct Other;
+
+    /// A world with `count` samples, and the id of their storage.
+    fn world(count: u32) -> (World, Vec<EntityId>, ArchStorageId) {
+        let mut world = World::default();
+        world.register_archivable_component::<Sample>();
+        world.register_cloneable_component::<Sample>();
+        let entities: Vec<EntityId> = (0..count).map(|i| world.spawn(Sample(i))).collect();
+        let sid = world
+            .entities
+            .get_entity_meta(entities[0])
+            .unwrap()
+            .archetype_storage_id;
+        (world, entities, sid)
+    }
+
+    fn column_ptr(world: &mut World) -> *const Sample {
+        world
+            .iter_component_grouped::<Sample>()
+            .next()
+            .unwrap()
+            .2
+            .as_ptr()
+    }
+
+    fn room(world: &World, sid: ArchStorageId) -> usize {
+        let storage = world.storages.arch_storages.get_storage(sid).unwrap();
+        (0..)
+            .find(|incoming| !storage.has_room_for(incoming + 1))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_mutation_and_spawns_with_headroom_are_allowed() {
+        let (mut world, entities, sid) = world(20);
+        let pin = world.pin_storage(sid);
+        assert!(world.is_storage_pinned(sid));
+        let ptr = column_ptr(&mut world);
+
+        world.get_component_mut::<Sample>(entities[0]).unwrap().0 = 100;
+        world
+            .query::<&mut Sample>()
+            .for_each(|sample| sample.0 += 1);
+        world.write_component_to_all(Sample(7));
+        world.patch(entities[1]).unwrap().set(Sample(8)).commit();
+        // Spawns into other storages, and into the pinned storage while they fit its capacity.
+        world.spawn((Sample(0), Other));
+        for _ in 0..room(&world, sid) {
+            world.spawn(Sample(9));
+        }
+        assert_eq!(room(&world, sid), 0);
+        assert_eq!(column_ptr(&mut world), ptr);
+        assert_eq!(world.get_component::<Sample>(entities[1]), Some(&Sample(8)));
+        assert_eq!(pin.storage(), sid);
+        world.assert_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: spawn failed: the storage ArchStorageId(0) of (")]
+    fn test_spawn_beyond_capacity_panics() {
+        let (mut world, _, sid) = world(20);
+        let _pin = world.pin_storage(sid);
+        for _ in 0..=room(&world, sid) {
+            world.spawn(Sample(0));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: spawn_batch failed: the storage")]
+    fn test_batch_spawn_beyond_capacity_panics() {
+        let (mut world, _, sid) = world(20);
+        let _pin = world.pin_storage(sid);
+        let arch_info = Sample::arch_info(&world.components).unwrap();
+        let incoming = room(&world, sid) + 1;
+        // SAFETY: Every bundle is a `Sample`.
+        unsafe { world.spawn_batch_with_info(&arch_info, (0..incoming as u32).map(Sample)) };
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: despawn failed: the storage")]
+    fn test_despawn_panics() {
+        let (mut world, entities, sid) = world(3);
+        let _pin = world.pin_storage(sid);
+        world.despawn(entities[2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: despawn_matching failed: the storage")]
+    fn test_despawn_matching_panics() {
+        let (mut world, _, sid) = world(3);
+        let _pin = world.pin_storage(sid);
+        world.despawn_matching::<Has<Sample>>();
+    }
+
+    #[test]
+    #[should_panic(expected = "is pinned")]
+    fn test_clear_panics() {
+        let (mut world, _, sid) = world(3);
+        let _pin = world.pin_storage(sid);
+        world.clear();
+    }
+
+    #[test]
+    fn test_archive_and_restore_return_errors() {
+        let (mut world, entities, sid) = world(3);
+        world.archive(entities[0]).unwrap();
+        let pin = world.pin_storage(sid);
+        let pinned = ArchiveError::Pinned(StoragePinned {
+            storage: sid,
+            archetype: world.archetype_id_of(entities[1]).unwrap(),
+            components: vec![std::any::type_name::<Sample>()],
+            frozen: false,
+        });
+        assert_eq!(world.archive(entities[1]), Err(pinned.clone()));
+        assert_eq!(world.archive_matching::<()>(), Err(pinned.clone()));
+        // There is room for the restored entity.
+        assert_eq!(world.restore(entities[0]), Ok(()));
+        world.archive_matching::<()>().unwrap_err();
+        drop(pin);
+        assert_eq!(world.archive_matching::<()>(), Ok(3));
+
+        // Fill the storage up, so restoring would grow it.
+        world.spawn(Sample(0));
+        let fill = room(&world, sid);
+        for _ in 0..fill {
+            world.spawn(Sample(0));
+        }
+        let _pin = world.pin_storage(sid);
+        assert_eq!(world.restore(entities[0]), Err(pinned));
+        assert!(world.is_archived(entities[0]));
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_dropping_pins_releases_the_storage() {
+        let (mut world, entities, sid) = world(3);
+        world.maintain_sort::<Sample, Sample, u32>(|sample| sample.0);
+        let first = world.pin_storage(sid);
+        let second = world.pin_storage(sid);
+        // Pins can accompany a job on another thread.
+        std::thread::spawn(move || drop(first)).join().unwrap();
+        assert!(world.is_storage_pinned(sid));
+
+        // Sorting waits for the storage to be unpinned.
+        world.get_component_mut::<Sample>(entities[0]).unwrap().0 = 10;
+        assert_eq!(world.resort_dirty(), 0);
+        drop(second);
+        assert!(!world.is_storage_pinned(sid));
+        assert_eq!(world.resort_dirty(), 1);
+        let order: Vec<u32> = world.query::<&Sample>().map(|sample| sample.0).collect();
+        assert_eq!(order, [1, 2, 10]);
+        world.despawn(entities[1]);
+        world.spawn(Sample(5));
+        world.assert_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: spawn failed: the storage")]
+    fn test_spawn_into_pinned_sorted_storage_panics() {
+        let (mut world, _, sid) = world(1);
+        world.maintain_sort::<Sample, Sample, u32>(|sample| sample.0);
+        let _pin = world.pin_storage(sid);
+        world.spawn(Sample(0));
+    }
+
+    #[test]
+    fn test_pinned_storages_are_not_shared() {
+        let (mut world, entities, sid) = world(20);
+        let copy = world.clone_cow().unwrap();
+        let _pin = world.pin_storage(sid);
+        let ptr = column_ptr(&mut world);
+        let second_copy = world.clone_cow().unwrap();
+        world.get_component_mut::<Sample>(entities[0]).unwrap().0 = 100;
+        assert_eq!(column_ptr(&mut world), ptr);
+        assert_eq!(copy.get_component::<Sample>(entities[0]), Some(&Sample(0)));
+        assert_eq!(
+            second_copy.get_component::<Sample>(entities[0]),
+            Some(&Sample(0))
+        );
+        assert!(!second_copy.is_storage_pinned(sid));
+    }
+}
@@ -0,0 +1,331 @@
+use super::World;
+use crate::{
+    archetype::{Archetype, ArchetypeInfo},
+    component::ComponentFactory,
+    prelude::ComponentId,
+};
+use std::fmt;
+
+/// A list of archetypes whose storages are created ahead of time (see [`World::precreate_from_manifest`]), so the
+/// first spawn of each one doesn't pay for creating its storage. Entries are either archetype types (see
+/// [`Self::with`], and [`precreate_archetypes!`](crate::precreate_archetypes)), or lists of component names, for
+/// data-driven prefabs and scenes (see [`Self::with_names`]).
+#[derive(Default, Clone)]
+pub struct ArchetypeManifest {
+    entries: Vec<ManifestEntry>,
+}
+
+#[derive(Clone)]
+enum ManifestEntry {
+    Typed {
+        name: &'static str,
+        info: fn(&mut ComponentFactory) -> ArchetypeInfo,
+    },
+    Named(Vec<String>),
+}
+
+impl ArchetypeManifest {
+    /// An empty manifest.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the archetype `A` (a component, or a tuple of components) to the manifest. Its components are registered
+    /// when the manifest is precreated.
+    pub fn with<A: Archetype>(mut self) -> Self {
+        self.entries.push(ManifestEntry::Typed {
+            name: std::any::type_name::<A>(),
+            info: A::get_info_or_register,
+        });
+        self
+    }
+
+    /// Add the archetype made of the components with these names (the full paths of their types, see
+    /// [`ComponentFactory::get_component_id_by_name`]) to the manifest. The components must be registered when the
+    /// manifest is precreated.
+    pub fn with_names<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.entries.push(ManifestEntry::Named(
+            names.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// The amount of archetypes in the manifest.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the manifest has no archetypes.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// An error when creating the storages of an [`ArchetypeManifest`] (see [`World::precreate_from_manifest`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrecreateError {
+    /// No registered component has this name.
+    UnknownComponent {
+        /// The name of the component.
+        name: String,
+    },
+    /// A component appears more than once in an archetype.
+    DuplicateComponents {
+        /// The archetype: its type, or the names of its components.
+        archetype: String,
+    },
+}
+
+impl fmt::Display for PrecreateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrecreateError::UnknownComponent { name } => {
+                write!(f, "no registered component is named `{name}`")
+            }
+            PrecreateError::DuplicateComponents { archetype } => {
+                write!(f, "a component appears more than once in `{archetype}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PrecreateError {}
+
+/// How the storages of a [`World`] were created, see [`World::storage_creations`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageCreations {
+    /// How many storages were created ahead of time, by [`World::precreate_from_manifest`].
+    pub precreated: usize,
+    /// The storages that were created on demand, by the first entity that needed them, as the names of their
+    /// components. These are the archetypes that are missing from the manifest.
+    pub on_demand: Vec<Vec<&'static str>>,
+}
+
+impl World {
+    /// Create the storages of the archetypes in the manifest ahead of time (for example, at load time), so the first
+    /// spawn of each one doesn't pay for creating its storage (like the first time a boss spawns). Returns how many
+    /// storages were created: the storages that already exist are reused.
+    ///
+    /// Every archetype is resolved before any storage is created, so if an error is returned, no storage was
+    /// created (the components of the typed entries may have been registered). See [`World::storage_creations`]
+    /// to find the archetypes that are missing from the manifest.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Boss;
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let manifest = ArchetypeManifest::new().with::<(Boss, Health)>().with::<Health>();
+    /// assert_eq!(world.precreate_from_manifest(&manifest), Ok(2));
+    /// // The storages already exist.
+    /// assert_eq!(world.precreate_from_manifest(&manifest), Ok(0));
+    /// let unknown = ArchetypeManifest::new().with_names(["Minion"]);
+    /// assert!(world.precreate_from_manifest(&unknown).is_err());
+    /// ```
+    pub fn precreate_from_manifest(
+        &mut self,
+        manifest: &ArchetypeManifest,
+    ) -> Result<usize, PrecreateError> {
+        let mut infos = Vec::with_capacity(manifest.entries.len());
+        for entry in &manifest.entries {
+            let (arch_info, archetype) = match entry {
+                ManifestEntry::Typed { name, info } => {
+                    (info(&mut self.components), name.to_string())
+                }
+                ManifestEntry::Named(names) => {
+                    let component_ids = names
+                        .iter()
+                        .map(|name| {
+                            self.components
+                                .get_component_id_by_name(name)
+                                .ok_or_else(|| PrecreateError::UnknownComponent {
+                                    name: name.clone(),
+                                })
+                        })
+                        .collect::<Result<Vec<ComponentId>, _>>()?;
+                    (
+                        ArchetypeInfo::from_component_ids(component_ids),
+                        format!("({})", names.join(", ")),
+                    )
+                }
+            };
+            if arch_info.check_for_duplicates() {
+                return Err(PrecreateError::DuplicateComponents { archetype });
+            }
+            infos.push(arch_info);
+        }
+        Ok(infos
+            .iter()
+            .filter(|arch_info| {
+                self.storages
+                    .arch_storages
+                    .precreate_storage_with_info(arch_info, &self.components)
+                    .expect("the archetype was resolved, without duplicates")
+            })
+            .count())
+    }
+
+    /// How the storages of the world were created: ahead of time (see [`World::precreate_from_manifest`]), or on
+    /// demand, by the first entity that needed them. An on-demand storage is a hitch that a manifest could have
+    /// avoided.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Boss;
+    /// #[derive(Component)]
+    /// struct Minion;
+    ///
+    /// let mut world = World::default();
+    /// world.precreate_from_manifest(&ArchetypeManifest::new().with::<Boss>()).unwrap();
+    /// world.spawn(Boss);
+    /// world.spawn(Minion);
+    /// let creations = world.storage_creations();
+    /// assert_eq!(creations.precreated, 1);
+    /// assert_eq!(creations.on_demand, vec![vec![std::any::type_name::<Minion>()]]);
+    /// ```
+    pub fn storage_creations(&self) -> StorageCreations {
+        let arch_storages = &self.storages.arch_storages;
+        StorageCreations {
+            precreated: arch_storages.precreated(),
+            on_demand: arch_storages
+                .created_on_demand()
+                .iter()
+                .map(|sid| {
+                    arch_storages
+                        .storage_key(*sid)
+                        .expect("storages are never removed")
+                        .iter_component_ids(&self.components)
+                        .map(|comp_id| {
+                            self.components
+                                .get_component_info_from_component_id(comp_id)
+                                .expect("stored components are registered")
+                                .name()
+                        })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Create the storages of a list of archetypes ahead of time, see [`World::precreate_from_manifest`]. Returns how
+/// many storages were created.
+///
+/// ```
+/// # use worlds_ecs::prelude::*;
+/// #[derive(Component)]
+/// struct Boss;
+/// #[derive(Component)]
+/// struct Health(u32);
+///
+/// let mut world = World::default();
+/// assert_eq!(worlds_ecs::precreate_archetypes!(world; (Boss, Health), Health), Ok(2));
+/// world.spawn((Boss, Health(1000)));
+/// assert!(world.storage_creations().on_demand.is_empty());
+/// ```
+#[macro_export]
+macro_rules! precreate_archetypes {
+    ($world:expr; $($archetype:ty),* $(,)?) => {
+        $world.precreate_from_manifest(
+            &$crate::world::precreate::ArchetypeManifest::new()$(.with::<$archetype>())*
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchetypeManifest, PrecreateError};
+    use crate::prelude::*;
+
+    #[derive(Component)]
+    struct Boss;
+    #[derive(Component)]
+    struct Health(#[allow(unused)] u32);
+    #[derive(Component)]
+    struct Minion;
+
+    fn name<C: Component>() -> &'static str {
+        std::any::type_name::<C>()
+    }
+
+    #[test]
+    fn test_precreated_storages_are_reused() {
+        let mut world = World::default();
+        assert_eq!(precreate_archetypes!(world; (Boss, Health), Minion), Ok(2));
+        assert_eq!(world.storages.arch_storages.storage_keys().len(), 2);
+        world.spawn((Health(1000), Boss));
+        let info = <Minion as crate::archetype::Archetype>::arch_info(&world.components).unwrap();
+        // SAFETY: Every bundle stores exactly the components of `info`.
+        unsafe { world.spawn_batch_with_info(&info, (0..20).map(|_| Minion)) }.unwrap();
+        assert_eq!(world.storages.arch_storages.storage_keys().len(), 2);
+        // Precreating again reuses the storages.
+        assert_eq!(precreate_archetypes!(world; (Health, Boss)), Ok(0));
+
+        let creations = world.storage_creations();
+        assert_eq!(creations.precreated, 2);
+        assert!(creations.on_demand.is_empty());
+    }
+
+    #[test]
+    fn test_manifest_from_names() {
+        let mut world = World::default();
+        world.components.register_component::<Boss>();
+        world.components.register_component::<Health>();
+        let manifest = ArchetypeManifest::new()
+            .with_names([name::<Boss>(), name::<Health>()])
+            .with_names([name::<Health>()]);
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(world.precreate_from_manifest(&manifest), Ok(2));
+        world.spawn((Boss, Health(1)));
+        world.spawn(Health(1));
+        assert_eq!(world.storages.arch_storages.storage_keys().len(), 2);
+
+        let unknown = ArchetypeManifest::new()
+            .with_names([name::<Minion>()])
+            .with::<Minion>();
+        assert_eq!(
+            world.precreate_from_manifest(&unknown),
+            Err(PrecreateError::UnknownComponent {
+                name: name::<Minion>().to_string()
+            })
+        );
+        let duplicates = ArchetypeManifest::new()
+            .with::<Minion>()
+            .with_names([name::<Boss>(), name::<Boss>()]);
+        assert!(matches!(
+            world.precreate_from_manifest(&duplicates),
+            Err(PrecreateError::DuplicateComponents { .. })
+        ));
+        assert!(matches!(
+            precreate_archetypes!(world; (Minion, Minion)),
+            Err(PrecreateError::DuplicateComponents { .. })
+        ));
+        // Nothing was created by the failed manifests.
+        assert_eq!(world.storages.arch_storages.storage_keys().len(), 2);
+    }
+
+    #[test]
+    fn test_storages_created_on_demand() {
+        let mut world = World::default();
+        precreate_archetypes!(world; Boss).unwrap();
+        world.spawn(Boss);
+        world.spawn((Minion, Health(1)));
+        world.spawn((Health(2), Minion));
+        world.spawn(Minion);
+        let creations = world.storage_creations();
+        assert_eq!(creations.precreated, 1);
+        assert_eq!(creations.on_demand.len(), 2);
+        let mut missing = creations.on_demand[0].clone();
+        missing.sort();
+        let mut expected = vec![name::<Health>(), name::<Minion>()];
+        expected.sort();
+        assert_eq!(missing, expected);
+        assert_eq!(creations.on_demand[1], [name::<Minion>()]);
+    }
+}
@@ -0,0 +1,460 @@
+//! A recovery mode for shipped builds: [`World::check_and_quarantine`] checks the invariants of every storage, and
+//! detaches the inconsistent ones from the world instead of letting them crash it.
+//!
+//! A quarantined storage is replaced with an empty storage of its archetype, which is never spawned into again (the
+//! next entity of the archetype gets a new storage). Its entities are destroyed like despawned entities, except that
+//! their components aren't dropped, so their ids are stale and fail safely. The memory of the components is leaked
+//! by default, since dropping values of a corrupted storage could run drops on bad data (see
+//! [`QuarantineMemory`](crate::world::quarantine::QuarantineMemory)).
+use super::{
+    storage::{
+        storages::{ArchStorageId, ArchetypeId},
+        ArchEntityStorage,
+    },
+    World,
+};
+use crate::{component::ComponentId, entity::EntityId};
+use std::{fmt, sync::Arc};
+
+/// A broken invariant of a storage (see [`World::check_and_quarantine`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// The column of a component doesn't have a component for every row of the storage.
+    ColumnLength {
+        /// The name of the component.
+        component: &'static str,
+        /// The amount of components in the column.
+        len: usize,
+        /// The amount of rows in the storage.
+        rows: usize,
+    },
+    /// The storage doesn't have an entity for every row.
+    EntityCount {
+        /// The amount of entities in the storage.
+        entities: usize,
+        /// The amount of rows in the storage.
+        rows: usize,
+    },
+    /// An entity in the storage was despawned.
+    DeadEntity {
+        /// The despawned entity.
+        entity: EntityId,
+        /// The row that the entity is in.
+        row: usize,
+    },
+    /// The meta of an entity in the storage doesn't point to its row.
+    MisplacedEntity {
+        /// The misplaced entity.
+        entity: EntityId,
+        /// The row that the entity is in.
+        row: usize,
+        /// The storage that the meta points to.
+        meta_storage: ArchStorageId,
+        /// The row that the meta points to.
+        meta_row: usize,
+    },
+    /// The meta of an entity in the storage doesn't have the components of the storage.
+    ComponentMismatch {
+        /// The entity whose meta is wrong.
+        entity: EntityId,
+    },
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inconsistency::ColumnLength {
+                component,
+                len,
+                rows,
+            } => write!(
+                f,
+                "the column of `{component}` has {len} components, but the storage has {rows} rows"
+            ),
+            Inconsistency::EntityCount { entities, rows } => {
+                write!(f, "the storage has {entities} entities, but {rows} rows")
+            }
+            Inconsistency::DeadEntity { entity, row } => {
+                write!(f, "{entity:?} is in row {row}, but it was despawned")
+            }
+            Inconsistency::MisplacedEntity {
+                entity,
+                row,
+                meta_storage,
+                meta_row,
+            } => write!(
+                f,
+                "{entity:?} is in row {row}, but its meta points to row {meta_row} of {meta_storage:?}"
+            ),
+            Inconsistency::ComponentMismatch { entity } => write!(
+                f,
+                "the meta of {entity:?} doesn't have the components of the storage"
+            ),
+        }
+    }
+}
+
+/// What is done with the memory of a quarantined storage (see [`World::check_and_quarantine_with`]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QuarantineMemory {
+    /// Leak all of the memory of the storage, without dropping any of its components. It's always safe, whatever
+    /// broke the storage, but the memory (and anything the components own) is never freed.
+    #[default]
+    Leak,
+    /// Drop the components of the columns whose length matches the amount of entities in the storage, and leak the
+    /// rest. It's best-effort: a column with the right length may still hold bad values, if they were corrupted too.
+    DropConsistentColumns,
+}
+
+/// A storage that was quarantined by [`World::check_and_quarantine`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuarantinedStorage {
+    /// The storage that was quarantined. Its entry is left empty.
+    pub storage: ArchStorageId,
+    /// The archetype of the storage.
+    pub archetype: ArchetypeId,
+    /// The names of the components that the storage stored, in the order of its columns.
+    pub components: Vec<&'static str>,
+    /// How many entities were destroyed with the storage.
+    pub entities: usize,
+    /// The broken invariants that were found. Only the first inconsistency of the entities is listed, since one
+    /// desync usually misplaces every entity after it.
+    pub inconsistencies: Vec<Inconsistency>,
+    /// How many columns had their components dropped (see [`QuarantineMemory::DropConsistentColumns`]).
+    pub dropped_columns: usize,
+}
+
+/// The result of [`World::check_and_quarantine`], for telemetry.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QuarantineReport {
+    /// The storages that were quarantined, in the order of their ids.
+    pub storages: Vec<QuarantinedStorage>,
+}
+
+impl QuarantineReport {
+    /// Returns `true` if every storage was consistent, so nothing was quarantined.
+    pub fn is_clean(&self) -> bool {
+        self.storages.is_empty()
+    }
+
+    /// How many entities were destroyed with the quarantined storages.
+    pub fn entities(&self) -> usize {
+        self.storages.iter().map(|storage| storage.entities).sum()
+    }
+}
+
+impl World {
+    /// Check the invariants of every storage (the checks of [`World::assert_invariants`], and that every column
+    /// has a component for every row), and quarantine the inconsistent storages instead of crashing: the storage
+    /// is detached from the world (queries don't see it, and its archetype is spawned into a new storage), and its
+    /// entities are destroyed, so their ids are stale. The components of a quarantined storage are leaked, see
+    /// [`World::check_and_quarantine_with`] to drop the ones that seem intact. The rest of the world is untouched.
+    ///
+    /// The check reads every entity, so it's meant for checkpoints (like loading a level, or every few seconds of a
+    /// shipped build), rather than every frame.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(10));
+    /// let report = world.check_and_quarantine();
+    /// assert!(report.is_clean());
+    /// ```
+    pub fn check_and_quarantine(&mut self) -> QuarantineReport {
+        self.check_and_quarantine_with(QuarantineMemory::Leak)
+    }
+
+    /// Like [`World::check_and_quarantine`], deciding what is done with the memory of the quarantined storages.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(10));
+    /// let report = world.check_and_quarantine_with(QuarantineMemory::DropConsistentColumns);
+    /// assert!(report.is_clean());
+    /// assert_eq!(report.entities(), 0);
+    /// ```
+    pub fn check_and_quarantine_with(&mut self, memory: QuarantineMemory) -> QuarantineReport {
+        let mut report = QuarantineReport::default();
+        for sid in (0..self.storages.arch_storages.storage_count()).map(ArchStorageId) {
+            if self.storages.arch_storages.is_quarantined(sid) {
+                continue;
+            }
+            let inconsistencies = self.storage_inconsistencies(sid);
+            if !inconsistencies.is_empty() {
+                report
+                    .storages
+                    .push(self.quarantine(sid, inconsistencies, memory));
+            }
+        }
+        report
+    }
+
+    /// The broken invariants of the storage with the [`ArchStorageId`] `sid`. Only the first inconsistency of the
+    /// entities is listed.
+    pub(crate) fn storage_inconsistencies(&self, sid: ArchStorageId) -> Vec<Inconsistency> {
+        let Some(storage) = self.storages.arch_storages.get_storage(sid) else {
+            return Vec::new();
+        };
+        let rows = storage.len();
+        let mut inconsistencies: Vec<Inconsistency> = (storage.column_lens().into_iter())
+            .filter(|(_, len)| *len != rows)
+            .map(|(comp_id, len)| Inconsistency::ColumnLength {
+                component: self.component_name(comp_id),
+                len,
+                rows,
+            })
+            .collect();
+        if storage.entities().len() != rows {
+            inconsistencies.push(Inconsistency::EntityCount {
+                entities: storage.entities().len(),
+                rows,
+            });
+        }
+        let misplaced = storage
+            .entities()
+            .iter()
+            .enumerate()
+            .find_map(|(row, entity)| {
+                let Some(entity_meta) = self.entities.get_entity_meta(*entity) else {
+                    return Some(Inconsistency::DeadEntity {
+                        entity: *entity,
+                        row,
+                    });
+                };
+                if entity_meta.archetype_storage_id != sid
+                    || entity_meta.archetype_storage_index.0 != row
+                {
+                    return Some(Inconsistency::MisplacedEntity {
+                        entity: *entity,
+                        row,
+                        meta_storage: entity_meta.archetype_storage_id,
+                        meta_row: entity_meta.archetype_storage_index.0,
+                    });
+                }
+                (entity_meta.component_mask != storage.component_mask())
+                    .then_some(Inconsistency::ComponentMismatch { entity: *entity })
+            });
+        inconsistencies.extend(misplaced);
+        inconsistencies
+    }
+
+    /// Quarantine the storage with the [`ArchStorageId`] `sid`, destroying the entities whose metas point to it.
+    fn quarantine(
+        &mut self,
+        sid: ArchStorageId,
+        inconsistencies: Vec<Inconsistency>,
+        memory: QuarantineMemory,
+    ) -> QuarantinedStorage {
+        let archetype = self
+            .storages
+            .arch_storages
+            .archetype_of_storage(sid)
+            .unwrap();
+        let storage = self
+            .storages
+            .arch_storages
+            .quarantine(sid, &self.components)
+            .expect("stored components are registered");
+        let components = (storage.column_lens().into_iter())
+            .map(|(comp_id, _)| self.component_name(comp_id))
+            .collect();
+        let entities = self.entities.alive_in_storage(sid);
+        self.destroy_quarantined(&entities);
+        QuarantinedStorage {
+            storage: sid,
+            archetype,
+            components,
+            entities: entities.len(),
+            inconsistencies,
+            dropped_columns: release(storage, memory),
+        }
+    }
+
+    fn component_name(&self, comp_id: ComponentId) -> &'static str {
+        self.components
+            .get_component_info_from_component_id(comp_id)
+            .expect("stored components are registered")
+            .name()
+    }
+}
+
+/// Release the memory of a quarantined storage, and return how many columns had their components dropped.
+fn release(storage: ArchEntityStorage, memory: QuarantineMemory) -> usize {
+    if memory == QuarantineMemory::Leak {
+        std::mem::forget(storage);
+        return 0;
+    }
+    let rows = storage.entities().len();
+    let (mut columns, _) = storage.into_columns();
+    let mut dropped = 0;
+    // Shared or frozen columns are referenced elsewhere too, so they are leaked as a whole.
+    if let Some(columns) = Arc::get_mut(&mut columns) {
+        for column in 0..columns.column_count() {
+            if columns.len(column) == rows {
+                columns.truncate_column(column, 0);
+                dropped += 1;
+            }
+        }
+    }
+    std::mem::forget(columns);
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Inconsistency, QuarantineMemory};
+    use crate::{prelude::*, world::storage::storages::ArchStorageId};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Component, Debug, PartialEq)]
+    struct A(usize);
+    #[derive(Component)]
+    struct B(#[allow(dead_code)] usize);
+    #[derive(Component)]
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn storage_of(world: &World, entity: EntityId) -> ArchStorageId {
+        world
+            .entities
+            .get_entity_meta(entity)
+            .unwrap()
+            .archetype_storage_id
+    }
+
+    #[test]
+    fn test_clean_world() {
+        let mut world = World::default();
+        world.spawn_batch((0..10).map(|i| (A(i), B(i))));
+        assert!(world.check_and_quarantine().is_clean());
+        assert_eq!(world.query::<&A>().count(), 10);
+    }
+
+    #[test]
+    fn test_quarantine_column_length() {
+        let mut world = World::default();
+        let corrupted = world.spawn_batch((0..10).map(|i| (A(i), B(i))));
+        let intact = world.spawn_batch((10..15).map(A));
+        let sid = storage_of(&world, corrupted[0]);
+        let b = world.components.get_component_id::<B>().unwrap();
+        unsafe {
+            world
+                .storages
+                .arch_storages
+                .get_storage_mut(sid)
+                .unwrap()
+                .set_column_len(b, 9);
+        }
+
+        let report = world.check_and_quarantine();
+        assert_eq!(report.storages.len(), 1);
+        assert_eq!(report.entities(), 10);
+        let quarantined = &report.storages[0];
+        assert_eq!(quarantined.storage, sid);
+        assert_eq!(quarantined.entities, 10);
+        assert_eq!(quarantined.dropped_columns, 0);
+        assert!(quarantined.components.contains(&std::any::type_name::<A>()));
+        assert!(quarantined.components.contains(&std::any::type_name::<B>()));
+        assert_eq!(
+            quarantined.inconsistencies,
+            [Inconsistency::ColumnLength {
+                component: std::any::type_name::<B>(),
+                len: 9,
+                rows: 10
+            }]
+        );
+
+        // The corrupted entities are gone, and their ids are stale.
+        assert_eq!(world.query::<&B>().count(), 0);
+        for entity in &corrupted {
+            assert!(world.get_component::<A>(*entity).is_none());
+            assert!(world.entities.get_entity_meta(*entity).is_none());
+        }
+        // The other storages are untouched.
+        let mut values: Vec<usize> = world.query::<&A>().map(|a| a.0).collect();
+        values.sort_unstable();
+        assert_eq!(values, (10..15).collect::<Vec<_>>());
+        for (i, entity) in intact.iter().enumerate() {
+            assert_eq!(world.get_component::<A>(*entity), Some(&A(i + 10)));
+        }
+        world.assert_invariants();
+
+        // The archetype is spawned into a new storage.
+        let respawned = world.spawn((A(20), B(20)));
+        assert_ne!(storage_of(&world, respawned), sid);
+        assert_eq!(world.query::<(&A, &B)>().count(), 1);
+        assert!(world.check_and_quarantine().is_clean());
+    }
+
+    #[test]
+    fn test_quarantine_entities_desync() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut world = World::default();
+        let corrupted = world.spawn_batch((0..8).map(|i| (A(i), Counted(drops.clone()))));
+        let sid = storage_of(&world, corrupted[0]);
+        world
+            .storages
+            .arch_storages
+            .get_storage_mut(sid)
+            .unwrap()
+            .entities_mut()
+            .swap(2, 5);
+
+        let report = world.check_and_quarantine_with(QuarantineMemory::DropConsistentColumns);
+        assert_eq!(report.entities(), 8);
+        assert!(matches!(
+            report.storages[0].inconsistencies[..],
+            [Inconsistency::MisplacedEntity { row: 2, .. }]
+        ));
+        // Every column had a component for every entity, so they were all dropped.
+        assert_eq!(report.storages[0].dropped_columns, 2);
+        assert_eq!(drops.load(Ordering::Relaxed), 8);
+        assert_eq!(world.query::<&Counted>().count(), 0);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_quarantine_leaks_by_default() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut world = World::default();
+        let corrupted = world.spawn_batch((0..4).map(|_| Counted(drops.clone())));
+        let sid = storage_of(&world, corrupted[0]);
+        world
+            .storages
+            .arch_storages
+            .get_storage_mut(sid)
+            .unwrap()
+            .entities_mut()
+            .pop();
+
+        let report = world.check_and_quarantine();
+        assert!(matches!(
+            report.storages[0].inconsistencies[..],
+            [Inconsistency::EntityCount {
+                entities: 3,
+                rows: 4
+            }]
+        ));
+        // The entity that was dropped from the storage's list is found by its meta.
+        assert_eq!(report.entities(), 4);
+        drop(world);
+        assert_eq!(drops.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,432 @@
+use super::{rules::ComponentRuleError, World};
+use crate::{
+    archetype::{key::PrimeArchKey, Archetype},
+    component::ComponentFactory,
+};
+use std::{collections::HashMap, fmt};
+
+/// What a quota limits (see [`World::set_entity_limit`] and [`World::set_archetype_limit`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotaScope {
+    /// The live entities of the [`World`].
+    Entities,
+    /// The entities of one archetype.
+    Archetype {
+        /// The key of the archetype.
+        key: PrimeArchKey,
+        /// The names of the components of the archetype, to identify it.
+        components: Vec<&'static str>,
+    },
+}
+
+impl fmt::Display for QuotaScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaScope::Entities => write!(f, "live entities"),
+            QuotaScope::Archetype { components, .. } => {
+                write!(f, "entities of ({})", components.join(", "))
+            }
+        }
+    }
+}
+
+/// An error when entities can't be spawned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpawnError {
+    /// The entity would break a component rule (see [`World::require_component`]).
+    Rule(ComponentRuleError),
+    /// Spawning the entities would exceed a quota (see [`World::set_entity_limit`]).
+    QuotaExceeded {
+        /// What the quota limits.
+        scope: QuotaScope,
+        /// The limit of the quota.
+        limit: usize,
+        /// How many entities the quota counted before the spawn.
+        current: usize,
+    },
+}
+
+impl From<ComponentRuleError> for SpawnError {
+    fn from(error: ComponentRuleError) -> Self {
+        SpawnError::Rule(error)
+    }
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpawnError::Rule(error) => error.fmt(f),
+            SpawnError::QuotaExceeded {
+                scope,
+                limit,
+                current,
+            } => write!(
+                f,
+                "spawning would exceed the quota of {limit} {scope} ({current} already exist)"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpawnError {}
+
+/// The quotas of the [`World`]: the most entities that can be alive, overall and per archetype.
+#[derive(Default, Clone)]
+pub(crate) struct Quotas {
+    entities: Option<u32>,
+    archetypes: HashMap<PrimeArchKey, usize>,
+}
+
+impl Quotas {
+    /// Returns `true` if no quota is set, so spawns don't need to be checked.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entities.is_none() && self.archetypes.is_empty()
+    }
+
+    /// Returns an error if spawning `incoming` more entities of the archetype `key` would exceed a quota, when
+    /// `live` entities are alive and `stored` of them are of that archetype.
+    pub(crate) fn check(
+        &self,
+        live: usize,
+        key: PrimeArchKey,
+        stored: usize,
+        incoming: usize,
+        components: &ComponentFactory,
+    ) -> Result<(), SpawnError> {
+        if let Some(limit) = self.entities {
+            let limit = limit as usize;
+            if live + incoming > limit {
+                return Err(SpawnError::QuotaExceeded {
+                    scope: QuotaScope::Entities,
+                    limit,
+                    current: live,
+                });
+            }
+        }
+        if let Some(&limit) = self.archetypes.get(&key) {
+            if stored + incoming > limit {
+                return Err(SpawnError::QuotaExceeded {
+                    scope: QuotaScope::Archetype {
+                        key,
+                        components: key
+                            .iter_component_ids(components)
+                            .map(|comp_id| {
+                                components
+                                    .get_component_info_from_component_id(comp_id)
+                                    .expect("the components of a key are registered")
+                                    .name()
+                            })
+                            .collect(),
+                    },
+                    limit,
+                    current: stored,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl World {
+    /// Limit how many entities can be alive at once, so content that spawns entities (like scripts, or scenes made
+    /// by players) can't exhaust the memory of the host. Every way to spawn entities (like [`World::try_spawn`],
+    /// [`World::spawn_batch_with_info`] and `World::load_scene`), and [`World::restore`], checks the limit: the
+    /// fallible ones return a [`SpawnError::QuotaExceeded`] error, and the others panic with the same information.
+    ///
+    /// Archived entities (see [`World::archive`]) don't count towards the limit, because they aren't stored, but
+    /// restoring them does. Despawned entities free their quota immediately. A batch is spawned all-or-nothing:
+    /// if the whole batch doesn't fit in the remaining quota, none of it is spawned.
+    ///
+    /// Lowering the limit below the current amount of entities doesn't despawn any of them, it only stops new
+    /// spawns until enough of them are despawned.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Projectile;
+    ///
+    /// let mut world = World::default();
+    /// world.set_entity_limit(2);
+    /// let first = world.try_spawn(Projectile).unwrap();
+    /// world.try_spawn(Projectile).unwrap();
+    /// assert!(matches!(
+    ///     world.try_spawn(Projectile),
+    ///     Err(SpawnError::QuotaExceeded { limit: 2, current: 2, .. })
+    /// ));
+    /// // Despawning frees the quota right away.
+    /// world.despawn(first);
+    /// assert!(world.try_spawn(Projectile).is_ok());
+    /// ```
+    pub fn set_entity_limit(&mut self, max_live: u32) {
+        self.quotas.entities = Some(max_live);
+    }
+
+    /// Remove the limit of [`World::set_entity_limit`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// world.set_entity_limit(0);
+    /// assert!(world.try_spawn(()).is_err());
+    /// world.remove_entity_limit();
+    /// assert!(world.try_spawn(()).is_ok());
+    /// ```
+    pub fn remove_entity_limit(&mut self) {
+        self.quotas.entities = None;
+    }
+
+    /// The limit of [`World::set_entity_limit`], if there is one.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// let mut world = World::default();
+    /// assert_eq!(world.entity_limit(), None);
+    /// world.set_entity_limit(1000);
+    /// assert_eq!(world.entity_limit(), Some(1000));
+    /// ```
+    pub fn entity_limit(&self) -> Option<u32> {
+        self.quotas.entities
+    }
+
+    /// Limit how many entities of the archetype `A` (exactly `A`, not archetypes that contain it) can be alive at
+    /// once. It's checked like [`World::set_entity_limit`]. Entities that move into the archetype from others (for
+    /// example, when a component is inserted) count towards the limit, but aren't stopped by it.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Boss;
+    /// #[derive(Component)]
+    /// struct Minion;
+    ///
+    /// let mut world = World::default();
+    /// world.set_archetype_limit::<Boss>(1);
+    /// world.try_spawn(Boss).unwrap();
+    /// assert!(world.try_spawn(Boss).is_err());
+    /// // Other archetypes aren't limited, even if they contain `Boss`.
+    /// world.try_spawn((Boss, Minion)).unwrap();
+    /// world.try_spawn(Minion).unwrap();
+    /// ```
+    ///
+    /// # Panics
+    /// If the components of `A` can't be registered, or if `A` has the same component more than once.
+    #[track_caller]
+    pub fn set_archetype_limit<A: Archetype>(&mut self, max: usize) {
+        let key = A::get_info_or_register(&mut self.components).prime_key();
+        self.set_archetype_limit_by_key(key, max);
+    }
+
+    /// Like [`World::set_archetype_limit`], for an archetype that is only known at runtime, by its [`PrimeArchKey`]
+    /// (see [`ArchetypeInfo::prime_key`](crate::archetype::ArchetypeInfo::prime_key)).
+    ///
+    /// ```
+    /// use worlds_ecs::archetype::Archetype;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Boss;
+    ///
+    /// let mut world = World::default();
+    /// world.register_component::<Boss>();
+    /// let key = Boss::prime_key(world.components()).unwrap();
+    /// world.set_archetype_limit_by_key(key, 0);
+    /// assert!(world.try_spawn(Boss).is_err());
+    /// ```
+    pub fn set_archetype_limit_by_key(&mut self, key: PrimeArchKey, max: usize) {
+        self.quotas.archetypes.insert(key, max);
+    }
+
+    /// Remove the limit of [`World::set_archetype_limit_by_key`], and return it.
+    ///
+    /// ```
+    /// use worlds_ecs::archetype::Archetype;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Boss;
+    ///
+    /// let mut world = World::default();
+    /// world.set_archetype_limit::<Boss>(0);
+    /// let key = Boss::prime_key(world.components()).unwrap();
+    /// assert_eq!(world.remove_archetype_limit_by_key(key), Some(0));
+    /// assert_eq!(world.remove_archetype_limit_by_key(key), None);
+    /// world.try_spawn(Boss).unwrap();
+    /// ```
+    pub fn remove_archetype_limit_by_key(&mut self, key: PrimeArchKey) -> Option<usize> {
+        self.quotas.archetypes.remove(&key)
+    }
+
+    /// How many entities count towards [`World::set_entity_limit`]: the entities that are alive, and neither archived
+    /// nor tombstones.
+    pub(crate) fn live_entity_count(&self) -> usize {
+        self.entities.entities() as usize - self.archive.len() - self.tombstones.len()
+    }
+
+    /// Returns an error if spawning `incoming` more entities of the archetype `key` would exceed a quota.
+    pub(crate) fn check_quota(&self, key: PrimeArchKey, incoming: usize) -> Result<(), SpawnError> {
+        self.check_quota_after(0, key, incoming)
+    }
+
+    /// Like [`World::check_quota`], after `pending` entities (of other archetypes) that the same operation is
+    /// about to spawn.
+    pub(crate) fn check_quota_after(
+        &self,
+        pending: usize,
+        key: PrimeArchKey,
+        incoming: usize,
+    ) -> Result<(), SpawnError> {
+        if self.quotas.is_empty() {
+            return Ok(());
+        }
+        let stored = self
+            .storages
+            .arch_storages
+            .get_storage_with_exact_archetype(key)
+            .map_or(0, |storage| storage.len());
+        self.quotas.check(
+            self.live_entity_count() + pending,
+            key,
+            stored,
+            incoming,
+            &self.components,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QuotaScope, SpawnError};
+    use crate::{archetype::Archetype, prelude::*};
+    use std::any::type_name;
+
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Bullet(u32);
+    #[derive(Component, Clone, Copy, Debug, PartialEq, Default)]
+    struct Enemy(u32);
+
+    fn exceeded(scope: QuotaScope, limit: usize, current: usize) -> SpawnError {
+        SpawnError::QuotaExceeded {
+            scope,
+            limit,
+            current,
+        }
+    }
+
+    #[test]
+    fn test_entity_limit() {
+        let mut world = World::default();
+        world.set_entity_limit(3);
+        let entities: Vec<EntityId> = (0..3).map(|i| world.spawn(Bullet(i))).collect();
+        assert_eq!(
+            world.try_spawn(Enemy(0)),
+            Err(exceeded(QuotaScope::Entities, 3, 3))
+        );
+        // Despawns free quota immediately.
+        world.despawn(entities[0]);
+        let enemy = world.try_spawn(Enemy(0)).unwrap();
+        assert!(world.try_spawn(Enemy(1)).is_err());
+        assert_eq!(world.clear(), 3);
+        assert!(world.try_spawn(Enemy(1)).is_ok());
+        assert_eq!(world.get_component::<Enemy>(enemy), None);
+
+        world.remove_entity_limit();
+        for i in 0..10 {
+            world.spawn(Bullet(i));
+        }
+        assert_eq!(world.entity_limit(), None);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_archetype_limit() {
+        let mut world = World::default();
+        world.set_archetype_limit::<Bullet>(2);
+        world.spawn(Bullet(0));
+        let bullet = world.spawn(Bullet(1));
+        let error = world.try_spawn(Bullet(2)).unwrap_err();
+        assert_eq!(
+            error,
+            exceeded(
+                QuotaScope::Archetype {
+                    key: Bullet::prime_key(&world.components).unwrap(),
+                    components: vec![type_name::<Bullet>()],
+                },
+                2,
+                2
+            )
+        );
+        assert!(error.to_string().contains("quota of 2 entities of ("));
+        // Other archetypes, even ones that contain the limited one, aren't limited.
+        world.spawn((Bullet(2), Enemy(0)));
+        world.spawn(Enemy(1));
+        world.despawn(bullet);
+        world.spawn(Bullet(3));
+
+        let key = Bullet::prime_key(&world.components).unwrap();
+        assert_eq!(world.remove_archetype_limit_by_key(key), Some(2));
+        world.spawn(Bullet(4));
+        assert_eq!(world.iter_component::<Bullet>().count(), 4);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "worlds_ecs: spawn failed: spawning would exceed the quota of 1 live entities"
+    )]
+    fn test_spawn_panics() {
+        let mut world = World::default();
+        world.set_entity_limit(1);
+        world.spawn(Bullet(0));
+        world.spawn(Bullet(1));
+    }
+
+    #[test]
+    fn test_batches_are_all_or_nothing() {
+        let mut world = World::default();
+        let arch_info = Bullet::get_info_or_register(&mut world.components);
+        world.set_archetype_limit_by_key(arch_info.prime_key(), 5);
+        world.spawn(Bullet(100));
+        // SAFETY: Every bundle stores exactly a `Bullet`.
+        let spawned = unsafe { world.spawn_batch_with_info(&arch_info, (0..4).map(Bullet)) };
+        assert_eq!(spawned.unwrap().len(), 4);
+
+        world.despawn_matching::<Has<Bullet>>();
+        world.spawn(Bullet(100));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            // SAFETY: Every bundle stores exactly a `Bullet`.
+            unsafe { world.spawn_batch_with_info(&arch_info, (0..5).map(Bullet)) }
+        }));
+        assert!(result.is_err());
+        // Nothing from the batch was spawned.
+        assert_eq!(world.iter_component::<Bullet>().count(), 1);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_streamed_batches_are_checked_as_they_come() {
+        let mut world = World::default();
+        world.set_entity_limit(3);
+        // The size of the batch isn't known up front, so it's checked bundle by bundle.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            world.spawn_batch((0..5).filter(|_| true).map(Bullet))
+        }));
+        assert!(result.is_err());
+        assert_eq!(world.iter_component::<Bullet>().count(), 3);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_required_components_are_counted_in_the_completed_archetype() {
+        let mut world = World::default();
+        world.require_component_with_default::<Bullet, Enemy>();
+        world.set_archetype_limit::<(Bullet, Enemy)>(1);
+        world.spawn(Bullet(0));
+        assert!(matches!(
+            world.try_spawn(Bullet(1)),
+            Err(SpawnError::QuotaExceeded { limit: 1, .. })
+        ));
+    }
+}
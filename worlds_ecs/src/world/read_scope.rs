@@ -0,0 +1,597 @@
+use super::{
+    storage::{
+        storages::{ArchStorageId, ArchetypeId},
+        ArchEntityStorage,
+    },
+    World,
+};
+use crate::{
+    entity::EntityId,
+    prelude::{Component, ComponentFactory, FetchContext, ReadOnlyArchQuery},
+    utils::panics,
+    world::storage::arch_storage::ArchStorageIndex,
+};
+use std::{marker::PhantomData, ops::Range};
+
+/// A read-only view of a [`World`], that can be shared with scoped threads (see [`World::read_scope`]).
+/// It only exposes APIs that read from the world, and only [`ReadOnlyArchQuery`]s can be queried through it.
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Cost(u32);
+///
+/// let mut world = World::default();
+/// for i in 0..100 {
+///     world.spawn(Cost(i));
+/// }
+/// let total = world.read_scope(|scope| {
+///     std::thread::scope(|s| {
+///         let jobs: Vec<_> = scope
+///             .par_chunks::<&Cost>(30)
+///             .into_iter()
+///             .map(|chunk| s.spawn(move || chunk.iter().map(|cost| cost.0).sum::<u32>()))
+///             .collect();
+///         jobs.into_iter().map(|job| job.join().unwrap()).sum::<u32>()
+///     })
+/// });
+/// assert_eq!(total, (0..100).sum::<u32>());
+/// ```
+///
+/// The world can't be mutated while the scope is alive, not even by the closure that got the scope:
+///
+/// ```compile_fail
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Cost(u32);
+///
+/// let mut world = World::default();
+/// world.read_scope(|scope| {
+///     world.spawn(Cost(0));
+/// });
+/// ```
+///
+/// And the scope can't be used to query components mutably:
+///
+/// ```compile_fail
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Cost(u32);
+///
+/// let mut world = World::default();
+/// world.read_scope(|scope| {
+///     for cost in scope.query::<&mut Cost>() {
+///         cost.0 += 1;
+///     }
+/// });
+/// ```
+///
+/// Components with interior mutability (like atomics, or a [`Mutex`](std::sync::Mutex)) can be mutated through the
+/// shared references of the scope, from every thread. This is sound: the components are only ever borrowed through
+/// the world, and the `UnsafeCell` is inside the component, so a shared reference to it allows what the component
+/// allows. For example, to accumulate contacts into the bodies they hit:
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+/// use std::sync::{
+///     atomic::{AtomicU32, Ordering},
+///     Mutex,
+/// };
+///
+/// #[derive(Component)]
+/// struct Probe(EntityId);
+/// #[derive(Component, Default)]
+/// struct Contacts(Mutex<Vec<EntityId>>, AtomicU32);
+///
+/// let mut world = World::default();
+/// world.register_interior_mutable_component::<Contacts>();
+/// let body = world.spawn(Contacts::default());
+/// for _ in 0..100 {
+///     world.spawn(Probe(body));
+/// }
+/// world.read_scope(|scope| {
+///     std::thread::scope(|s| {
+///         for chunk in scope.par_chunks::<(EntityId, &Probe)>(25) {
+///             s.spawn(move || {
+///                 for (probe, target) in chunk.iter() {
+///                     let contacts = scope.get_component::<Contacts>(target.0).unwrap();
+///                     contacts.0.lock().unwrap().push(probe);
+///                     contacts.1.fetch_add(1, Ordering::Relaxed);
+///                 }
+///             });
+///         }
+///     })
+/// });
+/// let contacts = world.get_component::<Contacts>(body).unwrap();
+/// assert_eq!(contacts.0.lock().unwrap().len(), 100);
+/// assert_eq!(contacts.1.load(Ordering::Relaxed), 100);
+/// ```
+///
+/// The world doesn't know about these mutations: they aren't recorded as writes by [`World::record_access`]. The
+/// world has no change detection, so there's no `Changed<C>` filter that misses them, and no `mark_changed` to
+/// report them with. Copies of the world (see [`World::clone_cow`]) share a storage until it's accessed mutably, so
+/// components like these must be registered with [`World::register_interior_mutable_component`], whose storages
+/// aren't shared by the copies.
+pub struct WorldReadScope<'w> {
+    world: &'w World,
+}
+
+impl World {
+    /// Share the world with scoped threads that only read from it: `f` gets a [`WorldReadScope`], which is [`Sync`],
+    /// so it can be used from every thread spawned with [`std::thread::scope`] inside `f`.
+    ///
+    /// The world is borrowed mutably for as long as `f` runs, so nothing can mutate it until `f` (and so every
+    /// scoped thread) returns. This makes it safe to run read-heavy background work (like pathfinding) against
+    /// the live world, without copying it.
+    /// Components with interior mutability can still be mutated from the threads (see [`WorldReadScope`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Cost(u32);
+    ///
+    /// let mut world = World::default();
+    /// for i in 0..100 {
+    ///     world.spawn(Cost(i));
+    /// }
+    /// let total = world.read_scope(|scope| {
+    ///     let chunks = scope.par_chunks::<&Cost>(25);
+    ///     std::thread::scope(|s| {
+    ///         let workers: Vec<_> = chunks
+    ///             .iter()
+    ///             .map(|chunk| s.spawn(|| chunk.iter().map(|cost| cost.0).sum::<u32>()))
+    ///             .collect();
+    ///         workers.into_iter().map(|worker| worker.join().unwrap()).sum::<u32>()
+    ///     })
+    /// });
+    /// assert_eq!(total, 4950);
+    /// ```
+    pub fn read_scope<R>(&mut self, f: impl FnOnce(&WorldReadScope<'_>) -> R) -> R {
+        f(&WorldReadScope { world: self })
+    }
+}
+
+impl<'w> WorldReadScope<'w> {
+    /// Query the world for components that are only read. See [`World::query_shared`].
+    pub fn query<Q: ReadOnlyArchQuery>(&self) -> impl Iterator<Item = Q::Item<'w>> + 'w {
+        self.world.query_shared::<Q>()
+    }
+
+    /// Get a reference to a [`Component`] of an entity. See [`World::get_component`].
+    pub fn get_component<C: Component>(&self, entity: EntityId) -> Option<&'w C> {
+        self.world.get_component::<C>(entity)
+    }
+
+    /// Returns `true` if the entity has the [`Component`] `C`. See [`World::contains_component`].
+    pub fn contains_component<C: Component>(&self, entity: EntityId) -> bool {
+        self.world.contains_component::<C>(entity)
+    }
+
+    /// Iterate over every instance of a [`Component`], alongside the [`EntityId`] of its entity.
+    /// See [`World::iter_component`].
+    pub fn iter_component<C: Component>(&self) -> impl Iterator<Item = (EntityId, &'w C)> + 'w {
+        self.world.iter_component::<C>()
+    }
+
+    /// The amount of entities that have the [`Component`] `C`. See [`World::component_count`].
+    pub fn component_count<C: Component>(&self) -> usize {
+        self.world.component_count::<C>()
+    }
+
+    /// Split the matches of a query into [`QueryChunk`]s of at most `chunk_size` entities each, to be distributed
+    /// between threads. Every chunk is a range of rows of a single storage, so the chunks are cheap to hand out,
+    /// and every match is in exactly one chunk. If some of the components aren't registered, there are no chunks.
+    ///
+    /// # Panics
+    /// If `chunk_size` is 0.
+    #[track_caller]
+    pub fn par_chunks<Q: ReadOnlyArchQuery>(&self, chunk_size: usize) -> Vec<QueryChunk<'w, Q>> {
+        if chunk_size == 0 {
+            panics::fail("par_chunks", "the chunk size must be greater than 0", &[]);
+        }
+        let Some(pkey) = self.world.resolve_query_key::<Q>() else {
+            return Vec::new();
+        };
+        let (arch_storages, components) =
+            (&self.world.storages.arch_storages, &self.world.components);
+        let tags = self.world.entity_tags();
+        let mut chunks = Vec::new();
+        let mut sid = ArchStorageId(0);
+        while let Some(id) = arch_storages.next_storage_with_matching_archetype(sid, pkey) {
+            sid = ArchStorageId(id.0 + 1);
+            let storage = arch_storages.get_storage(id).unwrap();
+            let archetype_id = arch_storages.archetype_of_storage(id).unwrap();
+            let ctx = FetchContext::new(tags).for_query::<Q>(storage, components);
+            chunks.extend(
+                (0..storage.len())
+                    .step_by(chunk_size)
+                    .map(|start| QueryChunk {
+                        storage_id: id,
+                        archetype_id,
+                        storage,
+                        rows: start..storage.len().min(start + chunk_size),
+                        components,
+                        ctx,
+                        _query: PhantomData,
+                    }),
+            );
+        }
+        chunks
+    }
+}
+
+/// A chunk of the matches of a query, from a single storage. See [`WorldReadScope::par_chunks`].
+pub struct QueryChunk<'w, Q: ReadOnlyArchQuery> {
+    storage_id: ArchStorageId,
+    archetype_id: ArchetypeId,
+    storage: &'w ArchEntityStorage,
+    rows: Range<usize>,
+    components: &'w ComponentFactory,
+    ctx: FetchContext<'w>,
+    _query: PhantomData<fn() -> Q>,
+}
+
+impl<'w, Q: ReadOnlyArchQuery> QueryChunk<'w, Q> {
+    /// The [`ArchStorageId`] of the storage that the chunk is from.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Cost(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Cost(1));
+    /// let storage = world.entity_location(entity).unwrap().0;
+    /// world.read_scope(|scope| {
+    ///     let chunks = scope.par_chunks::<&Cost>(16);
+    ///     assert_eq!(chunks[0].storage_id(), storage);
+    /// });
+    /// ```
+    pub fn storage_id(&self) -> ArchStorageId {
+        self.storage_id
+    }
+
+    /// The [`ArchetypeId`] of the archetype of the storage that the chunk is from.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Cost(u32);
+    /// #[derive(Component)]
+    /// struct Heavy;
+    ///
+    /// let mut world = World::default();
+    /// let light = world.spawn(Cost(1));
+    /// let heavy = world.spawn((Cost(2), Heavy));
+    /// let expected = [world.archetype_id_of(light), world.archetype_id_of(heavy)];
+    /// world.read_scope(|scope| {
+    ///     let chunks = scope.par_chunks::<&Cost>(16);
+    ///     let archetypes: Vec<_> = chunks.iter().map(|chunk| Some(chunk.archetype_id())).collect();
+    ///     assert_eq!(archetypes, expected);
+    /// });
+    /// ```
+    pub fn archetype_id(&self) -> ArchetypeId {
+        self.archetype_id
+    }
+
+    /// The amount of matches in the chunk.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Cost(u32);
+    ///
+    /// let mut world = World::default();
+    /// for i in 0..10 {
+    ///     world.spawn(Cost(i));
+    /// }
+    /// world.read_scope(|scope| {
+    ///     let lens: Vec<usize> = scope.par_chunks::<&Cost>(4).iter().map(|chunk| chunk.len()).collect();
+    ///     assert_eq!(lens, vec![4, 4, 2]);
+    /// });
+    /// ```
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns `true` if there are no matches in the chunk.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Cost(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Cost(1));
+    /// world.read_scope(|scope| {
+    ///     assert!(scope.par_chunks::<&Cost>(4).iter().all(|chunk| !chunk.is_empty()));
+    /// });
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Iterate over the matches in the chunk.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Cost(u32);
+    ///
+    /// let mut world = World::default();
+    /// for i in 0..4 {
+    ///     world.spawn(Cost(i));
+    /// }
+    /// world.read_scope(|scope| {
+    ///     let chunks = scope.par_chunks::<&Cost>(2);
+    ///     let costs: Vec<u32> = chunks[1].iter().map(|cost| cost.0).collect();
+    ///     assert_eq!(costs, vec![2, 3]);
+    /// });
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = Q::Item<'w>> + 'w {
+        let (storage, components, ctx) = (self.storage, self.components, self.ctx);
+        self.rows.clone().map(move |row| {
+            // SAFETY: The rows of the chunk are in bounds of its storage (which can't change while it's borrowed),
+            // and `Q` only reads, so nothing is mutated through the pointer.
+            unsafe {
+                Q::fetch(
+                    storage as *const ArchEntityStorage as *mut ArchEntityStorage,
+                    ArchStorageIndex(row),
+                    components,
+                    ctx,
+                )
+            }
+        })
+    }
+}
+
+impl<Q: ReadOnlyArchQuery> Clone for QueryChunk<'_, Q> {
+    fn clone(&self) -> Self {
+        QueryChunk {
+            rows: self.rows.clone(),
+            _query: PhantomData,
+            ..*self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::{
+        collections::HashSet,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Mutex,
+        },
+    };
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Cell(usize);
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Blocked;
+
+    #[derive(Component)]
+    struct Unused;
+
+    fn navgrid() -> (World, Vec<EntityId>) {
+        let mut world = World::default();
+        let cells = (0..1000)
+            .map(|i| {
+                if i % 7 == 0 {
+                    world.spawn((Cell(i), Blocked))
+                } else {
+                    world.spawn(Cell(i))
+                }
+            })
+            .collect();
+        (world, cells)
+    }
+
+    #[test]
+    fn test_read_scope_threads() {
+        let (mut world, cells) = navgrid();
+        let cells = &cells;
+        let open = world.read_scope(|scope| {
+            std::thread::scope(|s| {
+                let counters: Vec<_> = (0..4)
+                    .map(|t| {
+                        s.spawn(move || {
+                            let open = scope
+                                .query::<(&Cell, Not<Has<Blocked>>)>()
+                                .filter(|(_, open)| *open)
+                                .count();
+                            for (i, entity) in cells.iter().enumerate().skip(t).step_by(4) {
+                                assert_eq!(scope.get_component::<Cell>(*entity), Some(&Cell(i)));
+                                assert_eq!(
+                                    scope.contains_component::<Blocked>(*entity),
+                                    i % 7 == 0
+                                );
+                            }
+                            open
+                        })
+                    })
+                    .collect();
+                let blocked = scope.component_count::<Blocked>();
+                assert_eq!(scope.iter_component::<Blocked>().count(), blocked);
+                counters
+                    .into_iter()
+                    .map(|counter| counter.join().unwrap())
+                    .inspect(|open| assert_eq!(open + blocked, 1000))
+                    .count()
+            })
+        });
+        assert_eq!(open, 4);
+        // The world can be mutated again once the scope is over.
+        world.spawn(Cell(1000));
+    }
+
+    /// A probe that hits a body.
+    #[derive(Component, Clone)]
+    struct Probe(EntityId, u32);
+
+    /// The contacts of a body, accumulated through shared references.
+    #[derive(Component, Default)]
+    struct Contacts {
+        hits: Mutex<Vec<(EntityId, u32)>>,
+        force: AtomicU32,
+    }
+
+    impl Clone for Contacts {
+        fn clone(&self) -> Self {
+            Contacts {
+                hits: Mutex::new(self.hits.lock().unwrap().clone()),
+                force: AtomicU32::new(self.force.load(Ordering::Relaxed)),
+            }
+        }
+    }
+
+    fn bodies() -> (World, Vec<EntityId>) {
+        let mut world = World::default();
+        world.register_cloneable_component::<Probe>();
+        world.register_cloneable_component::<Contacts>();
+        world.register_interior_mutable_component::<Contacts>();
+        let bodies: Vec<EntityId> = (0..8).map(|_| world.spawn(Contacts::default())).collect();
+        for i in 0..2000 {
+            world.spawn(Probe(bodies[i % bodies.len()], i as u32));
+        }
+        (world, bodies)
+    }
+
+    fn assert_contacts(world: &World, bodies: &[EntityId]) {
+        for (b, body) in bodies.iter().enumerate() {
+            let contacts = world.get_component::<Contacts>(*body).unwrap();
+            let mut forces: Vec<u32> = contacts
+                .hits
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(_, force)| *force)
+                .collect();
+            forces.sort();
+            let expected: Vec<u32> = (0..2000).skip(b).step_by(bodies.len()).collect();
+            assert_eq!(
+                contacts.force.load(Ordering::Relaxed),
+                expected.iter().sum::<u32>()
+            );
+            assert_eq!(forces, expected);
+        }
+    }
+
+    /// Mutate the contacts of the bodies from the probes, from scoped threads.
+    fn hit_bodies_in_scope(scope: &WorldReadScope<'_>) {
+        std::thread::scope(|s| {
+            for chunk in scope.par_chunks::<(EntityId, &Probe)>(100) {
+                s.spawn(move || {
+                    for (probe, Probe(body, force)) in chunk.iter() {
+                        let contacts = scope.get_component::<Contacts>(*body).unwrap();
+                        contacts.hits.lock().unwrap().push((probe, *force));
+                        contacts.force.fetch_add(*force, Ordering::Relaxed);
+                    }
+                });
+            }
+        })
+    }
+
+    #[test]
+    fn test_interior_mutability_from_threads() {
+        let (mut world, bodies) = bodies();
+        world.read_scope(hit_bodies_in_scope);
+        assert_contacts(&world, &bodies);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_interior_mutability_through_shared_queries() {
+        let (world, bodies) = bodies();
+        for (probe, Probe(body, force)) in world.query_shared::<(EntityId, &Probe)>() {
+            // The contacts are read by another shared query while the probes are iterated.
+            let (_, contacts) = world
+                .query_shared::<(EntityId, &Contacts)>()
+                .find(|(entity, _)| entity == body)
+                .unwrap();
+            contacts.hits.lock().unwrap().push((probe, *force));
+            contacts.force.fetch_add(*force, Ordering::Relaxed);
+        }
+        assert_contacts(&world, &bodies);
+    }
+
+    /// Mutate the contacts of the bodies from the probes, through a shared query.
+    fn hit_bodies(world: &World) {
+        for (probe, Probe(body, force)) in world.query_shared::<(EntityId, &Probe)>() {
+            let contacts = world.get_component::<Contacts>(*body).unwrap();
+            contacts.hits.lock().unwrap().push((probe, *force));
+            contacts.force.fetch_add(*force, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_shared_mutation_doesnt_reach_clones() {
+        let (world, bodies) = bodies();
+        let snapshot = world.clone_cow().unwrap();
+        hit_bodies(&world);
+        assert_contacts(&world, &bodies);
+        for body in &bodies {
+            let contacts = snapshot.get_component::<Contacts>(*body).unwrap();
+            assert!(contacts.hits.lock().unwrap().is_empty());
+            assert_eq!(contacts.force.load(Ordering::Relaxed), 0);
+        }
+
+        // Mutating the copy from a read scope doesn't reach the original either.
+        let mut copy = snapshot.clone_cow().unwrap();
+        copy.read_scope(hit_bodies_in_scope);
+        assert_contacts(&copy, &bodies);
+        assert_contacts(&world, &bodies);
+        let snapshot_force: u32 = snapshot
+            .query_shared::<&Contacts>()
+            .map(|contacts| contacts.force.load(Ordering::Relaxed))
+            .sum();
+        assert_eq!(snapshot_force, 0);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_par_chunks() {
+        let (mut world, _) = navgrid();
+        world.read_scope(|scope| {
+            let chunks = scope.par_chunks::<(EntityId, &Cell)>(64);
+            assert!(chunks
+                .iter()
+                .all(|chunk| !chunk.is_empty() && chunk.len() <= 64));
+            assert_eq!(chunks.iter().map(|chunk| chunk.len()).sum::<usize>(), 1000);
+            let storages: HashSet<_> = chunks.iter().map(|chunk| chunk.storage_id()).collect();
+            assert_eq!(storages.len(), 2);
+
+            let seen = std::thread::scope(|s| {
+                let jobs: Vec<_> = chunks
+                    .chunks(chunks.len().div_ceil(3))
+                    .map(|jobs| {
+                        s.spawn(move || {
+                            jobs.iter()
+                                .flat_map(|chunk| chunk.iter())
+                                .map(|(_, cell)| cell.0)
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                jobs.into_iter()
+                    .flat_map(|job| job.join().unwrap())
+                    .collect::<HashSet<_>>()
+            });
+            assert_eq!(seen, (0..1000).collect());
+
+            assert!(scope.par_chunks::<&Unused>(64).is_empty());
+            assert_eq!(scope.par_chunks::<&Blocked>(1000).len(), 1);
+        });
+    }
+}
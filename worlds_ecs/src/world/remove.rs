@@ -0,0 +1,431 @@
+use super::{
+    access::AccessKind,
+    storage::{arch_storage::ArchStorageIndex, ArchEntityStorage},
+    World,
+};
+use crate::{
+    archetype::ArchetypeInfo,
+    bundle::Bundle,
+    component::{take_component, Component, ComponentFactory, ComponentId},
+    entity::{EntityId, EntityMeta},
+    utils::panics,
+};
+use bevy_ptr::OwningPtr;
+use std::panic::Location;
+
+/// The components of an entity, as they are swap-removed from its storage, without the component `C`, which is
+/// moved out to `removed` instead (see [`World::remove_component`]).
+struct WithoutComponent<'a, C> {
+    source: &'a mut ArchEntityStorage,
+    index: ArchStorageIndex,
+    comp_id: ComponentId,
+    removed: &'a mut Option<C>,
+    /// The entity that was moved into the index of the removed row (see [`ArchEntityStorage::swap_remove`]).
+    swapped: &'a mut Option<EntityId>,
+}
+
+impl<C: Component> Bundle for WithoutComponent<'_, C> {
+    fn raw_components_scope(
+        self,
+        _comp_factory: &ComponentFactory,
+        f: &mut impl FnMut(ComponentId, OwningPtr<'_>),
+    ) {
+        let WithoutComponent {
+            source,
+            index,
+            comp_id,
+            removed,
+            swapped,
+        } = self;
+        // SAFETY: Every component is moved out, either to `f` or to `removed`.
+        *swapped = unsafe {
+            source.swap_remove_and_forget(index, |id, raw_comp| match id == comp_id {
+                // SAFETY: The component with the id of `C` is a stored `C`, which isn't shared.
+                true => *removed = Some(take_component::<C>(raw_comp)),
+                false => f(id, raw_comp),
+            })
+        };
+    }
+}
+
+impl World {
+    /// Remove the [`Component`] `C` from an entity, and return it. The other components of the entity are moved
+    /// to the storage of its archetype without `C` (the storage whose
+    /// [`PrimeArchKey`](crate::archetype::key::PrimeArchKey) is the entity's key divided by the prime of `C`),
+    /// which is created if it doesn't exist yet. Nothing is dropped: `C` is handed back, and the other components
+    /// are moved, not copied. Returns `None`, and changes nothing, if the entity isn't alive (or is archived, see
+    /// [`World::archive`]), or doesn't have `C`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Debug, PartialEq)]
+    /// struct Health(u32);
+    /// #[derive(Component)]
+    /// struct Burning;
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn((Health(10), Burning));
+    /// assert!(world.remove_component::<Burning>(entity).is_some());
+    /// assert!(!world.contains_component::<Burning>(entity));
+    /// assert_eq!(world.get_component::<Health>(entity), Some(&Health(10)));
+    /// assert!(world.remove_component::<Burning>(entity).is_none());
+    /// ```
+    ///
+    /// # Panics
+    /// If `C` is shared (see [`Component::SHARED`]), because its value belongs to every entity that has it. If
+    /// either storage is pinned (see [`World::pin_storage`]). If the entity would break a component rule (see
+    /// [`World::require_component`]), or exceed the quota of its new archetype (see
+    /// [`World::set_archetype_limit`]).
+    #[track_caller]
+    pub fn remove_component<C: Component>(&mut self, entity: EntityId) -> Option<C> {
+        if C::SHARED {
+            panics::fail_component(
+                "remove_component",
+                "shared components can't be moved out of the world",
+                std::any::type_name::<C>(),
+            )
+        }
+        self.access
+            .record_component::<C>(&self.components, AccessKind::Write, Location::caller());
+        let entity_meta = *self.entities.get_entity_meta(entity)?;
+        let comp_id = self.components.get_component_id::<C>()?;
+        if entity_meta.is_archived() || !entity_meta.component_mask.contains(comp_id) {
+            return None;
+        }
+        let old_sid = entity_meta.archetype_storage_id;
+        if let Err(error) = self.check_pin_for_remove(old_sid) {
+            panics::fail_entity("remove_component", error, entity);
+        }
+        let storage = self.storages.arch_storages.get_storage(old_sid).unwrap();
+        let key = storage.prime_key().without_component(comp_id);
+        let mut component_ids: Vec<ComponentId> = storage
+            .component_ids()
+            .filter(|id| *id != comp_id)
+            .collect();
+        component_ids.sort_unstable();
+        let arch_info = ArchetypeInfo::from_component_ids(component_ids);
+        match self.check_component_rules(entity, key, |_| arch_info.clone()) {
+            Ok(None) => {}
+            // The missing requirement would be inserted with its default value, which is `C` itself (unless the
+            // entity was spawned before the rule was declared), so removing it would replace it instead.
+            Ok(Some(_)) => panics::fail_entity(
+                "remove_component",
+                "another component of the entity requires it",
+                entity,
+            ),
+            Err(error) => panics::fail_entity("remove_component", error, entity),
+        }
+        if !self.quotas.is_empty() {
+            let stored = self
+                .storages
+                .arch_storages
+                .get_storage_with_exact_archetype(key)
+                .map_or(0, |storage| storage.len());
+            // The entity is already alive, so only the quota of its new archetype can be exceeded.
+            let live = self.live_entity_count() - 1;
+            if let Err(error) = self.quotas.check(live, key, stored, 1, &self.components) {
+                panics::fail_entity("remove_component", error, entity);
+            }
+        }
+        let (new_sid, _) = self
+            .storages
+            .arch_storages
+            .get_mut_or_create_storage_with_info(&arch_info, &self.components)
+            .expect("The components of a stored entity are registered");
+        if let Err(error) = self.check_pin_for_store(new_sid, 1) {
+            panics::fail_entity("remove_component", error, entity);
+        }
+
+        let (source, target) = self
+            .storages
+            .arch_storages
+            .get_two_storages_mut(old_sid, new_sid);
+        // A sort-maintained storage keeps its order, so the row is moved to the end and removed from there.
+        let (sorted, mut index) = (
+            source.is_sort_maintained(),
+            entity_meta.archetype_storage_index,
+        );
+        if sorted {
+            let last = ArchStorageIndex(source.len() - 1);
+            source.move_row(index, last);
+            index = last;
+        }
+        let (mut removed, mut swapped) = (None, None);
+        let bundle = WithoutComponent {
+            source,
+            index,
+            comp_id,
+            removed: &mut removed,
+            swapped: &mut swapped,
+        };
+        // SAFETY: The bundle stores the components of the entity without `C`, which are the components of the
+        // target storage.
+        let new_index = unsafe { target.store_entity_unchecked(entity, bundle, &self.components) };
+        let component_mask = target.component_mask();
+        let target_sorted = target.is_sort_maintained();
+        let source = self.storages.arch_storages.get_storage(old_sid).unwrap();
+        if sorted {
+            let first = entity_meta.archetype_storage_index.0;
+            for (row, entity_to_update) in source.entities().iter().enumerate().skip(first) {
+                self.entities
+                    .set_entity_arch_storage_index(ArchStorageIndex(row), *entity_to_update);
+            }
+        } else if let Some(entity_to_update) = swapped {
+            self.entities
+                .set_entity_arch_storage_index(index, entity_to_update);
+        }
+        self.entities.set_entity_meta(
+            EntityMeta {
+                archetype_storage_id: new_sid,
+                archetype_storage_index: new_index,
+                component_mask,
+            },
+            entity,
+        );
+        self.ordered.removed(entity);
+        self.ordered.stored(entity, component_mask);
+        if target_sorted {
+            self.sort_rows_from(new_sid, new_index.0);
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, test_utils::*, world::storage::storages::ArchStorageId};
+    use std::{
+        panic::{catch_unwind, AssertUnwindSafe},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Health(u32);
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Armor(u32);
+    #[derive(Component, Debug, PartialEq)]
+    struct Burning;
+    #[derive(Component, Debug, PartialEq)]
+    #[component(boxed)]
+    struct Inventory(Vec<u32>);
+
+    fn sid_of(world: &World, entity: EntityId) -> ArchStorageId {
+        world
+            .entities
+            .get_entity_meta(entity)
+            .unwrap()
+            .archetype_storage_id
+    }
+
+    #[test]
+    fn test_remove_component() {
+        let mut world = World::default();
+        let entities: Vec<EntityId> = (0..5)
+            .map(|i| world.spawn((Health(i), Armor(i * 10), Burning)))
+            .collect();
+        let old_sid = sid_of(&world, entities[1]);
+        assert_eq!(
+            world.remove_component::<Armor>(entities[1]),
+            Some(Armor(10))
+        );
+
+        // The entity moved to the storage of its archetype without `Armor`.
+        let key = world
+            .storages
+            .arch_storages
+            .storage_key(sid_of(&world, entities[1]))
+            .unwrap();
+        let comp_id = world.components.get_component_id::<Armor>().unwrap();
+        let old_key = world.storages.arch_storages.storage_key(old_sid).unwrap();
+        assert_eq!(key, old_key.without_component(comp_id));
+        assert!(!world.contains_component::<Armor>(entities[1]));
+        assert_entity_has(&world, entities[1], Health(1));
+        assert!(world.contains_component::<Burning>(entities[1]));
+
+        // The last entity was swapped into the vacated row, and the others stayed.
+        for (i, entity) in entities.iter().enumerate().filter(|(i, _)| *i != 1) {
+            assert_entity_has(&world, *entity, Health(i as u32));
+            assert_entity_has(&world, *entity, Armor(i as u32 * 10));
+        }
+        assert_eq!(
+            world
+                .entities
+                .get_entity_meta(entities[4])
+                .unwrap()
+                .archetype_storage_index
+                .0,
+            1
+        );
+        assert_query_count::<&Armor>(&mut world, 4);
+        assert_query_count::<(&Health, &Burning)>(&mut world, 5);
+        world.assert_invariants();
+
+        // Removing every component leaves the entity alive, in the empty archetype.
+        world.remove_component::<Health>(entities[1]);
+        world.remove_component::<Burning>(entities[1]);
+        assert!(world.iter_entities().any(|entity| entity == entities[1]));
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_removing_a_missing_component() {
+        #[derive(Component)]
+        struct Unregistered;
+
+        let mut world = World::default();
+        let entity = world.spawn((Health(1), Burning));
+        world.spawn(Armor(2));
+        let sid = sid_of(&world, entity);
+        let storages = world.storages.arch_storages.storage_count();
+        let generation = world
+            .storages
+            .arch_storages
+            .get_storage(sid)
+            .unwrap()
+            .generation();
+
+        assert_eq!(world.remove_component::<Armor>(entity), None);
+        assert!(world.remove_component::<Unregistered>(entity).is_none());
+        let despawned = world.spawn(Armor(3));
+        world.despawn(despawned);
+        assert_eq!(world.remove_component::<Armor>(despawned), None);
+
+        let storage = world.storages.arch_storages.get_storage(sid).unwrap();
+        assert_eq!(storage.generation(), generation);
+        assert_eq!(sid_of(&world, entity), sid);
+        assert_eq!(world.storages.arch_storages.storage_count(), storages);
+        assert_entity_has(&world, entity, Health(1));
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_removed_components_are_dropped_once() {
+        #[derive(Component)]
+        struct Counted(Arc<AtomicUsize>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut world = World::default();
+        let entities: Vec<EntityId> = (0..3)
+            .map(|i| world.spawn((Counted(drops.clone()), Health(i))))
+            .collect();
+        let removed = world.remove_component::<Counted>(entities[0]).unwrap();
+        assert_eq!(drops.load(Ordering::Relaxed), 0);
+        drop(removed);
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        // The components that were moved along aren't dropped either.
+        let counted = world.spawn((Counted(drops.clone()), Inventory(vec![1, 2])));
+        assert_eq!(
+            world.remove_component::<Inventory>(counted),
+            Some(Inventory(vec![1, 2]))
+        );
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        world.clear();
+        assert_eq!(drops.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_boxed_and_marker_components() {
+        let mut world = World::default();
+        let entity = world.spawn((Inventory(vec![1, 2, 3]), Burning, Health(5)));
+        let other = world.spawn((Inventory(vec![4]), Burning, Health(6)));
+        assert_eq!(world.remove_component::<Burning>(entity), Some(Burning));
+        assert_eq!(
+            world.get_component::<Inventory>(entity),
+            Some(&Inventory(vec![1, 2, 3]))
+        );
+        assert_eq!(
+            world.remove_component::<Inventory>(entity),
+            Some(Inventory(vec![1, 2, 3]))
+        );
+        assert_eq!(
+            world.get_component::<Inventory>(other),
+            Some(&Inventory(vec![4]))
+        );
+        assert_entity_has(&world, entity, Health(5));
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_sort_maintained_storages_keep_their_order() {
+        let mut world = World::default();
+        let entities: Vec<EntityId> = [5, 1, 4, 2, 3]
+            .into_iter()
+            .map(|i| world.spawn((Health(i), Armor(i))))
+            .collect();
+        world.spawn(Health(0));
+        world.maintain_sort::<(Health, Armor), Health, u32>(|health| health.0);
+        world.maintain_sort::<Health, Health, u32>(|health| health.0);
+        world.remove_component::<Armor>(entities[3]);
+        world.remove_component::<Armor>(entities[0]);
+        let healths = |world: &mut World| -> Vec<u32> {
+            world
+                .query_filtered::<&Health, Has<Armor>>()
+                .map(|health| health.0)
+                .collect()
+        };
+        assert_eq!(healths(&mut world), [1, 3, 4]);
+        let without: Vec<u32> = world
+            .iter_component::<Health>()
+            .filter(|(entity, _)| !world.contains_component::<Armor>(*entity))
+            .map(|(_, health)| health.0)
+            .collect();
+        assert_eq!(without, [0, 2, 5]);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_ordered_index_follows_the_entity() {
+        let mut world = World::default();
+        world.track_ordered::<Armor>();
+        let entities: Vec<EntityId> = (0..3).map(|i| world.spawn((Health(i), Armor(i)))).collect();
+        world.remove_component::<Armor>(entities[1]);
+        let ordered: Vec<EntityId> = world
+            .iter_component_ordered::<Armor>()
+            .map(|(entity, _)| entity)
+            .collect();
+        let mut expected = vec![entities[0], entities[2]];
+        expected.sort_by_key(|entity| entity.to_bits());
+        assert_eq!(ordered, expected);
+    }
+
+    #[test]
+    fn test_pins_and_rules_leave_the_entity_untouched() {
+        let mut world = World::default();
+        let entity = world.spawn((Health(1), Armor(2), Burning));
+        let pin = world.pin_storage(sid_of(&world, entity));
+        let pinned = catch_unwind(AssertUnwindSafe(|| {
+            world.remove_component::<Burning>(entity)
+        }));
+        assert!(pinned.is_err());
+        drop(pin);
+
+        world.require_component::<Burning, Armor>();
+        let result = catch_unwind(AssertUnwindSafe(|| world.remove_component::<Armor>(entity)));
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.starts_with("worlds_ecs: remove_component failed: "));
+        assert_entity_has(&world, entity, Armor(2));
+        assert_eq!(world.remove_component::<Burning>(entity), Some(Burning));
+        world.assert_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "shared components can't be moved out of the world")]
+    fn test_shared_components_panic() {
+        #[derive(Component, Hash, PartialEq, Eq)]
+        #[component(shared)]
+        struct Team(u8);
+
+        let mut world = World::default();
+        let entity = world.spawn(Team(1));
+        world.remove_component::<Team>(entity);
+    }
+}
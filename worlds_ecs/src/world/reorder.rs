@@ -0,0 +1,374 @@
+use super::{pin::StoragePinned, storage::storages::ArchStorageId, World};
+use crate::{
+    archetype::Archetype, entity::EntityId, world::storage::arch_storage::ArchStorageIndex,
+};
+use std::fmt;
+
+/// An error when reordering the rows of a storage (see [`World::reorder_storage`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReorderError {
+    /// There is no storage with this id.
+    NoStorage(ArchStorageId),
+    /// The storage is pinned (see [`World::pin_storage`]), so its rows can't be moved.
+    Pinned(StoragePinned),
+    /// The storage is sort-maintained (see [`World::maintain_sort`]), so it's kept in its own order.
+    SortMaintained(ArchStorageId),
+}
+
+impl fmt::Display for ReorderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReorderError::NoStorage(id) => write!(f, "there is no storage {id:?}"),
+            ReorderError::Pinned(pinned) => write!(f, "can't reorder the storage: {pinned}"),
+            ReorderError::SortMaintained(id) => {
+                write!(
+                    f,
+                    "the storage {id:?} is sort-maintained, so it can't be reordered"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReorderError {}
+
+impl World {
+    /// Reorder the rows of a storage by the key that `order` computes from each of its entities (in ascending
+    /// order, entities with equal keys keep their order), so iterating over the storage visits the entities in
+    /// that order. This is meant to be called once in a while in long-lived worlds, where the order of the rows
+    /// reflects the history of spawns and despawns: for example, sorting by a Morton code of the positions of the
+    /// entities keeps entities that are close to each other in neighbouring rows.
+    ///
+    /// Every column is permuted in place, without dropping or cloning any component, and the [`EntityId`]s of the
+    /// entities don't change. Like any other move of rows, handles to the components of the storage (see
+    /// [`World::handle`]) are resolved again on their next use.
+    ///
+    /// Returns an error (without calling `order`) if the storage doesn't exist, is pinned (see
+    /// [`World::pin_storage`]), or is sort-maintained (see [`World::maintain_sort`]).
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Depth(u64);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Depth(3));
+    /// world.spawn(Depth(1));
+    /// world.spawn(Depth(2));
+    /// let depths: HashMap<EntityId, u64> = world.iter_component::<Depth>().map(|(e, depth)| (e, depth.0)).collect();
+    /// let storage = world.entity_location(entity).unwrap().0;
+    /// world.reorder_storage(storage, |entity| depths[&entity]).unwrap();
+    /// let sorted: Vec<u64> = world.query::<&Depth>().map(|depth| depth.0).collect();
+    /// assert_eq!(sorted, vec![1, 2, 3]);
+    /// ```
+    pub fn reorder_storage(
+        &mut self,
+        id: ArchStorageId,
+        mut order: impl FnMut(EntityId) -> u64,
+    ) -> Result<(), ReorderError> {
+        self.check_reorderable(id)?;
+        let storage = self.storages.arch_storages.get_storage_mut(id).unwrap();
+        let keys: Vec<u64> = storage.entities().iter().map(|e| order(*e)).collect();
+        let mut rows: Vec<usize> = (0..keys.len()).collect();
+        rows.sort_by_key(|row| keys[*row]);
+        if rows.iter().enumerate().all(|(i, row)| i == *row) {
+            return Ok(());
+        }
+        storage.permute_rows(&rows);
+        for (index, entity) in storage.entities().iter().enumerate() {
+            self.entities
+                .set_entity_arch_storage_index(ArchStorageIndex(index), *entity);
+        }
+        Ok(())
+    }
+
+    /// Reorder every storage that stores the components of `A` (see [`World::reorder_storage`]), and return how
+    /// many storages were reordered. Sort-maintained storages (see [`World::maintain_sort`]) are skipped. If one
+    /// of the storages is pinned, none are reordered.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(u32);
+    /// #[derive(Component)]
+    /// struct Velocity(u32);
+    ///
+    /// let mut world = World::default();
+    /// for i in 0..3 {
+    ///     world.spawn(Position(i));
+    ///     world.spawn((Position(i), Velocity(i)));
+    /// }
+    /// // Reverse the rows of both storages with `Position`.
+    /// assert_eq!(world.reorder_matching::<Position>(|entity| u64::MAX - entity.to_bits()), Ok(2));
+    /// let positions: Vec<u32> = world.query::<&Position>().map(|position| position.0).collect();
+    /// assert_eq!(positions, vec![2, 1, 0, 2, 1, 0]);
+    /// ```
+    pub fn reorder_matching<A: Archetype>(
+        &mut self,
+        mut order: impl FnMut(EntityId) -> u64,
+    ) -> Result<usize, ReorderError> {
+        let Some(pkey) = A::prime_key(&self.components) else {
+            return Ok(0);
+        };
+        let ids: Vec<ArchStorageId> = self
+            .storages
+            .arch_storages
+            .iter_ids_and_storages_with_matching_archetype_mut(pkey)
+            .filter(|(_, storage)| storage.sort.is_none())
+            .map(|(id, _)| id)
+            .collect();
+        for id in &ids {
+            self.check_pin_for_remove(*id)
+                .map_err(ReorderError::Pinned)?;
+        }
+        for id in &ids {
+            self.reorder_storage(*id, &mut order)?;
+        }
+        Ok(ids.len())
+    }
+
+    fn check_reorderable(&self, id: ArchStorageId) -> Result<(), ReorderError> {
+        let Some(storage) = self.storages.arch_storages.get_storage(id) else {
+            return Err(ReorderError::NoStorage(id));
+        };
+        if storage.sort.is_some() {
+            return Err(ReorderError::SortMaintained(id));
+        }
+        self.check_pin_for_remove(id).map_err(ReorderError::Pinned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReorderError;
+    use crate::prelude::*;
+    use crate::world::storage::storages::ArchStorageId;
+
+    #[derive(Component, Debug, PartialEq, Clone)]
+    struct Position(i32, i32);
+    #[derive(Component, Debug, PartialEq, Clone)]
+    struct Name(String);
+    #[derive(Component, Debug, PartialEq, Clone)]
+    struct Inventory(Vec<u32>);
+    #[derive(Component)]
+    struct Static;
+
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    fn storage_of(world: &World, entity: EntityId) -> ArchStorageId {
+        world
+            .entities
+            .get_entity_meta(entity)
+            .unwrap()
+            .archetype_storage_id
+    }
+
+    fn spawn_scattered(world: &mut World, amount: usize, seed: u64) -> Vec<EntityId> {
+        let mut random = Random(seed);
+        let mut entities = Vec::new();
+        for i in 0..amount {
+            let pos = Position((random.next() % 1000) as i32, (random.next() % 1000) as i32);
+            entities.push(world.spawn((
+                pos,
+                Name(format!("entity {i}")),
+                Inventory(vec![i as u32; i % 4]),
+                Static,
+            )));
+        }
+        // Churn, so the rows aren't in the order of the spawns.
+        for entity in entities.iter().step_by(3) {
+            world.despawn(*entity);
+        }
+        entities
+            .into_iter()
+            .filter(|entity| world.entity_state(*entity) == EntityState::Alive)
+            .collect()
+    }
+
+    fn check(world: &World, entities: &[EntityId]) {
+        world.assert_invariants();
+        for entity in entities {
+            let name = world.get_component::<Name>(*entity).unwrap();
+            let i: u32 = name.0.strip_prefix("entity ").unwrap().parse().unwrap();
+            let inventory = world.get_component::<Inventory>(*entity).unwrap();
+            assert_eq!(inventory.0, vec![i; i as usize % 4]);
+            assert!(world.contains_component::<Static>(*entity));
+        }
+    }
+
+    #[test]
+    fn test_reorder_storage() {
+        let mut world = World::default();
+        let entities = spawn_scattered(&mut world, 200, 0x2222);
+        let sid = storage_of(&world, entities[0]);
+        let positions: Vec<_> = entities
+            .iter()
+            .map(|e| world.get_component::<Position>(*e).unwrap().clone())
+            .collect();
+
+        let key = |world: &World, entity| {
+            let pos = world.get_component::<Position>(entity).unwrap();
+            (pos.0 * 1000 + pos.1) as u64
+        };
+        let keys: Vec<_> = entities.iter().map(|e| (*e, key(&world, *e))).collect();
+        let lookup = |entity| keys.iter().find(|(e, _)| *e == entity).unwrap().1;
+        world.reorder_storage(sid, lookup).unwrap();
+
+        check(&world, &entities);
+        for (entity, pos) in entities.iter().zip(&positions) {
+            assert_eq!(world.get_component::<Position>(*entity), Some(pos));
+        }
+        let visited: Vec<u64> = world
+            .query::<&Position>()
+            .map(|pos| (pos.0 * 1000 + pos.1) as u64)
+            .collect();
+        assert!(visited.is_sorted());
+        assert_eq!(visited.len(), entities.len());
+
+        // The storage keeps working after it's reordered.
+        world.despawn(entities[5]);
+        let spawned = world.spawn((
+            Position(1, 1),
+            Name("entity 7".into()),
+            Inventory(vec![7; 3]),
+            Static,
+        ));
+        check(&world, &entities[6..]);
+        check(&world, &[spawned]);
+    }
+
+    #[test]
+    fn test_reorder_random_permutations() {
+        let mut world = World::default();
+        let entities = spawn_scattered(&mut world, 120, 7);
+        let sid = storage_of(&world, entities[0]);
+        let mut random = Random(99);
+        for _ in 0..8 {
+            world.reorder_storage(sid, |_| random.next() % 16).unwrap();
+            check(&world, &entities);
+        }
+        // Equal keys keep their order.
+        let before: Vec<EntityId> = world.query::<EntityId>().collect();
+        world.reorder_storage(sid, |_| 0).unwrap();
+        assert_eq!(world.query::<EntityId>().collect::<Vec<_>>(), before);
+    }
+
+    #[test]
+    fn test_reorder_small() {
+        // Small enough for Miri, with both inline and blob columns.
+        for amount in [6, 12] {
+            let mut world = World::default();
+            let entities: Vec<_> = (0..amount)
+                .map(|i| world.spawn((Name(i.to_string()), Inventory(vec![i; 2]))))
+                .collect();
+            let sid = storage_of(&world, entities[0]);
+            let inverse = |entity: EntityId| {
+                u64::MAX - entities.iter().position(|e| *e == entity).unwrap() as u64
+            };
+            world.reorder_storage(sid, inverse).unwrap();
+            assert_eq!(
+                world
+                    .query::<&Name>()
+                    .map(|n| n.0.clone())
+                    .collect::<Vec<_>>(),
+                (0..amount).rev().map(|i| i.to_string()).collect::<Vec<_>>()
+            );
+            for (i, entity) in entities.iter().enumerate() {
+                assert_eq!(
+                    world.get_component::<Name>(*entity).unwrap().0,
+                    i.to_string()
+                );
+                assert_eq!(
+                    world.get_component::<Inventory>(*entity).unwrap().0,
+                    vec![i as u32; 2]
+                );
+            }
+            world.assert_invariants();
+            world.despawn(entities[0]);
+            world.despawn(entities[3]);
+            world.assert_invariants();
+        }
+    }
+
+    #[test]
+    fn test_reorder_errors() {
+        let mut world = World::default();
+        let entity = world.spawn((Position(0, 0), Name("a".into())));
+        world.spawn((Position(1, 0), Name("b".into())));
+        let sid = storage_of(&world, entity);
+
+        assert_eq!(
+            world.reorder_storage(ArchStorageId(100), |_| 0),
+            Err(ReorderError::NoStorage(ArchStorageId(100)))
+        );
+        let pin = world.pin_storage(sid);
+        let mut called = false;
+        assert!(matches!(
+            world.reorder_storage(sid, |_| {
+                called = true;
+                0
+            }),
+            Err(ReorderError::Pinned(_))
+        ));
+        assert!(!called);
+        assert!(matches!(
+            world.reorder_matching::<Position>(|_| 0),
+            Err(ReorderError::Pinned(_))
+        ));
+        drop(pin);
+
+        world.maintain_sort::<(Position, Name), Position, i32>(|pos| -pos.0);
+        assert_eq!(
+            world.reorder_storage(sid, |_| 0),
+            Err(ReorderError::SortMaintained(sid))
+        );
+        assert_eq!(world.reorder_matching::<Position>(|_| 0), Ok(0));
+    }
+
+    #[test]
+    fn test_reorder_matching() {
+        let mut world = World::default();
+        let mut entities = Vec::new();
+        for i in (0..30).rev() {
+            entities.push(world.spawn((Position(i, 0), Name(i.to_string()))));
+            entities.push(world.spawn(Position(i, 1)));
+            entities.push(world.spawn(Name(i.to_string())));
+        }
+        let x = |world: &World, entity| world.get_component::<Position>(entity).unwrap().0 as u64;
+        let xs: Vec<_> = entities
+            .iter()
+            .map(|e| world.get_component::<Position>(*e).map(|_| x(&world, *e)))
+            .collect();
+        let lookup = |entity| xs[entities.iter().position(|e| *e == entity).unwrap()].unwrap();
+        assert_eq!(world.reorder_matching::<Position>(lookup), Ok(2));
+        assert_eq!(world.reorder_matching::<Inventory>(|_| 0), Ok(0));
+
+        world.assert_invariants();
+        let visited: Vec<i32> = world
+            .query_filtered::<&Position, Has<Name>>()
+            .map(|pos| pos.0)
+            .collect();
+        assert_eq!(visited, (0..30).collect::<Vec<_>>());
+        // Storages that don't store a `Position` are left alone.
+        let names: Vec<String> = world
+            .query_filtered::<&Name, Not<Has<Position>>>()
+            .map(|name| name.0.clone())
+            .collect();
+        assert_eq!(
+            names,
+            (0..30).rev().map(|i| i.to_string()).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use super::{data::Data, World};
+use crate::utils::TypeIdMap;
+use std::any::{Any, TypeId};
+
+/// The resources of a [`World`]: one value of each type, that isn't attached to any entity (see
+/// [`World::insert_resource`]).
+#[derive(Default)]
+pub(crate) struct Resources {
+    values: TypeIdMap<Box<dyn Any + Send + Sync>>,
+}
+
+impl Resources {
+    /// Returns `true` if there are no resources.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Insert a resource, and drop the resource of the same type that was there before.
+    pub(crate) fn insert<R: Data>(&mut self, value: R) {
+        self.values.insert(TypeId::of::<R>(), Box::new(value));
+    }
+
+    pub(crate) fn get<R: Data>(&self) -> Option<&R> {
+        self.values.get(&TypeId::of::<R>())?.downcast_ref()
+    }
+
+    pub(crate) fn get_mut<R: Data>(&mut self) -> Option<&mut R> {
+        self.values.get_mut(&TypeId::of::<R>())?.downcast_mut()
+    }
+
+    pub(crate) fn remove<R: Data>(&mut self) -> Option<R> {
+        (self.values.remove(&TypeId::of::<R>())).map(|value| {
+            *value
+                .downcast::<R>()
+                .expect("resources are keyed by their type")
+        })
+    }
+}
+
+impl World {
+    /// Insert a resource: a value that isn't attached to any entity, like the time step, the state of the input or
+    /// the seed of the RNG. The world holds one resource of each type, so the resource of the same type that was
+    /// there before is replaced, and dropped. Resources are dropped with the world.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// struct TimeStep(f32);
+    /// impl Data for TimeStep {}
+    ///
+    /// let mut world = World::default();
+    /// world.insert_resource(TimeStep(1.0 / 60.0));
+    /// world.resource_mut::<TimeStep>().unwrap().0 = 1.0 / 30.0;
+    /// assert_eq!(world.resource::<TimeStep>().unwrap().0, 1.0 / 30.0);
+    /// ```
+    pub fn insert_resource<R: Data>(&mut self, value: R) {
+        self.resources.insert(value);
+    }
+
+    /// The resource of type `R`, or `None` if it wasn't inserted (see [`World::insert_resource`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// struct Seed(u64);
+    /// impl Data for Seed {}
+    ///
+    /// let mut world = World::default();
+    /// assert!(world.resource::<Seed>().is_none());
+    /// world.insert_resource(Seed(7));
+    /// assert_eq!(world.resource::<Seed>().unwrap().0, 7);
+    /// ```
+    pub fn resource<R: Data>(&self) -> Option<&R> {
+        self.resources.get()
+    }
+
+    /// The resource of type `R` mutably, or `None` if it wasn't inserted (see [`World::insert_resource`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// struct Score(u32);
+    /// impl Data for Score {}
+    ///
+    /// let mut world = World::default();
+    /// world.insert_resource(Score(0));
+    /// world.resource_mut::<Score>().unwrap().0 += 10;
+    /// assert_eq!(world.resource::<Score>().unwrap().0, 10);
+    /// ```
+    pub fn resource_mut<R: Data>(&mut self) -> Option<&mut R> {
+        self.resources.get_mut()
+    }
+
+    /// Remove the resource of type `R`, and return it, or `None` if it wasn't inserted (see
+    /// [`World::insert_resource`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// struct Seed(u64);
+    /// impl Data for Seed {}
+    ///
+    /// let mut world = World::default();
+    /// world.insert_resource(Seed(7));
+    /// assert_eq!(world.remove_resource::<Seed>().map(|seed| seed.0), Some(7));
+    /// assert!(world.remove_resource::<Seed>().is_none());
+    /// ```
+    pub fn remove_resource<R: Data>(&mut self) -> Option<R> {
+        self.resources.remove()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Seed(u64);
+    impl Data for Seed {}
+
+    #[derive(Debug, PartialEq)]
+    struct Input(Vec<&'static str>);
+    impl Data for Input {}
+
+    /// Counts how many times it was dropped.
+    struct Dropped(Arc<AtomicUsize>);
+    impl Data for Dropped {}
+
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_resources() {
+        let mut world = World::default();
+        assert_eq!(world.resource::<Seed>(), None);
+        world.insert_resource(Seed(7));
+        world.insert_resource(Input(vec!["jump"]));
+        assert_eq!(world.resource::<Seed>(), Some(&Seed(7)));
+        world.resource_mut::<Input>().unwrap().0.push("fire");
+        assert_eq!(
+            world.resource::<Input>(),
+            Some(&Input(vec!["jump", "fire"]))
+        );
+
+        // Re-inserting replaces the value.
+        world.insert_resource(Seed(8));
+        assert_eq!(world.resource::<Seed>(), Some(&Seed(8)));
+
+        assert_eq!(world.remove_resource::<Seed>(), Some(Seed(8)));
+        assert_eq!(world.remove_resource::<Seed>(), None);
+        assert_eq!(world.resource_mut::<Seed>(), None);
+        assert!(world.resource::<Input>().is_some());
+    }
+
+    #[test]
+    fn test_resources_are_dropped() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut world = World::default();
+        world.insert_resource(Dropped(drops.clone()));
+        world.insert_resource(Dropped(drops.clone()));
+        // The replaced resource was dropped.
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        let removed = world.remove_resource::<Dropped>();
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        drop(removed);
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+
+        world.insert_resource(Dropped(drops.clone()));
+        drop(world);
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+
+        let mut world = World::default();
+        world.insert_resource(Dropped(drops.clone()));
+        world.into_teardown().run_to_completion();
+        assert_eq!(drops.load(Ordering::Relaxed), 4);
+    }
+}
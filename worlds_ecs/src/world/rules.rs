@@ -0,0 +1,547 @@
+use super::{data::DefaultFn, World};
+use crate::{
+    archetype::key::PrimeArchKey,
+    archetype::{Archetype, ArchetypeInfo},
+    bundle::Bundle,
+    component::{Component, ComponentFactory, ComponentId},
+    entity::EntityId,
+    utils::panics,
+};
+use bevy_ptr::OwningPtr;
+use std::{collections::HashMap, fmt, sync::Arc};
+
+/// A component rule that an entity would break.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleViolation {
+    /// The entity has a component, but not a component that it requires (see [`World::require_component`]).
+    MissingRequirement {
+        /// The name of the component that has the requirement.
+        component: &'static str,
+        /// The name of the component that is missing.
+        requires: &'static str,
+    },
+    /// The entity has two components that conflict with each other (see [`World::conflict_components`]).
+    Conflict {
+        /// The name of one of the components.
+        component: &'static str,
+        /// The name of the component it conflicts with.
+        conflicts_with: &'static str,
+    },
+}
+
+/// An error when the archetype of an entity would break a component rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentRuleError {
+    /// The entity whose archetype would break the rule (for a spawn, the id the entity would have gotten).
+    pub entity: EntityId,
+    /// The rule that would be broken.
+    pub violation: RuleViolation,
+}
+
+impl fmt::Display for ComponentRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} would break a component rule: ", self.entity)?;
+        match self.violation {
+            RuleViolation::MissingRequirement {
+                component,
+                requires,
+            } => write!(f, "`{component}` requires `{requires}`, which is missing"),
+            RuleViolation::Conflict {
+                component,
+                conflicts_with,
+            } => write!(f, "`{component}` conflicts with `{conflicts_with}`"),
+        }
+    }
+}
+
+impl std::error::Error for ComponentRuleError {}
+
+#[derive(Clone, Copy)]
+struct Requirement {
+    requires: ComponentId,
+    /// Insert the default value of the required component when it's missing, instead of failing.
+    auto_insert: bool,
+}
+
+/// The archetype that an archetype becomes once the default values of its missing requirements are inserted.
+pub(crate) struct Completion {
+    pub(crate) arch_info: ArchetypeInfo,
+    defaults: Box<[(ComponentId, DefaultFn)]>,
+}
+
+impl Completion {
+    /// Wrap a bundle of the original archetype, so it also stores the inserted default values.
+    pub(crate) fn complete<B: Bundle>(&self, bundle: B) -> WithDefaults<'_, B> {
+        WithDefaults {
+            bundle,
+            defaults: &self.defaults,
+        }
+    }
+}
+
+/// A [`Bundle`] followed by the default values of some components. See [`Completion::complete`].
+pub(crate) struct WithDefaults<'a, B> {
+    bundle: B,
+    defaults: &'a [(ComponentId, DefaultFn)],
+}
+
+impl<B: Bundle> Bundle for WithDefaults<'_, B> {
+    fn raw_components_scope(
+        self,
+        comp_factory: &ComponentFactory,
+        f: &mut impl FnMut(ComponentId, OwningPtr<'_>),
+    ) {
+        self.bundle.raw_components_scope(comp_factory, f);
+        for (comp_id, default_fn) in self.defaults {
+            default_fn(&mut |raw_comp| f(*comp_id, raw_comp));
+        }
+    }
+}
+
+/// `Ok(None)` if the archetype follows the rules, `Ok(Some(_))` if it follows them once the defaults of its
+/// missing requirements are inserted.
+pub(crate) type Verdict = Result<Option<Arc<Completion>>, RuleViolation>;
+
+/// The rules that the components declare about each other: which components require other components, and which
+/// components conflict with each other. Every archetype is checked against the rules once, and the verdict is cached
+/// (by its [`PrimeArchKey`]) until the rules change, so checking an archetype that was already seen is a single lookup.
+#[derive(Default, Clone)]
+pub struct ComponentRules {
+    requirements: HashMap<ComponentId, Vec<Requirement>>,
+    conflicts: HashMap<ComponentId, Vec<ComponentId>>,
+    verdicts: HashMap<PrimeArchKey, Verdict>,
+    /// How many times an archetype was checked against the rules (rather than found in the cache).
+    evaluations: usize,
+}
+
+impl ComponentRules {
+    /// Returns `true` if there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty() && self.conflicts.is_empty()
+    }
+
+    /// Describe every rule (with the name of each component), sorted so the description doesn't depend on the order
+    /// that the rules were declared in.
+    pub(crate) fn describe(&self, name: impl Fn(ComponentId) -> &'static str) -> Vec<String> {
+        let mut rules: Vec<String> = self
+            .requirements
+            .iter()
+            .flat_map(|(component, requirements)| {
+                requirements.iter().map(|requirement| {
+                    format!(
+                        "`{}` requires `{}`{}",
+                        name(*component),
+                        name(requirement.requires),
+                        if requirement.auto_insert {
+                            " (inserted by default)"
+                        } else {
+                            ""
+                        }
+                    )
+                })
+            })
+            .chain(self.conflicts.iter().flat_map(|(component, conflicts)| {
+                conflicts
+                    .iter()
+                    .filter(|other| *other > component)
+                    .map(|other| {
+                        format!("`{}` conflicts with `{}`", name(*component), name(*other))
+                    })
+            }))
+            .collect();
+        rules.sort_unstable();
+        rules
+    }
+
+    fn add_requirement(&mut self, component: ComponentId, requirement: Requirement) {
+        let requirements = self.requirements.entry(component).or_default();
+        match requirements
+            .iter_mut()
+            .find(|existing| existing.requires == requirement.requires)
+        {
+            Some(existing) => existing.auto_insert |= requirement.auto_insert,
+            None => requirements.push(requirement),
+        }
+        self.verdicts.clear();
+    }
+
+    fn add_conflict(&mut self, a: ComponentId, b: ComponentId) {
+        for (component, other) in [(a, b), (b, a)] {
+            let conflicts = self.conflicts.entry(component).or_default();
+            if !conflicts.contains(&other) {
+                conflicts.push(other);
+            }
+        }
+        self.verdicts.clear();
+    }
+}
+
+impl ComponentFactory {
+    /// How many times an archetype was checked against the [`ComponentRules`]. Each archetype is only checked once,
+    /// until the rules change.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Rigidbody;
+    /// #[derive(Component, Default)]
+    /// struct Transform;
+    ///
+    /// let mut world = World::default();
+    /// world.require_component_with_default::<Rigidbody, Transform>();
+    /// for _ in 0..3 {
+    ///     world.spawn(Rigidbody);
+    /// }
+    /// // The archetype was only checked once.
+    /// assert_eq!(world.components().rule_evaluations(), 1);
+    /// ```
+    pub fn rule_evaluations(&self) -> usize {
+        self.rules.evaluations
+    }
+
+    /// Get the verdict of the rules on the archetype with the [`PrimeArchKey`] `prime_key`, from the cache, or by
+    /// checking its [`ArchetypeInfo`] against the rules.
+    pub(crate) fn judge_archetype(
+        &mut self,
+        prime_key: PrimeArchKey,
+        arch_info: impl FnOnce(&ComponentFactory) -> ArchetypeInfo,
+    ) -> Verdict {
+        if let Some(verdict) = self.rules.verdicts.get(&prime_key) {
+            return verdict.clone();
+        }
+        let verdict = self.evaluate_rules(arch_info(self));
+        self.rules.evaluations += 1;
+        self.rules.verdicts.insert(prime_key, verdict.clone());
+        verdict
+    }
+
+    fn evaluate_rules(&self, arch_info: ArchetypeInfo) -> Verdict {
+        let name = |comp_id| {
+            self.get_component_info_from_component_id(comp_id)
+                .expect("components with rules are registered")
+                .name()
+        };
+        let mut component_ids = arch_info.component_ids().to_vec();
+        let mut defaults = Vec::new();
+        // Inserted defaults can have requirements of their own, so they are checked as well.
+        let mut i = 0;
+        while let Some(&component) = component_ids.get(i) {
+            for requirement in self
+                .rules
+                .requirements
+                .get(&component)
+                .into_iter()
+                .flatten()
+            {
+                if component_ids.contains(&requirement.requires) {
+                    continue;
+                }
+                let default_fn = requirement
+                    .auto_insert
+                    .then(|| {
+                        self.get_component_info_from_component_id(requirement.requires)
+                            .and_then(|info| info.default_fn())
+                    })
+                    .flatten();
+                let Some(default_fn) = default_fn else {
+                    return Err(RuleViolation::MissingRequirement {
+                        component: name(component),
+                        requires: name(requirement.requires),
+                    });
+                };
+                component_ids.push(requirement.requires);
+                defaults.push((requirement.requires, default_fn));
+            }
+            i += 1;
+        }
+        for &component in &component_ids {
+            if let Some(&other) = self
+                .rules
+                .conflicts
+                .get(&component)
+                .into_iter()
+                .flatten()
+                .find(|other| component_ids.contains(other))
+            {
+                return Err(RuleViolation::Conflict {
+                    component: name(component),
+                    conflicts_with: name(other),
+                });
+            }
+        }
+        Ok((!defaults.is_empty()).then(|| {
+            Arc::new(Completion {
+                arch_info: ArchetypeInfo::from_component_ids(component_ids),
+                defaults: defaults.into_boxed_slice(),
+            })
+        }))
+    }
+}
+
+impl World {
+    /// Declare that every entity with the [`Component`] `C` must also have all the components of `R`.
+    /// Spawning an entity that breaks the rule fails (see [`World::try_spawn`]).
+    ///
+    /// The rule is checked whenever the archetype of an entity is established, so entities that were spawned before
+    /// the rule was declared aren't checked.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Rigidbody;
+    /// #[derive(Component)]
+    /// struct Transform;
+    ///
+    /// let mut world = World::default();
+    /// world.require_component::<Rigidbody, Transform>();
+    /// assert!(world.try_spawn(Rigidbody).is_err());
+    /// assert!(world.try_spawn((Rigidbody, Transform)).is_ok());
+    /// ```
+    #[track_caller]
+    pub fn require_component<C: Component, R: Archetype>(&mut self) {
+        let component = self.register_rule_component::<C>();
+        for &requires in R::get_info_or_register(&mut self.components).component_ids() {
+            self.components.rules.add_requirement(
+                component,
+                Requirement {
+                    requires,
+                    auto_insert: false,
+                },
+            );
+        }
+    }
+
+    /// Declare that every entity with the [`Component`] `C` must also have the component `R`, like
+    /// [`World::require_component`], but when `R` is missing, its [`Default`] value is inserted instead of failing.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Rigidbody;
+    /// #[derive(Component, Default)]
+    /// struct Transform(f32);
+    ///
+    /// let mut world = World::default();
+    /// world.require_component_with_default::<Rigidbody, Transform>();
+    /// let entity = world.spawn(Rigidbody);
+    /// assert_eq!(world.get_component::<Transform>(entity).unwrap().0, 0.0);
+    /// ```
+    #[track_caller]
+    pub fn require_component_with_default<C: Component, R: Component + Default>(&mut self) {
+        let component = self.register_rule_component::<C>();
+        let Some(requires) = self.components.register_component_with_default::<R>() else {
+            panics::fail_component_limit::<R>();
+        };
+        self.components.rules.add_requirement(
+            component,
+            Requirement {
+                requires,
+                auto_insert: true,
+            },
+        );
+    }
+
+    /// Declare that no entity can have both of the [`Component`]s `A` and `B`.
+    /// Spawning an entity that breaks the rule fails (see [`World::try_spawn`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Static;
+    /// #[derive(Component)]
+    /// struct Rigidbody;
+    ///
+    /// let mut world = World::default();
+    /// world.conflict_components::<Static, Rigidbody>();
+    /// assert!(world.try_spawn((Static, Rigidbody)).is_err());
+    /// assert!(world.try_spawn(Static).is_ok());
+    /// ```
+    #[track_caller]
+    pub fn conflict_components<A: Component, B: Component>(&mut self) {
+        let a = self.register_rule_component::<A>();
+        let b = self.register_rule_component::<B>();
+        self.components.rules.add_conflict(a, b);
+    }
+
+    #[track_caller]
+    fn register_rule_component<C: Component>(&mut self) -> ComponentId {
+        let Some(component) = self.components.register_component::<C>() else {
+            panics::fail_component_limit::<C>();
+        };
+        component
+    }
+
+    /// Check the archetype that `entity` is about to have against the component rules. Returns the archetype to
+    /// store the entity in instead, if the defaults of missing requirements need to be inserted.
+    /// Every path that establishes or changes the archetype of an entity goes through this.
+    pub(crate) fn check_component_rules(
+        &mut self,
+        entity: EntityId,
+        prime_key: PrimeArchKey,
+        arch_info: impl FnOnce(&ComponentFactory) -> ArchetypeInfo,
+    ) -> Result<Option<Arc<Completion>>, ComponentRuleError> {
+        if self.components.rules.is_empty() {
+            return Ok(None);
+        }
+        self.components
+            .judge_archetype(prime_key, arch_info)
+            .map_err(|violation| ComponentRuleError { entity, violation })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ComponentRuleError, RuleViolation};
+    use crate::{archetype::Archetype, prelude::*};
+    use std::any::type_name;
+
+    #[derive(Component, Debug, PartialEq)]
+    struct RigidBody;
+    #[derive(Component, Debug, PartialEq)]
+    struct StaticBody;
+    #[derive(Component, Debug, PartialEq)]
+    struct Collider(f32);
+    #[derive(Component, Debug, PartialEq, Default)]
+    struct Velocity([f32; 3]);
+    #[derive(Component, Debug, PartialEq, Default)]
+    struct Mass(f32);
+
+    #[test]
+    fn test_requirement_violation_at_spawn() {
+        let mut world = World::default();
+        world.require_component::<RigidBody, (Collider, Velocity)>();
+        let valid = world.spawn((RigidBody, Collider(1.0), Velocity::default()));
+        let next = world.entities.next_entity_id();
+        assert_eq!(
+            world.try_spawn((RigidBody, Velocity::default())),
+            Err(SpawnError::Rule(ComponentRuleError {
+                entity: next,
+                violation: RuleViolation::MissingRequirement {
+                    component: type_name::<RigidBody>(),
+                    requires: type_name::<Collider>(),
+                },
+            }))
+        );
+        // A failed spawn leaves no trace.
+        assert_eq!(world.entities.next_entity_id(), next);
+        assert_eq!(world.component_count::<RigidBody>(), 1);
+        assert_eq!(world.spawn(Collider(2.0)), next);
+        assert!(world.contains_component::<Collider>(valid));
+        world.assert_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: spawn failed: ")]
+    fn test_spawn_panics_on_violation() {
+        let mut world = World::default();
+        world.require_component::<RigidBody, Collider>();
+        world.spawn(RigidBody);
+    }
+
+    #[test]
+    fn test_auto_insert_defaults() {
+        let mut world = World::default();
+        world.require_component_with_default::<RigidBody, Velocity>();
+        world.require_component_with_default::<Velocity, Mass>();
+        let body = world.spawn((RigidBody, Collider(1.0)));
+        assert_eq!(
+            world.get_component::<Velocity>(body),
+            Some(&Velocity::default())
+        );
+        assert_eq!(world.get_component::<Mass>(body), Some(&Mass(0.0)));
+        assert_eq!(world.get_component::<Collider>(body), Some(&Collider(1.0)));
+
+        // Given requirements aren't replaced by defaults.
+        let fast = world.spawn((RigidBody, Velocity([1.0; 3])));
+        assert_eq!(
+            world.get_component::<Velocity>(fast),
+            Some(&Velocity([1.0; 3]))
+        );
+        assert_eq!(world.get_component::<Mass>(fast), Some(&Mass(0.0)));
+
+        // The batch path inserts them too.
+        let components = &world.components;
+        let arch_info = <(RigidBody, Collider)>::arch_info(components).unwrap();
+        // SAFETY: Every bundle stores exactly the components of the archetype.
+        let batch = unsafe {
+            world
+                .spawn_batch_with_info(&arch_info, (0..10).map(|i| (RigidBody, Collider(i as f32))))
+        }
+        .unwrap();
+        assert_eq!(world.query::<(&RigidBody, &Velocity, &Mass)>().count(), 12);
+        assert_eq!(
+            world.get_component::<Collider>(batch[9]),
+            Some(&Collider(9.0))
+        );
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_conflict_on_insert_path() {
+        let mut world = World::default();
+        world.conflict_components::<RigidBody, StaticBody>();
+        world.require_component_with_default::<RigidBody, Velocity>();
+        let wall = world.spawn((StaticBody, Collider(1.0)));
+        // Inserting `RigidBody` into the wall would give it this archetype.
+        let arch_info = <(StaticBody, Collider, RigidBody)>::arch_info(&world.components).unwrap();
+        let error = world
+            .check_component_rules(wall, arch_info.prime_key(), |_| arch_info.clone())
+            .err()
+            .unwrap();
+        assert_eq!(error.entity, wall);
+        // The components are checked in the order of their ids, and `RigidBody` was registered first.
+        assert_eq!(
+            error.violation,
+            RuleViolation::Conflict {
+                component: type_name::<RigidBody>(),
+                conflicts_with: type_name::<StaticBody>(),
+            }
+        );
+        assert!(error.to_string().contains("conflicts with"));
+        assert!(world.try_spawn((RigidBody, StaticBody)).is_err());
+
+        // An inserted default can cause a conflict as well.
+        world.conflict_components::<Velocity, StaticBody>();
+        let arch_info = <(StaticBody, Collider)>::arch_info(&world.components).unwrap();
+        let prime_key = arch_info.prime_key();
+        assert!(world
+            .check_component_rules(wall, prime_key, |_| arch_info)
+            .unwrap()
+            .is_none());
+        assert!(world.try_spawn((RigidBody, Collider(0.0))).is_ok());
+        assert!(matches!(
+            world.try_spawn((RigidBody, Collider(0.0), StaticBody)),
+            Err(SpawnError::Rule(ComponentRuleError {
+                violation: RuleViolation::Conflict { .. },
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_verdicts_are_cached() {
+        let mut world = World::default();
+        // Without rules, nothing is checked.
+        world.spawn(RigidBody);
+        assert_eq!(world.components.rule_evaluations(), 0);
+
+        world.require_component_with_default::<RigidBody, Velocity>();
+        world.conflict_components::<RigidBody, StaticBody>();
+        for i in 0..100 {
+            world.spawn((RigidBody, Collider(i as f32)));
+            world.spawn(Collider(i as f32));
+            assert!(world.try_spawn((RigidBody, StaticBody)).is_err());
+        }
+        assert_eq!(world.components.rule_evaluations(), 3);
+
+        // New rules invalidate the cached verdicts.
+        world.require_component::<Collider, Mass>();
+        assert!(world.try_spawn(Collider(0.0)).is_err());
+        assert!(world.try_spawn(Collider(0.0)).is_err());
+        assert_eq!(world.components.rule_evaluations(), 4);
+    }
+}
@@ -0,0 +1,415 @@
+use super::{group::GroupId, World};
+use crate::{
+    archetype::Archetype,
+    bundle::Bundle,
+    component::{Component, ComponentId},
+    entity::EntityId,
+    prelude::{ArchQuery, QueryIter},
+    utils::component_mask::ComponentMask,
+};
+use std::fmt;
+
+/// The components that a [`ScopedWorld`] can access, and the group that the entities it spawns join (see
+/// [`World::scoped_view`]). Components are whitelisted by [`ComponentId`], or by name (the full path of their type,
+/// see [`DataInfo::name`](crate::prelude::DataInfo::name)), so components that aren't registered yet can be
+/// whitelisted too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentWhitelist {
+    scope: String,
+    ids: Vec<ComponentId>,
+    names: Vec<String>,
+}
+
+impl ComponentWhitelist {
+    /// An empty whitelist, whose views enroll the entities they spawn in the group named `scope` (see
+    /// [`World::create_group`]).
+    pub fn new(scope: impl Into<String>) -> Self {
+        ComponentWhitelist {
+            scope: scope.into(),
+            ids: Vec::new(),
+            names: Vec::new(),
+        }
+    }
+
+    /// Whitelist a component by its id.
+    pub fn with_id(mut self, comp_id: ComponentId) -> Self {
+        self.ids.push(comp_id);
+        self
+    }
+
+    /// Whitelist a component by its name (the full path of its type).
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.names.push(name.into());
+        self
+    }
+
+    /// Whitelist the component `C`, by its name.
+    pub fn with<C: Component>(self) -> Self {
+        self.with_name(std::any::type_name::<C>())
+    }
+
+    /// The name of the group that the entities spawned by the views join.
+    pub fn scope(&self) -> &str {
+        &self.scope
+    }
+}
+
+/// An error when a [`ScopedWorld`] is asked to access a component that isn't whitelisted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessDenied {
+    /// The name of the component.
+    pub component: &'static str,
+}
+
+impl fmt::Display for AccessDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` isn't whitelisted in this view", self.component)
+    }
+}
+
+impl std::error::Error for AccessDenied {}
+
+/// A view of a [`World`] that can only access the components of its [`ComponentWhitelist`], to hand to plugins
+/// (see [`World::scoped_view`]). Every operation that would access a component outside of the whitelist fails with
+/// [`AccessDenied`], before anything is changed.
+pub struct ScopedWorld<'w> {
+    world: &'w mut World,
+    /// The whitelisted components that were registered when they were checked.
+    allowed: ComponentMask,
+    /// The whitelisted names, for the components that are registered after the view was created.
+    names: Vec<String>,
+    scope: GroupId,
+}
+
+impl ScopedWorld<'_> {
+    /// The group that the entities spawned by this view join.
+    pub fn scope(&self) -> GroupId {
+        self.scope
+    }
+
+    /// Spawn an entity, like [`World::spawn`], and enroll it in the group of the view (see [`Self::scope`]).
+    ///
+    /// # Errors
+    /// If the bundle has a component that isn't whitelisted.
+    ///
+    /// # Panics
+    /// Like [`World::spawn`].
+    #[track_caller]
+    pub fn spawn<B: Bundle + Archetype>(&mut self, bundle: B) -> Result<EntityId, AccessDenied> {
+        let arch_info = B::get_info_or_register(&mut self.world.components);
+        for comp_id in arch_info.component_ids() {
+            self.check(*comp_id)?;
+        }
+        let entity = self.world.spawn(bundle);
+        self.world.group_insert(self.scope, entity);
+        Ok(entity)
+    }
+
+    /// Query the components of the world, like [`World::query`]. Components that aren't registered are left out of
+    /// the check, since no entity has them.
+    ///
+    /// # Errors
+    /// If the query reads or writes a component that isn't whitelisted.
+    #[track_caller]
+    pub fn query<Q: ArchQuery>(&mut self) -> Result<QueryIter<'_, Q>, AccessDenied> {
+        let mut denied = None;
+        Q::for_each_access(&self.world.components, &mut |comp_id, _| {
+            if denied.is_none() {
+                denied = self.check_shared(comp_id).err();
+            }
+        });
+        match denied {
+            Some(error) => Err(error),
+            None => Ok(self.world.query::<Q>()),
+        }
+    }
+
+    /// Get a [`Component`] of an entity, like [`World::get_component`].
+    ///
+    /// # Errors
+    /// If `C` isn't whitelisted.
+    pub fn get_component<C: Component>(
+        &self,
+        entity: EntityId,
+    ) -> Result<Option<&C>, AccessDenied> {
+        self.check_shared(self.component_id::<C>())?;
+        Ok(self.world.get_component::<C>(entity))
+    }
+
+    /// Get a [`Component`] of an entity mutably, like [`World::get_component_mut`].
+    ///
+    /// # Errors
+    /// If `C` isn't whitelisted.
+    pub fn get_component_mut<C: Component>(
+        &mut self,
+        entity: EntityId,
+    ) -> Result<Option<&mut C>, AccessDenied> {
+        self.check(self.component_id::<C>())?;
+        Ok(self.world.get_component_mut::<C>(entity))
+    }
+
+    /// Remove a [`Component`] from an entity, like [`World::remove_component`].
+    ///
+    /// # Errors
+    /// If `C` isn't whitelisted.
+    ///
+    /// # Panics
+    /// Like [`World::remove_component`].
+    #[track_caller]
+    pub fn remove_component<C: Component>(
+        &mut self,
+        entity: EntityId,
+    ) -> Result<Option<C>, AccessDenied> {
+        self.check(self.component_id::<C>())?;
+        Ok(self.world.remove_component::<C>(entity))
+    }
+
+    /// Despawn an entity, like [`World::despawn`]. Returns `false` if the entity isn't in the storages of the world
+    /// (it was despawned, archived, or is a tombstone), so nothing was despawned.
+    ///
+    /// # Errors
+    /// If the entity has a component that isn't whitelisted.
+    ///
+    /// # Panics
+    /// Like [`World::despawn`].
+    #[track_caller]
+    pub fn despawn(&mut self, entity: EntityId) -> Result<bool, AccessDenied> {
+        let Some(&entity_meta) = self.world.entities.get_entity_meta(entity) else {
+            return Ok(false);
+        };
+        if entity_meta.is_archived() || entity_meta.is_tombstone() {
+            return Ok(false);
+        }
+        for comp_id in entity_meta.component_mask.iter() {
+            self.check(comp_id)?;
+        }
+        self.world.despawn(entity);
+        Ok(true)
+    }
+
+    /// The id of `C`, or `None` if it isn't registered.
+    fn component_id<C: Component>(&self) -> Option<ComponentId> {
+        self.world.components.get_component_id::<C>()
+    }
+
+    /// Check that a component is whitelisted, and remember it if it was whitelisted by name.
+    fn check(&mut self, comp_id: impl Into<Option<ComponentId>>) -> Result<(), AccessDenied> {
+        let comp_id = comp_id.into();
+        self.check_shared(comp_id)?;
+        if let Some(comp_id) = comp_id {
+            self.allowed.insert(comp_id);
+        }
+        Ok(())
+    }
+
+    /// Check that a component is whitelisted. A component that isn't registered can't be accessed, so it passes.
+    fn check_shared(&self, comp_id: impl Into<Option<ComponentId>>) -> Result<(), AccessDenied> {
+        let Some(comp_id) = comp_id.into() else {
+            return Ok(());
+        };
+        if self.allowed.contains(comp_id) {
+            return Ok(());
+        }
+        let component = (self.world.components)
+            .get_component_info_from_component_id(comp_id)
+            .expect("the accessed components are registered")
+            .name();
+        match self.names.iter().any(|name| name == component) {
+            true => Ok(()),
+            false => Err(AccessDenied { component }),
+        }
+    }
+}
+
+impl World {
+    /// A view of the world that can only access the components of `whitelist`, to hand to third-party plugins, so
+    /// a misbehaving plugin can't corrupt the state of the host. The view spawns, queries, gets, removes and
+    /// despawns like the world, but fails with [`AccessDenied`] whenever the operation would access a component
+    /// outside of the whitelist: a spawn with such a component in its bundle, a query that reads or writes one, and
+    /// getting, removing, or despawning an entity that has one.
+    ///
+    /// The entities that the view spawns join the group named by [`ComponentWhitelist::scope`], so the host can
+    /// clean them all up with [`World::despawn_group`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Particle(f32);
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(10));
+    /// let whitelist = ComponentWhitelist::new("particles").with::<Particle>();
+    /// let mut view = world.scoped_view(&whitelist);
+    /// view.spawn(Particle(1.0)).unwrap();
+    /// assert_eq!(view.query::<&Particle>().unwrap().count(), 1);
+    /// assert!(view.query::<&mut Health>().is_err());
+    ///
+    /// let scope = view.scope();
+    /// assert_eq!(world.despawn_group(scope), 1);
+    /// ```
+    pub fn scoped_view(&mut self, whitelist: &ComponentWhitelist) -> ScopedWorld<'_> {
+        let scope = self.create_group(&whitelist.scope);
+        let mut allowed = ComponentMask::from_component_ids(whitelist.ids.iter().copied());
+        let mut names = Vec::new();
+        for name in &whitelist.names {
+            match self.components.get_component_id_by_name(name) {
+                Some(comp_id) => allowed.insert(comp_id),
+                None => names.push(name.clone()),
+            }
+        }
+        ScopedWorld {
+            world: self,
+            allowed,
+            names,
+            scope,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccessDenied, ComponentWhitelist, ScopedWorld};
+    use crate::prelude::*;
+
+    #[derive(Component, Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Component, Debug, PartialEq)]
+    struct Spin(i32);
+    #[derive(Component, Debug, PartialEq)]
+    struct Health(u32);
+    #[derive(Component, Debug, PartialEq)]
+    struct Owner;
+
+    fn denied<C>() -> AccessDenied {
+        AccessDenied {
+            component: std::any::type_name::<C>(),
+        }
+    }
+
+    /// A plugin that spins the positions it's allowed to see.
+    fn plugin(view: &mut ScopedWorld) -> Result<Vec<EntityId>, AccessDenied> {
+        let spawned = (0..3)
+            .map(|i| view.spawn((Position(i), Spin(1))))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (position, spin) in view.query::<(&mut Position, &Spin)>()? {
+            position.0 += spin.0;
+        }
+        *view.get_component_mut::<Spin>(spawned[0])?.unwrap() = Spin(5);
+        assert_eq!(view.remove_component::<Spin>(spawned[1])?, Some(Spin(1)));
+        assert!(view.despawn(spawned[2])?);
+        Ok(spawned)
+    }
+
+    #[test]
+    fn test_allowed_operations() {
+        let mut world = World::default();
+        let core = world.spawn((Position(100), Health(10)));
+        let whitelist = ComponentWhitelist::new("plugin")
+            .with::<Position>()
+            .with::<Spin>();
+        let mut view = world.scoped_view(&whitelist);
+        let spawned = plugin(&mut view).unwrap();
+        let scope = view.scope();
+
+        assert_eq!(
+            world.get_component::<Position>(spawned[0]),
+            Some(&Position(1))
+        );
+        assert_eq!(world.get_component::<Spin>(spawned[0]), Some(&Spin(5)));
+        assert_eq!(world.get_component::<Spin>(spawned[1]), None);
+        assert!(!world.is_alive(spawned[2]));
+        // The query of the plugin saw the core entity's position, but it has no spin, so it wasn't matched.
+        assert_eq!(world.get_component::<Position>(core), Some(&Position(100)));
+        assert_eq!(world.group_len(scope), 2);
+        assert_eq!(world.group("plugin"), Some(scope));
+    }
+
+    #[test]
+    fn test_disallowed_operations() {
+        let mut world = World::default();
+        let core = world.spawn((Position(0), Health(10)));
+        let allowed = world.spawn(Position(1));
+        let whitelist = ComponentWhitelist::new("plugin").with::<Position>();
+        let mut view = world.scoped_view(&whitelist);
+
+        assert_eq!(
+            view.spawn((Position(2), Health(1))),
+            Err(denied::<Health>())
+        );
+        // A component that wasn't registered is registered by the spawn, and denied too.
+        assert_eq!(view.spawn(Owner), Err(denied::<Owner>()));
+        assert_eq!(
+            view.query::<(&Position, &mut Health)>().err(),
+            Some(denied::<Health>())
+        );
+        assert_eq!(view.query::<&Owner>().err(), Some(denied::<Owner>()));
+        assert_eq!(view.get_component::<Health>(core), Err(denied::<Health>()));
+        assert_eq!(
+            view.get_component_mut::<Health>(core).err(),
+            Some(denied::<Health>())
+        );
+        assert_eq!(
+            view.remove_component::<Health>(core),
+            Err(denied::<Health>())
+        );
+        assert_eq!(view.despawn(core), Err(denied::<Health>()));
+        assert_eq!(
+            denied::<Health>().to_string(),
+            format!(
+                "`{}` isn't whitelisted in this view",
+                std::any::type_name::<Health>()
+            )
+        );
+
+        // The allowed parts of the same entities are still accessible, and nothing was changed.
+        assert_eq!(view.get_component::<Position>(core), Ok(Some(&Position(0))));
+        assert_eq!(view.query::<&Position>().unwrap().count(), 2);
+        assert_eq!(view.despawn(allowed), Ok(true));
+        assert_eq!(view.despawn(allowed), Ok(false));
+        let scope = view.scope();
+        assert_eq!(world.entities(), 1);
+        assert_eq!(world.get_component::<Health>(core), Some(&Health(10)));
+        assert_eq!(world.group_len(scope), 0);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_whitelist_by_id_and_name() {
+        let mut world = World::default();
+        let entity = world.spawn((Position(0), Health(1)));
+        let position = world.components().get_component_id::<Position>().unwrap();
+        let whitelist = ComponentWhitelist::new("plugin")
+            .with_id(position)
+            .with_name(std::any::type_name::<Health>());
+        let mut view = world.scoped_view(&whitelist);
+        assert!(view.get_component::<Position>(entity).unwrap().is_some());
+        assert!(view.get_component::<Health>(entity).unwrap().is_some());
+        assert_eq!(view.get_component::<Spin>(entity), Ok(None));
+        assert_eq!(view.despawn(entity), Ok(true));
+    }
+
+    #[test]
+    fn test_host_cleans_up_the_scope() {
+        let mut world = World::default();
+        let core = world.spawn(Health(10));
+        let whitelist = ComponentWhitelist::new("plugin").with::<Position>();
+        let mut view = world.scoped_view(&whitelist);
+        for i in 0..10 {
+            view.spawn(Position(i)).unwrap();
+        }
+        let scope = view.scope();
+        // A second view of the same whitelist enrolls in the same scope.
+        let mut view = world.scoped_view(&whitelist);
+        view.spawn(Position(10)).unwrap();
+        assert_eq!(view.scope(), scope);
+
+        assert_eq!(world.despawn_group(scope), 11);
+        assert_eq!(world.query::<&Position>().count(), 0);
+        assert!(world.is_alive(core));
+        assert_eq!(world.despawn_group(scope), 0);
+        world.assert_invariants();
+    }
+}
@@ -0,0 +1,523 @@
+use super::World;
+use crate::{
+    component::{make_component, Component},
+    entity::EntityId,
+    utils::{panics, TypeIdMap},
+    world::data::Data,
+};
+use bevy_ptr::{OwningPtr, Ptr};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    hash::{BuildHasher, Hash, Hasher, RandomState},
+    mem::size_of,
+    panic::Location,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+/// A value of a shared component (see [`Component::SHARED`]), which the storages of the component hold a
+/// [`SharedHandle`] to.
+pub(crate) struct SharedValue<C> {
+    value: C,
+    /// The hash that the value was interned with.
+    hash: u64,
+    /// The table that the value was interned in, or nothing if it wasn't interned.
+    table: Weak<InternTable<C>>,
+}
+
+impl<C> SharedValue<C> {
+    /// The value of the component.
+    #[inline]
+    pub(crate) fn value(&self) -> &C {
+        &self.value
+    }
+}
+
+/// How the storages of a shared component hold its values: a reference-counted pointer to the interned value, so
+/// every entity with an equal value points at the same one.
+pub(crate) type SharedHandle<C> = Arc<SharedValue<C>>;
+
+/// Compares two values of a shared component, for interning them.
+type EqFn<C> = Box<dyn Fn(&C, &C) -> bool + Send + Sync>;
+
+/// The interned values of a shared component, see [`World::register_shared`].
+struct InternTable<C> {
+    hash: Box<dyn Fn(&C) -> u64 + Send + Sync>,
+    eq: EqFn<C>,
+    /// The interned values, by their hash. The table holds a handle to each of them, so a value is released when
+    /// the table holds the last handle.
+    values: Mutex<HashMap<u64, Vec<SharedHandle<C>>>>,
+    /// How many values are interned, kept up to date so it can be read without the lock.
+    interned: AtomicUsize,
+    /// The table itself, for the values to point back at it.
+    this: Weak<InternTable<C>>,
+}
+
+impl<C: Data> InternTable<C> {
+    /// Return the handle of the value that is equal to `value`, or intern it.
+    fn intern(&self, value: C) -> SharedHandle<C> {
+        let hash = (self.hash)(&value);
+        let mut values = self.values.lock().unwrap();
+        let bucket = values.entry(hash).or_default();
+        if let Some(handle) = bucket
+            .iter()
+            .find(|handle| (self.eq)(&handle.value, &value))
+        {
+            return handle.clone();
+        }
+        let handle = Arc::new(SharedValue {
+            value,
+            hash,
+            table: self.this.clone(),
+        });
+        bucket.push(handle.clone());
+        self.interned.fetch_add(1, Ordering::Relaxed);
+        handle
+    }
+
+    /// Drop a handle of an interned value, and release the value if the table holds the last handle to it.
+    fn release(&self, handle: SharedHandle<C>) {
+        let released = {
+            let mut values = self.values.lock().unwrap();
+            // The table and `handle` are the only handles, and no other one can be made without the lock.
+            (Arc::strong_count(&handle) == 2)
+                .then(|| {
+                    let bucket = values.get_mut(&handle.hash)?;
+                    let index = bucket
+                        .iter()
+                        .position(|interned| Arc::ptr_eq(interned, &handle))?;
+                    let released = bucket.swap_remove(index);
+                    if bucket.is_empty() {
+                        values.remove(&handle.hash);
+                    }
+                    self.interned.fetch_sub(1, Ordering::Relaxed);
+                    Some(released)
+                })
+                .flatten()
+        };
+        // The values are dropped outside of the lock, in case dropping them releases other shared values.
+        drop((handle, released));
+    }
+}
+
+/// The type-erased [`InternTable`] of a component.
+trait AnyInternTable: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    /// How many values are interned.
+    fn values(&self) -> usize;
+
+    /// The bytes of the interned values.
+    fn bytes(&self) -> usize;
+}
+
+impl<C: Data> AnyInternTable for InternTable<C> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn values(&self) -> usize {
+        self.interned.load(Ordering::Relaxed)
+    }
+
+    fn bytes(&self) -> usize {
+        self.values() * size_of::<C>()
+    }
+}
+
+/// The intern tables of the shared components that were registered with [`World::register_shared`]. Copies of the
+/// world (like [`World::clone_cow`]) share the tables, like they share the values.
+#[derive(Default, Clone)]
+pub(crate) struct SharedTables {
+    tables: TypeIdMap<Arc<dyn AnyInternTable>>,
+}
+
+impl SharedTables {
+    fn table<C: Data>(&self) -> Option<&InternTable<C>> {
+        self.tables
+            .get(&TypeId::of::<C>())
+            .and_then(|table| table.as_any().downcast_ref())
+    }
+
+    fn register<C: Data>(
+        &mut self,
+        hash: impl Fn(&C) -> u64 + Send + Sync + 'static,
+        eq: impl Fn(&C, &C) -> bool + Send + Sync + 'static,
+    ) {
+        self.tables.entry(TypeId::of::<C>()).or_insert_with(|| {
+            Arc::new_cyclic(|this| InternTable {
+                hash: Box::new(hash),
+                eq: Box::new(eq),
+                values: Mutex::default(),
+                interned: AtomicUsize::new(0),
+                this: this.clone(),
+            })
+        });
+    }
+
+    /// The handle of the shared component `C` with this value. The value is interned if `C` was registered with
+    /// [`World::register_shared`], or gets a handle of its own otherwise.
+    pub(crate) fn intern<C: Data>(&self, value: C) -> SharedHandle<C> {
+        match self.table::<C>() {
+            Some(table) => table.intern(value),
+            None => Arc::new(SharedValue {
+                value,
+                hash: 0,
+                table: Weak::new(),
+            }),
+        }
+    }
+
+    /// The bytes of the interned values of every shared component.
+    pub(crate) fn bytes(&self) -> usize {
+        self.tables.values().map(|table| table.bytes()).sum()
+    }
+}
+
+/// Drop a stored [`SharedHandle`], and release its value if no other entity holds it.
+///
+/// # Safety
+/// `ptr` must point to a `SharedHandle<C>`.
+pub(crate) unsafe fn drop_shared<C: Data>(ptr: OwningPtr<'_>) {
+    release(ptr.read::<SharedHandle<C>>());
+}
+
+fn release<C: Data>(handle: SharedHandle<C>) {
+    match handle.table.upgrade() {
+        Some(table) => table.release(handle),
+        None => drop(handle),
+    }
+}
+
+/// Compare the values of two stored [`SharedHandle`]s.
+///
+/// # Safety
+/// `a` and `b` must point to `SharedHandle<C>`s.
+pub(crate) unsafe fn eq_shared<C: Data + PartialEq>(a: Ptr<'_>, b: Ptr<'_>) -> bool {
+    let (a, b) = (a.deref::<SharedHandle<C>>(), b.deref::<SharedHandle<C>>());
+    Arc::ptr_eq(a, b) || a.value == b.value
+}
+
+/// Hash the value of a shared component that the handle points to.
+///
+/// # Safety
+/// `ptr` must point to a `SharedHandle<C>`.
+pub(crate) unsafe fn hash_shared<C: Data + Hash>(ptr: Ptr<'_>, mut state: &mut dyn Hasher) {
+    ptr.deref::<SharedHandle<C>>().value.hash(&mut state)
+}
+
+impl World {
+    /// Register the shared component `C` (see [`Component::SHARED`]), so its values are interned: every entity whose
+    /// value of `C` is equal to the value of another entity shares the same copy of it. Values are compared with
+    /// the [`Hash`] and [`Eq`] implementations of `C`. If `C` is already registered as shared, this does nothing.
+    ///
+    /// The values of a shared component that isn't registered with this (or with
+    /// [`World::register_shared_by_key`]) aren't deduplicated: each entity gets a copy of its own, behind a pointer.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, PartialEq, Eq, Hash)]
+    /// #[component(shared)]
+    /// struct Material(&'static str);
+    ///
+    /// let mut world = World::default();
+    /// world.register_shared::<Material>();
+    /// for _ in 0..10 {
+    ///     world.spawn(Material("stone"));
+    /// }
+    /// world.spawn(Material("wood"));
+    /// assert_eq!(world.shared_values::<Material>(), 2);
+    /// ```
+    ///
+    /// # Panics
+    /// If `C` isn't shared, or if it can't be registered.
+    #[track_caller]
+    pub fn register_shared<C: Component + Hash + Eq>(&mut self) {
+        let state = RandomState::new();
+        self.register_shared_with(move |value: &C| state.hash_one(value), C::eq);
+    }
+
+    /// Like [`World::register_shared`], with values that are equal if they have the same key, like the id of the
+    /// asset that a mesh was loaded from.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// #[component(shared)]
+    /// struct Mesh {
+    ///     asset: u32,
+    ///     vertices: Vec<[f32; 3]>,
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.register_shared_by_key::<Mesh, u32>(|mesh| mesh.asset);
+    /// for _ in 0..10 {
+    ///     world.spawn(Mesh { asset: 7, vertices: vec![[0.0; 3]; 3] });
+    /// }
+    /// assert_eq!(world.shared_values::<Mesh>(), 1);
+    /// ```
+    ///
+    /// # Panics
+    /// If `C` isn't shared, or if it can't be registered.
+    #[track_caller]
+    pub fn register_shared_by_key<C: Component, K: Hash + Eq>(
+        &mut self,
+        key: impl Fn(&C) -> K + Send + Sync + 'static,
+    ) {
+        let (key, state) = (Arc::new(key), RandomState::new());
+        let eq_key = key.clone();
+        self.register_shared_with(
+            move |value: &C| state.hash_one(key(value)),
+            move |a: &C, b: &C| eq_key(a) == eq_key(b),
+        );
+    }
+
+    #[track_caller]
+    fn register_shared_with<C: Component>(
+        &mut self,
+        hash: impl Fn(&C) -> u64 + Send + Sync + 'static,
+        eq: impl Fn(&C, &C) -> bool + Send + Sync + 'static,
+    ) {
+        if !C::SHARED {
+            panics::fail_component(
+                "register_shared",
+                "the component isn't shared, mark it with #[component(shared)]",
+                std::any::type_name::<C>(),
+            )
+        }
+        if self.components.register_component::<C>().is_none() {
+            panics::fail_component(
+                "register_shared",
+                "the component can't be registered",
+                std::any::type_name::<C>(),
+            )
+        }
+        self.components.shared.register(hash, eq);
+    }
+
+    /// Replace the value of the shared component `C` of an entity (see [`Component::SHARED`]), which can't be
+    /// mutated in place. The new value is interned, and the old one is released if no other entity holds it.
+    /// Returns `false` if the entity isn't alive, or doesn't have the component.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, PartialEq, Eq, Hash)]
+    /// #[component(shared)]
+    /// struct Material(&'static str);
+    ///
+    /// let mut world = World::default();
+    /// world.register_shared::<Material>();
+    /// let entity = world.spawn(Material("stone"));
+    /// assert!(world.replace_shared(entity, Material("wood")));
+    /// assert_eq!(world.get_component::<Material>(entity).unwrap().0, "wood");
+    /// // The old value was released, since no other entity holds it.
+    /// assert_eq!(world.shared_values::<Material>(), 1);
+    /// ```
+    ///
+    /// # Panics
+    /// If `C` isn't shared.
+    #[track_caller]
+    pub fn replace_shared<C: Component>(&mut self, entity: EntityId, value: C) -> bool {
+        if !C::SHARED {
+            panics::fail_component(
+                "replace_shared",
+                "the component isn't shared, mutate it with World::get_component_mut",
+                std::any::type_name::<C>(),
+            )
+        }
+        self.access.record_component::<C>(
+            &self.components,
+            super::access::AccessKind::Write,
+            Location::caller(),
+        );
+        let Some(entity_meta) = self.entities.get_entity_meta(entity) else {
+            return false;
+        };
+        let Some(comp_id) = self.components.get_component_id::<C>() else {
+            return false;
+        };
+        let Some(raw_comp) = self
+            .storages
+            .arch_storages
+            .get_storage_mut(entity_meta.archetype_storage_id)
+            .and_then(|storage| {
+                storage.get_component_mut(entity_meta.archetype_storage_index, comp_id)
+            })
+        else {
+            return false;
+        };
+        let handle = make_component(value, &self.components, |ptr| {
+            // SAFETY: `make_component` makes a handle of a shared component.
+            unsafe { ptr.read::<SharedHandle<C>>() }
+        });
+        // SAFETY: The pointer was fetched with the id of `C`, which is stored as a `SharedHandle<C>`.
+        let old = std::mem::replace(unsafe { raw_comp.deref_mut::<SharedHandle<C>>() }, handle);
+        release(old);
+        true
+    }
+
+    /// How many values of the shared component `C` are interned (see [`World::register_shared`]), which is how
+    /// many distinct values the entities with `C` have.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, PartialEq, Eq, Hash)]
+    /// #[component(shared)]
+    /// struct Material(&'static str);
+    ///
+    /// let mut world = World::default();
+    /// assert_eq!(world.shared_values::<Material>(), 0);
+    /// world.register_shared::<Material>();
+    /// let (a, b) = (world.spawn(Material("stone")), world.spawn(Material("stone")));
+    /// assert_eq!(world.shared_values::<Material>(), 1);
+    /// world.despawn(a);
+    /// world.despawn(b);
+    /// assert_eq!(world.shared_values::<Material>(), 0);
+    /// ```
+    pub fn shared_values<C: Component>(&self) -> usize {
+        self.components
+            .shared
+            .table::<C>()
+            .map_or(0, |table| table.values())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Component, Clone, Debug, PartialEq, Eq, Hash)]
+    #[component(shared)]
+    struct StatBlock {
+        name: &'static str,
+        stats: [u32; 64],
+    }
+
+    fn stats(name: &'static str) -> StatBlock {
+        StatBlock {
+            name,
+            stats: [name.len() as u32; 64],
+        }
+    }
+
+    #[derive(Component)]
+    struct Unit(#[allow(unused)] u32);
+
+    #[test]
+    fn test_equal_values_are_interned_once() {
+        let mut world = World::default();
+        world.register_shared::<StatBlock>();
+        let (a, b) = (
+            world.spawn((Unit(0), stats("archer"))),
+            world.spawn((Unit(1), stats("archer"))),
+        );
+        let knight = world.spawn(stats("knight"));
+        assert_eq!(world.shared_values::<StatBlock>(), 2);
+        let (a_stats, b_stats) = (
+            world.get_component::<StatBlock>(a).unwrap(),
+            world.get_component::<StatBlock>(b).unwrap(),
+        );
+        assert!(std::ptr::eq(a_stats, b_stats));
+        assert_eq!(*a_stats, stats("archer"));
+        let mut names: Vec<&str> = world
+            .query::<&StatBlock>()
+            .map(|stats| stats.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["archer", "archer", "knight"]);
+        assert_eq!(world.query::<Option<&StatBlock>>().flatten().count(), 3);
+
+        assert!(world.replace_shared(knight, stats("archer")));
+        assert_eq!(world.shared_values::<StatBlock>(), 1);
+        assert!(std::ptr::eq(
+            world.get_component::<StatBlock>(knight).unwrap(),
+            world.get_component::<StatBlock>(a).unwrap()
+        ));
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_values_are_released_with_their_last_entity() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Component)]
+        #[component(shared)]
+        struct Mesh(u32);
+
+        impl Drop for Mesh {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let mut world = World::default();
+        world.register_shared_by_key::<Mesh, u32>(|mesh| mesh.0);
+        let entities: Vec<EntityId> = (0..10).map(|i| world.spawn(Mesh(i % 2))).collect();
+        // Each spawn of an existing value drops the spawned copy.
+        assert_eq!(DROPS.load(Ordering::Relaxed), 8);
+        assert_eq!(world.shared_values::<Mesh>(), 2);
+
+        for entity in &entities[..9] {
+            world.despawn(*entity);
+        }
+        assert_eq!(world.shared_values::<Mesh>(), 1);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 9);
+        // A copy of the world shares the value, so it's only released when both of them drop it.
+        let copy = world.clone_cow().unwrap();
+        world.despawn(entities[9]);
+        assert_eq!(world.shared_values::<Mesh>(), 1);
+        drop(copy);
+        assert_eq!(world.shared_values::<Mesh>(), 0);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "shared components are immutable")]
+    fn test_mutable_queries_are_rejected() {
+        let mut world = World::default();
+        world.register_shared::<StatBlock>();
+        world.spawn(stats("archer"));
+        world.query::<&mut StatBlock>().for_each(drop);
+    }
+
+    #[test]
+    #[should_panic(expected = "shared components are immutable")]
+    fn test_mutable_access_is_rejected() {
+        let mut world = World::default();
+        let entity = world.spawn(stats("archer"));
+        world.get_component_mut::<StatBlock>(entity);
+    }
+
+    #[test]
+    fn test_memory_savings() {
+        #[derive(Component, Clone, PartialEq, Eq, Hash)]
+        struct InlineStatBlock(#[allow(unused)] [u32; 64]);
+
+        let mut world = World::default();
+        world.track_allocations(0);
+        world.register_shared::<StatBlock>();
+        for _ in 0..1000 {
+            world.spawn((Unit(0), stats("archer")));
+            world.spawn((Unit(0), InlineStatBlock([6; 64])));
+        }
+        let shared = world.bytes_for_archetype::<(Unit, StatBlock)>().unwrap();
+        let inline = world
+            .bytes_for_archetype::<(Unit, InlineStatBlock)>()
+            .unwrap();
+        assert!(shared * 10 < inline);
+        // The interned value is counted once.
+        let report = world.stats();
+        assert_eq!(report.shared_bytes, std::mem::size_of::<StatBlock>());
+        assert!(report
+            .to_string()
+            .contains(&format!("shared bytes: {}", report.shared_bytes)));
+    }
+}
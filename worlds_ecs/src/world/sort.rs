@@ -0,0 +1,363 @@
+use super::{storage::storages::ArchStorageId, World};
+use crate::{
+    archetype::Archetype,
+    component::{deref_component, Component, ComponentId},
+    utils::panics,
+    world::storage::{arch_storage::ArchStorageIndex, ArchEntityStorage},
+};
+use bevy_ptr::Ptr;
+use std::{cmp::Ordering, sync::Arc};
+
+/// The order that a sort-maintained storage keeps its entities in (see [`World::maintain_sort`]).
+#[derive(Clone)]
+pub(crate) struct SortOrder {
+    /// The component that the key is computed from.
+    comp_id: ComponentId,
+    /// Compares the keys of two components with [`SortOrder::comp_id`].
+    cmp: Arc<dyn Fn(Ptr<'_>, Ptr<'_>) -> Ordering + Send + Sync>,
+    /// The generation of the storage when it was last sorted.
+    sorted_generation: u64,
+}
+
+impl SortOrder {
+    /// The component that the key is computed from.
+    pub(crate) fn key_component(&self) -> ComponentId {
+        self.comp_id
+    }
+
+    /// Compare the keys of two rows of the storage.
+    ///
+    /// # Safety
+    /// The storage must store the component of the key, and both rows must be in bounds.
+    unsafe fn cmp_rows(&self, storage: &ArchEntityStorage, a: usize, b: usize) -> Ordering {
+        (self.cmp)(
+            storage.get_component_unchecked(ArchStorageIndex(a), self.comp_id),
+            storage.get_component_unchecked(ArchStorageIndex(b), self.comp_id),
+        )
+    }
+
+    /// The row that `row` belongs in, among the rows before `end` (which must be sorted): right after the last row
+    /// whose key isn't greater than its key, so rows with equal keys keep their order.
+    ///
+    /// # Safety
+    /// See [`Self::cmp_rows`].
+    unsafe fn insertion_point(&self, storage: &ArchEntityStorage, row: usize, end: usize) -> usize {
+        let (mut low, mut high) = (0, end);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.cmp_rows(storage, mid, row) == Ordering::Greater {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        low
+    }
+}
+
+impl World {
+    /// Keep the storage of the exact archetype `A` sorted by the key that `key_fn` computes from its component `C`,
+    /// so querying it yields its entities in that order, without sorting them every frame. The storage is
+    /// sorted right away.
+    ///
+    /// This trades mutation cost for iteration order: spawning into the storage inserts the entity in its place
+    /// (shifting every entity after it), and despawning from it shifts the entities after it back, instead of
+    /// moving a single entity. Mutating the keys doesn't reorder the storage, call [`World::resort_dirty`] to
+    /// restore the order after the keys change.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Depth(i32);
+    ///
+    /// let mut world = World::default();
+    /// world.maintain_sort::<Depth, Depth, i32>(|depth| depth.0);
+    /// for depth in [3, 1, 2] {
+    ///     world.spawn(Depth(depth));
+    /// }
+    /// let depths: Vec<i32> = world.query::<&Depth>().map(|depth| depth.0).collect();
+    /// assert_eq!(depths, vec![1, 2, 3]);
+    /// ```
+    ///
+    /// # Panics
+    /// If `C` isn't one of the components of `A`.
+    #[track_caller]
+    pub fn maintain_sort<A: Archetype, C: Component, K: Ord + 'static>(
+        &mut self,
+        key_fn: fn(&C) -> K,
+    ) {
+        let (sid, storage) = self
+            .storages
+            .arch_storages
+            .get_mut_or_create_storage_with_exact_archetype::<A>(&mut self.components);
+        let Some(comp_id) = self
+            .components
+            .get_component_id::<C>()
+            .filter(|comp_id| storage.contains(*comp_id))
+        else {
+            panics::fail_component(
+                "maintain_sort",
+                "the key of a sorted storage must be one of the components of its archetype",
+                std::any::type_name::<C>(),
+            );
+        };
+        storage.sort = Some(SortOrder {
+            comp_id,
+            // SAFETY: The order only compares components with `comp_id`, which are `C`s.
+            cmp: Arc::new(move |a, b| unsafe {
+                key_fn(deref_component::<C>(a)).cmp(&key_fn(deref_component::<C>(b)))
+            }),
+            sorted_generation: storage.generation().wrapping_sub(1),
+        });
+        self.resort_dirty_storage(sid);
+    }
+
+    /// Restore the order of the sort-maintained storages (see [`World::maintain_sort`]) whose components may
+    /// have changed since they were last sorted, and return how many entities were moved. Every entity that's out
+    /// of order is moved back into its place, so when only a few keys changed, only a few entities move. Storages
+    /// that weren't mutated (see [`ArchStorage::generation`](super::storage::arch_storage::ArchStorage::generation))
+    /// are skipped without looking at their keys.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Depth(i32);
+    ///
+    /// let mut world = World::default();
+    /// world.maintain_sort::<Depth, Depth, i32>(|depth| depth.0);
+    /// let entity = world.spawn(Depth(0));
+    /// world.spawn(Depth(1));
+    /// world.spawn(Depth(2));
+    /// world.get_component_mut::<Depth>(entity).unwrap().0 = 3;
+    /// assert!(world.resort_dirty() > 0);
+    /// let depths: Vec<i32> = world.query::<&Depth>().map(|depth| depth.0).collect();
+    /// assert_eq!(depths, vec![1, 2, 3]);
+    /// // Nothing changed since.
+    /// assert_eq!(world.resort_dirty(), 0);
+    /// ```
+    pub fn resort_dirty(&mut self) -> usize {
+        let mut moved = 0;
+        let mut sid = ArchStorageId(0);
+        while let Some(storage) = self.storages.arch_storages.get_storage(sid) {
+            if storage
+                .sort
+                .as_ref()
+                .is_some_and(|sort| sort.sorted_generation != storage.generation())
+            {
+                moved += self.resort_dirty_storage(sid);
+            }
+            sid = ArchStorageId(sid.0 + 1);
+        }
+        moved
+    }
+
+    fn resort_dirty_storage(&mut self, sid: ArchStorageId) -> usize {
+        if self.is_storage_pinned(sid) {
+            // It stays dirty, and is sorted after it's unpinned.
+            return 0;
+        }
+        let moved = self.sort_rows_from(sid, 1);
+        let storage = self.storages.arch_storages.get_storage_mut(sid).unwrap();
+        let generation = storage.generation();
+        if let Some(sort) = &mut storage.sort {
+            sort.sorted_generation = generation;
+        }
+        moved
+    }
+
+    /// Move every row of a sort-maintained storage from `first` onwards that's out of order into its place, assuming
+    /// the rows before it are sorted, and update the [`EntityMeta`](crate::entity::EntityMeta)s of the entities
+    /// that moved. Returns how many rows were moved.
+    ///
+    /// When two neighbouring rows are out of order, the one whose key changed is moved: a row whose key grew is moved
+    /// forward (past the rows that are now smaller than it), and any other row is moved back into the sorted rows
+    /// before it. So after a few keys change, only the rows of those keys are moved.
+    pub(crate) fn sort_rows_from(&mut self, sid: ArchStorageId, first: usize) -> usize {
+        let storage = self.storages.arch_storages.get_storage_mut(sid).unwrap();
+        let Some(sort) = storage.sort.clone() else {
+            return 0;
+        };
+        let len = storage.len();
+        let mut moved = 0;
+        // The rows before `row` are sorted.
+        let mut row = first.max(1);
+        while row < len {
+            // SAFETY: The storage stores the component of its key, and the rows are in bounds.
+            let (from, to) = unsafe {
+                let greater = |a, b| sort.cmp_rows(storage, a, b) == Ordering::Greater;
+                if !greater(row - 1, row) {
+                    row += 1;
+                    continue;
+                }
+                let grew = (row + 1 == len || greater(row - 1, row + 1))
+                    && (row < 2 || !greater(row - 2, row));
+                if grew {
+                    // The rows after it that are sorted and not greater than it move back a row.
+                    let mut end = row + 1;
+                    while end < len && !greater(end, row - 1) && !greater(end - 1, end) {
+                        end += 1;
+                    }
+                    (row - 1, end - 1)
+                } else {
+                    (row, sort.insertion_point(storage, row, row - 1))
+                }
+            };
+            storage.move_row(ArchStorageIndex(from), ArchStorageIndex(to));
+            for index in from.min(to)..=from.max(to) {
+                self.entities.set_entity_arch_storage_index(
+                    ArchStorageIndex(index),
+                    storage.entities()[index],
+                );
+            }
+            moved += 1;
+            row = from.max(to) + 1;
+        }
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[derive(Component, Debug, PartialEq, Clone, Copy)]
+    struct Depth(i32);
+    #[derive(Component, Debug, PartialEq)]
+    struct Label(usize);
+
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 as usize
+        }
+
+        fn depth(&mut self) -> Depth {
+            Depth((self.next() % 200) as i32 - 100)
+        }
+    }
+
+    fn assert_sorted(world: &mut World) {
+        let depths: Vec<i32> = world
+            .query::<(&Depth, &Label)>()
+            .map(|(d, _)| d.0)
+            .collect();
+        assert!(depths.is_sorted(), "{depths:?}");
+    }
+
+    #[test]
+    fn test_sorted_storage_randomized() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let mut world = World::default();
+        let mut alive: Vec<EntityId> = (0..100)
+            .map(|i| world.spawn((random.depth(), Label(i))))
+            .collect();
+        world.maintain_sort::<(Depth, Label), Depth, i32>(|depth| depth.0);
+        assert_sorted(&mut world);
+        world.assert_invariants();
+
+        let mut dirty = false;
+        for step in 0..3000 {
+            match random.next() % 8 {
+                0..=3 => alive.push(world.spawn((random.depth(), Label(100 + step)))),
+                4 | 5 if !alive.is_empty() => {
+                    world.despawn(alive.swap_remove(random.next() % alive.len()));
+                }
+                6 if !alive.is_empty() => {
+                    let entity = alive[random.next() % alive.len()];
+                    *world.get_component_mut::<Depth>(entity).unwrap() = random.depth();
+                    dirty = true;
+                }
+                _ => {
+                    world.resort_dirty();
+                    dirty = false;
+                }
+            }
+            if !dirty {
+                assert_sorted(&mut world);
+            }
+            if step % 100 == 0 {
+                world.assert_invariants();
+            }
+        }
+        world.resort_dirty();
+        assert_sorted(&mut world);
+        world.assert_invariants();
+        let mut labels: Vec<usize> = world.query::<&Label>().map(|label| label.0).collect();
+        let mut expected: Vec<usize> = alive
+            .iter()
+            .map(|entity| world.get_component::<Label>(*entity).unwrap().0)
+            .collect();
+        labels.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(labels, expected);
+
+        // Nothing changed, so nothing is looked at.
+        assert_eq!(world.resort_dirty(), 0);
+        // A single changed key moves a single entity.
+        let first = world
+            .query::<(EntityId, &Depth, &Label)>()
+            .next()
+            .unwrap()
+            .0;
+        world.get_component_mut::<Depth>(first).unwrap().0 = 1000;
+        assert_eq!(world.resort_dirty(), 1);
+        assert_eq!(
+            world
+                .query::<(EntityId, &Depth, &Label)>()
+                .last()
+                .unwrap()
+                .0,
+            first
+        );
+    }
+
+    #[test]
+    fn test_equal_keys_keep_spawn_order() {
+        let mut world = World::default();
+        world.maintain_sort::<(Depth, Label), Depth, i32>(|depth| depth.0);
+        for i in 0..20 {
+            world.spawn((Depth(i as i32 % 3), Label(i)));
+        }
+        let order: Vec<(i32, usize)> = world
+            .query::<(&Depth, &Label)>()
+            .map(|(depth, label)| (depth.0, label.0))
+            .collect();
+        let mut expected = order.clone();
+        expected.sort_by_key(|(depth, _)| *depth);
+        assert_eq!(order, expected);
+        assert!(order
+            .windows(2)
+            .all(|w| w[0].0 != w[1].0 || w[0].1 < w[1].1));
+    }
+
+    #[test]
+    fn test_unsorted_storages_unaffected() {
+        let mut world = World::default();
+        world.maintain_sort::<(Depth, Label), Depth, i32>(|depth| depth.0);
+        let entities: Vec<EntityId> = (0..10).map(|i| world.spawn(Depth(10 - i))).collect();
+        // Spawning doesn't reorder other storages.
+        assert!(world
+            .query::<&Depth>()
+            .map(|depth| depth.0)
+            .eq((1..=10).rev()));
+        // Despawning still swap-removes in other storages.
+        world.despawn(entities[0]);
+        assert_eq!(world.query::<&Depth>().next(), Some(&Depth(1)));
+        world.get_component_mut::<Depth>(entities[5]).unwrap().0 = 100;
+        assert_eq!(world.resort_dirty(), 0);
+        world.assert_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: maintain_sort failed: the key of a sorted storage")]
+    fn test_key_must_be_in_archetype() {
+        let mut world = World::default();
+        world.maintain_sort::<Label, Depth, i32>(|depth| depth.0);
+    }
+}
@@ -0,0 +1,579 @@
+use super::{storage::storages::ArchStorageId, World};
+use crate::{entity::EntityId, prelude::Component, utils::panics};
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+};
+
+/// An axis-aligned bounding box, the region of a [`World::query_region`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    /// The corner of the box with the smallest coordinates.
+    pub min: [f32; 3],
+    /// The corner of the box with the largest coordinates.
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    /// Create a new [`Aabb`] from its corners.
+    pub fn new(min: [f32; 3], max: [f32; 3]) -> Self {
+        Aabb { min, max }
+    }
+
+    /// Returns `true` if the point is inside the box (including its boundary).
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        (0..3).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+}
+
+/// A component that has a position in space, so it can be indexed by a [`UniformGrid`].
+pub trait SpatialPosition {
+    /// The position of the component. 2D positions should use `0.0` for the last coordinate.
+    fn position(&self) -> [f32; 3];
+}
+
+/// A spatial index of the entities with the component `C`, that can be attached to a [`World`] with
+/// [`World::attach_spatial_index`]. The world tells the index about every entity whose `C` was added, changed
+/// or removed, so the index only needs to answer region queries.
+pub trait SpatialIndex<C: Component>: Send + Sync {
+    /// An entity with the component was added.
+    fn insert(&mut self, entity: EntityId, component: &C);
+    /// The component of an entity that was already inserted changed.
+    fn update(&mut self, entity: EntityId, component: &C);
+    /// An entity that was inserted was despawned.
+    fn remove(&mut self, entity: EntityId);
+    /// The entities inside the region (in no particular order).
+    fn query_region(&self, aabb: &Aabb) -> Vec<EntityId>;
+}
+
+/// A [`SpatialIndex`] that divides space into a uniform grid of cubic cells, for components that implement
+/// [`SpatialPosition`].
+pub struct UniformGrid {
+    cell_size: f32,
+    cells: HashMap<[i32; 3], Vec<EntityId>>,
+    positions: HashMap<EntityId, [f32; 3]>,
+}
+
+impl UniformGrid {
+    /// Create a new empty [`UniformGrid`] whose cells are `cell_size` long on each axis.
+    /// Regions that are around the size of a cell are the fastest to query.
+    pub fn new(cell_size: f32) -> Self {
+        assert!(
+            cell_size > 0.0,
+            "The cells of a grid must have a positive size"
+        );
+        UniformGrid {
+            cell_size,
+            cells: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    fn cell_of(&self, position: [f32; 3]) -> [i32; 3] {
+        position.map(|coordinate| (coordinate / self.cell_size).floor() as i32)
+    }
+
+    fn insert_at(&mut self, entity: EntityId, position: [f32; 3]) {
+        self.cells
+            .entry(self.cell_of(position))
+            .or_default()
+            .push(entity);
+        self.positions.insert(entity, position);
+    }
+
+    fn remove_from_cell(&mut self, entity: EntityId, position: [f32; 3]) {
+        let cell = self.cell_of(position);
+        if let Some(entities) = self.cells.get_mut(&cell) {
+            if let Some(i) = entities.iter().position(|e| *e == entity) {
+                entities.swap_remove(i);
+            }
+            if entities.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+}
+
+impl<C: Component + SpatialPosition> SpatialIndex<C> for UniformGrid {
+    fn insert(&mut self, entity: EntityId, component: &C) {
+        self.insert_at(entity, component.position());
+    }
+
+    fn update(&mut self, entity: EntityId, component: &C) {
+        let position = component.position();
+        match self.positions.get(&entity).copied() {
+            Some(old) if self.cell_of(old) == self.cell_of(position) => {
+                self.positions.insert(entity, position);
+            }
+            Some(old) => {
+                self.remove_from_cell(entity, old);
+                self.insert_at(entity, position);
+            }
+            None => self.insert_at(entity, position),
+        }
+    }
+
+    fn remove(&mut self, entity: EntityId) {
+        if let Some(position) = self.positions.remove(&entity) {
+            self.remove_from_cell(entity, position);
+        }
+    }
+
+    fn query_region(&self, aabb: &Aabb) -> Vec<EntityId> {
+        let min = self.cell_of(aabb.min);
+        let max = self.cell_of(aabb.max);
+        let in_region = |entity: &&EntityId| aabb.contains(self.positions[*entity]);
+        let region_cells = (0..3)
+            .map(|axis| (max[axis] as i64 - min[axis] as i64 + 1).max(0) as u128)
+            .product::<u128>();
+        // Large regions are cheaper to query by going over the occupied cells.
+        if region_cells > self.cells.len() as u128 {
+            return self
+                .cells
+                .iter()
+                .filter(|(cell, _)| {
+                    (0..3).all(|axis| min[axis] <= cell[axis] && cell[axis] <= max[axis])
+                })
+                .flat_map(|(_, entities)| entities.iter().filter(in_region).copied())
+                .collect();
+        }
+        let mut entities = Vec::new();
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    if let Some(cell) = self.cells.get(&[x, y, z]) {
+                        entities.extend(cell.iter().filter(in_region).copied());
+                    }
+                }
+            }
+        }
+        entities
+    }
+}
+
+/// A type-erased attached index, so indexes of different components can be stored together.
+trait SpatialSync: Send + Sync {
+    /// Tell the index about the entities whose component was added, changed or removed since the last sync.
+    /// Returns how many entities were touched.
+    fn sync(&mut self, world: &World) -> usize;
+
+    fn query_region(&self, aabb: &Aabb) -> Vec<EntityId>;
+}
+
+/// The component of an entity, as it was when the index was last synced.
+struct Synced<C> {
+    component: C,
+    /// The sync in which the entity was last seen.
+    seen: u64,
+}
+
+struct AttachedIndex<C: Component> {
+    index: Box<dyn SpatialIndex<C>>,
+    /// The generation of each storage with `C` when the index was last synced.
+    generations: HashMap<ArchStorageId, u64>,
+    /// The entities of each storage with `C` when the index was last synced.
+    rows: HashMap<ArchStorageId, Vec<EntityId>>,
+    synced: HashMap<EntityId, Synced<C>>,
+    syncs: u64,
+}
+
+impl<C: Component + Clone + PartialEq> SpatialSync for AttachedIndex<C> {
+    fn sync(&mut self, world: &World) -> usize {
+        let Some(comp_id) = world.components.get_component_id::<C>() else {
+            return 0;
+        };
+        self.syncs += 1;
+        let mut touched = 0;
+        let arch_storages = &world.storages.arch_storages;
+        let mut start = ArchStorageId(0);
+        while let Some(sid) =
+            arch_storages.next_storage_with_matching_archetype(start, comp_id.prime_key())
+        {
+            start = ArchStorageId(sid.0 + 1);
+            // SAFETY: The id came from the storages.
+            let storage = unsafe { arch_storages.get_storage(sid).unwrap_unchecked() };
+            // Storages that didn't change since the last sync (nothing was stored, removed or mutably accessed)
+            // can be skipped entirely.
+            if self.generations.insert(sid, storage.generation()) == Some(storage.generation()) {
+                continue;
+            }
+            // SAFETY: The component id matches `C`.
+            let column = unsafe { storage.iter_column::<C>(comp_id).unwrap_unchecked() };
+            for (entity, component) in storage.entities().iter().zip(column) {
+                match self.synced.get_mut(entity) {
+                    Some(synced) => {
+                        synced.seen = self.syncs;
+                        if synced.component != *component {
+                            self.index.update(*entity, component);
+                            synced.component = component.clone();
+                            touched += 1;
+                        }
+                    }
+                    None => {
+                        self.index.insert(*entity, component);
+                        self.synced.insert(
+                            *entity,
+                            Synced {
+                                component: component.clone(),
+                                seen: self.syncs,
+                            },
+                        );
+                        touched += 1;
+                    }
+                }
+            }
+            // An entity that was in the storage, but wasn't seen in this sync was despawned. Only the changed
+            // storages can lose entities, so the other entities don't need to be checked.
+            let previous = self.rows.insert(sid, storage.entities().to_vec());
+            for entity in previous.into_iter().flatten() {
+                if self
+                    .synced
+                    .get(&entity)
+                    .is_some_and(|synced| synced.seen != self.syncs)
+                {
+                    self.synced.remove(&entity);
+                    self.index.remove(entity);
+                    touched += 1;
+                }
+            }
+        }
+        touched
+    }
+
+    fn query_region(&self, aabb: &Aabb) -> Vec<EntityId> {
+        self.index.query_region(aabb)
+    }
+}
+
+/// The spatial indexes that are attached to a [`World`], by the [`TypeId`] of their component.
+#[derive(Default)]
+pub(crate) struct SpatialRegistry {
+    indexes: HashMap<TypeId, Box<dyn SpatialSync>>,
+}
+
+impl SpatialRegistry {
+    /// Returns `true` if no spatial indexes are attached.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    #[track_caller]
+    fn query_region<C: Component>(&self, aabb: &Aabb) -> Vec<EntityId> {
+        let Some(index) = self.indexes.get(&TypeId::of::<C>()) else {
+            panics::fail_component(
+                "query_region",
+                "no spatial index is attached for the component",
+                type_name::<C>(),
+            );
+        };
+        index.query_region(aabb)
+    }
+}
+
+impl World {
+    /// Attach a [`SpatialIndex`] of the entities with the component `C` (replacing the index that was attached for
+    /// `C` before, if there was one). The index is maintained by [`World::sync_spatial_indexes`], and queried with
+    /// [`World::query_region`].
+    ///
+    /// Changes are detected by comparing the components with their values from the last sync,
+    /// which is why `C` must be [`Clone`] and [`PartialEq`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, PartialEq)]
+    /// struct Position([f32; 3]);
+    ///
+    /// impl SpatialPosition for Position {
+    ///     fn position(&self) -> [f32; 3] {
+    ///         self.0
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.attach_spatial_index::<Position>(Box::new(UniformGrid::new(10.0)));
+    /// let near = world.spawn(Position([1.0, 1.0, 0.0]));
+    /// world.spawn(Position([50.0, 50.0, 0.0]));
+    /// world.sync_spatial_indexes();
+    /// let region = Aabb::new([0.0, 0.0, 0.0], [5.0, 5.0, 0.0]);
+    /// let found: Vec<EntityId> = world.query_region::<EntityId, Position>(region).collect();
+    /// assert_eq!(found, vec![near]);
+    /// ```
+    pub fn attach_spatial_index<C: Component + Clone + PartialEq>(
+        &mut self,
+        index: Box<dyn SpatialIndex<C>>,
+    ) {
+        let attached: Box<dyn SpatialSync> = Box::new(AttachedIndex {
+            index,
+            generations: HashMap::new(),
+            rows: HashMap::new(),
+            synced: HashMap::new(),
+            syncs: 0,
+        });
+        self.spatial.indexes.insert(TypeId::of::<C>(), attached);
+    }
+
+    /// Tell every attached [`SpatialIndex`] about the entities whose component was added, changed or removed since
+    /// the last sync, and return how many entities were touched. This is meant to run once per frame.
+    ///
+    /// Storages that weren't changed (see [`ArchStorage::generation`](super::storage::arch_storage::ArchStorage::generation))
+    /// since the last sync are skipped, and only the entities whose component doesn't equal its value from the last
+    /// sync are passed to the index.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, PartialEq)]
+    /// struct Position([f32; 3]);
+    ///
+    /// impl SpatialPosition for Position {
+    ///     fn position(&self) -> [f32; 3] {
+    ///         self.0
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.attach_spatial_index::<Position>(Box::new(UniformGrid::new(10.0)));
+    /// let entity = world.spawn(Position([1.0, 1.0, 0.0]));
+    /// world.spawn(Position([2.0, 2.0, 0.0]));
+    /// assert_eq!(world.sync_spatial_indexes(), 2);
+    /// // Only the entity that moved is passed to the index.
+    /// world.get_component_mut::<Position>(entity).unwrap().0 = [3.0, 3.0, 0.0];
+    /// assert_eq!(world.sync_spatial_indexes(), 1);
+    /// ```
+    pub fn sync_spatial_indexes(&mut self) -> usize {
+        let mut spatial = std::mem::take(&mut self.spatial);
+        let touched = spatial
+            .indexes
+            .values_mut()
+            .map(|index| index.sync(self))
+            .sum();
+        self.spatial = spatial;
+        touched
+    }
+
+    /// Iterate over the matches of the query `Q` for the entities inside the region, according to the [`SpatialIndex`]
+    /// that's attached for `C`. Only the entities that the index returns are fetched (see [`World::query_one`]), and
+    /// the ones that don't match `Q` are skipped.
+    ///
+    /// The index reflects the world as it was in the last [`World::sync_spatial_indexes`].
+    /// Panics if no index is attached for `C`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone, PartialEq)]
+    /// struct Position([f32; 3]);
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// impl SpatialPosition for Position {
+    ///     fn position(&self) -> [f32; 3] {
+    ///         self.0
+    ///     }
+    /// }
+    ///
+    /// let mut world = World::default();
+    /// world.attach_spatial_index::<Position>(Box::new(UniformGrid::new(10.0)));
+    /// world.spawn((Position([1.0, 1.0, 0.0]), Health(10)));
+    /// world.spawn(Position([2.0, 2.0, 0.0]));
+    /// world.sync_spatial_indexes();
+    /// let region = Aabb::new([0.0, 0.0, 0.0], [5.0, 5.0, 0.0]);
+    /// // Both entities are in the region, but only one of them has `Health`.
+    /// for health in world.query_region::<&mut Health, Position>(region) {
+    ///     health.0 -= 1;
+    /// }
+    /// assert_eq!(world.query::<&Health>().next().unwrap().0, 9);
+    /// ```
+    #[track_caller]
+    pub fn query_region<Q: crate::prelude::ArchQuery, C: Component>(
+        &mut self,
+        aabb: Aabb,
+    ) -> impl Iterator<Item = Q::Item<'_>> + '_ {
+        let mut entities = self.spatial.query_region::<C>(&aabb);
+        entities.sort_unstable_by_key(|entity| (entity.id(), entity.generation()));
+        entities.dedup();
+        // SAFETY: The entities are distinct, so every component is fetched at most once.
+        unsafe { self.query_many::<Q, _>(entities) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::collections::HashSet;
+
+    #[derive(Component, Clone, PartialEq, Debug)]
+    struct Position([f32; 3]);
+
+    impl SpatialPosition for Position {
+        fn position(&self) -> [f32; 3] {
+            self.0
+        }
+    }
+
+    #[derive(Component)]
+    struct Label(usize);
+
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 as usize
+        }
+
+        fn coordinate(&mut self) -> f32 {
+            (self.next() % 2000) as f32 / 20.0 - 50.0
+        }
+    }
+
+    fn spawn(world: &mut World, random: &mut Random, i: usize) -> EntityId {
+        let position = Position([
+            random.coordinate(),
+            random.coordinate(),
+            random.coordinate() / 10.0,
+        ]);
+        if i.is_multiple_of(3) {
+            world.spawn((position, Label(i)))
+        } else {
+            world.spawn(position)
+        }
+    }
+
+    fn grid_world() -> World {
+        let mut world = World::default();
+        world.attach_spatial_index::<Position>(Box::new(UniformGrid::new(4.0)));
+        world
+    }
+
+    #[test]
+    fn test_sync_touches_only_changes() {
+        let mut world = grid_world();
+        let entities: Vec<EntityId> = (0..100)
+            .map(|i| world.spawn(Position([i as f32, 0.0, 0.0])))
+            .collect();
+        world.spawn(Label(0));
+        assert_eq!(world.sync_spatial_indexes(), 100);
+        assert_eq!(world.sync_spatial_indexes(), 0);
+
+        world.get_component_mut::<Position>(entities[0]).unwrap().0[1] = 50.0;
+        world.get_component_mut::<Position>(entities[1]).unwrap().0[0] = 1.5;
+        // Mutable access without an actual change isn't passed to the index.
+        world.get_component_mut::<Position>(entities[2]);
+        world.despawn(entities[3]);
+        assert_eq!(world.sync_spatial_indexes(), 3);
+
+        let region = |world: &mut World, aabb| {
+            let mut found: Vec<EntityId> = world.query_region::<EntityId, Position>(aabb).collect();
+            found.sort_by_key(|entity| entity.id());
+            found
+        };
+        assert_eq!(
+            region(&mut world, Aabb::new([0.0, 40.0, -1.0], [10.0, 60.0, 1.0])),
+            vec![entities[0]]
+        );
+        assert_eq!(
+            region(&mut world, Aabb::new([1.0, -1.0, -1.0], [3.5, 1.0, 1.0])),
+            vec![entities[1], entities[2]]
+        );
+
+        // The index reflects the last sync.
+        world.get_component_mut::<Position>(entities[4]).unwrap().0[1] = 50.0;
+        assert_eq!(
+            region(&mut world, Aabb::new([4.0, -1.0, -1.0], [4.0, 1.0, 1.0])),
+            vec![entities[4]]
+        );
+        world.sync_spatial_indexes();
+        assert!(region(&mut world, Aabb::new([4.0, -1.0, -1.0], [4.0, 1.0, 1.0])).is_empty());
+    }
+
+    #[test]
+    fn test_query_region_matches_brute_force() {
+        let mut world = grid_world();
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let mut alive: Vec<EntityId> = (0..1000)
+            .map(|i| spawn(&mut world, &mut random, i))
+            .collect();
+
+        for frame in 0..30 {
+            // Move some of the entities.
+            world.query::<&mut Position>().for_each(|position| {
+                if random.next().is_multiple_of(4) {
+                    position.0[0] += (random.next() % 100) as f32 / 10.0 - 5.0;
+                    position.0[1] += (random.next() % 100) as f32 / 10.0 - 5.0;
+                }
+            });
+            // Churn: despawn some entities, and spawn (at most) as many.
+            let despawned = random.next() % 50;
+            for _ in 0..despawned {
+                let entity = alive.swap_remove(random.next() % alive.len());
+                world.despawn(entity);
+            }
+            for i in 0..despawned - despawned % 3 {
+                alive.push(spawn(&mut world, &mut random, frame * 100 + i));
+            }
+            world.sync_spatial_indexes();
+
+            for _ in 0..10 {
+                let (x, y, z) = (
+                    random.coordinate(),
+                    random.coordinate(),
+                    random.coordinate() / 10.0,
+                );
+                let size = (random.next() % 300) as f32 / 10.0;
+                let aabb = Aabb::new([x, y, z], [x + size, y + size, z + size / 4.0]);
+                let expected: HashSet<EntityId> = world
+                    .query::<(EntityId, &Position)>()
+                    .filter(|(_, position)| aabb.contains(position.0))
+                    .map(|(entity, _)| entity)
+                    .collect();
+                let found: Vec<EntityId> = world.query_region::<EntityId, Position>(aabb).collect();
+                assert_eq!(found.len(), expected.len());
+                assert_eq!(found.into_iter().collect::<HashSet<_>>(), expected);
+
+                // Entities that don't match the query are skipped.
+                let labeled = world
+                    .query_region::<(&Position, &Label), Position>(aabb)
+                    .inspect(|(position, _)| assert!(aabb.contains(position.0)))
+                    .count();
+                assert_eq!(
+                    labeled,
+                    world
+                        .query::<(&Position, &Label)>()
+                        .filter(|(position, _)| aabb.contains(position.0))
+                        .count()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_query_one() {
+        let mut world = World::default();
+        let labeled = world.spawn((Position([1.0, 2.0, 3.0]), Label(7)));
+        let unlabeled = world.spawn(Position([0.0; 3]));
+
+        let (position, label) = world.query_one::<(&mut Position, &Label)>(labeled).unwrap();
+        position.0[0] = label.0 as f32;
+        assert_eq!(
+            world.get_component::<Position>(labeled),
+            Some(&Position([7.0, 2.0, 3.0]))
+        );
+        assert!(world.query_one::<&Label>(unlabeled).is_none());
+        world.despawn(labeled);
+        assert!(world.query_one::<&Position>(labeled).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: query_region failed: no spatial index is attached")]
+    fn test_query_region_without_index() {
+        let mut world = World::default();
+        world.spawn(Position([0.0; 3]));
+        world
+            .query_region::<EntityId, Position>(Aabb::new([0.0; 3], [1.0; 3]))
+            .count();
+    }
+}
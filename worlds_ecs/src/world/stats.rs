@@ -0,0 +1,260 @@
+use super::World;
+use crate::archetype::key::PrimeArchKey;
+use std::fmt;
+
+/// A snapshot of the statistics of a [`World`], see [`World::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldStats {
+    /// The entities in the world, including the archived entities.
+    pub entities: u32,
+    /// The archived entities (see [`World::archive`]).
+    pub archived: usize,
+    /// The storages of archetypes.
+    pub storages: usize,
+    /// How many entities were ever spawned.
+    pub spawned: u64,
+    /// How many entities were ever despawned.
+    pub despawned: u64,
+    /// The bytes that the storages allocated, or `None` if the allocations aren't tracked (see
+    /// [`World::track_allocations`]).
+    pub component_bytes: Option<usize>,
+    /// The archetype whose storage allocated the most bytes, with its bytes. `None` if the allocations aren't
+    /// tracked, or if no storage allocated anything.
+    pub largest_archetype: Option<(PrimeArchKey, usize)>,
+    /// The bytes of the interned values of the shared components (see
+    /// [`Component::SHARED`](crate::prelude::Component::SHARED)), which the storages only hold pointers to. Each
+    /// distinct value is counted once, however many entities have it.
+    pub shared_bytes: usize,
+}
+
+impl WorldStats {
+    /// The changes since the `previous` snapshot, like the spawns and despawns of a frame.
+    pub fn delta(&self, previous: &WorldStats) -> WorldStatsDelta {
+        WorldStatsDelta {
+            entities: self.entities as i64 - previous.entities as i64,
+            storages: self.storages - previous.storages,
+            spawned: self.spawned - previous.spawned,
+            despawned: self.despawned - previous.despawned,
+            component_bytes: self
+                .component_bytes
+                .zip(previous.component_bytes)
+                .map(|(bytes, previous)| bytes as isize - previous as isize),
+        }
+    }
+}
+
+impl fmt::Display for WorldStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "entities: {} ({} archived), storages: {}, spawned: {}, despawned: {}",
+            self.entities, self.archived, self.storages, self.spawned, self.despawned
+        )?;
+        if let Some(bytes) = self.component_bytes {
+            write!(f, ", component bytes: {bytes}")?;
+        }
+        if let Some((_, bytes)) = self.largest_archetype {
+            write!(f, ", largest archetype: {bytes} bytes")?;
+        }
+        if self.shared_bytes > 0 {
+            write!(f, ", shared bytes: {}", self.shared_bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// The changes between two [`WorldStats`], see [`WorldStats::delta`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldStatsDelta {
+    /// The change in the amount of entities.
+    pub entities: i64,
+    /// The storages that were created (storages are never removed).
+    pub storages: usize,
+    /// The entities that were spawned.
+    pub spawned: u64,
+    /// The entities that were despawned.
+    pub despawned: u64,
+    /// The change in the bytes that the storages allocated, or `None` if either snapshot didn't track them.
+    pub component_bytes: Option<isize>,
+}
+
+impl fmt::Display for WorldStatsDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "entities: {:+}, storages: +{}, spawned: {}, despawned: {}",
+            self.entities, self.storages, self.spawned, self.despawned
+        )?;
+        if let Some(bytes) = self.component_bytes {
+            write!(f, ", component bytes: {bytes:+}")?;
+        }
+        Ok(())
+    }
+}
+
+impl World {
+    /// A snapshot of the statistics of the world, for a HUD that shows them every frame. Every number is read from
+    /// a counter that is kept up to date as the world changes, so this doesn't visit the storages, and doesn't
+    /// allocate. The byte counts require the allocations to be tracked (see [`World::track_allocations`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Unit;
+    ///
+    /// let mut world = World::default();
+    /// let before = world.stats();
+    /// let unit = world.spawn(Unit);
+    /// world.spawn(Unit);
+    /// world.despawn(unit);
+    /// let stats = world.stats();
+    /// assert_eq!(stats.entities, 1);
+    /// let delta = stats.delta(&before);
+    /// assert_eq!((delta.spawned, delta.despawned, delta.storages), (2, 1, 1));
+    /// ```
+    pub fn stats(&self) -> WorldStats {
+        let (component_bytes, largest_archetype) = self
+            .components
+            .storage_alloc()
+            .tracker()
+            .map(|tracker| tracker.totals())
+            .unzip();
+        WorldStats {
+            entities: self.entities.entities(),
+            archived: self.archived_count(),
+            storages: self.storages.arch_storages.storage_count(),
+            spawned: self.entities.produced(),
+            despawned: self.entities.removed(),
+            component_bytes,
+            largest_archetype: largest_archetype.flatten(),
+            shared_bytes: self.components.shared.bytes(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WorldStats, WorldStatsDelta};
+    use crate::{
+        archetype::key::PrimeArchKey,
+        prelude::*,
+        test_utils::{BumpAlloc, ChurnOp, ChurnScript},
+        world::storage::storages::ArchStorageId,
+    };
+    use std::{collections::HashMap, sync::Arc};
+
+    /// The statistics of the world, counted from scratch: by visiting the storages, and replaying every allocation.
+    struct Recount {
+        spawned: u64,
+        despawned: u64,
+        bytes: HashMap<PrimeArchKey, usize>,
+    }
+
+    impl Recount {
+        fn check(&mut self, world: &mut World, bump: &BumpAlloc) {
+            for event in world.take_allocation_events() {
+                let bytes = self.bytes.entry(event.archetype).or_default();
+                *bytes = *bytes + event.new_bytes - event.old_bytes;
+            }
+            let mut storages = 0;
+            while world
+                .storages
+                .arch_storages
+                .get_storage(ArchStorageId(storages))
+                .is_some()
+            {
+                storages += 1;
+            }
+            let stats = world.stats();
+            assert_eq!(stats.entities as usize, world.iter_entities().count());
+            assert_eq!(stats.archived, 0);
+            assert_eq!(stats.storages, storages);
+            assert_eq!(stats.spawned, self.spawned);
+            assert_eq!(stats.despawned, self.despawned);
+            assert_eq!(stats.component_bytes, Some(bump.live_bytes()));
+            let largest = self
+                .bytes
+                .values()
+                .copied()
+                .max()
+                .filter(|bytes| *bytes > 0);
+            assert_eq!(stats.largest_archetype.map(|(_, bytes)| bytes), largest);
+            if let Some((archetype, bytes)) = stats.largest_archetype {
+                assert_eq!(self.bytes[&archetype], bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_counters_agree_with_a_recount() {
+        let bump = Arc::new(BumpAlloc::new(1 << 22));
+        let mut world = World::with_allocator(bump.clone());
+        world.track_allocations(usize::MAX);
+        let mut recount = Recount {
+            spawned: 0,
+            despawned: 0,
+            bytes: HashMap::new(),
+        };
+        ChurnScript::new(7)
+            .steps(2000)
+            .initial_entities(40)
+            .archetypes(&[&[0], &[0, 1], &[1, 2], &[0, 1, 2, 3]])
+            .run_with(&mut world, |world, op| {
+                match op {
+                    ChurnOp::Spawn(_) => recount.spawned += 1,
+                    ChurnOp::Despawn(_) => recount.despawned += 1,
+                    ChurnOp::Mutate(_) => {}
+                }
+                recount.check(world, &bump);
+            });
+        world.shrink_to_fit();
+        recount.check(&mut world, &bump);
+        recount.despawned += world.clear() as u64;
+        world.shrink_to_fit();
+        recount.check(&mut world, &bump);
+        assert_eq!(world.stats().largest_archetype, None);
+    }
+
+    #[test]
+    fn test_delta() {
+        #[derive(Component)]
+        struct Unit;
+        #[derive(Component)]
+        struct Marker;
+
+        let mut world = World::default();
+        let entities: Vec<EntityId> = (0..10).map(|_| world.spawn(Unit)).collect();
+        let frame = world.stats();
+        assert_eq!(frame.component_bytes, None);
+        world.spawn((Unit, Marker));
+        for entity in &entities[..4] {
+            world.despawn(*entity);
+        }
+        let delta = world.stats().delta(&frame);
+        assert_eq!(
+            delta,
+            WorldStatsDelta {
+                entities: -3,
+                storages: 1,
+                spawned: 1,
+                despawned: 4,
+                component_bytes: None,
+            }
+        );
+        assert_eq!(
+            delta.to_string(),
+            "entities: -3, storages: +1, spawned: 1, despawned: 4"
+        );
+        assert_eq!(
+            WorldStats {
+                component_bytes: Some(640),
+                largest_archetype: Some((PrimeArchKey::IDENTITY, 512)),
+                ..frame
+            }
+            .to_string(),
+            "entities: 10 (0 archived), storages: 1, spawned: 10, despawned: 0, component bytes: 640, \
+             largest archetype: 512 bytes"
+        );
+    }
+}
@@ -1,71 +1,693 @@
 use crate::{
-    archetype::{Archetype, MAX_COMPS_PER_ARCH},
-    prelude::{Bundle, ComponentFactory, ComponentId},
-    storage::blob_vec::BlobVec,
-    utils::prime_key::PrimeArchKey,
+    archetype::key::PrimeArchKey,
+    archetype::{Archetype, ArchetypeInfo},
+    component::reject_shared_mut,
+    prelude::{Bundle, Component, ComponentFactory, ComponentId},
+    storage::{blob_vec::OnDrop, columns::Columns},
+    utils::{
+        component_mask::ComponentMask,
+        panics,
+        paranoid::{self, Violation},
+    },
+    world::{data::CloneFn, shared::SharedHandle},
 };
 use bevy_ptr::{OwningPtr, Ptr, PtrMut};
-use smallvec::SmallVec;
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, sync::Arc};
 
 /// Used to index an [`ArchStorage`]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(transparent)]
 pub struct ArchStorageIndex(pub(crate) usize);
 
+impl ArchStorageIndex {
+    /// The row that the index points to.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Health(10));
+    /// let second = world.spawn(Health(20));
+    /// let (_, index) = world.entity_location(second).unwrap();
+    /// assert_eq!(index.index(), 1);
+    /// ```
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// An error when creating an [`ArchStorage`] for an archetype.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchStorageError {
+    /// Some of the components of the archetype aren't registered.
+    UnregisteredComponents,
+    /// A component appears more than once in the archetype (see [`ArchetypeInfo::check_for_duplicates`]).
+    DuplicateComponents,
+}
+
+impl fmt::Display for ArchStorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchStorageError::UnregisteredComponents => {
+                write!(
+                    f,
+                    "some of the components of the archetype aren't registered"
+                )
+            }
+            ArchStorageError::DuplicateComponents => {
+                write!(f, "a component appears more than once in the archetype")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArchStorageError {}
+
+/// An iterator over a column of components (see [`ArchStorage::iter_column`]), which goes through the boxes of a
+/// boxed component (see [`Component::BOXED`]), and the handles of a shared component (see [`Component::SHARED`]).
+pub enum ColumnIter<'a, C> {
+    /// The components are stored in the column.
+    Inline(std::slice::Iter<'a, C>),
+    /// The column stores boxes of the components.
+    Boxed(std::slice::Iter<'a, Box<C>>),
+    /// The column stores handles of the shared values of the components.
+    #[allow(private_interfaces)]
+    Shared(std::slice::Iter<'a, SharedHandle<C>>),
+}
+
+impl<'a, C> Iterator for ColumnIter<'a, C> {
+    type Item = &'a C;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ColumnIter::Inline(iter) => iter.next(),
+            ColumnIter::Boxed(iter) => iter.next().map(|boxed| &**boxed),
+            ColumnIter::Shared(iter) => iter.next().map(|handle| handle.value()),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            ColumnIter::Inline(iter) => iter.size_hint(),
+            ColumnIter::Boxed(iter) => iter.size_hint(),
+            ColumnIter::Shared(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<C> ExactSizeIterator for ColumnIter<'_, C> {}
+
+/// Like [`ColumnIter`], mutably (see [`ArchStorage::iter_column_mut`]).
+pub enum ColumnIterMut<'a, C> {
+    /// The components are stored in the column.
+    Inline(std::slice::IterMut<'a, C>),
+    /// The column stores boxes of the components.
+    Boxed(std::slice::IterMut<'a, Box<C>>),
+}
+
+impl<'a, C> Iterator for ColumnIterMut<'a, C> {
+    type Item = &'a mut C;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ColumnIterMut::Inline(iter) => iter.next(),
+            ColumnIterMut::Boxed(iter) => iter.next().map(|boxed| &mut **boxed),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            ColumnIterMut::Inline(iter) => iter.size_hint(),
+            ColumnIterMut::Boxed(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<C> ExactSizeIterator for ColumnIterMut<'_, C> {}
+
 /// A data-structure that stores the data of an archetype (a.k.a [`Bundle`]).
+///
+/// The components may be shared with a copy of the storage (see [`World::clone_cow`](crate::world::World::clone_cow)),
+/// in which case they are copied (unshared) the first time either of the storages is mutated.
 pub struct ArchStorage {
     /// By indexing this list using [`ComponentId::id`], we get the index to the component's storage
     /// in the `comp_storage` field.
     comp_indexes: HashMap<ComponentId, usize>, // TODO: optimize later
-    /// The raw storage of the components.
-    comp_storage: SmallVec<[BlobVec; MAX_COMPS_PER_ARCH]>,
+    /// The raw storage of the components, stored inline while the storage is tiny. Only mutated through
+    /// [`Self::columns_mut`], which unshares them first.
+    comp_storage: Arc<Columns>,
+    /// The function that clones the component of each column, if it can be cloned.
+    clone_fns: Box<[Option<CloneFn>]>,
+    /// The columns in the order that the components of a row are dropped in, if it isn't the order of the columns
+    /// (see [`World::drop_order`](crate::world::World::drop_order)).
+    drop_order: Option<Arc<[usize]>>,
+    /// The amount of times the components were copied because they were shared.
+    unshares: usize,
     /// The [`PrimeArchKey`] of the archetype stored here.
     prime_key: PrimeArchKey,
+    /// The components of the archetype stored here, as a [`ComponentMask`].
+    component_mask: ComponentMask,
+    /// The zero-sized components of the archetype stored here, sorted (see [`Self::markers`]).
+    markers: Arc<[ComponentId]>,
     /// The amount of bundles stored
     len: usize,
+    /// Bumped whenever mutable access to the stored components is handed out, or bundles are stored or removed.
+    generation: u64,
+    /// Bumped whenever bundles are removed, which may move other bundles to different rows.
+    row_generation: u64,
+    /// Whether the components are frozen, so they can't be mutated (see [`Self::is_frozen`]).
+    frozen: bool,
 }
 
 impl ArchStorage {
     /// Create a new [`ArchStorage`] for an archetype
-    pub fn new<A: Archetype>(comp_factory: &ComponentFactory) -> Option<ArchStorage> {
-        let arch_info = A::arch_info(comp_factory)?;
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::{ArchStorage, ArchStorageError};
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// // Every component of the archetype must be registered.
+    /// assert_eq!(
+    ///     ArchStorage::new::<(Position, Velocity)>(&components).err(),
+    ///     Some(ArchStorageError::UnregisteredComponents)
+    /// );
+    /// components.register_component::<Velocity>();
+    /// let storage = ArchStorage::new::<(Position, Velocity)>(&components).unwrap();
+    /// assert!(storage.is_empty());
+    /// ```
+    pub fn new<A: Archetype>(
+        comp_factory: &ComponentFactory,
+    ) -> Result<ArchStorage, ArchStorageError> {
+        let arch_info =
+            A::arch_info(comp_factory).ok_or(ArchStorageError::UnregisteredComponents)?;
+        Self::from_arch_info(&arch_info, comp_factory)
+    }
+
+    /// Create a new [`ArchStorage`] for the archetype described by an [`ArchetypeInfo`].
+    pub(crate) fn from_arch_info(
+        arch_info: &ArchetypeInfo,
+        comp_factory: &ComponentFactory,
+    ) -> Result<ArchStorage, ArchStorageError> {
+        if arch_info.check_for_duplicates() {
+            return Err(ArchStorageError::DuplicateComponents);
+        }
         let components = arch_info.component_ids();
-        let mut comp_storage = SmallVec::new();
-        let mut comp_indexes = HashMap::with_capacity(MAX_COMPS_PER_ARCH);
+        let mut data_infos: Vec<&_> = Vec::with_capacity(components.len());
+        let mut comp_indexes = HashMap::with_capacity(components.len());
         for (i, comp_id) in components.iter().enumerate() {
-            // SAFETY: the safety is dependant on whether each of the archetype's components'
-            // [`DataInfo`] that is stored internally in the `ComponentFactory` matches their type.
-            comp_storage.push(unsafe { comp_factory.new_component_storage(*comp_id)? });
-            assert!(
-                comp_indexes.insert(*comp_id, i).is_none(),
-                "Cannot store archetypes with duplicate components."
+            data_infos.push(
+                comp_factory
+                    .get_component_info_from_component_id(*comp_id)
+                    .ok_or(ArchStorageError::UnregisteredComponents)?,
             );
+            comp_indexes.insert(*comp_id, i);
         }
-        Some(ArchStorage {
+        let clone_fns = data_infos.iter().map(|info| info.clone_fn()).collect();
+        let mut markers: Vec<ComponentId> = components
+            .iter()
+            .zip(&data_infos)
+            .filter(|(_, info)| info.layout().size() == 0)
+            .map(|(comp_id, _)| *comp_id)
+            .collect();
+        markers.sort_unstable();
+        // SAFETY: the safety is dependant on whether each of the archetype's components'
+        // [`DataInfo`] that is stored internally in the `ComponentFactory` matches their type.
+        let comp_storage = unsafe {
+            Columns::new(
+                data_infos.iter().copied(),
+                comp_factory
+                    .storage_alloc()
+                    .for_storage(arch_info.prime_key(), components),
+            )
+        };
+        Ok(ArchStorage {
             comp_indexes,
             prime_key: arch_info.prime_key(),
-            comp_storage,
+            component_mask: ComponentMask::from_component_ids(components.iter().copied()),
+            markers: markers.into(),
+            comp_storage: Arc::new(comp_storage),
+            clone_fns,
+            drop_order: comp_factory.drop_order.sequence(components),
+            unshares: 0,
             len: 0,
+            generation: 0,
+            row_generation: 0,
+            frozen: false,
         })
     }
 
     /// The amount of bundles stored in [`Self`]
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.store_bundle(&components, Position(1));
+    /// storage.store_bundle(&components, Position(2));
+    /// assert_eq!(storage.len(), 2);
+    /// ```
     pub fn len(&self) -> usize {
         self.len
     }
 
+    /// The generation of [`Self`]. It changes whenever the storage may have changed: when bundles are stored
+    /// or removed, and when mutable access to any of the components is handed out (even if nothing is written).
+    /// If the generation is the same as it was before, nothing in the storage changed since.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// let index = storage.store_bundle(&components, Position(1)).unwrap();
+    /// let generation = storage.generation();
+    /// // Reading doesn't change the generation, handing out mutable access does.
+    /// storage.get_component(index, position).unwrap();
+    /// assert_eq!(storage.generation(), generation);
+    /// storage.get_component_mut(index, position).unwrap();
+    /// assert_ne!(storage.generation(), generation);
+    /// ```
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The row generation of [`Self`]. It changes whenever bundles are removed, which may move other bundles to
+    /// different rows. If the row generation is the same as it was before, every bundle is still in the same row.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// let first = storage.store_bundle(&components, Position(1)).unwrap();
+    /// storage.store_bundle(&components, Position(2));
+    /// let row_generation = storage.row_generation();
+    /// // SAFETY: The index is in bounds.
+    /// unsafe { storage.swap_remove_unchecked(first) };
+    /// // `Position(2)` moved to the first row.
+    /// assert_ne!(storage.row_generation(), row_generation);
+    /// ```
+    pub fn row_generation(&self) -> u64 {
+        self.row_generation
+    }
+
+    /// Returns `true` if the components stored here are shared with a copy of the storage
+    /// (see [`World::clone_cow`](crate::world::World::clone_cow)).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// world.register_cloneable_component::<Position>();
+    /// let entity = world.spawn(Position(1));
+    /// let mut copy = world.clone_cow().unwrap();
+    /// let (sid, _) = copy.entity_location(entity).unwrap();
+    /// let shared = |world: &World| world.storages().arch_storages().get_storage(sid).unwrap().is_shared();
+    /// assert!(shared(&copy));
+    /// // Mutating the copy unshares its components.
+    /// copy.get_component_mut::<Position>(entity).unwrap().0 = 2;
+    /// assert!(!shared(&copy));
+    /// assert!(!shared(&world));
+    /// ```
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.comp_storage) > 1
+    }
+
+    /// The amount of times the components stored here were copied, because they were mutated while they
+    /// were shared (see [`Self::is_shared`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component, Clone)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// world.register_cloneable_component::<Position>();
+    /// let entity = world.spawn(Position(1));
+    /// let mut copy = world.clone_cow().unwrap();
+    /// copy.get_component_mut::<Position>(entity).unwrap().0 = 2;
+    /// copy.get_component_mut::<Position>(entity).unwrap().0 = 3;
+    /// let (sid, _) = copy.entity_location(entity).unwrap();
+    /// let storage = copy.storages().arch_storages().get_storage(sid).unwrap();
+    /// // The components were only copied the first time.
+    /// assert_eq!(storage.unshare_count(), 1);
+    /// ```
+    pub fn unshare_count(&self) -> usize {
+        self.unshares
+    }
+
+    /// Returns the [`ComponentId`] of a component stored here that can't be cloned, if there is one.
+    pub(crate) fn uncloneable_component(&self) -> Option<ComponentId> {
+        self.comp_indexes
+            .iter()
+            .find(|(_, column)| self.clone_fns[**column].is_none())
+            .map(|(comp_id, _)| *comp_id)
+    }
+
+    /// Update the clone functions of the stored components, after they were registered as cloneable.
+    pub(crate) fn refresh_clone_fns(&mut self, comp_factory: &ComponentFactory) {
+        for (comp_id, column) in &self.comp_indexes {
+            self.clone_fns[*column] = comp_factory
+                .get_component_info_from_component_id(*comp_id)
+                .and_then(|info| info.clone_fn());
+        }
+    }
+
+    /// Update the order that the components of a row are dropped in, after the drop order was declared
+    /// (see [`World::drop_order`](crate::world::World::drop_order)).
+    pub(crate) fn refresh_drop_order(&mut self, comp_factory: &ComponentFactory) {
+        self.drop_order = comp_factory
+            .drop_order
+            .sequence(&self.component_ids_by_column());
+    }
+
+    /// The [`ComponentId`]s of the components stored here, in the order that the components of a row are dropped
+    /// in: the order of the columns (ascending [`ComponentId`]s), unless a drop order was declared between some of
+    /// them (see [`World::drop_order`](crate::world::World::drop_order)).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Texture(u32);
+    ///
+    /// #[derive(Component)]
+    /// struct Device(u32);
+    ///
+    /// let mut world = World::default();
+    /// let device = world.register_component::<Device>().unwrap();
+    /// let texture = world.register_component::<Texture>().unwrap();
+    /// // The textures must be released before the device that created them.
+    /// world.drop_order::<Texture, Device>().unwrap();
+    /// let entity = world.spawn((Device(0), Texture(1)));
+    /// let (sid, _) = world.entity_location(entity).unwrap();
+    /// let storage = world.storages().arch_storages().get_storage(sid).unwrap();
+    /// assert_eq!(storage.drop_sequence(), [texture, device]);
+    /// ```
+    pub fn drop_sequence(&self) -> Vec<ComponentId> {
+        let components = self.component_ids_by_column();
+        match &self.drop_order {
+            Some(order) => order.iter().map(|column| components[*column]).collect(),
+            None => components,
+        }
+    }
+
+    /// The [`ComponentId`] of the component of each column.
+    fn component_ids_by_column(&self) -> Vec<ComponentId> {
+        let mut components = vec![ComponentId::new(0); self.comp_indexes.len()];
+        for (comp_id, column) in &self.comp_indexes {
+            components[*column] = *comp_id;
+        }
+        components
+    }
+
+    /// The [`ComponentId`] of the component of each column, with the length of the column. Every column has
+    /// [`Self::len`] components, unless the storage is corrupted (see
+    /// [`World::check_and_quarantine`](crate::world::World::check_and_quarantine)).
+    pub(crate) fn column_lens(&self) -> Vec<(ComponentId, usize)> {
+        (self.component_ids_by_column().into_iter().enumerate())
+            .map(|(column, comp_id)| (comp_id, self.comp_storage.len(column)))
+            .collect()
+    }
+
+    /// Set the length of the column of a component, without dropping anything, to corrupt the storage in tests.
+    ///
+    /// # Safety
+    /// See [`Columns::set_column_len`].
+    #[cfg(test)]
+    pub(crate) unsafe fn set_column_len(&mut self, comp_id: ComponentId, len: usize) {
+        let column = self.comp_indexes[&comp_id];
+        self.columns_mut().set_column_len(column, len);
+    }
+
+    /// Create a copy of this storage that shares its components, until either of them is mutated.
+    /// An empty storage isn't shared, the copy gets its own (empty) columns.
+    ///
+    /// # Panics
+    /// If the storage isn't empty, and a stored component can't be cloned (see [`Self::uncloneable_component`]).
+    pub(crate) fn share(&self) -> ArchStorage {
+        let comp_storage = if self.is_empty() {
+            Arc::new(self.comp_storage.empty_like())
+        } else {
+            assert!(
+                self.uncloneable_component().is_none(),
+                "Can't share a storage with components that can't be cloned"
+            );
+            Arc::clone(&self.comp_storage)
+        };
+        ArchStorage {
+            comp_indexes: self.comp_indexes.clone(),
+            comp_storage,
+            clone_fns: self.clone_fns.clone(),
+            drop_order: self.drop_order.clone(),
+            unshares: 0,
+            prime_key: self.prime_key,
+            component_mask: self.component_mask,
+            markers: Arc::clone(&self.markers),
+            len: self.len,
+            generation: self.generation,
+            row_generation: self.row_generation,
+            frozen: false,
+        }
+    }
+
+    /// Mutable access to the raw storage of the components. Every mutable access goes through here,
+    /// so if the components are shared, they are unshared first.
+    #[inline]
+    fn columns_mut(&mut self) -> &mut Columns {
+        if self.is_shared() {
+            self.unshare();
+        }
+        // SAFETY: There are no weak references to the columns, and this is the only strong reference.
+        unsafe { Arc::get_mut(&mut self.comp_storage).unwrap_unchecked() }
+    }
+
+    /// Take the components out of the storage, to drop them later (see
+    /// [`World::into_teardown`](crate::world::World::into_teardown)), alongside the order to drop the columns in
+    /// (`None` if it's the order of the columns).
+    pub(crate) fn into_columns(mut self) -> (Arc<Columns>, Option<Arc<[usize]>>) {
+        // An empty placeholder, that doesn't allocate any columns.
+        let columns =
+            std::mem::replace(&mut self.comp_storage, Arc::new(Columns::Blobs(Vec::new())));
+        (columns, self.drop_order.take())
+    }
+
+    /// If the components are shared, replace them with a deep copy. This must happen before any reference into
+    /// the components is handed out, if a mutable reference may be handed out while it's alive. Frozen components
+    /// are left as they are, since they can't be mutated anyway.
+    pub(crate) fn make_unique(&mut self) {
+        if self.is_shared() && !self.frozen {
+            self.unshare();
+        }
+    }
+
+    /// Returns `true` if the components are frozen (see
+    /// [`World::freeze_archetype`](crate::world::World::freeze_archetype)), so any mutable access to them panics.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh(u32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Mesh(1));
+    /// let (sid, _) = world.entity_location(entity).unwrap();
+    /// let frozen = world.freeze_archetype::<Mesh>();
+    /// assert!(world.storages().arch_storages().get_storage(sid).unwrap().is_frozen());
+    /// drop(frozen);
+    /// world.unfreeze_archetype::<Mesh>().unwrap();
+    /// assert!(!world.storages().arch_storages().get_storage(sid).unwrap().is_frozen());
+    /// ```
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Freeze the components, and share them (alongside the order to drop the columns in) with the caller, who
+    /// reads them while they are frozen. Frozen components are always shared, so every mutable access to them goes
+    /// through [`Self::unshare`], which panics.
+    pub(crate) fn freeze(&mut self) -> (Arc<Columns>, Option<Arc<[usize]>>) {
+        self.frozen = true;
+        (Arc::clone(&self.comp_storage), self.drop_order.clone())
+    }
+
+    /// Allow the components to be mutated again, once no one else reads them (see [`Self::freeze`]).
+    pub(crate) fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    #[cold]
+    #[track_caller]
+    fn unshare(&mut self) {
+        if self.frozen {
+            panics::fail(
+                "write",
+                "the storage is frozen, so its components can't be mutated (see World::freeze_archetype)",
+                &[],
+            );
+        }
+        let clone_fns: Vec<CloneFn> = self
+            .clone_fns
+            .iter()
+            .map(|clone_fn| clone_fn.expect("shared components can be cloned"))
+            .collect();
+        // SAFETY: The clone functions match the components of their columns.
+        self.comp_storage = Arc::new(unsafe { self.comp_storage.clone_with(&clone_fns) });
+        self.unshares += 1;
+    }
+
+    /// The [`PrimeArchKey`] of the archetype stored in [`Self`]
+    pub(crate) fn prime_key(&self) -> PrimeArchKey {
+        self.prime_key
+    }
+
+    /// The components of the archetype stored in [`Self`], as a [`ComponentMask`].
+    pub(crate) fn component_mask(&self) -> ComponentMask {
+        self.component_mask
+    }
+
+    /// The [`ComponentId`]s of the zero-sized components (markers) of the archetype stored in [`Self`], sorted.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Player;
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let player = components.register_component::<Player>().unwrap();
+    /// let storage = ArchStorage::new::<(Position, Player)>(&components).unwrap();
+    /// assert_eq!(storage.markers(), [player]);
+    /// ```
+    pub fn markers(&self) -> &[ComponentId] {
+        &self.markers
+    }
+
+    /// Iterate over the [`ComponentId`]s of the components stored in [`Self`] (in no particular order).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let velocity = components.register_component::<Velocity>().unwrap();
+    /// let storage = ArchStorage::new::<(Position, Velocity)>(&components).unwrap();
+    /// let mut ids: Vec<ComponentId> = storage.component_ids().collect();
+    /// ids.sort();
+    /// assert_eq!(ids, [position, velocity]);
+    /// ```
+    pub fn component_ids(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.comp_indexes.keys().copied()
+    }
+
     /// Return `true` if there is nothing stored here. else `false`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// assert!(storage.is_empty());
+    /// storage.store_bundle(&components, Position(1));
+    /// assert!(!storage.is_empty());
+    /// ```
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
     /// Return `true` if the storage stores a component with this [`ComponentId`]
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let velocity = components.register_component::<Velocity>().unwrap();
+    /// let storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// assert!(storage.contains(position));
+    /// assert!(!storage.contains(velocity));
+    /// ```
     pub fn contains(&self, comp_id: ComponentId) -> bool {
         self.prime_key.is_sub_archetype(comp_id.prime_key())
     }
 
     /// Return `true` if the storage stores a all the components of this [`Archetype`]
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// components.register_component::<Velocity>();
+    /// let storage = ArchStorage::new::<(Position, Velocity)>(&components).unwrap();
+    /// // A storage contains its sub-archetypes.
+    /// assert!(storage.contains_archetype::<Position>(&components));
+    /// assert!(storage.contains_archetype::<(Velocity, Position)>(&components));
+    /// ```
     pub fn contains_archetype<A: Archetype>(&self, comp_factory: &ComponentFactory) -> bool {
         A::prime_key(comp_factory)
             .map(|prime_key| self.prime_key.is_sub_archetype(prime_key))
@@ -73,6 +695,28 @@ impl ArchStorage {
     }
 
     /// Store a [`Bundle`] of components with a matching archetype in this storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// components.register_component::<Velocity>();
+    /// let mut storage = ArchStorage::new::<(Position, Velocity)>(&components).unwrap();
+    /// // The order of the components in the bundle doesn't matter.
+    /// let index = storage.store_bundle(&components, (Velocity(1), Position(0))).unwrap();
+    /// assert_eq!(index.index(), 0);
+    /// // The bundle must have exactly the archetype of the storage.
+    /// assert!(storage.store_bundle(&components, Position(0)).is_none());
+    /// assert_eq!(storage.len(), 1);
+    /// ```
     pub fn store_bundle<B: Bundle + Archetype>(
         &mut self,
         comp_factory: &ComponentFactory,
@@ -81,11 +725,30 @@ impl ArchStorage {
         B::prime_key(comp_factory)?
             .is_exact_archetype(self.prime_key)
             // SAFETY: We checked that the archetypes are matching
-            .then_some(unsafe { self.store_bundle_unchecked(comp_factory, bundle) })
+            .then(|| unsafe { self.store_bundle_unchecked(comp_factory, bundle) })
     }
 
     /// Store a [`Bundle`] of components in this storage, without checking whether the archetypes are matching.
     ///
+    /// If storing the bundle panics midway, the components of the bundle that were already stored are removed
+    /// (and dropped), so the storage stays consistent.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// // SAFETY: The storage stores exactly `Position`.
+    /// let index = unsafe { storage.store_bundle_unchecked(&components, Position(1)) };
+    /// assert_eq!(storage.len(), 1);
+    /// assert_eq!(index.index(), 0);
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that the bundle's archetypes matches the archetype that is stored in this storage.
     pub unsafe fn store_bundle_unchecked<B: Bundle>(
@@ -93,39 +756,361 @@ impl ArchStorage {
         comp_factory: &ComponentFactory,
         bundle: B,
     ) -> ArchStorageIndex {
+        self.store_bundle_with(
+            "ArchStorage::store_bundle_unchecked",
+            comp_factory,
+            bundle,
+            ArchStorage::store_component_unchecked,
+        )
+    }
+
+    /// Store each component of the bundle with `store`. If that panics, truncate all of the component
+    /// storages back to the length of the storage. `method` names the unchecked method in paranoid checks.
+    unsafe fn store_bundle_with<B: Bundle>(
+        &mut self,
+        method: &str,
+        comp_factory: &ComponentFactory,
+        bundle: B,
+        store: unsafe fn(&mut ArchStorage, ComponentId, OwningPtr<'_>),
+    ) -> ArchStorageIndex {
+        // Every access goes through this pointer, because the guard needs it while the bundle is being stored.
+        let this: *mut ArchStorage = self;
+        let guard = OnDrop::new(|| {
+            // SAFETY: This only runs on unwind, while `this` is still valid and nothing else uses it.
+            let this = unsafe { &mut *this };
+            let len = this.len;
+            this.columns_mut().truncate(len);
+        });
         bundle.raw_components_scope(comp_factory, &mut |comp_id, raw_comp| {
-            self.store_component_unchecked(comp_id, raw_comp)
+            store(&mut *this, comp_id, raw_comp)
+        });
+        std::mem::forget(guard);
+        (*this).len += 1;
+        paranoid::check(method, || {
+            let this = &*this;
+            match (0..this.comp_storage.column_count())
+                .find(|column| this.comp_storage.len(*column) != this.len)
+            {
+                Some(column) => Err(Violation::IncompleteBundle { column }),
+                None => Ok(()),
+            }
+        });
+        ArchStorageIndex((*this).len - 1)
+    }
+
+    /// Reserve room for at least `additional` more bundles in each of the component storages.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.reserve(64);
+    /// assert!(storage.has_room_for(64));
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        let len = self.len;
+        self.columns_mut().reserve(len, additional);
+    }
+
+    /// Release the memory that isn't used by the stored bundles. Components that are shared (see
+    /// [`Self::is_shared`]) are left as they are, since releasing their memory would copy them.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.reserve(64);
+    /// storage.store_bundle(&components, Position(1));
+    /// storage.shrink_to_fit();
+    /// assert!(!storage.has_room_for(64));
+    /// assert_eq!(storage.len(), 1);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        if !self.is_shared() {
+            self.columns_mut().shrink_to_fit();
+        }
+    }
+
+    /// Move the components to new allocations, so every pointer to them changes (see
+    /// [`World::enable_chaos`](crate::world::World::enable_chaos)). Components that are shared (see
+    /// [`Self::is_shared`]) are left as they are, like in [`Self::shrink_to_fit`].
+    #[cfg(feature = "chaos")]
+    pub(crate) fn relocate(&mut self) {
+        if !self.is_shared() {
+            self.columns_mut().relocate();
+        }
+    }
+
+    /// Store a batch of [`Bundle`]s in this storage, reserving room for the whole batch up front, without
+    /// checking whether the archetypes are matching. Returns the index of the first bundle in the batch.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.store_bundle(&components, Position(0));
+    /// // SAFETY: The storage stores exactly `Position`.
+    /// let first = unsafe { storage.store_bundles_unchecked(&components, (1..4).map(Position)) };
+    /// assert_eq!(first.index(), 1);
+    /// // SAFETY: `position` is the id of `Position`.
+    /// let column = unsafe { storage.get_column::<Position>(position) }.unwrap();
+    /// assert_eq!(column.iter().map(|p| p.0).collect::<Vec<_>>(), [0, 1, 2, 3]);
+    /// ```
+    ///
+    /// # Safety
+    /// The caller must ensure that the bundle's archetypes matches the archetype that is stored in this storage.
+    pub unsafe fn store_bundles_unchecked<B: Bundle>(
+        &mut self,
+        comp_factory: &ComponentFactory,
+        bundles: impl ExactSizeIterator<Item = B>,
+    ) -> ArchStorageIndex {
+        let first = ArchStorageIndex(self.len);
+        let reserved = bundles.len();
+        self.reserve(reserved);
+        #[cfg(debug_assertions)]
+        self.debug_assert_reserved(reserved);
+        for (i, bundle) in bundles.enumerate() {
+            if i < reserved {
+                self.store_bundle_prereserved_unchecked(comp_factory, bundle);
+            } else {
+                // The iterator reported the wrong length, fall back to the reserving path.
+                self.store_bundle_unchecked(comp_factory, bundle);
+            }
+        }
+        first
+    }
+
+    /// Store a [`Bundle`] of components in this storage, without checking whether the archetypes are matching,
+    /// and without reserving room for it.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.reserve(2);
+    /// for i in 0..2 {
+    ///     // SAFETY: The storage stores exactly `Position`, and there is room for both bundles.
+    ///     unsafe { storage.store_bundle_prereserved_unchecked(&components, Position(i)) };
+    /// }
+    /// assert_eq!(storage.len(), 2);
+    /// ```
+    ///
+    /// # Safety
+    /// The caller must ensure that:
+    ///     - The bundle's archetypes matches the archetype that is stored in this storage.
+    ///     - There is room for at least one more bundle in this storage (see [`Self::reserve`]).
+    pub unsafe fn store_bundle_prereserved_unchecked<B: Bundle>(
+        &mut self,
+        comp_factory: &ComponentFactory,
+        bundle: B,
+    ) -> ArchStorageIndex {
+        self.store_bundle_with(
+            "ArchStorage::store_bundle_prereserved_unchecked",
+            comp_factory,
+            bundle,
+            ArchStorage::store_component_prereserved_unchecked,
+        )
+    }
+
+    /// Store a single component in its matching column, without reserving room for it.
+    ///
+    /// ```
+    /// use bevy_ptr::OwningPtr;
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.reserve(1);
+    /// // SAFETY: `Position` is the only component of the storage, and there is room for it.
+    /// OwningPtr::make(Position(1), |ptr| unsafe {
+    ///     storage.store_component_prereserved_unchecked(position, ptr)
+    /// });
+    /// assert_eq!(unsafe { storage.get_column::<Position>(position) }.unwrap()[0].0, 1);
+    /// ```
+    ///
+    /// # Safety
+    /// The caller must ensure that:
+    ///     - The same safety requirements as [`Self::store_component_unchecked`] are met.
+    ///     - There is room for the component in its column (see [`Self::reserve`]).
+    pub unsafe fn store_component_prereserved_unchecked(
+        &mut self,
+        comp_id: ComponentId,
+        raw_comp: OwningPtr<'_>,
+    ) {
+        paranoid::check("ArchStorage::store_component_prereserved_unchecked", || {
+            self.validate_column_of(comp_id).map(drop)
         });
-        self.len += 1;
-        ArchStorageIndex(self.len - 1)
+        self.generation = self.generation.wrapping_add(1);
+        let column = *self.comp_indexes.get(&comp_id).unwrap_unchecked();
+        self.columns_mut().push_unchecked(column, raw_comp)
+    }
+
+    /// Returns `true` if `incoming` more bundles can be stored without growing any of the component storages (so
+    /// without moving the components that are already stored).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.reserve(8);
+    /// assert!(storage.has_room_for(8));
+    /// assert!(!storage.has_room_for(usize::MAX));
+    /// ```
+    pub fn has_room_for(&self, incoming: usize) -> bool {
+        self.comp_indexes
+            .values()
+            .all(|column| self.comp_storage.has_room_for(*column, incoming))
+    }
+
+    /// Assert that there is room for at least `incoming` more bundles in each of the component storages.
+    #[cfg(debug_assertions)]
+    fn debug_assert_reserved(&self, incoming: usize) {
+        for column in self.comp_indexes.values() {
+            assert!(
+                self.comp_storage.has_room_for(*column, incoming),
+                "Storage wasn't reserved for {incoming} incoming bundles"
+            );
+        }
     }
 
-    /// Store a single component in its matching [`BlobVec`].
+    /// Store a single component in its matching column.
+    ///
+    /// ```
+    /// use bevy_ptr::OwningPtr;
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// // SAFETY: `Position` is the only component of the storage, and the pointer is to a `Position`.
+    /// OwningPtr::make(Position(1), |ptr| unsafe { storage.store_component_unchecked(position, ptr) });
+    /// assert_eq!(unsafe { storage.get_column::<Position>(position) }.unwrap()[0].0, 1);
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that:
-    ///     - All the other components will also be stored in the same "go" (no [`BlobVec`]) in
+    ///     - All the other components will also be stored in the same "go" (no column in
     ///        `Self::comp_storage` will have a different length of the others.
     ///     - The raw data (`raw_comp`) matches the component's `Layout` (the same safety requirements
-    ///       that are needed when using [`BlobVec::push`])
+    ///       that are needed when using [`BlobVec::push`](crate::storage::blob_vec::BlobVec::push))
     ///     - The component is part of the archetypes (Components of this type are stored in [`Self`])
     pub unsafe fn store_component_unchecked(
         &mut self,
         comp_id: ComponentId,
         raw_comp: OwningPtr<'_>,
     ) {
-        self.comp_storage[*self.comp_indexes.get(&comp_id).unwrap_unchecked()].push(raw_comp)
+        paranoid::check("ArchStorage::store_component_unchecked", || {
+            self.validate_column_of(comp_id).map(drop)
+        });
+        self.generation = self.generation.wrapping_add(1);
+        let column = *self.comp_indexes.get(&comp_id).unwrap_unchecked();
+        self.columns_mut().push(column, raw_comp)
+    }
+
+    /// The column of the component with this [`ComponentId`], or the violation if it isn't stored in [`Self`].
+    fn validate_column_of(&self, comp_id: ComponentId) -> Result<usize, Violation> {
+        self.column_of(comp_id)
+            .ok_or(Violation::MissingComponent(comp_id))
+    }
+
+    /// The column of the component with this [`ComponentId`], or the violation if it isn't stored in [`Self`], or
+    /// if the index is out of bounds. The checked accessors validate with this before they access the component
+    /// like the unchecked ones do.
+    fn validate_component(
+        &self,
+        index: ArchStorageIndex,
+        comp_id: ComponentId,
+    ) -> Result<usize, Violation> {
+        let column = self.validate_column_of(comp_id)?;
+        paranoid::index("index", index.0, self.len)?;
+        Ok(column)
     }
 
     /// Get a type-erased reference to a pointer, from its index and [`ComponentId`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let velocity = components.register_component::<Velocity>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// let index = storage.store_bundle(&components, Position(7)).unwrap();
+    /// let ptr = storage.get_component(index, position).unwrap();
+    /// // SAFETY: The pointer is to a `Position`.
+    /// assert_eq!(unsafe { ptr.deref::<Position>() }.0, 7);
+    /// assert!(storage.get_component(index, velocity).is_none());
+    /// ```
     pub fn get_component(&self, index: ArchStorageIndex, comp_id: ComponentId) -> Option<Ptr<'_>> {
-        (index.0 < self.len).then_some(
-            // SAFETY: We ensured that `index < self.len`.
-            unsafe { self.comp_storage[*self.comp_indexes.get(&comp_id)?].get_unchecked(index.0) },
-        )
+        let column = self.validate_component(index, comp_id).ok()?;
+        // SAFETY: We validated that the column is stored here, and that `index < self.len`.
+        Some(unsafe { self.comp_storage.get_unchecked(column, index.0) })
     }
 
     /// Get a type-erased reference to a pointer, from its index and [`ComponentId`].
     ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// let index = storage.store_bundle(&components, Position(7)).unwrap();
+    /// // SAFETY: `Position` is stored here, the index is in bounds, and the pointer is to a `Position`.
+    /// let value = unsafe { storage.get_component_unchecked(index, position).deref::<Position>() };
+    /// assert_eq!(value.0, 7);
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that the component matching the given [`ComponentId`] is indeed
     /// stored in [`Self`], and that `index < self.len()`.
@@ -134,27 +1119,121 @@ impl ArchStorage {
         index: ArchStorageIndex,
         comp_id: ComponentId,
     ) -> Ptr<'_> {
-        self.comp_storage[*self.comp_indexes.get(&comp_id).unwrap_unchecked()]
-            .get_unchecked(index.0)
+        paranoid::check("ArchStorage::get_component_unchecked", || {
+            self.validate_component(index, comp_id).map(drop)
+        });
+        self.comp_storage
+            .get_unchecked(*self.comp_indexes.get(&comp_id).unwrap_unchecked(), index.0)
     }
 
     /// Get a type-erased mutable reference to a pointer, from its index and [`ComponentId`].
     /// Retuns `None` if the index is out of bounds, or if the component is not stored in this storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// let index = storage.store_bundle(&components, Position(7)).unwrap();
+    /// let ptr = storage.get_component_mut(index, position).unwrap();
+    /// // SAFETY: The pointer is to a `Position`.
+    /// unsafe { ptr.deref_mut::<Position>() }.0 = 8;
+    /// let ptr = storage.get_component(index, position).unwrap();
+    /// assert_eq!(unsafe { ptr.deref::<Position>() }.0, 8);
+    /// ```
     pub fn get_component_mut(
         &mut self,
         index: ArchStorageIndex,
         comp_id: ComponentId,
     ) -> Option<PtrMut<'_>> {
-        (index.0 < self.len).then_some(
-            // SAFETY: We ensured that `index < self.len`.
-            unsafe {
-                self.comp_storage[*self.comp_indexes.get(&comp_id)?].get_mut_unchecked(index.0)
-            },
-        )
+        self.generation = self.generation.wrapping_add(1);
+        let column = self.validate_component(index, comp_id).ok()?;
+        // SAFETY: We validated that the column is stored here, and that `index < self.len`.
+        Some(unsafe { self.columns_mut().get_mut_unchecked(column, index.0) })
+    }
+
+    /// The position of the column that stores the component with this [`ComponentId`], which can be used to access the
+    /// component without looking its column up again (see [`Self::get_component_mut_in_column_unchecked`]).
+    /// Returns `None` if the component is not stored in this storage.
+    pub(crate) fn column_of(&self, comp_id: ComponentId) -> Option<usize> {
+        self.comp_indexes.get(&comp_id).copied()
+    }
+
+    /// Get a type-erased mutable reference to a component, from its index and the position of its column
+    /// (see [`Self::column_of`]).
+    ///
+    /// # Safety
+    /// The caller must ensure that the column is one of the columns of [`Self`], and that `index < self.len()`.
+    pub(crate) unsafe fn get_component_mut_in_column_unchecked(
+        &mut self,
+        index: ArchStorageIndex,
+        column: usize,
+    ) -> PtrMut<'_> {
+        self.check_row_in_column(
+            "ArchStorage::get_component_mut_in_column_unchecked",
+            index,
+            column,
+        );
+        self.generation = self.generation.wrapping_add(1);
+        self.columns_mut().get_mut_unchecked(column, index.0)
+    }
+
+    /// Like [`Self::get_component_mut_in_column_unchecked`], but without changing the generation. The caller is
+    /// responsible for calling [`Self::bump_generation`] for the writes.
+    ///
+    /// # Safety
+    /// The same safety requirements as [`Self::get_component_mut_in_column_unchecked`].
+    pub(crate) unsafe fn get_component_mut_in_column_untracked(
+        &mut self,
+        index: ArchStorageIndex,
+        column: usize,
+    ) -> PtrMut<'_> {
+        self.check_row_in_column(
+            "ArchStorage::get_component_mut_in_column_untracked",
+            index,
+            column,
+        );
+        self.columns_mut().get_mut_unchecked(column, index.0)
+    }
+
+    /// Check that the index and the column are in bounds, for the unchecked `method` (see [`paranoid::check`]).
+    #[inline(always)]
+    #[track_caller]
+    fn check_row_in_column(&self, method: &str, index: ArchStorageIndex, column: usize) {
+        paranoid::check(method, || {
+            paranoid::index("column", column, self.comp_storage.column_count())?;
+            paranoid::index("index", index.0, self.len)
+        });
+    }
+
+    /// Change the generation, for writes that were made without changing it.
+    pub(crate) fn bump_generation(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
     }
 
     /// Get a type-erased mutable reference to a pointer, from its index and [`ComponentId`].
     ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// let index = storage.store_bundle(&components, Position(7)).unwrap();
+    /// // SAFETY: `Position` is stored here, the index is in bounds, and the pointer is to a `Position`.
+    /// unsafe { storage.get_component_mut_unchecked(index, position).deref_mut::<Position>() }.0 = 8;
+    /// assert_eq!(unsafe { storage.get_column::<Position>(position) }.unwrap()[0].0, 8);
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that the component matching the given [`ComponentId`] is indeed
     /// stored in [`Self`], and that `index < self.len()`.
@@ -163,32 +1242,407 @@ impl ArchStorage {
         index: ArchStorageIndex,
         comp_id: ComponentId,
     ) -> PtrMut<'_> {
-        self.comp_storage[*self.comp_indexes.get(&comp_id).unwrap_unchecked()]
-            .get_mut_unchecked(index.0)
+        paranoid::check("ArchStorage::get_component_mut_unchecked", || {
+            self.validate_component(index, comp_id).map(drop)
+        });
+        self.generation = self.generation.wrapping_add(1);
+        let column = *self.comp_indexes.get(&comp_id).unwrap_unchecked();
+        self.columns_mut().get_mut_unchecked(column, index.0)
+    }
+
+    /// Get all of the components with this [`ComponentId`] stored here, as a typed slice indexed by [`ArchStorageIndex`].
+    /// Returns `None` if the component is not stored in this storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.store_bundle(&components, Position(1));
+    /// storage.store_bundle(&components, Position(2));
+    /// // SAFETY: `position` is the id of `Position`.
+    /// let column = unsafe { storage.get_column::<Position>(position) }.unwrap();
+    /// assert_eq!(column.iter().map(|p| p.0).sum::<i32>(), 3);
+    /// ```
+    ///
+    /// # Panics
+    /// If `C` is boxed (see [`Component::BOXED`]) or shared (see [`Component::SHARED`]), because its values aren't
+    /// stored contiguously. Use [`Self::iter_column`] instead.
+    ///
+    /// # Safety
+    /// The caller must ensure that `C` is the component that is represented by the [`ComponentId`].
+    #[track_caller]
+    pub unsafe fn get_column<C: Component>(&self, comp_id: ComponentId) -> Option<&[C]> {
+        if C::BOXED || C::SHARED {
+            fail_indirect_column::<C>("get_column");
+        }
+        self.comp_indexes
+            .get(&comp_id)
+            .map(|i| self.comp_storage.as_slice::<C>(*i))
+    }
+
+    /// Get all of the components with this [`ComponentId`] stored here, as a typed mutable slice indexed by [`ArchStorageIndex`].
+    /// Returns `None` if the component is not stored in this storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.store_bundle(&components, Position(1));
+    /// storage.store_bundle(&components, Position(2));
+    /// // SAFETY: `position` is the id of `Position`.
+    /// for p in unsafe { storage.get_column_mut::<Position>(position) }.unwrap() {
+    ///     p.0 *= 10;
+    /// }
+    /// assert_eq!(unsafe { storage.get_column::<Position>(position) }.unwrap()[1].0, 20);
+    /// ```
+    ///
+    /// # Panics
+    /// If `C` is boxed (see [`Component::BOXED`]) or shared (see [`Component::SHARED`]), because its values aren't
+    /// stored contiguously. Use [`Self::iter_column_mut`] instead.
+    ///
+    /// # Safety
+    /// The caller must ensure that `C` is the component that is represented by the [`ComponentId`].
+    #[track_caller]
+    pub unsafe fn get_column_mut<C: Component>(
+        &mut self,
+        comp_id: ComponentId,
+    ) -> Option<&mut [C]> {
+        if C::BOXED || C::SHARED {
+            fail_indirect_column::<C>("get_column_mut");
+        }
+        self.generation = self.generation.wrapping_add(1);
+        let column = *self.comp_indexes.get(&comp_id)?;
+        Some(self.columns_mut().as_mut_slice::<C>(column))
+    }
+
+    /// Iterate over all of the components with this [`ComponentId`] stored here, in the order of their
+    /// [`ArchStorageIndex`]. Unlike [`Self::get_column`], this works for boxed and shared components too (see
+    /// [`Component::BOXED`] and [`Component::SHARED`]). Returns `None` if the component is not stored in this
+    /// storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// #[component(boxed)]
+    /// struct Inventory(Vec<u32>);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let inventory = components.register_component::<Inventory>().unwrap();
+    /// let mut storage = ArchStorage::new::<Inventory>(&components).unwrap();
+    /// storage.store_bundle(&components, Inventory(vec![1, 2]));
+    /// // SAFETY: `inventory` is the id of `Inventory`.
+    /// let column = unsafe { storage.iter_column::<Inventory>(inventory) }.unwrap();
+    /// assert_eq!(column.map(|inventory| inventory.0.len()).sum::<usize>(), 2);
+    /// ```
+    ///
+    /// # Safety
+    /// The caller must ensure that `C` is the component that is represented by the [`ComponentId`].
+    pub unsafe fn iter_column<C: Component>(
+        &self,
+        comp_id: ComponentId,
+    ) -> Option<ColumnIter<'_, C>> {
+        let column = *self.comp_indexes.get(&comp_id)?;
+        let columns = &self.comp_storage;
+        Some(match (C::SHARED, C::BOXED) {
+            (true, _) => ColumnIter::Shared(columns.as_slice::<SharedHandle<C>>(column).iter()),
+            (false, true) => ColumnIter::Boxed(columns.as_slice::<Box<C>>(column).iter()),
+            (false, false) => ColumnIter::Inline(columns.as_slice::<C>(column).iter()),
+        })
+    }
+
+    /// Like [`Self::iter_column`], mutably. Like [`Self::get_column_mut`], this bumps the
+    /// [`generation`](Self::generation) of the storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// #[component(boxed)]
+    /// struct Inventory(Vec<u32>);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let inventory = components.register_component::<Inventory>().unwrap();
+    /// let mut storage = ArchStorage::new::<Inventory>(&components).unwrap();
+    /// storage.store_bundle(&components, Inventory(vec![1, 2]));
+    /// // SAFETY: `inventory` is the id of `Inventory`.
+    /// for items in unsafe { storage.iter_column_mut::<Inventory>(inventory) }.unwrap() {
+    ///     items.0.push(3);
+    /// }
+    /// let mut column = unsafe { storage.iter_column::<Inventory>(inventory) }.unwrap();
+    /// assert_eq!(column.next().unwrap().0, [1, 2, 3]);
+    /// ```
+    ///
+    /// # Panics
+    /// If `C` is shared (see [`Component::SHARED`]), because its values are immutable.
+    ///
+    /// # Safety
+    /// The caller must ensure that `C` is the component that is represented by the [`ComponentId`].
+    #[track_caller]
+    pub unsafe fn iter_column_mut<C: Component>(
+        &mut self,
+        comp_id: ComponentId,
+    ) -> Option<ColumnIterMut<'_, C>> {
+        reject_shared_mut::<C>("iter_column_mut");
+        self.generation = self.generation.wrapping_add(1);
+        let column = *self.comp_indexes.get(&comp_id)?;
+        let columns = self.columns_mut();
+        Some(match C::BOXED {
+            true => ColumnIterMut::Boxed(columns.as_mut_slice::<Box<C>>(column).iter_mut()),
+            false => ColumnIterMut::Inline(columns.as_mut_slice::<C>(column).iter_mut()),
+        })
     }
 
     /// Iterate over all of the indicies in this storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.store_bundle(&components, Position(1));
+    /// storage.store_bundle(&components, Position(2));
+    /// let rows: Vec<usize> = storage.iter_indices().map(|index| index.index()).collect();
+    /// assert_eq!(rows, [0, 1]);
+    /// ```
     pub fn iter_indices(&self) -> impl Iterator<Item = ArchStorageIndex> {
-        (0..self.len()).map(|i| ArchStorageIndex(i))
+        (0..self.len()).map(ArchStorageIndex)
+    }
+
+    /// Remove (and drop) all of the components stored in [`Self`]. If they are shared, nothing is copied: the
+    /// storage lets go of them instead.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// storage.store_bundle(&components, Position(1));
+    /// storage.clear();
+    /// assert!(storage.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.row_generation = self.row_generation.wrapping_add(1);
+        if self.is_shared() {
+            self.comp_storage = Arc::new(self.comp_storage.empty_like());
+        } else if let Some(order) = self.drop_order.clone() {
+            self.columns_mut().truncate_in_order(0, &order);
+        } else {
+            self.columns_mut().truncate(0);
+        }
+        self.len = 0;
+    }
+
+    /// Move the components at `from` to `to`, shifting the components in between by one row, so the order of the
+    /// other rows is kept.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// let rows: Vec<_> = (0..3).map(|i| storage.store_bundle(&components, Position(i)).unwrap()).collect();
+    /// // SAFETY: Both indices are in bounds.
+    /// unsafe { storage.move_row_unchecked(rows[2], rows[0]) };
+    /// let column = unsafe { storage.get_column::<Position>(position) }.unwrap();
+    /// assert_eq!(column.iter().map(|p| p.0).collect::<Vec<_>>(), [2, 0, 1]);
+    /// ```
+    ///
+    /// # Safety
+    /// It is the caller responsibility to ensure that both indices are in bounds.
+    pub unsafe fn move_row_unchecked(&mut self, from: ArchStorageIndex, to: ArchStorageIndex) {
+        paranoid::check("ArchStorage::move_row_unchecked", || {
+            paranoid::index("from", from.0, self.len)?;
+            paranoid::index("to", to.0, self.len)
+        });
+        self.generation = self.generation.wrapping_add(1);
+        self.row_generation = self.row_generation.wrapping_add(1);
+        self.columns_mut().move_row_unchecked(from.0, to.0);
+    }
+
+    /// Permute the rows, so the components at row `i` are the components that were at row `order[i]`.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// (0..3).for_each(|i| drop(storage.store_bundle(&components, Position(i))));
+    /// // SAFETY: The order is a permutation of the rows.
+    /// unsafe { storage.permute_rows_unchecked(&[2, 0, 1]) };
+    /// let column = unsafe { storage.get_column::<Position>(position) }.unwrap();
+    /// assert_eq!(column.iter().map(|p| p.0).collect::<Vec<_>>(), [2, 0, 1]);
+    /// ```
+    ///
+    /// # Safety
+    /// It is the caller responsibility to ensure that `order` is a permutation of `0..self.len()`.
+    pub unsafe fn permute_rows_unchecked(&mut self, order: &[usize]) {
+        paranoid::check("ArchStorage::permute_rows_unchecked", || {
+            paranoid::permutation(order, self.len)
+        });
+        self.generation = self.generation.wrapping_add(1);
+        self.row_generation = self.row_generation.wrapping_add(1);
+        self.columns_mut().permute_rows_unchecked(order);
     }
 
     /// Performs a swap-remove, pop the last components in the storages and place them in the given index.
-    /// components corresponding to the given index are removed.
+    /// components corresponding to the given index are removed, in the order of [`Self::drop_sequence`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// let first = storage.store_bundle(&components, Position(0)).unwrap();
+    /// storage.store_bundle(&components, Position(1));
+    /// storage.store_bundle(&components, Position(2));
+    /// // SAFETY: The index is in bounds.
+    /// unsafe { storage.swap_remove_unchecked(first) };
+    /// // The last row took the place of the removed one.
+    /// let column = unsafe { storage.get_column::<Position>(position) }.unwrap();
+    /// assert_eq!(column.iter().map(|p| p.0).collect::<Vec<_>>(), [2, 1]);
+    /// ```
+    ///
     /// # Safety
     /// It is the caller responsibility to ensure that the index is in bounds.
     pub unsafe fn swap_remove_unchecked(&mut self, index: ArchStorageIndex) {
-        self.comp_storage
-            .iter_mut()
-            .for_each(|bvec| bvec.swap_remove_and_drop_unchecked(index.0));
+        paranoid::check("ArchStorage::swap_remove_unchecked", || {
+            paranoid::index("index", index.0, self.len)
+        });
+        self.generation = self.generation.wrapping_add(1);
+        self.row_generation = self.row_generation.wrapping_add(1);
+        match self.drop_order.clone() {
+            Some(order) => self
+                .columns_mut()
+                .swap_remove_and_drop_in_order_unchecked(index.0, &order),
+            None => self.columns_mut().swap_remove_and_drop_unchecked(index.0),
+        }
         self.len -= 1;
     }
+
+    /// Performs a swap-remove like [`Self::swap_remove_unchecked`], but the components at the given index are
+    /// moved out to `f` (with their [`ComponentId`]) instead of being dropped.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::arch_storage::ArchStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchStorage::new::<Position>(&components).unwrap();
+    /// let index = storage.store_bundle(&components, Position(7)).unwrap();
+    /// let mut removed = Vec::new();
+    /// // SAFETY: The index is in bounds, and the closure takes ownership of the components.
+    /// unsafe {
+    ///     storage.swap_remove_and_forget_unchecked(index, |comp_id, ptr| {
+    ///         assert_eq!(comp_id, position);
+    ///         removed.push(ptr.read::<Position>());
+    ///     })
+    /// };
+    /// assert_eq!(removed[0].0, 7);
+    /// assert!(storage.is_empty());
+    /// ```
+    ///
+    /// # Safety
+    /// It is the caller responsibility to ensure that the index is in bounds, and that `f` takes ownership of
+    /// (or drops) every component that it's handed.
+    pub unsafe fn swap_remove_and_forget_unchecked(
+        &mut self,
+        index: ArchStorageIndex,
+        mut f: impl FnMut(ComponentId, OwningPtr<'_>),
+    ) {
+        paranoid::check("ArchStorage::swap_remove_and_forget_unchecked", || {
+            paranoid::index("index", index.0, self.len)
+        });
+        self.generation = self.generation.wrapping_add(1);
+        self.row_generation = self.row_generation.wrapping_add(1);
+        let columns: Vec<(ComponentId, usize)> = self
+            .comp_indexes
+            .iter()
+            .map(|(comp_id, column)| (*comp_id, *column))
+            .collect();
+        for (comp_id, column) in columns {
+            f(
+                comp_id,
+                self.columns_mut()
+                    .swap_remove_and_forget_unchecked(column, index.0),
+            );
+        }
+        self.len -= 1;
+    }
+}
+
+/// Panic because a column of the boxed or shared component `C` was requested as a slice.
+#[cold]
+#[inline(never)]
+#[track_caller]
+pub(crate) fn fail_indirect_column<C: Component>(operation: &str) -> ! {
+    let reason = match C::SHARED {
+        true => "the component is shared, so its values aren't stored contiguously",
+        false => "the component is boxed, so its values aren't stored contiguously",
+    };
+    panics::fail_component(operation, reason, std::any::type_name::<C>())
+}
+
+impl Drop for ArchStorage {
+    fn drop(&mut self) {
+        // The columns drop their components in the order of the columns, so a declared drop order is applied first.
+        if let (Some(order), Some(columns)) =
+            (&self.drop_order, Arc::get_mut(&mut self.comp_storage))
+        {
+            columns.truncate_in_order(0, order);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::ArchStorage;
     use super::ArchStorageIndex;
-    use crate::prelude::*;
+    use crate::{prelude::*, storage::columns::INLINE_ROWS};
 
     #[derive(Component)]
     struct A(usize);
@@ -197,6 +1651,20 @@ mod tests {
     #[derive(Component)]
     struct C([u8; 3]);
 
+    #[test]
+    fn test_store_bundle_with_other_archetype() {
+        let mut comp_factory = ComponentFactory::default();
+        comp_factory.register_component::<A>();
+        comp_factory.register_component::<B>();
+        let mut ab_storage = ArchStorage::new::<(A, B)>(&comp_factory).unwrap();
+        assert!(ab_storage.store_bundle(&comp_factory, A(0)).is_none());
+        // Nothing is stored, not even the components that the storage has.
+        assert!(ab_storage.is_empty());
+        let a = comp_factory.get_component_id::<A>().unwrap();
+        // SAFETY: `a` is the id of `A`.
+        assert!(unsafe { ab_storage.get_column::<A>(a) }.unwrap().is_empty());
+    }
+
     #[test]
     fn test_component_storage() {
         let mut comp_factory = ComponentFactory::default();
@@ -349,4 +1817,94 @@ mod tests {
 
         //
     }
+
+    #[test]
+    fn test_store_bundles() {
+        let mut comp_factory = ComponentFactory::default();
+        let a_id = comp_factory.register_component::<A>().unwrap();
+        let b_id = comp_factory.register_component::<B>().unwrap();
+        comp_factory.register_component::<C>();
+
+        let mut ab_storage = ArchStorage::new::<(A, B)>(&comp_factory).unwrap();
+        ab_storage.store_bundle(&comp_factory, (A(0), B([0; 2])));
+
+        // SAFETY: The bundles match the archetype of the storage.
+        let first = unsafe {
+            ab_storage.store_bundles_unchecked(&comp_factory, (1..100).map(|i| (A(i), B([i; 2]))))
+        };
+        assert_eq!(first.0, 1);
+        assert_eq!(ab_storage.len(), 100);
+
+        /// An iterator that reports a length that is shorter than the actual length.
+        struct ShortLen(std::ops::Range<usize>);
+        impl Iterator for ShortLen {
+            type Item = (A, B);
+            fn next(&mut self) -> Option<Self::Item> {
+                self.0.next().map(|i| (A(i), B([i; 2])))
+            }
+        }
+        impl ExactSizeIterator for ShortLen {
+            fn len(&self) -> usize {
+                1
+            }
+        }
+
+        // SAFETY: The bundles match the archetype of the storage.
+        let first =
+            unsafe { ab_storage.store_bundles_unchecked(&comp_factory, ShortLen(100..200)) };
+        assert_eq!(first.0, 100);
+        assert_eq!(ab_storage.len(), 200);
+
+        unsafe {
+            let a_column = ab_storage.get_column::<A>(a_id).unwrap();
+            let b_column = ab_storage.get_column::<B>(b_id).unwrap();
+            for i in 0..200 {
+                assert_eq!(a_column[i].0, i);
+                assert_eq!(b_column[i].0, [i; 2]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tiny_storage_boundary() {
+        let mut comp_factory = ComponentFactory::default();
+        let a_id = comp_factory.register_component::<A>().unwrap();
+        let c_id = comp_factory.register_component::<C>().unwrap();
+
+        let mut ac_storage = ArchStorage::new::<(A, C)>(&comp_factory).unwrap();
+        for i in 0..INLINE_ROWS {
+            ac_storage.store_bundle(&comp_factory, (A(i), C([i as u8; 3])));
+        }
+        assert!(ac_storage.comp_storage.is_inline());
+        // SAFETY: The index is in bounds.
+        unsafe { ac_storage.swap_remove_unchecked(ArchStorageIndex(2)) };
+        ac_storage.store_bundle(&comp_factory, (A(8), C([8; 3])));
+        assert!(ac_storage.comp_storage.is_inline());
+        ac_storage.store_bundle(&comp_factory, (A(9), C([9; 3])));
+        assert!(!ac_storage.comp_storage.is_inline());
+        assert_eq!(ac_storage.len(), INLINE_ROWS + 1);
+
+        let expected = [0, 1, 7, 3, 4, 5, 6, 8, 9];
+        unsafe {
+            let a_column = ac_storage.get_column::<A>(a_id).unwrap();
+            let c_column = ac_storage.get_column::<C>(c_id).unwrap();
+            for (index, i) in expected.iter().enumerate() {
+                assert_eq!(a_column[index].0, *i);
+                assert_eq!(c_column[index].0, [*i as u8; 3]);
+                assert_eq!(
+                    ac_storage
+                        .get_component(ArchStorageIndex(index), a_id)
+                        .unwrap()
+                        .deref::<A>()
+                        .0,
+                    *i
+                );
+            }
+        }
+        ac_storage.clear();
+        assert!(ac_storage.is_empty());
+        assert!(unsafe { ac_storage.get_column::<A>(a_id) }
+            .unwrap()
+            .is_empty());
+    }
 }
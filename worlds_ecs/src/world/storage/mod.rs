@@ -1,11 +1,20 @@
-use self::arch_storage::{ArchStorage, ArchStorageIndex};
+use self::arch_storage::{ArchStorage, ArchStorageError, ArchStorageIndex, ColumnIterMut};
 use crate::{
-    archetype::Archetype,
+    archetype::{Archetype, ArchetypeInfo},
     entity::EntityId,
-    prelude::{Bundle, ComponentFactory, ComponentId},
+    prelude::{Bundle, Component, ComponentFactory, ComponentId},
+    storage::{blob_vec::OnDrop, columns::Columns},
+    utils::paranoid,
+    world::{freeze::FrozenColumns, sort::SortOrder},
+};
+use bevy_ptr::{OwningPtr, PtrMut};
+use std::{
+    ops::Deref,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
-use bevy_ptr::PtrMut;
-use std::ops::Deref;
 
 /// Defining a data-structures to store a bundle of components, a.k.a archetype storage.
 pub mod arch_storage;
@@ -21,6 +30,14 @@ pub struct ArchEntityStorage {
     arch_storage: ArchStorage,
     /// The Id of each entity in the storage. Indexed by the entity's index in the [`ArchStorage`] ([`ArchStorageIndex`])
     entities: Vec<EntityId>,
+    /// The order that the entities are kept in, if the storage is sort-maintained (see
+    /// [`World::maintain_sort`](crate::world::World::maintain_sort)).
+    pub(crate) sort: Option<SortOrder>,
+    /// How many [`StoragePin`](crate::world::pin::StoragePin)s of this storage are alive.
+    pub(crate) pins: Arc<AtomicUsize>,
+    /// The handle that the storage keeps of its own columns while it's frozen (see
+    /// [`World::freeze_archetype`](crate::world::World::freeze_archetype)).
+    pub(crate) frozen: Option<FrozenColumns>,
 }
 
 impl Deref for ArchEntityStorage {
@@ -33,31 +50,241 @@ impl Deref for ArchEntityStorage {
 
 impl ArchEntityStorage {
     /// Create a new [`ArchEntityStorage`] for the given [`Archetype`].
-    pub fn new<A: Archetype>(compf: &ComponentFactory) -> Option<Self> {
-        Some(Self {
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// assert!(ArchEntityStorage::new::<Position>(&components).is_err());
+    /// components.register_component::<Position>();
+    /// let storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// assert!(storage.entities().is_empty());
+    /// ```
+    pub fn new<A: Archetype>(compf: &ComponentFactory) -> Result<Self, ArchStorageError> {
+        Ok(Self {
             arch_storage: ArchStorage::new::<A>(compf)?,
             entities: Vec::new(),
+            sort: None,
+            pins: Arc::default(),
+            frozen: None,
         })
     }
 
+    /// Create a new [`ArchEntityStorage`] for the archetype described by an [`ArchetypeInfo`].
+    pub(crate) fn from_arch_info(
+        arch_info: &ArchetypeInfo,
+        compf: &ComponentFactory,
+    ) -> Result<Self, ArchStorageError> {
+        Ok(Self {
+            arch_storage: ArchStorage::from_arch_info(arch_info, compf)?,
+            entities: Vec::new(),
+            sort: None,
+            pins: Arc::default(),
+            frozen: None,
+        })
+    }
+
+    /// Take the components out of the storage, to drop them later (see
+    /// [`World::into_teardown`](crate::world::World::into_teardown)), alongside the order to drop the columns in.
+    pub(crate) fn into_columns(self) -> (Arc<Columns>, Option<Arc<[usize]>>) {
+        self.arch_storage.into_columns()
+    }
+
+    /// Reserve room for at least `additional` more entities.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// storage.reserve(16);
+    /// assert!(storage.has_room_for(16));
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.arch_storage.reserve(additional);
+        self.entities.reserve(additional);
+    }
+
+    /// Release the memory that isn't used by the stored entities (see [`ArchStorage::shrink_to_fit`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// storage.reserve(16);
+    /// storage.shrink_to_fit();
+    /// assert!(!storage.has_room_for(16));
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.arch_storage.shrink_to_fit();
+        self.entities.shrink_to_fit();
+    }
+
+    /// Move the components and the entities to new allocations (see [`ArchStorage::relocate`]).
+    #[cfg(feature = "chaos")]
+    pub(crate) fn relocate(&mut self) {
+        self.arch_storage.relocate();
+        let mut entities = Vec::with_capacity(self.entities.capacity());
+        entities.extend_from_slice(&self.entities);
+        self.entities = entities;
+    }
+
     /// Get the next index. As in, if a new entity were to be stored right now, that index it would get.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let next = storage.next_index();
+    /// let index = storage.store_entity(EntityId::from_bits(0), Position(1), &components);
+    /// assert_eq!(index, Some(next));
+    /// ```
     pub fn next_index(&self) -> ArchStorageIndex {
         ArchStorageIndex(self.len())
     }
 
     /// Store an entity in the storage, with a [`Bundle`] of components, and return its index.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// components.register_component::<Velocity>();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let entity = EntityId::from_bits(7);
+    /// let index = storage.store_entity(entity, Position(1), &components).unwrap();
+    /// assert_eq!(storage.get_entity_at(index), Some(entity));
+    /// // The bundle must have exactly the archetype of the storage.
+    /// assert!(storage.store_entity(entity, (Position(1), Velocity(2)), &components).is_none());
+    /// ```
     pub fn store_entity<B: Bundle + Archetype>(
         &mut self,
         entity_id: EntityId,
         bundle: B,
         compf: &ComponentFactory,
     ) -> Option<ArchStorageIndex> {
+        // The bundle is stored first, so the entity isn't pushed if storing it panics.
+        let index = self.arch_storage.store_bundle(compf, bundle)?;
         self.entities.push(entity_id);
-        self.arch_storage.store_bundle(compf, bundle)
+        Some(index)
+    }
+
+    /// Store an entity in the storage, with a [`Bundle`] of components, and return its index. Unlike
+    /// [`Self::store_entity`], the bundle doesn't need to be an [`Archetype`].
+    ///
+    /// # Safety
+    /// The caller must ensure that the bundle stores exactly the components of the archetype that is stored
+    /// in this storage.
+    pub(crate) unsafe fn store_entity_unchecked<B: Bundle>(
+        &mut self,
+        entity_id: EntityId,
+        bundle: B,
+        compf: &ComponentFactory,
+    ) -> ArchStorageIndex {
+        let index = self.arch_storage.store_bundle_unchecked(compf, bundle);
+        self.entities.push(entity_id);
+        index
+    }
+
+    /// Store a batch of entities in the storage, each with a [`Bundle`] of components, reserving room for the
+    /// whole batch up front. Returns the index of the first entity in the batch.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let batch = (0..3u32).map(|i| (EntityId::from_bits(i.into()), Position(i as i32)));
+    /// let first = storage.store_entities(batch, &components).unwrap();
+    /// assert_eq!(first.index(), 0);
+    /// assert_eq!(storage.len(), 3);
+    /// ```
+    pub fn store_entities<B: Bundle + Archetype>(
+        &mut self,
+        entities: impl ExactSizeIterator<Item = (EntityId, B)>,
+        compf: &ComponentFactory,
+    ) -> Option<ArchStorageIndex> {
+        if !B::prime_key(compf)?.is_exact_archetype(self.arch_storage.prime_key()) {
+            return None;
+        }
+        self.entities.reserve(entities.len());
+        // Every access goes through this pointer, because the guard needs it while the batch is being stored.
+        let this: *mut ArchEntityStorage = self;
+        // If storing a bundle panics, its entity was already pushed, so it's removed (together with any entity
+        // whose bundle wasn't stored).
+        let guard = OnDrop::new(|| {
+            // SAFETY: This only runs on unwind, while `this` is still valid and nothing else uses it.
+            let this = unsafe { &mut *this };
+            this.entities.truncate(this.arch_storage.len());
+        });
+        // SAFETY: We checked that the archetypes are matching, and the closure only accesses `entities`,
+        // which is disjoint from `arch_storage`.
+        let index = unsafe {
+            (*this).arch_storage.store_bundles_unchecked(
+                compf,
+                entities.map(|(entity_id, bundle)| {
+                    (*this).entities.push(entity_id);
+                    bundle
+                }),
+            )
+        };
+        std::mem::forget(guard);
+        Some(index)
     }
 
     /// Get a type-erased mutable reference to a pointer, from its index and [`ComponentId`].
     /// Retuns `None` if the index is out of bounds, or if the component is not stored in this storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let index = storage.store_entity(EntityId::from_bits(0), Position(1), &components).unwrap();
+    /// let ptr = storage.get_component_mut(index, position).unwrap();
+    /// // SAFETY: The pointer is to a `Position`.
+    /// unsafe { ptr.deref_mut::<Position>() }.0 = 2;
+    /// assert_eq!(unsafe { storage.get_component(index, position).unwrap().deref::<Position>() }.0, 2);
+    /// ```
     pub fn get_component_mut(
         &mut self,
         index: ArchStorageIndex,
@@ -68,6 +295,22 @@ impl ArchEntityStorage {
 
     /// Get a type-erased mutable reference to a pointer, from its index and [`ComponentId`].
     ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let index = storage.store_entity(EntityId::from_bits(0), Position(1), &components).unwrap();
+    /// // SAFETY: `Position` is stored here, the index is in bounds, and the pointer is to a `Position`.
+    /// unsafe { storage.get_component_mut_unchecked(index, position).deref_mut::<Position>() }.0 = 2;
+    /// assert_eq!(unsafe { storage.get_column::<Position>(position) }.unwrap()[0].0, 2);
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that the component matching the given [`ComponentId`] is indeed
     /// stored in [`Self`], and that `index < self.len()`.
@@ -80,16 +323,188 @@ impl ArchEntityStorage {
             .get_component_mut_unchecked(index, comp_id)
     }
 
+    /// Get a type-erased mutable reference to a component, from its index and the position of its column.
+    ///
+    /// # Safety
+    /// The same safety requirements as [`ArchStorage::get_component_mut_in_column_unchecked`].
+    pub(crate) unsafe fn get_component_mut_in_column_unchecked(
+        &mut self,
+        index: ArchStorageIndex,
+        column: usize,
+    ) -> PtrMut<'_> {
+        self.arch_storage
+            .get_component_mut_in_column_unchecked(index, column)
+    }
+
+    /// Get a type-erased mutable reference to a component, from its index and the position of its column, without
+    /// changing the generation of the storage.
+    ///
+    /// # Safety
+    /// The same safety requirements as [`ArchStorage::get_component_mut_in_column_untracked`].
+    pub(crate) unsafe fn get_component_mut_in_column_untracked(
+        &mut self,
+        index: ArchStorageIndex,
+        column: usize,
+    ) -> PtrMut<'_> {
+        self.arch_storage
+            .get_component_mut_in_column_untracked(index, column)
+    }
+
+    /// Change the generation of the storage, for writes that were made without changing it.
+    pub(crate) fn bump_generation(&mut self) {
+        self.arch_storage.bump_generation();
+    }
+
+    /// Get the [`EntityId`]s of all the entities stored here, indexed by [`ArchStorageIndex`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let a = world.spawn(Position(1));
+    /// let b = world.spawn(Position(2));
+    /// let (sid, _) = world.entity_location(a).unwrap();
+    /// let storage = world.storages().arch_storages().get_storage(sid).unwrap();
+    /// assert_eq!(storage.entities(), [a, b]);
+    /// ```
+    pub fn entities(&self) -> &[EntityId] {
+        &self.entities
+    }
+
+    /// Mutable access to the [`EntityId`]s of the entities stored here, to corrupt the storage in tests.
+    #[cfg(test)]
+    pub(crate) fn entities_mut(&mut self) -> &mut Vec<EntityId> {
+        &mut self.entities
+    }
+
+    /// Set the length of the column of a component, without dropping anything, to corrupt the storage in tests.
+    ///
+    /// # Safety
+    /// See [`ArchStorage::set_column_len`].
+    #[cfg(test)]
+    pub(crate) unsafe fn set_column_len(&mut self, comp_id: ComponentId, len: usize) {
+        self.arch_storage.set_column_len(comp_id, len);
+    }
+
+    /// Get the [`EntityId`]s of all the entities stored here, alongside a typed mutable slice of
+    /// the components with this [`ComponentId`]. Both slices are indexed by [`ArchStorageIndex`].
+    /// Returns `None` if the component is not stored in this storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let batch = (0..3u32).map(|i| (EntityId::from_bits(i.into()), Position(0)));
+    /// storage.store_entities(batch, &components);
+    /// // SAFETY: `position` is the id of `Position`.
+    /// let (entities, column) = unsafe { storage.entities_and_column_mut::<Position>(position) }.unwrap();
+    /// for (entity, position) in entities.iter().zip(column) {
+    ///     position.0 = entity.to_bits() as i32 * 10;
+    /// }
+    /// assert_eq!(unsafe { storage.get_column::<Position>(position) }.unwrap()[2].0, 20);
+    /// ```
+    ///
+    /// # Panics
+    /// If `C` is boxed (see [`Component::BOXED`]), see [`ArchStorage::get_column_mut`].
+    ///
+    /// # Safety
+    /// The caller must ensure that `C` is the component that is represented by the [`ComponentId`].
+    #[track_caller]
+    pub unsafe fn entities_and_column_mut<C: Component>(
+        &mut self,
+        comp_id: ComponentId,
+    ) -> Option<(&[EntityId], &mut [C])> {
+        let column = self.arch_storage.get_column_mut::<C>(comp_id)?;
+        Some((&self.entities, column))
+    }
+
+    /// Like [`Self::entities_and_column_mut`], with an iterator over the components instead of a slice, so it works
+    /// for boxed components too (see [`Component::BOXED`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// #[component(boxed)]
+    /// struct Inventory(Vec<u64>);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let inventory = components.register_component::<Inventory>().unwrap();
+    /// let mut storage = ArchEntityStorage::new::<Inventory>(&components).unwrap();
+    /// let batch = (0..2u32).map(|i| (EntityId::from_bits(i.into()), Inventory(Vec::new())));
+    /// storage.store_entities(batch, &components);
+    /// // SAFETY: `inventory` is the id of `Inventory`.
+    /// let (entities, column) =
+    ///     unsafe { storage.entities_and_iter_column_mut::<Inventory>(inventory) }.unwrap();
+    /// for (entity, inventory) in entities.iter().zip(column) {
+    ///     inventory.0.push(entity.to_bits());
+    /// }
+    /// let mut column = unsafe { storage.iter_column::<Inventory>(inventory) }.unwrap();
+    /// assert_eq!(column.nth(1).unwrap().0, [1]);
+    /// ```
+    ///
+    /// # Safety
+    /// The caller must ensure that `C` is the component that is represented by the [`ComponentId`].
+    pub unsafe fn entities_and_iter_column_mut<C: Component>(
+        &mut self,
+        comp_id: ComponentId,
+    ) -> Option<(&[EntityId], ColumnIterMut<'_, C>)> {
+        let column = self.arch_storage.iter_column_mut::<C>(comp_id)?;
+        Some((&self.entities, column))
+    }
+
     /// Get the [`EntityId`] of the entity stored at that index.
     /// Return `None` if the index is out of bounds.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(1));
+    /// let (sid, index) = world.entity_location(entity).unwrap();
+    /// let storage = world.storages().arch_storages().get_storage(sid).unwrap();
+    /// assert_eq!(storage.get_entity_at(index), Some(entity));
+    /// assert_eq!(storage.get_entity_at(storage.next_index()), None);
+    /// ```
     pub fn get_entity_at(&self, index: ArchStorageIndex) -> Option<EntityId> {
         self.entities.get(index.0).copied()
     }
 
     /// Get the [`EntityId`] of the entity stored at that index, without doing bounds checking.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(1));
+    /// let (sid, index) = world.entity_location(entity).unwrap();
+    /// let storage = world.storages().arch_storages().get_storage(sid).unwrap();
+    /// // SAFETY: The index of a live entity is in bounds.
+    /// assert_eq!(unsafe { storage.get_entity_at_unchecked(index) }, entity);
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that the `index` is valid, and within the bounds of the storage.
     pub unsafe fn get_entity_at_unchecked(&self, index: ArchStorageIndex) -> EntityId {
+        paranoid::check("ArchEntityStorage::get_entity_at_unchecked", || {
+            paranoid::index("index", index.0, self.entities.len())
+        });
         *self.entities.get_unchecked(index.0)
     }
 
@@ -97,6 +512,24 @@ impl ArchEntityStorage {
     /// Returns the [`EntityId`] that was last, so its [`EntityMeta`] can be updated
     /// to reflect the new [`ArchStorageIndex`].
     /// Return `None` if no `EntityMeta` needs to be updated (that swap-remove removed the last entity)
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let batch = (0..3u32).map(|i| (EntityId::from_bits(i.into()), Position(i as i32)));
+    /// let first = storage.store_entities(batch, &components).unwrap();
+    /// // The last entity moved into the removed row, so its location must be updated.
+    /// assert_eq!(storage.swap_remove(first), Some(EntityId::from_bits(2)));
+    /// assert_eq!(storage.entities(), [EntityId::from_bits(2), EntityId::from_bits(1)]);
+    /// ```
+    ///
     /// # Panics
     /// Panics if the index is out of bounds.
     pub fn swap_remove(&mut self, index: ArchStorageIndex) -> Option<EntityId> {
@@ -107,4 +540,252 @@ impl ArchEntityStorage {
         self.get_entity_at(index) // If we swap-remove the last entity, that means that there is no entity that
                                   // whose `EntityMeta` needs updating. So we return `None`.
     }
+
+    /// Swap-remove an entity like [`Self::swap_remove`], but its components are moved out to `f` (with their
+    /// [`ComponentId`]) instead of being dropped. Returns the [`EntityId`] that was moved into its index, like
+    /// [`Self::swap_remove`].
+    /// # Safety
+    /// The caller must ensure that `f` takes ownership of (or drops) every component that it's handed.
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    pub(crate) unsafe fn swap_remove_and_forget(
+        &mut self,
+        index: ArchStorageIndex,
+        f: impl FnMut(ComponentId, OwningPtr<'_>),
+    ) -> Option<EntityId> {
+        self.entities.swap_remove(index.0);
+        // SAFETY: doing `swap_remove` on self.entities didn't panic, so the index is in bounds (see
+        // `Self::swap_remove`).
+        self.arch_storage.swap_remove_and_forget_unchecked(index, f);
+        self.get_entity_at(index)
+    }
+
+    /// Move the entity at `from` (and its data) to `to`, shifting the entities in between by one row, so the order
+    /// of the other entities is kept. The [`EntityMeta`](crate::entity::EntityMeta)s of the moved entities need
+    /// to be updated to reflect their new [`ArchStorageIndex`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let batch = (0..3u32).map(|i| (EntityId::from_bits(i.into()), Position(i as i32)));
+    /// storage.store_entities(batch, &components);
+    /// let rows: Vec<_> = storage.iter_indices().collect();
+    /// storage.move_row(rows[0], rows[2]);
+    /// let ids: Vec<u64> = storage.entities().iter().map(|entity| entity.to_bits()).collect();
+    /// assert_eq!(ids, [1, 2, 0]);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if either index is out of bounds.
+    pub fn move_row(&mut self, from: ArchStorageIndex, to: ArchStorageIndex) {
+        assert!(
+            from.0 < self.len() && to.0 < self.len(),
+            "Can't move rows that are out of bounds"
+        );
+        if from == to {
+            return;
+        }
+        // SAFETY: We checked that both indices are in bounds.
+        unsafe { self.arch_storage.move_row_unchecked(from, to) }
+        if from.0 < to.0 {
+            self.entities[from.0..=to.0].rotate_left(1);
+        } else {
+            self.entities[to.0..=from.0].rotate_right(1);
+        }
+    }
+
+    /// Permute the entities (and their data), so the entity at row `i` is the entity that was at row `order[i]`.
+    /// The [`EntityMeta`](crate::entity::EntityMeta)s of the entities need to be updated to reflect their new
+    /// [`ArchStorageIndex`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let position = components.register_component::<Position>().unwrap();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let batch = (0..3u32).map(|i| (EntityId::from_bits(i.into()), Position(i as i32)));
+    /// storage.store_entities(batch, &components);
+    /// storage.permute_rows(&[2, 1, 0]);
+    /// let ids: Vec<u64> = storage.entities().iter().map(|entity| entity.to_bits()).collect();
+    /// assert_eq!(ids, [2, 1, 0]);
+    /// // The components move with their entities.
+    /// assert_eq!(unsafe { storage.get_column::<Position>(position) }.unwrap()[0].0, 2);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `order` isn't a permutation of the rows.
+    pub fn permute_rows(&mut self, order: &[usize]) {
+        let mut seen = vec![false; self.len()];
+        assert!(
+            order.len() == self.len()
+                && order
+                    .iter()
+                    .all(|&row| row < seen.len() && !std::mem::replace(&mut seen[row], true)),
+            "Can't permute rows with an order that isn't a permutation of the rows"
+        );
+        // SAFETY: We checked that `order` is a permutation of the rows.
+        unsafe { self.arch_storage.permute_rows_unchecked(order) }
+        self.entities = order.iter().map(|&row| self.entities[row]).collect();
+    }
+
+    /// Remove an entity and its data, like [`Self::swap_remove`], but every entity after it moves up a row, so
+    /// the order of the entities is kept. The [`EntityMeta`](crate::entity::EntityMeta)s of the entities from
+    /// `index` onwards need to be updated to reflect their new [`ArchStorageIndex`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let batch = (0..3u32).map(|i| (EntityId::from_bits(i.into()), Position(i as i32)));
+    /// let first = storage.store_entities(batch, &components).unwrap();
+    /// storage.shift_remove(first);
+    /// // Unlike `swap_remove`, the order of the other entities is kept.
+    /// let ids: Vec<u64> = storage.entities().iter().map(|entity| entity.to_bits()).collect();
+    /// assert_eq!(ids, [1, 2]);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    pub fn shift_remove(&mut self, index: ArchStorageIndex) {
+        let last = ArchStorageIndex(self.len().saturating_sub(1));
+        self.move_row(index, last);
+        self.swap_remove(last);
+    }
+
+    /// Returns `true` if a [`StoragePin`](crate::world::pin::StoragePin) of this storage is alive, so its rows
+    /// can't be moved (see [`World::pin_storage`](crate::world::World::pin_storage)). A frozen storage is pinned
+    /// too (see [`ArchStorage::is_frozen`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(1));
+    /// let (sid, _) = world.entity_location(entity).unwrap();
+    /// let pinned = |world: &World| world.storages().arch_storages().get_storage(sid).unwrap().is_pinned();
+    /// let pin = world.pin_storage(sid);
+    /// assert!(pinned(&world));
+    /// drop(pin);
+    /// assert!(!pinned(&world));
+    /// ```
+    pub fn is_pinned(&self) -> bool {
+        self.pins.load(Ordering::Acquire) > 0 || self.frozen.is_some()
+    }
+
+    /// Returns `true` if `incoming` more entities can be stored without moving the rows of a pinned storage: they
+    /// must fit the current capacity, and the storage can't be sort-maintained or frozen. Always `true` if it isn't
+    /// pinned.
+    pub(crate) fn can_store_while_pinned(&self, incoming: usize) -> bool {
+        !self.is_pinned()
+            || (!self.is_frozen() && !self.is_sort_maintained() && self.has_room_for(incoming))
+    }
+
+    /// Freeze the components, and share them with the caller (see [`ArchStorage::freeze`]). The caller keeps a
+    /// handle of them in [`Self::frozen`].
+    pub(crate) fn freeze(&mut self) -> (Arc<Columns>, Option<Arc<[usize]>>) {
+        self.arch_storage.freeze()
+    }
+
+    /// Drop the handle of the frozen components, and allow them to be mutated again (see
+    /// [`ArchStorage::unfreeze`]).
+    pub(crate) fn unfreeze(&mut self) {
+        self.frozen = None;
+        self.arch_storage.unfreeze();
+    }
+
+    /// Returns `true` if the order of the entities is maintained (see
+    /// [`World::maintain_sort`](crate::world::World::maintain_sort)).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Depth(u32);
+    ///
+    /// let mut world = World::default();
+    /// world.maintain_sort::<Depth, Depth, u32>(|depth| depth.0);
+    /// let entity = world.spawn(Depth(1));
+    /// let (sid, _) = world.entity_location(entity).unwrap();
+    /// let storage = world.storages().arch_storages().get_storage(sid).unwrap();
+    /// assert!(storage.is_sort_maintained());
+    /// ```
+    pub fn is_sort_maintained(&self) -> bool {
+        self.sort.is_some()
+    }
+
+    /// Remove (and drop) all the entities in the storage, and return their [`EntityId`]s.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::ArchEntityStorage;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storage = ArchEntityStorage::new::<Position>(&components).unwrap();
+    /// let batch = (0..2u32).map(|i| (EntityId::from_bits(i.into()), Position(i as i32)));
+    /// storage.store_entities(batch, &components);
+    /// assert_eq!(storage.clear(), [EntityId::from_bits(0), EntityId::from_bits(1)]);
+    /// assert!(storage.is_empty());
+    /// ```
+    pub fn clear(&mut self) -> Vec<EntityId> {
+        self.arch_storage.clear();
+        std::mem::take(&mut self.entities)
+    }
+
+    /// Create a copy of this storage that shares its components, until either of them is mutated
+    /// (see [`ArchStorage::is_shared`]). Panics if a stored component can't be cloned.
+    /// A pinned storage (see [`Self::is_pinned`]) isn't shared: the copy gets its own components right away, so
+    /// mutating the pinned storage never moves its components.
+    pub(crate) fn share(&self) -> Self {
+        let mut copy = Self {
+            arch_storage: self.arch_storage.share(),
+            entities: self.entities.clone(),
+            sort: self.sort.clone(),
+            pins: Arc::default(),
+            frozen: None,
+        };
+        if self.is_pinned() {
+            copy.make_unique();
+        }
+        copy
+    }
+
+    /// If the components are shared, replace them with a deep copy (see [`ArchStorage::make_unique`]).
+    pub(crate) fn make_unique(&mut self) {
+        self.arch_storage.make_unique();
+    }
+
+    /// Update the clone functions of the stored components (see [`ArchStorage::refresh_clone_fns`]).
+    pub(crate) fn refresh_clone_fns(&mut self, compf: &ComponentFactory) {
+        self.arch_storage.refresh_clone_fns(compf);
+    }
+
+    /// Update the order that the stored components are dropped in (see [`ArchStorage::refresh_drop_order`]).
+    pub(crate) fn refresh_drop_order(&mut self, compf: &ComponentFactory) {
+        self.arch_storage.refresh_drop_order(compf);
+    }
 }
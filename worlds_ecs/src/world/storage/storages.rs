@@ -1,87 +1,672 @@
-use crate::{archetype::Archetype, prelude::ComponentFactory, utils::prime_key::PrimeArchKey};
+use crate::{
+    archetype::key::PrimeArchKey,
+    archetype::{Archetype, ArchetypeInfo, MAX_COMPS_PER_ARCH},
+    prelude::ComponentFactory,
+    query::match_cache::{MatchCache, MatchRecord},
+    utils::{
+        component_mask::ComponentMask,
+        panics,
+        paranoid::{self, Violation},
+    },
+    world::latency::LatencyCategory,
+};
+use std::{borrow::Borrow, collections::HashMap};
 
 use super::{arch_storage::ArchStorage, tag_storage::TagStorage, ArchEntityStorage};
 
-/// A data structure to keep track of all the storages in the world, and their information.
-// TODO: Better docs
+/// A data structure to keep track of all the storages in the world, and their information. Every archetype that was
+/// ever spawned has an [`ArchEntityStorage`] in [`Self::arch_storages`], which stores the entities of the archetype
+/// and their components in columns. The tags of the entities are in a [`TagStorage`]. See
+/// [`World::storages`](crate::world::World::storages).
 #[derive(Default)]
 pub struct StorageFactory {
     pub(crate) arch_storages: ArchStorages,
     pub(crate) tag_storage: TagStorage,
 }
 
+/// A storage, with the keys it's matched by. The keys are copied out of the storage, so matching storages only
+/// reads the entries, and they are only ever set together with the storage (see [`StorageEntry::new`]).
+pub(crate) struct StorageEntry {
+    pkey: PrimeArchKey,
+    mask: ComponentMask,
+    storage: ArchEntityStorage,
+    /// The archetype of the storage, which identifies it across frames.
+    archetype: ArchetypeId,
+    /// How many times a storage was put in this entry (`0` if the entry was never initialized). Storages are only
+    /// replaced when they are quarantined (see [`ArchStorages::quarantine`]), so caches that remember an
+    /// [`ArchStorageId`] can compare it to know if the entry still holds the same storage.
+    generation: u32,
+    /// Whether the storage was quarantined, so its archetype is never stored in it again.
+    quarantined: bool,
+}
+
+impl StorageEntry {
+    fn new(storage: ArchEntityStorage, archetype: ArchetypeId) -> Self {
+        let entry = StorageEntry {
+            pkey: storage.prime_key(),
+            mask: storage.component_mask(),
+            storage,
+            archetype,
+            generation: 1,
+            quarantined: false,
+        };
+        entry.debug_assert_initialized();
+        entry
+    }
+
+    /// Panic (in debug builds) if the keys of the entry don't describe its storage.
+    fn debug_assert_initialized(&self) {
+        debug_assert!(self.generation > 0, "The storage entry isn't initialized");
+        debug_assert!(
+            self.pkey.is_exact_archetype(self.storage.prime_key()),
+            "The prime key of the storage entry doesn't match its storage"
+        );
+        debug_assert_eq!(
+            self.mask,
+            self.storage.component_mask(),
+            "The component mask of the storage entry doesn't match its storage"
+        );
+    }
+
+    /// The [`PrimeArchKey`] of the archetype of the storage.
+    pub(crate) fn pkey(&self) -> PrimeArchKey {
+        self.pkey
+    }
+}
+
+impl Borrow<PrimeArchKey> for StorageEntry {
+    fn borrow(&self) -> &PrimeArchKey {
+        &self.pkey
+    }
+}
+
 /// All the [`ArchStorage`]s in the [`World`](crate::prelude::World)
 #[derive(Default)]
 pub struct ArchStorages {
-    storages: Vec<ArchEntityStorage>,
-    pkeys: Vec<PrimeArchKey>,
+    /// The storages, indexed by [`ArchStorageId`]. Storages are never removed.
+    entries: Vec<StorageEntry>,
+    /// Which query keys matched any of the storages, so queries that can't match bail early.
+    match_cache: MatchCache,
+    /// How many storages were created ahead of time (see [`World::precreate_from_manifest`](crate::world::World::precreate_from_manifest)).
+    precreated: usize,
+    /// The storages that were created on demand, by the first entity that needed them.
+    created_on_demand: Vec<ArchStorageId>,
+    /// The [`ArchetypeId`] of every archetype that was ever stored, so an archetype keeps its id even if its storage
+    /// is created again.
+    archetype_ids: HashMap<PrimeArchKey, ArchetypeId>,
+    /// The storage of each archetype, indexed by [`ArchetypeId`] (`None` if the archetype has no storage).
+    locations: Vec<Option<ArchStorageId>>,
+    /// The perturbations of the storages, see [`World::enable_chaos`](crate::world::World::enable_chaos).
+    #[cfg(feature = "chaos")]
+    pub(crate) chaos: Option<crate::world::chaos::Chaos>,
 }
 
 /// Identifies an [`ArchStorage`] in the [`StorageFactory`]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 #[repr(transparent)]
 pub struct ArchStorageId(pub(crate) usize);
 
+/// Identifies an archetype of a [`World`](crate::prelude::World), so it can be used as a key across frames (like a
+/// render pipeline for each vertex layout, or a network baseline for each archetype). An [`ArchStorageId`] only
+/// locates the storage of an archetype for now, while the id of an archetype stays the same for as long as the
+/// world lives, even if its storage moves (see [`ArchStorages::storage_for_archetype`]).
+///
+/// Ids are assigned in the order that the archetypes are first stored in, starting from `0`, and are never reused.
+/// They are local to their world: two worlds that store the same archetypes in a different order give them
+/// different ids.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+#[repr(transparent)]
+pub struct ArchetypeId(pub(crate) u32);
+
+impl ArchetypeId {
+    /// The index of the id: how many archetypes were stored before this one.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh;
+    /// #[derive(Component)]
+    /// struct Skin;
+    ///
+    /// let mut world = World::default();
+    /// let (mesh, skinned) = (world.spawn(Mesh), world.spawn((Mesh, Skin)));
+    /// assert_eq!(world.archetype_id_of(mesh).unwrap().index(), 0);
+    /// assert_eq!(world.archetype_id_of(skinned).unwrap().index(), 1);
+    /// ```
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+impl StorageFactory {
+    /// The storages of the archetypes.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Mesh;
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Mesh);
+    /// assert_eq!(world.storages().arch_storages().archetype_count(), 1);
+    /// ```
+    pub fn arch_storages(&self) -> &ArchStorages {
+        &self.arch_storages
+    }
+
+    /// Create a copy of all the storages, whose components are shared until they are mutated
+    /// (see [`ArchEntityStorage::share`]). Panics if a stored component can't be cloned.
+    pub(crate) fn share(&self) -> StorageFactory {
+        StorageFactory {
+            arch_storages: ArchStorages {
+                entries: self
+                    .arch_storages
+                    .entries
+                    .iter()
+                    .map(|entry| StorageEntry {
+                        storage: entry.storage.share(),
+                        ..*entry
+                    })
+                    .collect(),
+                match_cache: MatchCache::default(),
+                precreated: self.arch_storages.precreated,
+                created_on_demand: self.arch_storages.created_on_demand.clone(),
+                archetype_ids: self.arch_storages.archetype_ids.clone(),
+                locations: self.arch_storages.locations.clone(),
+                #[cfg(feature = "chaos")]
+                chaos: self.arch_storages.chaos.clone(),
+            },
+            tag_storage: self.tag_storage.deep_clone(),
+        }
+    }
+}
+
 impl ArchStorages {
+    /// Take all of the storages out.
+    pub(crate) fn into_storages(self) -> Vec<ArchEntityStorage> {
+        self.entries
+            .into_iter()
+            .map(|entry| entry.storage)
+            .collect()
+    }
+
+    /// Add a storage, and return its [`ArchStorageId`].
+    fn push(
+        &mut self,
+        storage: ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) -> ArchStorageId {
+        #[cfg(feature = "chaos")]
+        self.push_empty_sub_storage(&storage, comp_factory);
+        #[cfg(not(feature = "chaos"))]
+        let _ = comp_factory;
+        self.push_entry(storage)
+    }
+
+    /// Add a storage in a new entry, with the [`ArchetypeId`] of its archetype (which is assigned if the archetype
+    /// was never stored), and return its [`ArchStorageId`].
+    fn push_entry(&mut self, storage: ArchEntityStorage) -> ArchStorageId {
+        let sid = ArchStorageId(self.entries.len());
+        let next = ArchetypeId(self.locations.len() as u32);
+        let archetype = *self
+            .archetype_ids
+            .entry(storage.prime_key())
+            .or_insert(next);
+        if archetype == next {
+            self.locations.push(None);
+        }
+        self.locations[archetype.0 as usize] = Some(sid);
+        self.entries.push(StorageEntry::new(storage, archetype));
+        sid
+    }
+
+    /// Add an empty storage of a sub-archetype of `storage` (which is about to be added), if it isn't stored yet and
+    /// the chaos rolls it (see [`ChaosConfig::empty_storages`](crate::world::chaos::ChaosConfig::empty_storages)).
+    #[cfg(feature = "chaos")]
+    fn push_empty_sub_storage(
+        &mut self,
+        storage: &ArchEntityStorage,
+        comp_factory: &ComponentFactory,
+    ) {
+        let Some(chaos) = &self.chaos else {
+            return;
+        };
+        let mut comp_ids: Vec<_> = storage.component_ids().collect();
+        if comp_ids.is_empty() || !chaos.roll(chaos.config.empty_storages) {
+            return;
+        }
+        comp_ids.sort();
+        comp_ids.remove(chaos.below(comp_ids.len()));
+        let arch_info = ArchetypeInfo::from_component_ids(comp_ids);
+        if self
+            .position_of_exact_archetype(arch_info.prime_key())
+            .is_none()
+        {
+            if let Ok(empty) = ArchEntityStorage::from_arch_info(&arch_info, comp_factory) {
+                self.push_entry(empty);
+            }
+        }
+    }
+
+    /// The [`ArchStorageId`] that queries start iterating from, wrapping around to the first storage after the last
+    /// one. It's always the first storage, unless the chaos permutes the queries (see
+    /// [`ChaosConfig::permute_queries`](crate::world::chaos::ChaosConfig::permute_queries)).
+    #[inline]
+    pub(crate) fn query_start(&self) -> ArchStorageId {
+        #[cfg(feature = "chaos")]
+        if let Some(chaos) = &self.chaos {
+            if chaos.config.permute_queries && !self.entries.is_empty() {
+                return ArchStorageId(chaos.below(self.entries.len()));
+            }
+        }
+        ArchStorageId(0)
+    }
+
+    /// The [`ArchStorageId`] of the storage of an archetype, or `None` if the archetype has no storage (or if the id is
+    /// from another world).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(1));
+    /// let archetype = world.archetype_id_of(entity).unwrap();
+    /// let (sid, _) = world.entity_location(entity).unwrap();
+    /// assert_eq!(world.storages().arch_storages().storage_for_archetype(archetype), Some(sid));
+    /// ```
+    pub fn storage_for_archetype(&self, id: ArchetypeId) -> Option<ArchStorageId> {
+        self.locations.get(id.0 as usize).copied().flatten()
+    }
+
+    /// The [`ArchetypeId`] of the archetype of a storage, or `None` if there is no storage with this id.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(1));
+    /// let (sid, _) = world.entity_location(entity).unwrap();
+    /// let archetype = world.storages().arch_storages().archetype_of_storage(sid);
+    /// assert_eq!(archetype, world.archetype_id_of(entity));
+    /// ```
+    pub fn archetype_of_storage(&self, sid: ArchStorageId) -> Option<ArchetypeId> {
+        self.entries.get(sid.0).map(|entry| entry.archetype)
+    }
+
+    /// The amount of archetypes that have a storage.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::storages::ArchStorages;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let mut storages = ArchStorages::default();
+    /// storages.get_mut_or_create_storage_with_exact_archetype::<Position>(&mut components);
+    /// storages.get_mut_or_create_storage_with_exact_archetype::<(Position, Velocity)>(&mut components);
+    /// storages.get_mut_or_create_storage_with_exact_archetype::<Position>(&mut components);
+    /// assert_eq!(storages.archetype_count(), 2);
+    /// ```
+    pub fn archetype_count(&self) -> usize {
+        self.locations.iter().flatten().count()
+    }
+
+    /// The [`ArchetypeId`]s of every archetype that was ever stored, in ascending order.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::storages::ArchStorages;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let mut storages = ArchStorages::default();
+    /// storages.get_mut_or_create_storage_with_exact_archetype::<Position>(&mut components);
+    /// storages.get_mut_or_create_storage_with_exact_archetype::<Velocity>(&mut components);
+    /// let ids: Vec<u32> = storages.archetype_ids().map(|id| id.index()).collect();
+    /// assert_eq!(ids, [0, 1]);
+    /// ```
+    pub fn archetype_ids(&self) -> impl Iterator<Item = ArchetypeId> + '_ {
+        (0..self.locations.len() as u32).map(ArchetypeId)
+    }
+
+    /// Swap the entries of two storages, like a compaction of the storages would, and update the locations of their
+    /// archetypes. The metas of the stored entities aren't updated, so this is only for tests of the locations.
+    #[cfg(test)]
+    pub(crate) fn swap_entries(&mut self, a: ArchStorageId, b: ArchStorageId) {
+        self.entries.swap(a.0, b.0);
+        for sid in [a, b] {
+            self.locations[self.entries[sid.0].archetype.0 as usize] = Some(sid);
+        }
+        self.match_cache = MatchCache::default();
+    }
+
     /// Get a shared reference to an [`ArchStorage`] from its [`ArchStorageId`]
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(1));
+    /// let (sid, index) = world.entity_location(entity).unwrap();
+    /// let storage = world.storages().arch_storages().get_storage(sid).unwrap();
+    /// assert_eq!(storage.get_entity_at(index), Some(entity));
+    /// ```
     pub fn get_storage(&self, id: ArchStorageId) -> Option<&ArchEntityStorage> {
-        self.storages.get(id.0)
+        self.entries.get(id.0).map(|entry| &entry.storage)
     }
 
     /// Get an exclusive reference to an [`ArchStorage`] from its [`ArchStorageId`]
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::storages::ArchStorages;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let mut storages = ArchStorages::default();
+    /// let (sid, _) = storages.get_mut_or_create_storage_with_exact_archetype::<Position>(&mut components);
+    /// let storage = storages.get_storage_mut(sid).unwrap();
+    /// storage.store_entity(EntityId::from_bits(0), Position(1), &components);
+    /// assert_eq!(storages.get_storage(sid).unwrap().len(), 1);
+    /// ```
     pub fn get_storage_mut(&mut self, id: ArchStorageId) -> Option<&mut ArchEntityStorage> {
-        self.storages.get_mut(id.0)
+        self.entries.get_mut(id.0).map(|entry| &mut entry.storage)
+    }
+
+    /// Get exclusive references to two different storages at once, from their [`ArchStorageId`]s.
+    ///
+    /// # Panics
+    /// If the ids are equal, or out of bounds.
+    pub(crate) fn get_two_storages_mut(
+        &mut self,
+        a: ArchStorageId,
+        b: ArchStorageId,
+    ) -> (&mut ArchEntityStorage, &mut ArchEntityStorage) {
+        assert_ne!(a, b, "Can't borrow the same storage twice");
+        let (low, high) = self.entries.split_at_mut(a.0.max(b.0));
+        let (low, high) = (&mut low[a.0.min(b.0)].storage, &mut high[0].storage);
+        match a.0 < b.0 {
+            true => (low, high),
+            false => (high, low),
+        }
     }
 
     /// Get a shared reference to an [`ArchStorage`] from its [`ArchStorageId`], without doing any bounds checking
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut world = World::default();
+    /// let entity = world.spawn(Position(1));
+    /// let (sid, _) = world.entity_location(entity).unwrap();
+    /// // SAFETY: The storage of a live entity exists.
+    /// let storage = unsafe { world.storages().arch_storages().get_storage_unchecked(sid) };
+    /// assert_eq!(storage.len(), 1);
+    /// ```
+    ///
+    /// # Safety
+    /// The caller must ensure that the [`ArchStorageId`] is in bounds.
     pub unsafe fn get_storage_unchecked(&self, id: ArchStorageId) -> &ArchStorage {
-        self.storages.get_unchecked(id.0)
+        paranoid::check("ArchStorages::get_storage_unchecked", || {
+            paranoid::index("id", id.0, self.entries.len())
+        });
+        &self.entries.get_unchecked(id.0).storage
     }
 
     /// Get an exclusive reference to an [`ArchStorage`] from its [`ArchStorageId`], without doing any bounds checking
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::storages::ArchStorages;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let mut storages = ArchStorages::default();
+    /// let (sid, _) = storages.get_mut_or_create_storage_with_exact_archetype::<Position>(&mut components);
+    /// // SAFETY: The storage was just created.
+    /// let storage = unsafe { storages.get_storage_mut_unchecked(sid) };
+    /// storage.store_entity(EntityId::from_bits(0), Position(1), &components);
+    /// assert_eq!(storage.len(), 1);
+    /// ```
+    ///
+    /// # Safety
+    /// The caller must ensure that the [`ArchStorageId`] is in bounds.
     pub unsafe fn get_storage_mut_unchecked(
         &mut self,
         id: ArchStorageId,
     ) -> &mut ArchEntityStorage {
-        self.storages.get_unchecked_mut(id.0)
+        paranoid::check("ArchStorages::get_storage_mut_unchecked", || {
+            paranoid::index("id", id.0, self.entries.len())
+        });
+        &mut self.entries.get_unchecked_mut(id.0).storage
+    }
+
+    /// The [`ArchStorageId`] of the storage that stores archetypes with the exact same [`PrimeArchKey`]. Quarantined
+    /// storages are skipped.
+    fn position_of_exact_archetype(&self, pkey: PrimeArchKey) -> Option<ArchStorageId> {
+        self.entries
+            .iter()
+            .position(|entry| !entry.quarantined && entry.pkey.is_exact_archetype(pkey))
+            .map(ArchStorageId)
+    }
+
+    /// Replace the storage with the [`ArchStorageId`] `sid` with an empty storage of the same archetype, and return
+    /// the replaced storage. The entry is never used to store its archetype again: a new storage is created for
+    /// it. Returns `None` if there is no such storage, or if it was already quarantined.
+    pub(crate) fn quarantine(
+        &mut self,
+        sid: ArchStorageId,
+        comp_factory: &ComponentFactory,
+    ) -> Option<ArchEntityStorage> {
+        let entry = self
+            .entries
+            .get_mut(sid.0)
+            .filter(|entry| !entry.quarantined)?;
+        let arch_info = ArchetypeInfo::from_component_ids(entry.storage.component_ids().collect());
+        let empty = ArchEntityStorage::from_arch_info(&arch_info, comp_factory).ok()?;
+        entry.quarantined = true;
+        entry.generation += 1;
+        let location = &mut self.locations[entry.archetype.0 as usize];
+        if *location == Some(sid) {
+            *location = None;
+        }
+        Some(std::mem::replace(&mut entry.storage, empty))
+    }
+
+    /// Whether the storage with the [`ArchStorageId`] `sid` was quarantined (see [`Self::quarantine`]).
+    pub(crate) fn is_quarantined(&self, sid: ArchStorageId) -> bool {
+        self.entries
+            .get(sid.0)
+            .is_some_and(|entry| entry.quarantined)
     }
 
     /// Get the [`ArchStorage`]s that stores archetypes with the exact same [`PrimeArchKey`]
+    ///
+    /// ```
+    /// use worlds_ecs::{archetype::Archetype, prelude::*};
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn((Position(0), Velocity(1)));
+    /// let storages = world.storages().arch_storages();
+    /// let key = <(Position, Velocity)>::prime_key(world.components()).unwrap();
+    /// assert_eq!(storages.get_storage_with_exact_archetype(key).unwrap().len(), 1);
+    /// // Unlike queries, this doesn't match the storages of sub-archetypes.
+    /// let key = Position::prime_key(world.components()).unwrap();
+    /// assert!(storages.get_storage_with_exact_archetype(key).is_none());
+    /// ```
     pub fn get_storage_with_exact_archetype(
         &self,
         pkey: PrimeArchKey,
     ) -> Option<&ArchEntityStorage> {
-        self.pkeys
-            .iter()
-            .zip(&self.storages)
-            .find_map(move |(p, storage)| p.is_exact_archetype(pkey).then_some(storage))
+        self.position_of_exact_archetype(pkey)
+            .map(|sid| &self.entries[sid.0].storage)
     }
 
     /// Get mutable access to the [`ArchStorage`]s that stores archetypes with the exact same [`PrimeArchKey`]
+    ///
+    /// ```
+    /// use worlds_ecs::{archetype::Archetype, prelude::*, world::storage::storages::ArchStorages};
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let mut storages = ArchStorages::default();
+    /// storages.get_mut_or_create_storage_with_exact_archetype::<Position>(&mut components);
+    /// let key = Position::prime_key(&components).unwrap();
+    /// let storage = storages.get_mut_storage_with_exact_archetype(key).unwrap();
+    /// storage.store_entity(EntityId::from_bits(0), Position(1), &components);
+    /// assert_eq!(storage.len(), 1);
+    /// ```
     pub fn get_mut_storage_with_exact_archetype(
         &mut self,
         pkey: PrimeArchKey,
     ) -> Option<&mut ArchEntityStorage> {
-        self.pkeys
-            .iter_mut()
-            .zip(&mut self.storages)
-            .find_map(move |(p, storage)| p.is_exact_archetype(pkey).then_some(storage))
+        self.position_of_exact_archetype(pkey)
+            .map(|sid| &mut self.entries[sid.0].storage)
     }
 
     /// Get mutable access to the [`ArchStorage`]s that stores archetypes with the exact same [`PrimeArchKey`].
     /// If a storage for this Archetype doesn't exist already, a new one will be created.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::storages::ArchStorages;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let mut storages = ArchStorages::default();
+    /// // The components of the archetype are registered if they aren't already.
+    /// let (sid, _) = storages.get_mut_or_create_storage_with_exact_archetype::<Position>(&mut components);
+    /// assert!(components.is_registered::<Position>());
+    /// let (again, _) = storages.get_mut_or_create_storage_with_exact_archetype::<Position>(&mut components);
+    /// assert_eq!(sid, again);
+    /// ```
+    ///
+    /// # Panics
+    /// If a component appears more than once in the archetype, or if it has more than [`MAX_COMPS_PER_ARCH`]
+    /// components.
+    #[track_caller]
     pub fn get_mut_or_create_storage_with_exact_archetype<A: Archetype>(
         &mut self,
         comp_factory: &mut ComponentFactory,
     ) -> (ArchStorageId, &mut ArchEntityStorage) {
-        let pkey = A::get_prime_key_or_register(comp_factory);
-        for i in 0..self.storages.len() {
-            if self.pkeys[i].is_exact_archetype(pkey) {
-                return (ArchStorageId(i), &mut self.storages[i]);
+        let mut pkey = PrimeArchKey::IDENTITY;
+        if !A::merge_prime_key_or_register(comp_factory, &mut pkey) {
+            panics::fail_duplicate_components::<A>("store_archetype");
+        }
+        let sid = match self.position_of_exact_archetype(pkey) {
+            Some(sid) => sid,
+            None => {
+                let arch_info = A::arch_info(comp_factory).unwrap();
+                if arch_info.component_ids().len() > MAX_COMPS_PER_ARCH {
+                    panics::fail_too_many_components::<A>(
+                        "store_archetype",
+                        &arch_info,
+                        comp_factory,
+                    );
+                }
+                let sid = self.store_new_archetype_checked::<A>(comp_factory).unwrap();
+                self.created_on_demand.push(sid);
+                sid
             }
+        };
+        (sid, &mut self.entries[sid.0].storage)
+    }
+
+    /// Like [`Self::get_mut_or_create_storage_with_exact_archetype`], for the archetype described by an
+    /// [`ArchetypeInfo`]. Returns `None` if some of its components aren't registered, or if it has duplicate
+    /// components.
+    pub(crate) fn get_mut_or_create_storage_with_info(
+        &mut self,
+        arch_info: &ArchetypeInfo,
+        comp_factory: &ComponentFactory,
+    ) -> Option<(ArchStorageId, &mut ArchEntityStorage)> {
+        let (sid, created) = self.get_or_create_storage_with_info(arch_info, comp_factory)?;
+        if created {
+            self.created_on_demand.push(sid);
+        }
+        Some((sid, &mut self.entries[sid.0].storage))
+    }
+
+    /// Create the storage of the archetype described by an [`ArchetypeInfo`] ahead of time, before any entity needs
+    /// it. Returns whether it was created (`false` if it already existed), or `None` like
+    /// [`Self::get_mut_or_create_storage_with_info`].
+    pub(crate) fn precreate_storage_with_info(
+        &mut self,
+        arch_info: &ArchetypeInfo,
+        comp_factory: &ComponentFactory,
+    ) -> Option<bool> {
+        let (_, created) = self.get_or_create_storage_with_info(arch_info, comp_factory)?;
+        self.precreated += created as usize;
+        Some(created)
+    }
+
+    fn get_or_create_storage_with_info(
+        &mut self,
+        arch_info: &ArchetypeInfo,
+        comp_factory: &ComponentFactory,
+    ) -> Option<(ArchStorageId, bool)> {
+        if arch_info.check_for_duplicates() {
+            return None;
+        }
+        if let Some(sid) = self.position_of_exact_archetype(arch_info.prime_key()) {
+            return Some((sid, false));
         }
-        let sid = self.store_new_archetype_checked::<A>(comp_factory).unwrap();
-        (sid, self.get_storage_mut(sid).unwrap())
+        let storage = comp_factory
+            .storage_alloc()
+            .latency()
+            .time(LatencyCategory::ArchetypeCreation, || {
+                ArchEntityStorage::from_arch_info(arch_info, comp_factory)
+            })
+            .ok()?;
+        Some((self.push(storage, comp_factory), true))
+    }
+
+    /// How many storages were created ahead of time, see [`Self::precreate_storage_with_info`].
+    pub(crate) fn precreated(&self) -> usize {
+        self.precreated
+    }
+
+    /// The storages that were created on demand, by the first entity that needed them.
+    pub(crate) fn created_on_demand(&self) -> &[ArchStorageId] {
+        &self.created_on_demand
     }
 
     /// Iterate over all of the [`ArchStorage`]s that store archetypes with a matching archetype of `pkey`.
@@ -89,14 +674,32 @@ impl ArchStorages {
     /// For components: A, B, C, D, E
     /// For archetypes storages (represented by the archetypes they store): (A, B, C, D, E), (A, B), (D), (D, E)
     /// The archetypes storages "matching" the archetype (D, E) are: (A, B, C, D, E) and (D, E)
+    ///
+    /// ```
+    /// use worlds_ecs::{archetype::Archetype, prelude::*};
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn(Position(0));
+    /// world.spawn((Position(0), Velocity(1)));
+    /// world.spawn(Velocity(1));
+    /// let key = Position::prime_key(world.components()).unwrap();
+    /// let matching = world.storages().arch_storages().iter_storages_with_matching_archetype(key);
+    /// // The storages of `Position` and of `(Position, Velocity)`.
+    /// assert_eq!(matching.map(|storage| storage.len()).sum::<usize>(), 2);
+    /// ```
     pub fn iter_storages_with_matching_archetype(
         &self,
         pkey: PrimeArchKey,
     ) -> impl Iterator<Item = &ArchEntityStorage> + '_ {
-        self.pkeys
+        self.entries
             .iter()
-            .zip(&self.storages)
-            .filter_map(move |(p, storage)| p.is_sub_archetype(pkey).then_some(storage))
+            .filter_map(move |entry| entry.pkey.is_sub_archetype(pkey).then_some(&entry.storage))
     }
 
     /// Iterate over all of the [`ArchStorage`]s that store archetypes with a matching archetype of `pkey` mutably.
@@ -104,52 +707,389 @@ impl ArchStorages {
     /// For components: A, B, C, D, E
     /// For archetypes storages (represented by the archetypes they store): (A, B, C, D, E), (A, B), (D), (D, E)
     /// The archetypes storages "matching" the archetype (D, E) are: (A, B, C, D, E) and (D, E)
+    ///
+    /// ```
+    /// use worlds_ecs::{archetype::Archetype, prelude::*, world::storage::storages::ArchStorages};
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let mut storages = ArchStorages::default();
+    /// storages.get_mut_or_create_storage_with_exact_archetype::<Position>(&mut components);
+    /// storages.get_mut_or_create_storage_with_exact_archetype::<(Position, Velocity)>(&mut components);
+    /// storages.get_mut_or_create_storage_with_exact_archetype::<Velocity>(&mut components);
+    /// let key = Position::prime_key(&components).unwrap();
+    /// for storage in storages.iter_storages_with_matching_archetype_mut(key) {
+    ///     storage.reserve(16);
+    /// }
+    /// assert_eq!(storages.iter_storages_with_matching_archetype(key).count(), 2);
+    /// ```
     pub fn iter_storages_with_matching_archetype_mut(
         &mut self,
         pkey: PrimeArchKey,
     ) -> impl Iterator<Item = &mut ArchEntityStorage> + '_ {
-        self.pkeys
+        self.entries.iter_mut().filter_map(move |entry| {
+            entry
+                .pkey
+                .is_sub_archetype(pkey)
+                .then_some(&mut entry.storage)
+        })
+    }
+
+    /// Like [`Self::iter_storages_with_matching_archetype_mut`], but also yields the [`ArchStorageId`] of each storage.
+    ///
+    /// ```
+    /// use worlds_ecs::{archetype::Archetype, prelude::*, world::storage::storages::ArchStorages};
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let mut storages = ArchStorages::default();
+    /// let (position, _) = storages.get_mut_or_create_storage_with_exact_archetype::<Position>(&mut components);
+    /// storages.get_mut_or_create_storage_with_exact_archetype::<Velocity>(&mut components);
+    /// let key = Position::prime_key(&components).unwrap();
+    /// let ids: Vec<_> = storages
+    ///     .iter_ids_and_storages_with_matching_archetype_mut(key)
+    ///     .map(|(sid, _)| sid)
+    ///     .collect();
+    /// assert_eq!(ids, [position]);
+    /// ```
+    pub fn iter_ids_and_storages_with_matching_archetype_mut(
+        &mut self,
+        pkey: PrimeArchKey,
+    ) -> impl Iterator<Item = (ArchStorageId, &mut ArchEntityStorage)> + '_ {
+        self.entries
             .iter_mut()
-            .zip(&mut self.storages)
-            .filter_map(move |(p, storage)| p.is_sub_archetype(pkey).then_some(storage))
+            .enumerate()
+            .filter_map(move |(i, entry)| {
+                entry
+                    .pkey
+                    .is_sub_archetype(pkey)
+                    .then_some((ArchStorageId(i), &mut entry.storage))
+            })
+    }
+
+    /// Find the first [`ArchStorage`], starting from the storage with the [`ArchStorageId`] `start`, that stores
+    /// a matching archetype of `pkey` (see [`Self::iter_storages_with_matching_archetype`]).
+    pub(crate) fn next_storage_with_matching_archetype(
+        &self,
+        start: ArchStorageId,
+        pkey: PrimeArchKey,
+    ) -> Option<ArchStorageId> {
+        self.entries
+            .get(start.0..)?
+            .iter()
+            .position(|entry| entry.pkey.is_sub_archetype(pkey))
+            .map(|offset| ArchStorageId(start.0 + offset))
+    }
+
+    /// Returns `false` if none of the storages match `pkey`, so a query with this key can't yield anything. The
+    /// result is cached per key, and only storages that were created since the last call with the same key are
+    /// checked (see [`MatchCache`]).
+    #[inline]
+    pub(crate) fn can_match(&mut self, pkey: PrimeArchKey) -> bool {
+        self.match_cache.can_match(&self.entries, pkey)
+    }
+
+    /// Like [`Self::can_match`], with a [`MatchRecord`] that is kept outside of the storages (like by a
+    /// [`QueryState`](crate::query::QueryState)).
+    #[inline]
+    pub(crate) fn can_match_record(&self, record: &mut MatchRecord, pkey: PrimeArchKey) -> bool {
+        record.can_match(&self.entries, pkey)
+    }
+
+    /// The amount of storages.
+    pub(crate) fn storage_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The [`PrimeArchKey`] of the storage with the [`ArchStorageId`] `sid`.
+    pub(crate) fn storage_key(&self, sid: ArchStorageId) -> Option<PrimeArchKey> {
+        self.entries.get(sid.0).map(StorageEntry::pkey)
+    }
+
+    /// The keys of the storages, indexed by [`ArchStorageId`].
+    #[cfg(test)]
+    pub(crate) fn storage_keys(&self) -> Vec<PrimeArchKey> {
+        self.entries.iter().map(StorageEntry::pkey).collect()
     }
 
     /// Checks if this archetype is stored here.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// #[derive(Component)]
+    /// struct Velocity(i32);
+    ///
+    /// let mut world = World::default();
+    /// world.spawn((Position(0), Velocity(1)));
+    /// let storages = world.storages().arch_storages();
+    /// assert!(storages.is_archetype_stored::<(Velocity, Position)>(world.components()));
+    /// assert!(!storages.is_archetype_stored::<Position>(world.components()));
+    /// ```
     pub fn is_archetype_stored<A: Archetype>(&self, comp_factory: &ComponentFactory) -> bool {
-        A::prime_key(comp_factory).map_or(false, |pkey1| {
-            self.pkeys
-                .iter()
-                .find(|pkey2| pkey2.is_exact_archetype(pkey1))
-                .map_or(false, |_| true)
-        })
+        A::prime_key(comp_factory)
+            .is_some_and(|pkey| self.position_of_exact_archetype(pkey).is_some())
     }
 
     /// Internally, create a new [`ArchStorage`] to store the given archetype. Returns `None` if there was
     /// already an [`ArchStorage`] storing the given archetype. If there were no previous storages storing the
-    /// given [`Archetype`], a new one is created an its [`PrimeArchKey`] is returned.
+    /// given [`Archetype`], a new one is created an its [`PrimeArchKey`] is returned. Returns `None` if some of
+    /// the components aren't registered, or if one of them appears more than once.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::storages::ArchStorages;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// let mut storages = ArchStorages::default();
+    /// // The components must be registered.
+    /// assert_eq!(storages.store_new_archetype_checked::<Position>(&components), None);
+    /// components.register_component::<Position>();
+    /// let sid = storages.store_new_archetype_checked::<Position>(&components).unwrap();
+    /// assert!(storages.get_storage(sid).is_some());
+    /// // The archetype is stored already.
+    /// assert_eq!(storages.store_new_archetype_checked::<Position>(&components), None);
+    /// ```
     pub fn store_new_archetype_checked<A: Archetype>(
         &mut self,
         comp_factory: &ComponentFactory,
     ) -> Option<ArchStorageId> {
-        (A::arch_info(comp_factory).is_some() && !self.is_archetype_stored::<A>(comp_factory))
-            // SAFETY: We checked that the components are registered, and that archetype isn't being stored already.
-            .then_some(unsafe { self.store_new_archetype_unchecked::<A>(comp_factory) })
+        (A::arch_info(comp_factory).is_some_and(|arch_info| !arch_info.check_for_duplicates())
+            && !self.is_archetype_stored::<A>(comp_factory))
+        // SAFETY: We checked that the components are registered without duplicates, and that archetype isn't
+        // being stored already.
+        .then(|| unsafe { self.store_new_archetype_unchecked::<A>(comp_factory) })
     }
 
     /// Internally, create a new [`ArchStorage`] to store the given archetype. Without checking if a previous
     /// [`ArchStorage`] already exists for this [`Archetype`], or if the components are registered in the [`ComponentFactory`].
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::storages::ArchStorages;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(i32);
+    ///
+    /// let mut components = ComponentFactory::default();
+    /// components.register_component::<Position>();
+    /// let mut storages = ArchStorages::default();
+    /// // SAFETY: `Position` is registered, and its archetype isn't stored yet.
+    /// let sid = unsafe { storages.store_new_archetype_unchecked::<Position>(&components) };
+    /// assert!(storages.is_archetype_stored::<Position>(&components));
+    /// assert!(storages.get_storage(sid).unwrap().is_empty());
+    /// ```
+    ///
     /// # Safety
     /// The caller must ensure that:
-    ///     - All of the components in the [`Archetype`] are registered in the [`ComponentFactory`].
+    ///     - All of the components in the [`Archetype`] are registered in the [`ComponentFactory`], and none of
+    ///       them appears more than once.
     ///     - The archetype isn't currently being stored in [`Self`] (using the [`Self::is_archetype_stored`] method.)
     pub unsafe fn store_new_archetype_unchecked<A: Archetype>(
         &mut self,
         comp_factory: &ComponentFactory,
     ) -> ArchStorageId {
-        self.storages
-            .push(ArchEntityStorage::new::<A>(comp_factory).unwrap_unchecked());
-        let pkey = A::prime_key(comp_factory).unwrap_unchecked();
-        self.pkeys.push(pkey);
-        ArchStorageId(self.pkeys.len() - 1)
+        let storage = comp_factory
+            .storage_alloc()
+            .latency()
+            .time(LatencyCategory::ArchetypeCreation, || {
+                ArchEntityStorage::new::<A>(comp_factory)
+            });
+        paranoid::check("ArchStorages::store_new_archetype_unchecked", || {
+            storage
+                .as_ref()
+                .map(drop)
+                .map_err(|_| Violation::InvalidArchetype(std::any::type_name::<A>()))
+        });
+        self.push(storage.unwrap_unchecked(), comp_factory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchStorageId, ArchStorages};
+    use crate::{
+        archetype::{Archetype, ArchetypeInfo},
+        prelude::*,
+        world::chaos::Chaos,
+    };
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+
+    #[derive(Component)]
+    struct A;
+    #[derive(Component)]
+    struct B;
+    #[derive(Component)]
+    struct C;
+    #[derive(Component)]
+    struct D;
+
+    /// A way to create a storage, for the archetype with the components of the bits of the mask (`A` is bit `0`).
+    #[derive(Debug, Clone, Copy)]
+    enum Create {
+        Typed(u8),
+        Checked(u8),
+        WithInfo(u8),
+        Precreate(u8),
+    }
+
+    fn create() -> impl Strategy<Value = Create> {
+        prop_oneof![
+            (0u8..16).prop_map(Create::Typed),
+            (0u8..16).prop_map(Create::Checked),
+            (0u8..16).prop_map(Create::WithInfo),
+            (0u8..16).prop_map(Create::Precreate),
+        ]
+    }
+
+    fn typed_key<T: Archetype>(
+        storages: &mut ArchStorages,
+        compf: &mut ComponentFactory,
+        checked: bool,
+    ) -> Option<ArchStorageId> {
+        if checked {
+            storages.store_new_archetype_checked::<T>(compf)
+        } else {
+            Some(
+                storages
+                    .get_mut_or_create_storage_with_exact_archetype::<T>(compf)
+                    .0,
+            )
+        }
+    }
+
+    fn typed(
+        storages: &mut ArchStorages,
+        compf: &mut ComponentFactory,
+        mask: u8,
+        checked: bool,
+    ) -> Option<ArchStorageId> {
+        match mask {
+            0 => typed_key::<()>(storages, compf, checked),
+            1 => typed_key::<A>(storages, compf, checked),
+            2 => typed_key::<B>(storages, compf, checked),
+            3 => typed_key::<(A, B)>(storages, compf, checked),
+            4 => typed_key::<C>(storages, compf, checked),
+            5 => typed_key::<(A, C)>(storages, compf, checked),
+            6 => typed_key::<(B, C)>(storages, compf, checked),
+            7 => typed_key::<(A, B, C)>(storages, compf, checked),
+            8 => typed_key::<D>(storages, compf, checked),
+            9 => typed_key::<(A, D)>(storages, compf, checked),
+            10 => typed_key::<(B, D)>(storages, compf, checked),
+            11 => typed_key::<(A, B, D)>(storages, compf, checked),
+            12 => typed_key::<(C, D)>(storages, compf, checked),
+            13 => typed_key::<(A, C, D)>(storages, compf, checked),
+            14 => typed_key::<(B, C, D)>(storages, compf, checked),
+            _ => typed_key::<(A, B, C, D)>(storages, compf, checked),
+        }
+    }
+
+    /// Create storages with `ops`, and compare them to a model. With a `chaos` seed, empty storages are created
+    /// along with new ones (see [`World::enable_chaos`](crate::world::World::enable_chaos)), and they are reused
+    /// like any other storage.
+    fn run(ops: &[Create], chaos: Option<u64>) {
+        let mut compf = ComponentFactory::default();
+        let ids = [
+            compf.register_component::<A>().unwrap(),
+            compf.register_component::<B>().unwrap(),
+            compf.register_component::<C>().unwrap(),
+            compf.register_component::<D>().unwrap(),
+        ];
+        let info = |mask: u8| {
+            ArchetypeInfo::from_component_ids(
+                (0..4)
+                    .filter(|bit| mask & (1 << bit) != 0)
+                    .map(|bit| ids[bit])
+                    .collect(),
+            )
+        };
+        let mut storages = ArchStorages {
+            chaos: chaos.map(|seed| Chaos::new(seed, ChaosConfig::default())),
+            ..ArchStorages::default()
+        };
+        let mut model: HashMap<u8, ArchStorageId> = HashMap::new();
+        for op in ops {
+            let (mask, sid) = match *op {
+                Create::Typed(mask) => (mask, typed(&mut storages, &mut compf, mask, false)),
+                Create::Checked(mask) => (mask, typed(&mut storages, &mut compf, mask, true)),
+                Create::WithInfo(mask) => (
+                    mask,
+                    storages
+                        .get_mut_or_create_storage_with_info(&info(mask), &compf)
+                        .map(|(sid, _)| sid),
+                ),
+                Create::Precreate(mask) => {
+                    let created = storages.precreate_storage_with_info(&info(mask), &compf);
+                    assert_eq!(created, Some(!model.contains_key(&mask)));
+                    (
+                        mask,
+                        storages.position_of_exact_archetype(info(mask).prime_key()),
+                    )
+                }
+            };
+            let created = !model.contains_key(&mask);
+            let next = match chaos {
+                // The new storage is the last one, after the empty storage that may have been created with it.
+                Some(_) => ArchStorageId(storages.storage_count() - 1),
+                None => ArchStorageId(model.len()),
+            };
+            let expected = *model.entry(mask).or_insert(next);
+            if let Create::Checked(_) = op {
+                // Only a new storage is returned.
+                assert_eq!(sid, created.then_some(expected));
+            } else {
+                assert_eq!(sid, Some(expected));
+            }
+            if chaos.is_some() {
+                for mask in 0..16 {
+                    if let Some(sid) = storages.position_of_exact_archetype(info(mask).prime_key())
+                    {
+                        model.entry(mask).or_insert(sid);
+                    }
+                }
+            }
+
+            assert_eq!(storages.storage_count(), model.len());
+            for (mask, sid) in &model {
+                let pkey = info(*mask).prime_key();
+                assert_eq!(storages.position_of_exact_archetype(pkey), Some(*sid));
+                assert!(storages.storage_key(*sid).unwrap().is_exact_archetype(pkey));
+            }
+            storages
+                .entries
+                .iter()
+                .for_each(|entry| entry.debug_assert_initialized());
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig { cases: 64, failure_persistence: None, ..ProptestConfig::default() })]
+
+        #[test]
+        fn test_storage_creation(ops in prop::collection::vec(create(), 1..64)) {
+            run(&ops, None);
+        }
+
+        #[test]
+        fn test_storage_creation_under_chaos(seed: u64, ops in prop::collection::vec(create(), 1..64)) {
+            run(&ops, Some(seed));
+        }
     }
 }
@@ -1,13 +1,15 @@
 use std::sync::Arc;
 
 use crate::{
-    entity::EntityId,
+    entity::{EntityFactory, EntityId},
     tag::{TagFactory, TagTracker},
+    utils::paranoid,
 };
 
-/// A data-structure to keep track of which entities have which tags.
+/// A data-structure to keep track of which entities have which tags. The entities are identified by their dense
+/// index in the [`EntityFactory`], which is their id unless the ids are salted.
 pub struct TagStorage {
-    /// The [`TagTracker`] for each entity, indexed by the entity's id.
+    /// The [`TagTracker`] for each entity, indexed by the entity's dense index.
     tag_trackers: Vec<TagTracker>,
     /// The factory to create and manage tags.
     tag_factory: Arc<TagFactory>,
@@ -15,15 +17,27 @@ pub struct TagStorage {
 
 impl Default for TagStorage {
     fn default() -> Self {
-        Self {
-            tag_trackers: Vec::new(),
-            tag_factory: Arc::new(TagFactory::default()),
-        }
+        Self::new(Arc::new(TagFactory::default()))
     }
 }
 
 impl TagStorage {
     /// Create a new [`TagStorage`] with the given [`TagFactory`].
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::tag_storage::TagStorage;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let storage = TagStorage::new(Arc::new(tagf));
+    /// assert_eq!(storage.tag_factory().tag_count(), 1);
+    /// assert!(storage.get_tag_tracker(0).is_none());
+    /// ```
     pub fn new(tagf: Arc<TagFactory>) -> Self {
         Self {
             tag_trackers: Vec::new(),
@@ -31,27 +45,186 @@ impl TagStorage {
         }
     }
 
-    /// Creates room to store the [`TagTracker`] of a new entity.
-    pub fn new_entity(&mut self) {
-        self.tag_trackers
-            .push(TagFactory::new_tracker(&self.tag_factory));
+    /// The tags of the entities of the [`EntityFactory`], for the filters of queries (see [`EntityTags`]).
+    pub(crate) fn entity_tags<'a>(&'a self, entities: &'a EntityFactory) -> EntityTags<'a> {
+        EntityTags {
+            trackers: &self.tag_trackers,
+            tag_factory: &self.tag_factory,
+            entities,
+        }
+    }
+
+    /// Creates room to store the [`TagTracker`] of a new entity, if there isn't one at its index already (from an
+    /// entity that was despawned).
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::tag_storage::TagStorage;
+    ///
+    /// let mut storage = TagStorage::new(Arc::new(TagFactory::default()));
+    /// storage.new_entity(2);
+    /// // Trackers are created for every index up to the entity's.
+    /// assert!(storage.get_tag_tracker(0).is_some());
+    /// assert!(storage.get_tag_tracker(2).is_some());
+    /// assert!(storage.get_tag_tracker(3).is_none());
+    /// ```
+    pub fn new_entity(&mut self, index: usize) {
+        while self.tag_trackers.len() <= index {
+            self.tag_trackers
+                .push(TagFactory::new_tracker(&self.tag_factory));
+        }
+    }
+
+    /// Create a copy of this storage whose [`TagTracker`]s are independent of the trackers of `self`.
+    /// The [`TagFactory`] is shared, because it can't change.
+    pub(crate) fn deep_clone(&self) -> Self {
+        Self {
+            tag_trackers: self
+                .tag_trackers
+                .iter()
+                .map(TagTracker::deep_clone)
+                .collect(),
+            tag_factory: Arc::clone(&self.tag_factory),
+        }
+    }
+
+    /// Take the [`TagTracker`]s of all the entities out, to drop them later.
+    pub(crate) fn take_trackers(&mut self) -> Vec<TagTracker> {
+        std::mem::take(&mut self.tag_trackers)
     }
 
     /// Untag all of the tags of an entity.
-    pub fn untag_all(&mut self, entity: EntityId) {
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::tag_storage::TagStorage;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut storage = TagStorage::new(Arc::new(tagf));
+    /// storage.new_entity(0);
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { storage.get_tag_tracker(0).unwrap().tag::<Selected>() };
+    /// storage.untag_all(0);
+    /// assert!(!unsafe { storage.get_tag_tracker(0).unwrap().is_tagged::<Selected>() });
+    /// ```
+    pub fn untag_all(&mut self, index: usize) {
         // SAFETY: No other `TagTracker`s are being accessed
-        unsafe { self.tag_trackers[entity.id() as usize].untag_all() }
+        unsafe { self.tag_trackers[index].untag_all() }
     }
 
-    /// Get the [`TagTracker`] of an entity.
-    pub fn get_tag_tracker(&self, entity: EntityId) -> TagTracker {
-        self.tag_trackers[entity.id() as usize].clone()
+    /// Get the [`TagTracker`] of an entity, or `None` if no tracker was created at its index (see
+    /// [`Self::new_entity`]).
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::tag_storage::TagStorage;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut storage = TagStorage::new(Arc::new(tagf));
+    /// storage.new_entity(0);
+    /// let mut tracker = storage.get_tag_tracker(0).unwrap();
+    /// // SAFETY: `Selected` is registered, and no other tracker of the entity is accessed.
+    /// unsafe { tracker.tag::<Selected>() };
+    /// // The tracker is shared with the storage.
+    /// assert!(unsafe { storage.get_tag_tracker(0).unwrap().is_tagged::<Selected>() });
+    /// ```
+    pub fn get_tag_tracker(&self, index: usize) -> Option<TagTracker> {
+        self.tag_trackers.get(index).cloned()
+    }
+
+    /// Get the [`TagFactory`] that manages the tags in this storage.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::tag_storage::TagStorage;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// let selected = tagf.register_tag::<Selected>();
+    /// let storage = TagStorage::new(Arc::new(tagf));
+    /// assert_eq!(storage.tag_factory().tag_id::<Selected>(), Some(selected));
+    /// ```
+    pub fn tag_factory(&self) -> &TagFactory {
+        &self.tag_factory
     }
 
     /// Get the [`TagTracker`] of an entity, without checking if the entity exists.
-    pub unsafe fn get_tag_tracker_unchecked(&self, entity: EntityId) -> TagTracker {
-        self.tag_trackers
-            .get_unchecked(entity.id() as usize)
-            .clone()
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use worlds_ecs::prelude::*;
+    /// use worlds_ecs::world::storage::tag_storage::TagStorage;
+    ///
+    /// #[derive(Tag)]
+    /// struct Selected;
+    ///
+    /// let mut tagf = TagFactory::default();
+    /// tagf.register_tag::<Selected>();
+    /// let mut storage = TagStorage::new(Arc::new(tagf));
+    /// storage.new_entity(0);
+    /// // SAFETY: A tracker was created at index `0`.
+    /// let tracker = unsafe { storage.get_tag_tracker_unchecked(0) };
+    /// assert!(tracker.is_tag_registered::<Selected>());
+    /// ```
+    ///
+    /// # Safety
+    /// The caller must ensure that a [`TagTracker`] was created for the entity at this index.
+    pub unsafe fn get_tag_tracker_unchecked(&self, index: usize) -> TagTracker {
+        paranoid::check("TagStorage::get_tag_tracker_unchecked", || {
+            paranoid::index("index", index, self.tag_trackers.len())
+        });
+        self.tag_trackers.get_unchecked(index).clone()
+    }
+}
+
+/// The tags of the entities, as [`ArchQuery::fetch`](crate::query::ArchQuery::fetch) sees them, so filters like
+/// [`Tagged`](crate::query::query_filter::Tagged) can check the tags of the entity they're evaluated for. The
+/// trackers are found by the dense indexes of the entities in their [`EntityFactory`].
+#[derive(Clone, Copy)]
+pub struct EntityTags<'a> {
+    trackers: &'a [TagTracker],
+    tag_factory: &'a TagFactory,
+    entities: &'a EntityFactory,
+}
+
+impl<'a> EntityTags<'a> {
+    /// The same tags, without the trackers, for entities that aren't alive (like tombstones), whose dense indexes
+    /// may be reused by other entities.
+    pub(crate) fn untracked(self) -> Self {
+        EntityTags {
+            trackers: &[],
+            ..self
+        }
+    }
+
+    /// The [`TagFactory`] that manages the tags.
+    pub fn tag_factory(&self) -> &'a TagFactory {
+        self.tag_factory
+    }
+
+    /// Returns `true` if the entity has the tag with this id. The entity must be alive (like the entities in the
+    /// storages), because the tracker of a despawned entity is the tracker of the entity that reuses its slot.
+    ///
+    /// # Safety
+    /// No tracker of the entity may be mutated while it's checked.
+    #[inline]
+    pub(crate) unsafe fn is_tagged_id(&self, entity: EntityId, tag_id: u32) -> bool {
+        self.trackers
+            .get(self.entities.dense_index(entity))
+            .is_some_and(|tracker| tracker.is_tagged_id(tag_id))
     }
 }
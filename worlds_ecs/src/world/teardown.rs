@@ -0,0 +1,387 @@
+use super::{
+    latency::{LatencyCategory, LatencyRecorder},
+    World,
+};
+use crate::{
+    storage::{blob_vec::BlobVec, columns::Columns},
+    tag::TagTracker,
+};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+use std::{sync::Arc, time::Duration};
+
+/// The most components of a column (or tag trackers) that are dropped in one step of a [`WorldTeardown`], before
+/// the budget is checked again.
+pub const TEARDOWN_CHUNK_ROWS: usize = 1024;
+
+/// A [`World`] that is dropped a chunk at a time, so dropping a huge world doesn't stall the thread that drops it:
+/// see [`World::into_teardown`].
+///
+/// The components are dropped a storage at a time, a column at a time, and at most [`TEARDOWN_CHUNK_ROWS`] rows
+/// at a time. The memory of a column is released as soon as it's empty. The tag trackers of the entities are
+/// dropped next, and the rest of the world (like its entity metadata, userdata and resources) is dropped last, in one step.
+/// The columns of a storage are dropped in the order of the columns, unless a drop order was declared between its
+/// components (see [`World::drop_order`]), in which case they are dropped in that order.
+///
+/// The teardown is [`Send`], so instead of running it in budgeted steps, it can be moved to another thread and
+/// run there:
+///
+/// ```
+/// use worlds_ecs::prelude::*;
+///
+/// #[derive(Component)]
+/// struct Name(String);
+///
+/// let mut world = World::default();
+/// for i in 0..10_000 {
+///     world.spawn(Name(i.to_string()));
+/// }
+/// let teardown = world.into_teardown();
+/// std::thread::spawn(move || teardown.run_to_completion()).join().unwrap();
+/// ```
+///
+/// Everything that a world stores is [`Send`] (components and userdata must be `Send + Sync`), so all of it moves
+/// into the teardown. State that is bound to the thread of the world must not: [`World::into_teardown`] is where
+/// it's drained (on the calling thread), before the teardown is handed out.
+pub struct WorldTeardown {
+    /// The columns of each storage that weren't dropped yet. The last storage is dropped first, from its last column
+    /// (see [`in_drop_order`]).
+    storages: Vec<Columns>,
+    /// The tag trackers of the entities, dropped once every storage was dropped.
+    tag_trackers: Vec<TagTracker>,
+    /// The rest of the world, without its components and tag trackers, dropped last.
+    rest: Option<World>,
+    /// The latency histograms of the world, which outlive it.
+    latency: LatencyRecorder,
+    /// How many components and tag trackers are left to drop.
+    remaining: usize,
+}
+
+/// How far a [`WorldTeardown`] got, returned by [`WorldTeardown::run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TeardownProgress {
+    /// How many components and tag trackers were dropped by this call.
+    pub dropped: usize,
+    /// How many components and tag trackers are left to drop.
+    pub remaining: usize,
+}
+
+impl TeardownProgress {
+    /// Returns `true` if the whole world was dropped, and its memory was released.
+    pub fn is_done(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+impl World {
+    /// Start tearing the world down, so it can be dropped a chunk at a time with [`WorldTeardown::run`] (or on
+    /// another thread), instead of all at once.
+    ///
+    /// Components that are shared with a copy of the world (see [`World::clone_cow`]) are left to the copy right
+    /// away, since dropping them doesn't drop any component.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Chunk(Vec<u8>);
+    ///
+    /// let mut world = World::default();
+    /// for _ in 0..100 {
+    ///     world.spawn(Chunk(vec![0; 64]));
+    /// }
+    /// let mut teardown = world.into_teardown();
+    /// // A component and a tag tracker for each entity.
+    /// assert_eq!(teardown.remaining(), 200);
+    /// // A frame at a time, or `teardown.run_to_completion()` on another thread.
+    /// while !teardown.run(Duration::from_micros(500)).is_done() {}
+    /// assert_eq!(teardown.remaining(), 0);
+    /// ```
+    pub fn into_teardown(mut self) -> WorldTeardown {
+        let storages: Vec<Columns> = std::mem::take(&mut self.storages.arch_storages)
+            .into_storages()
+            .into_iter()
+            .filter_map(|storage| {
+                let (columns, drop_order) = storage.into_columns();
+                Some(in_drop_order(
+                    Arc::into_inner(columns)?,
+                    drop_order.as_deref(),
+                ))
+            })
+            .collect();
+        let tag_trackers = self.storages.tag_storage.take_trackers();
+        let remaining = storages.iter().map(component_count).sum::<usize>() + tag_trackers.len();
+        WorldTeardown {
+            storages,
+            tag_trackers,
+            latency: self.components.storage_alloc().latency().clone(),
+            rest: Some(self),
+            remaining,
+        }
+    }
+}
+
+/// Arrange the columns so the teardown (which drops the last column first) drops them in the order of the columns,
+/// or in `drop_order` if there is one (see [`World::drop_order`]). Inline columns are dropped all at once, in the
+/// order of the columns, so they are moved out of line if there is a drop order.
+fn in_drop_order(columns: Columns, drop_order: Option<&[usize]>) -> Columns {
+    let (blob_vecs, drop_order) = match (columns, drop_order) {
+        (Columns::Inline(inline), None) => return Columns::Inline(inline),
+        (Columns::Blobs(mut blob_vecs), None) => {
+            blob_vecs.reverse();
+            return Columns::Blobs(blob_vecs);
+        }
+        (Columns::Inline(inline), Some(drop_order)) => (inline.into_blob_vecs(0), drop_order),
+        (Columns::Blobs(blob_vecs), Some(drop_order)) => (blob_vecs, drop_order),
+    };
+    let mut blob_vecs: Vec<Option<BlobVec>> = blob_vecs.into_iter().map(Some).collect();
+    Columns::Blobs(
+        drop_order
+            .iter()
+            .rev()
+            .map(|column| blob_vecs[*column].take().unwrap())
+            .collect(),
+    )
+}
+
+/// How many components are stored in the columns.
+fn component_count(columns: &Columns) -> usize {
+    match columns {
+        Columns::Inline(inline) => (0..inline.column_count())
+            .map(|column| inline.len(column))
+            .sum(),
+        Columns::Blobs(blob_vecs) => blob_vecs.iter().map(|bvec| bvec.len()).sum(),
+    }
+}
+
+/// When the budget of [`WorldTeardown::run`] runs out. `wasm32-unknown-unknown` has no clock (`Instant::now`
+/// panics there), so there a limited budget runs out after every step.
+enum Deadline {
+    Never,
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    At(Instant),
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    NextStep,
+}
+
+impl Deadline {
+    fn after(budget: Duration) -> Deadline {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        return (Instant::now().checked_add(budget)).map_or(Deadline::Never, Deadline::At);
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        return match budget {
+            Duration::MAX => Deadline::Never,
+            _ => Deadline::NextStep,
+        };
+    }
+
+    fn passed(&self) -> bool {
+        match self {
+            Deadline::Never => false,
+            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+            Deadline::At(deadline) => Instant::now() >= *deadline,
+            #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+            Deadline::NextStep => true,
+        }
+    }
+}
+
+impl WorldTeardown {
+    /// Drop components until `budget` runs out (or the whole world is dropped), and return how far the teardown got.
+    /// Each call makes progress, even with a zero budget: the budget is checked after every step, and a step drops
+    /// at most [`TEARDOWN_CHUNK_ROWS`] components (plus the memory of their column, if it's empty).
+    ///
+    /// Once [`TeardownProgress::is_done`], calling this again does nothing.
+    ///
+    /// On `wasm32-unknown-unknown` there is no clock to measure the budget with, so each call makes a single step
+    /// (unless the budget is [`Duration::MAX`]).
+    pub fn run(&mut self, budget: Duration) -> TeardownProgress {
+        let deadline = Deadline::after(budget);
+        let mut dropped = 0;
+        loop {
+            dropped += self.step();
+            if self.remaining == 0 {
+                // Nothing is left to drop but the rest of the world, which doesn't store components.
+                self.rest = None;
+                break;
+            }
+            if deadline.passed() {
+                break;
+            }
+        }
+        TeardownProgress {
+            dropped,
+            remaining: self.remaining,
+        }
+    }
+
+    /// Drop the whole world, without a budget.
+    pub fn run_to_completion(mut self) {
+        self.run(Duration::MAX);
+    }
+
+    /// How many components and tag trackers are left to drop.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Drop the next chunk, and return how many components (or tag trackers) were dropped.
+    fn step(&mut self) -> usize {
+        let latency = self.latency.start();
+        let dropped = match self.storages.last_mut() {
+            // Inline columns have no more than a handful of rows, so they are dropped in one step.
+            Some(Columns::Inline(_)) => {
+                let columns = self.storages.pop().unwrap();
+                component_count(&columns)
+            }
+            Some(Columns::Blobs(blob_vecs)) => match blob_vecs.last_mut() {
+                Some(bvec) if bvec.drop_fn().is_some() && bvec.len() > TEARDOWN_CHUNK_ROWS => {
+                    bvec.truncate(bvec.len() - TEARDOWN_CHUNK_ROWS);
+                    TEARDOWN_CHUNK_ROWS
+                }
+                // The rest of the column is dropped with it.
+                Some(_) => blob_vecs.pop().unwrap().len(),
+                None => {
+                    self.storages.pop();
+                    0
+                }
+            },
+            None => {
+                let len = self.tag_trackers.len();
+                self.tag_trackers
+                    .truncate(len.saturating_sub(TEARDOWN_CHUNK_ROWS));
+                self.tag_trackers.shrink_to_fit();
+                len - self.tag_trackers.len()
+            }
+        };
+        self.latency.finish(LatencyCategory::TeardownChunk, latency);
+        self.remaining -= dropped;
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WorldTeardown, TEARDOWN_CHUNK_ROWS};
+    use crate::prelude::*;
+    use std::{
+        sync::{
+            atomic::{AtomicU8, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    /// Counts how many times each instance is dropped.
+    #[derive(Component)]
+    struct Counted {
+        id: usize,
+        drops: Arc<[AtomicU8]>,
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.drops[self.id].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[derive(Component, Debug, PartialEq, Clone, Copy)]
+    struct Position(f32, f32);
+    #[derive(Component)]
+    struct Label;
+    #[derive(Tag)]
+    struct Visible;
+
+    fn counted_world(count: usize) -> (World, Arc<[AtomicU8]>) {
+        let drops: Arc<[AtomicU8]> = (0..count).map(|_| AtomicU8::new(0)).collect();
+        let mut tagf = TagFactory::default();
+        tagf.register_tag::<Visible>();
+        let mut world = World::with_tags(tagf);
+        for id in 0..count {
+            let counted = Counted {
+                id,
+                drops: drops.clone(),
+            };
+            // Spread the entities over a few storages, some of them tiny.
+            match id % 3 {
+                0 => world.spawn(counted),
+                1 => world.spawn((counted, Position(id as f32, 0.0))),
+                _ if id < 5 => world.spawn((counted, Label)),
+                _ => world.spawn((counted, Position(0.0, 1.0), Label)),
+            };
+        }
+        (world, drops)
+    }
+
+    #[test]
+    fn test_every_component_is_dropped_once() {
+        let count = 10 * TEARDOWN_CHUNK_ROWS + 7;
+        let (world, drops) = counted_world(count);
+        let mut teardown = world.into_teardown();
+        let total = teardown.remaining();
+        let mut calls = 0;
+        let mut dropped = 0;
+        loop {
+            let progress = teardown.run(Duration::ZERO);
+            calls += 1;
+            dropped += progress.dropped;
+            assert_eq!(progress.remaining, total - dropped);
+            if progress.is_done() {
+                break;
+            }
+            // Nothing is dropped twice along the way.
+            assert!(drops.iter().all(|drops| drops.load(Ordering::Relaxed) <= 1));
+        }
+        assert!(calls > 10);
+        assert_eq!(dropped, total);
+        assert!(drops.iter().all(|drops| drops.load(Ordering::Relaxed) == 1));
+        // Running it again does nothing.
+        assert_eq!(teardown.run(Duration::ZERO).dropped, 0);
+        // Only `drops` itself is left.
+        assert_eq!(Arc::strong_count(&drops), 1);
+    }
+
+    #[test]
+    fn test_teardown_interleaved_with_a_new_world() {
+        let (world, drops) = counted_world(4 * TEARDOWN_CHUNK_ROWS);
+        let mut teardown = world.into_teardown();
+        let mut next = World::default();
+        let mut frame = 0;
+        while !teardown.run(Duration::from_micros(50)).is_done() {
+            next.spawn(Position(frame as f32, 0.0));
+            next.query::<&mut Position>()
+                .for_each(|position| position.1 += 1.0);
+            frame += 1;
+        }
+        let sum: f32 = next.query::<&Position>().map(|position| position.1).sum();
+        assert_eq!(sum, (1..=frame).sum::<usize>() as f32);
+        assert!(drops.iter().all(|drops| drops.load(Ordering::Relaxed) == 1));
+    }
+
+    #[test]
+    fn test_teardown_leaves_shared_components_to_the_copy() {
+        let mut world = World::default();
+        world.register_cloneable_component::<Position>();
+        let entities: Vec<EntityId> = (0..100)
+            .map(|i| world.spawn(Position(i as f32, 0.0)))
+            .collect();
+        let copy = world.clone_cow().unwrap();
+        let teardown = world.into_teardown();
+        // Only the tag trackers are left to drop.
+        assert_eq!(teardown.remaining(), 100);
+        std::thread::spawn(move || teardown.run_to_completion())
+            .join()
+            .unwrap();
+        assert_eq!(
+            copy.get_component::<Position>(entities[99]),
+            Some(&Position(99.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_teardown_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<WorldTeardown>();
+    }
+}
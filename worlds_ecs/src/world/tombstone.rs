@@ -0,0 +1,529 @@
+use super::{
+    destroy::DespawnReason,
+    storage::{
+        arch_storage::ArchStorageIndex,
+        storages::{ArchStorageId, ArchStorages},
+        ArchEntityStorage,
+    },
+    World,
+};
+use crate::{
+    archetype::Archetype,
+    bundle::Bundle,
+    component::{deref_component, Component, ComponentFactory, ComponentId},
+    entity::{EntityId, EntityMeta},
+    prelude::{ArchQuery, QueryIter},
+    utils::{component_mask::ComponentMask, panics},
+};
+use bevy_ptr::OwningPtr;
+use std::{
+    any::Any,
+    collections::HashMap,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe, Location},
+};
+
+/// The tombstones of the world: entities that were despawned with [`World::despawn_retaining`], whose retained
+/// components stay readable for a few frames.
+#[derive(Default)]
+pub(crate) struct Tombstones {
+    /// The storages of the retained components, one for each archetype that was retained. They aren't storages of
+    /// the world, so the queries of the world never see them.
+    storages: ArchStorages,
+    /// The row of each tombstone in [`Self::storages`], and how many frame boundaries it has left.
+    entries: HashMap<EntityId, Tombstone>,
+}
+
+struct Tombstone {
+    storage: ArchStorageId,
+    index: ArchStorageIndex,
+    frames_left: u32,
+}
+
+impl Tombstones {
+    /// Returns `true` if there are no tombstones.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The amount of tombstones.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The storage of a tombstone, and its row.
+    fn get(&self, entity: EntityId) -> Option<(&ArchEntityStorage, ArchStorageIndex)> {
+        let tombstone = self.entries.get(&entity)?;
+        let storage = self.storages.get_storage(tombstone.storage)?;
+        Some((storage, tombstone.index))
+    }
+
+    /// Remove a tombstone, dropping its retained components. Returns `false` if the entity isn't a tombstone.
+    pub(crate) fn remove(&mut self, entity: EntityId) -> bool {
+        let Some(tombstone) = self.entries.remove(&entity) else {
+            return false;
+        };
+        let storage = self.storages.get_storage_mut(tombstone.storage).unwrap();
+        if let Some(swapped) = storage.swap_remove(tombstone.index) {
+            self.entries.get_mut(&swapped).unwrap().index = tombstone.index;
+        }
+        true
+    }
+
+    /// Count down a frame boundary, and return the tombstones whose time is up, in a stable order.
+    fn end_frame(&mut self) -> Vec<EntityId> {
+        let mut expired: Vec<EntityId> = (self.entries.iter_mut())
+            .filter_map(|(entity, tombstone)| {
+                tombstone.frames_left = tombstone.frames_left.saturating_sub(1);
+                (tombstone.frames_left == 0).then_some(*entity)
+            })
+            .collect();
+        expired.sort_unstable_by_key(|entity| entity.to_bits());
+        expired
+    }
+
+    /// The tombstones, in a stable order.
+    fn entities(&self) -> Vec<EntityId> {
+        let mut entities: Vec<EntityId> = self.entries.keys().copied().collect();
+        entities.sort_unstable_by_key(|entity| entity.to_bits());
+        entities
+    }
+}
+
+/// The components of an entity, as they are swap-removed from its storage: the retained components are moved to
+/// the storage of the tombstone, and the others are dropped (see [`World::despawn_retaining`]).
+struct Retained<'a> {
+    source: &'a mut ArchEntityStorage,
+    index: ArchStorageIndex,
+    keep: ComponentMask,
+    /// The entity that was moved into the index of the removed row (see [`ArchEntityStorage::swap_remove`]).
+    swapped: &'a mut Option<EntityId>,
+    /// The first panic of a component that was dropped, resumed once the entity is a tombstone.
+    panic: &'a mut Option<Box<dyn Any + Send>>,
+}
+
+impl Bundle for Retained<'_> {
+    fn raw_components_scope(
+        self,
+        comp_factory: &ComponentFactory,
+        f: &mut impl FnMut(ComponentId, OwningPtr<'_>),
+    ) {
+        let Retained {
+            source,
+            index,
+            keep,
+            swapped,
+            panic,
+        } = self;
+        // SAFETY: Every component is moved out, either to `f` or to its drop function.
+        *swapped = unsafe {
+            source.swap_remove_and_forget(index, |comp_id, raw_comp| {
+                if keep.contains(comp_id) {
+                    return f(comp_id, raw_comp);
+                }
+                let drop_fn = (comp_factory.get_component_info_from_component_id(comp_id))
+                    .and_then(|info| info.drop_fn());
+                if let Some(drop_fn) = drop_fn {
+                    // A panicking component can't stop the move halfway, so the panic is resumed after it.
+                    // SAFETY: The pointer points to a stored component of the id, which isn't used again.
+                    if let Err(payload) = catch_unwind(AssertUnwindSafe(|| drop_fn(raw_comp))) {
+                        panic.get_or_insert(payload);
+                    }
+                }
+            })
+        };
+    }
+}
+
+impl World {
+    /// Despawn an entity from the gameplay, but keep its components of the archetype `Keep` readable for
+    /// `ttl_frames` frames, for death animations, kill feeds and network reconciliation. The entity becomes a
+    /// tombstone: its `Keep` components are moved to a storage of tombstones, its other components are dropped, and
+    /// it stops matching the queries of the world. The retained components are read with [`World::get_tombstone`]
+    /// and [`World::query_tombstones`].
+    ///
+    /// The [`EntityId`] stays alive (with the same generation) until the tombstone is destroyed, so references to the
+    /// entity that are held elsewhere still resolve it. The tombstone is destroyed at the `ttl_frames`-th call to
+    /// [`World::end_tombstone_frame`] (at least one), or earlier by [`World::despawn`], like any other entity (see
+    /// [`destroy`](super::destroy)). Its tags and userdata are kept until then.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32, f32);
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let goblin = world.spawn((Position(1.0, 2.0), Health(0)));
+    /// world.despawn_retaining::<Position>(goblin, 2);
+    /// assert_eq!(world.query::<&Position>().count(), 0);
+    /// assert_eq!(world.get_tombstone::<Position>(goblin).unwrap().0, 1.0);
+    /// world.end_tombstone_frame();
+    /// assert!(world.is_tombstone(goblin));
+    /// world.end_tombstone_frame();
+    /// assert!(!world.is_alive(goblin));
+    /// ```
+    ///
+    /// # Panics
+    /// If the entity isn't alive, is archived (see [`World::archive`]) or is already a tombstone, if it doesn't have
+    /// all of the components of `Keep`, or if its storage is pinned (see [`World::pin_storage`]). If one of the
+    /// dropped components panics, the panic is resumed after the entity became a tombstone.
+    #[track_caller]
+    pub fn despawn_retaining<Keep: Archetype>(&mut self, entity: EntityId, ttl_frames: u32) {
+        const OPERATION: &str = "despawn_retaining";
+        let Some(&entity_meta) = self.entities.get_entity_meta(entity) else {
+            panics::fail_entity(OPERATION, "the entity isn't alive", entity);
+        };
+        if entity_meta.is_archived() || entity_meta.is_tombstone() {
+            panics::fail_entity(
+                OPERATION,
+                "the entity is archived, or already a tombstone",
+                entity,
+            );
+        }
+        let sid = entity_meta.archetype_storage_id;
+        if let Err(error) = self.check_pin_for_remove(sid) {
+            panics::fail_entity(OPERATION, error, entity);
+        }
+        let Some(arch_info) = Keep::arch_info(&self.components).filter(|arch_info| {
+            (arch_info.component_ids().iter())
+                .all(|comp_id| entity_meta.component_mask.contains(*comp_id))
+        }) else {
+            panics::fail_entity(
+                OPERATION,
+                "the entity doesn't have all of the components to retain",
+                entity,
+            );
+        };
+        if arch_info.check_for_duplicates() {
+            panics::fail_duplicate_components::<Keep>(OPERATION);
+        }
+        let keep = ComponentMask::from_component_ids(arch_info.component_ids().iter().copied());
+        let (tombstone_sid, target) = (self.tombstones.storages)
+            .get_mut_or_create_storage_with_info(&arch_info, &self.components)
+            .expect("The components of a stored entity are registered");
+        let source = self.storages.arch_storages.get_storage_mut(sid).unwrap();
+        // A sort-maintained storage keeps its order, so the row is moved to the end and removed from there.
+        let (sorted, mut index) = (
+            source.is_sort_maintained(),
+            entity_meta.archetype_storage_index,
+        );
+        if sorted {
+            let last = ArchStorageIndex(source.len() - 1);
+            source.move_row(index, last);
+            index = last;
+        }
+        let (mut swapped, mut panic) = (None, None);
+        let bundle = Retained {
+            source,
+            index,
+            keep,
+            swapped: &mut swapped,
+            panic: &mut panic,
+        };
+        // SAFETY: The bundle stores the `Keep` components of the entity, which are the components of the target
+        // storage.
+        let tombstone_index =
+            unsafe { target.store_entity_unchecked(entity, bundle, &self.components) };
+        let source = self.storages.arch_storages.get_storage(sid).unwrap();
+        if sorted {
+            let first = entity_meta.archetype_storage_index.0;
+            for (row, entity_to_update) in source.entities().iter().enumerate().skip(first) {
+                self.entities
+                    .set_entity_arch_storage_index(ArchStorageIndex(row), *entity_to_update);
+            }
+        } else if let Some(entity_to_update) = swapped {
+            self.entities
+                .set_entity_arch_storage_index(index, entity_to_update);
+        }
+        self.entities.set_entity_meta(EntityMeta::TOMBSTONE, entity);
+        self.ordered.removed(entity);
+        self.tombstones.entries.insert(
+            entity,
+            Tombstone {
+                storage: tombstone_sid,
+                index: tombstone_index,
+                frames_left: ttl_frames.max(1),
+            },
+        );
+        if let Some(payload) = panic {
+            resume_unwind(payload);
+        }
+    }
+
+    /// Mark the end of a frame for the tombstones (see [`World::despawn_retaining`]): every tombstone has one frame
+    /// less to live, and the tombstones whose time is up are destroyed. Returns how many were destroyed.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32, f32);
+    ///
+    /// let mut world = World::default();
+    /// let goblin = world.spawn(Position(1.0, 2.0));
+    /// world.despawn_retaining::<Position>(goblin, 1);
+    /// assert_eq!(world.end_tombstone_frame(), 1);
+    /// assert!(!world.is_alive(goblin));
+    /// ```
+    #[track_caller]
+    pub fn end_tombstone_frame(&mut self) -> usize {
+        let expired = self.tombstones.end_frame();
+        for entity in &expired {
+            self.destroy_entity(*entity, DespawnReason::EndTombstoneFrame);
+        }
+        expired.len()
+    }
+
+    /// Returns `true` if the entity is a tombstone (see [`World::despawn_retaining`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32, f32);
+    ///
+    /// let mut world = World::default();
+    /// let goblin = world.spawn(Position(1.0, 2.0));
+    /// assert!(!world.is_tombstone(goblin));
+    /// world.despawn_retaining::<Position>(goblin, 1);
+    /// assert!(world.is_tombstone(goblin));
+    /// ```
+    pub fn is_tombstone(&self, entity: EntityId) -> bool {
+        self.entities
+            .get_entity_meta(entity)
+            .is_some_and(EntityMeta::is_tombstone)
+    }
+
+    /// Returns how many entities are tombstones (see [`World::despawn_retaining`]).
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32, f32);
+    ///
+    /// let mut world = World::default();
+    /// for _ in 0..3 {
+    ///     let goblin = world.spawn(Position(1.0, 2.0));
+    ///     world.despawn_retaining::<Position>(goblin, 1);
+    /// }
+    /// assert_eq!(world.tombstone_count(), 3);
+    /// world.end_tombstone_frame();
+    /// assert_eq!(world.tombstone_count(), 0);
+    /// ```
+    pub fn tombstone_count(&self) -> usize {
+        self.tombstones.len()
+    }
+
+    /// The retained component `C` of a tombstone (see [`World::despawn_retaining`]), or `None` if the entity isn't a
+    /// tombstone, or `C` wasn't retained.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32, f32);
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::default();
+    /// let goblin = world.spawn((Position(1.0, 2.0), Health(0)));
+    /// world.despawn_retaining::<Position>(goblin, 1);
+    /// assert_eq!(world.get_tombstone::<Position>(goblin).unwrap().1, 2.0);
+    /// assert!(world.get_tombstone::<Health>(goblin).is_none());
+    /// ```
+    pub fn get_tombstone<C: Component>(&self, entity: EntityId) -> Option<&C> {
+        let (storage, index) = self.tombstones.get(entity)?;
+        let comp_id = self.components.get_component_id::<C>()?;
+        let raw_comp = storage.get_component(index, comp_id)?;
+        // SAFETY: This type-erased pointer was fetched using this component id.
+        Some(unsafe { deref_component::<C>(raw_comp) })
+    }
+
+    /// Query the retained components of the tombstones (see [`World::despawn_retaining`]), like [`World::query`]
+    /// queries the components of the entities that are alive. The queries of the world never match tombstones, and
+    /// this query only matches tombstones.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32, f32);
+    ///
+    /// let mut world = World::default();
+    /// let goblin = world.spawn(Position(1.0, 2.0));
+    /// world.spawn(Position(3.0, 4.0));
+    /// world.despawn_retaining::<Position>(goblin, 1);
+    /// let positions: Vec<f32> = world.query_tombstones::<&Position>().map(|position| position.0).collect();
+    /// assert_eq!(positions, vec![1.0]);
+    /// ```
+    ///
+    /// # Panics
+    /// Like [`World::query`].
+    #[track_caller]
+    pub fn query_tombstones<Q: ArchQuery>(&mut self) -> QueryIter<'_, Q> {
+        self.access
+            .record_query::<Q>(&self.components, Location::caller());
+        // Tombstones aren't alive, so they don't have tags, and the trackers at their dense indexes aren't theirs.
+        let tags = self
+            .storages
+            .tag_storage
+            .entity_tags(&self.entities)
+            .untracked();
+        let arch_storages = &mut self.tombstones.storages;
+        // SAFETY: The query is safe to use, because the pointer to the storages came from a &mut.
+        unsafe {
+            if Q::is_resolvable(&self.components) {
+                Q::iter_query_matches(arch_storages, &self.components, tags)
+            } else {
+                QueryIter::empty(arch_storages, &self.components, tags, false)
+            }
+        }
+    }
+
+    /// Destroy every tombstone (see [`World::clear`]), and return how many were destroyed.
+    #[track_caller]
+    pub(crate) fn clear_tombstones(&mut self, reason: DespawnReason) -> usize {
+        let tombstones = self.tombstones.entities();
+        for entity in &tombstones {
+            self.destroy_entity(*entity, reason);
+        }
+        tombstones.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Position(i32, i32);
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    struct Team(u8);
+    #[derive(Component)]
+    struct Health(#[allow(dead_code)] u32);
+    #[derive(Component)]
+    #[component(boxed)]
+    struct Inventory(#[allow(dead_code)] Vec<u32>);
+
+    fn world() -> (World, Vec<EntityId>) {
+        let mut world = World::default();
+        let entities = (0..10)
+            .map(|i| {
+                world.spawn((
+                    Position(i, -i),
+                    Team(i as u8 % 2),
+                    Health(100),
+                    Inventory(vec![i as u32]),
+                ))
+            })
+            .collect();
+        (world, entities)
+    }
+
+    #[test]
+    fn test_tombstones_are_only_seen_by_tombstone_queries() {
+        let (mut world, entities) = world();
+        world.despawn_retaining::<(Position, Team)>(entities[3], 5);
+        world.despawn_retaining::<Position>(entities[7], 5);
+        world.assert_invariants();
+
+        assert_eq!(world.query::<&Position>().count(), 8);
+        assert_eq!(world.query::<EntityId>().count(), 8);
+        assert_eq!(world.get_component::<Position>(entities[3]), None);
+        assert!(!world.contains_component::<Health>(entities[3]));
+
+        let mut positions: Vec<(EntityId, Position)> = world
+            .query_tombstones::<(EntityId, &Position)>()
+            .map(|(entity, position)| (entity, *position))
+            .collect();
+        positions.sort_by_key(|(_, position)| position.0);
+        assert_eq!(
+            positions,
+            [
+                (entities[3], Position(3, -3)),
+                (entities[7], Position(7, -7))
+            ]
+        );
+        assert_eq!(world.query_tombstones::<&Team>().count(), 1);
+        assert_eq!(world.query_tombstones::<&Health>().count(), 0);
+        assert_eq!(world.get_tombstone::<Team>(entities[3]), Some(&Team(1)));
+        assert_eq!(world.get_tombstone::<Team>(entities[7]), None);
+        assert_eq!(world.get_tombstone::<Position>(entities[0]), None);
+        // The other entities moved, and are still where their metas say.
+        assert!((entities.iter())
+            .filter(|entity| !world.is_tombstone(**entity))
+            .all(|entity| world.get_component::<Position>(*entity).is_some()));
+    }
+
+    #[test]
+    fn test_tombstones_expire() {
+        let (mut world, entities) = world();
+        world.despawn_retaining::<Position>(entities[0], 1);
+        world.despawn_retaining::<Position>(entities[1], 3);
+        assert_eq!(world.tombstone_count(), 2);
+
+        assert_eq!(world.end_tombstone_frame(), 1);
+        assert!(!world.is_alive(entities[0]));
+        assert!(world.is_tombstone(entities[1]));
+        assert_eq!(world.end_tombstone_frame(), 0);
+        assert!(world.get_tombstone::<Position>(entities[1]).is_some());
+        assert_eq!(world.end_tombstone_frame(), 1);
+        assert!(!world.is_alive(entities[1]));
+        assert_eq!(world.tombstone_count(), 0);
+        assert_eq!(world.query_tombstones::<&Position>().count(), 0);
+        world.assert_invariants();
+    }
+
+    #[test]
+    fn test_early_finalization() {
+        let (mut world, entities) = world();
+        world.despawn_retaining::<Position>(entities[2], 10);
+        world.despawn_retaining::<Position>(entities[4], 10);
+        world.despawn(entities[2]);
+        assert!(!world.is_alive(entities[2]));
+        // The other tombstone was moved into the removed row, and can still be read.
+        assert_eq!(
+            world.get_tombstone::<Position>(entities[4]),
+            Some(&Position(4, -4))
+        );
+        assert_eq!(world.query_tombstones::<&Position>().count(), 1);
+
+        assert_eq!(world.clear(), 9);
+        assert_eq!(world.tombstone_count(), 0);
+        assert_eq!(world.entities(), 0);
+    }
+
+    #[test]
+    fn test_ids_of_tombstones() {
+        let (mut world, entities) = world();
+        let entity = entities[5];
+        world.despawn_retaining::<Team>(entity, 2);
+        // The id stays alive with the same generation, and isn't reused while the entity is a tombstone.
+        assert!(world.is_alive(entity));
+        assert_eq!(world.entities(), 10);
+        let spawned = world.spawn((Position(0, 0), Team(0)));
+        assert_ne!(spawned.id(), entity.id());
+
+        world.end_tombstone_frame();
+        world.end_tombstone_frame();
+        assert!(!world.is_alive(entity));
+        let reused = world.spawn((Position(0, 0), Team(0)));
+        assert_eq!(reused.id(), entity.id());
+        assert_eq!(reused.generation(), entity.generation() + 1);
+        assert!(!world.is_tombstone(reused));
+        assert_eq!(world.get_tombstone::<Team>(reused), None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "worlds_ecs: despawn_retaining failed: the entity doesn't have all of the components to retain"
+    )]
+    fn test_retain_missing_component() {
+        let mut world = World::default();
+        let entity = world.spawn(Position(0, 0));
+        world.despawn_retaining::<(Position, Team)>(entity, 1);
+    }
+}
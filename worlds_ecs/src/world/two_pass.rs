@@ -0,0 +1,217 @@
+use super::World;
+use crate::query::{ArchQuery, ReadOnlyArchQuery};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ops::Deref,
+};
+
+/// The data that the first pass of [`World::query_two_pass`] gathers for the second one. It derefs to a slice of
+/// the gathered items, in the order they were pushed.
+pub struct ScratchBuffer<T> {
+    items: Vec<T>,
+}
+
+impl<T> ScratchBuffer<T> {
+    /// Add an item to the buffer.
+    #[inline]
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+    }
+}
+
+impl<T> Deref for ScratchBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+/// The [`ScratchBuffer`]s of the [`World`], one for each type of item, kept between calls of
+/// [`World::query_two_pass`] so their allocations are reused.
+#[derive(Default)]
+pub(crate) struct ScratchBuffers {
+    buffers: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl ScratchBuffers {
+    /// Take the (empty) buffer of `T` out, or create one.
+    fn take<T: Send + Sync + 'static>(&mut self) -> ScratchBuffer<T> {
+        let items = self
+            .buffers
+            .remove(&TypeId::of::<T>())
+            .and_then(|items| items.downcast::<Vec<T>>().ok())
+            .map_or_else(Vec::new, |items| *items);
+        ScratchBuffer { items }
+    }
+
+    /// Put the buffer of `T` back, emptied, to be reused.
+    fn put<T: Send + Sync + 'static>(&mut self, mut buffer: ScratchBuffer<T>) {
+        buffer.items.clear();
+        self.buffers
+            .insert(TypeId::of::<T>(), Box::new(buffer.items));
+    }
+}
+
+impl World {
+    /// Run a read-only query `R`, then a query `W`, one after the other. `gather` is called with every item of `R`
+    /// and a [`ScratchBuffer`] to collect what the second pass needs, then `apply` is called with every item of `W`
+    /// and the gathered buffer.
+    ///
+    /// This is the way to write components based on the components of other entities, when a single query would
+    /// access the same component more than once (like comparing the `Position` of each entity to the `Position` of
+    /// every other entity, to steer it). The passes don't overlap, so `W` can access the same components as `R`,
+    /// mutably. The buffer of each type of item is kept in the world and reused, so running this every frame
+    /// doesn't allocate once the buffer is large enough.
+    ///
+    /// ```
+    /// use worlds_ecs::prelude::*;
+    ///
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    ///
+    /// let mut world = World::default();
+    /// for x in [0.0, 4.0, 8.0] {
+    ///     world.spawn(Position(x));
+    /// }
+    /// // Move every entity halfway to the average position.
+    /// world.query_two_pass::<&Position, &mut Position, f32>(
+    ///     |position, scratch| scratch.push(position.0),
+    ///     |position, scratch| {
+    ///         let average = scratch.iter().sum::<f32>() / scratch.len() as f32;
+    ///         position.0 += (average - position.0) / 2.0;
+    ///     },
+    /// );
+    /// let mut positions: Vec<f32> = world.query::<&Position>().map(|position| position.0).collect();
+    /// positions.sort_by(f32::total_cmp);
+    /// assert_eq!(positions, [2.0, 4.0, 6.0]);
+    /// ```
+    ///
+    /// # Panics
+    /// Like [`World::query`], for each of the queries.
+    #[track_caller]
+    pub fn query_two_pass<R: ReadOnlyArchQuery, W: ArchQuery, T: Send + Sync + 'static>(
+        &mut self,
+        mut gather: impl FnMut(R::Item<'_>, &mut ScratchBuffer<T>),
+        mut apply: impl FnMut(W::Item<'_>, &ScratchBuffer<T>),
+    ) {
+        let mut scratch = self.scratch.take::<T>();
+        for item in self.query::<R>() {
+            gather(item, &mut scratch);
+        }
+        for item in self.query::<W>() {
+            apply(item, &scratch);
+        }
+        self.scratch.put(scratch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::any::TypeId;
+
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Position(i64, i64);
+    #[derive(Component, Clone, Copy, Debug, PartialEq)]
+    struct Velocity(i64, i64);
+
+    fn flock() -> World {
+        let mut world = World::default();
+        let mut seed: i64 = 0x2545F491;
+        for _ in 0..50 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let (x, y) = ((seed >> 20) % 1000, (seed >> 40) % 1000);
+            world.spawn((Position(x, y), Velocity(0, 0)));
+        }
+        world
+    }
+
+    /// The steering of the entity `this` at `position` toward the centroid of the other entities.
+    fn steer<'a>(
+        this: EntityId,
+        position: Position,
+        others: impl Iterator<Item = &'a (EntityId, Position)>,
+    ) -> Velocity {
+        let (mut x, mut y, mut count) = (0, 0, 0);
+        for (_, other) in others.filter(|(other, _)| *other != this) {
+            (x, y, count) = (x + other.0, y + other.1, count + 1);
+        }
+        Velocity((x / count - position.0) / 8, (y / count - position.1) / 8)
+    }
+
+    #[test]
+    fn test_boids_match_the_naive_reference() {
+        let mut world = flock();
+        let before: Vec<(EntityId, Position)> = world
+            .query::<(EntityId, &Position)>()
+            .map(|(entity, position)| (entity, *position))
+            .collect();
+        // The naive reference compares each entity to every other one.
+        let mut expected: Vec<(EntityId, Velocity)> = before
+            .iter()
+            .map(|(entity, position)| (*entity, steer(*entity, *position, before.iter())))
+            .collect();
+
+        world.query_two_pass::<(EntityId, &Position), (EntityId, &Position, &mut Velocity), _>(
+            |(entity, position), scratch| scratch.push((entity, *position)),
+            |(entity, position, velocity), scratch| {
+                *velocity = steer(entity, *position, scratch.iter());
+            },
+        );
+        let mut steered: Vec<(EntityId, Velocity)> = world
+            .query::<(EntityId, &Velocity)>()
+            .map(|(entity, velocity)| (entity, *velocity))
+            .collect();
+        steered.sort_by_key(|(entity, _)| entity.id());
+        expected.sort_by_key(|(entity, _)| entity.id());
+        assert_eq!(steered, expected);
+        assert!(steered
+            .iter()
+            .any(|(_, velocity)| *velocity != Velocity(0, 0)));
+    }
+
+    #[test]
+    fn test_writing_the_gathered_component() {
+        let mut world = flock();
+        // Snap every entity to the leftmost one, which a single query over `&mut Position` and `&Position` of the
+        // other entities can't do.
+        let leftmost = world.query::<&Position>().map(|position| position.0).min();
+        world.query_two_pass::<&Position, &mut Position, i64>(
+            |position, scratch| scratch.push(position.0),
+            |position, scratch| position.0 = *scratch.iter().min().unwrap(),
+        );
+        assert!(world
+            .query::<&Position>()
+            .all(|position| Some(position.0) == leftmost));
+    }
+
+    #[test]
+    fn test_scratch_buffers_are_reused() {
+        let mut world = flock();
+        let mut lens = Vec::new();
+        for _ in 0..2 {
+            world.query_two_pass::<&Position, &Velocity, Position>(
+                |position, scratch| scratch.push(*position),
+                |_, scratch| lens.push(scratch.len()),
+            );
+        }
+        // Each call starts with an empty buffer.
+        assert!(lens.iter().all(|len| *len == 50));
+        let buffer = world.scratch.buffers[&TypeId::of::<Position>()]
+            .downcast_ref::<Vec<Position>>()
+            .unwrap();
+        assert!(buffer.is_empty());
+        assert!(buffer.capacity() >= 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "with World::query_two_pass)")]
+    fn test_each_query_is_still_checked() {
+        let mut world = flock();
+        world.query_two_pass::<&Position, (&mut Velocity, &Velocity), ()>(|_, _| {}, |_, _| {});
+    }
+}
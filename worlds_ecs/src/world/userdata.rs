@@ -0,0 +1,227 @@
+use crate::entity::EntityId;
+use std::{any::Any, collections::HashMap};
+
+/// A type-erased value that is attached to an entity, see [`World::set_userdata`](super::World::set_userdata).
+pub type Userdata = Box<dyn Any + Send + Sync>;
+
+/// Called with each value of a [`UserdataKey`] that is removed because its entity was despawned.
+type CleanupFn = Box<dyn Fn(EntityId, Userdata) + Send + Sync>;
+
+/// Identifies a userdata slot of an entity. Each system that attaches userdata (like a scripting runtime)
+/// should use its own key, so multiple systems can attach userdata to the same entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UserdataKey {
+    /// A key identified by a name.
+    Named(&'static str),
+    /// A key identified by a number.
+    Namespace(u64),
+}
+
+/// The userdata of every entity, for each [`UserdataKey`].
+#[derive(Default)]
+pub(crate) struct UserdataStorage {
+    /// The userdata of each key. Entities are identified by their full [`EntityId`] (including the generation),
+    /// so an entity that reuses the id of a despawned entity never sees its userdata.
+    slots: HashMap<UserdataKey, HashMap<EntityId, Userdata>>,
+    cleanups: HashMap<UserdataKey, CleanupFn>,
+}
+
+impl UserdataStorage {
+    /// Returns `true` if no entity has userdata, and no cleanups are set.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.slots.values().all(HashMap::is_empty) && self.cleanups.is_empty()
+    }
+
+    pub(crate) fn set(
+        &mut self,
+        entity: EntityId,
+        key: UserdataKey,
+        value: Userdata,
+    ) -> Option<Userdata> {
+        self.slots.entry(key).or_default().insert(entity, value)
+    }
+
+    pub(crate) fn get(
+        &self,
+        entity: EntityId,
+        key: UserdataKey,
+    ) -> Option<&(dyn Any + Send + Sync)> {
+        self.slots.get(&key)?.get(&entity).map(Box::as_ref)
+    }
+
+    pub(crate) fn get_mut(
+        &mut self,
+        entity: EntityId,
+        key: UserdataKey,
+    ) -> Option<&mut (dyn Any + Send + Sync)> {
+        self.slots.get_mut(&key)?.get_mut(&entity).map(Box::as_mut)
+    }
+
+    pub(crate) fn remove(&mut self, entity: EntityId, key: UserdataKey) -> Option<Userdata> {
+        self.slots.get_mut(&key)?.remove(&entity)
+    }
+
+    pub(crate) fn take_all(&mut self, entity: EntityId) -> Vec<(UserdataKey, Userdata)> {
+        self.slots
+            .iter_mut()
+            .filter_map(|(key, slot)| slot.remove(&entity).map(|value| (*key, value)))
+            .collect()
+    }
+
+    pub(crate) fn iter(
+        &self,
+        key: UserdataKey,
+    ) -> impl Iterator<Item = (EntityId, &(dyn Any + Send + Sync))> + '_ {
+        self.slots
+            .get(&key)
+            .into_iter()
+            .flat_map(|slot| slot.iter().map(|(entity, value)| (*entity, value.as_ref())))
+    }
+
+    pub(crate) fn set_cleanup(&mut self, key: UserdataKey, cleanup: CleanupFn) {
+        self.cleanups.insert(key, cleanup);
+    }
+
+    /// Remove the userdata of despawned entities, passing each value to the cleanup of its key (if there is one).
+    /// The userdata of all of the entities is removed before any cleanup is called, so a panicking cleanup
+    /// doesn't leave userdata behind.
+    pub(crate) fn despawned(&mut self, entities: &[EntityId]) {
+        if self.slots.is_empty() {
+            return;
+        }
+        let removed: Vec<(EntityId, UserdataKey, Userdata)> = entities
+            .iter()
+            .flat_map(|entity| {
+                self.take_all(*entity)
+                    .into_iter()
+                    .map(|(key, value)| (*entity, key, value))
+            })
+            .collect();
+        for (entity, key, value) in removed {
+            if let Some(cleanup) = self.cleanups.get(&key) {
+                cleanup(entity, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use std::sync::{Arc, Mutex};
+
+    const LUA: UserdataKey = UserdataKey::Named("lua");
+    const WASM: UserdataKey = UserdataKey::Namespace(7);
+
+    #[derive(Component)]
+    struct A(#[allow(dead_code)] usize);
+
+    #[test]
+    fn test_userdata_slots() {
+        let mut world = World::default();
+        let a = world.spawn(A(0));
+        let b = world.spawn(A(1));
+
+        assert!(world.set_userdata(a, LUA, Box::new(10u32)).is_none());
+        world.set_userdata(a, WASM, Box::new("wasm object"));
+        world.set_userdata(b, LUA, Box::new(20u32));
+
+        assert_eq!(
+            world.get_userdata(a, LUA).unwrap().downcast_ref::<u32>(),
+            Some(&10)
+        );
+        assert_eq!(
+            world.get_userdata(a, WASM).unwrap().downcast_ref::<&str>(),
+            Some(&"wasm object")
+        );
+        assert!(world.get_userdata(b, WASM).is_none());
+
+        *world
+            .get_userdata_mut(b, LUA)
+            .unwrap()
+            .downcast_mut::<u32>()
+            .unwrap() += 1;
+        let mut lua: Vec<(EntityId, u32)> = world
+            .iter_userdata(LUA)
+            .map(|(entity, value)| (entity, *value.downcast_ref::<u32>().unwrap()))
+            .collect();
+        let mut expected = vec![(a, 10), (b, 21)];
+        lua.sort_by_key(|(entity, _)| entity.id());
+        expected.sort_by_key(|(entity, _)| entity.id());
+        assert_eq!(lua, expected);
+
+        let previous = world.set_userdata(a, LUA, Box::new(11u32)).unwrap();
+        assert_eq!(previous.downcast_ref::<u32>(), Some(&10));
+        assert_eq!(
+            world.remove_userdata(b, LUA).unwrap().downcast_ref::<u32>(),
+            Some(&21)
+        );
+        assert!(world.get_userdata(b, LUA).is_none());
+
+        let mut taken = world.take_all_userdata(a);
+        taken.sort_by_key(|(key, _)| *key == WASM);
+        assert_eq!(taken.len(), 2);
+        assert_eq!(taken[0].0, LUA);
+        assert_eq!(taken[1].0, WASM);
+        assert!(world.get_userdata(a, LUA).is_none());
+        assert_eq!(world.iter_userdata(WASM).count(), 0);
+    }
+
+    #[test]
+    fn test_userdata_id_reuse() {
+        let mut world = World::default();
+        let old = world.spawn(A(0));
+        world.set_userdata(old, LUA, Box::new(1u8));
+        world.despawn(old);
+        assert!(world.get_userdata(old, LUA).is_none());
+
+        let new = world.spawn(A(1));
+        assert_eq!(new.id(), old.id());
+        assert!(world.get_userdata(new, LUA).is_none());
+        assert_eq!(world.iter_userdata(LUA).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "worlds_ecs: set_userdata failed: the entity isn't alive (entity=")]
+    fn test_userdata_of_despawned_entity() {
+        let mut world = World::default();
+        let entity = world.spawn(A(0));
+        world.despawn(entity);
+        world.set_userdata(entity, LUA, Box::new(()));
+    }
+
+    #[test]
+    fn test_userdata_cleanup() {
+        let released = Arc::new(Mutex::new(Vec::new()));
+        let mut world = World::default();
+        let lua_released = released.clone();
+        world.set_userdata_cleanup(LUA, move |entity, value| {
+            lua_released
+                .lock()
+                .unwrap()
+                .push((entity, *value.downcast::<u32>().unwrap()));
+        });
+        let entities: Vec<EntityId> = (0..4).map(|i| world.spawn(A(i))).collect();
+        for (i, entity) in entities.iter().enumerate() {
+            world.set_userdata(*entity, LUA, Box::new(i as u32));
+            world.set_userdata(*entity, WASM, Box::new(i));
+        }
+
+        world.despawn(entities[1]);
+        assert_eq!(*released.lock().unwrap(), vec![(entities[1], 1)]);
+        assert_eq!(world.iter_userdata(WASM).count(), 3);
+
+        // Removing userdata doesn't call the cleanup.
+        world.remove_userdata(entities[0], LUA);
+        assert_eq!(world.clear(), 3);
+        let mut released = released.lock().unwrap().clone();
+        released.sort_by_key(|(entity, _)| entity.id());
+        assert_eq!(
+            released,
+            vec![(entities[1], 1), (entities[2], 2), (entities[3], 3)]
+        );
+        assert_eq!(world.iter_userdata(LUA).count(), 0);
+        assert_eq!(world.iter_userdata(WASM).count(), 0);
+        assert_eq!(world.iter_entities().count(), 0);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::prelude::ComponentId;
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt,
+    mem::{discriminant, Discriminant},
+};
+
+/// The maximum amount of [`EcsWarning`]s that are kept before the oldest ones are discarded.
+pub const MAX_QUEUED_WARNINGS: usize = 256;
+
+/// After how many spawns of the empty bundle `()` an [`EcsWarning::EmptyBundleSpawned`] is emitted.
+pub const EMPTY_BUNDLE_SPAWN_THRESHOLD: usize = 64;
+
+/// The default amount of storages that can be created while a [`QueryState`](crate::prelude::QueryState) isn't
+/// iterated, before it emits an [`EcsWarning::StaleQueryState`] when it finally is (see
+/// [`World::set_stale_query_state_threshold`](crate::prelude::World::set_stale_query_state_threshold)).
+pub const STALE_QUERY_STATE_THRESHOLD: usize = 1024;
+
+/// The default size (in bytes) above which a component that isn't boxed emits an [`EcsWarning::LargeComponent`]
+/// (see [`World::set_large_component_threshold`](crate::prelude::World::set_large_component_threshold)).
+pub const LARGE_COMPONENT_THRESHOLD: usize = 4096;
+
+/// Which [`EcsWarning`]s the [`World`](crate::prelude::World) emits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WarnLevel {
+    /// No warnings are emitted. This is the default.
+    #[default]
+    Off,
+    /// All warnings are emitted.
+    All,
+}
+
+/// Usage of the [`World`](crate::prelude::World) that is legal, but is almost always a bug.
+/// Each warning is emitted at most once for the same subject.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EcsWarning {
+    /// The empty bundle `()` was spawned many times.
+    EmptyBundleSpawned {
+        /// How many times it was spawned when this warning was emitted.
+        count: usize,
+    },
+    /// Two different components were registered with the same name.
+    DuplicateComponentName {
+        /// The name of the components.
+        name: &'static str,
+        /// The component that was registered first.
+        first: ComponentId,
+        /// The component that was registered second.
+        second: ComponentId,
+    },
+    /// A component that is larger than the threshold (see
+    /// [`World::set_large_component_threshold`](crate::prelude::World::set_large_component_threshold)) is stored
+    /// inline, so every row that is moved in its storage (like the last row, when an entity is despawned) copies
+    /// all of its bytes.
+    LargeComponent {
+        /// The name of the component.
+        name: &'static str,
+        /// The size of the component, in bytes.
+        size: usize,
+    },
+    /// A query was made for components that were never registered, so it didn't match anything.
+    UnregisteredComponentQueried {
+        /// The name of the query, which names its components.
+        query: &'static str,
+    },
+    /// A [`QueryState`](crate::prelude::QueryState) was iterated after more storages than the threshold (see
+    /// [`World::set_stale_query_state_threshold`](crate::prelude::World::set_stale_query_state_threshold)) were
+    /// created since it last was, so it was held without being used while the world changed a lot.
+    StaleQueryState {
+        /// The name of the query, which names its components.
+        query: &'static str,
+        /// How many storages were created since the state was last iterated.
+        storages_created: usize,
+    },
+}
+
+impl EcsWarning {
+    /// What this warning is about, used to emit each warning only once.
+    fn subject(&self) -> &'static str {
+        match self {
+            EcsWarning::EmptyBundleSpawned { .. } => "",
+            EcsWarning::DuplicateComponentName { name, .. } => name,
+            EcsWarning::LargeComponent { name, .. } => name,
+            EcsWarning::UnregisteredComponentQueried { query } => query,
+            EcsWarning::StaleQueryState { query, .. } => query,
+        }
+    }
+}
+
+impl fmt::Display for EcsWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EcsWarning::EmptyBundleSpawned { count } => {
+                write!(f, "the empty bundle `()` was spawned {count} times")
+            }
+            EcsWarning::DuplicateComponentName {
+                name,
+                first,
+                second,
+            } => write!(
+                f,
+                "the components {} and {} are both named `{name}`",
+                first.id(),
+                second.id()
+            ),
+            EcsWarning::LargeComponent { name, size } => write!(
+                f,
+                "the component `{name}` is {size} bytes, consider storing it behind a box with `#[component(boxed)]`"
+            ),
+            EcsWarning::UnregisteredComponentQueried { query } => write!(
+                f,
+                "the query `{query}` didn't match anything, because some of its components were never registered"
+            ),
+            EcsWarning::StaleQueryState {
+                query,
+                storages_created,
+            } => write!(
+                f,
+                "the query state `{query}` was iterated after {storages_created} storages were created since it \
+                 last was"
+            ),
+        }
+    }
+}
+
+/// Collects the [`EcsWarning`]s of a [`World`](crate::prelude::World).
+#[derive(Clone)]
+pub struct WarningsChannel {
+    enabled: bool,
+    warnings: VecDeque<EcsWarning>,
+    emitted: HashSet<(Discriminant<EcsWarning>, &'static str)>,
+    pub(crate) empty_bundle_spawns: usize,
+    pub(crate) checked_components: usize,
+    pub(crate) large_component_threshold: usize,
+    pub(crate) stale_query_state_threshold: usize,
+}
+
+impl Default for WarningsChannel {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            warnings: VecDeque::new(),
+            emitted: HashSet::new(),
+            empty_bundle_spawns: 0,
+            checked_components: 0,
+            large_component_threshold: LARGE_COMPONENT_THRESHOLD,
+            stale_query_state_threshold: STALE_QUERY_STATE_THRESHOLD,
+        }
+    }
+}
+
+impl WarningsChannel {
+    /// Set which warnings are emitted.
+    pub fn set_level(&mut self, level: WarnLevel) {
+        self.enabled = level != WarnLevel::Off;
+    }
+
+    /// Returns `true` if warnings are emitted.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Emit a warning, unless it was already emitted for the same subject.
+    pub fn emit(&mut self, warning: EcsWarning) {
+        if !self
+            .emitted
+            .insert((discriminant(&warning), warning.subject()))
+        {
+            return;
+        }
+        if self.warnings.len() == MAX_QUEUED_WARNINGS {
+            self.warnings.pop_front();
+        }
+        self.warnings.push_back(warning);
+    }
+
+    /// Take all of the warnings that were emitted since the last time they were taken.
+    pub fn take(&mut self) -> Vec<EcsWarning> {
+        self.warnings.drain(..).collect()
+    }
+}
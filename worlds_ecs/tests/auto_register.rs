@@ -0,0 +1,101 @@
+//! The components of this test are registered automatically, by the derive:
+//! `cargo test -p worlds_ecs --features auto-register --test auto_register`.
+#![cfg(feature = "auto-register")]
+
+use worlds_ecs::{component::ComponentId, prelude::*, test_utils::assert_entity_has};
+
+#[derive(Component, Debug, PartialEq)]
+struct Zebra(u32);
+#[derive(Component, Debug, PartialEq)]
+struct Apple(u32);
+#[derive(Component, Debug, PartialEq)]
+struct Mango;
+#[derive(Component, Clone)]
+#[component(cloneable)]
+struct Cached(u32);
+#[derive(Component, Default)]
+#[component(default)]
+struct Fallback;
+#[derive(Component)]
+#[component(no_auto_register)]
+struct Manual;
+#[derive(Component)]
+struct Wrapper<T: Send + Sync + 'static>(T);
+
+#[test]
+fn test_never_registered_component_is_queryable() {
+    let mut world = World::default();
+    // Nothing was spawned or registered yet, but the query resolves.
+    assert_eq!(world.query::<&Zebra>().count(), 0);
+    let entity = world.spawn((Zebra(1), Apple(2)));
+    let matches: Vec<(&Zebra, &Apple)> = world.query::<(&Zebra, &Apple)>().collect();
+    assert_eq!(matches, [(&Zebra(1), &Apple(2))]);
+    assert_entity_has(&world, entity, Zebra(1));
+}
+
+#[test]
+fn test_ids_follow_type_names() {
+    let world = World::default();
+    let names: Vec<&str> = (0..world.components().component_count())
+        .map(|id| {
+            world
+                .components()
+                .get_component_info_from_component_id(ComponentId::new(id))
+                .unwrap()
+                .name()
+        })
+        .collect();
+    let mut sorted = names.clone();
+    sorted.sort_unstable();
+    assert_eq!(names, sorted);
+    assert_eq!(names.len(), 5);
+    let id = |name: &str| names.iter().position(|other| other.ends_with(name));
+    assert!(id("::Apple") < id("::Mango"));
+    assert!(id("::Mango") < id("::Zebra"));
+
+    // Every world gets the same ids.
+    let other = World::default();
+    assert_eq!(
+        other.components().get_component_id::<Zebra>(),
+        world.components().get_component_id::<Zebra>()
+    );
+}
+
+#[test]
+fn test_opt_out_and_generics() {
+    let mut world = World::default();
+    assert_eq!(world.components().get_component_id::<Manual>(), None);
+    assert_eq!(world.components().get_component_id::<Wrapper<u8>>(), None);
+    let count = world.components().component_count();
+    world.spawn((Manual, Wrapper(0u8)));
+    assert_eq!(world.components().component_count(), count + 2);
+    // Registering again doesn't register anything twice.
+    assert_eq!(ComponentFactory::default().register_auto_registered(), 0);
+}
+
+#[test]
+fn test_cloneable_and_default() {
+    let mut world = World::default();
+    let info = |world: &World, id| {
+        world
+            .components()
+            .get_component_info_from_component_id(id)
+            .unwrap()
+            .clone()
+    };
+    let cached = world.components().get_component_id::<Cached>().unwrap();
+    let fallback = world.components().get_component_id::<Fallback>().unwrap();
+    assert!(info(&world, cached).clone_fn().is_some());
+    assert!(info(&world, fallback).default_fn().is_some());
+    assert!(info(&world, fallback).clone_fn().is_none());
+
+    world.spawn(Cached(3));
+    let mut snapshot = world.clone_cow().unwrap();
+    assert_eq!(
+        snapshot
+            .query::<&Cached>()
+            .map(|cached| cached.0)
+            .sum::<u32>(),
+        3
+    );
+}
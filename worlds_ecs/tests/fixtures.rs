@@ -0,0 +1,60 @@
+//! Tests of the world's bookkeeping, using the fixtures of `worlds_ecs::test_utils`.
+
+use worlds_ecs::prelude::*;
+use worlds_ecs::test_utils::*;
+
+#[test]
+fn test_populated_queries() {
+    let mut fixture = WorldFixture::populated(&[(&[0], 10), (&[0, 1], 5), (&[1, 2], 3)]);
+    let world = fixture.world_mut();
+    assert_query_count::<&Fx<0>>(world, 15);
+    assert_query_count::<&Fx<1>>(world, 8);
+    assert_query_count::<(&Fx<0>, &Fx<1>)>(world, 5);
+    assert_query_count::<(&Fx<0>, &Fx<2>)>(world, 0);
+}
+
+/// The value of the fixtures of an entity spawned by a [`ChurnScript`]: every archetype of the default script has
+/// `Fx<0>` or `Fx<1>`, and all of the entity's fixtures have the same value.
+fn value_of(world: &World, entity: EntityId) -> u64 {
+    world
+        .get_component::<Fx<0>>(entity)
+        .map(|fx| fx.0)
+        .or(world.get_component::<Fx<1>>(entity).map(|fx| fx.0))
+        .unwrap()
+}
+
+#[test]
+fn test_churned_components_survive() {
+    let mut world = World::default();
+    let mut expected = std::collections::HashMap::new();
+    let alive =
+        ChurnScript::new(0xdead_beef)
+            .steps(3000)
+            .run_with(&mut world, |world, op| match op {
+                ChurnOp::Spawn(entity) => {
+                    expected.insert(entity, value_of(world, entity));
+                }
+                ChurnOp::Mutate(entity) => *expected.get_mut(&entity).unwrap() += 1,
+                ChurnOp::Despawn(entity) => {
+                    expected.remove(&entity);
+                }
+            });
+    assert_eq!(alive.len(), expected.len());
+    for entity in alive {
+        let value = expected[&entity];
+        if world.get_component::<Fx<0>>(entity).is_some() {
+            assert_entity_has(&world, entity, Fx::<0>(value));
+        } else {
+            assert_entity_has(&world, entity, Fx::<1>(value));
+        }
+    }
+}
+
+#[test]
+fn test_churn_with_custom_archetypes() {
+    let mut world = World::default();
+    let script = ChurnScript::new(42).archetypes(&[&[3], &[3, 4], &[4, 5, 6]]);
+    let alive = script.run(&mut world);
+    assert_query_count::<EntityId>(&mut world, alive.len());
+    assert_query_count::<Option<&Fx<3>>>(&mut world, alive.len());
+}
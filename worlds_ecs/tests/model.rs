@@ -0,0 +1,608 @@
+//! A property test of the whole public surface of the [`World`]: random sequences of operations are applied to both
+//! a world and a simple model of it, and everything that can be observed is compared after every operation.
+//!
+//! A failure is shrunk to a short sequence, printed as a list of [`Op`]s that can be pasted into
+//! [`test_model_regressions`]. The short configuration always runs, the long one is ignored by default:
+//! `cargo test -p worlds_ecs --test model -- --ignored`.
+#![cfg(not(target_arch = "wasm32"))]
+
+use proptest::prelude::*;
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+    fmt,
+};
+use worlds_ecs::prelude::*;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+struct A(u32);
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+struct B(u32);
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+struct C(u32);
+#[derive(Tag)]
+struct Red;
+#[derive(Tag)]
+struct Blue;
+
+/// The components that an entity is spawned with. The value is given to all of them.
+#[derive(Debug, Clone, Copy)]
+enum Shape {
+    A,
+    C,
+    AB,
+    BC,
+    Abc,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comp {
+    A,
+    B,
+    C,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Mark {
+    Red,
+    Blue,
+}
+
+/// A filter of a query (or of [`World::despawn_matching`]).
+#[derive(Debug, Clone, Copy)]
+enum Filter {
+    All,
+    HasA,
+    NotB,
+    HasAOrC,
+    MatchesBC,
+}
+
+/// An operation on the world. Entities are picked by their index (modulo the amount of candidates): [`Op::Despawn`]
+/// and the tag operations pick one of the entities that aren't dead, the others pick any entity that was ever spawned.
+#[derive(Clone, Copy)]
+enum Op {
+    Spawn(Shape, u32),
+    Despawn(usize),
+    Set(usize, Comp, u32),
+    Remove(usize, Comp),
+    Tag(usize, Mark),
+    Untag(usize, Mark),
+    Query(Filter),
+    DespawnMatching(Filter),
+    Archive(usize),
+    Restore(usize),
+    Clear,
+}
+
+/// Written as the expression that constructs the operation, so failures can be pasted into a test.
+impl fmt::Debug for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Spawn(shape, value) => write!(f, "Op::Spawn(Shape::{shape:?}, {value})"),
+            Op::Despawn(i) => write!(f, "Op::Despawn({i})"),
+            Op::Set(i, comp, value) => write!(f, "Op::Set({i}, Comp::{comp:?}, {value})"),
+            Op::Remove(i, comp) => write!(f, "Op::Remove({i}, Comp::{comp:?})"),
+            Op::Tag(i, mark) => write!(f, "Op::Tag({i}, Mark::{mark:?})"),
+            Op::Untag(i, mark) => write!(f, "Op::Untag({i}, Mark::{mark:?})"),
+            Op::Query(filter) => write!(f, "Op::Query(Filter::{filter:?})"),
+            Op::DespawnMatching(filter) => write!(f, "Op::DespawnMatching(Filter::{filter:?})"),
+            Op::Archive(i) => write!(f, "Op::Archive({i})"),
+            Op::Restore(i) => write!(f, "Op::Restore({i})"),
+            Op::Clear => write!(f, "Op::Clear"),
+        }
+    }
+}
+
+fn shape() -> impl Strategy<Value = Shape> {
+    prop_oneof![
+        Just(Shape::A),
+        Just(Shape::C),
+        Just(Shape::AB),
+        Just(Shape::BC),
+        Just(Shape::Abc)
+    ]
+}
+
+fn comp() -> impl Strategy<Value = Comp> {
+    prop_oneof![Just(Comp::A), Just(Comp::B), Just(Comp::C)]
+}
+
+fn mark() -> impl Strategy<Value = Mark> {
+    prop_oneof![Just(Mark::Red), Just(Mark::Blue)]
+}
+
+fn filter() -> impl Strategy<Value = Filter> {
+    prop_oneof![
+        Just(Filter::All),
+        Just(Filter::HasA),
+        Just(Filter::NotB),
+        Just(Filter::HasAOrC),
+        Just(Filter::MatchesBC)
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let pick = 0..64usize;
+    prop_oneof![
+        8 => (shape(), 0..1000u32).prop_map(|(shape, value)| Op::Spawn(shape, value)),
+        4 => pick.clone().prop_map(Op::Despawn),
+        4 => (pick.clone(), comp(), 0..1000u32).prop_map(|(i, comp, value)| Op::Set(i, comp, value)),
+        2 => (pick.clone(), comp()).prop_map(|(i, comp)| Op::Remove(i, comp)),
+        2 => (pick.clone(), mark()).prop_map(|(i, mark)| Op::Tag(i, mark)),
+        2 => (pick.clone(), mark()).prop_map(|(i, mark)| Op::Untag(i, mark)),
+        2 => filter().prop_map(Op::Query),
+        1 => filter().prop_map(Op::DespawnMatching),
+        1 => pick.clone().prop_map(Op::Archive),
+        1 => pick.prop_map(Op::Restore),
+        1 => Just(Op::Clear),
+    ]
+}
+
+/// An entity of the [`Model`] that isn't dead.
+#[derive(Default)]
+struct ModelEntity {
+    components: HashMap<TypeId, Box<dyn Any>>,
+    tags: HashSet<TypeId>,
+    archived: bool,
+}
+
+impl ModelEntity {
+    fn get<T: 'static>(&self) -> Option<&T> {
+        self.components
+            .get(&TypeId::of::<T>())
+            .and_then(|component| component.downcast_ref())
+    }
+
+    fn has<T: 'static>(&self) -> bool {
+        self.components.contains_key(&TypeId::of::<T>())
+    }
+
+    fn matches(&self, filter: Filter) -> bool {
+        !self.archived
+            && match filter {
+                Filter::All => true,
+                Filter::HasA => self.has::<A>(),
+                Filter::NotB => !self.has::<B>(),
+                Filter::HasAOrC => self.has::<A>() || self.has::<C>(),
+                Filter::MatchesBC => self.has::<B>() && self.has::<C>(),
+            }
+    }
+
+    /// What a query for `(EntityId, Option<&A>, Option<&B>, Option<&C>)` yields for the entity.
+    fn row(&self, entity: EntityId) -> Row {
+        (
+            entity,
+            self.get::<A>().copied(),
+            self.get::<B>().copied(),
+            self.get::<C>().copied(),
+        )
+    }
+}
+
+type Row = (EntityId, Option<A>, Option<B>, Option<C>);
+
+/// The oracle: what the world should look like.
+#[derive(Default)]
+struct Model {
+    /// The entities that aren't dead.
+    entities: HashMap<EntityId, ModelEntity>,
+    /// Every entity that was ever spawned, in order, so entities can be picked deterministically.
+    spawned: Vec<EntityId>,
+}
+
+impl Model {
+    /// Pick one of the entities that were ever spawned.
+    fn pick_any(&self, i: usize) -> Option<EntityId> {
+        (!self.spawned.is_empty()).then(|| self.spawned[i % self.spawned.len()])
+    }
+
+    /// Pick one of the entities that aren't dead.
+    fn pick_live(&self, i: usize) -> Option<EntityId> {
+        let live: Vec<EntityId> = self
+            .spawned
+            .iter()
+            .copied()
+            .filter(|entity| self.entities.contains_key(entity))
+            .collect();
+        (!live.is_empty()).then(|| live[i % live.len()])
+    }
+
+    fn state(&self, entity: EntityId) -> EntityState {
+        match self.entities.get(&entity) {
+            Some(model) if model.archived => EntityState::Archived,
+            Some(_) => EntityState::Alive,
+            None => EntityState::Dead,
+        }
+    }
+
+    /// The entities that match the filter, sorted.
+    fn matching(&self, filter: Filter) -> Vec<EntityId> {
+        let mut entities: Vec<EntityId> = self
+            .entities
+            .iter()
+            .filter(|(_, model)| model.matches(filter))
+            .map(|(entity, _)| *entity)
+            .collect();
+        entities.sort_by_key(key);
+        entities
+    }
+}
+
+fn key(entity: &EntityId) -> (u32, u32) {
+    (entity.id(), entity.generation())
+}
+
+fn new_world() -> World {
+    let mut tagf = TagFactory::default();
+    tagf.register_tag::<Red>();
+    tagf.register_tag::<Blue>();
+    let mut world = World::with_tags(tagf);
+    // SAFETY: The components are a single `u32`, so they have no padding.
+    unsafe {
+        world.register_pod_component::<A>();
+        world.register_pod_component::<B>();
+        world.register_pod_component::<C>();
+    }
+    world
+}
+
+fn query_rows(world: &mut World, filter: Filter) -> Vec<Row> {
+    type Q = (
+        EntityId,
+        Option<&'static A>,
+        Option<&'static B>,
+        Option<&'static C>,
+    );
+    fn rows<F: ArchFilter>(world: &mut World) -> Vec<Row> {
+        world
+            .query_filtered::<Q, F>()
+            .map(|(entity, a, b, c)| (entity, a.copied(), b.copied(), c.copied()))
+            .collect()
+    }
+    let mut rows = match filter {
+        Filter::All => rows::<()>(world),
+        Filter::HasA => rows::<Has<A>>(world),
+        Filter::NotB => rows::<Not<Has<B>>>(world),
+        Filter::HasAOrC => rows::<Or<(Has<A>, Has<C>)>>(world),
+        Filter::MatchesBC => rows::<Matches<(B, C)>>(world),
+    };
+    rows.sort_by_key(|row| key(&row.0));
+    rows
+}
+
+fn despawn_matching(world: &mut World, filter: Filter) -> usize {
+    match filter {
+        Filter::All => world.despawn_matching::<()>(),
+        Filter::HasA => world.despawn_matching::<Has<A>>(),
+        Filter::NotB => world.despawn_matching::<Not<Has<B>>>(),
+        Filter::HasAOrC => world.despawn_matching::<Or<(Has<A>, Has<C>)>>(),
+        Filter::MatchesBC => world.despawn_matching::<Matches<(B, C)>>(),
+    }
+}
+
+fn is_tagged(world: &World, entity: EntityId, mark: Mark) -> bool {
+    let tracker = world.get_tag_tracker(entity).unwrap();
+    // SAFETY: Both tags are registered, and no other tracker of the entity is mutated.
+    unsafe {
+        match mark {
+            Mark::Red => tracker.is_tagged::<Red>(),
+            Mark::Blue => tracker.is_tagged::<Blue>(),
+        }
+    }
+}
+
+fn set_tag(world: &World, entity: EntityId, mark: Mark, tagged: bool) {
+    let mut tracker = world.get_tag_tracker(entity).unwrap();
+    // SAFETY: Both tags are registered, and no other tracker of the entity is accessed.
+    unsafe {
+        match (mark, tagged) {
+            (Mark::Red, true) => tracker.tag::<Red>(),
+            (Mark::Red, false) => tracker.untag::<Red>(),
+            (Mark::Blue, true) => tracker.tag::<Blue>(),
+            (Mark::Blue, false) => tracker.untag::<Blue>(),
+        }
+    }
+}
+
+fn tag_type(mark: Mark) -> TypeId {
+    match mark {
+        Mark::Red => TypeId::of::<Red>(),
+        Mark::Blue => TypeId::of::<Blue>(),
+    }
+}
+
+/// Apply an operation to both the world and the model, and compare what it returned.
+fn apply(world: &mut World, model: &mut Model, op: Op) {
+    match op {
+        Op::Spawn(shape, value) => {
+            let entity = match shape {
+                Shape::A => world.spawn(A(value)),
+                Shape::C => world.spawn(C(value)),
+                Shape::AB => world.spawn((A(value), B(value))),
+                Shape::BC => world.spawn((B(value), C(value))),
+                Shape::Abc => world.spawn((A(value), B(value), C(value))),
+            };
+            assert!(
+                !model.entities.contains_key(&entity),
+                "{entity:?} was spawned, but it's already alive"
+            );
+            assert!(
+                !model.spawned.contains(&entity),
+                "{entity:?} was spawned again, with the same generation"
+            );
+            let components: Vec<Box<dyn Any>> = match shape {
+                Shape::A => vec![Box::new(A(value))],
+                Shape::C => vec![Box::new(C(value))],
+                Shape::AB => vec![Box::new(A(value)), Box::new(B(value))],
+                Shape::BC => vec![Box::new(B(value)), Box::new(C(value))],
+                Shape::Abc => vec![Box::new(A(value)), Box::new(B(value)), Box::new(C(value))],
+            };
+            let spawned = ModelEntity {
+                components: components
+                    .into_iter()
+                    .map(|component| ((*component).type_id(), component))
+                    .collect(),
+                ..Default::default()
+            };
+            model.entities.insert(entity, spawned);
+            model.spawned.push(entity);
+        }
+        Op::Despawn(i) => {
+            if let Some(entity) = model.pick_live(i) {
+                world.despawn(entity);
+                model.entities.remove(&entity);
+            }
+        }
+        Op::Set(i, comp, value) => {
+            let Some(entity) = model.pick_any(i) else {
+                return;
+            };
+            let target = model
+                .entities
+                .get_mut(&entity)
+                .filter(|model| !model.archived);
+            fn set<T: Component + Copy>(
+                world: &mut World,
+                target: Option<&mut ModelEntity>,
+                entity: EntityId,
+                value: T,
+            ) {
+                let in_world = world.get_component_mut::<T>(entity);
+                let in_model = target.and_then(|model| {
+                    model
+                        .components
+                        .get_mut(&TypeId::of::<T>())
+                        .map(|component| component.downcast_mut::<T>().unwrap())
+                });
+                assert_eq!(
+                    in_world.is_some(),
+                    in_model.is_some(),
+                    "{entity:?} has a mismatched `{}`",
+                    std::any::type_name::<T>()
+                );
+                if let (Some(in_world), Some(in_model)) = (in_world, in_model) {
+                    *in_world = value;
+                    *in_model = value;
+                }
+            }
+            match comp {
+                Comp::A => set(world, target, entity, A(value)),
+                Comp::B => set(world, target, entity, B(value)),
+                Comp::C => set(world, target, entity, C(value)),
+            }
+        }
+        Op::Remove(i, comp) => {
+            let Some(entity) = model.pick_any(i) else {
+                return;
+            };
+            let target = model
+                .entities
+                .get_mut(&entity)
+                .filter(|model| !model.archived);
+            fn remove<T: Component + Copy + PartialEq + fmt::Debug>(
+                world: &mut World,
+                target: Option<&mut ModelEntity>,
+                entity: EntityId,
+            ) {
+                let in_model = target
+                    .and_then(|model| model.components.remove(&TypeId::of::<T>()))
+                    .map(|component| *component.downcast::<T>().unwrap());
+                assert_eq!(world.remove_component::<T>(entity), in_model, "{entity:?}");
+            }
+            match comp {
+                Comp::A => remove::<A>(world, target, entity),
+                Comp::B => remove::<B>(world, target, entity),
+                Comp::C => remove::<C>(world, target, entity),
+            }
+        }
+        Op::Tag(i, mark) | Op::Untag(i, mark) => {
+            if let Some(entity) = model.pick_live(i) {
+                let tagged = matches!(op, Op::Tag(..));
+                set_tag(world, entity, mark, tagged);
+                let tags = &mut model.entities.get_mut(&entity).unwrap().tags;
+                if tagged {
+                    tags.insert(tag_type(mark));
+                } else {
+                    tags.remove(&tag_type(mark));
+                }
+            }
+        }
+        Op::Query(filter) => {
+            let expected: Vec<Row> = model
+                .matching(filter)
+                .into_iter()
+                .map(|entity| model.entities[&entity].row(entity))
+                .collect();
+            assert_eq!(query_rows(world, filter), expected, "{filter:?}");
+        }
+        Op::DespawnMatching(filter) => {
+            let expected = model.matching(filter);
+            assert_eq!(
+                despawn_matching(world, filter),
+                expected.len(),
+                "{filter:?}"
+            );
+            for entity in expected {
+                model.entities.remove(&entity);
+            }
+        }
+        Op::Archive(i) => {
+            let Some(entity) = model.pick_any(i) else {
+                return;
+            };
+            let result = world.archive(entity);
+            match model.entities.get_mut(&entity) {
+                Some(model) if !model.archived => {
+                    assert_eq!(result, Ok(()));
+                    model.archived = true;
+                }
+                Some(_) => assert_eq!(result, Err(ArchiveError::AlreadyArchived(entity))),
+                None => assert_eq!(result, Err(ArchiveError::Despawned(entity))),
+            }
+        }
+        Op::Restore(i) => {
+            let Some(entity) = model.pick_any(i) else {
+                return;
+            };
+            let result = world.restore(entity);
+            match model.entities.get_mut(&entity) {
+                Some(model) if model.archived => {
+                    assert_eq!(result, Ok(()));
+                    model.archived = false;
+                }
+                Some(_) => assert_eq!(result, Err(ArchiveError::NotArchived(entity))),
+                None => assert_eq!(result, Err(ArchiveError::Despawned(entity))),
+            }
+        }
+        Op::Clear => {
+            assert_eq!(world.clear(), model.entities.len());
+            model.entities.clear();
+        }
+    }
+}
+
+/// Compare everything that can be observed about the world with the model.
+fn compare(world: &World, model: &Model) {
+    world.assert_invariants();
+    for &entity in &model.spawned {
+        assert_eq!(
+            world.entity_state(entity),
+            model.state(entity),
+            "{entity:?}"
+        );
+        let live = model.entities.get(&entity).filter(|model| !model.archived);
+        assert_eq!(
+            world.get_component::<A>(entity),
+            live.and_then(ModelEntity::get::<A>),
+            "{entity:?}"
+        );
+        assert_eq!(
+            world.get_component::<B>(entity),
+            live.and_then(ModelEntity::get::<B>),
+            "{entity:?}"
+        );
+        assert_eq!(
+            world.get_component::<C>(entity),
+            live.and_then(ModelEntity::get::<C>),
+            "{entity:?}"
+        );
+        assert_eq!(
+            world.contains_component::<B>(entity),
+            live.is_some_and(ModelEntity::has::<B>),
+            "{entity:?}"
+        );
+        if let Some(model) = model.entities.get(&entity) {
+            for mark in [Mark::Red, Mark::Blue] {
+                assert_eq!(
+                    is_tagged(world, entity, mark),
+                    model.tags.contains(&tag_type(mark)),
+                    "{entity:?} {mark:?}"
+                );
+            }
+        }
+    }
+    let mut entities: Vec<EntityId> = world.iter_entities().collect();
+    entities.sort_by_key(key);
+    assert_eq!(entities, model.matching(Filter::All));
+    assert_eq!(
+        world.component_count::<A>(),
+        model.matching(Filter::HasA).len()
+    );
+}
+
+/// Run the operations against a new world and a new model, comparing them after every operation.
+fn run(ops: &[Op]) {
+    run_on(new_world(), ops);
+}
+
+/// Like [`run`], with the world under chaos (see [`World::enable_chaos`]).
+fn run_under_chaos(seed: u64, ops: &[Op]) {
+    let mut world = new_world();
+    world.enable_chaos(seed, ChaosConfig::default());
+    run_on(world, ops);
+}
+
+fn run_on(mut world: World, ops: &[Op]) {
+    let mut model = Model::default();
+    for op in ops {
+        apply(&mut world, &mut model, *op);
+        compare(&world, &model);
+    }
+}
+
+fn config(cases: u32) -> ProptestConfig {
+    ProptestConfig {
+        cases,
+        // Failures are reproduced by pasting them into `test_model_regressions`, instead of from files.
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    }
+}
+
+proptest! {
+    #![proptest_config(config(32))]
+
+    #[test]
+    fn test_model(ops in prop::collection::vec(op(), 1..200)) {
+        run(&ops);
+    }
+}
+
+proptest! {
+    #![proptest_config(config(32))]
+
+    #[test]
+    fn test_model_under_chaos(seed: u64, ops in prop::collection::vec(op(), 1..200)) {
+        run_under_chaos(seed, &ops);
+    }
+}
+
+proptest! {
+    #![proptest_config(config(1024))]
+
+    #[test]
+    #[ignore = "long configuration of `test_model`"]
+    fn test_model_long(ops in prop::collection::vec(op(), 1..600)) {
+        run(&ops);
+    }
+}
+
+/// Sequences that the model test failed on once.
+#[test]
+fn test_model_regressions() {
+    // A revived id also allocated a new slot, and new ids were derived from the amount of live entities, so
+    // they aliased the slots of other entities.
+    run(&[
+        Op::Spawn(Shape::A, 0),
+        Op::Despawn(0),
+        Op::Spawn(Shape::A, 0),
+        Op::Spawn(Shape::A, 0),
+    ]);
+    run(&[
+        Op::Spawn(Shape::C, 250),
+        Op::DespawnMatching(Filter::NotB),
+        Op::Spawn(Shape::Abc, 662),
+        Op::Spawn(Shape::Abc, 240),
+    ]);
+}
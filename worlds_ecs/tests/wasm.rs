@@ -0,0 +1,74 @@
+//! A headless test of the world on `wasm32-unknown-unknown`, including the JSON exports for JS debug tooling:
+//! `wasm-pack test --node worlds_ecs -- --test wasm`.
+#![cfg(target_arch = "wasm32")]
+
+use serde_json::Value;
+use wasm_bindgen_test::wasm_bindgen_test;
+use worlds_ecs::{prelude::*, wasm};
+
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+struct Position(i32, i32);
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+struct Velocity(i32, i32);
+#[derive(Tag)]
+struct Selected;
+
+#[wasm_bindgen_test]
+fn test_spawn_query_despawn() {
+    let mut world = World::default();
+    let entities: Vec<EntityId> = (0..100)
+        .map(|i| world.spawn((Position(i, 0), Velocity(1, 1))))
+        .collect();
+    world.spawn(Position(-1, -1));
+    for (position, velocity) in world.query::<(&mut Position, &Velocity)>() {
+        position.0 += velocity.0;
+        position.1 += velocity.1;
+    }
+    assert_eq!(
+        world.get_component::<Position>(entities[9]),
+        Some(&Position(10, 1))
+    );
+    for entity in &entities[..50] {
+        world.despawn(*entity);
+    }
+    assert_eq!(world.query::<&Position>().count(), 51);
+    assert_eq!(world.query::<&Velocity>().count(), 50);
+    world.assert_invariants();
+}
+
+#[wasm_bindgen_test]
+fn test_json_exports() {
+    let mut tagf = TagFactory::default();
+    tagf.register_tag::<Selected>();
+    let mut world = World::with_tags(tagf);
+    let entity = world.spawn((Position(0, 0), Velocity(1, 0)));
+    world.entity_mut(entity).unwrap().tag::<Selected>();
+    let despawned = world.spawn(Position(1, 1));
+    world.despawn(despawned);
+
+    let stats: Value = serde_json::from_str(&wasm::world_stats_json(&world)).unwrap();
+    assert_eq!(stats["entities"], 1);
+    assert_eq!(stats["despawned"], 1);
+    assert_eq!(stats["archetypes"].as_array().unwrap().len(), 2);
+
+    let dump: Value =
+        serde_json::from_str(&wasm::dump_entity_json(&world, entity.to_bits())).unwrap();
+    assert_eq!(dump["state"], "alive");
+    assert_eq!(dump["components"].as_array().unwrap().len(), 2);
+    assert_eq!(dump["tags"].as_array().unwrap().len(), 1);
+    let dump: Value =
+        serde_json::from_str(&wasm::dump_entity_json(&world, despawned.to_bits())).unwrap();
+    assert_eq!(dump["state"], "dead");
+}
+
+#[wasm_bindgen_test]
+fn test_teardown_without_a_clock() {
+    let mut world = World::default();
+    world.spawn_batch((0..5000).map(|i| Position(i, i)));
+    let mut teardown = world.into_teardown();
+    let mut steps = 0;
+    while !teardown.run(std::time::Duration::from_millis(1)).is_done() {
+        steps += 1;
+    }
+    assert!(steps > 1);
+}